@@ -0,0 +1,15 @@
+fn main() {
+    // Only the `grpc` feature needs the generated aggregator client
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/aggregator.proto");
+        // Use the vendored protoc unless the environment provides one
+        if std::env::var_os("PROTOC").is_none() {
+            std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().expect("vendored protoc"));
+        }
+        tonic_build::configure()
+            .build_server(false)
+            .compile_protos(&["proto/aggregator.proto"], &["proto"])
+            .expect("compiling proto/aggregator.proto");
+    }
+}
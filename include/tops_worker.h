@@ -0,0 +1,85 @@
+/*
+ * C API of the tops-worker attempt engine.
+ *
+ * Build:  cargo build --release --features ffi[,cuda|gpu|cpu-fallback]
+ * Link:   target/release/libtops_worker.so (tops_worker.dll on Windows)
+ *
+ * Every function returning int yields TOPS_OK or a TOPS_ERR_* code; the code and a
+ * message for the last failure on the calling thread are available from
+ * tops_last_error() / tops_last_error_message(). Handles are opaque and must be
+ * released with the matching *_free function. Executor and signer handles may be
+ * shared between threads.
+ *
+ * Functions writing strings take (buf, len, required): on success buf holds a
+ * NUL-terminated string; if len is too small TOPS_ERR_BUFFER_TOO_SMALL is returned
+ * and *required (when not NULL) holds the size needed, NUL included.
+ */
+#ifndef TOPS_WORKER_H
+#define TOPS_WORKER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define TOPS_OK                       0
+#define TOPS_ERR_NULL_POINTER         1
+#define TOPS_ERR_INVALID_ARGUMENT     2
+#define TOPS_ERR_BACKEND_UNAVAILABLE  3
+#define TOPS_ERR_EXECUTION            4
+#define TOPS_ERR_SIGNING              5
+#define TOPS_ERR_BUFFER_TOO_SMALL     6
+#define TOPS_ERR_PANIC                7
+
+#define TOPS_BACKEND_AUTO    0  /* CUDA, then OpenCL, then CPU */
+#define TOPS_BACKEND_CPU     1
+#define TOPS_BACKEND_OPENCL  2
+#define TOPS_BACKEND_CUDA    3
+
+typedef struct TopsExecutor TopsExecutor;
+typedef struct TopsSigner TopsSigner;
+
+typedef struct TopsSizes {
+    uint32_t m;
+    uint32_t n;
+    uint32_t k;
+} TopsSizes;
+
+typedef struct TopsAttemptResult {
+    uint8_t work_root[32];
+    uint64_t elapsed_ms;
+} TopsAttemptResult;
+
+/* Errors */
+int tops_last_error(void);
+size_t tops_last_error_message(char *buf, size_t len);  /* returns length incl. NUL */
+const char *tops_version(void);
+
+/* Executor */
+int tops_executor_new(int backend, TopsExecutor **out);
+void tops_executor_free(TopsExecutor *executor);
+int tops_executor_describe(const TopsExecutor *executor, char *buf, size_t len, size_t *required);
+
+/* Inputs are derived from prev_hash (32 bytes) and nonce, and the outputs sampled
+   into the work root from those and the whole output, exactly as in the worker
+   (WORK_ROOT_SAMPLING=committed). */
+int tops_run_attempt(const TopsExecutor *executor, const uint8_t *prev_hash, uint32_t nonce,
+                     const TopsSizes *sizes, TopsAttemptResult *out);
+
+/* Signing */
+int tops_signer_new(const char *sk_hex, TopsSigner **out);
+void tops_signer_free(TopsSigner *signer);
+int tops_signer_pubkey(const TopsSigner *signer, char *buf, size_t len, size_t *required);
+
+/* receipt_json is a work receipt as submitted by the worker (sig_hex may be empty or
+ * omitted); the signature hex covers the encoding of its receipt_version. */
+int tops_sign_receipt(const TopsSigner *signer, const char *receipt_json,
+                      char *buf, size_t len, size_t *required);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TOPS_WORKER_H */
@@ -9,6 +9,7 @@ echo ""
 
 # Set up environment
 export WORKER_SK_HEX=7b706b652278aba9b01dd473e026fd0baf215fd5afbf92d860b03fa661e07dc2
+export NETWORK_ID=peaq-testnet
 export AGGREGATOR_URL=https://httpbin.org/post
 
 echo "Environment set up!"
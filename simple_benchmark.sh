@@ -17,6 +17,7 @@ NC='\033[0m' # No Color
 
 # Environment setup
 export WORKER_SK_HEX=7b706b652278aba9b01dd473e026fd0baf215fd5afbf92d860b03fa661e07dc2
+export NETWORK_ID=peaq-testnet
 export AGGREGATOR_URL=https://httpbin.org/post
 
 # Function to run a single benchmark
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::integrity::{self, StateFile};
+use crate::types::Sizes;
+use crate::log_warn;
+
+/// GEMM algorithm picked by a tuning pass for one device and shape.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedAlgo {
+    /// Opaque algorithm descriptor as returned by the library, hex-encoded.
+    pub algo_hex: String,
+    /// Mean time of the winning candidate during tuning.
+    pub elapsed_us: f64,
+    /// How many heuristic candidates were benchmarked.
+    pub candidates: usize,
+    pub tuned_at: String,
+}
+
+/// On-disk cache of tuned GEMM algorithms keyed by (GPU model, sizes).
+///
+/// Loaded once at startup so a known shape skips the tuning pass; every new
+/// entry rewrites the file through a temporary file and a rename. With
+/// `STATE_INTEGRITY=1` the file is sealed, and one that fails the check is
+/// ignored like an unreadable one.
+pub struct AlgoCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, CachedAlgo>>,
+}
+
+impl AlgoCache {
+    /// Open the cache at `path`; a missing, unreadable or tampered file starts empty.
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read(&path).map(|bytes| integrity::open(StateFile::AlgoCache, &path, &seal_name(&path), bytes)) {
+            Ok(Ok(body)) => serde_json::from_slice(&body).unwrap_or_else(|e| {
+                log_warn!("[algo-cache] ignoring unreadable {}: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Ok(Err(_)) | Err(_) => HashMap::new(),
+        };
+        Self { path, entries: Mutex::new(entries) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, device: &str, sizes: &Sizes) -> Option<CachedAlgo> {
+        self.entries.lock().unwrap().get(&cache_key(device, sizes)).cloned()
+    }
+
+    pub fn insert(&self, device: &str, sizes: &Sizes, algo: CachedAlgo) -> anyhow::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(cache_key(device, sizes), algo);
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        integrity::write_atomic(&self.path, &integrity::seal(&seal_name(&self.path), serde_json::to_vec_pretty(&*entries)?))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn seal_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+// The scale only changes the epilogue's alpha, so the shape alone identifies the problem
+// The `i32` suffix is the GEMM's output type: algorithms tuned for the old int8 output
+// do not apply to the int32 accumulators and are never looked up again
+fn cache_key(device: &str, sizes: &Sizes) -> String {
+    format!("{}|{}x{}x{}|i32", device, sizes.m, sizes.n, sizes.k)
+}
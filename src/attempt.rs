@@ -1,73 +1,495 @@
 use std::time::Instant;
-use crate::types::Sizes;
+use crate::types::{DeviceInfo, Requant, Sizes};
 use crate::prng::DPrng;
+use crate::sparse::{spmm_int8_relu_q, CsrMatrix};
+use crate::memhard::{romix, run_memhard_stage, MemHardParams, MEMHARD_BLOCK_WORDS};
+use crate::workload::{execute_workload, generate_workload_inputs, ProofWorkload, Workload};
+use crate::phases::{self, PhaseTimings};
+use crate::spotcheck::SpotCheckResult;
+use crate::device_memory::DeviceMemory;
+use crate::capabilities::Capabilities;
+use crate::workload::WorkloadKind;
+use crate::work_hash::{HashKind, OutputSampling, SampledRoot, WorkSampling};
+use crate::persistent::{run_range_on_host, NonceRange, RangeRoot};
 
 pub struct AttemptOutput {
     pub work_root: [u8;32],
     pub y1: Vec<i8>,
     pub y2_samples: Vec<i8>,
     pub elapsed_ms: u64,
+    /// `elapsed_ms` broken down by phase.
+    pub phases: PhaseTimings,
+    /// CPU recomputation of a few output elements, when the pipeline runs one.
+    pub spot_check: Option<SpotCheckResult>,
+    /// Sizes the attempt ran at; drawn per attempt when the epoch sets a size distribution.
+    pub sizes: Sizes,
 }
 
 // Trait for execution backends
 pub trait Executor {
-    fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes) -> anyhow::Result<Vec<i8>>;
+    fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>>;
+
+    /// Run a GEMM on one of the executor's independent queues/streams.
+    /// Backends without multiple queues just serialize onto `run_gemm`.
+    fn run_gemm_on(&self, stream: usize, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        let _ = stream;
+        self.run_gemm(a, b, sizes, scale)
+    }
+
+    /// `sizes.batch` GEMMs of one shape: `a` and `b` hold the items' matrices back
+    /// to back and the outputs come back the same way. Backends without a batched
+    /// kernel run the items one after another.
+    fn run_gemm_batched(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        gemm_per_item(a, b, sizes, |a, b, item| self.run_gemm(a, b, item, scale))
+    }
+
+    /// `run_gemm_batched` on a specific queue/stream.
+    fn run_gemm_batched_on(&self, stream: usize, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        gemm_per_item(a, b, sizes, |a, b, item| self.run_gemm_on(stream, a, b, item, scale))
+    }
+
+    /// `run_gemm_batched` whose output stays where it was computed: only the work_root
+    /// samples `sample` picks and their BLAKE3 root come back. Backends without a
+    /// sampling kernel read the output back and sample it on the host.
+    fn run_gemm_sampled(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant, sample: &OutputSampling) -> anyhow::Result<SampledRoot> {
+        Ok(sample.root_of(&self.run_gemm_batched(a, b, sizes, scale)?))
+    }
+
+    /// `run_gemm_sampled` on a specific queue/stream.
+    fn run_gemm_sampled_on(&self, stream: usize, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant, sample: &OutputSampling) -> anyhow::Result<SampledRoot> {
+        Ok(sample.root_of(&self.run_gemm_batched_on(stream, a, b, sizes, scale)?))
+    }
+
+    /// CSR x dense SpMM for the sparse workload. Backends without a sparse kernel use the CPU reference.
+    fn run_spmm(&self, a: &CsrMatrix, b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        phases::record_host_compute();
+        Ok(spmm_int8_relu_q(a, b, sizes.n, scale))
+    }
+
+    /// `run_spmm` on a specific queue/stream.
+    fn run_spmm_on(&self, stream: usize, a: &CsrMatrix, b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        let _ = stream;
+        self.run_spmm(a, b, sizes, scale)
+    }
+
+    /// Memory-hard ROMix stage: final block for `block`. Backends without a kernel use the CPU reference.
+    fn run_memhard(&self, block: &[u32; MEMHARD_BLOCK_WORDS], params: &MemHardParams) -> anyhow::Result<[u32; MEMHARD_BLOCK_WORDS]> {
+        phases::record_host_compute();
+        Ok(romix(block, params))
+    }
+
+    /// `run_memhard` on a specific queue/stream.
+    fn run_memhard_on(&self, stream: usize, block: &[u32; MEMHARD_BLOCK_WORDS], params: &MemHardParams) -> anyhow::Result<[u32; MEMHARD_BLOCK_WORDS]> {
+        let _ = stream;
+        self.run_memhard(block, params)
+    }
+
+    /// Every attempt of `range` end to end (seed, fill, GEMM, sampling, BLAKE3 work
+    /// root), returning only the roots and their samples. Backends with a persistent
+    /// kernel do it in one launch; the rest run the attempts one after another.
+    fn run_nonce_range(&self, range: &NonceRange) -> anyhow::Result<Vec<RangeRoot>> {
+        run_range_on_host(self, range)
+    }
+
+    /// `run_nonce_range` on a specific queue/stream.
+    fn run_nonce_range_on(&self, stream: usize, range: &NonceRange) -> anyhow::Result<Vec<RangeRoot>> {
+        let _ = stream;
+        self.run_nonce_range(range)
+    }
+
+    /// Backend and device identification reported in v2 receipts.
+    fn device_info(&self) -> DeviceInfo {
+        DeviceInfo::default()
+    }
+
+    /// Device memory, for backends that allocate on a device.
+    fn memory_info(&self) -> Option<DeviceMemory> {
+        None
+    }
+
+    /// PCI address of the device (`0000:01:00.0`), where the backend can tell. Energy
+    /// and utilization are read from this device only.
+    fn pci_address(&self) -> Option<String> {
+        None
+    }
+
+    /// Tag appended to the receipt's `kernel_ver` when GEMMs run through a library
+    /// instead of the built-in kernels (e.g. `gemm_path=clblast`).
+    fn kernel_ver_tag(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Workloads, size limits and alignment of the backend. Backends that only
+    /// implement `run_gemm` report the GEMM as their sole native workload.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::gemm_only(self.memory_info())
+    }
 }
 
 // Implement for GPU (only when gpu feature is enabled)
 #[cfg(feature = "gpu")]
 impl Executor for crate::gpu::GpuExec {
-    fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes) -> anyhow::Result<Vec<i8>> {
-        self.run_gemm(a, b, sizes)
+    fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.run_gemm(a, b, sizes, scale)
+    }
+
+    fn run_gemm_on(&self, stream: usize, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.run_gemm_on(stream, a, b, sizes, scale)
+    }
+
+    fn run_gemm_batched(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.run_gemm_batched_on(0, a, b, sizes, scale)
+    }
+
+    fn run_gemm_batched_on(&self, stream: usize, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.run_gemm_batched_on(stream, a, b, sizes, scale)
+    }
+
+    fn run_gemm_sampled(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant, sample: &OutputSampling) -> anyhow::Result<SampledRoot> {
+        self.run_gemm_sampled_on(0, a, b, sizes, scale, sample)
+    }
+
+    fn run_gemm_sampled_on(&self, stream: usize, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant, sample: &OutputSampling) -> anyhow::Result<SampledRoot> {
+        self.run_gemm_sampled_on(stream, a, b, sizes, scale, sample)
+    }
+
+    fn run_spmm(&self, a: &CsrMatrix, b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.run_spmm_on(0, a, b, sizes, scale)
+    }
+
+    fn run_spmm_on(&self, stream: usize, a: &CsrMatrix, b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.run_spmm_on(stream, a, b, sizes, scale)
+    }
+
+    fn run_memhard(&self, block: &[u32; MEMHARD_BLOCK_WORDS], params: &MemHardParams) -> anyhow::Result<[u32; MEMHARD_BLOCK_WORDS]> {
+        self.run_memhard_on(0, block, params)
+    }
+
+    fn run_memhard_on(&self, stream: usize, block: &[u32; MEMHARD_BLOCK_WORDS], params: &MemHardParams) -> anyhow::Result<[u32; MEMHARD_BLOCK_WORDS]> {
+        self.run_memhard_on(stream, block, params)
+    }
+
+    fn run_nonce_range(&self, range: &NonceRange) -> anyhow::Result<Vec<RangeRoot>> {
+        self.run_nonce_range_on(0, range)
+    }
+
+    fn run_nonce_range_on(&self, stream: usize, range: &NonceRange) -> anyhow::Result<Vec<RangeRoot>> {
+        self.run_nonce_range_on(stream, range)
+    }
+
+    fn device_info(&self) -> DeviceInfo {
+        self.device_info()
+    }
+
+    fn memory_info(&self) -> Option<DeviceMemory> {
+        self.memory_info()
+    }
+
+    fn pci_address(&self) -> Option<String> {
+        self.pci_address()
+    }
+
+    fn kernel_ver_tag(&self) -> Option<&'static str> {
+        self.gemm_kernel().kernel_ver_tag()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            workloads: vec![WorkloadKind::Gemm, WorkloadKind::Spmm],
+            native_workloads: vec![WorkloadKind::Gemm, WorkloadKind::Spmm],
+            native_memhard: true,
+            device_sampling: true,
+            int8_dot: false,
+            alignment: self.gemm_kernel().alignment(),
+            max_side: Some(crate::capabilities::OPENCL_MAX_SIDE),
+            memory: self.memory_info(),
+        }
     }
 }
 
-// Implement for CPU
-#[cfg(feature = "cpu-fallback")]
+// Implement for CPU (always available: it is also the reference implementation)
 impl Executor for crate::cpu::CpuExec {
-    fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes) -> anyhow::Result<Vec<i8>> {
-        self.run_gemm(a, b, sizes)
+    fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.run_gemm(a, b, sizes, scale)
+    }
+
+    fn device_info(&self) -> DeviceInfo {
+        self.device_info()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        // The reference implementations are the CPU's own kernels
+        Capabilities {
+            workloads: vec![WorkloadKind::Gemm, WorkloadKind::Spmm],
+            native_workloads: vec![WorkloadKind::Gemm, WorkloadKind::Spmm],
+            native_memhard: true,
+            device_sampling: false,
+            int8_dot: self.kernel() != crate::cpu::CpuKernel::Scalar,
+            alignment: self.kernel().lanes(),
+            max_side: None,
+            memory: None,
+        }
     }
 }
 
 // Implement for CUDA
 #[cfg(feature = "cuda")]
 impl Executor for crate::gpu_cuda::CudaExec {
-    fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes) -> anyhow::Result<Vec<i8>> {
-        self.run_gemm(a, b, sizes)
+    fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.run_gemm(a, b, sizes, scale)
+    }
+
+    fn run_gemm_on(&self, stream: usize, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.run_gemm_on(stream, a, b, sizes, scale)
+    }
+
+    // No sparse kernel, and receipts naming the CUDA device must not come from the CPU
+    fn run_spmm(&self, _a: &CsrMatrix, _b: &[i8], _sizes: &Sizes, _scale: Requant) -> anyhow::Result<Vec<i8>> {
+        anyhow::bail!("the CUDA backend has no SpMM kernel")
+    }
+
+    fn device_info(&self) -> DeviceInfo {
+        self.device_info()
+    }
+
+    fn memory_info(&self) -> Option<DeviceMemory> {
+        self.memory_info()
+    }
+
+    fn pci_address(&self) -> Option<String> {
+        self.pci_address()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            workloads: vec![WorkloadKind::Gemm],
+            int8_dot: true,
+            // cuBLASLt's int8 tensor-core algorithms want sides in multiples of 4
+            alignment: 4,
+            max_side: Some(i32::MAX as usize),
+            ..Capabilities::gemm_only(self.memory_info())
+        }
     }
 }
 
-pub fn run_attempt<E: Executor + ?Sized>(executor: &E, prev_hash_bytes: &[u8;32], nonce: u32, sizes: &Sizes) -> anyhow::Result<AttemptOutput> {
-    let start = Instant::now();
-    
+// Implement for Metal (and the other wgpu backends)
+#[cfg(feature = "metal")]
+impl Executor for crate::gpu_metal::MetalExec {
+    fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.run_gemm(a, b, sizes, scale)
+    }
+
+    fn device_info(&self) -> DeviceInfo {
+        self.device_info()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            // Outputs are written four int8 to a word
+            alignment: 4,
+            max_side: Some(self.max_side()),
+            ..Capabilities::gemm_only(None)
+        }
+    }
+}
+
+/// Generate the deterministic A (m x k) and B (k x n) inputs for (prev_hash, nonce).
+pub fn generate_inputs(prev_hash_bytes: &[u8;32], nonce: u32, sizes: &Sizes) -> (Vec<i8>, Vec<i8>) {
     // Deterministic PRNG seeded by prev_hash + nonce
     let seed = crate::prng::derive_seed(prev_hash_bytes, nonce);
     let mut prng = DPrng::from_seed(seed);
     
     // Generate input matrices deterministically
-    let a: Vec<i8> = (0..sizes.m * sizes.k).map(|_| prng.next_i8()).collect();
-    let b: Vec<i8> = (0..sizes.k * sizes.n).map(|_| prng.next_i8()).collect();
+    generate_batched_inputs(&mut prng, sizes)
+}
+
+pub use tops_verify_core::workload::generate_batched_inputs;
+
+/// Run a batched GEMM item by item through `gemm`, which sees single-item sizes,
+/// and concatenate the outputs.
+pub fn gemm_per_item(
+    a: &[i8],
+    b: &[i8],
+    sizes: &Sizes,
+    mut gemm: impl FnMut(&[i8], &[i8], &Sizes) -> anyhow::Result<Vec<i8>>,
+) -> anyhow::Result<Vec<i8>> {
+    let batch = sizes.batch.max(1);
+    if batch == 1 {
+        return gemm(a, b, sizes);
+    }
+    let item = Sizes { batch: 1, ..sizes.clone() };
+    let (len_a, len_b) = (sizes.m * sizes.k, sizes.k * sizes.n);
+    if a.len() < batch * len_a || b.len() < batch * len_b {
+        anyhow::bail!("batch of {} needs {} + {} input elements, got {} + {}", batch, batch * len_a, batch * len_b, a.len(), b.len());
+    }
+    let mut y = Vec::with_capacity(batch * sizes.m * sizes.n);
+    for i in 0..batch {
+        y.extend(gemm(&a[i * len_a..(i + 1) * len_a], &b[i * len_b..(i + 1) * len_b], &item)?);
+    }
+    Ok(y)
+}
+
+/// Sample the output of attempt (prev_hash, nonce) with `sampling` and hash the
+/// samples into the work root with `hash`.
+pub fn compute_work_root(y1: &[i8], hash: HashKind, sampling: WorkSampling, prev_hash: &[u8;32], nonce: u32) -> ([u8;32], Vec<i8>) {
+    let y2_samples = sampling.sample(y1, prev_hash, nonce);
     
-    // Run GEMM
-    let y1 = executor.run_gemm(&a, &b, sizes)?;
+    // Compute work root (hash of samples)
+    let work_root = hash.digest(&y2_samples);
+    (work_root, y2_samples)
+}
+
+/// Adapter that pins every GEMM of an executor to a single stream.
+pub struct StreamExecutor<'a, E: Executor + ?Sized> {
+    pub executor: &'a E,
+    pub stream: usize,
+}
+
+impl<E: Executor + ?Sized> Executor for StreamExecutor<'_, E> {
+    fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.executor.run_gemm_on(self.stream, a, b, sizes, scale)
+    }
+
+    fn run_gemm_batched(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.executor.run_gemm_batched_on(self.stream, a, b, sizes, scale)
+    }
+
+    fn run_gemm_sampled(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant, sample: &OutputSampling) -> anyhow::Result<SampledRoot> {
+        self.executor.run_gemm_sampled_on(self.stream, a, b, sizes, scale, sample)
+    }
+
+    fn run_spmm(&self, a: &CsrMatrix, b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.executor.run_spmm_on(self.stream, a, b, sizes, scale)
+    }
+
+    fn run_memhard(&self, block: &[u32; MEMHARD_BLOCK_WORDS], params: &MemHardParams) -> anyhow::Result<[u32; MEMHARD_BLOCK_WORDS]> {
+        self.executor.run_memhard_on(self.stream, block, params)
+    }
+
+    fn run_nonce_range(&self, range: &NonceRange) -> anyhow::Result<Vec<RangeRoot>> {
+        self.executor.run_nonce_range_on(self.stream, range)
+    }
+
+    fn device_info(&self) -> DeviceInfo {
+        self.executor.device_info()
+    }
+
+    fn memory_info(&self) -> Option<DeviceMemory> {
+        self.executor.memory_info()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.executor.capabilities()
+    }
+}
+
+pub fn run_attempt<E: Executor + ?Sized>(executor: &E, prev_hash_bytes: &[u8;32], nonce: u32, sizes: &Sizes) -> anyhow::Result<AttemptOutput> {
+    let start = Instant::now();
     
-    // Sample some outputs for work root
-    let num_samples = 1024.min(y1.len());
-    let y2_samples: Vec<i8> = y1.iter().take(num_samples).cloned().collect();
+    let (a, b) = generate_inputs(prev_hash_bytes, nonce, sizes);
+    let fill = start.elapsed();
     
-    // Convert i8 samples to u8 for hashing
-    let samples_u8: Vec<u8> = y2_samples.iter().map(|&x| x as u8).collect();
+    // Run GEMM
+    phases::reset();
+    let y1 = executor.run_gemm_batched(&a, &b, sizes, Requant::IDENTITY)?;
+    let compute = start.elapsed() - fill;
     
-    // Compute work root (hash of samples)
-    let work_root = blake3::hash(&samples_u8).into();
+    let (work_root, y2_samples) = compute_work_root(&y1, HashKind::default(), WorkSampling::Committed, prev_hash_bytes, nonce);
     
-    let elapsed_ms = start.elapsed().as_millis() as u64;
+    let elapsed = start.elapsed();
     
     Ok(AttemptOutput {
         work_root,
         y1,
         y2_samples,
-        elapsed_ms,
+        elapsed_ms: elapsed.as_millis() as u64,
+        phases: PhaseTimings::from_stages(fill, compute, elapsed - fill - compute),
+        spot_check: None,
+        sizes: sizes.clone(),
+    })
+}
+
+/// `run_attempt` for any workload, optionally with the memory-hard stage and an
+/// epoch salt; for `Workload::Gemm` without either the result is identical.
+pub fn run_workload_attempt<E: Executor + ?Sized>(
+    executor: &E,
+    workload: Workload,
+    memhard: Option<&MemHardParams>,
+    prev_hash_bytes: &[u8;32],
+    nonce: u32,
+    salt: Option<&[u8;32]>,
+    sizes: &Sizes,
+) -> anyhow::Result<AttemptOutput> {
+    let start = Instant::now();
+    let mut input = generate_workload_inputs(workload, prev_hash_bytes, nonce, salt, sizes);
+    let fill = start.elapsed();
+    phases::reset();
+    if let Some(params) = memhard {
+        let seed = crate::prng::derive_salted_seed(prev_hash_bytes, nonce, salt);
+        input.perturb(&run_memhard_stage(executor, &seed, params)?);
+    }
+    let y1 = execute_workload(executor, &input, sizes, Requant::from_salt(salt))?;
+    let compute = start.elapsed() - fill;
+    let (work_root, y2_samples) = compute_work_root(&y1, HashKind::default(), WorkSampling::Committed, prev_hash_bytes, nonce);
+    let elapsed = start.elapsed();
+    Ok(AttemptOutput {
+        work_root,
+        y1,
+        y2_samples,
+        elapsed_ms: elapsed.as_millis() as u64,
+        phases: PhaseTimings::from_stages(fill, compute, elapsed - fill - compute),
+        spot_check: None,
+        sizes: sizes.clone(),
+    })
+}
+
+/// What a `run_proof_attempt` runs with besides its inputs: the parts the epoch and
+/// the configuration decide, as the main loop applies them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttemptSettings {
+    pub salt: Option<[u8; 32]>,
+    pub memhard: Option<MemHardParams>,
+    pub scale: Requant,
+    pub hash: HashKind,
+    pub sampling: WorkSampling,
+}
+
+impl Default for AttemptSettings {
+    /// No salt or memory-hard stage, the unsalted scale, BLAKE3 and committed sampling.
+    fn default() -> Self {
+        Self { salt: None, memhard: None, scale: Requant::IDENTITY, hash: HashKind::default(), sampling: WorkSampling::Committed }
+    }
+}
+
+/// `run_workload_attempt` for any `ProofWorkload`, such as one registered with
+/// `WorkerRuntime`; the work root is built with the workload's own `work_root`.
+pub fn run_proof_attempt(
+    executor: &dyn Executor,
+    workload: &dyn ProofWorkload,
+    prev_hash_bytes: &[u8;32],
+    nonce: u32,
+    sizes: &Sizes,
+    settings: &AttemptSettings,
+) -> anyhow::Result<AttemptOutput> {
+    let start = Instant::now();
+    let seed = crate::prng::derive_salted_seed(prev_hash_bytes, nonce, settings.salt.as_ref());
+    let mut input = workload.generate_inputs(seed, sizes);
+    let fill = start.elapsed();
+    phases::reset();
+    if let Some(params) = &settings.memhard {
+        input.perturb(&run_memhard_stage(executor, &seed, params)?);
+    }
+    let y1 = workload.execute(executor, &input, sizes, settings.scale)?;
+    let compute = start.elapsed() - fill;
+    let (work_root, y2_samples) = workload.work_root(&y1, settings.hash, settings.sampling, prev_hash_bytes, nonce);
+    let elapsed = start.elapsed();
+    Ok(AttemptOutput {
+        work_root,
+        y1,
+        y2_samples,
+        elapsed_ms: elapsed.as_millis() as u64,
+        phases: PhaseTimings::from_stages(fill, compute, elapsed - fill - compute),
+        spot_check: None,
+        sizes: sizes.clone(),
     })
 }
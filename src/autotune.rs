@@ -0,0 +1,251 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use crate::attempt::{run_attempt, run_workload_attempt, Executor};
+use crate::device_memory::is_out_of_memory;
+use crate::memhard::MemHardParams;
+use crate::thermal::gpu_utilization_pct;
+use crate::types::Sizes;
+use crate::workload::{ProofWorkload, Workload};
+use crate::log_info;
+
+/// Consecutive attempts slower than the drift threshold before sizes are re-tuned.
+pub const RETUNE_STREAK: u32 = 10;
+// How often GPU utilization is sampled while batch scaling measures an attempt
+const UTILIZATION_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn parse_target_ms() -> u64 {
+    std::env::var("AUTOTUNE_TARGET_MS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(300)
+}
+
+pub fn candidate_sizes() -> Vec<Sizes> {
+    if let Ok(preset) = std::env::var("AUTOTUNE_PRESETS") {
+        // Format: "m1,n1,k1;m2,n2,k2;..."
+        let mut v = Vec::new();
+        for triplet in preset.split(';') {
+            let parts: Vec<_> = triplet.split(',').collect();
+            if parts.len() == 3 {
+                if let (Ok(m), Ok(n), Ok(k)) = (parts[0].parse(), parts[1].parse(), parts[2].parse()) {
+                    v.push(Sizes { m, n, k, batch: 1 });
+                }
+            }
+        }
+        if !v.is_empty() { return v; }
+    }
+    vec![
+        Sizes { m: 512, n: 512, k: 512, batch: 1 },
+        Sizes { m: 768, n: 768, k: 768, batch: 1 },
+        Sizes { m: 1024, n: 1024, k: 1024, batch: 1 },
+        Sizes { m: 1280, n: 1280, k: 1280, batch: 1 },
+        Sizes { m: 1536, n: 1536, k: 1536, batch: 1 },
+    ]
+}
+
+pub fn autotune_sizes<E: Executor + ?Sized>(executor: &E, prev_hash_bytes: &[u8;32]) -> anyhow::Result<Sizes> {
+    let target_ms = parse_target_ms();
+    let mut best_sizes: Option<Sizes> = None;
+    let mut best_score: u64 = u64::MAX;
+    let mut nonce: u32 = 0;
+    for s in candidate_sizes() {
+        // Run one attempt to gauge time
+        let out = run_attempt(executor, prev_hash_bytes, nonce, &s)?;
+        let dt = out.elapsed_ms;
+        let score = dt.abs_diff(target_ms);
+        log_info!("[autotune] m,n,k=({},{},{}) -> {} ms (|diff|={})", s.m, s.n, s.k, dt, score);
+        if score < best_score { best_score = score; best_sizes = Some(s); }
+        // Increase nonce so each run is unique yet deterministic
+        nonce = nonce.wrapping_add(1);
+    }
+    best_sizes.ok_or_else(|| anyhow::anyhow!("autotune produced no candidates"))
+}
+
+/// Measured latency of one candidate size.
+#[derive(Debug, Clone)]
+pub struct TuneResult {
+    pub sizes: Sizes,
+    pub elapsed_ms: u64,
+}
+
+/// Smallest square size (multiple of 64) whose attempt does at least `min_tops_seconds` of work.
+pub fn minimum_square_sizes(workload: &Workload, min_tops_seconds: f64) -> Sizes {
+    let unit = workload.tera_ops(&Sizes { m: 1, n: 1, k: 1, batch: 1 });
+    let side = (min_tops_seconds / unit).cbrt().ceil().max(1.0) as usize;
+    let side = side.div_ceil(64) * 64;
+    Sizes { m: side, n: side, k: side, batch: 1 }
+}
+
+/// Time one attempt per candidate. With a requirement, the smallest square size that
+/// meets it is measured too, so at least one candidate always qualifies.
+pub fn measure_candidates<E: Executor + ?Sized>(
+    executor: &E,
+    workload: Workload,
+    memhard: Option<&MemHardParams>,
+    prev_hash_bytes: &[u8;32],
+    min_tops_seconds: Option<f64>,
+) -> anyhow::Result<Vec<TuneResult>> {
+    let mut candidates = candidate_sizes();
+    if let Some(required) = min_tops_seconds {
+        let minimum = minimum_square_sizes(&workload, required);
+        if !candidates.iter().any(|c| (c.m, c.n, c.k) == (minimum.m, minimum.n, minimum.k)) {
+            candidates.push(minimum);
+        }
+    }
+    let capabilities = executor.capabilities();
+    let mut results = Vec::with_capacity(candidates.len());
+    for (nonce, s) in candidates.into_iter().enumerate() {
+        if capabilities.max_side.is_some_and(|max| s.m.max(s.n).max(s.k) > max) {
+            log_info!("[autotune] m,n,k=({},{},{}) exceeds the backend's largest side, skipped", s.m, s.n, s.k);
+            continue;
+        }
+        // Two copies: the next attempt is prepared while one is on the device
+        if !capabilities.admits(workload, memhard, &s, 2) {
+            log_info!("[autotune] m,n,k=({},{},{}) does not fit in device memory, skipped", s.m, s.n, s.k);
+            continue;
+        }
+        let out = match run_workload_attempt(executor, workload, memhard, prev_hash_bytes, nonce as u32, None, &s) {
+            Ok(out) => out,
+            Err(e) if is_out_of_memory(&e) => {
+                log_info!("[autotune] m,n,k=({},{},{}) does not fit in device memory, skipped", s.m, s.n, s.k);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        log_info!("[autotune] m,n,k=({},{},{}) -> {} ms ({:.6} TOPS-s)", s.m, s.n, s.k, out.elapsed_ms, workload.tera_ops(&s));
+        results.push(TuneResult { sizes: s, elapsed_ms: out.elapsed_ms });
+    }
+    Ok(results)
+}
+
+/// Pick from measured candidates: with a TOPS-seconds requirement the fastest size
+/// that meets it, otherwise the size closest to `target_ms`.
+pub fn select_sizes<'a>(results: &'a [TuneResult], workload: &Workload, min_tops_seconds: Option<f64>, target_ms: u64) -> Option<&'a TuneResult> {
+    match min_tops_seconds {
+        Some(required) => results
+            .iter()
+            .filter(|r| workload.tera_ops(&r.sizes) >= required)
+            .min_by_key(|r| r.elapsed_ms),
+        None => results.iter().min_by_key(|r| r.elapsed_ms.abs_diff(target_ms)),
+    }
+}
+
+/// Batch count picked by `scale_batch`, with the attempt it was measured on.
+#[derive(Debug, Clone)]
+pub struct BatchScale {
+    pub result: TuneResult,
+    /// Peak GPU busy percentage during that attempt, when the host reports one.
+    pub utilization_pct: Option<f64>,
+}
+
+/// Scale the batch count of the tuned GEMM size up from `tuned`, doubling it while
+/// attempts stay within `target_ms`, the GPU stays below `utilization_pct` busy and
+/// the batch fits in device memory, up to `max_batch`. Small sizes leave fast cards
+/// partly idle; batching fills them without making every matrix bigger. The largest
+/// batch measured within the target is kept. Only the GEMM workload batches.
+#[allow(clippy::too_many_arguments)]
+pub fn scale_batch<E: Executor + ?Sized>(
+    executor: &E,
+    workload: Workload,
+    memhard: Option<&MemHardParams>,
+    prev_hash_bytes: &[u8;32],
+    tuned: &TuneResult,
+    target_ms: u64,
+    max_batch: usize,
+    utilization_pct: u32,
+) -> anyhow::Result<BatchScale> {
+    let mut best = BatchScale { result: tuned.clone(), utilization_pct: None };
+    if workload != Workload::Gemm || max_batch <= 1 || tuned.elapsed_ms >= target_ms {
+        return Ok(best);
+    }
+    let capabilities = executor.capabilities();
+    // Clear of the nonces measure_candidates and warm-up use
+    let mut nonce = u32::MAX / 2;
+    while best.result.sizes.batch < max_batch {
+        let sizes = Sizes { batch: (best.result.sizes.batch * 2).min(max_batch), ..best.result.sizes.clone() };
+        if !capabilities.admits(workload, memhard, &sizes, 2) {
+            log_info!("[autotune] batch {} does not fit in device memory", sizes.batch);
+            break;
+        }
+        let (out, busy) = with_utilization(|| run_workload_attempt(executor, workload, memhard, prev_hash_bytes, nonce, None, &sizes));
+        nonce = nonce.wrapping_add(1);
+        let out = match out {
+            Ok(out) => out,
+            Err(e) if is_out_of_memory(&e) => {
+                log_info!("[autotune] batch {} does not fit in device memory", sizes.batch);
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        let busy_text = busy.map_or("n/a".to_string(), |pct| format!("{:.0}%", pct));
+        log_info!("[autotune] m,n,k=({},{},{}) batch {} -> {} ms, GPU busy {}",
+            sizes.m, sizes.n, sizes.k, sizes.batch, out.elapsed_ms, busy_text);
+        if out.elapsed_ms > target_ms {
+            break;
+        }
+        best = BatchScale { result: TuneResult { sizes, elapsed_ms: out.elapsed_ms }, utilization_pct: busy };
+        if busy.is_some_and(|pct| pct >= utilization_pct as f64) {
+            break;
+        }
+    }
+    Ok(best)
+}
+
+// Run `f` while sampling GPU utilization; the peak sample, if the host reports any
+fn with_utilization<T>(f: impl FnOnce() -> T) -> (T, Option<f64>) {
+    let done = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        let sampler = scope.spawn(|| {
+            let mut peak: Option<f64> = None;
+            loop {
+                if let Some(pct) = gpu_utilization_pct() {
+                    peak = Some(peak.map_or(pct, |p| p.max(pct)));
+                }
+                if done.load(Ordering::Relaxed) {
+                    return peak;
+                }
+                std::thread::sleep(UTILIZATION_SAMPLE_INTERVAL);
+            }
+        });
+        let result = f();
+        done.store(true, Ordering::Relaxed);
+        (result, sampler.join().ok().flatten())
+    })
+}
+
+/// Watches attempt latency for the device slowing down (thermal throttling, clock
+/// changes) enough to warrant re-tuning. The baseline is the mean of the first
+/// `RETUNE_STREAK` attempts after (re)tuning, so it already includes the contention
+/// of several attempt streams.
+#[derive(Debug)]
+pub struct DriftMonitor {
+    drift_pct: u32,
+    baseline_samples: u32,
+    baseline_total_ms: u64,
+    slow_streak: u32,
+}
+
+impl DriftMonitor {
+    pub fn new(drift_pct: u32) -> Self {
+        Self { drift_pct, baseline_samples: 0, baseline_total_ms: 0, slow_streak: 0 }
+    }
+
+    /// Record an attempt; true once `RETUNE_STREAK` consecutive attempts exceeded the threshold.
+    pub fn observe(&mut self, elapsed_ms: u64) -> bool {
+        if self.drift_pct == 0 {
+            return false;
+        }
+        if self.baseline_samples < RETUNE_STREAK {
+            self.baseline_samples += 1;
+            self.baseline_total_ms += elapsed_ms;
+            return false;
+        }
+        let baseline = self.baseline_total_ms / self.baseline_samples as u64;
+        if elapsed_ms > baseline + baseline * self.drift_pct as u64 / 100 {
+            self.slow_streak += 1;
+        } else {
+            self.slow_streak = 0;
+        }
+        self.slow_streak >= RETUNE_STREAK
+    }
+}
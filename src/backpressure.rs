@@ -0,0 +1,177 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+/// What the submission backlog asks of the attempt loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThrottleMode {
+    Running,
+    /// Attempts are spaced out in proportion to the backlog.
+    Slowed,
+    /// No attempts until the backlog drains to the resume threshold.
+    Paused,
+}
+
+impl std::fmt::Display for ThrottleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThrottleMode::Running => write!(f, "running"),
+            ThrottleMode::Slowed => write!(f, "slowed"),
+            ThrottleMode::Paused => write!(f, "paused"),
+        }
+    }
+}
+
+/// Back-pressure state reported in /status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackPressureStatus {
+    pub mode: ThrottleMode,
+    /// Receipts buffered by the transport at the last check.
+    pub queue_depth: usize,
+    pub slow_at: usize,
+    pub pause_at: usize,
+    pub resume_at: usize,
+    /// Delay added before each attempt while slowed.
+    pub delay_ms: u64,
+    /// When the current mode was entered, unless running.
+    pub since: Option<String>,
+    /// Times the loop was paused, and seconds spent slowed or paused, since startup.
+    pub pauses: u64,
+    pub throttled_seconds: f64,
+}
+
+/// Throttles the attempt loop on the depth of the submission backlog
+/// (`BACKPRESSURE_*`), so an aggregator outage does not keep the device busy
+/// producing receipts that can only pile up on disk.
+///
+/// From `slow_at` buffered receipts each attempt is delayed, linearly up to
+/// `max_delay` at `pause_at`; from `pause_at` attempts stop until the backlog
+/// is down to `resume_at`. A threshold of 0 disables that stage.
+#[derive(Debug)]
+pub struct BackPressure {
+    slow_at: usize,
+    pause_at: usize,
+    resume_at: usize,
+    max_delay: Duration,
+    state: Mutex<BackPressureState>,
+}
+
+#[derive(Debug)]
+struct BackPressureState {
+    mode: ThrottleMode,
+    queue_depth: usize,
+    delay: Duration,
+    since: Option<(Instant, String)>,
+    pauses: u64,
+    throttled: Duration,
+}
+
+impl BackPressure {
+    pub fn new(slow_at: usize, pause_at: usize, resume_at: usize, max_delay: Duration) -> Self {
+        Self {
+            slow_at,
+            pause_at,
+            resume_at,
+            max_delay,
+            state: Mutex::new(BackPressureState {
+                mode: ThrottleMode::Running,
+                queue_depth: 0,
+                delay: Duration::ZERO,
+                since: None,
+                pauses: 0,
+                throttled: Duration::ZERO,
+            }),
+        }
+    }
+
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self::new(config.backpressure_slow_at, config.backpressure_pause_at, config.backpressure_resume_at,
+            config.get_backpressure_max_delay())
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay
+    }
+
+    /// Whether any stage is enabled.
+    pub fn enabled(&self) -> bool {
+        self.slow_at > 0 || self.pause_at > 0
+    }
+
+    /// Classify the backlog `depth`; returns the new mode when it changed.
+    pub fn observe(&self, depth: usize) -> Option<ThrottleMode> {
+        let Ok(mut state) = self.state.lock() else { return None };
+        let paused = state.mode == ThrottleMode::Paused;
+        let mode = if self.pause_at > 0 && (depth >= self.pause_at || (paused && depth > self.resume_at)) {
+            ThrottleMode::Paused
+        } else if self.slow_at > 0 && depth >= self.slow_at {
+            ThrottleMode::Slowed
+        } else {
+            ThrottleMode::Running
+        };
+        state.queue_depth = depth;
+        state.delay = match mode {
+            ThrottleMode::Slowed => self.delay_for(depth),
+            _ => Duration::ZERO,
+        };
+        if mode == state.mode {
+            return None;
+        }
+        if let Some((since, _)) = state.since.take() {
+            state.throttled += since.elapsed();
+        }
+        if mode != ThrottleMode::Running {
+            state.since = Some((Instant::now(), chrono::Utc::now().to_rfc3339()));
+        }
+        if mode == ThrottleMode::Paused {
+            state.pauses += 1;
+        }
+        state.mode = mode;
+        Some(mode)
+    }
+
+    // Linear from nothing at slow_at to max_delay at pause_at (or twice slow_at without a pause stage)
+    fn delay_for(&self, depth: usize) -> Duration {
+        let full_at = if self.pause_at > self.slow_at { self.pause_at } else { self.slow_at * 2 };
+        let fraction = (depth - self.slow_at) as f64 / (full_at - self.slow_at) as f64;
+        self.max_delay.mul_f64(fraction.clamp(0.0, 1.0))
+    }
+
+    pub fn mode(&self) -> ThrottleMode {
+        self.state.lock().map(|s| s.mode).unwrap_or(ThrottleMode::Running)
+    }
+
+    /// Delay to add before the next attempt.
+    pub fn delay(&self) -> Duration {
+        self.state.lock().map(|s| s.delay).unwrap_or_default()
+    }
+
+    pub fn status(&self) -> BackPressureStatus {
+        let Ok(state) = self.state.lock() else {
+            return BackPressureStatus {
+                mode: ThrottleMode::Running,
+                queue_depth: 0,
+                slow_at: self.slow_at,
+                pause_at: self.pause_at,
+                resume_at: self.resume_at,
+                delay_ms: 0,
+                since: None,
+                pauses: 0,
+                throttled_seconds: 0.0,
+            };
+        };
+        let current = state.since.as_ref().map(|(since, _)| since.elapsed()).unwrap_or_default();
+        BackPressureStatus {
+            mode: state.mode,
+            queue_depth: state.queue_depth,
+            slow_at: self.slow_at,
+            pause_at: self.pause_at,
+            resume_at: self.resume_at,
+            delay_ms: state.delay.as_millis() as u64,
+            since: state.since.as_ref().map(|(_, at)| at.clone()),
+            pauses: state.pauses,
+            throttled_seconds: (state.throttled + current).as_secs_f64(),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Stand-in aggregator for running the worker end to end without the real one.
+//!
+//! `mock-aggregator [--listen ADDR] [--pubkey HEX] [--network-id ID] [--recompute-max-macs N]
+//! [--fail MODE] [--fail-every N] [--epoch-id N] [--prev-hash HEX] [--hash-kind KIND]
+//! [--sign-sk HEX] [--matrix-cache DIR] [--matrix-cache-mb N] [--clock-skew-ms N]`
+
+use std::sync::Arc;
+use tops_worker::matrix_cache::{self, MatrixCache};
+use tops_worker::mock_aggregator::{MockAggregator, MockConfig};
+
+const USAGE: &str = "usage: mock-aggregator [options]
+  --listen ADDR             address to serve on (default 127.0.0.1:8081)
+  --pubkey HEX              verify receipt signatures against this key (default: unchecked)
+  --network-id ID           require this network_id on receipts
+  --recompute-max-macs N    recompute the work_root of receipts up to N multiply-accumulates (default 2097152, 0: never)
+  --fail MODE               inject none, reject:<reason>, throttle, error, hang or close (default none)
+  --fail-every N            inject the failure into every Nth submission (default 1)
+  --epoch-id N              epoch served at GET /epoch (default 1)
+  --prev-hash HEX           prev_hash served at GET /epoch
+  --hash-kind KIND          work_root hash served at GET /epoch and required on receipts (blake3, sha3-256, poseidon; default blake3)
+  --sign-sk HEX             sign verdicts and epochs with this key, for AGGREGATOR_PUBKEY
+  --matrix-cache DIR        cache the matrices of recomputed receipts in DIR (default: off)
+  --matrix-cache-mb N       size budget of the matrix cache (default 1024)
+  --clock-skew-ms N         shift the Date header of responses by N ms (default 0)";
+
+fn parse_args() -> anyhow::Result<MockConfig> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        std::process::exit(0);
+    }
+    let mut config = MockConfig::default();
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let value = iter.next().ok_or_else(|| anyhow::anyhow!("{} expects a value\n{}", flag, USAGE))?;
+        let invalid = || anyhow::anyhow!("invalid value for {}: {}", flag, value);
+        match flag.as_str() {
+            "--listen" => config.listen = value.parse().map_err(|_| invalid())?,
+            "--pubkey" => config.pubkey = Some(value.clone()),
+            "--network-id" => config.network_id = Some(value.clone()),
+            "--recompute-max-macs" => config.recompute_max_macs = value.parse().map_err(|_| invalid())?,
+            "--fail" => config.failure = value.parse().map_err(|e: String| anyhow::anyhow!(e))?,
+            "--fail-every" => config.fail_every = value.parse().map_err(|_| invalid())?,
+            "--epoch-id" => config.epoch.epoch_id = value.parse().map_err(|_| invalid())?,
+            "--prev-hash" => config.epoch.prev_hash = value.clone(),
+            "--hash-kind" => config.epoch.hash_kind = Some(value.clone()),
+            "--sign-sk" => config.response_sk_hex = Some(value.clone()),
+            "--matrix-cache" => config.matrix_cache_dir = Some(value.into()),
+            "--matrix-cache-mb" => config.matrix_cache_mb = value.parse().map_err(|_| invalid())?,
+            "--clock-skew-ms" => config.clock_skew_ms = value.parse().map_err(|_| invalid())?,
+            other => anyhow::bail!("unknown option {}\n{}", other, USAGE),
+        }
+    }
+    // Reject a malformed epoch here rather than on the worker's first poll
+    config.epoch.clone().into_info()?;
+    Ok(config)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = parse_args()?;
+    println!("[mock-aggregator] listening on {} (signatures {}, recompute up to {} MACs, failure {} every {})",
+        config.listen,
+        if config.pubkey.is_some() { "checked" } else { "unchecked" },
+        config.recompute_max_macs,
+        config.failure,
+        config.fail_every);
+    if let Some(dir) = &config.matrix_cache_dir {
+        matrix_cache::install(MatrixCache::open(dir, config.matrix_cache_mb * 1024 * 1024)?);
+        println!("[mock-aggregator] caching recomputed matrices in {} (up to {} MiB)", dir.display(), config.matrix_cache_mb);
+    }
+    Arc::new(MockAggregator::new(config)?).serve().await
+}
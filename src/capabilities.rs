@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use crate::device_memory::{DeviceMemory, Footprint};
+use crate::memhard::MemHardParams;
+use crate::types::Sizes;
+use crate::workload::{Workload, WorkloadKind};
+
+/// Largest side the OpenCL kernels handle: they index `m * n` (and `m * k`, `k * n`) as `int`.
+pub const OPENCL_MAX_SIDE: usize = 46_340;
+
+/// What an execution backend can run, queried before sizes and workload are chosen
+/// so an unsuitable choice is caught at startup instead of failing an attempt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Workloads the backend accepts, natively or through the CPU reference.
+    pub workloads: Vec<WorkloadKind>,
+    /// Workloads with a kernel of the backend's own.
+    pub native_workloads: Vec<WorkloadKind>,
+    /// Whether the memory-hard stage has a kernel of the backend's own.
+    pub native_memhard: bool,
+    /// Whether `Executor::run_gemm_sampled` picks and hashes the work_root samples on
+    /// the device rather than reading the whole output back.
+    pub device_sampling: bool,
+    /// Hardware or SIMD int8 dot products (AVX2, AVX-512 VNNI, NEON, tensor cores).
+    pub int8_dot: bool,
+    /// Sides that are a multiple of this run without partial tiles or vector tails.
+    pub alignment: usize,
+    /// Largest side the kernels can index, regardless of memory; `None` when unbounded.
+    pub max_side: Option<usize>,
+    /// Device memory at the time of the query, for backends that allocate on a device.
+    pub memory: Option<DeviceMemory>,
+}
+
+impl Capabilities {
+    /// A backend with a GEMM kernel only: the other workloads run on the CPU reference.
+    pub fn gemm_only(memory: Option<DeviceMemory>) -> Self {
+        Self {
+            workloads: vec![WorkloadKind::Gemm, WorkloadKind::Spmm],
+            native_workloads: vec![WorkloadKind::Gemm],
+            native_memhard: false,
+            device_sampling: false,
+            int8_dot: false,
+            alignment: 1,
+            max_side: None,
+            memory,
+        }
+    }
+
+    pub fn supports(&self, kind: WorkloadKind) -> bool {
+        self.workloads.contains(&kind)
+    }
+
+    pub fn is_native(&self, kind: WorkloadKind) -> bool {
+        self.native_workloads.contains(&kind)
+    }
+
+    /// Whether every side is within `max_side` and `copies` attempts fit in device memory.
+    pub fn admits(&self, workload: Workload, memhard: Option<&MemHardParams>, sizes: &Sizes, copies: usize) -> bool {
+        let within_side = self.max_side.is_none_or(|max| sizes.m.max(sizes.n).max(sizes.k) <= max);
+        within_side && self.memory.is_none_or(|mem| mem.fits(&Footprint::of(workload, memhard, sizes), copies, 0))
+    }
+
+    /// `sizes` with every side clamped to `max_side`, rounded down to the alignment.
+    pub fn clamp_sides(&self, sizes: &Sizes) -> Sizes {
+        let Some(max) = self.max_side else { return sizes.clone() };
+        let align = self.alignment.max(1);
+        let max = (max / align * align).max(align);
+        Sizes { m: sizes.m.min(max), n: sizes.n.min(max), k: sizes.k.min(max), batch: sizes.batch }
+    }
+
+    /// Largest aligned square side `copies` attempts can run at, or `None` when
+    /// neither the kernels nor the device memory bound it.
+    pub fn max_square_side(&self, workload: Workload, memhard: Option<&MemHardParams>, copies: usize) -> Option<usize> {
+        let align = self.alignment.max(1);
+        let fits = |units: usize| {
+            let side = units * align;
+            self.admits(workload, memhard, &Sizes { m: side, n: side, k: side, batch: 1 }, copies)
+        };
+        let limit = match (self.max_side, self.memory) {
+            (Some(max), _) => max / align,
+            // Memory alone: no side can exceed the square root of the device's bytes
+            (None, Some(mem)) => ((mem.total_bytes as f64).sqrt() as usize / align).max(1),
+            (None, None) => return None,
+        };
+        if !fits(1) {
+            return Some(0);
+        }
+        // Binary search over multiples of the alignment: `low` fits, `high` does not
+        let (mut low, mut high) = (1, limit + 1);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if fits(mid) { low = mid } else { high = mid }
+        }
+        Some(low * align)
+    }
+
+    /// One line for the startup log.
+    pub fn describe(&self) -> String {
+        let kinds = |kinds: &[WorkloadKind]| kinds.iter().map(|k| k.to_string()).collect::<Vec<_>>().join(",");
+        let mut line = format!("workloads [{}] (native [{}]), memhard {}, work_root sampling {}, int8 dot {}, alignment {}",
+            kinds(&self.workloads), kinds(&self.native_workloads),
+            if self.native_memhard { "native" } else { "CPU reference" },
+            if self.device_sampling { "on device" } else { "on host" },
+            if self.int8_dot { "yes" } else { "no" },
+            self.alignment);
+        if let Some(max) = self.max_side {
+            line.push_str(&format!(", max side {}", max));
+        }
+        if let Some(mem) = &self.memory {
+            line.push_str(&format!(", {} MiB device memory", mem.total_bytes >> 20));
+        }
+        line
+    }
+}
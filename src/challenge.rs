@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::submit::hex32;
+use crate::{log_error, log_info, log_warn};
+
+/// Nonce of the attempt answering a challenge. Its prev_hash is fresh, so the
+/// nonce only has to keep clear of the periodic checks keyed on multiples of 100.
+pub const CHALLENGE_NONCE: u32 = 1;
+
+/// Longest deadline a challenge is held to; longer ones are clamped, so no
+/// `deadline_ms` can overflow the clock.
+pub const MAX_CHALLENGE_DEADLINE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A liveness challenge from the aggregator, carried in a submission verdict:
+/// `"challenge":{"id":"c-17","prev_hash":"<64 hex>","deadline_ms":5000}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Challenge {
+    pub id: String,
+    /// prev_hash (hex) the answering attempt chains from instead of the epoch's.
+    pub prev_hash: String,
+    /// Time from the verdict's arrival until the answering receipt must be accepted.
+    pub deadline_ms: u64,
+}
+
+/// How a challenge ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengeOutcome {
+    /// The answer was accepted within the deadline.
+    Met,
+    /// The answer was accepted after the deadline.
+    Late,
+    /// The deadline passed before an attempt could start.
+    Expired,
+    /// The attempt or its submission failed, or the aggregator refused the answer.
+    Failed,
+    /// The queue was full.
+    Dropped,
+}
+
+impl std::fmt::Display for ChallengeOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChallengeOutcome::Met => write!(f, "met"),
+            ChallengeOutcome::Late => write!(f, "late"),
+            ChallengeOutcome::Expired => write!(f, "expired"),
+            ChallengeOutcome::Failed => write!(f, "failed"),
+            ChallengeOutcome::Dropped => write!(f, "dropped"),
+        }
+    }
+}
+
+/// Challenges waiting and answered since startup, served in `/status`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChallengeStatus {
+    pub pending: usize,
+    pub met: u64,
+    pub late: u64,
+    pub expired: u64,
+    pub failed: u64,
+    pub dropped: u64,
+    /// Time from arrival to the accepted answer of the latest challenge met or late.
+    pub last_response_ms: Option<u64>,
+    pub last_challenge_id: Option<String>,
+}
+
+struct Pending {
+    challenge: Challenge,
+    prev_hash: [u8; 32],
+    received: Instant,
+    deadline: Instant,
+}
+
+/// Challenges received but not yet answered, answered earliest deadline first
+/// (`CHALLENGE_QUEUE_MAX`).
+pub struct ChallengeQueue {
+    capacity: usize,
+    pending: Mutex<VecDeque<Pending>>,
+    status: Mutex<ChallengeStatus>,
+    metrics: Option<Arc<PrometheusMetrics>>,
+}
+
+impl ChallengeQueue {
+    /// A queue of up to `capacity` challenges; 0 ignores every challenge.
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, pending: Mutex::new(VecDeque::new()), status: Mutex::new(ChallengeStatus::default()), metrics: None }
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<PrometheusMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Queue `challenge`, received now. Repeats of a queued id are ignored.
+    pub fn push(&self, challenge: Challenge) {
+        if !self.enabled() {
+            return;
+        }
+        let Some(prev_hash) = hex32(&challenge.prev_hash) else {
+            log_warn!("[challenge] ignoring {}: prev_hash is not 32 bytes of hex", challenge.id);
+            return;
+        };
+        let received = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        if pending.iter().any(|p| p.challenge.id == challenge.id) {
+            return;
+        }
+        if pending.len() >= self.capacity {
+            drop(pending);
+            log_warn!("[challenge] queue full, dropping {}", challenge.id);
+            self.record(ChallengeOutcome::Dropped, &challenge.id, None);
+            return;
+        }
+        log_info!("[challenge] {} received, answer due within {} ms", challenge.id, challenge.deadline_ms);
+        let deadline = received + Duration::from_millis(challenge.deadline_ms).min(MAX_CHALLENGE_DEADLINE);
+        pending.push_back(Pending { challenge, prev_hash, received, deadline });
+        self.set_pending_gauge(pending.len());
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.lock().unwrap().is_empty()
+    }
+
+    /// The challenge to answer next, earliest deadline first. Challenges whose
+    /// deadline has already passed are counted as expired and skipped.
+    pub fn next_due(self: &Arc<Self>) -> Option<ChallengeAttempt> {
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        let mut expired = Vec::new();
+        pending.retain(|p| {
+            let live = p.deadline > now;
+            if !live {
+                expired.push(p.challenge.id.clone());
+            }
+            live
+        });
+        let next = (0..pending.len()).min_by_key(|&i| pending[i].deadline).and_then(|i| pending.remove(i));
+        self.set_pending_gauge(pending.len());
+        drop(pending);
+        for id in expired {
+            log_warn!("[challenge] {} expired before an attempt could start", id);
+            self.record(ChallengeOutcome::Expired, &id, None);
+        }
+        next.map(|p| ChallengeAttempt {
+            queue: Arc::clone(self),
+            challenge: p.challenge,
+            prev_hash: p.prev_hash,
+            received: p.received,
+            deadline: p.deadline,
+            finished: false,
+        })
+    }
+
+    pub fn status(&self) -> ChallengeStatus {
+        let mut status = self.status.lock().unwrap().clone();
+        status.pending = self.pending.lock().unwrap().len();
+        status
+    }
+
+    fn record(&self, outcome: ChallengeOutcome, id: &str, response: Option<Duration>) {
+        let mut status = self.status.lock().unwrap();
+        match outcome {
+            ChallengeOutcome::Met => status.met += 1,
+            ChallengeOutcome::Late => status.late += 1,
+            ChallengeOutcome::Expired => status.expired += 1,
+            ChallengeOutcome::Failed => status.failed += 1,
+            ChallengeOutcome::Dropped => status.dropped += 1,
+        }
+        if let Some(response) = response {
+            status.last_response_ms = Some(response.as_millis() as u64);
+        }
+        status.last_challenge_id = Some(id.to_string());
+        if let Some(metrics) = &self.metrics {
+            metrics.record_challenge(&outcome.to_string(), response);
+        }
+    }
+
+    fn set_pending_gauge(&self, pending: usize) {
+        if let Some(metrics) = &self.metrics {
+            metrics.set_challenges_pending(pending);
+        }
+    }
+}
+
+/// A challenge taken off the queue to be answered. Dropping it without
+/// `finish` counts it as failed, so every way out of the attempt is accounted for.
+pub struct ChallengeAttempt {
+    queue: Arc<ChallengeQueue>,
+    challenge: Challenge,
+    prev_hash: [u8; 32],
+    received: Instant,
+    deadline: Instant,
+    finished: bool,
+}
+
+impl ChallengeAttempt {
+    pub fn id(&self) -> &str {
+        &self.challenge.id
+    }
+
+    pub fn prev_hash(&self) -> [u8; 32] {
+        self.prev_hash
+    }
+
+    pub fn prev_hash_hex(&self) -> String {
+        hex::encode(self.prev_hash)
+    }
+
+    /// Time left until the deadline; zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    /// Record the aggregator's verdict on the answer.
+    pub fn finish(mut self, accepted: bool) -> ChallengeOutcome {
+        self.finished = true;
+        let response = self.received.elapsed();
+        let outcome = match (accepted, Instant::now() <= self.deadline) {
+            (true, true) => ChallengeOutcome::Met,
+            (true, false) => ChallengeOutcome::Late,
+            (false, _) => ChallengeOutcome::Failed,
+        };
+        self.queue.record(outcome, &self.challenge.id, accepted.then_some(response));
+        log_info!("[challenge] {} {} after {} ms (deadline {} ms)",
+            self.challenge.id, outcome, response.as_millis(), self.challenge.deadline_ms);
+        outcome
+    }
+}
+
+impl Drop for ChallengeAttempt {
+    fn drop(&mut self) {
+        if !self.finished {
+            log_error!("[challenge] {} failed: no receipt was submitted for it", self.challenge.id);
+            self.queue.record(ChallengeOutcome::Failed, &self.challenge.id, None);
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use crate::error_handling::{CircuitBreaker, CircuitPermit};
+use crate::epoch_summary::EpochSummary;
+use crate::export::{ExportRow, ReceiptExporter};
+use crate::quarantine::{Quarantine, QuarantinedReceipt};
+use crate::queue::PersistentQueue;
+use crate::submit::{EpochInfo, FailureKind, SubmitError, SubmitOutcome, Submission, Submitter, SummaryRejected};
+use crate::types::WorkReceipt;
+use crate::{log_info, log_warn};
+
+/// Wraps a transport with the submission circuit breaker.
+///
+/// While the circuit is open, receipts are parked on disk instead of being sent.
+/// Once the recovery timeout has passed, exactly one receipt goes out as a canary:
+/// the oldest parked one if there is any. The circuit closes only if the aggregator
+/// answers it; otherwise it opens for another recovery timeout. With the circuit
+/// closed again, every delivered receipt is followed by one parked receipt until
+/// the backlog is gone.
+///
+/// Epoch summaries that cannot be delivered are parked too, in `summaries/` under
+/// the backlog directory, and sent again with the circuit closed: one after each
+/// delivered receipt, and all of them before the next summary. A summary the
+/// endpoint refuses outright is dropped.
+///
+/// A parked receipt is exported twice: as `queued` when it is parked, and signed
+/// with its final outcome once the aggregator accepts or rejects it.
+pub struct CircuitSubmitter {
+    inner: Arc<dyn Submitter>,
+    breaker: Arc<CircuitBreaker>,
+    backlog: PersistentQueue,
+    summaries: PersistentQueue,
+    quarantine: Option<Arc<Quarantine>>,
+    exporter: Option<Arc<ReceiptExporter>>,
+}
+
+impl CircuitSubmitter {
+    pub fn new(inner: Arc<dyn Submitter>, breaker: Arc<CircuitBreaker>, backlog_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let summaries = PersistentQueue::open(backlog_dir.as_ref().join("summaries"))?;
+        Ok(Self { inner, breaker, backlog: PersistentQueue::open(backlog_dir)?, summaries, quarantine: None, exporter: None })
+    }
+
+    /// Keep parked receipts the aggregator rejects once it is back, as the main loop
+    /// does for the receipts it submits itself.
+    pub fn with_quarantine(mut self, quarantine: Option<Arc<Quarantine>>) -> Self {
+        self.quarantine = quarantine;
+        self
+    }
+
+    /// Export parked receipts again once their fate is known, as the main loop
+    /// does for the receipts it submits itself.
+    pub fn with_exporter(mut self, exporter: Option<Arc<ReceiptExporter>>) -> Self {
+        self.exporter = exporter;
+        self
+    }
+
+    fn park(&self, receipt: WorkReceipt) -> Result<Submission, SubmitError> {
+        self.backlog.push(&receipt).map_err(|e| SubmitError::Queue(e.to_string()))?;
+        Ok(Submission {
+            target: format!("circuit backlog {}", self.backlog.dir().display()),
+            latency: Duration::ZERO,
+            outcome: SubmitOutcome::Queued,
+            compression: None,
+            response: None,
+            retries: 0,
+            signed: None,
+        })
+    }
+
+    // Submit and feed the outcome to the breaker: only a network or server failure counts against it
+    async fn send(&self, receipt: WorkReceipt, probe: bool) -> Result<Submission, SubmitError> {
+        match self.inner.submit(receipt).await {
+            Ok(submission) => {
+                match submission.outcome {
+                    SubmitOutcome::Failed { .. } => self.breaker.record_failure(),
+                    _ => self.breaker.record_success(),
+                }
+                Ok(submission)
+            }
+            Err(e) => {
+                // The probe has to come back one way or the other, or the circuit stays half-open
+                if probe {
+                    self.breaker.record_failure();
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // Export a replayed receipt once it leaves the backlog; a retry that keeps it parked is not its final outcome
+    fn export(&self, receipt: &WorkReceipt, submission: &Submission) {
+        if matches!(submission.outcome, SubmitOutcome::Throttled { .. } | SubmitOutcome::Failed { .. }) {
+            return;
+        }
+        if let Some(exporter) = &self.exporter {
+            if let Err(e) = exporter.record(&ExportRow::new(receipt, submission)) {
+                log_warn!("[export] could not record parked nonce {}: {}", receipt.nonce, e);
+            }
+        }
+    }
+
+    // Deliver the oldest parked receipt; it stays parked unless the aggregator answered
+    async fn replay(&self, probe: bool) {
+        let (seq, receipt) = match self.backlog.peek::<WorkReceipt>() {
+            Ok(Some(entry)) => entry,
+            result => {
+                if let Err(e) = result {
+                    log_warn!("[circuit] could not read the backlog: {}", e);
+                }
+                // Nothing readable went out: count the probe as failed rather than leave
+                // the circuit half-open with no call in flight
+                if probe {
+                    self.breaker.record_failure();
+                }
+                return;
+            }
+        };
+        let nonce = receipt.nonce;
+        let delivered = match self.send(receipt.clone(), probe).await {
+            Ok(submission) => {
+                self.export(&receipt, &submission);
+                match submission.outcome {
+                    SubmitOutcome::Accepted { .. } | SubmitOutcome::Queued => {
+                        log_info!("[circuit] delivered parked nonce {} ({} left)", nonce, self.backlog.len().saturating_sub(1));
+                        true
+                    }
+                    SubmitOutcome::Rejected { .. } if submission.failure_kind() == Some(FailureKind::Duplicate) => {
+                        log_info!("[circuit] parked nonce {} was already delivered ({} left)", nonce, self.backlog.len().saturating_sub(1));
+                        true
+                    }
+                    SubmitOutcome::Rejected { status, body } => {
+                        log_warn!("[circuit] parked nonce {} rejected ({}): {}", nonce, status, body);
+                        if let Some(quarantine) = &self.quarantine {
+                            let entry = QuarantinedReceipt::new(receipt, &submission.target, status, &body, submission.response.as_ref());
+                            if let Err(e) = quarantine.store(&entry) {
+                                log_warn!("[quarantine] could not keep rejected nonce {}: {}", nonce, e);
+                            }
+                        }
+                        true
+                    }
+                    SubmitOutcome::Throttled { .. } | SubmitOutcome::Failed { .. } => false,
+                }
+            }
+            // Already delivered by an earlier run
+            Err(SubmitError::Duplicate(_)) => true,
+            // Signed once the signer is back
+            Err(e @ SubmitError::SignerUnavailable(_)) => {
+                log_warn!("[circuit] parked nonce {} stays parked: {}", nonce, e);
+                false
+            }
+            Err(e) => {
+                log_warn!("[circuit] dropping parked nonce {}: {}", nonce, e);
+                true
+            }
+        };
+        if delivered {
+            if let Err(e) = self.backlog.remove(seq) {
+                log_warn!("[circuit] could not remove parked nonce {}: {}", nonce, e);
+            }
+        }
+    }
+
+    // Deliver the oldest parked summary; false when it stays parked (or nothing is parked)
+    async fn replay_summary(&self) -> bool {
+        let (seq, summary) = match self.summaries.peek::<EpochSummary>() {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return false,
+            Err(e) => {
+                log_warn!("[circuit] could not read the summary backlog: {}", e);
+                return false;
+            }
+        };
+        let epoch_id = summary.epoch_id;
+        match self.inner.submit_summary(summary).await {
+            Ok(true) => log_info!("[epoch] parked summary of epoch {} delivered", epoch_id),
+            // The transport no longer sends summaries
+            Ok(false) => {}
+            Err(e) if e.is::<SummaryRejected>() => log_warn!("[epoch] dropping parked summary of epoch {}: {}", epoch_id, e),
+            Err(_) => return false,
+        }
+        if let Err(e) = self.summaries.remove(seq) {
+            log_warn!("[circuit] could not remove parked summary of epoch {}: {}", epoch_id, e);
+            return false;
+        }
+        true
+    }
+}
+
+#[async_trait]
+impl Submitter for CircuitSubmitter {
+    fn describe(&self) -> String {
+        self.inner.describe()
+    }
+
+    async fn submit(&self, receipt: WorkReceipt) -> Result<Submission, SubmitError> {
+        match self.breaker.acquire() {
+            CircuitPermit::Refused => self.park(receipt),
+            CircuitPermit::Probe if !self.backlog.is_empty() => {
+                // The new receipt waits its turn behind the backlog
+                let parked = self.park(receipt)?;
+                log_info!("[circuit] half-open, probing with the oldest of {} parked receipt(s)", self.backlog.len());
+                self.replay(true).await;
+                Ok(parked)
+            }
+            CircuitPermit::Probe => {
+                log_info!("[circuit] half-open, probing with nonce {}", receipt.nonce);
+                self.send(receipt, true).await
+            }
+            CircuitPermit::Closed => {
+                let submission = match self.send(receipt.clone(), false).await {
+                    Err(SubmitError::SignerUnavailable(e)) => {
+                        log_warn!("[circuit] parking nonce {} until the signer is back: {}", receipt.nonce, e);
+                        return self.park(receipt);
+                    }
+                    result => result?,
+                };
+                if !matches!(submission.outcome, SubmitOutcome::Failed { .. }) {
+                    if !self.backlog.is_empty() {
+                        self.replay(false).await;
+                    }
+                    if !self.summaries.is_empty() {
+                        self.replay_summary().await;
+                    }
+                }
+                Ok(submission)
+            }
+        }
+    }
+
+    async fn current_epoch(&self) -> anyhow::Result<Option<EpochInfo>> {
+        self.inner.current_epoch().await
+    }
+
+    fn pending(&self) -> usize {
+        self.inner.pending() + self.backlog.len()
+    }
+
+    async fn drain_one(&self) -> bool {
+        if self.backlog.is_empty() {
+            return self.inner.drain_one().await;
+        }
+        match self.breaker.acquire() {
+            CircuitPermit::Refused => false,
+            CircuitPermit::Probe => {
+                log_info!("[circuit] half-open, probing with the oldest of {} parked receipt(s)", self.backlog.len());
+                self.replay(true).await;
+                true
+            }
+            CircuitPermit::Closed => {
+                self.replay(false).await;
+                true
+            }
+        }
+    }
+    async fn submit_summary(&self, summary: EpochSummary) -> anyhow::Result<bool> {
+        // Parked summaries go first, until one fails again
+        while !self.summaries.is_empty() && self.replay_summary().await {}
+        match self.inner.submit_summary(summary.clone()).await {
+            Err(e) if !e.is::<SummaryRejected>() => {
+                self.summaries.push(&summary)?;
+                Err(anyhow::anyhow!("{}; parked for another try", e))
+            }
+            result => result,
+        }
+    }
+}
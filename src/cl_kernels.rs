@@ -1,3 +1,43 @@
+/// Requantization shared by the GEMM and SpMM kernels; must match `Requant::apply`.
+/// The `mode` argument is `Requant::mode_code`.
+pub const REQUANT: &str = r#"
+// mode bits 0-3 activation: 0 relu, 1 identity, 2 relu6 (Q3.4: 96 = 6.0), 3 leaky (slope 1/8)
+// bits 4-5 rounding: 0 toward zero, 1 floor, 2 half away from zero
+// bit 6 overflow: 0 saturate, 1 wrap
+// REQUANT_FN qualifies the function for compilers other than OpenCL's (CUDA's __device__)
+#ifndef REQUANT_FN
+#define REQUANT_FN
+#endif
+REQUANT_FN char requantize(int acc, int scale_num, int scale_den, int mode) {
+    long prod = (long)acc * (long)scale_num;
+    // C division truncates, so the remainder has the sign of the product
+    long tmp = prod / (long)scale_den;
+    long rem = prod % (long)scale_den;
+    switch ((mode >> 4) & 3) {
+        case 1: if (rem < 0) tmp -= 1; break;
+        case 2: if (2 * (rem < 0 ? -rem : rem) >= (long)scale_den) tmp += (prod < 0) ? -1 : 1; break;
+        default: break;
+    }
+    int q;
+    if ((mode >> 6) & 1) {
+        // Low 8 bits as two's complement, without relying on an out-of-range conversion
+        q = (int)(tmp & 0xFF);
+        if (q > 127) q -= 256;
+    } else {
+        if (tmp < -128) tmp = -128;
+        if (tmp > 127) tmp = 127;
+        q = (int)tmp;
+    }
+    switch (mode & 15) {
+        case 1: break;
+        case 2: q = clamp(q, 0, 96); break;
+        case 3: if (q < 0) q = q / 8; break;
+        default: q = max(q, 0); break;
+    }
+    return (char)q;
+}
+"#;
+
 pub const GEMM_INT8: &str = r#"
 #ifndef TM
 #define TM 1
@@ -14,11 +54,17 @@ __kernel void gemm_int8_relu_q(
     __global char*       Y,   // int8: M x N (output)
     const int M, const int N, const int K,
     const int lda, const int ldb, const int ldy,
-    const int scale_num, const int scale_den // requant: q = (acc * num) / den
+    const int scale_num, const int scale_den, // requant: q = (acc * num) / den
+    const int mode
 ) {
     int row = get_global_id(0);
     int col = get_global_id(1);
     if (row >= M || col >= N) return;
+    // Batched launches: dimension 2 is the item, stored after the previous one
+    size_t item = get_global_id(2);
+    A += item * (size_t)M * lda;
+    B += item * (size_t)K * ldb;
+    Y += item * (size_t)M * ldy;
 
     int acc = 0;
     // simple strip-mined loop over K with TK tile (placeholder for future local-mem tiling)
@@ -30,10 +76,384 @@ __kernel void gemm_int8_relu_q(
             acc += a * b;
         }
     }
-    // Requantize to int8 with ReLU
-    long tmp = ((long)acc * (long)scale_num) / (long)scale_den;
-    if (tmp < 0) tmp = 0;
-    if (tmp > 127) tmp = 127;
-    Y[row*ldy + col] = (char)tmp;
+    // Requantize to int8 and apply the activation
+    Y[row*ldy + col] = requantize(acc, scale_num, scale_den, mode);
+}
+
+#ifndef TILE
+#define TILE 16
+#endif
+// Local-memory tiled variant: each work-group stages TILE x TILE blocks of A and B.
+// Launched with a TILE x TILE local size and the global size rounded up to it.
+__kernel void gemm_int8_relu_q_tiled(
+    __global const char* A,
+    __global const char* B,
+    __global char*       Y,
+    const int M, const int N, const int K,
+    const int lda, const int ldb, const int ldy,
+    const int scale_num, const int scale_den,
+    const int mode
+) {
+    __local char As[TILE][TILE];
+    __local char Bs[TILE][TILE];
+    int lr = get_local_id(0);
+    int lc = get_local_id(1);
+    int row = get_global_id(0);
+    int col = get_global_id(1);
+    size_t item = get_global_id(2);
+    A += item * (size_t)M * lda;
+    B += item * (size_t)K * ldb;
+    Y += item * (size_t)M * ldy;
+
+    int acc = 0;
+    for (int t0 = 0; t0 < K; t0 += TILE) {
+        // Out-of-range elements load as 0 so the tail tiles add nothing
+        As[lr][lc] = (row < M && t0 + lc < K) ? A[row*lda + t0 + lc] : 0;
+        Bs[lr][lc] = (t0 + lr < K && col < N) ? B[(t0 + lr)*ldb + col] : 0;
+        barrier(CLK_LOCAL_MEM_FENCE);
+        for (int t = 0; t < TILE; ++t) {
+            acc += (int)As[lr][t] * (int)Bs[t][lc];
+        }
+        barrier(CLK_LOCAL_MEM_FENCE);
+    }
+    if (row < M && col < N) {
+        Y[row*ldy + col] = requantize(acc, scale_num, scale_den, mode);
+    }
+}
+"#;
+
+/// Glue around CLBlast's SGEMM for the exact int8 path: widen the inputs to float,
+/// add each K panel's float product into an int32 accumulator, then requantize.
+pub const CLBLAST_GLUE: &str = r#"
+__kernel void int8_to_f32(__global const char* src, __global float* dst, const int len) {
+    int i = get_global_id(0);
+    if (i < len) dst[i] = (float)src[i];
+}
+
+// Panels are at most 1024 deep, so every partial sum is an integer of at most 2^24
+// and the float product is exact
+__kernel void accumulate_f32_i32(__global const float* panel, __global int* acc, const int len) {
+    int i = get_global_id(0);
+    if (i < len) acc[i] += (int)panel[i];
+}
+
+__kernel void requantize_i32(
+    __global const int* acc, __global char* Y, const int len,
+    const int scale_num, const int scale_den, const int mode
+) {
+    int i = get_global_id(0);
+    if (i < len) Y[i] = requantize(acc[i], scale_num, scale_den, mode);
+}
+"#;
+
+pub const SPMM_CSR_INT8: &str = r#"
+__kernel void spmm_csr_int8_relu_q(
+    __global const uint* row_ptr, // M + 1 offsets
+    __global const uint* col_idx, // nnz column indices into K
+    __global const char* vals,    // nnz int8 values of A
+    __global const char* B,       // int8: K x N
+    __global char*       Y,       // int8: M x N (output)
+    const int M, const int N,
+    const int scale_num, const int scale_den, const int mode
+) {
+    int row = get_global_id(0);
+    int col = get_global_id(1);
+    if (row >= M || col >= N) return;
+
+    // One work-item per output walks its row of A; neighbouring columns share the
+    // gathered rows of B, so this is bound by memory rather than ALU throughput
+    int acc = 0;
+    for (uint p = row_ptr[row]; p < row_ptr[row + 1]; ++p) {
+        acc += (int)vals[p] * (int)B[col_idx[p]*N + col];
+    }
+    Y[row*N + col] = requantize(acc, scale_num, scale_den, mode);
+}
+"#;
+
+pub const MEMHARD_ROMIX: &str = r#"
+#define ROTL(a, b) (((a) << (b)) | ((a) >> (32 - (b))))
+#define QR(a, b, c, d) \
+    x[b] ^= ROTL(x[a] + x[d], 7);  \
+    x[c] ^= ROTL(x[b] + x[a], 9);  \
+    x[d] ^= ROTL(x[c] + x[b], 13); \
+    x[a] ^= ROTL(x[d] + x[c], 18);
+
+void salsa20_8(uint* b) {
+    uint x[16];
+    for (int i = 0; i < 16; ++i) x[i] = b[i];
+    for (int r = 0; r < 4; ++r) {
+        QR(0, 4, 8, 12) QR(5, 9, 13, 1) QR(10, 14, 2, 6) QR(15, 3, 7, 11)
+        QR(0, 1, 2, 3)  QR(5, 6, 7, 4)  QR(10, 11, 8, 9) QR(15, 12, 13, 14)
+    }
+    for (int i = 0; i < 16; ++i) b[i] += x[i];
+}
+
+// ROMix is sequential by construction: a single work-item walks the whole buffer
+__kernel void memhard_romix(
+    __global uint* V,       // N x 16 words of scratch
+    __global uint* X,       // 16 words: initial block in, final block out
+    const uint N, const ulong iters
+) {
+    uint x[16];
+    for (int i = 0; i < 16; ++i) x[i] = X[i];
+    for (uint n = 0; n < N; ++n) {
+        for (int i = 0; i < 16; ++i) V[n*16 + i] = x[i];
+        salsa20_8(x);
+    }
+    for (ulong it = 0; it < iters; ++it) {
+        uint j = x[0] % N;
+        for (int i = 0; i < 16; ++i) x[i] ^= V[j*16 + i];
+        salsa20_8(x);
+    }
+    for (int i = 0; i < 16; ++i) X[i] = x[i];
+}
+"#;
+
+/// BLAKE3 of inputs of at most one chunk (1024 bytes), fed a byte at a time so the
+/// bytes can come from any address space; every input the device hashes fits.
+pub const BLAKE3: &str = r#"
+#define B3_CHUNK_START 1u
+#define B3_CHUNK_END 2u
+#define B3_ROOT 8u
+#define B3_DERIVE_KEY_CONTEXT 32u
+#define B3_DERIVE_KEY_MATERIAL 64u
+
+__constant uint B3_IV[8] = {
+    0x6A09E667u, 0xBB67AE85u, 0x3C6EF372u, 0xA54FF53Au,
+    0x510E527Fu, 0x9B05688Cu, 0x1F83D9ABu, 0x5BE0CD19u
+};
+// Message word order of each of the seven rounds
+__constant uchar B3_SCHEDULE[7][16] = {
+    {0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15},
+    {2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8},
+    {3, 4, 10, 12, 13, 2, 7, 14, 6, 5, 9, 0, 11, 15, 8, 1},
+    {10, 7, 12, 9, 14, 3, 13, 15, 4, 0, 11, 2, 5, 8, 1, 6},
+    {12, 13, 9, 11, 15, 10, 14, 8, 7, 2, 5, 3, 0, 1, 6, 4},
+    {9, 14, 11, 5, 8, 12, 15, 1, 13, 3, 0, 10, 2, 6, 4, 7},
+    {11, 15, 5, 0, 1, 9, 8, 6, 14, 10, 2, 12, 3, 4, 7, 13}
+};
+
+#define B3_G(a, b, c, d, x, y) \
+    v[a] = v[a] + v[b] + (x); v[d] = rotate(v[d] ^ v[a], 16u); \
+    v[c] = v[c] + v[d];       v[b] = rotate(v[b] ^ v[c], 20u); \
+    v[a] = v[a] + v[b] + (y); v[d] = rotate(v[d] ^ v[a], 24u); \
+    v[c] = v[c] + v[d];       v[b] = rotate(v[b] ^ v[c], 25u);
+
+typedef struct {
+    uint cv[8];
+    uint block[16];
+    uint block_len;
+    uint blocks;
+    uint flags;
+} b3_state;
+
+// Compress one block into the chaining value; the chunk counter is always 0
+void b3_compress(uint* cv, const uint* m, uint block_len, uint flags) {
+    uint v[16];
+    for (int i = 0; i < 8; ++i) v[i] = cv[i];
+    for (int i = 0; i < 4; ++i) v[8 + i] = B3_IV[i];
+    v[12] = 0; v[13] = 0; v[14] = block_len; v[15] = flags;
+    for (int r = 0; r < 7; ++r) {
+        __constant uchar* s = B3_SCHEDULE[r];
+        B3_G(0, 4, 8, 12, m[s[0]], m[s[1]])
+        B3_G(1, 5, 9, 13, m[s[2]], m[s[3]])
+        B3_G(2, 6, 10, 14, m[s[4]], m[s[5]])
+        B3_G(3, 7, 11, 15, m[s[6]], m[s[7]])
+        B3_G(0, 5, 10, 15, m[s[8]], m[s[9]])
+        B3_G(1, 6, 11, 12, m[s[10]], m[s[11]])
+        B3_G(2, 7, 8, 13, m[s[12]], m[s[13]])
+        B3_G(3, 4, 9, 14, m[s[14]], m[s[15]])
+    }
+    for (int i = 0; i < 8; ++i) cv[i] = v[i] ^ v[i + 8];
+}
+
+// `key` is the IV for a plain hash, or the context key when deriving a key
+void b3_init(b3_state* st, const uint* key, uint flags) {
+    for (int i = 0; i < 8; ++i) st->cv[i] = key[i];
+    for (int i = 0; i < 16; ++i) st->block[i] = 0;
+    st->block_len = 0;
+    st->blocks = 0;
+    st->flags = flags;
+}
+
+void b3_init_iv(b3_state* st, uint flags) {
+    uint iv[8];
+    for (int i = 0; i < 8; ++i) iv[i] = B3_IV[i];
+    b3_init(st, iv, flags);
+}
+
+// A full block is only compressed once the next byte arrives: the last block
+// of the chunk gets the end and root flags
+void b3_update(b3_state* st, uchar byte) {
+    if (st->block_len == 64) {
+        b3_compress(st->cv, st->block, 64, st->flags | (st->blocks == 0 ? B3_CHUNK_START : 0));
+        st->blocks += 1;
+        for (int i = 0; i < 16; ++i) st->block[i] = 0;
+        st->block_len = 0;
+    }
+    st->block[st->block_len / 4] |= (uint)byte << (8 * (st->block_len % 4));
+    st->block_len += 1;
+}
+
+void b3_update_u32(b3_state* st, uint word) {
+    for (int i = 0; i < 4; ++i) b3_update(st, (uchar)(word >> (8 * i)));
+}
+
+// The 32-byte root as eight little-endian words
+void b3_finalize(b3_state* st, uint* out) {
+    uint flags = st->flags | B3_CHUNK_END | B3_ROOT | (st->blocks == 0 ? B3_CHUNK_START : 0);
+    b3_compress(st->cv, st->block, st->block_len, flags);
+    for (int i = 0; i < 8; ++i) out[i] = st->cv[i];
+}
+"#;
+
+/// Seed derivation, Xoshiro128++ and the work_root sampling of `work_hash`, and a
+/// kernel that samples an output left on the device and hashes the samples with
+/// BLAKE3, so only they and the root are read back. Needs `BLAKE3`.
+pub const WORK_ROOT: &str = r#"
+__constant char SAMPLING_CONTEXT[] = "tops-worker work-root sampling v1";
+
+uint xoshiro128pp_next(uint* s) {
+    uint result = rotate(s[0] + s[3], 7u) + s[0];
+    uint t = s[1] << 9;
+    s[2] ^= s[0];
+    s[3] ^= s[1];
+    s[1] ^= s[2];
+    s[0] ^= s[3];
+    s[2] ^= t;
+    s[3] = rotate(s[3], 11u);
+    return result;
+}
+
+// `prng::derive_salted_seed`: the first 16 bytes of BLAKE3(prev_hash || nonce [|| salt])
+void derive_seed(__global const uchar* prev_hash, uint nonce, __global const uchar* salt, int has_salt, uint* seed) {
+    b3_state st;
+    b3_init_iv(&st, 0);
+    for (int i = 0; i < 32; ++i) b3_update(&st, prev_hash[i]);
+    b3_update_u32(&st, nonce);
+    if (has_salt) {
+        for (int i = 0; i < 32; ++i) b3_update(&st, salt[i]);
+    }
+    uint out[8];
+    b3_finalize(&st, out);
+    for (int i = 0; i < 4; ++i) seed[i] = out[i];
+}
+
+// `WorkSampling::Seeded`: a PRNG keyed with derive_key(SAMPLING_CONTEXT, unsalted seed)
+void sampling_seed(__global const uchar* prev_hash, uint nonce, uint* seed) {
+    uint unsalted[4];
+    derive_seed(prev_hash, nonce, prev_hash, 0, unsalted);
+    b3_state st;
+    b3_init_iv(&st, B3_DERIVE_KEY_CONTEXT);
+    for (int i = 0; SAMPLING_CONTEXT[i] != 0; ++i) b3_update(&st, (uchar)SAMPLING_CONTEXT[i]);
+    uint context_key[8];
+    b3_finalize(&st, context_key);
+    b3_init(&st, context_key, B3_DERIVE_KEY_MATERIAL);
+    for (int i = 0; i < 4; ++i) b3_update_u32(&st, unsalted[i]);
+    uint out[8];
+    b3_finalize(&st, out);
+    for (int i = 0; i < 4; ++i) seed[i] = out[i];
+}
+
+// `WorkSampling::sample` of attempt (prev_hash, nonce) from the `total` elements of
+// Y into `out`, and the BLAKE3 work root of the samples
+void sample_and_hash(__global const char* Y, uint total, __global const uchar* prev_hash, uint nonce, int seeded,
+                     uint sample_count, __global char* out, uint* root) {
+    uint s[4];
+    if (seeded) sampling_seed(prev_hash, nonce, s);
+    b3_state st;
+    b3_init_iv(&st, 0);
+    for (uint i = 0; i < sample_count; ++i) {
+        char v = Y[seeded ? xoshiro128pp_next(s) % total : i];
+        out[i] = v;
+        b3_update(&st, (uchar)v);
+    }
+    b3_finalize(&st, root);
+}
+
+// A single work-item: the sampling is one sequential PRNG stream and the hash one chunk
+__kernel void sample_work_root(
+    __global const char* Y,
+    const uint total,
+    __global const uchar* prev_hash, // 32 bytes
+    const uint nonce,
+    const int seeded,                // WorkSampling::Seeded, else the prefix
+    const uint sample_count,
+    __global char* samples,          // sample_count
+    __global uint* root              // 8 words (32 bytes)
+) {
+    uint words[8];
+    sample_and_hash(Y, total, prev_hash, nonce, seeded, sample_count, samples, words);
+    for (int i = 0; i < 8; ++i) root[i] = words[i];
+}
+"#;
+
+/// Persistent-kernel attempts: each work-group walks its share of a nonce range,
+/// doing on the device what the host pipeline does per attempt (seed derivation,
+/// Xoshiro128++ fill, batched GEMM, seeded sampling and the BLAKE3 work root), and
+/// only the roots and their samples come back. Needs `REQUANT`, `BLAKE3` and `WORK_ROOT`.
+pub const PERSISTENT_GEMM: &str = r#"
+// Launched with one work-group per slot of scratch: A, B and Y hold one attempt
+// per group. The attempt's fill, sampling and hashing are sequential and run on
+// the group's first work-item; the GEMM is spread over the whole group.
+__kernel void persistent_gemm_int8_relu_q(
+    __global const uchar* prev_hash, // 32 bytes
+    __global const uchar* salt,      // 32 bytes, read only when has_salt
+    const int has_salt,
+    const uint first_nonce, const uint stride, const uint count,
+    const int M, const int N, const int K, const int batch,
+    const int scale_num, const int scale_den, const int mode,
+    __global char* A,                // groups x batch x M x K scratch
+    __global char* B,                // groups x batch x K x N scratch
+    __global char* Y,                // groups x batch x M x N scratch
+    __global uint* roots,            // count x 8 words (32 bytes)
+    __global char* samples,          // count x sample_count
+    const uint sample_count
+) {
+    uint lid = get_local_id(0);
+    uint lsize = get_local_size(0);
+    size_t group = get_group_id(0);
+    size_t len_a = (size_t)M * K, len_b = (size_t)K * N, len_y = (size_t)M * N;
+    A += group * batch * len_a;
+    B += group * batch * len_b;
+    Y += group * batch * len_y;
+    uint total = (uint)(batch * len_y);
+
+    // Every work-item of a group walks the same slots, so the barriers are uniform
+    for (uint slot = group; slot < count; slot += get_num_groups(0)) {
+        uint nonce = first_nonce + slot * stride;
+        if (lid == 0) {
+            uint s[4];
+            derive_seed(prev_hash, nonce, salt, has_salt, s);
+            // Item by item, A then B, one u32 draw per element
+            for (int item = 0; item < batch; ++item) {
+                for (size_t i = 0; i < len_a; ++i) A[item * len_a + i] = (char)xoshiro128pp_next(s);
+                for (size_t i = 0; i < len_b; ++i) B[item * len_b + i] = (char)xoshiro128pp_next(s);
+            }
+        }
+        barrier(CLK_GLOBAL_MEM_FENCE);
+
+        for (uint idx = lid; idx < total; idx += lsize) {
+            size_t item = idx / len_y;
+            uint row = (uint)((idx % len_y) / N);
+            uint col = (uint)(idx % N);
+            __global const char* a = A + item * len_a + (size_t)row * K;
+            __global const char* b = B + item * len_b + col;
+            int acc = 0;
+            for (int t = 0; t < K; ++t) {
+                acc += (int)a[t] * (int)b[(size_t)t * N];
+            }
+            Y[idx] = requantize(acc, scale_num, scale_den, mode);
+        }
+        barrier(CLK_GLOBAL_MEM_FENCE);
+
+        if (lid == 0) {
+            uint root[8];
+            sample_and_hash(Y, total, prev_hash, nonce, 1, sample_count, samples + (size_t)slot * sample_count, root);
+            for (int i = 0; i < 8; ++i) roots[slot * 8 + i] = root[i];
+        }
+        // The next slot overwrites this one's scratch
+        barrier(CLK_GLOBAL_MEM_FENCE);
+    }
 }
 "#;
@@ -0,0 +1,59 @@
+use std::os::raw::c_int;
+use anyhow::anyhow;
+use ocl::core::ffi::{cl_command_queue, cl_event, cl_mem};
+use ocl::{Buffer, Queue};
+
+/// Deepest K panel one SGEMM may cover while every partial sum of int8 products
+/// (at most 2^14 each) stays an integer float represents exactly (2^24).
+pub const MAX_EXACT_K: usize = 1024;
+
+// CLBlast C API (clblast_c.h)
+const LAYOUT_ROW_MAJOR: c_int = 101;
+const TRANSPOSE_NO: c_int = 111;
+const STATUS_SUCCESS: c_int = 0;
+
+#[link(name = "clblast")]
+extern "C" {
+    #[allow(clippy::too_many_arguments)]
+    fn CLBlastSgemm(
+        layout: c_int, a_transpose: c_int, b_transpose: c_int,
+        m: usize, n: usize, k: usize,
+        alpha: f32,
+        a_buffer: cl_mem, a_offset: usize, a_ld: usize,
+        b_buffer: cl_mem, b_offset: usize, b_ld: usize,
+        beta: f32,
+        c_buffer: cl_mem, c_offset: usize, c_ld: usize,
+        queue: *mut cl_command_queue, event: *mut cl_event,
+    ) -> c_int;
+}
+
+/// Row-major `C = A[.., a_offset..] x B[b_offset..]` on `queue`, where A is read as
+/// `m x k` with row stride `lda` and B as `k x n` with row stride `ldb`.
+/// CLBlast picks its tuned kernel for the device; unsupported devices return an error status.
+#[allow(clippy::too_many_arguments)]
+pub fn sgemm(
+    queue: &Queue,
+    m: usize, n: usize, k: usize,
+    a: &Buffer<f32>, a_offset: usize, lda: usize,
+    b: &Buffer<f32>, b_offset: usize, ldb: usize,
+    c: &Buffer<f32>, ldc: usize,
+) -> anyhow::Result<()> {
+    let mut raw_queue = queue.as_core().as_ptr();
+    let status = unsafe {
+        CLBlastSgemm(
+            LAYOUT_ROW_MAJOR, TRANSPOSE_NO, TRANSPOSE_NO,
+            m, n, k,
+            1.0,
+            a.as_core().as_ptr(), a_offset, lda,
+            b.as_core().as_ptr(), b_offset, ldb,
+            0.0,
+            c.as_core().as_ptr(), 0, ldc,
+            &mut raw_queue, std::ptr::null_mut(),
+        )
+    };
+    if status == STATUS_SUCCESS {
+        Ok(())
+    } else {
+        Err(anyhow!("CLBlastSgemm failed with status {}", status))
+    }
+}
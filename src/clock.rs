@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::{log_info, log_warn};
+
+// Aggregator Date samples the reported offset is the median of
+const DATE_WINDOW: usize = 15;
+// Seconds between 1900 (NTP era 0) and 1970
+const NTP_UNIX_OFFSET_SECS: u64 = 2_208_988_800;
+
+/// Where a clock offset was measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockSource {
+    /// `Date` header of aggregator responses, one-second resolution.
+    Aggregator,
+    /// SNTP query to `CLOCK_NTP_SERVER`.
+    Ntp,
+}
+
+impl std::fmt::Display for ClockSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClockSource::Aggregator => write!(f, "aggregator"),
+            ClockSource::Ntp => write!(f, "ntp"),
+        }
+    }
+}
+
+/// Clock offset reported in /status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClockStatus {
+    /// Reference time minus local time; positive when the local clock is behind.
+    pub offset_ms: Option<i64>,
+    /// How far the true offset may be from `offset_ms`.
+    pub uncertainty_ms: Option<u64>,
+    pub source: Option<ClockSource>,
+    pub measured_at: Option<String>,
+    pub samples: u64,
+    pub max_offset_ms: u64,
+    pub within_threshold: bool,
+    /// Whether receipt timestamps are shifted by `offset_ms` (`CLOCK_CORRECT=1`).
+    pub correcting: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Measurement {
+    offset_ms: i64,
+    uncertainty_ms: u64,
+    source: ClockSource,
+}
+
+#[derive(Default)]
+struct ClockState {
+    dates: VecDeque<(i64, u64)>,
+    ntp: Option<(i64, u64)>,
+    current: Option<Measurement>,
+    measured_at: Option<String>,
+    samples: u64,
+    warned: bool,
+}
+
+/// Local clock against the aggregator and, when configured, an NTP server.
+///
+/// Aggregators refuse receipts whose timestamps are too far off, so the offset
+/// is measured from every HTTP response's `Date` header (median of the last
+/// few, since the header only has whole seconds) and from SNTP queries, which
+/// win when available. Beyond `CLOCK_MAX_OFFSET_MS` a warning is logged; with
+/// `CLOCK_CORRECT=1` receipts are stamped with the corrected time.
+pub struct ClockSync {
+    max_offset_ms: u64,
+    correct: bool,
+    metrics: Option<Arc<PrometheusMetrics>>,
+    state: Mutex<ClockState>,
+}
+
+impl ClockSync {
+    pub fn new(max_offset_ms: u64, correct: bool) -> Self {
+        Self { max_offset_ms, correct, metrics: None, state: Mutex::new(ClockState::default()) }
+    }
+
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self::new(config.clock_max_offset_ms, config.clock_correct)
+    }
+
+    pub fn with_metrics(mut self, metrics: Option<Arc<PrometheusMetrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Record the `Date` of a response to a request sent at `sent_ms` (local Unix ms).
+    pub fn observe_date(&self, headers: &reqwest::header::HeaderMap, sent_ms: i64) {
+        let Some(date) = headers.get(reqwest::header::DATE).and_then(|v| v.to_str().ok()) else { return };
+        let Ok(date) = chrono::DateTime::parse_from_rfc2822(date) else { return };
+        let received_ms = local_ms();
+        // The server's clock read somewhere in [date, date + 1 s) while the request was in flight
+        let offset_ms = date.timestamp_millis() + 500 - (sent_ms + received_ms) / 2;
+        let uncertainty_ms = 500 + (received_ms - sent_ms).max(0) as u64 / 2;
+        let Ok(mut state) = self.state.lock() else { return };
+        state.dates.push_back((offset_ms, uncertainty_ms));
+        if state.dates.len() > DATE_WINDOW {
+            state.dates.pop_front();
+        }
+        self.update(&mut state);
+    }
+
+    /// Record an SNTP measurement.
+    pub fn observe_ntp(&self, offset_ms: i64, round_trip_ms: u64) {
+        let Ok(mut state) = self.state.lock() else { return };
+        state.ntp = Some((offset_ms, round_trip_ms / 2));
+        self.update(&mut state);
+    }
+
+    fn update(&self, state: &mut ClockState) {
+        let measurement = match state.ntp {
+            Some((offset_ms, uncertainty_ms)) => Measurement { offset_ms, uncertainty_ms, source: ClockSource::Ntp },
+            None => {
+                let mut dates: Vec<_> = state.dates.iter().copied().collect();
+                dates.sort_by_key(|&(offset, _)| offset);
+                let (offset_ms, uncertainty_ms) = dates[dates.len() / 2];
+                Measurement { offset_ms, uncertainty_ms, source: ClockSource::Aggregator }
+            }
+        };
+        state.current = Some(measurement);
+        state.measured_at = Some(chrono::Utc::now().to_rfc3339());
+        state.samples += 1;
+        if let Some(metrics) = &self.metrics {
+            metrics.set_clock_offset_ms(measurement.offset_ms);
+        }
+        let beyond = !self.within(measurement.offset_ms);
+        if beyond && !state.warned {
+            log_warn!("[clock] local clock is {} ms {} the {} (±{} ms, threshold {} ms); the aggregator may reject receipt timestamps{}",
+                measurement.offset_ms.abs(), if measurement.offset_ms > 0 { "behind" } else { "ahead of" },
+                measurement.source, measurement.uncertainty_ms, self.max_offset_ms,
+                if self.correct { ", correcting them" } else { " (CLOCK_CORRECT=1 corrects them)" });
+        } else if !beyond && state.warned {
+            log_info!("[clock] local clock back within {} ms of the {} ({} ms)", self.max_offset_ms, measurement.source, measurement.offset_ms);
+        }
+        state.warned = beyond;
+    }
+
+    fn within(&self, offset_ms: i64) -> bool {
+        self.max_offset_ms == 0 || offset_ms.unsigned_abs() <= self.max_offset_ms
+    }
+
+    /// Latest offset (reference minus local) in milliseconds.
+    pub fn offset_ms(&self) -> Option<i64> {
+        self.state.lock().ok().and_then(|s| s.current.map(|m| m.offset_ms))
+    }
+
+    /// Unix time in milliseconds for receipts: local time, plus the offset with `CLOCK_CORRECT=1`.
+    pub fn now_ms(&self) -> i64 {
+        let correction = if self.correct { self.offset_ms().unwrap_or(0) } else { 0 };
+        local_ms() + correction
+    }
+
+    pub fn status(&self) -> ClockStatus {
+        let state = self.state.lock().ok();
+        let current = state.as_ref().and_then(|s| s.current);
+        ClockStatus {
+            offset_ms: current.map(|m| m.offset_ms),
+            uncertainty_ms: current.map(|m| m.uncertainty_ms),
+            source: current.map(|m| m.source),
+            measured_at: state.as_ref().and_then(|s| s.measured_at.clone()),
+            samples: state.as_ref().map(|s| s.samples).unwrap_or(0),
+            max_offset_ms: self.max_offset_ms,
+            within_threshold: current.is_none_or(|m| self.within(m.offset_ms)),
+            correcting: self.correct,
+        }
+    }
+}
+
+fn local_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// Query `server` (`host` or `host:port`, port 123 by default) over SNTP;
+/// returns the offset (server minus local) and the round trip, in milliseconds.
+pub async fn sntp_offset(server: &str, timeout: Duration) -> anyhow::Result<(i64, u64)> {
+    let target = if server.contains(':') { server.to_string() } else { format!("{}:123", server) };
+    let addr = tokio::net::lookup_host(&target).await?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("{} does not resolve", target))?;
+    let socket = tokio::net::UdpSocket::bind(if addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" }).await?;
+    socket.connect(addr).await?;
+
+    // LI 0, version 4, mode 3 (client); our transmit time comes back as the originate time
+    let mut request = [0u8; 48];
+    request[0] = 0x23;
+    let t1 = local_ms();
+    let stamp = to_ntp(t1);
+    request[40..48].copy_from_slice(&stamp);
+    socket.send(&request).await?;
+    let mut reply = [0u8; 48];
+    let len = tokio::time::timeout(timeout, socket.recv(&mut reply)).await
+        .map_err(|_| anyhow::anyhow!("no answer from {} within {:?}", target, timeout))??;
+    let t4 = local_ms();
+
+    if len < 48 || reply[0] & 0x07 != 4 || reply[1] == 0 || reply[24..32] != stamp {
+        anyhow::bail!("{} sent an invalid or unsynchronized SNTP reply", target);
+    }
+    let t2 = from_ntp(&reply[32..40]);
+    let t3 = from_ntp(&reply[40..48]);
+    let offset_ms = ((t2 - t1) + (t3 - t4)) / 2;
+    let round_trip_ms = ((t4 - t1) - (t3 - t2)).max(0) as u64;
+    Ok((offset_ms, round_trip_ms))
+}
+
+fn to_ntp(unix_ms: i64) -> [u8; 8] {
+    let ms = unix_ms.max(0) as u64;
+    let secs = (ms / 1000 + NTP_UNIX_OFFSET_SECS) as u32;
+    let frac = (((ms % 1000) << 32) / 1000) as u32;
+    let mut out = [0u8; 8];
+    out[..4].copy_from_slice(&secs.to_be_bytes());
+    out[4..].copy_from_slice(&frac.to_be_bytes());
+    out
+}
+
+fn from_ntp(bytes: &[u8]) -> i64 {
+    let secs = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64;
+    let frac = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as u64;
+    (secs as i64 - NTP_UNIX_OFFSET_SECS as i64) * 1000 + ((frac * 1000) >> 32) as i64
+}
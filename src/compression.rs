@@ -0,0 +1,143 @@
+use std::io::Write;
+use reqwest::header::{HeaderMap, ACCEPT_ENCODING};
+use serde::{Deserialize, Serialize};
+
+/// Content-Encoding applied to submission bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncoding {
+    Identity,
+    Gzip,
+    Zstd,
+}
+
+impl std::str::FromStr for ContentEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "identity" => Ok(ContentEncoding::Identity),
+            "gzip" => Ok(ContentEncoding::Gzip),
+            "zstd" => Ok(ContentEncoding::Zstd),
+            other => Err(format!("unknown content encoding '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for ContentEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContentEncoding::Identity => write!(f, "identity"),
+            ContentEncoding::Gzip => write!(f, "gzip"),
+            ContentEncoding::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// How the HTTP submitter compresses receipt bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionMode {
+    /// Never compress.
+    Off,
+    /// Use the best encoding the aggregator advertises in `Accept-Encoding` (zstd, then gzip).
+    Auto,
+    /// Always gzip, for aggregators known to accept it without advertising.
+    Gzip,
+    /// Always zstd, for aggregators known to accept it without advertising.
+    Zstd,
+}
+
+impl std::str::FromStr for CompressionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(CompressionMode::Off),
+            "auto" => Ok(CompressionMode::Auto),
+            "gzip" => Ok(CompressionMode::Gzip),
+            "zstd" => Ok(CompressionMode::Zstd),
+            other => Err(format!("unknown compression mode '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for CompressionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressionMode::Off => write!(f, "off"),
+            CompressionMode::Auto => write!(f, "auto"),
+            CompressionMode::Gzip => write!(f, "gzip"),
+            CompressionMode::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+impl CompressionMode {
+    /// Encoding to use for a body given what the endpoint accepts.
+    pub fn choose(&self, accepted: &[ContentEncoding]) -> ContentEncoding {
+        match self {
+            CompressionMode::Off => ContentEncoding::Identity,
+            CompressionMode::Gzip => ContentEncoding::Gzip,
+            CompressionMode::Zstd => ContentEncoding::Zstd,
+            CompressionMode::Auto => [ContentEncoding::Zstd, ContentEncoding::Gzip]
+                .into_iter()
+                .find(|e| accepted.contains(e))
+                .unwrap_or(ContentEncoding::Identity),
+        }
+    }
+}
+
+/// Size of a compressed submission body before and after encoding.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionStats {
+    pub encoding: ContentEncoding,
+    pub original_bytes: usize,
+    pub compressed_bytes: usize,
+}
+
+impl CompressionStats {
+    pub fn bytes_saved(&self) -> u64 {
+        self.original_bytes.saturating_sub(self.compressed_bytes) as u64
+    }
+}
+
+/// Encode `body`; `Identity` returns it unchanged.
+pub fn compress(encoding: ContentEncoding, body: &[u8]) -> std::io::Result<Vec<u8>> {
+    match encoding {
+        ContentEncoding::Identity => Ok(body.to_vec()),
+        ContentEncoding::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+        ContentEncoding::Zstd => zstd::encode_all(body, 0),
+    }
+}
+
+/// Decode a body sent with `encoding`.
+pub fn decompress(encoding: ContentEncoding, body: &[u8]) -> std::io::Result<Vec<u8>> {
+    match encoding {
+        ContentEncoding::Identity => Ok(body.to_vec()),
+        ContentEncoding::Gzip => {
+            let mut decoded = Vec::new();
+            std::io::Read::read_to_end(&mut flate2::read::GzDecoder::new(body), &mut decoded)?;
+            Ok(decoded)
+        }
+        ContentEncoding::Zstd => zstd::decode_all(body),
+    }
+}
+
+/// Encodings listed in an `Accept-Encoding` header with a non-zero q-value.
+pub fn parse_accept_encoding(headers: &HeaderMap) -> Option<Vec<ContentEncoding>> {
+    let value = headers.get(ACCEPT_ENCODING)?.to_str().ok()?;
+    Some(value
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let encoding = parts.next()?.trim().to_ascii_lowercase().parse().ok()?;
+            let refused = parts.any(|p| matches!(p.trim().strip_prefix("q="), Some(q) if q.parse::<f32>().is_ok_and(|q| q == 0.0)));
+            (!refused).then_some(encoding)
+        })
+        .collect())
+}
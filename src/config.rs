@@ -2,6 +2,25 @@ use std::env;
 use std::time::Duration;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use crate::endpoints::EndpointMode;
+use crate::selftest::SelfTestPolicy;
+use crate::power::PowerStaleAction;
+use crate::submit::{AggregatorProtocol, ReceiptWireFormat};
+use crate::compression::CompressionMode;
+use crate::dns::{parse_overrides, ResolveOverride};
+use crate::log_warn;
+use crate::net::{HttpVersion, IpFamily, TlsBackend};
+use crate::types::{parse_scale, Activation, Overflow, RequantParams, Rounding};
+use crate::workload::{Workload, WorkloadKind};
+use crate::memhard::{MemHardParams, MEMHARD_MAX_KIB, MEMHARD_MAX_PASSES};
+use crate::identity::{parse_identities, IdentitySpec, KeyRef};
+use crate::limits::IoPriority;
+use crate::pacing::PacingTarget;
+use crate::tariff::TariffSchedule;
+use crate::energy::EnergyMeterKind;
+use crate::lifecycle::LogFormat;
+use crate::work_hash::WorkSampling;
+use crate::export::ExportFormat;
 
 #[derive(Error, Debug)]
 pub enum ConfigError {
@@ -11,6 +30,8 @@ pub enum ConfigError {
     InvalidEnvVar(String, String),
     #[error("Configuration validation failed: {0}")]
     ValidationError(String),
+    #[error("Cannot read config file {0}: {1}")]
+    FileError(String, String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,30 +40,294 @@ pub struct Config {
     pub worker_sk_hex: String,
     pub device_did: String,
     pub aggregator_url: String,
+    // Signing domain of receipts (NETWORK_ID), e.g. peaq-mainnet
+    pub network_id: Option<String>,
+    
+    // peaq DID binding
+    pub did_key_seed_hex: Option<String>,
+    pub peaq_rpc_url: Option<String>,
+    pub did_key_attribute: String,
+    pub did_verify_required: bool,
+    
+    // Extra signing identities (WORKER_IDENTITIES); empty means DEVICE_DID only
+    pub identities: Vec<IdentitySpec>,
+    // Compute and report an estimate only: no keys are loaded, nothing is signed or submitted
+    pub watch_only: bool,
+    
+    // Aggregator endpoints (AGGREGATOR_URL may be a comma-separated list)
+    pub aggregator_urls: Vec<String>,
+    pub aggregator_mode: EndpointMode,
+    pub aggregator_failover_threshold: u32,
+    pub aggregator_failover_cooldown_secs: u64,
+    // Submission circuit breaker: consecutive failures to open it, wait before the canary
+    pub circuit_failure_threshold: u32,
+    pub circuit_recovery_timeout_secs: u64,
+    // Second aggregator that gets a best-effort copy of every receipt, its kill switch and in-flight cap
+    pub shadow_aggregator_url: Option<String>,
+    pub shadow_enabled: bool,
+    pub shadow_max_in_flight: usize,
+    // Aggregator key that must sign epoch descriptors and receipt verdicts (hex SEC1)
+    pub aggregator_pubkey: Option<String>,
+    // Signed config documents pulled from a fleet management endpoint, and how often
+    pub fleet_config_url: Option<String>,
+    pub fleet_config_pubkey: Option<String>,
+    pub fleet_config_poll_secs: u64,
+    // Signed release manifest checked for newer versions, how often, which artifact
+    // this build takes and whether it is downloaded for the supervisor to swap in
+    pub update_manifest_url: Option<String>,
+    pub update_pubkey: Option<String>,
+    pub update_check_secs: u64,
+    pub update_target: String,
+    pub update_download: bool,
+    
+    // Outbound network path to the aggregator (proxy, local bind)
+    pub aggregator_proxy: Option<String>,
+    pub aggregator_bind_address: Option<std::net::IpAddr>,
+    pub aggregator_bind_interface: Option<String>,
+    pub aggregator_ip_family: IpFamily,
+    // Aggregator name resolution: static host:ip answers, cache lifetime, how long a
+    // previous answer stands in for a failed lookup, and dual-stack connection racing
+    pub aggregator_resolve: Vec<ResolveOverride>,
+    pub aggregator_dns_ttl_secs: u64,
+    pub aggregator_dns_stale_secs: u64,
+    pub aggregator_happy_eyeballs: bool,
+    // Aggregator HTTP client: protocol, TLS stack, connection pool, keep-alive and timeouts
+    pub aggregator_http_version: HttpVersion,
+    pub aggregator_tls: TlsBackend,
+    pub aggregator_pool_max_idle: usize,
+    pub aggregator_pool_idle_timeout_secs: u64,
+    pub aggregator_keepalive_secs: u64,
+    pub aggregator_connect_timeout_secs: u64,
+    pub aggregator_request_timeout_secs: u64,
+    
+    // Receipt transport (AGGREGATOR_PROTOCOL) and its settings
+    pub aggregator_protocol: AggregatorProtocol,
+    pub state_dir: String,
+    pub mqtt_url: Option<String>,
+    pub mqtt_topic: String,
+    pub mqtt_client_id: Option<String>,
+    pub mqtt_username: Option<String>,
+    pub mqtt_password: Option<String>,
+    pub mqtt_ca_file: Option<String>,
+    pub mqtt_client_cert_file: Option<String>,
+    pub mqtt_client_key_file: Option<String>,
+    pub grpc_url: Option<String>,
+    pub grpc_deadline_ms: u64,
+    
+    // Highest receipt schema version to negotiate with aggregators
+    pub receipt_version_max: u16,
+    
+    // HTTP request body compression
+    pub submit_compression: CompressionMode,
+    pub submit_compression_min_bytes: usize,
+    
+    // Body format of HTTP receipt submissions
+    pub receipt_wire_format: ReceiptWireFormat,
+    
+    // Recently delivered receipt keys remembered to suppress resends (0 disables)
+    pub idempotency_cache_size: usize,
+    
+    // Aggregator liveness challenges held until answered (0 ignores them)
+    pub challenge_queue_max: usize,
     
     // Performance tuning
     pub autotune_target_ms: u64,
     pub autotune_presets: Vec<String>,
     pub autotune_disable: bool,
+    pub autotune_retune_drift_pct: u32,
+    // Largest batch count autotune may scale GEMM attempts to (1 keeps every attempt unbatched)
+    pub autotune_max_batch: usize,
+    // GPU busy percentage at which batch scaling stops
+    pub autotune_batch_utilization_pct: u32,
+    pub min_tops_seconds: Option<f64>,
+    pub pipeline_depth: usize,
+    pub attempts_in_flight: usize,
+    // Nonces per persistent-kernel launch, whose roots are verified on the host (0 keeps one launch per attempt)
+    pub persistent_kernel_range: u32,
+    // Pick and hash the work_root samples on the device instead of reading every output back
+    pub device_sampling: bool,
+    // Run one more attempt stream on the CPU next to the GPU streams
+    pub hybrid_cpu: bool,
+    pub warmup_attempts: u32,
+    pub warmup_secs: u64,
+    
+    // Workload
+    pub workload_kind: WorkloadKind,
+    pub spmm_density: f64,
+    pub memhard_kib: u32,
+    pub memhard_passes: u32,
+    /// Largest memory-hard buffer an epoch descriptor may ask for, in KiB.
+    pub memhard_max_kib: u32,
+    /// Requantization scale `(num, den)`; unset derives it from the epoch salt.
+    pub requant_scale: Option<(i32, i32)>,
+    pub activation: Option<Activation>,
+    /// Rounding of the scaled accumulator and int8 overflow; unset truncates and saturates.
+    pub requant_rounding: Option<Rounding>,
+    pub requant_overflow: Option<Overflow>,
     
     // OpenCL tuning
     pub wg_m: Option<u32>,
     pub wg_n: Option<u32>,
     pub tk: Option<u32>,
+    pub opencl_program_cache: bool,
+    
+    // CUDA cuBLASLt algorithm tuning
+    pub cuda_algo_tuning: bool,
+    pub cuda_algo_candidates: usize,
+    
+    // Correctness self-test against the CPU reference
+    pub selftest_enabled: bool,
+    pub selftest_interval: u32,
+    pub selftest_policy: SelfTestPolicy,
+    // Output elements of every attempt recomputed on the CPU (0 disables)
+    pub spotcheck_elements: usize,
+    // Largest gap between the wall-clock and device kernel times, in percent, before a receipt is flagged
+    pub timing_drift_pct: f64,
+    // Power sensor attempts are metered with, how often it is sampled, and whether receipts carry the estimate
+    pub energy_meter: EnergyMeterKind,
+    pub energy_sample_ms: u64,
+    pub receipt_energy_estimate: bool,
+    // Streams, pipeline depth, thermal and power state recorded in receipts
+    pub receipt_perf_context: bool,
+    // Outputs hashed into the work_root: drawn across Y by a commitment to all of it, by (prev_hash, nonce) alone, or the first ones for current aggregators
+    pub work_root_sampling: WorkSampling,
+    
+    // Audit evidence: full outputs of sampled attempts
+    pub evidence_sample_rate: u32,
+    pub evidence_max_mb: u64,
+    // Journal of every attempt for `tops-worker replay`, and its size before rotation
+    pub attempt_journal: bool,
+    pub attempt_journal_max_mb: u64,
+    // Receipt export for offline analytics (unset disables), its directory, and retention
+    pub export_format: Option<ExportFormat>,
+    pub export_dir: Option<String>,
+    pub export_retention_days: u32,
+    pub export_max_mb: u64,
+    // Generated matrices kept for verification and cross-checks (0 disables)
+    pub matrix_cache_max_mb: u64,
+    
+    // Hourly statistics in SQLite (`stats` feature)
+    pub stats_enabled: bool,
+    pub stats_retention_days: u32,
+    
+    // Signing key rotation: poll of file: keys, and the admin endpoint token
+    pub key_rotation_poll_secs: u64,
+    pub admin_token: Option<String>,
+    
+    // Remote signing: the signer compute nodes send receipts to, and the bearer token they send it;
+    // in the signer role (`tops-worker signer`), its listen address and the clients it serves
+    pub remote_signer_url: Option<String>,
+    pub remote_signer_token: Option<String>,
+    pub signer_listen: String,
+    pub signer_allowlist: Vec<String>,
+    
+    // HMAC seals on the queues, journal and algorithm cache; the key is derived from the
+    // signing key unless STATE_KEY_HEX sets one
+    pub state_integrity: bool,
+    pub state_key_hex: Option<String>,
+    /// UNIX socket serving the health and admin endpoints, guarded by its file mode.
+    pub control_socket: Option<String>,
+    pub control_socket_mode: u32,
+    
+    // Back-pressure from the submission backlog: slow from, pause at and resume below these depths
+    pub backpressure_slow_at: usize,
+    pub backpressure_pause_at: usize,
+    pub backpressure_resume_at: usize,
+    pub backpressure_max_delay_ms: u64,
+    
+    // Clock sanity: optional NTP server and its poll, the offset worth a warning, and
+    // whether receipt timestamps are corrected by the measured offset
+    pub clock_ntp_server: Option<String>,
+    pub clock_check_interval_secs: u64,
+    pub clock_max_offset_ms: u64,
+    pub clock_correct: bool,
+    
+    // Graceful shutdown (SIGTERM, POST /admin/restart)
+    pub drain_timeout_secs: u64,
+    
+    // Epoch feed: HTTP descriptor URL and how often the transport is asked for the epoch
+    pub epoch_url: Option<String>,
+    pub epoch_poll_secs: u64,
+    // Where the signed end-of-epoch summary is POSTed; unset sends none
+    pub epoch_summary_url: Option<String>,
+    
+    // Resource limits for shared hosts (CPU fallback)
+    pub cpu_threads: usize,
+    pub worker_nice: Option<i32>,
+    pub worker_ionice: Option<IoPriority>,
+    pub cpu_cgroup: Option<String>,
+    pub cpu_max_cores: Option<f64>,
     
     // Monitoring and logging
     pub worker_debug_receipt: bool,
     pub log_level: String,
+    // Text banner, or JSON startup/shutdown events for log pipelines
+    pub log_format: LogFormat,
+    // Recent log lines kept in memory for /logs (0 keeps none)
+    pub log_buffer_size: usize,
     pub metrics_enabled: bool,
+    pub main_loop_stall_secs: u64,
+    pub main_loop_stall_restart: bool,
+    pub liveness_url: Option<String>,
+    pub liveness_interval_secs: u64,
+    pub liveness_max_backoff_secs: u64,
+    // Prometheus Pushgateway for devices that cannot be scraped; unset disables pushing
+    pub metrics_push_url: Option<String>,
+    pub metrics_push_interval_secs: u64,
+    pub metrics_push_job: String,
+    pub enroll_url: Option<String>,
+    pub enroll_sustained_secs: u64,
+    // Enroll only after `tops-worker stress` passed on this device
+    pub enroll_require_stress: bool,
+    pub quarantine_max_entries: usize,
     
     // Error handling and recovery
     pub max_retries: u32,
     pub retry_delay_ms: u64,
     pub health_check_interval_ms: u64,
     
+    // Health classification thresholds (see `HealthPolicy`)
+    pub health_degraded_consecutive_failures: u32,
+    pub health_unhealthy_consecutive_failures: u32,
+    pub health_critical_consecutive_failures: u32,
+    pub health_failure_window: usize,
+    pub health_degraded_failure_rate: f64,
+    pub health_unhealthy_failure_rate: f64,
+    pub health_stale_success_secs: u64,
+    pub health_degraded_gpu_temp_c: Option<f64>,
+    pub health_unhealthy_gpu_temp_c: Option<f64>,
+    
+    /// Pause between attempts: a fixed delay, a receipts/attempts rate, or none.
+    pub pacing: PacingTarget,
+    /// Autotune target and duty cycle by time of day, for time-of-use power tariffs.
+    pub tariff_schedule: TariffSchedule,
+    
+    // Fleet desynchronization: random pause jitter, a per-device startup delay,
+    // and the fleet size that spreads backlog flushes
+    pub submit_jitter_ms: u64,
+    pub startup_jitter_ms: u64,
+    pub fleet_size: u32,
+    
     // Security
     pub rate_limit_per_second: u32,
     pub max_concurrent_requests: u32,
+    
+    // Adaptive rate control (AIMD on 429/503)
+    pub rate_limit_min_per_second: f64,
+    pub rate_increase_step: f64,
+    pub rate_decrease_factor: f64,
+    
+    // Solar/price-aware power policy (disabled unless POWER_SIGNAL_URL is set)
+    pub power_signal_url: Option<String>,
+    pub power_poll_interval_secs: u64,
+    pub power_pause_watts: f64,
+    pub power_resume_watts: f64,
+    pub power_full_watts: f64,
+    pub power_max_price: Option<f64>,
+    pub power_resume_price: Option<f64>,
+    pub power_min_duty: f64,
+    pub power_stale_secs: u64,
+    pub power_stale_action: PowerStaleAction,
 }
 
 impl Default for Config {
@@ -51,6 +336,67 @@ impl Default for Config {
             worker_sk_hex: String::new(),
             device_did: "did:peaq:DEVICE123".to_string(),
             aggregator_url: "http://localhost:8081/verify".to_string(),
+            network_id: None,
+            
+            did_key_seed_hex: None,
+            peaq_rpc_url: None,
+            did_key_attribute: "tops-worker-key".to_string(),
+            did_verify_required: false,
+            
+            identities: Vec::new(),
+            watch_only: false,
+            
+            aggregator_urls: vec!["http://localhost:8081/verify".to_string()],
+            aggregator_mode: EndpointMode::PrimaryBackup,
+            aggregator_failover_threshold: 3,
+            aggregator_failover_cooldown_secs: 30,
+            circuit_failure_threshold: 5,
+            circuit_recovery_timeout_secs: 60,
+            shadow_aggregator_url: None,
+            shadow_enabled: true,
+            shadow_max_in_flight: 16,
+            aggregator_pubkey: None,
+            fleet_config_url: None,
+            fleet_config_pubkey: None,
+            fleet_config_poll_secs: 300,
+            update_manifest_url: None,
+            update_pubkey: None,
+            update_check_secs: 21600,
+            update_target: format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS),
+            update_download: false,
+            aggregator_proxy: None,
+            aggregator_bind_address: None,
+            aggregator_bind_interface: None,
+            aggregator_ip_family: IpFamily::Any,
+            aggregator_resolve: Vec::new(),
+            aggregator_dns_ttl_secs: 60,
+            aggregator_dns_stale_secs: 3600,
+            aggregator_happy_eyeballs: true,
+            aggregator_http_version: HttpVersion::Auto,
+            aggregator_tls: TlsBackend::Rustls,
+            aggregator_pool_max_idle: 8,
+            aggregator_pool_idle_timeout_secs: 90,
+            aggregator_keepalive_secs: 30,
+            aggregator_connect_timeout_secs: 10,
+            aggregator_request_timeout_secs: 30,
+            aggregator_protocol: AggregatorProtocol::Http,
+            state_dir: "state".to_string(),
+            mqtt_url: None,
+            mqtt_topic: "tops/receipts".to_string(),
+            mqtt_client_id: None,
+            mqtt_username: None,
+            mqtt_password: None,
+            mqtt_ca_file: None,
+            mqtt_client_cert_file: None,
+            mqtt_client_key_file: None,
+            grpc_url: None,
+            grpc_deadline_ms: 5000,
+            receipt_version_max: crate::types::RECEIPT_VERSION_V2,
+            submit_compression: CompressionMode::Auto,
+            submit_compression_min_bytes: 512,
+            receipt_wire_format: ReceiptWireFormat::Default,
+            idempotency_cache_size: 4096,
+            challenge_queue_max: 8,
             
             autotune_target_ms: 300,
             autotune_presets: vec![
@@ -58,139 +404,1729 @@ impl Default for Config {
                 "1024,1024,1024".to_string(),
             ],
             autotune_disable: false,
+            autotune_retune_drift_pct: 30,
+            autotune_max_batch: 1,
+            autotune_batch_utilization_pct: 90,
+            min_tops_seconds: None,
+            pipeline_depth: 2,
+            attempts_in_flight: 1,
+            persistent_kernel_range: 0,
+            device_sampling: false,
+            hybrid_cpu: false,
+            warmup_attempts: 3,
+            warmup_secs: 0,
+            
+            workload_kind: WorkloadKind::Gemm,
+            spmm_density: 0.1,
+            memhard_kib: 0,
+            requant_scale: None,
+            activation: None,
+            requant_rounding: None,
+            requant_overflow: None,
+            memhard_passes: 1,
+            memhard_max_kib: 1024 * 1024,
             
             wg_m: None,
             wg_n: None,
             tk: None,
+            opencl_program_cache: true,
+            
+            cuda_algo_tuning: true,
+            cuda_algo_candidates: 8,
+            
+            selftest_enabled: true,
+            selftest_interval: 1000,
+            selftest_policy: SelfTestPolicy::Refuse,
+            spotcheck_elements: 16,
+            timing_drift_pct: 50.0,
+            energy_meter: EnergyMeterKind::Auto,
+            energy_sample_ms: 100,
+            receipt_energy_estimate: false,
+            receipt_perf_context: false,
+            work_root_sampling: WorkSampling::Committed,
+            evidence_sample_rate: 0,
+            evidence_max_mb: 1024,
+            attempt_journal: false,
+            attempt_journal_max_mb: 64,
+            export_format: None,
+            export_dir: None,
+            export_retention_days: 30,
+            export_max_mb: 1024,
+            matrix_cache_max_mb: 0,
+            stats_enabled: false,
+            stats_retention_days: 90,
+            key_rotation_poll_secs: 30,
+            admin_token: None,
+            remote_signer_url: None,
+            remote_signer_token: None,
+            signer_listen: "127.0.0.1:8090".to_string(),
+            signer_allowlist: Vec::new(),
+            state_integrity: false,
+            state_key_hex: None,
+            control_socket: None,
+            control_socket_mode: 0o600,
+            backpressure_slow_at: 100,
+            backpressure_pause_at: 1000,
+            backpressure_resume_at: 250,
+            backpressure_max_delay_ms: 10000,
+            clock_ntp_server: None,
+            clock_check_interval_secs: 900,
+            clock_max_offset_ms: 2000,
+            clock_correct: false,
+            drain_timeout_secs: 30,
+            epoch_url: None,
+            epoch_poll_secs: 60,
+            epoch_summary_url: None,
+            cpu_threads: 0,
+            worker_nice: None,
+            worker_ionice: None,
+            cpu_cgroup: None,
+            cpu_max_cores: None,
             
             worker_debug_receipt: false,
             log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+            log_buffer_size: 1000,
             metrics_enabled: true,
+            main_loop_stall_secs: 300,
+            main_loop_stall_restart: false,
+            liveness_url: None,
+            enroll_url: None,
+            enroll_sustained_secs: 60,
+            enroll_require_stress: false,
+            quarantine_max_entries: 10000,
+            liveness_interval_secs: 60,
+            liveness_max_backoff_secs: 300,
+            metrics_push_url: None,
+            metrics_push_interval_secs: 30,
+            metrics_push_job: "tops-worker".to_string(),
             
             max_retries: 3,
             retry_delay_ms: 1000,
             health_check_interval_ms: 30000,
+            health_degraded_consecutive_failures: 2,
+            health_unhealthy_consecutive_failures: 5,
+            health_critical_consecutive_failures: 10,
+            health_failure_window: 100,
+            health_degraded_failure_rate: 0.2,
+            health_unhealthy_failure_rate: 0.5,
+            health_stale_success_secs: 0,
+            health_degraded_gpu_temp_c: None,
+            health_unhealthy_gpu_temp_c: None,
             
+            pacing: PacingTarget::DEFAULT,
+            tariff_schedule: TariffSchedule::default(),
+            submit_jitter_ms: 0,
+            startup_jitter_ms: 0,
+            fleet_size: 1,
             rate_limit_per_second: 10,
             max_concurrent_requests: 5,
+            
+            rate_limit_min_per_second: 0.1,
+            rate_increase_step: 0.1,
+            rate_decrease_factor: 0.5,
+            power_signal_url: None,
+            power_poll_interval_secs: 30,
+            power_pause_watts: 50.0,
+            power_resume_watts: 100.0,
+            power_full_watts: 300.0,
+            power_max_price: None,
+            power_resume_price: None,
+            power_min_duty: 0.1,
+            power_stale_secs: 300,
+            power_stale_action: PowerStaleAction::Run,
         }
     }
 }
 
 impl Config {
+    /// The environment, falling back to the settings of `CONFIG_FILE` (tops-worker.toml) when set.
     pub fn from_env() -> Result<Self, ConfigError> {
-        let mut config = Config::default();
-        
-        // Required configuration
-        config.worker_sk_hex = env::var("WORKER_SK_HEX")
-            .map_err(|_| ConfigError::MissingEnvVar("WORKER_SK_HEX".to_string()))?;
+        Self::from_lookup(crate::config_file::env_lookup()?)
+    }
+
+    /// `from_env` over any source of variables, e.g. the environment overlaid with a
+    /// fleet config document.
+    pub fn from_lookup(var: impl Fn(&str) -> Result<String, env::VarError>) -> Result<Self, ConfigError> {
+        // Required configuration (unless keys come from a DID seed, WORKER_IDENTITIES or a remote signer, or none are needed)
+        let watch_only = var("WATCH_ONLY").is_ok_and(|val| val == "1");
+        let remote_signer_url = var("REMOTE_SIGNER_URL").ok();
+        let did_key_seed_hex = var("DID_KEY_SEED_HEX").ok();
+        let identities = match var("WORKER_IDENTITIES") {
+            Ok(val) => parse_identities(&val)
+                .map_err(|_| ConfigError::InvalidEnvVar("WORKER_IDENTITIES".to_string(), val))?,
+            Err(_) => Vec::new(),
+        };
+        let worker_sk_hex = match var("WORKER_SK_HEX") {
+            Ok(val) => val,
+            Err(_) if watch_only || did_key_seed_hex.is_some() || !identities.is_empty() || remote_signer_url.is_some() => String::new(),
+            Err(_) => return Err(ConfigError::MissingEnvVar("WORKER_SK_HEX".to_string())),
+        };
+        let mut config = Config {
+            worker_sk_hex,
+            did_key_seed_hex,
+            identities,
+            watch_only,
+            remote_signer_url,
+            ..Config::default()
+        };
         
         // Optional configuration with defaults
-        if let Ok(val) = env::var("DEVICE_DID") {
+        if let Ok(val) = var("DEVICE_DID") {
             config.device_did = val;
         }
         
-        if let Ok(val) = env::var("AGGREGATOR_URL") {
-            config.aggregator_url = val;
+        if let Ok(val) = var("NETWORK_ID") {
+            config.network_id = Some(val);
+        }
+        
+        if let Ok(val) = var("PEAQ_RPC_URL") {
+            config.peaq_rpc_url = Some(val);
+        }
+        
+        if let Ok(val) = var("DID_KEY_ATTRIBUTE") {
+            config.did_key_attribute = val;
+        }
+        
+        if let Ok(val) = var("DID_VERIFY_REQUIRED") {
+            config.did_verify_required = val == "1";
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_URL") {
+            let urls: Vec<String> = val.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+            if urls.is_empty() {
+                return Err(ConfigError::InvalidEnvVar("AGGREGATOR_URL".to_string(), val));
+            }
+            config.aggregator_url = urls[0].clone();
+            config.aggregator_urls = urls;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_MODE") {
+            config.aggregator_mode = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_MODE".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_FAILOVER_THRESHOLD") {
+            config.aggregator_failover_threshold = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_FAILOVER_THRESHOLD".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_FAILOVER_COOLDOWN_SECS") {
+            config.aggregator_failover_cooldown_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_FAILOVER_COOLDOWN_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("CIRCUIT_FAILURE_THRESHOLD") {
+            config.circuit_failure_threshold = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("CIRCUIT_FAILURE_THRESHOLD".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("CIRCUIT_RECOVERY_TIMEOUT_SECS") {
+            config.circuit_recovery_timeout_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("CIRCUIT_RECOVERY_TIMEOUT_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("SHADOW_AGGREGATOR_URL") {
+            config.shadow_aggregator_url = Some(val);
+        }
+        
+        if let Ok(val) = var("SHADOW_ENABLED") {
+            config.shadow_enabled = val == "1";
+        }
+        
+        if let Ok(val) = var("SHADOW_MAX_IN_FLIGHT") {
+            config.shadow_max_in_flight = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("SHADOW_MAX_IN_FLIGHT".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_PUBKEY") {
+            config.aggregator_pubkey = Some(val);
+        }
+        
+        if let Ok(val) = var("FLEET_CONFIG_URL") {
+            config.fleet_config_url = Some(val);
+        }
+        
+        if let Ok(val) = var("FLEET_CONFIG_PUBKEY") {
+            config.fleet_config_pubkey = Some(val);
+        }
+        
+        if let Ok(val) = var("FLEET_CONFIG_POLL_SECS") {
+            config.fleet_config_poll_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("FLEET_CONFIG_POLL_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("UPDATE_MANIFEST_URL") {
+            config.update_manifest_url = Some(val);
+        }
+        
+        if let Ok(val) = var("UPDATE_PUBKEY") {
+            config.update_pubkey = Some(val);
+        }
+        
+        if let Ok(val) = var("UPDATE_CHECK_SECS") {
+            config.update_check_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("UPDATE_CHECK_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("UPDATE_TARGET") {
+            config.update_target = val;
+        }
+        
+        if let Ok(val) = var("UPDATE_DOWNLOAD") {
+            config.update_download = val == "1";
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_PROXY") {
+            config.aggregator_proxy = Some(val);
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_BIND_ADDRESS") {
+            config.aggregator_bind_address = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_BIND_ADDRESS".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_BIND_INTERFACE") {
+            config.aggregator_bind_interface = Some(val);
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_IP_FAMILY") {
+            config.aggregator_ip_family = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_IP_FAMILY".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_RESOLVE") {
+            config.aggregator_resolve = parse_overrides(&val)
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_RESOLVE".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_DNS_TTL_SECS") {
+            config.aggregator_dns_ttl_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_DNS_TTL_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_DNS_STALE_SECS") {
+            config.aggregator_dns_stale_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_DNS_STALE_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_HAPPY_EYEBALLS") {
+            config.aggregator_happy_eyeballs = val == "1";
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_HTTP_VERSION") {
+            config.aggregator_http_version = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_HTTP_VERSION".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_TLS") {
+            config.aggregator_tls = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_TLS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_POOL_MAX_IDLE") {
+            config.aggregator_pool_max_idle = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_POOL_MAX_IDLE".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_POOL_IDLE_TIMEOUT_SECS") {
+            config.aggregator_pool_idle_timeout_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_POOL_IDLE_TIMEOUT_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_KEEPALIVE_SECS") {
+            config.aggregator_keepalive_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_KEEPALIVE_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_CONNECT_TIMEOUT_SECS") {
+            config.aggregator_connect_timeout_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_CONNECT_TIMEOUT_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_REQUEST_TIMEOUT_SECS") {
+            config.aggregator_request_timeout_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_REQUEST_TIMEOUT_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AGGREGATOR_PROTOCOL") {
+            config.aggregator_protocol = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AGGREGATOR_PROTOCOL".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("STATE_DIR") {
+            config.state_dir = val;
+        }
+        
+        if let Ok(val) = var("MQTT_URL") {
+            config.mqtt_url = Some(val);
+        }
+        
+        if let Ok(val) = var("MQTT_TOPIC") {
+            config.mqtt_topic = val;
+        }
+        
+        if let Ok(val) = var("MQTT_CLIENT_ID") {
+            config.mqtt_client_id = Some(val);
+        }
+        
+        if let Ok(val) = var("MQTT_USERNAME") {
+            config.mqtt_username = Some(val);
+        }
+        
+        if let Ok(val) = var("MQTT_PASSWORD") {
+            config.mqtt_password = Some(val);
+        }
+        
+        if let Ok(val) = var("MQTT_CA_FILE") {
+            config.mqtt_ca_file = Some(val);
+        }
+        
+        if let Ok(val) = var("MQTT_CLIENT_CERT_FILE") {
+            config.mqtt_client_cert_file = Some(val);
         }
         
-        if let Ok(val) = env::var("AUTOTUNE_TARGET_MS") {
+        if let Ok(val) = var("MQTT_CLIENT_KEY_FILE") {
+            config.mqtt_client_key_file = Some(val);
+        }
+        
+        if let Ok(val) = var("GRPC_URL") {
+            config.grpc_url = Some(val);
+        }
+        
+        if let Ok(val) = var("GRPC_DEADLINE_MS") {
+            config.grpc_deadline_ms = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("GRPC_DEADLINE_MS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("RECEIPT_VERSION_MAX") {
+            config.receipt_version_max = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("RECEIPT_VERSION_MAX".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("SUBMIT_COMPRESSION") {
+            config.submit_compression = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("SUBMIT_COMPRESSION".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("SUBMIT_COMPRESSION_MIN_BYTES") {
+            config.submit_compression_min_bytes = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("SUBMIT_COMPRESSION_MIN_BYTES".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("RECEIPT_WIRE_FORMAT") {
+            config.receipt_wire_format = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("RECEIPT_WIRE_FORMAT".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("IDEMPOTENCY_CACHE_SIZE") {
+            config.idempotency_cache_size = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("IDEMPOTENCY_CACHE_SIZE".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("CHALLENGE_QUEUE_MAX") {
+            config.challenge_queue_max = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("CHALLENGE_QUEUE_MAX".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AUTOTUNE_TARGET_MS") {
             config.autotune_target_ms = val.parse()
                 .map_err(|_| ConfigError::InvalidEnvVar("AUTOTUNE_TARGET_MS".to_string(), val))?;
         }
         
-        if let Ok(val) = env::var("AUTOTUNE_PRESETS") {
+        if let Ok(val) = var("AUTOTUNE_PRESETS") {
             config.autotune_presets = val.split(';').map(|s| s.to_string()).collect();
         }
         
-        if let Ok(val) = env::var("AUTOTUNE_DISABLE") {
+        if let Ok(val) = var("AUTOTUNE_DISABLE") {
             config.autotune_disable = val == "1";
         }
         
+        if let Ok(val) = var("AUTOTUNE_RETUNE_DRIFT_PCT") {
+            config.autotune_retune_drift_pct = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AUTOTUNE_RETUNE_DRIFT_PCT".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AUTOTUNE_MAX_BATCH") {
+            config.autotune_max_batch = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AUTOTUNE_MAX_BATCH".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("AUTOTUNE_BATCH_UTILIZATION_PCT") {
+            config.autotune_batch_utilization_pct = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("AUTOTUNE_BATCH_UTILIZATION_PCT".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("MIN_TOPS_SECONDS") {
+            config.min_tops_seconds = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("MIN_TOPS_SECONDS".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("PIPELINE_DEPTH") {
+            config.pipeline_depth = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("PIPELINE_DEPTH".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("ATTEMPTS_IN_FLIGHT") {
+            config.attempts_in_flight = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("ATTEMPTS_IN_FLIGHT".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("PERSISTENT_KERNEL_RANGE") {
+            config.persistent_kernel_range = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("PERSISTENT_KERNEL_RANGE".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("DEVICE_SAMPLING") {
+            config.device_sampling = val == "1";
+        }
+        
+        if let Ok(val) = var("HYBRID_CPU") {
+            config.hybrid_cpu = val == "1";
+        }
+        
+        if let Ok(val) = var("WARMUP_ATTEMPTS") {
+            config.warmup_attempts = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("WARMUP_ATTEMPTS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("WARMUP_SECS") {
+            config.warmup_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("WARMUP_SECS".to_string(), val))?;
+        }
+        
+        // Workload selection
+        if let Ok(val) = var("WORKLOAD_KIND") {
+            config.workload_kind = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("WORKLOAD_KIND".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("SPMM_DENSITY") {
+            config.spmm_density = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("SPMM_DENSITY".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("MEMHARD_KIB") {
+            config.memhard_kib = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("MEMHARD_KIB".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("MEMHARD_PASSES") {
+            config.memhard_passes = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("MEMHARD_PASSES".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("MEMHARD_MAX_KIB") {
+            config.memhard_max_kib = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("MEMHARD_MAX_KIB".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("REQUANT_SCALE") {
+            config.requant_scale = Some(parse_scale(&val)
+                .ok_or_else(|| ConfigError::InvalidEnvVar("REQUANT_SCALE".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("ACTIVATION") {
+            config.activation = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("ACTIVATION".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("REQUANT_ROUNDING") {
+            config.requant_rounding = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("REQUANT_ROUNDING".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("REQUANT_OVERFLOW") {
+            config.requant_overflow = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("REQUANT_OVERFLOW".to_string(), val))?);
+        }
+        
         // OpenCL tuning parameters
-        if let Ok(val) = env::var("WG_M") {
+        if let Ok(val) = var("WG_M") {
             config.wg_m = Some(val.parse()
                 .map_err(|_| ConfigError::InvalidEnvVar("WG_M".to_string(), val))?);
         }
         
-        if let Ok(val) = env::var("WG_N") {
+        if let Ok(val) = var("WG_N") {
             config.wg_n = Some(val.parse()
                 .map_err(|_| ConfigError::InvalidEnvVar("WG_N".to_string(), val))?);
         }
         
-        if let Ok(val) = env::var("TK") {
+        if let Ok(val) = var("TK") {
             config.tk = Some(val.parse()
                 .map_err(|_| ConfigError::InvalidEnvVar("TK".to_string(), val))?);
         }
         
-        // Debug and logging
-        if let Ok(val) = env::var("WORKER_DEBUG_RECEIPT") {
-            config.worker_debug_receipt = val == "1";
+        if let Ok(val) = var("OPENCL_PROGRAM_CACHE") {
+            config.opencl_program_cache = val == "1";
         }
         
-        if let Ok(val) = env::var("LOG_LEVEL") {
-            config.log_level = val;
+        // CUDA algorithm tuning
+        if let Ok(val) = var("CUDA_ALGO_TUNING") {
+            config.cuda_algo_tuning = val == "1";
         }
         
-        if let Ok(val) = env::var("METRICS_ENABLED") {
-            config.metrics_enabled = val == "1";
+        if let Ok(val) = var("CUDA_ALGO_CANDIDATES") {
+            config.cuda_algo_candidates = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("CUDA_ALGO_CANDIDATES".to_string(), val))?;
         }
         
-        // Error handling
-        if let Ok(val) = env::var("MAX_RETRIES") {
-            config.max_retries = val.parse()
-                .map_err(|_| ConfigError::InvalidEnvVar("MAX_RETRIES".to_string(), val))?;
+        // Correctness self-test
+        if let Ok(val) = var("SELFTEST_ENABLED") {
+            config.selftest_enabled = val == "1";
         }
         
-        if let Ok(val) = env::var("RETRY_DELAY_MS") {
-            config.retry_delay_ms = val.parse()
-                .map_err(|_| ConfigError::InvalidEnvVar("RETRY_DELAY_MS".to_string(), val))?;
+        if let Ok(val) = var("SELFTEST_INTERVAL") {
+            config.selftest_interval = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("SELFTEST_INTERVAL".to_string(), val))?;
         }
         
-        if let Ok(val) = env::var("HEALTH_CHECK_INTERVAL_MS") {
-            config.health_check_interval_ms = val.parse()
-                .map_err(|_| ConfigError::InvalidEnvVar("HEALTH_CHECK_INTERVAL_MS".to_string(), val))?;
+        if let Ok(val) = var("SELFTEST_ON_MISMATCH") {
+            config.selftest_policy = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("SELFTEST_ON_MISMATCH".to_string(), val))?;
         }
         
-        // Security
-        if let Ok(val) = env::var("RATE_LIMIT_PER_SECOND") {
-            config.rate_limit_per_second = val.parse()
-                .map_err(|_| ConfigError::InvalidEnvVar("RATE_LIMIT_PER_SECOND".to_string(), val))?;
+        if let Ok(val) = var("SPOTCHECK_ELEMENTS") {
+            config.spotcheck_elements = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("SPOTCHECK_ELEMENTS".to_string(), val))?;
         }
         
-        if let Ok(val) = env::var("MAX_CONCURRENT_REQUESTS") {
-            config.max_concurrent_requests = val.parse()
-                .map_err(|_| ConfigError::InvalidEnvVar("MAX_CONCURRENT_REQUESTS".to_string(), val))?;
+        if let Ok(val) = var("TIMING_DRIFT_PCT") {
+            config.timing_drift_pct = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("TIMING_DRIFT_PCT".to_string(), val))?;
         }
         
-        Ok(config)
-    }
-    
-    pub fn validate(&self) -> Result<(), ConfigError> {
-        if self.worker_sk_hex.is_empty() {
-            return Err(ConfigError::ValidationError("WORKER_SK_HEX is required".to_string()));
+        if let Ok(val) = var("ENERGY_METER") {
+            config.energy_meter = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("ENERGY_METER".to_string(), val))?;
         }
         
-        if self.worker_sk_hex.len() != 64 {
-            return Err(ConfigError::ValidationError("WORKER_SK_HEX must be 64 characters".to_string()));
+        if let Ok(val) = var("ENERGY_SAMPLE_MS") {
+            config.energy_sample_ms = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("ENERGY_SAMPLE_MS".to_string(), val))?;
         }
         
-        if !self.aggregator_url.starts_with("http") {
-            return Err(ConfigError::ValidationError("AGGREGATOR_URL must be a valid HTTP URL".to_string()));
+        if let Ok(val) = var("RECEIPT_ENERGY_ESTIMATE") {
+            config.receipt_energy_estimate = val == "1";
         }
         
-        if self.autotune_target_ms == 0 {
-            return Err(ConfigError::ValidationError("AUTOTUNE_TARGET_MS must be greater than 0".to_string()));
+        if let Ok(val) = var("RECEIPT_PERF_CONTEXT") {
+            config.receipt_perf_context = val == "1";
         }
         
-        Ok(())
-    }
-    
-    pub fn get_retry_delay(&self) -> Duration {
-        Duration::from_millis(self.retry_delay_ms)
-    }
-    
-    pub fn get_health_check_interval(&self) -> Duration {
-        Duration::from_millis(self.health_check_interval_ms)
+        if let Ok(val) = var("WORK_ROOT_SAMPLING") {
+            config.work_root_sampling = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("WORK_ROOT_SAMPLING".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("EVIDENCE_SAMPLE_RATE") {
+            config.evidence_sample_rate = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("EVIDENCE_SAMPLE_RATE".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("EVIDENCE_MAX_MB") {
+            config.evidence_max_mb = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("EVIDENCE_MAX_MB".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("ATTEMPT_JOURNAL") {
+            config.attempt_journal = val == "1";
+        }
+        
+        if let Ok(val) = var("ATTEMPT_JOURNAL_MAX_MB") {
+            config.attempt_journal_max_mb = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("ATTEMPT_JOURNAL_MAX_MB".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("EXPORT_FORMAT") {
+            config.export_format = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("EXPORT_FORMAT".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("EXPORT_DIR") {
+            config.export_dir = Some(val);
+        }
+        
+        if let Ok(val) = var("EXPORT_RETENTION_DAYS") {
+            config.export_retention_days = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("EXPORT_RETENTION_DAYS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("EXPORT_MAX_MB") {
+            config.export_max_mb = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("EXPORT_MAX_MB".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("MATRIX_CACHE_MAX_MB") {
+            config.matrix_cache_max_mb = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("MATRIX_CACHE_MAX_MB".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("STATS_ENABLED") {
+            config.stats_enabled = val == "1";
+        }
+        
+        if let Ok(val) = var("STATS_RETENTION_DAYS") {
+            config.stats_retention_days = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("STATS_RETENTION_DAYS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("KEY_ROTATION_POLL_SECS") {
+            config.key_rotation_poll_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("KEY_ROTATION_POLL_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("ADMIN_TOKEN") {
+            config.admin_token = Some(val);
+        }
+        
+        if let Ok(val) = var("REMOTE_SIGNER_TOKEN") {
+            config.remote_signer_token = Some(val);
+        }
+        
+        if let Ok(val) = var("SIGNER_LISTEN") {
+            config.signer_listen = val;
+        }
+        
+        if let Ok(val) = var("STATE_INTEGRITY") {
+            config.state_integrity = val == "1";
+        }
+        
+        if let Ok(val) = var("STATE_KEY_HEX") {
+            config.state_key_hex = Some(val);
+        }
+        
+        if let Ok(val) = var("SIGNER_ALLOWLIST") {
+            config.signer_allowlist = val.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+        }
+        
+        if let Ok(val) = var("CONTROL_SOCKET") {
+            config.control_socket = Some(val);
+        }
+        
+        if let Ok(val) = var("CONTROL_SOCKET_MODE") {
+            config.control_socket_mode = u32::from_str_radix(val.trim_start_matches("0o"), 8)
+                .map_err(|_| ConfigError::InvalidEnvVar("CONTROL_SOCKET_MODE".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("BACKPRESSURE_SLOW_AT") {
+            config.backpressure_slow_at = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("BACKPRESSURE_SLOW_AT".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("BACKPRESSURE_PAUSE_AT") {
+            config.backpressure_pause_at = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("BACKPRESSURE_PAUSE_AT".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("BACKPRESSURE_RESUME_AT") {
+            config.backpressure_resume_at = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("BACKPRESSURE_RESUME_AT".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("BACKPRESSURE_MAX_DELAY_MS") {
+            config.backpressure_max_delay_ms = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("BACKPRESSURE_MAX_DELAY_MS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("CLOCK_NTP_SERVER") {
+            config.clock_ntp_server = Some(val).filter(|server| !server.is_empty());
+        }
+        
+        if let Ok(val) = var("CLOCK_CHECK_INTERVAL_SECS") {
+            config.clock_check_interval_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("CLOCK_CHECK_INTERVAL_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("CLOCK_MAX_OFFSET_MS") {
+            config.clock_max_offset_ms = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("CLOCK_MAX_OFFSET_MS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("CLOCK_CORRECT") {
+            config.clock_correct = val == "1";
+        }
+        
+        if let Ok(val) = var("DRAIN_TIMEOUT_SECS") {
+            config.drain_timeout_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("DRAIN_TIMEOUT_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("EPOCH_URL") {
+            config.epoch_url = Some(val);
+        }
+        
+        if let Ok(val) = var("EPOCH_POLL_SECS") {
+            config.epoch_poll_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("EPOCH_POLL_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("EPOCH_SUMMARY_URL") {
+            config.epoch_summary_url = Some(val).filter(|url| !url.is_empty());
+        }
+        
+        if let Ok(val) = var("CPU_THREADS") {
+            config.cpu_threads = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("CPU_THREADS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("WORKER_NICE") {
+            config.worker_nice = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("WORKER_NICE".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("WORKER_IONICE") {
+            config.worker_ionice = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("WORKER_IONICE".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("CPU_CGROUP") {
+            config.cpu_cgroup = Some(val);
+        }
+        
+        if let Ok(val) = var("CPU_MAX_CORES") {
+            config.cpu_max_cores = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("CPU_MAX_CORES".to_string(), val))?);
+        }
+        
+        // Debug and logging
+        if let Ok(val) = var("WORKER_DEBUG_RECEIPT") {
+            config.worker_debug_receipt = val == "1";
+        }
+        
+        if let Ok(val) = var("LOG_LEVEL") {
+            config.log_level = val;
+        }
+        
+        if let Ok(val) = var("LOG_FORMAT") {
+            config.log_format = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("LOG_FORMAT".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("LOG_BUFFER_SIZE") {
+            config.log_buffer_size = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("LOG_BUFFER_SIZE".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("METRICS_ENABLED") {
+            config.metrics_enabled = val == "1";
+        }
+        
+        if let Ok(val) = var("MAIN_LOOP_STALL_SECS") {
+            config.main_loop_stall_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("MAIN_LOOP_STALL_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("MAIN_LOOP_STALL_RESTART") {
+            config.main_loop_stall_restart = val == "1";
+        }
+        
+        if let Ok(val) = var("LIVENESS_URL") {
+            config.liveness_url = Some(val);
+        }
+        
+        if let Ok(val) = var("LIVENESS_INTERVAL_SECS") {
+            config.liveness_interval_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("LIVENESS_INTERVAL_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("LIVENESS_MAX_BACKOFF_SECS") {
+            config.liveness_max_backoff_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("LIVENESS_MAX_BACKOFF_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("METRICS_PUSH_URL") {
+            config.metrics_push_url = Some(val).filter(|v| !v.is_empty());
+        }
+        
+        if let Ok(val) = var("METRICS_PUSH_INTERVAL_SECS") {
+            config.metrics_push_interval_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("METRICS_PUSH_INTERVAL_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("METRICS_PUSH_JOB") {
+            config.metrics_push_job = val;
+        }
+        
+        if let Ok(val) = var("ENROLL_URL") {
+            config.enroll_url = Some(val);
+        }
+        
+        if let Ok(val) = var("ENROLL_SUSTAINED_SECS") {
+            config.enroll_sustained_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("ENROLL_SUSTAINED_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("ENROLL_REQUIRE_STRESS") {
+            config.enroll_require_stress = val == "1";
+        }
+        
+        if let Ok(val) = var("QUARANTINE_MAX_ENTRIES") {
+            config.quarantine_max_entries = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("QUARANTINE_MAX_ENTRIES".to_string(), val))?;
+        }
+        
+        // Error handling
+        if let Ok(val) = var("MAX_RETRIES") {
+            config.max_retries = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("MAX_RETRIES".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("RETRY_DELAY_MS") {
+            config.retry_delay_ms = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("RETRY_DELAY_MS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("HEALTH_CHECK_INTERVAL_MS") {
+            config.health_check_interval_ms = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("HEALTH_CHECK_INTERVAL_MS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("HEALTH_DEGRADED_CONSECUTIVE_FAILURES") {
+            config.health_degraded_consecutive_failures = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("HEALTH_DEGRADED_CONSECUTIVE_FAILURES".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("HEALTH_UNHEALTHY_CONSECUTIVE_FAILURES") {
+            config.health_unhealthy_consecutive_failures = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("HEALTH_UNHEALTHY_CONSECUTIVE_FAILURES".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("HEALTH_CRITICAL_CONSECUTIVE_FAILURES") {
+            config.health_critical_consecutive_failures = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("HEALTH_CRITICAL_CONSECUTIVE_FAILURES".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("HEALTH_FAILURE_WINDOW") {
+            config.health_failure_window = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("HEALTH_FAILURE_WINDOW".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("HEALTH_DEGRADED_FAILURE_RATE") {
+            config.health_degraded_failure_rate = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("HEALTH_DEGRADED_FAILURE_RATE".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("HEALTH_UNHEALTHY_FAILURE_RATE") {
+            config.health_unhealthy_failure_rate = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("HEALTH_UNHEALTHY_FAILURE_RATE".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("HEALTH_STALE_SUCCESS_SECS") {
+            config.health_stale_success_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("HEALTH_STALE_SUCCESS_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("HEALTH_DEGRADED_GPU_TEMP_C") {
+            config.health_degraded_gpu_temp_c = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("HEALTH_DEGRADED_GPU_TEMP_C".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("HEALTH_UNHEALTHY_GPU_TEMP_C") {
+            config.health_unhealthy_gpu_temp_c = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("HEALTH_UNHEALTHY_GPU_TEMP_C".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("PACING") {
+            config.pacing = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("PACING".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("TARIFF_SCHEDULE") {
+            config.tariff_schedule = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("TARIFF_SCHEDULE".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("SUBMIT_JITTER_MS") {
+            config.submit_jitter_ms = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("SUBMIT_JITTER_MS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("STARTUP_JITTER_MS") {
+            config.startup_jitter_ms = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("STARTUP_JITTER_MS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("FLEET_SIZE") {
+            config.fleet_size = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("FLEET_SIZE".to_string(), val))?;
+        }
+        
+        // Security
+        if let Ok(val) = var("RATE_LIMIT_PER_SECOND") {
+            config.rate_limit_per_second = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("RATE_LIMIT_PER_SECOND".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("MAX_CONCURRENT_REQUESTS") {
+            config.max_concurrent_requests = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("MAX_CONCURRENT_REQUESTS".to_string(), val))?;
+        }
+        
+        // Adaptive rate control
+        if let Ok(val) = var("RATE_LIMIT_MIN_PER_SECOND") {
+            config.rate_limit_min_per_second = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("RATE_LIMIT_MIN_PER_SECOND".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("RATE_INCREASE_STEP") {
+            config.rate_increase_step = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("RATE_INCREASE_STEP".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("RATE_DECREASE_FACTOR") {
+            config.rate_decrease_factor = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("RATE_DECREASE_FACTOR".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("POWER_SIGNAL_URL") {
+            config.power_signal_url = Some(val);
+        }
+        
+        if let Ok(val) = var("POWER_POLL_INTERVAL_SECS") {
+            config.power_poll_interval_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("POWER_POLL_INTERVAL_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("POWER_PAUSE_WATTS") {
+            config.power_pause_watts = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("POWER_PAUSE_WATTS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("POWER_RESUME_WATTS") {
+            config.power_resume_watts = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("POWER_RESUME_WATTS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("POWER_FULL_WATTS") {
+            config.power_full_watts = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("POWER_FULL_WATTS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("POWER_MAX_PRICE") {
+            config.power_max_price = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("POWER_MAX_PRICE".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("POWER_RESUME_PRICE") {
+            config.power_resume_price = Some(val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("POWER_RESUME_PRICE".to_string(), val))?);
+        }
+        
+        if let Ok(val) = var("POWER_MIN_DUTY") {
+            config.power_min_duty = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("POWER_MIN_DUTY".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("POWER_STALE_SECS") {
+            config.power_stale_secs = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("POWER_STALE_SECS".to_string(), val))?;
+        }
+        
+        if let Ok(val) = var("POWER_STALE_ACTION") {
+            config.power_stale_action = val.parse()
+                .map_err(|_| ConfigError::InvalidEnvVar("POWER_STALE_ACTION".to_string(), val))?;
+        }
+        
+        Ok(config)
+    }
+    
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        // A key is required unless the worker only watches, which never loads one, or a remote signer holds it
+        if let Some(url) = &self.remote_signer_url {
+            if !url.starts_with("http") {
+                return Err(ConfigError::ValidationError("REMOTE_SIGNER_URL must be a valid HTTP URL".to_string()));
+            }
+            if !self.worker_sk_hex.is_empty() || self.did_key_seed_hex.is_some() || !self.identities.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "REMOTE_SIGNER_URL replaces local keys; unset WORKER_SK_HEX, DID_KEY_SEED_HEX and WORKER_IDENTITIES".to_string()));
+            }
+            // These sign with the key directly
+            if self.watch_only || self.liveness_url.is_some() || self.enroll_url.is_some() {
+                return Err(ConfigError::ValidationError(
+                    "REMOTE_SIGNER_URL cannot be combined with WATCH_ONLY, LIVENESS_URL or ENROLL_URL".to_string()));
+            }
+        } else if let Some(seed) = &self.did_key_seed_hex {
+            if seed.len() < 32 || hex::decode(seed).is_err() {
+                return Err(ConfigError::ValidationError("DID_KEY_SEED_HEX must be at least 16 bytes of hex".to_string()));
+            }
+        } else if self.identities.is_empty() && !self.watch_only {
+            if self.worker_sk_hex.is_empty() {
+                return Err(ConfigError::ValidationError("WORKER_SK_HEX is required".to_string()));
+            }
+            
+            if self.worker_sk_hex.len() != 64 {
+                return Err(ConfigError::ValidationError("WORKER_SK_HEX must be 64 characters".to_string()));
+            }
+        }
+        
+        match &self.network_id {
+            Some(id) if id.is_empty() || id.len() > 64
+                || !id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') => {
+                return Err(ConfigError::ValidationError(
+                    "NETWORK_ID must be 1-64 characters of a-z, 0-9 and '-' (e.g. peaq-mainnet)".to_string()));
+            }
+            // Release builds never sign receipts that could be replayed on another network
+            None if !cfg!(debug_assertions) && !self.watch_only => {
+                return Err(ConfigError::ValidationError("NETWORK_ID is required in release builds".to_string()));
+            }
+            _ => {}
+        }
+        
+        if self.wg_m.is_some() != self.wg_n.is_some() || self.wg_m == Some(0) || self.wg_n == Some(0) {
+            return Err(ConfigError::ValidationError("WG_M and WG_N must be set together, to positive sizes".to_string()));
+        }
+        
+        if let Some(key) = &self.aggregator_pubkey {
+            if crate::signing::parse_pubkey(key).is_err() {
+                return Err(ConfigError::ValidationError("AGGREGATOR_PUBKEY must be a hex SEC1 secp256k1 public key".to_string()));
+            }
+        }
+        
+        if self.fleet_config_url.is_some() {
+            match &self.fleet_config_pubkey {
+                None => return Err(ConfigError::ValidationError("FLEET_CONFIG_URL needs FLEET_CONFIG_PUBKEY".to_string())),
+                Some(key) if crate::signing::parse_pubkey(key).is_err() => {
+                    return Err(ConfigError::ValidationError("FLEET_CONFIG_PUBKEY must be a hex SEC1 secp256k1 public key".to_string()));
+                }
+                Some(_) => {}
+            }
+            if self.fleet_config_poll_secs == 0 {
+                return Err(ConfigError::ValidationError("FLEET_CONFIG_POLL_SECS must be greater than 0".to_string()));
+            }
+        }
+        
+        if self.update_manifest_url.is_some() {
+            match &self.update_pubkey {
+                None => return Err(ConfigError::ValidationError("UPDATE_MANIFEST_URL needs UPDATE_PUBKEY".to_string())),
+                Some(key) if crate::signing::parse_pubkey(key).is_err() => {
+                    return Err(ConfigError::ValidationError("UPDATE_PUBKEY must be a hex SEC1 secp256k1 public key".to_string()));
+                }
+                Some(_) => {}
+            }
+            if self.update_check_secs == 0 {
+                return Err(ConfigError::ValidationError("UPDATE_CHECK_SECS must be greater than 0".to_string()));
+            }
+            if self.update_target.is_empty() {
+                return Err(ConfigError::ValidationError("UPDATE_TARGET must not be empty".to_string()));
+            }
+        }
+        
+        for (idx, identity) in self.identities.iter().enumerate() {
+            if identity.weight == 0 {
+                return Err(ConfigError::ValidationError(format!("WORKER_IDENTITIES weight for {} must be greater than 0", identity.device_did)));
+            }
+            if identity.key == KeyRef::Seed && self.did_key_seed_hex.is_none() {
+                return Err(ConfigError::ValidationError(format!("WORKER_IDENTITIES key for {} is 'seed' but DID_KEY_SEED_HEX is not set", identity.device_did)));
+            }
+            if self.identities[..idx].iter().any(|other| other.device_did == identity.device_did) {
+                return Err(ConfigError::ValidationError(format!("WORKER_IDENTITIES lists {} more than once", identity.device_did)));
+            }
+        }
+        
+        if self.did_verify_required && self.peaq_rpc_url.is_none() {
+            return Err(ConfigError::ValidationError("DID_VERIFY_REQUIRED needs PEAQ_RPC_URL".to_string()));
+        }
+        
+        if self.aggregator_urls.is_empty() || self.aggregator_urls.iter().any(|u| !u.starts_with("http")) {
+            return Err(ConfigError::ValidationError("AGGREGATOR_URL must be a valid HTTP URL".to_string()));
+        }
+        
+        if self.aggregator_failover_threshold == 0 {
+            return Err(ConfigError::ValidationError("AGGREGATOR_FAILOVER_THRESHOLD must be greater than 0".to_string()));
+        }
+        
+        if let Some(url) = &self.shadow_aggregator_url {
+            if !url.starts_with("http") {
+                return Err(ConfigError::ValidationError("SHADOW_AGGREGATOR_URL must be a valid HTTP URL".to_string()));
+            }
+            if self.aggregator_urls.contains(url) {
+                return Err(ConfigError::ValidationError("SHADOW_AGGREGATOR_URL must not be one of AGGREGATOR_URL".to_string()));
+            }
+            if self.shadow_max_in_flight == 0 {
+                return Err(ConfigError::ValidationError("SHADOW_MAX_IN_FLIGHT must be greater than 0".to_string()));
+            }
+        }
+        
+        if self.circuit_failure_threshold == 0 {
+            return Err(ConfigError::ValidationError("CIRCUIT_FAILURE_THRESHOLD must be greater than 0".to_string()));
+        }
+        
+        if let Some(proxy) = &self.aggregator_proxy {
+            if !["http://", "https://", "socks5://", "socks5h://"].iter().any(|s| proxy.starts_with(s)) {
+                return Err(ConfigError::ValidationError("AGGREGATOR_PROXY must be an http://, https://, socks5:// or socks5h:// URL".to_string()));
+            }
+        }
+        
+        if let Some(addr) = &self.aggregator_bind_address {
+            if !self.aggregator_ip_family.allows(addr) {
+                return Err(ConfigError::ValidationError(format!(
+                    "AGGREGATOR_BIND_ADDRESS {} is not an {} address", addr, self.aggregator_ip_family)));
+            }
+        }
+        
+        if let Some(entry) = self.aggregator_resolve.iter().find(|entry| !self.aggregator_ip_family.allows(&entry.ip)) {
+            return Err(ConfigError::ValidationError(format!(
+                "AGGREGATOR_RESOLVE entry {} is not an {} address", entry, self.aggregator_ip_family)));
+        }
+        
+        if self.aggregator_dns_stale_secs > 0 && self.aggregator_dns_stale_secs < self.aggregator_dns_ttl_secs {
+            return Err(ConfigError::ValidationError("AGGREGATOR_DNS_STALE_SECS must be 0 or at least AGGREGATOR_DNS_TTL_SECS".to_string()));
+        }
+        
+        if self.aggregator_connect_timeout_secs == 0 || self.aggregator_request_timeout_secs == 0 {
+            return Err(ConfigError::ValidationError("AGGREGATOR_CONNECT_TIMEOUT_SECS and AGGREGATOR_REQUEST_TIMEOUT_SECS must be greater than 0".to_string()));
+        }
+        
+        if self.aggregator_protocol == AggregatorProtocol::Mqtt {
+            if !cfg!(feature = "mqtt") {
+                return Err(ConfigError::ValidationError("AGGREGATOR_PROTOCOL=mqtt needs a build with the `mqtt` feature".to_string()));
+            }
+            if self.mqtt_url.is_none() {
+                return Err(ConfigError::ValidationError("AGGREGATOR_PROTOCOL=mqtt requires MQTT_URL".to_string()));
+            }
+        }
+        
+        if self.aggregator_protocol == AggregatorProtocol::Grpc {
+            if !cfg!(feature = "grpc") {
+                return Err(ConfigError::ValidationError("AGGREGATOR_PROTOCOL=grpc needs a build with the `grpc` feature".to_string()));
+            }
+            match &self.grpc_url {
+                Some(url) if url.starts_with("http://") || url.starts_with("https://") => {}
+                _ => return Err(ConfigError::ValidationError("AGGREGATOR_PROTOCOL=grpc requires an http(s):// GRPC_URL".to_string())),
+            }
+            if self.grpc_deadline_ms == 0 {
+                return Err(ConfigError::ValidationError("GRPC_DEADLINE_MS must be greater than 0".to_string()));
+            }
+        }
+        
+        if !crate::types::SUPPORTED_RECEIPT_VERSIONS.contains(&self.receipt_version_max) {
+            return Err(ConfigError::ValidationError(format!(
+                "RECEIPT_VERSION_MAX must be one of {:?}", crate::types::SUPPORTED_RECEIPT_VERSIONS)));
+        }
+        
+        if self.autotune_target_ms == 0 {
+            return Err(ConfigError::ValidationError("AUTOTUNE_TARGET_MS must be greater than 0".to_string()));
+        }
+        
+        if !(1..=1024).contains(&self.autotune_max_batch) {
+            return Err(ConfigError::ValidationError("AUTOTUNE_MAX_BATCH must be between 1 and 1024".to_string()));
+        }
+        
+        if !(1..=100).contains(&self.autotune_batch_utilization_pct) {
+            return Err(ConfigError::ValidationError("AUTOTUNE_BATCH_UTILIZATION_PCT must be between 1 and 100".to_string()));
+        }
+        
+        if let Some(required) = self.min_tops_seconds {
+            if !(required > 0.0 && required.is_finite()) {
+                return Err(ConfigError::ValidationError("MIN_TOPS_SECONDS must be a positive number".to_string()));
+            }
+        }
+        
+        if self.pipeline_depth == 0 || self.pipeline_depth > 8 {
+            return Err(ConfigError::ValidationError("PIPELINE_DEPTH must be between 1 and 8".to_string()));
+        }
+        
+        if self.attempts_in_flight == 0 || self.attempts_in_flight > 16 {
+            return Err(ConfigError::ValidationError("ATTEMPTS_IN_FLIGHT must be between 1 and 16".to_string()));
+        }
+        
+        if self.persistent_kernel_range > crate::persistent::MAX_PERSISTENT_RANGE {
+            return Err(ConfigError::ValidationError(format!(
+                "PERSISTENT_KERNEL_RANGE must be at most {}", crate::persistent::MAX_PERSISTENT_RANGE)));
+        }
+        
+        // The persistent kernel only implements the dense GEMM with seeded sampling
+        if self.persistent_kernel_range > 0 && self.workload_kind != WorkloadKind::Gemm {
+            return Err(ConfigError::ValidationError("PERSISTENT_KERNEL_RANGE needs WORKLOAD_KIND=gemm".to_string()));
+        }
+        
+        if self.persistent_kernel_range > 0 && self.work_root_sampling != WorkSampling::Seeded {
+            return Err(ConfigError::ValidationError("PERSISTENT_KERNEL_RANGE needs WORK_ROOT_SAMPLING=seeded".to_string()));
+        }
+        
+        if let Some(url) = &self.epoch_url {
+            if !url.starts_with("http") {
+                return Err(ConfigError::ValidationError("EPOCH_URL must be a valid HTTP URL".to_string()));
+            }
+        }
+        
+        if let Some(url) = &self.epoch_summary_url {
+            if !url.starts_with("http") {
+                return Err(ConfigError::ValidationError("EPOCH_SUMMARY_URL must be a valid HTTP URL".to_string()));
+            }
+        }
+        
+        if let Some(url) = &self.liveness_url {
+            if !url.starts_with("http") {
+                return Err(ConfigError::ValidationError("LIVENESS_URL must be a valid HTTP URL".to_string()));
+            }
+            if self.liveness_interval_secs == 0 {
+                return Err(ConfigError::ValidationError("LIVENESS_INTERVAL_SECS must be greater than 0".to_string()));
+            }
+        }
+        
+        if let Some(url) = &self.metrics_push_url {
+            if !url.starts_with("http") {
+                return Err(ConfigError::ValidationError("METRICS_PUSH_URL must be a valid HTTP URL".to_string()));
+            }
+            if self.metrics_push_interval_secs == 0 {
+                return Err(ConfigError::ValidationError("METRICS_PUSH_INTERVAL_SECS must be greater than 0".to_string()));
+            }
+            if self.metrics_push_job.is_empty() || self.metrics_push_job.contains('/') {
+                return Err(ConfigError::ValidationError("METRICS_PUSH_JOB must be non-empty and contain no '/'".to_string()));
+            }
+        }
+        
+        if let Some(url) = &self.enroll_url {
+            if !url.starts_with("http") {
+                return Err(ConfigError::ValidationError("ENROLL_URL must be a valid HTTP URL".to_string()));
+            }
+            if self.enroll_sustained_secs == 0 {
+                return Err(ConfigError::ValidationError("ENROLL_SUSTAINED_SECS must be greater than 0".to_string()));
+            }
+        }
+        
+        if self.cuda_algo_tuning && (self.cuda_algo_candidates == 0 || self.cuda_algo_candidates > 64) {
+            return Err(ConfigError::ValidationError("CUDA_ALGO_CANDIDATES must be between 1 and 64".to_string()));
+        }
+        
+        if !(self.health_degraded_consecutive_failures <= self.health_unhealthy_consecutive_failures
+            && self.health_unhealthy_consecutive_failures <= self.health_critical_consecutive_failures)
+            || self.health_degraded_consecutive_failures == 0 {
+            return Err(ConfigError::ValidationError(
+                "HEALTH_*_CONSECUTIVE_FAILURES must satisfy 0 < degraded <= unhealthy <= critical".to_string()));
+        }
+        
+        if self.health_failure_window == 0 {
+            return Err(ConfigError::ValidationError("HEALTH_FAILURE_WINDOW must be greater than 0".to_string()));
+        }
+        
+        if !(self.health_degraded_failure_rate >= 0.0 && self.health_degraded_failure_rate <= self.health_unhealthy_failure_rate
+            && self.health_unhealthy_failure_rate <= 1.0) {
+            return Err(ConfigError::ValidationError(
+                "HEALTH_*_FAILURE_RATE must satisfy 0 <= degraded <= unhealthy <= 1".to_string()));
+        }
+        
+        if let (Some(degraded), Some(unhealthy)) = (self.health_degraded_gpu_temp_c, self.health_unhealthy_gpu_temp_c) {
+            if degraded > unhealthy {
+                return Err(ConfigError::ValidationError(
+                    "HEALTH_DEGRADED_GPU_TEMP_C must not exceed HEALTH_UNHEALTHY_GPU_TEMP_C".to_string()));
+            }
+        }
+        
+        if self.spotcheck_elements > 4096 {
+            return Err(ConfigError::ValidationError("SPOTCHECK_ELEMENTS must be at most 4096".to_string()));
+        }
+        
+        if !(self.timing_drift_pct > 0.0 && self.timing_drift_pct.is_finite()) {
+            return Err(ConfigError::ValidationError("TIMING_DRIFT_PCT must be a positive percentage".to_string()));
+        }
+        if self.energy_sample_ms == 0 {
+            return Err(ConfigError::ValidationError("ENERGY_SAMPLE_MS must be greater than 0".to_string()));
+        }
+        
+        if !(self.spmm_density > 0.0 && self.spmm_density <= 1.0) {
+            return Err(ConfigError::ValidationError("SPMM_DENSITY must be in (0, 1]".to_string()));
+        }
+        
+        if self.memhard_max_kib > MEMHARD_MAX_KIB {
+            return Err(ConfigError::ValidationError(format!("MEMHARD_MAX_KIB must be at most {} (4 GiB)", MEMHARD_MAX_KIB)));
+        }
+        
+        if self.memhard_kib > self.memhard_max_kib {
+            return Err(ConfigError::ValidationError(format!("MEMHARD_KIB must be at most MEMHARD_MAX_KIB ({})", self.memhard_max_kib)));
+        }
+        
+        if self.memhard_passes == 0 || self.memhard_passes > MEMHARD_MAX_PASSES {
+            return Err(ConfigError::ValidationError(format!("MEMHARD_PASSES must be between 1 and {}", MEMHARD_MAX_PASSES)));
+        }
+        
+        if self.evidence_max_mb == 0 {
+            return Err(ConfigError::ValidationError("EVIDENCE_MAX_MB must be greater than 0".to_string()));
+        }
+        
+        if self.backpressure_pause_at > 0 {
+            if self.backpressure_resume_at >= self.backpressure_pause_at {
+                return Err(ConfigError::ValidationError("BACKPRESSURE_RESUME_AT must be below BACKPRESSURE_PAUSE_AT".to_string()));
+            }
+            if self.backpressure_slow_at > self.backpressure_pause_at {
+                return Err(ConfigError::ValidationError("BACKPRESSURE_SLOW_AT must not be above BACKPRESSURE_PAUSE_AT".to_string()));
+            }
+        }
+        
+        if self.clock_ntp_server.is_some() && self.clock_check_interval_secs == 0 {
+            return Err(ConfigError::ValidationError("CLOCK_CHECK_INTERVAL_SECS must be greater than 0".to_string()));
+        }
+        
+        if self.attempt_journal && self.attempt_journal_max_mb == 0 {
+            return Err(ConfigError::ValidationError("ATTEMPT_JOURNAL_MAX_MB must be greater than 0".to_string()));
+        }
+        
+        if let Some(format) = self.export_format {
+            if format == ExportFormat::Parquet && !cfg!(feature = "parquet-export") {
+                return Err(ConfigError::ValidationError("EXPORT_FORMAT=parquet needs the `parquet-export` feature".to_string()));
+            }
+            if self.export_retention_days == 0 {
+                return Err(ConfigError::ValidationError("EXPORT_RETENTION_DAYS must be greater than 0".to_string()));
+            }
+        }
+        
+        if self.stats_enabled {
+            if !cfg!(feature = "stats") {
+                return Err(ConfigError::ValidationError("STATS_ENABLED=1 needs the `stats` feature".to_string()));
+            }
+            if self.stats_retention_days == 0 {
+                return Err(ConfigError::ValidationError("STATS_RETENTION_DAYS must be greater than 0".to_string()));
+            }
+        }
+        
+        if self.worker_nice.is_some_and(|nice| !(-20..=19).contains(&nice)) {
+            return Err(ConfigError::ValidationError("WORKER_NICE must be between -20 and 19".to_string()));
+        }
+        
+        if let Some(cores) = self.cpu_max_cores {
+            if cores.is_nan() || cores <= 0.0 {
+                return Err(ConfigError::ValidationError("CPU_MAX_CORES must be greater than 0".to_string()));
+            }
+            if self.cpu_cgroup.is_none() {
+                return Err(ConfigError::ValidationError("CPU_MAX_CORES needs CPU_CGROUP".to_string()));
+            }
+        }
+        
+        if self.admin_token.as_ref().is_some_and(|t| t.len() < 16) {
+            return Err(ConfigError::ValidationError("ADMIN_TOKEN must be at least 16 characters".to_string()));
+        }
+        
+        if self.signer_listen.parse::<std::net::SocketAddr>().is_err() {
+            return Err(ConfigError::ValidationError("SIGNER_LISTEN must be an address:port".to_string()));
+        }
+        
+        if let Err(e) = crate::signer::SignerAllowlist::parse(&self.signer_allowlist) {
+            return Err(ConfigError::ValidationError(format!("SIGNER_ALLOWLIST: {}", e)));
+        }
+        
+        if let Some(key) = &self.state_key_hex {
+            if key.trim().len() < 32 || hex::decode(key.trim()).is_err() {
+                return Err(ConfigError::ValidationError("STATE_KEY_HEX must be at least 16 bytes of hex".to_string()));
+            }
+        }
+        
+        // Without a local signing key there is nothing to derive the state key from
+        if self.state_integrity && self.state_key_hex.is_none() && (self.remote_signer_url.is_some() || self.watch_only) {
+            return Err(ConfigError::ValidationError(
+                "STATE_INTEGRITY needs STATE_KEY_HEX with REMOTE_SIGNER_URL or WATCH_ONLY".to_string()));
+        }
+        
+        if self.fleet_size == 0 {
+            return Err(ConfigError::ValidationError("FLEET_SIZE must be greater than 0".to_string()));
+        }
+        
+        // Anyone who can connect to the socket is an administrator
+        if self.control_socket_mode & 0o7 != 0 || self.control_socket_mode > 0o777 {
+            return Err(ConfigError::ValidationError("CONTROL_SOCKET_MODE must not grant access to other users".to_string()));
+        }
+        
+        if self.rate_limit_min_per_second <= 0.0 {
+            return Err(ConfigError::ValidationError("RATE_LIMIT_MIN_PER_SECOND must be greater than 0".to_string()));
+        }
+        
+        if !(self.rate_decrease_factor > 0.0 && self.rate_decrease_factor < 1.0) {
+            return Err(ConfigError::ValidationError("RATE_DECREASE_FACTOR must be between 0 and 1".to_string()));
+        }
+        
+        if self.power_signal_url.is_some() {
+            if !(self.power_pause_watts <= self.power_resume_watts && self.power_resume_watts <= self.power_full_watts) {
+                return Err(ConfigError::ValidationError(
+                    "POWER_PAUSE_WATTS <= POWER_RESUME_WATTS <= POWER_FULL_WATTS is required".to_string()));
+            }
+            if let (Some(max), Some(resume)) = (self.power_max_price, self.power_resume_price) {
+                if resume > max {
+                    return Err(ConfigError::ValidationError("POWER_RESUME_PRICE must not exceed POWER_MAX_PRICE".to_string()));
+                }
+            }
+            if !(self.power_min_duty > 0.0 && self.power_min_duty <= 1.0) {
+                return Err(ConfigError::ValidationError("POWER_MIN_DUTY must be in (0, 1]".to_string()));
+            }
+        }
+        
+        Ok(())
+    }
+    
+    pub fn get_retry_delay(&self) -> Duration {
+        Duration::from_millis(self.retry_delay_ms)
+    }
+    
+    pub fn get_submit_jitter(&self) -> Duration {
+        Duration::from_millis(self.submit_jitter_ms)
+    }
+    
+    pub fn get_startup_jitter(&self) -> Duration {
+        Duration::from_millis(self.startup_jitter_ms)
+    }
+    
+    pub fn get_failover_cooldown(&self) -> Duration {
+        Duration::from_secs(self.aggregator_failover_cooldown_secs)
+    }
+    
+    pub fn get_health_check_interval(&self) -> Duration {
+        Duration::from_millis(self.health_check_interval_ms)
+    }
+    
+    pub fn get_energy_sample_interval(&self) -> Duration {
+        Duration::from_millis(self.energy_sample_ms)
+    }
+    
+    pub fn get_circuit_recovery_timeout(&self) -> Duration {
+        Duration::from_secs(self.circuit_recovery_timeout_secs)
+    }
+    
+    pub fn get_aggregator_pool_idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.aggregator_pool_idle_timeout_secs)
+    }
+    
+    /// Keep-alive probe interval for aggregator connections; None when disabled (0).
+    pub fn get_aggregator_keepalive(&self) -> Option<Duration> {
+        (self.aggregator_keepalive_secs > 0).then(|| Duration::from_secs(self.aggregator_keepalive_secs))
+    }
+    
+    pub fn get_aggregator_dns_ttl(&self) -> Duration {
+        Duration::from_secs(self.aggregator_dns_ttl_secs)
+    }
+    
+    pub fn get_aggregator_dns_stale(&self) -> Duration {
+        Duration::from_secs(self.aggregator_dns_stale_secs)
+    }
+    
+    pub fn get_aggregator_connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.aggregator_connect_timeout_secs)
+    }
+    
+    pub fn get_aggregator_request_timeout(&self) -> Duration {
+        Duration::from_secs(self.aggregator_request_timeout_secs)
+    }
+    
+    /// Receipts held back while the submission circuit breaker is open.
+    pub fn get_circuit_backlog_dir(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("circuit_backlog")
+    }
+    
+    pub fn get_queue_dir(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("queue")
+    }
+    
+    /// Compiled OpenCL program binaries, or `None` when `OPENCL_PROGRAM_CACHE=0`.
+    pub fn get_program_cache_dir(&self) -> Option<std::path::PathBuf> {
+        self.opencl_program_cache.then(|| std::path::Path::new(&self.state_dir).join("cl_cache"))
+    }
+    
+    /// Tuned cuBLASLt algorithms, keyed by GPU model and sizes.
+    pub fn get_algo_cache_path(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("cublaslt_algos.json")
+    }
+    
+    /// SQLite database of hourly statistics (`STATS_ENABLED=1`).
+    pub fn get_stats_db_path(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("stats.sqlite")
+    }
+    
+    /// Compressed full outputs of sampled attempts and their index.
+    pub fn get_evidence_dir(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("evidence")
+    }
+    
+    pub fn get_evidence_max_bytes(&self) -> u64 {
+        self.evidence_max_mb * 1024 * 1024
+    }
+    
+    /// JSON-lines journal of attempts (`ATTEMPT_JOURNAL=1`); rotated to `journal.jsonl.1`.
+    pub fn get_journal_path(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("journal.jsonl")
+    }
+    
+    pub fn get_journal_max_bytes(&self) -> u64 {
+        self.attempt_journal_max_mb * 1024 * 1024
+    }
+    
+    /// Daily partitions of exported receipts (`EXPORT_FORMAT`): `EXPORT_DIR`, or `$STATE_DIR/export`.
+    pub fn get_export_dir(&self) -> std::path::PathBuf {
+        match &self.export_dir {
+            Some(dir) => std::path::PathBuf::from(dir),
+            None => std::path::Path::new(&self.state_dir).join("export"),
+        }
+    }
+    
+    pub fn get_export_max_bytes(&self) -> u64 {
+        self.export_max_mb * 1024 * 1024
+    }
+    
+    /// Memory-mapped cache of generated matrices (`MATRIX_CACHE_MAX_MB`).
+    pub fn get_matrix_cache_dir(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("matrix_cache")
+    }
+    
+    /// `None` when the matrix cache is disabled.
+    pub fn get_matrix_cache_max_bytes(&self) -> Option<u64> {
+        (self.matrix_cache_max_mb > 0).then(|| self.matrix_cache_max_mb * 1024 * 1024)
+    }
+    
+    /// Capability benchmark and enrollment progress.
+    pub fn get_enrollment_path(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("enrollment.json")
+    }
+    
+    /// The latest `tops-worker stress` report.
+    pub fn get_stress_path(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("stress.json")
+    }
+    
+    pub fn get_enroll_sustained_duration(&self) -> Duration {
+        Duration::from_secs(self.enroll_sustained_secs)
+    }
+    
+    /// Receipts the aggregator rejected, kept for `tops-worker resubmit`.
+    pub fn get_quarantine_dir(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("quarantine")
+    }
+    
+    /// High-water marks of the per-device receipt sequence numbers.
+    pub fn get_sequence_path(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("sequence.json")
+    }
+    
+    /// Chain and highest nonce of the last `tops-worker once` run.
+    pub fn get_nonce_checkpoint_path(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("nonce.json")
+    }
+    
+    /// Last known signing key and key epoch of every identity.
+    pub fn get_key_epochs_path(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("key_epochs.json")
+    }
+    
+    /// The latest accepted fleet config document, applied again at startup.
+    pub fn get_fleet_config_path(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("fleet_config.json")
+    }
+    
+    pub fn get_fleet_config_poll_interval(&self) -> Duration {
+        Duration::from_secs(self.fleet_config_poll_secs)
+    }
+    
+    /// Where downloaded releases are staged for the supervisor to swap in.
+    pub fn get_update_dir(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.state_dir).join("update")
+    }
+    
+    pub fn get_update_check_interval(&self) -> Duration {
+        Duration::from_secs(self.update_check_secs)
+    }
+    
+    /// How often `file:` keys are re-read for rotation, or `None` when `KEY_ROTATION_POLL_SECS=0`.
+    pub fn get_key_rotation_poll_interval(&self) -> Option<Duration> {
+        (self.key_rotation_poll_secs > 0).then(|| Duration::from_secs(self.key_rotation_poll_secs))
+    }
+    
+    /// How often the epoch feed polls, or `None` when `EPOCH_POLL_SECS=0`.
+    pub fn get_epoch_poll_interval(&self) -> Option<Duration> {
+        (self.epoch_poll_secs > 0).then(|| Duration::from_secs(self.epoch_poll_secs))
+    }
+    
+    pub fn get_backpressure_max_delay(&self) -> Duration {
+        Duration::from_millis(self.backpressure_max_delay_ms)
+    }
+    
+    pub fn get_clock_check_interval(&self) -> Duration {
+        Duration::from_secs(self.clock_check_interval_secs)
+    }
+    
+    /// Longest a graceful shutdown may take before the worker exits without finishing the drain.
+    pub fn get_drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs.max(1))
+    }
+    
+    /// MQTT_CLIENT_ID, or one derived from the device DID (brokers limit the charset).
+    pub fn mqtt_client_id(&self) -> String {
+        self.mqtt_client_id.clone().unwrap_or_else(|| {
+            let did: String = self.device_did.chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+                .collect();
+            format!("tops-worker-{}", did)
+        })
+    }
+    
+    /// Signing identities: `WORKER_IDENTITIES` when set, else `DEVICE_DID` with the
+    /// seed-derived key or `WORKER_SK_HEX`.
+    pub fn get_identities(&self) -> Vec<IdentitySpec> {
+        if !self.identities.is_empty() {
+            return self.identities.clone();
+        }
+        let key = match (&self.remote_signer_url, &self.did_key_seed_hex) {
+            (Some(_), _) => KeyRef::Remote,
+            (None, Some(_)) => KeyRef::Seed,
+            (None, None) => KeyRef::Hex(self.worker_sk_hex.clone()),
+        };
+        vec![IdentitySpec { device_did: self.device_did.clone(), key, weight: 1 }]
+    }
+    
+    pub fn get_workload(&self) -> Workload {
+        Workload::new(self.workload_kind, (self.spmm_density * 1000.0).round().clamp(1.0, 1000.0) as u16)
+    }
+    
+    /// Memory-hard stage parameters; a size from the epoch descriptor wins over `MEMHARD_KIB`
+    /// but is clamped to `MEMHARD_MAX_KIB`.
+    pub fn get_memhard(&self, epoch_kib: Option<u32>) -> Option<MemHardParams> {
+        let mem_kib = match epoch_kib {
+            Some(kib) if kib > self.memhard_max_kib => {
+                log_warn!("[memhard] epoch asks for {} KiB, clamped to MEMHARD_MAX_KIB {}", kib, self.memhard_max_kib);
+                self.memhard_max_kib
+            }
+            Some(kib) => kib,
+            None => self.memhard_kib,
+        };
+        (mem_kib > 0).then_some(MemHardParams { mem_kib, passes: self.memhard_passes })
+    }
+    
+    /// Requantization parameters; those of the epoch descriptor win over `REQUANT_SCALE`,
+    /// `ACTIVATION`, `REQUANT_ROUNDING` and `REQUANT_OVERFLOW`.
+    pub fn get_requant(&self, epoch: RequantParams) -> RequantParams {
+        epoch.or(RequantParams {
+            scale: self.requant_scale,
+            activation: self.activation,
+            rounding: self.requant_rounding,
+            overflow: self.requant_overflow,
+        })
+    }
+    
+    pub fn get_liveness_interval(&self) -> Duration {
+        Duration::from_secs(self.liveness_interval_secs)
+    }
+    
+    pub fn get_liveness_max_backoff(&self) -> Duration {
+        Duration::from_secs(self.liveness_max_backoff_secs)
+    }
+    
+    pub fn get_metrics_push_interval(&self) -> Duration {
+        Duration::from_secs(self.metrics_push_interval_secs)
+    }
+    
+    pub fn get_warmup_duration(&self) -> Duration {
+        Duration::from_secs(self.warmup_secs)
+    }
+    
+    /// `None` when the main-loop watchdog is disabled (`MAIN_LOOP_STALL_SECS=0`).
+    pub fn get_main_loop_stall(&self) -> Option<Duration> {
+        (self.main_loop_stall_secs > 0).then(|| Duration::from_secs(self.main_loop_stall_secs))
+    }
+    
+    pub fn get_grpc_deadline(&self) -> Duration {
+        Duration::from_millis(self.grpc_deadline_ms)
+    }
+    
+    pub fn get_power_poll_interval(&self) -> Duration {
+        Duration::from_secs(self.power_poll_interval_secs)
     }
 }
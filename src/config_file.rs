@@ -0,0 +1,248 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::config::{Config, ConfigError};
+use crate::config_report::{env_var_name, SECRET_FIELDS};
+use crate::identity::{parse_identities, write_key_file, KeyRef};
+
+/// File `migrate-config` writes unless told otherwise.
+pub const DEFAULT_FILE_NAME: &str = "tops-worker.toml";
+
+// Variables read outside `Config`, which only the environment can set
+const ENV_ONLY_SETTINGS: &[&str] = &[
+    "AUTOTUNE_PRESETS", "AUTOTUNE_TARGET_MS", "OPENCL_GEMM_KERNEL", "OPENCL_TRANSFER",
+    "TK", "TM", "TN", "WG_M", "WG_N",
+];
+
+/// A configuration file (`CONFIG_FILE`, usually tops-worker.toml): settings by
+/// environment variable name, and secrets as paths to keystore files holding them.
+///
+/// The environment wins over the file, so a unit can still override single
+/// settings while it moves off env-based configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+    /// Secret variables by name, each read from its file with surrounding whitespace trimmed.
+    #[serde(default)]
+    pub secrets: BTreeMap<String, PathBuf>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::FileError(path.display().to_string(), e.to_string()))?;
+        Self::parse(&text).map_err(|e| ConfigError::FileError(path.display().to_string(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// The file as TOML, headed by a comment saying where it came from.
+    pub fn to_toml(&self, header: &str) -> anyhow::Result<String> {
+        let mut out: String = header.lines().map(|line| format!("# {}\n", line)).collect();
+        out.push('\n');
+        out.push_str(&toml::to_string(self)?);
+        Ok(out)
+    }
+
+    /// Every setting with its value, secrets read from their keystore files.
+    pub fn values(&self) -> Result<BTreeMap<String, String>, ConfigError> {
+        let mut values = self.settings.clone();
+        for (name, path) in &self.secrets {
+            let secret = std::fs::read_to_string(path)
+                .map_err(|e| ConfigError::FileError(path.display().to_string(), format!("secret {}: {}", name, e)))?;
+            values.insert(name.clone(), secret.trim().to_string());
+        }
+        Ok(values)
+    }
+
+    /// Names the file sets, secrets included.
+    pub fn names(&self) -> BTreeSet<String> {
+        self.settings.keys().chain(self.secrets.keys()).cloned().collect()
+    }
+}
+
+/// The file named by `CONFIG_FILE`, if set.
+pub fn from_env() -> Result<Option<ConfigFile>, ConfigError> {
+    match env::var("CONFIG_FILE") {
+        Ok(path) => ConfigFile::load(Path::new(&path)).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Lookup for `Config::from_lookup`: the environment, then `CONFIG_FILE`.
+pub fn env_lookup() -> Result<impl Fn(&str) -> Result<String, env::VarError>, ConfigError> {
+    let file = match from_env()? {
+        Some(file) => file.values()?,
+        None => BTreeMap::new(),
+    };
+    Ok(move |name: &str| env::var(name).or_else(|e| file.get(name).cloned().ok_or(e)))
+}
+
+/// A field that behaves differently when loaded from the migrated file.
+#[derive(Debug, Clone)]
+pub struct Difference {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// Result of `tops-worker migrate-config`.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub file: ConfigFile,
+    /// Keystore files written for secrets and inline identity keys.
+    pub keystore_files: Vec<PathBuf>,
+    /// Variables that are set but only read from the environment; they stay there.
+    pub env_only: Vec<String>,
+    /// Fields whose effective value differs once loaded from the file; empty when equivalent.
+    pub differences: Vec<Difference>,
+}
+
+impl Migration {
+    pub fn equivalent(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = format!("{} setting(s), {} secret(s) in {} keystore file(s)\n",
+            self.file.settings.len(), self.file.secrets.len(), self.keystore_files.len());
+        for path in &self.keystore_files {
+            out.push_str(&format!("  keystore  {}\n", path.display()));
+        }
+        for name in &self.env_only {
+            out.push_str(&format!("  env-only  {} is not read from the file; keep it in the environment\n", name));
+        }
+        if self.differences.is_empty() {
+            out.push_str("effective configuration: unchanged\n");
+        } else {
+            out.push_str(&format!("effective configuration: {} field(s) differ\n", self.differences.len()));
+            let width = self.differences.iter().map(|d| d.field.len()).max().unwrap_or(0);
+            for d in &self.differences {
+                out.push_str(&format!("  {:<width$}  {} -> {}\n", d.field, d.before, d.after, width = width));
+            }
+        }
+        out
+    }
+}
+
+/// Move the configuration `lookup` yields (normally `env_lookup()`) into a
+/// `ConfigFile`: secrets and inline or env-referenced identity keys are written
+/// to `keystore` and referenced by path. The file is then loaded on its own,
+/// without the environment, and compared field by field with the original.
+pub fn migrate(lookup: impl Fn(&str) -> Result<String, env::VarError>, keystore: &Path) -> anyhow::Result<Migration> {
+    // Record what the configuration actually reads, so unrelated variables stay out of the file
+    let read = RefCell::new(BTreeMap::new());
+    let before = Config::from_lookup(|name| {
+        let value = lookup(name);
+        if let Ok(val) = &value {
+            read.borrow_mut().insert(name.to_string(), val.clone());
+        }
+        value
+    })?;
+    before.validate()?;
+
+    create_keystore(keystore)?;
+    // Absolute paths, so the file works from any working directory
+    let keystore = &keystore.canonicalize()?;
+    let secret_vars: Vec<String> = SECRET_FIELDS.iter().map(|field| env_var_name(field)).collect();
+    let mut file = ConfigFile::default();
+    let mut keystore_files = Vec::new();
+    for (name, value) in read.into_inner() {
+        if secret_vars.contains(&name) {
+            let path = keystore.join(name.to_lowercase());
+            write_key_file(&path, &value)?;
+            keystore_files.push(path.clone());
+            file.secrets.insert(name, path);
+        } else if name == "WORKER_IDENTITIES" {
+            let (identities, written) = move_identity_keys(&value, keystore)?;
+            keystore_files.extend(written);
+            file.settings.insert(name, identities);
+        } else {
+            file.settings.insert(name, value);
+        }
+    }
+
+    // Round trip through the TOML text, reading the keystore back from disk
+    let reloaded = ConfigFile::parse(&file.to_toml("")?).map_err(|e| anyhow::anyhow!("generated file does not parse: {}", e))?;
+    let values = reloaded.values()?;
+    let after = Config::from_lookup(|name| values.get(name).cloned().ok_or(env::VarError::NotPresent))?;
+    after.validate()?;
+
+    Ok(Migration {
+        file,
+        keystore_files,
+        env_only: ENV_ONLY_SETTINGS.iter().filter(|name| lookup(name).is_ok()).map(|name| name.to_string()).collect(),
+        differences: differences(&before, &after),
+    })
+}
+
+fn create_keystore(dir: &Path) -> anyhow::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir).map_err(|e| anyhow::anyhow!("creating keystore {}: {}", dir.display(), e))
+}
+
+// WORKER_IDENTITIES with `hex:` and `env:` keys replaced by `file:` keys in the keystore
+fn move_identity_keys(value: &str, keystore: &Path) -> anyhow::Result<(String, Vec<PathBuf>)> {
+    let identities = parse_identities(value).map_err(|e| anyhow::anyhow!("WORKER_IDENTITIES: {}", e))?;
+    let mut written = Vec::new();
+    let mut entries = Vec::new();
+    for (i, identity) in identities.into_iter().enumerate() {
+        let key = match &identity.key {
+            KeyRef::Hex(key) => Some(key.clone()),
+            KeyRef::Env(var) => Some(env::var(var)
+                .map_err(|_| anyhow::anyhow!("key variable {} for {} is not set", var, identity.device_did))?),
+            KeyRef::File(_) | KeyRef::Seed | KeyRef::Remote => None,
+        };
+        let key_ref = match key {
+            Some(key) => {
+                let path = keystore.join(format!("identity-{}.key", i));
+                write_key_file(&path, key.trim())?;
+                written.push(path.clone());
+                KeyRef::File(path)
+            }
+            None => identity.key,
+        };
+        entries.push(format!("{}={}@{}", identity.device_did, key_ref, identity.weight));
+    }
+    Ok((entries.join(","), written))
+}
+
+// Fields whose values differ; identities compare by public key, secrets are not shown
+fn differences(before: &Config, after: &Config) -> Vec<Difference> {
+    let (before_values, after_values) = (behavior(before), behavior(after));
+    before_values.iter()
+        .filter(|(field, value)| after_values.get(*field) != Some(*value))
+        .map(|(field, value)| {
+            let after = after_values.get(field).cloned().unwrap_or(Value::Null);
+            let show = |v: &Value| if SECRET_FIELDS.contains(&field.as_str()) { "<redacted>".to_string() } else { v.to_string() };
+            Difference { field: field.clone(), before: show(value), after: show(&after) }
+        })
+        .collect()
+}
+
+fn behavior(config: &Config) -> serde_json::Map<String, Value> {
+    let mut values = match serde_json::to_value(config) {
+        Ok(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    // Where a key is stored does not change what gets signed
+    let identities = config.identities.iter()
+        .map(|identity| {
+            let key = identity.key.load(&identity.device_did, config.did_key_seed_hex.as_deref())
+                .map(|secp| secp.pubkey_hex_compressed())
+                .unwrap_or_else(|e| format!("unloadable: {}", e));
+            serde_json::json!({ "device_did": identity.device_did, "pubkey": key, "weight": identity.weight })
+        })
+        .collect();
+    values.insert("identities".to_string(), Value::Array(identities));
+    values
+}
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::config::Config;
+use crate::fleet_config::FleetConfigSync;
+
+// Shown instead of a secret that is set
+const REDACTED: &str = "<redacted>";
+/// Fields holding key material or credentials.
+pub const SECRET_FIELDS: &[&str] = &[
+    "worker_sk_hex", "did_key_seed_hex", "mqtt_password", "admin_token", "remote_signer_token", "signer_allowlist",
+    "state_key_hex",
+];
+// Fields whose variable is not their name in capitals
+const RENAMED_FIELDS: &[(&str, &str)] = &[
+    ("aggregator_urls", "AGGREGATOR_URL"),
+    ("identities", "WORKER_IDENTITIES"),
+    ("selftest_policy", "SELFTEST_ON_MISMATCH"),
+];
+
+/// Where the value of a setting came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigSource {
+    /// Nothing set it.
+    Default,
+    /// The process environment.
+    Env,
+    /// The configuration file named by `CONFIG_FILE`.
+    ConfigFile,
+    /// The fleet config document stored in the state directory, overlaid at startup.
+    File,
+    /// A fleet config document pulled while running and reloaded live.
+    Remote,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::Env => write!(f, "env"),
+            ConfigSource::ConfigFile => write!(f, "config-file"),
+            ConfigSource::File => write!(f, "file"),
+            ConfigSource::Remote => write!(f, "remote"),
+        }
+    }
+}
+
+/// One configuration field as the worker runs with it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigEntry {
+    pub field: String,
+    /// Variable (or fleet config setting) that sets it.
+    pub env_var: String,
+    /// Effective value; secrets that are set read `<redacted>`.
+    pub value: Value,
+    pub redacted: bool,
+    pub source: ConfigSource,
+    /// A newer fleet config document changes it at the next restart.
+    pub staged_for_restart: bool,
+}
+
+/// Every configuration field with its value and provenance, served at `/config`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigReport {
+    /// Fleet config document version in effect, if any.
+    pub fleet_config_version: Option<u64>,
+    pub fields: Vec<ConfigEntry>,
+    pub generated_at: String,
+}
+
+/// Variable that sets `field`: its name in capitals unless renamed.
+pub fn env_var_name(field: &str) -> String {
+    RENAMED_FIELDS.iter()
+        .find(|(name, _)| *name == field)
+        .map(|(_, var)| var.to_string())
+        .unwrap_or_else(|| field.to_uppercase())
+}
+
+impl ConfigReport {
+    /// Report `config` (as the process started with it). With fleet config sync,
+    /// live-reloaded settings are shown at their current value.
+    pub fn new(config: &Config, fleet_config: Option<&FleetConfigSync>) -> Self {
+        let running = match fleet_config {
+            Some(sync) => sync.running_config(config),
+            None => config.clone(),
+        };
+        let startup_settings = fleet_config.map(|sync| sync.startup_settings().clone()).unwrap_or_default();
+        let reloaded = fleet_config.map(|sync| sync.reloaded_settings()).unwrap_or_default();
+        let status = fleet_config.map(|sync| sync.status());
+        let staged = status.as_ref().map(|s| s.staged_for_restart.clone()).unwrap_or_default();
+        let file_settings = crate::config_file::from_env().ok().flatten().map(|file| file.names()).unwrap_or_default();
+
+        let values = match serde_json::to_value(&running) {
+            Ok(Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        let fields = values.into_iter().map(|(field, value)| {
+            let env_var = env_var_name(&field);
+            let source = if reloaded.contains_key(&env_var) {
+                ConfigSource::Remote
+            } else if startup_settings.contains_key(&env_var) {
+                ConfigSource::File
+            } else if std::env::var(&env_var).is_ok() {
+                ConfigSource::Env
+            } else if file_settings.contains(&env_var) {
+                ConfigSource::ConfigFile
+            } else {
+                ConfigSource::Default
+            };
+            let (value, redacted) = redact(&field, value, &running);
+            ConfigEntry { staged_for_restart: staged.contains(&env_var), field, env_var, value, redacted, source }
+        }).collect();
+
+        ConfigReport {
+            fleet_config_version: status.and_then(|s| s.applied_version),
+            fields,
+            generated_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+// Secrets that are set, inline identity keys and URL passwords are masked; unset secrets stay visible as such
+fn redact(field: &str, value: Value, config: &Config) -> (Value, bool) {
+    if SECRET_FIELDS.contains(&field) {
+        return match &value {
+            Value::Null => (value, false),
+            Value::String(s) if s.is_empty() => (value, false),
+            Value::Array(items) if items.is_empty() => (value, false),
+            _ => (Value::String(REDACTED.to_string()), true),
+        };
+    }
+    if field == "identities" {
+        let identities: Vec<Value> = config.identities.iter()
+            .map(|identity| serde_json::json!({
+                "device_did": identity.device_did,
+                "key": identity.key.to_string(),
+                "weight": identity.weight,
+            }))
+            .collect();
+        let redacted = config.identities.iter().any(|identity| matches!(identity.key, crate::identity::KeyRef::Hex(_)));
+        return (Value::Array(identities), redacted);
+    }
+    redact_urls(value)
+}
+
+fn redact_urls(value: Value) -> (Value, bool) {
+    match value {
+        Value::String(s) => match reqwest::Url::parse(&s) {
+            Ok(mut url) if url.password().is_some() => {
+                // Angle brackets would be percent-encoded in the userinfo
+                let _ = url.set_password(Some("redacted"));
+                (Value::String(url.to_string()), true)
+            }
+            _ => (Value::String(s), false),
+        },
+        Value::Array(items) => {
+            let mut any = false;
+            let items = items.into_iter().map(|item| {
+                let (item, redacted) = redact_urls(item);
+                any |= redacted;
+                item
+            }).collect();
+            (Value::Array(items), any)
+        }
+        other => (other, false),
+    }
+}
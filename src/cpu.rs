@@ -1,31 +1,252 @@
-use crate::types::Sizes;
+use std::sync::OnceLock;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::types::{DeviceInfo, Requant, Sizes};
 
-pub struct CpuExec;
+// SIMD kernels accumulate in i32: |a*b| <= 2^14, so K below 2^17 cannot overflow
+const SIMD_MAX_K: usize = 1 << 17;
+
+type DotFn = fn(&[i8], &[i8]) -> i32;
+
+/// CPU implementations of the int8 GEMM. All of them produce bit-identical output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CpuKernel {
+    /// Portable reference loop.
+    Scalar,
+    /// x86_64 AVX2 (16 int8 lanes widened to int16, `vpmaddwd`).
+    Avx2,
+    /// x86_64 AVX-512 VNNI (32 int8 lanes widened to int16, `vpdpwssd`).
+    Avx512Vnni,
+    /// aarch64 NEON (`smull` + `sadalp`), e.g. Jetson.
+    Neon,
+}
+
+impl CpuKernel {
+    /// Whether the running CPU can execute this kernel.
+    pub fn is_supported(&self) -> bool {
+        match self {
+            CpuKernel::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            CpuKernel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            CpuKernel::Avx512Vnni => {
+                is_x86_feature_detected!("avx512f")
+                    && is_x86_feature_detected!("avx512bw")
+                    && is_x86_feature_detected!("avx512vnni")
+            }
+            #[cfg(target_arch = "aarch64")]
+            CpuKernel::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Fastest kernel the running CPU supports.
+    pub fn best() -> Self {
+        [CpuKernel::Avx512Vnni, CpuKernel::Avx2, CpuKernel::Neon]
+            .into_iter()
+            .find(|k| k.is_supported())
+            .unwrap_or(CpuKernel::Scalar)
+    }
+
+    /// int8 elements the kernel's dot product takes per step; a `k` that is a
+    /// multiple of this leaves no scalar tail.
+    pub fn lanes(&self) -> usize {
+        match self {
+            CpuKernel::Scalar => 1,
+            CpuKernel::Avx2 | CpuKernel::Neon => 16,
+            CpuKernel::Avx512Vnni => 32,
+        }
+    }
+
+    // Safe wrapper around the kernel's dot product; None for the scalar path
+    fn dot_product(&self) -> Option<DotFn> {
+        if !self.is_supported() {
+            return None;
+        }
+        match self {
+            // SAFETY (all arms): the required target features were checked just above
+            #[cfg(target_arch = "x86_64")]
+            CpuKernel::Avx2 => Some(|a, b| unsafe { x86::dot_avx2(a, b) }),
+            #[cfg(target_arch = "x86_64")]
+            CpuKernel::Avx512Vnni => Some(|a, b| unsafe { x86::dot_avx512_vnni(a, b) }),
+            #[cfg(target_arch = "aarch64")]
+            CpuKernel::Neon => Some(|a, b| unsafe { arm::dot_neon(a, b) }),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for CpuKernel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CpuKernel::Scalar => write!(f, "scalar"),
+            CpuKernel::Avx2 => write!(f, "avx2"),
+            CpuKernel::Avx512Vnni => write!(f, "avx512-vnni"),
+            CpuKernel::Neon => write!(f, "neon"),
+        }
+    }
+}
+
+/// Detected CPU features and the GEMM code path chosen from them, as reported in /status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CpuDispatch {
+    pub arch: String,
+    pub features: Vec<String>,
+    pub kernel: CpuKernel,
+}
+
+/// Runtime CPU feature detection, done once per process.
+pub fn dispatch() -> &'static CpuDispatch {
+    static DISPATCH: OnceLock<CpuDispatch> = OnceLock::new();
+    DISPATCH.get_or_init(|| {
+        let features = [CpuKernel::Avx2, CpuKernel::Avx512Vnni, CpuKernel::Neon]
+            .into_iter()
+            .filter(|k| k.is_supported())
+            .map(|k| k.to_string())
+            .collect();
+        CpuDispatch {
+            arch: std::env::consts::ARCH.into(),
+            features,
+            kernel: CpuKernel::best(),
+        }
+    })
+}
+
+pub struct CpuExec {
+    kernel: CpuKernel,
+}
 
 impl CpuExec {
+    /// CPU executor using the best kernel for this machine.
     pub fn new() -> anyhow::Result<Self> {
-        Ok(Self)
-    }
-    
-    pub fn gemm_int8_relu_q(&self, a: &[i8], b: &[i8], m: usize, n: usize, k: usize, num: i32, den: i32) -> Vec<i8> {
-        let mut y = vec![0i8; m*n];
-        for row in 0..m {
-            for col in 0..n {
-                let mut acc: i64 = 0;
-                for t in 0..k {
-                    acc += (a[row*k + t] as i32 as i64) * (b[t*n + col] as i32 as i64);
-                }
-                let mut q = (acc * num as i64) / den as i64;
-                if q < 0 { q = 0; }
-                if q > 127 { q = 127; }
-                y[row*n + col] = q as i8;
-            }
+        Self::with_kernel(dispatch().kernel)
+    }
+
+    /// CPU executor pinned to `kernel`, e.g. the scalar reference for self-tests.
+    pub fn with_kernel(kernel: CpuKernel) -> anyhow::Result<Self> {
+        if !kernel.is_supported() {
+            return Err(anyhow::anyhow!("CPU kernel {} is not supported on this machine", kernel));
+        }
+        Ok(Self { kernel })
+    }
+
+    pub fn kernel(&self) -> CpuKernel {
+        self.kernel
+    }
+
+    pub fn gemm_int8_relu_q(&self, a: &[i8], b: &[i8], m: usize, n: usize, k: usize, scale: Requant) -> Vec<i8> {
+        match self.kernel.dot_product() {
+            Some(dot) if k < SIMD_MAX_K => gemm_with_dot(dot, a, b, m, n, k, scale),
+            _ => gemm_scalar(a, b, m, n, k, scale),
         }
-        y
     }
-    
-    pub fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes) -> anyhow::Result<Vec<i8>> {
-        let result = self.gemm_int8_relu_q(a, b, sizes.m, sizes.n, sizes.k, 1, 1);
+
+    pub fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        let result = self.gemm_int8_relu_q(a, b, sizes.m, sizes.n, sizes.k, scale);
         Ok(result)
     }
+
+    pub fn device_info(&self) -> DeviceInfo {
+        DeviceInfo {
+            backend: "CPU".into(),
+            device_name: std::env::consts::ARCH.into(),
+            driver_version: String::new(),
+        }
+    }
+}
+
+// Rows are independent, so both paths split them across the rayon pool (CPU_THREADS)
+fn gemm_scalar(a: &[i8], b: &[i8], m: usize, n: usize, k: usize, scale: Requant) -> Vec<i8> {
+    let mut y = vec![0i8; m*n];
+    y.par_chunks_mut(n.max(1)).enumerate().for_each(|(row, y_row)| {
+        for (col, out) in y_row.iter_mut().enumerate() {
+            let mut acc: i64 = 0;
+            for t in 0..k {
+                acc += (a[row*k + t] as i32 as i64) * (b[t*n + col] as i32 as i64);
+            }
+            *out = scale.apply(acc);
+        }
+    });
+    y
+}
+
+// B is transposed once so every output element is a dot product of two contiguous rows
+fn gemm_with_dot(dot: DotFn, a: &[i8], b: &[i8], m: usize, n: usize, k: usize, scale: Requant) -> Vec<i8> {
+    let mut bt = vec![0i8; k*n];
+    for t in 0..k {
+        for col in 0..n {
+            bt[col*k + t] = b[t*n + col];
+        }
+    }
+    let mut y = vec![0i8; m*n];
+    y.par_chunks_mut(n.max(1)).enumerate().for_each(|(row, y_row)| {
+        let a_row = &a[row*k..(row + 1)*k];
+        for (col, out) in y_row.iter_mut().enumerate() {
+            let acc = dot(a_row, &bt[col*k..(col + 1)*k]);
+            *out = scale.apply(acc as i64);
+        }
+    });
+    y
+}
+
+fn dot_tail(a: &[i8], b: &[i8]) -> i32 {
+    a.iter().zip(b).map(|(&x, &y)| x as i32 * y as i32).sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn dot_avx2(a: &[i8], b: &[i8]) -> i32 {
+        let k = a.len().min(b.len());
+        let mut acc = _mm256_setzero_si256();
+        let mut t = 0;
+        while t + 16 <= k {
+            let va = _mm256_cvtepi8_epi16(_mm_loadu_si128(a.as_ptr().add(t) as *const __m128i));
+            let vb = _mm256_cvtepi8_epi16(_mm_loadu_si128(b.as_ptr().add(t) as *const __m128i));
+            acc = _mm256_add_epi32(acc, _mm256_madd_epi16(va, vb));
+            t += 16;
+        }
+        let sum = _mm_add_epi32(_mm256_castsi256_si128(acc), _mm256_extracti128_si256::<1>(acc));
+        let mut lanes = [0i32; 4];
+        _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, sum);
+        lanes.iter().sum::<i32>() + super::dot_tail(&a[t..k], &b[t..k])
+    }
+
+    #[target_feature(enable = "avx512f,avx512bw,avx512vnni")]
+    pub unsafe fn dot_avx512_vnni(a: &[i8], b: &[i8]) -> i32 {
+        let k = a.len().min(b.len());
+        let mut acc = _mm512_setzero_si512();
+        let mut t = 0;
+        while t + 32 <= k {
+            let va = _mm512_cvtepi8_epi16(_mm256_loadu_si256(a.as_ptr().add(t) as *const __m256i));
+            let vb = _mm512_cvtepi8_epi16(_mm256_loadu_si256(b.as_ptr().add(t) as *const __m256i));
+            acc = _mm512_dpwssd_epi32(acc, va, vb);
+            t += 32;
+        }
+        _mm512_reduce_add_epi32(acc) + super::dot_tail(&a[t..k], &b[t..k])
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arm {
+    use std::arch::aarch64::*;
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn dot_neon(a: &[i8], b: &[i8]) -> i32 {
+        let k = a.len().min(b.len());
+        let mut acc = vdupq_n_s32(0);
+        let mut t = 0;
+        while t + 16 <= k {
+            let va = vld1q_s8(a.as_ptr().add(t));
+            let vb = vld1q_s8(b.as_ptr().add(t));
+            acc = vpadalq_s16(acc, vmull_s8(vget_low_s8(va), vget_low_s8(vb)));
+            acc = vpadalq_s16(acc, vmull_high_s8(va, vb));
+            t += 16;
+        }
+        vaddvq_s32(acc) + super::dot_tail(&a[t..k], &b[t..k])
+    }
 }
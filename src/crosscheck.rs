@@ -0,0 +1,278 @@
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::attempt::{compute_work_root, Executor};
+use crate::work_hash::{HashKind, WorkSampling};
+use crate::cpu::{CpuExec, CpuKernel};
+use crate::types::{Activation, Overflow, Requant, Rounding, Sizes};
+use crate::matrix_cache;
+use crate::workload::{execute_workload, Workload};
+
+/// One attempt every backend runs from the same (seed, nonce, salt, sizes).
+#[derive(Debug, Clone)]
+pub struct CrossCheckCase {
+    pub name: String,
+    pub workload: Workload,
+    pub sizes: Sizes,
+    pub nonce: u32,
+    pub salt: Option<[u8; 32]>,
+    pub activation: Activation,
+    pub rounding: Rounding,
+    pub overflow: Overflow,
+}
+
+impl CrossCheckCase {
+    fn scale(&self) -> Requant {
+        Requant {
+            activation: self.activation,
+            rounding: self.rounding,
+            overflow: self.overflow,
+            ..Requant::from_salt(self.salt.as_ref())
+        }
+    }
+}
+
+// Odd shapes hit the kernels' tail handling; salted cases requantize with arbitrary scales
+fn standard_cases() -> Vec<CrossCheckCase> {
+    let salt = |tag: &[u8]| Some(*blake3::hash(tag).as_bytes());
+    let case = |name: &str, workload, (m, n, k), nonce, salt, activation| CrossCheckCase {
+        name: name.to_string(),
+        workload,
+        sizes: Sizes { m, n, k, batch: 1 },
+        nonce,
+        salt,
+        activation,
+        rounding: Rounding::TowardZero,
+        overflow: Overflow::Saturate,
+    };
+    vec![
+        case("gemm-64", Workload::Gemm, (64, 64, 64), 0, None, Activation::Relu),
+        case("gemm-256-salted", Workload::Gemm, (256, 256, 256), 1, salt(b"cross-check 1"), Activation::Relu),
+        case("gemm-odd", Workload::Gemm, (67, 45, 1031), 2, salt(b"cross-check 2"), Activation::Identity),
+        case("gemm-row", Workload::Gemm, (1, 513, 257), 3, salt(b"cross-check 3"), Activation::Relu6),
+        case("gemm-leaky", Workload::Gemm, (129, 97, 300), 4, salt(b"cross-check 4"), Activation::Leaky),
+        case("spmm-10pct", Workload::Spmm { density_permille: 100 }, (128, 128, 256), 5, salt(b"cross-check 5"), Activation::Relu),
+        case("spmm-odd", Workload::Spmm { density_permille: 37 }, (71, 33, 509), 6, None, Activation::Identity),
+        CrossCheckCase {
+            rounding: Rounding::Floor,
+            overflow: Overflow::Wrap,
+            ..case("gemm-floor-wrap", Workload::Gemm, (96, 80, 640), 7, salt(b"cross-check 7"), Activation::Identity)
+        },
+        CrossCheckCase {
+            rounding: Rounding::HalfAwayFromZero,
+            ..case("spmm-half-away", Workload::Spmm { density_permille: 250 }, (65, 64, 384), 8, salt(b"cross-check 8"), Activation::Leaky)
+        },
+        // Batched launches offset every item's matrices; odd sides catch a wrong stride
+        CrossCheckCase {
+            sizes: Sizes { m: 33, n: 47, k: 129, batch: 3 },
+            ..case("gemm-batch3", Workload::Gemm, (0, 0, 0), 9, salt(b"cross-check 9"), Activation::Relu)
+        },
+    ]
+}
+
+/// Where one case's output on a backend first differs from the reference.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseDeviation {
+    pub case: String,
+    pub sizes: Sizes,
+    pub mismatched_elements: usize,
+    /// (flat index, expected, got) of the first differing element
+    pub first_mismatch: Option<(usize, i8, i8)>,
+    pub work_root_matches: bool,
+    /// Set when the backend failed to run the case at all.
+    pub error: Option<String>,
+}
+
+/// Every case's outcome on one backend/device/driver combination.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendResult {
+    pub backend: String,
+    pub device_name: String,
+    pub driver_version: String,
+    pub deviations: Vec<CaseDeviation>,
+    pub elapsed_ms: u64,
+}
+
+impl BackendResult {
+    pub fn passed(&self) -> bool {
+        self.deviations.is_empty()
+    }
+
+    pub fn label(&self) -> String {
+        if self.driver_version.is_empty() {
+            format!("{} ({})", self.backend, self.device_name)
+        } else {
+            format!("{} ({}, driver {})", self.backend, self.device_name, self.driver_version)
+        }
+    }
+}
+
+/// Result of `tops-worker cross-check`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrossCheckReport {
+    pub cases: usize,
+    pub backends: Vec<BackendResult>,
+    /// Compiled-in backends that could not be initialised, with the reason.
+    pub unavailable: Vec<(String, String)>,
+}
+
+impl CrossCheckReport {
+    pub fn passed(&self) -> bool {
+        self.backends.iter().all(|b| b.passed())
+    }
+
+    /// Backends whose output differs from the scalar CPU reference.
+    pub fn deviating(&self) -> impl Iterator<Item = &BackendResult> {
+        self.backends.iter().filter(|b| !b.passed())
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = format!("cross-check: {} cases against the scalar CPU reference\n", self.cases);
+        for backend in &self.backends {
+            let status = if backend.passed() { "PASS" } else { "FAIL" };
+            out.push_str(&format!("{:<5} {} in {} ms\n", status, backend.label(), backend.elapsed_ms));
+            for d in &backend.deviations {
+                let detail = match (&d.error, d.first_mismatch) {
+                    (Some(e), _) => format!("error: {}", e),
+                    (None, Some((idx, expected, got))) => format!("{} elements differ, first at {} (expected {}, got {}), work_root {}",
+                        d.mismatched_elements, idx, expected, got, if d.work_root_matches { "matches" } else { "differs" }),
+                    (None, None) => "output length differs".to_string(),
+                };
+                out.push_str(&format!("      {} {}x{}x{}: {}\n", d.case, d.sizes.m, d.sizes.n, d.sizes.k, detail));
+            }
+        }
+        for (backend, reason) in &self.unavailable {
+            out.push_str(&format!("{:<5} {}: {}\n", "SKIP", backend, reason));
+        }
+        out
+    }
+}
+
+type NamedExecutor = (String, Box<dyn Executor>);
+
+// Every backend compiled into this build that initialises on this machine; the
+// scalar CPU kernel is the reference and is not in the list
+fn compiled_backends() -> (Vec<NamedExecutor>, Vec<(String, String)>) {
+    let mut backends: Vec<NamedExecutor> = Vec::new();
+    let mut unavailable = Vec::new();
+    for kernel in [CpuKernel::Avx2, CpuKernel::Avx512Vnni, CpuKernel::Neon] {
+        if kernel.is_supported() {
+            match CpuExec::with_kernel(kernel) {
+                Ok(exec) => backends.push((format!("CPU {}", kernel), Box::new(exec))),
+                Err(e) => unavailable.push((format!("CPU {}", kernel), e.to_string())),
+            }
+        }
+    }
+    #[cfg(feature = "gpu")]
+    match crate::gpu::GpuExec::new() {
+        Ok(exec) => backends.push(("OpenCL".to_string(), Box::new(exec))),
+        Err(e) => unavailable.push(("OpenCL".to_string(), e.to_string())),
+    }
+    #[cfg(feature = "cuda")]
+    match crate::gpu_cuda::CudaExec::new() {
+        Ok(exec) => backends.push(("CUDA".to_string(), Box::new(exec))),
+        Err(e) => unavailable.push(("CUDA".to_string(), e.to_string())),
+    }
+    #[cfg(feature = "metal")]
+    match crate::gpu_metal::MetalExec::new() {
+        Ok(exec) => backends.push(("Metal".to_string(), Box::new(exec))),
+        Err(e) => unavailable.push(("Metal".to_string(), e.to_string())),
+    }
+    (backends, unavailable)
+}
+
+/// Run the standard cases on every compiled backend and compare each Y and
+/// work_root bit-exactly against the scalar CPU kernel.
+pub fn run_cross_check() -> anyhow::Result<CrossCheckReport> {
+    let cases = standard_cases();
+    let prev_hash = *blake3::hash(b"tops-worker cross-check v1").as_bytes();
+    let reference = CpuExec::with_kernel(CpuKernel::Scalar)?;
+
+    // Reference outputs once, compared against every backend
+    let mut expected = Vec::with_capacity(cases.len());
+    let start = Instant::now();
+    for case in &cases {
+        let input = matrix_cache::generate_cached(case.workload, &prev_hash, case.nonce, case.salt.as_ref(), &case.sizes);
+        let y = execute_workload(&reference, &input, &case.sizes, case.scale())?;
+        let (work_root, _) = compute_work_root(&y, HashKind::default(), WorkSampling::Seeded, &prev_hash, case.nonce);
+        expected.push((input, y, work_root));
+    }
+    let mut results = vec![BackendResult {
+        deviations: Vec::new(),
+        elapsed_ms: start.elapsed().as_millis() as u64,
+        ..result_for(format!("CPU {}", CpuKernel::Scalar), &reference)
+    }];
+
+    let (backends, unavailable) = compiled_backends();
+    for (name, backend) in &backends {
+        let mut result = result_for(name.clone(), &**backend);
+        let capabilities = backend.capabilities();
+        let start = Instant::now();
+        // Workloads the backend refuses never reach it in production
+        for (case, (input, y_ref, root_ref)) in cases.iter().zip(&expected).filter(|(case, _)| capabilities.supports(case.workload.kind())) {
+            let deviation = match execute_workload(&**backend, input, &case.sizes, case.scale()) {
+                Ok(y) => compare(case, &prev_hash, y_ref, root_ref, &y),
+                Err(e) => Some(CaseDeviation {
+                    case: case.name.clone(),
+                    sizes: case.sizes.clone(),
+                    mismatched_elements: y_ref.len(),
+                    first_mismatch: None,
+                    work_root_matches: false,
+                    error: Some(e.to_string()),
+                }),
+            };
+            result.deviations.extend(deviation);
+        }
+        result.elapsed_ms = start.elapsed().as_millis() as u64;
+        results.push(result);
+    }
+
+    Ok(CrossCheckReport { cases: cases.len(), backends: results, unavailable })
+}
+
+fn result_for<E: Executor + ?Sized>(backend: String, executor: &E) -> BackendResult {
+    let info = executor.device_info();
+    BackendResult {
+        backend,
+        device_name: info.device_name,
+        driver_version: info.driver_version,
+        deviations: Vec::new(),
+        elapsed_ms: 0,
+    }
+}
+
+fn compare(case: &CrossCheckCase, prev_hash: &[u8; 32], expected: &[i8], root: &[u8; 32], got: &[i8]) -> Option<CaseDeviation> {
+    let work_root_matches = compute_work_root(got, HashKind::default(), WorkSampling::Seeded, prev_hash, case.nonce).0 == *root;
+    if expected == got && work_root_matches {
+        return None;
+    }
+    let mut mismatched_elements = expected.len().abs_diff(got.len());
+    let mut first_mismatch = None;
+    for (idx, (&e, &g)) in expected.iter().zip(got).enumerate() {
+        if e != g {
+            mismatched_elements += 1;
+            first_mismatch.get_or_insert((idx, e, g));
+        }
+    }
+    Some(CaseDeviation {
+        case: case.name.clone(),
+        sizes: case.sizes.clone(),
+        mismatched_elements,
+        first_mismatch,
+        work_root_matches,
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every backend this build and machine can run (the SIMD CPU kernels always, the
+    // GPUs when compiled in and present) matches the scalar kernel on the fixed cases
+    #[test]
+    fn compiled_backends_match_the_reference() {
+        let report = run_cross_check().unwrap();
+        assert_eq!(report.cases, standard_cases().len());
+        assert!(report.passed(), "{}", report.render());
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::memhard::MemHardParams;
+use crate::types::Sizes;
+use crate::workload::Workload;
+
+/// Share of device memory the attempts plan to use; the rest is left to the
+/// driver, the kernels' own scratch space and other processes.
+const USABLE_FRACTION: f64 = 0.8;
+/// Sides never step down below this.
+const MIN_SIDE: usize = 64;
+
+/// Device memory as reported by the backend at the time of the query.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct DeviceMemory {
+    pub total_bytes: u64,
+    /// Free memory, when the driver reports it (CUDA `cuMemGetInfo`).
+    pub free_bytes: Option<u64>,
+    /// Largest single buffer the device allows (OpenCL `CL_DEVICE_MAX_MEM_ALLOC_SIZE`).
+    pub max_alloc_bytes: Option<u64>,
+}
+
+impl DeviceMemory {
+    /// Whether `copies` concurrent attempts with this footprint fit. Free memory
+    /// already excludes what the worker holds, so `held_bytes` is added back.
+    pub fn fits(&self, footprint: &Footprint, copies: usize, held_bytes: u64) -> bool {
+        let available = self.free_bytes.map_or(self.total_bytes, |free| (free + held_bytes).min(self.total_bytes));
+        let within_total = footprint.total_bytes * copies as u64 <= (available as f64 * USABLE_FRACTION) as u64;
+        within_total && self.max_alloc_bytes.is_none_or(|max| footprint.largest_buffer_bytes <= max)
+    }
+}
+
+/// Device buffers one attempt allocates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Footprint {
+    pub total_bytes: u64,
+    pub largest_buffer_bytes: u64,
+}
+
+impl Footprint {
+    pub fn of(workload: Workload, memhard: Option<&MemHardParams>, sizes: &Sizes) -> Self {
+        let (m, n, k) = (sizes.m as u64, sizes.n as u64, sizes.k as u64);
+        let mut buffers = match workload {
+            Workload::Gemm => {
+                // Batched attempts hold every item's matrices at once
+                let batch = sizes.batch.max(1) as u64;
+                vec![batch * m * k, batch * k * n, batch * m * n]
+            }
+            Workload::Spmm { density_permille } => {
+                let nnz = m * k * u64::from(density_permille.min(1000)) / 1000;
+                // row_ptr and col_idx are u32, values and the dense matrices int8
+                vec![4 * (m + 1), 4 * nnz, nnz, k * n, m * n]
+            }
+        };
+        if let Some(params) = memhard {
+            buffers.push(u64::from(params.mem_kib) * 1024);
+        }
+        Footprint {
+            total_bytes: buffers.iter().sum(),
+            largest_buffer_bytes: buffers.into_iter().max().unwrap_or(0),
+        }
+    }
+}
+
+/// A device allocation failed; the attempt does not fit in device memory at its sizes.
+#[derive(Error, Debug)]
+#[error("out of device memory: {0}")]
+pub struct OutOfDeviceMemory(pub String);
+
+/// Whether `error` (or anything it wraps) is an `OutOfDeviceMemory`.
+pub fn is_out_of_memory(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.downcast_ref::<OutOfDeviceMemory>().is_some())
+}
+
+/// The next smaller sizes with roughly the same proportions: every side shrinks by
+/// a quarter, rounded down to a multiple of 64. `None` once all sides are at the minimum.
+pub fn step_down(sizes: &Sizes) -> Option<Sizes> {
+    let shrink = |side: usize| (side * 3 / 4 / MIN_SIDE * MIN_SIDE).max(MIN_SIDE).min(side);
+    let smaller = Sizes { m: shrink(sizes.m), n: shrink(sizes.n), k: shrink(sizes.k), batch: sizes.batch };
+    ((smaller.m, smaller.n, smaller.k) != (sizes.m, sizes.n, sizes.k)).then_some(smaller)
+}
+
+/// The largest sizes, stepping down from `sizes`, whose attempts fit in `memory`.
+/// Returns `sizes` unchanged when they fit, and the minimum when nothing does.
+pub fn fit_sizes(sizes: &Sizes, workload: Workload, memhard: Option<&MemHardParams>, memory: &DeviceMemory, copies: usize) -> Sizes {
+    let mut fitted = sizes.clone();
+    while !memory.fits(&Footprint::of(workload, memhard, &fitted), copies, 0) {
+        match step_down(&fitted) {
+            Some(smaller) => fitted = smaller,
+            None => break,
+        }
+    }
+    fitted
+}
@@ -0,0 +1,170 @@
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::gpu::{LocalWorkSize, TransferReport, WorkGroupLimits};
+use crate::types::DeviceInfo;
+
+// Backends that failed to initialise at startup, with the error
+static INIT_ERRORS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+// Device the attempts run on, once the backend is up
+static SELECTED: Mutex<Option<DeviceInfo>> = Mutex::new(None);
+// PCI address of that device, when the backend reports one
+static SELECTED_PCI_ADDRESS: Mutex<Option<String>> = Mutex::new(None);
+// OpenCL work-group limits and the local sizes chosen from them
+static WORK_GROUP: Mutex<Option<WorkGroupReport>> = Mutex::new(None);
+// OpenCL host transfer strategy and its calibration
+static TRANSFER: Mutex<Option<TransferReport>> = Mutex::new(None);
+
+/// One compute device as seen by a backend.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProbedDevice {
+    pub backend: String,
+    /// OpenCL platform; CUDA and the CPU have none.
+    pub platform: Option<String>,
+    pub name: String,
+    pub driver_version: String,
+    pub memory_bytes: Option<u64>,
+    /// OpenCL compute units, CUDA multiprocessors, CPU threads.
+    pub compute_units: Option<u32>,
+    /// The device the worker runs its attempts on.
+    pub selected: bool,
+}
+
+/// Result of asking one backend for its devices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendProbe {
+    pub backend: String,
+    pub devices: Vec<ProbedDevice>,
+    /// Why the backend could not be enumerated.
+    pub error: Option<String>,
+    /// Why the worker could not initialise the backend at startup, if it tried and failed.
+    pub init_error: Option<String>,
+}
+
+/// The OpenCL device's work-group limits and the local sizes the GEMM kernel ran with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkGroupReport {
+    pub limits: WorkGroupLimits,
+    /// One per output shape, in the order they were first run.
+    pub local_work_sizes: Vec<LocalWorkSize>,
+}
+
+/// Every device every compiled backend can see, served at `/devices`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceInventory {
+    /// `None` until a backend has been initialised.
+    pub selected: Option<DeviceInfo>,
+    pub backends: Vec<BackendProbe>,
+    /// `None` unless the OpenCL backend is up.
+    pub work_group: Option<WorkGroupReport>,
+    /// `None` unless the OpenCL backend is up.
+    pub transfer: Option<TransferReport>,
+    pub probed_at: String,
+}
+
+/// Remember the device the worker runs on, for `/devices`.
+pub fn record_selected(device: &DeviceInfo) {
+    if let Ok(mut selected) = SELECTED.lock() {
+        *selected = Some(device.clone());
+    }
+}
+
+/// Remember the PCI address of the device the worker runs on, so host sensors are
+/// read for that device only.
+pub fn record_selected_pci_address(address: Option<String>) {
+    if let Ok(mut selected) = SELECTED_PCI_ADDRESS.lock() {
+        *selected = address;
+    }
+}
+
+/// PCI address of the device the worker runs on, if the backend reported one.
+pub fn selected_pci_address() -> Option<String> {
+    SELECTED_PCI_ADDRESS.lock().ok().and_then(|selected| selected.clone())
+}
+
+/// Remember that `backend` failed to initialise, for `/devices`.
+pub fn record_init_error(backend: &str, error: &str) {
+    if let Ok(mut errors) = INIT_ERRORS.lock() {
+        errors.push((backend.to_string(), error.to_string()));
+    }
+}
+
+/// Remember the OpenCL device's work-group limits, for `/devices`.
+pub fn record_work_group(limits: WorkGroupLimits) {
+    if let Ok(mut work_group) = WORK_GROUP.lock() {
+        *work_group = Some(WorkGroupReport { limits, local_work_sizes: Vec::new() });
+    }
+}
+
+/// Remember a local size the GEMM kernel ran with; false if this shape already has one.
+pub fn record_local_work_size(choice: LocalWorkSize) -> bool {
+    let Ok(mut work_group) = WORK_GROUP.lock() else { return false };
+    let Some(report) = work_group.as_mut() else { return false };
+    if report.local_work_sizes.iter().any(|c| c.m == choice.m && c.n == choice.n) {
+        return false;
+    }
+    report.local_work_sizes.push(choice);
+    true
+}
+
+/// Remember the OpenCL host transfer strategy, for `/devices`.
+pub fn record_transfer(report: TransferReport) {
+    if let Ok(mut transfer) = TRANSFER.lock() {
+        *transfer = Some(report);
+    }
+}
+
+fn init_error(backend: &str) -> Option<String> {
+    INIT_ERRORS.lock().ok()?.iter().rev()
+        .find(|(b, _)| b == backend)
+        .map(|(_, error)| error.clone())
+}
+
+fn backend_probe(backend: &str, devices: anyhow::Result<Vec<ProbedDevice>>, selected: Option<&DeviceInfo>) -> BackendProbe {
+    let (mut devices, error) = match devices {
+        Ok(devices) => (devices, None),
+        Err(e) => (Vec::new(), Some(e.to_string())),
+    };
+    // Backends pick the first matching device, so the first one with its name is ours
+    if let Some(selected) = selected.filter(|s| s.backend == backend) {
+        if let Some(device) = devices.iter_mut().find(|d| d.name == selected.device_name) {
+            device.selected = true;
+        }
+    }
+    BackendProbe { backend: backend.to_string(), devices, error, init_error: init_error(backend) }
+}
+
+fn cpu_device(selected: Option<&DeviceInfo>) -> ProbedDevice {
+    let cpu = crate::cpu::dispatch();
+    ProbedDevice {
+        backend: "CPU".into(),
+        platform: None,
+        name: format!("{} [{}], {} kernel", cpu.arch, cpu.features.join(", "), cpu.kernel),
+        driver_version: String::new(),
+        memory_bytes: None,
+        compute_units: std::thread::available_parallelism().ok().map(|n| n.get() as u32),
+        selected: selected.is_some_and(|s| s.backend == "CPU"),
+    }
+}
+
+/// Enumerate the devices of every compiled backend now. Enumeration does not
+/// create contexts, so it is safe while attempts are running.
+pub fn probe() -> DeviceInventory {
+    let selected = SELECTED.lock().ok().and_then(|s| s.clone());
+    let selected = selected.as_ref();
+    let mut backends = Vec::new();
+    #[cfg(feature = "gpu")]
+    backends.push(backend_probe("OpenCL", crate::gpu::probe_devices(), selected));
+    #[cfg(feature = "cuda")]
+    backends.push(backend_probe("CUDA", crate::gpu_cuda::probe_devices(), selected));
+    #[cfg(feature = "metal")]
+    backends.push(backend_probe("Metal", crate::gpu_metal::probe_devices(), selected));
+    let cpu = cpu_device(selected);
+    backends.push(backend_probe("CPU", Ok(vec![cpu]), selected));
+    DeviceInventory {
+        selected: selected.cloned(),
+        backends,
+        work_group: WORK_GROUP.lock().ok().and_then(|w| w.clone()),
+        transfer: TRANSFER.lock().ok().and_then(|t| t.clone()),
+        probed_at: chrono::Utc::now().to_rfc3339(),
+    }
+}
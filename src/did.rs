@@ -0,0 +1,167 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::config::Config;
+use crate::signing::Secp;
+
+// Context string for deriving the receipt signing key from a DID seed; v2 length-prefixes
+// the seed and DID so different (seed, did) splits of the same bytes derive different keys
+const DID_KEY_CONTEXT: &str = "tops-worker did secp256k1 signing key v2";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DidVerificationState {
+    /// The DID document lists our public key.
+    Verified,
+    /// The DID exists but has no attribute for our key yet.
+    Missing,
+    /// The DID document lists a different key.
+    Mismatch,
+    /// The RPC could not be reached or returned garbage.
+    Error,
+    /// No PEAQ_RPC_URL configured.
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DidVerification {
+    pub did: String,
+    pub pubkey_hex: String,
+    pub state: DidVerificationState,
+    pub detail: Option<String>,
+    pub checked_at: String,
+}
+
+impl DidVerification {
+    pub fn is_ok(&self) -> bool {
+        matches!(self.state, DidVerificationState::Verified | DidVerificationState::Skipped)
+    }
+}
+
+/// Attribute payload an operator registers under the DID so the aggregator can
+/// tie receipts to it: the compressed pubkey plus a proof-of-possession signature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DidRegistration {
+    pub did: String,
+    pub attribute_name: String,
+    pub pubkey_hex: String,
+    pub proof_sig_hex: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse {
+    result: Option<RpcAttribute>,
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcAttribute {
+    value: String,
+}
+
+/// Resolve the receipt signing key: derived from DID_KEY_SEED_HEX when set, else WORKER_SK_HEX.
+pub fn resolve_signing_key(config: &Config) -> anyhow::Result<Secp> {
+    match &config.did_key_seed_hex {
+        Some(seed_hex) => {
+            let seed = hex::decode(seed_hex)?;
+            Secp::from_hex(&derive_signing_key_hex(&seed, &config.device_did)?)
+        }
+        None => Secp::from_hex(&config.worker_sk_hex),
+    }
+}
+
+/// Deterministically derive a secp256k1 secret key bound to `did` from a seed.
+pub fn derive_signing_key_hex(seed: &[u8], did: &str) -> anyhow::Result<String> {
+    // Retry with a counter in the (astronomically unlikely) case the output is not a valid scalar
+    for counter in 0u32..16 {
+        let mut material = Vec::with_capacity(seed.len() + did.len() + 20);
+        material.extend_from_slice(&(seed.len() as u64).to_le_bytes());
+        material.extend_from_slice(seed);
+        material.extend_from_slice(&(did.len() as u64).to_le_bytes());
+        material.extend_from_slice(did.as_bytes());
+        material.extend_from_slice(&counter.to_le_bytes());
+        let candidate = blake3::derive_key(DID_KEY_CONTEXT, &material);
+        let candidate_hex = hex::encode(candidate);
+        if Secp::from_hex(&candidate_hex).is_ok() {
+            return Ok(candidate_hex);
+        }
+    }
+    Err(anyhow::anyhow!("could not derive a valid signing key from DID seed"))
+}
+
+/// Build the attribute an operator needs to add to the DID document.
+pub fn registration_payload(secp: &Secp, did: &str, attribute_name: &str) -> anyhow::Result<DidRegistration> {
+    let pubkey_hex = secp.pubkey_hex_compressed();
+    let proof = format!("{}|{}|{}", did, attribute_name, pubkey_hex);
+    Ok(DidRegistration {
+        did: did.to_string(),
+        attribute_name: attribute_name.to_string(),
+        pubkey_hex,
+        proof_sig_hex: secp.sign_payload(proof.as_bytes())?,
+    })
+}
+
+/// Extract the account part of a `did:peaq:<address>` identifier.
+pub fn did_account(did: &str) -> Option<&str> {
+    did.strip_prefix("did:peaq:").filter(|a| !a.is_empty())
+}
+
+/// Check via the peaq RPC that the document of `did` carries our public key.
+pub async fn verify_did(config: &Config, did: &str, secp: &Secp) -> DidVerification {
+    let pubkey_hex = secp.pubkey_hex_compressed();
+    let result = |state, detail: Option<String>| DidVerification {
+        did: did.to_string(),
+        pubkey_hex: pubkey_hex.clone(),
+        state,
+        detail,
+        checked_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    let Some(rpc_url) = &config.peaq_rpc_url else {
+        return result(DidVerificationState::Skipped, None);
+    };
+    let Some(account) = did_account(did) else {
+        return result(DidVerificationState::Error, Some(format!("'{}' is not a did:peaq identifier", did)));
+    };
+
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "peaqdid_readAttribute",
+        "params": [account, format!("0x{}", hex::encode(config.did_key_attribute.as_bytes())), null],
+    });
+
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(10)).build() {
+        Ok(c) => c,
+        Err(e) => return result(DidVerificationState::Error, Some(e.to_string())),
+    };
+    let response = match client.post(rpc_url).json(&request).send().await {
+        Ok(r) => r,
+        Err(e) => return result(DidVerificationState::Error, Some(format!("RPC request failed: {}", e))),
+    };
+    let parsed: RpcResponse = match response.json().await {
+        Ok(p) => p,
+        Err(e) => return result(DidVerificationState::Error, Some(format!("invalid RPC response: {}", e))),
+    };
+    if let Some(err) = parsed.error {
+        return result(DidVerificationState::Error, Some(format!("RPC error: {}", err)));
+    }
+    let Some(attr) = parsed.result else {
+        return result(DidVerificationState::Missing, Some(format!("attribute '{}' not set", config.did_key_attribute)));
+    };
+
+    if attribute_contains_pubkey(&attr.value, &pubkey_hex) {
+        result(DidVerificationState::Verified, None)
+    } else {
+        result(DidVerificationState::Mismatch, Some(format!("attribute '{}' does not contain our pubkey", config.did_key_attribute)))
+    }
+}
+
+// The attribute value is hex-encoded bytes; accept either the raw key bytes or its hex text
+fn attribute_contains_pubkey(value_hex: &str, pubkey_hex: &str) -> bool {
+    let Ok(bytes) = hex::decode(value_hex.trim_start_matches("0x")) else { return false };
+    let Ok(pubkey) = hex::decode(pubkey_hex) else { return false };
+    if bytes.windows(pubkey.len()).any(|w| w == pubkey.as_slice()) {
+        return true;
+    }
+    String::from_utf8_lossy(&bytes).to_ascii_lowercase().contains(&pubkey_hex.to_ascii_lowercase())
+}
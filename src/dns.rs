@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::config::Config;
+use crate::net::{ConnectionStats, IpFamily};
+use crate::log_warn;
+
+/// One `host:ip` entry of `AGGREGATOR_RESOLVE`. IPv6 addresses may be written
+/// bare or in brackets (`host:[2001:db8::1]`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolveOverride {
+    pub host: String,
+    pub ip: IpAddr,
+}
+
+impl std::str::FromStr for ResolveOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Host names never contain ':', so the first one ends the host even before an IPv6 address
+        let (host, ip) = s.trim().split_once(':')
+            .ok_or_else(|| format!("'{}' is not host:ip", s))?;
+        if host.is_empty() {
+            return Err(format!("'{}' has no host", s));
+        }
+        let ip = ip.strip_prefix('[').and_then(|ip| ip.strip_suffix(']')).unwrap_or(ip);
+        let ip = ip.parse().map_err(|_| format!("'{}' is not an IP address", ip))?;
+        Ok(ResolveOverride { host: host.to_ascii_lowercase(), ip })
+    }
+}
+
+impl std::fmt::Display for ResolveOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.ip {
+            IpAddr::V4(ip) => write!(f, "{}:{}", self.host, ip),
+            IpAddr::V6(ip) => write!(f, "{}:[{}]", self.host, ip),
+        }
+    }
+}
+
+/// Parse a comma-separated `AGGREGATOR_RESOLVE`; a host may be listed once per address.
+pub fn parse_overrides(s: &str) -> Result<Vec<ResolveOverride>, String> {
+    s.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// A host name that did not resolve. Its own type, so the failure is classified
+/// as `FailureKind::Dns` however deep the HTTP stack wraps it.
+#[derive(Debug, Error)]
+pub enum ResolveError {
+    #[error("dns lookup of {host} failed: {source}")]
+    Lookup { host: String, source: std::io::Error },
+    #[error("dns lookup of {host} returned no {family} address")]
+    NoAddress { host: String, family: IpFamily },
+}
+
+// Answer of the latest successful lookup of a host
+struct CachedLookup {
+    ips: Vec<IpAddr>,
+    resolved_at: Instant,
+}
+
+/// Resolver of the aggregator client (`AGGREGATOR_DNS_*`, `AGGREGATOR_RESOLVE`).
+///
+/// Answers are cached for `ttl` so a burst of new connections does not hit a
+/// flaky resolver once each, and when a lookup fails the previous answer is used
+/// for up to `stale_for` after it was resolved. `AGGREGATOR_RESOLVE` entries are
+/// answered without a lookup at all.
+///
+/// With `happy_eyeballs` both families are returned, the system's preferred one
+/// first, and the connector races them (RFC 6555): the other family is tried
+/// 300 ms after the first if that has not connected yet. Without it only the
+/// addresses of the first family are returned and tried one after another.
+pub struct AggregatorResolver {
+    family: IpFamily,
+    happy_eyeballs: bool,
+    ttl: Duration,
+    stale_for: Duration,
+    overrides: HashMap<String, Vec<IpAddr>>,
+    cache: Arc<Mutex<HashMap<String, CachedLookup>>>,
+    stats: Option<Arc<ConnectionStats>>,
+}
+
+impl AggregatorResolver {
+    pub fn new(family: IpFamily) -> Self {
+        Self {
+            family,
+            happy_eyeballs: true,
+            ttl: Duration::ZERO,
+            stale_for: Duration::ZERO,
+            overrides: HashMap::new(),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            stats: None,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.aggregator_ip_family)
+            .with_happy_eyeballs(config.aggregator_happy_eyeballs)
+            .with_ttl(config.get_aggregator_dns_ttl())
+            .with_stale_for(config.get_aggregator_dns_stale())
+            .with_overrides(&config.aggregator_resolve)
+    }
+
+    pub fn with_happy_eyeballs(mut self, enabled: bool) -> Self {
+        self.happy_eyeballs = enabled;
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_stale_for(mut self, stale_for: Duration) -> Self {
+        self.stale_for = stale_for;
+        self
+    }
+
+    pub fn with_overrides(mut self, overrides: &[ResolveOverride]) -> Self {
+        for entry in overrides {
+            self.overrides.entry(entry.host.clone()).or_default().push(entry.ip);
+        }
+        self
+    }
+
+    /// Report lookups to `stats` (and its Prometheus metrics).
+    pub fn with_stats(mut self, stats: Arc<ConnectionStats>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+}
+
+impl Resolve for AggregatorResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_ascii_lowercase();
+        let (family, happy_eyeballs) = (self.family, self.happy_eyeballs);
+        let stats = self.stats.clone();
+        let record = move |outcome: &'static str| {
+            if let Some(stats) = &stats {
+                stats.record_dns(outcome);
+            }
+        };
+
+        if let Some(ips) = self.overrides.get(&host) {
+            record("override");
+            let answer = arrange(ips.clone(), family, happy_eyeballs)
+                .ok_or_else(|| ResolveError::NoAddress { host, family }.into());
+            return Box::pin(std::future::ready(answer));
+        }
+        if let Some(ips) = cached(&self.cache, &host, self.ttl) {
+            record("cached");
+            let answer = arrange(ips, family, happy_eyeballs)
+                .ok_or_else(|| ResolveError::NoAddress { host, family }.into());
+            return Box::pin(std::future::ready(answer));
+        }
+
+        let cache = Arc::clone(&self.cache);
+        let stale_for = self.stale_for;
+        Box::pin(async move {
+            let looked_up = tokio::net::lookup_host((host.as_str(), 0)).await
+                .map(|addrs| addrs.map(|addr| addr.ip()).collect::<Vec<_>>());
+            let error = match looked_up {
+                Ok(ips) => match arrange(ips.clone(), family, happy_eyeballs) {
+                    Some(addrs) => {
+                        if let Ok(mut cache) = cache.lock() {
+                            cache.insert(host, CachedLookup { ips, resolved_at: Instant::now() });
+                        }
+                        record("resolved");
+                        return Ok(addrs);
+                    }
+                    None => ResolveError::NoAddress { host: host.clone(), family },
+                },
+                Err(source) => ResolveError::Lookup { host: host.clone(), source },
+            };
+            if let Some(ips) = cached(&cache, &host, stale_for) {
+                if let Some(addrs) = arrange(ips, family, happy_eyeballs) {
+                    log_warn!("[dns] {}; using the previous answer", error);
+                    record("stale");
+                    return Ok(addrs);
+                }
+            }
+            record("failed");
+            Err(error.into())
+        })
+    }
+}
+
+// The cached answer for `host` if it was resolved less than `max_age` ago
+fn cached(cache: &Mutex<HashMap<String, CachedLookup>>, host: &str, max_age: Duration) -> Option<Vec<IpAddr>> {
+    let cache = cache.lock().ok()?;
+    let entry = cache.get(host)?;
+    (entry.resolved_at.elapsed() < max_age).then(|| entry.ips.clone())
+}
+
+// The addresses to connect to, in order, or `None` when none is of `family`. The
+// connector puts the first address's family first and races the rest after it.
+fn arrange(mut ips: Vec<IpAddr>, family: IpFamily, happy_eyeballs: bool) -> Option<Addrs> {
+    ips.retain(|ip| family.allows(ip));
+    let first = *ips.first()?;
+    if !happy_eyeballs {
+        ips.retain(|ip| ip.is_ipv6() == first.is_ipv6());
+    }
+    Some(Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0))) as Addrs)
+}
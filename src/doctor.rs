@@ -0,0 +1,311 @@
+use std::time::{Duration, Instant};
+use crate::attempt::{run_workload_attempt, Executor};
+use crate::config::Config;
+use crate::signer::RemoteSigner;
+use crate::submit::AggregatorProtocol;
+use crate::types::{DeviceInfo, Sizes};
+use crate::workload::ProofWorkload;
+
+// Small enough that even the CPU backend completes several attempts per second
+const BENCH_SIZES: Sizes = Sizes { m: 256, n: 256, k: 256, batch: 1 };
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl std::fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckStatus::Pass => f.pad("PASS"),
+            CheckStatus::Warn => f.pad("WARN"),
+            CheckStatus::Fail => f.pad("FAIL"),
+        }
+    }
+}
+
+/// Outcome of one preflight check, with what to do about it when it did not pass.
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    pub fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status: CheckStatus::Pass, detail: detail.into(), hint: None }
+    }
+
+    pub fn warn(name: impl Into<String>, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name: name.into(), status: CheckStatus::Warn, detail: detail.into(), hint: Some(hint.into()) }
+    }
+
+    pub fn fail(name: impl Into<String>, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name: name.into(), status: CheckStatus::Fail, detail: detail.into(), hint: Some(hint.into()) }
+    }
+}
+
+/// Results of `tops-worker doctor`, printed as a pass/fail table.
+#[derive(Debug, Default)]
+pub struct DoctorReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl DoctorReport {
+    pub fn push(&mut self, check: CheckResult) {
+        self.checks.push(check);
+    }
+
+    pub fn extend(&mut self, checks: impl IntoIterator<Item = CheckResult>) {
+        self.checks.extend(checks);
+    }
+
+    /// True when no check failed; warnings do not count.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.status != CheckStatus::Fail)
+    }
+
+    pub fn render(&self) -> String {
+        let width = self.checks.iter().map(|c| c.name.len()).max().unwrap_or(0).max("CHECK".len());
+        let mut out = format!("{:<width$}  STATUS  DETAIL\n", "CHECK", width = width);
+        for check in &self.checks {
+            out.push_str(&format!("{:<width$}  {:<6}  {}\n", check.name, check.status, check.detail, width = width));
+            if let Some(hint) = &check.hint {
+                out.push_str(&format!("{:<width$}          -> {}\n", "", hint, width = width));
+            }
+        }
+        let failed = self.checks.iter().filter(|c| c.status == CheckStatus::Fail).count();
+        let warned = self.checks.iter().filter(|c| c.status == CheckStatus::Warn).count();
+        out.push_str(&format!("\n{} checks, {} failed, {} warnings\n", self.checks.len(), failed, warned));
+        out
+    }
+}
+
+/// Load and validate the configuration from the environment.
+pub fn check_config() -> (Option<Config>, CheckResult) {
+    let loaded = Config::from_env().and_then(|config| config.validate().map(|_| config));
+    match loaded {
+        Ok(config) => {
+            let check = CheckResult::pass("config", format!("{} aggregator URL(s), protocol {}",
+                config.aggregator_urls.len(), config.aggregator_protocol));
+            (Some(config), check)
+        }
+        Err(e) => (None, CheckResult::fail("config", e.to_string(),
+            "fix the environment variable named above; see PRODUCTION_FEATURES.md for valid values")),
+    }
+}
+
+/// Load every signing key and derive its public key.
+pub fn check_keys(config: &Config) -> Vec<CheckResult> {
+    config.get_identities()
+        .into_iter()
+        .map(|spec| {
+            let name = format!("key {}", spec.device_did);
+            match spec.key.load(&spec.device_did, config.did_key_seed_hex.as_deref()) {
+                Ok(secp) => CheckResult::pass(name, format!("pubkey {}", secp.pubkey_hex_compressed())),
+                Err(e) => CheckResult::fail(name, format!("{} ({})", e, spec.key),
+                    "keys are 32-byte secp256k1 secrets as 64 hex characters; check WORKER_SK_HEX / WORKER_IDENTITIES"),
+            }
+        })
+        .collect()
+}
+
+/// Fetch the identities a remote signer (`REMOTE_SIGNER_URL`) holds for this worker.
+pub async fn check_remote_signer(config: &Config) -> CheckResult {
+    const HINT: &str = "check REMOTE_SIGNER_URL and REMOTE_SIGNER_TOKEN, and that the signer's SIGNER_ALLOWLIST admits this host";
+    let signer = match RemoteSigner::from_config(config) {
+        Ok(Some(signer)) => signer,
+        Ok(None) => return CheckResult::pass("remote signer", "not configured"),
+        Err(e) => return CheckResult::fail("remote signer", e.to_string(), HINT),
+    };
+    match signer.identities().await {
+        Ok(identities) if identities.is_empty() => CheckResult::fail("remote signer",
+            format!("{} holds no identities", signer.url()), "configure WORKER_SK_HEX or WORKER_IDENTITIES on the signer host"),
+        Ok(identities) => CheckResult::pass("remote signer", format!("{} identit{} at {}",
+            identities.len(), if identities.len() == 1 { "y" } else { "ies" }, signer.url())),
+        Err(e) => CheckResult::fail("remote signer", e.to_string(), HINT),
+    }
+}
+
+/// Resolve and contact every configured aggregator endpoint.
+pub async fn check_aggregator(config: &Config) -> Vec<CheckResult> {
+    match config.aggregator_protocol {
+        AggregatorProtocol::Http => {
+            let client = match crate::net::aggregator_client(config) {
+                Ok(client) => client,
+                Err(e) => return vec![CheckResult::fail("aggregator", e.to_string(),
+                    "check AGGREGATOR_PROXY and the AGGREGATOR_BIND_* settings")],
+            };
+            let mut checks = Vec::new();
+            for url in &config.aggregator_urls {
+                checks.push(check_http_endpoint(config, &client, url).await);
+            }
+            checks
+        }
+        AggregatorProtocol::Grpc => vec![check_tcp_endpoint("aggregator grpc", config.grpc_url.as_deref(), 443).await],
+        AggregatorProtocol::Mqtt => vec![check_tcp_endpoint("aggregator mqtt", config.mqtt_url.as_deref(), 1883).await],
+    }
+}
+
+async fn check_http_endpoint(config: &Config, client: &reqwest::Client, url: &str) -> CheckResult {
+    let name = format!("aggregator {}", url);
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return CheckResult::fail(name, format!("invalid URL: {}", e), "AGGREGATOR_URL must be an http(s) URL"),
+    };
+    let host = parsed.host_str().unwrap_or_default();
+    // Hosts in AGGREGATOR_RESOLVE are never looked up by the client
+    let overridden = config.aggregator_resolve.iter().any(|entry| entry.host == host);
+    if config.aggregator_proxy.is_none() && !overridden {
+        let port = parsed.port_or_known_default().unwrap_or(80);
+        if let Err(check) = resolve(&name, host, port).await {
+            return check;
+        }
+    }
+    let start = Instant::now();
+    match client.request(reqwest::Method::OPTIONS, url).timeout(CONNECT_TIMEOUT).send().await {
+        // Any HTTP answer proves the path works; the method may well be unsupported
+        Ok(resp) => CheckResult::pass(name, format!("HTTP {} in {} ms", resp.status().as_u16(), start.elapsed().as_millis())),
+        Err(e) if e.is_timeout() => CheckResult::fail(name, format!("no answer within {}s", CONNECT_TIMEOUT.as_secs()),
+            "a firewall may be dropping traffic; check outbound rules and AGGREGATOR_PROXY"),
+        // reqwest's own message is generic; the cause (refused, TLS, ...) is in the chain
+        Err(e) => CheckResult::fail(name, format!("{:#}", anyhow::Error::from(e)),
+            "check that the aggregator is up and the URL, port and TLS scheme are right"),
+    }
+}
+
+// Plain TCP reachability for transports without a cheap request to send
+async fn check_tcp_endpoint(name: &str, url: Option<&str>, default_port: u16) -> CheckResult {
+    let Some(url) = url else {
+        return CheckResult::fail(name, "no URL configured", "set GRPC_URL or MQTT_URL for the selected AGGREGATOR_PROTOCOL");
+    };
+    let name = format!("{} {}", name, url);
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return CheckResult::fail(name, format!("invalid URL: {}", e), "use scheme://host[:port]"),
+    };
+    let host = parsed.host_str().unwrap_or_default().to_string();
+    let port = parsed.port().unwrap_or(default_port);
+    let addrs = match resolve(&name, &host, port).await {
+        Ok(addrs) => addrs,
+        Err(check) => return check,
+    };
+    let start = Instant::now();
+    match tokio::time::timeout(CONNECT_TIMEOUT, tokio::net::TcpStream::connect(&addrs[..])).await {
+        Ok(Ok(_)) => CheckResult::pass(name, format!("TCP connect in {} ms", start.elapsed().as_millis())),
+        Ok(Err(e)) => CheckResult::fail(name, e.to_string(), "check that the service is up and the port is right"),
+        Err(_) => CheckResult::fail(name, format!("no answer within {}s", CONNECT_TIMEOUT.as_secs()),
+            "a firewall may be dropping traffic; check outbound rules"),
+    }
+}
+
+async fn resolve(name: &str, host: &str, port: u16) -> Result<Vec<std::net::SocketAddr>, CheckResult> {
+    match tokio::net::lookup_host((host, port)).await {
+        Ok(addrs) => {
+            let addrs: Vec<_> = addrs.collect();
+            if addrs.is_empty() {
+                Err(CheckResult::fail(name, format!("{} resolves to no addresses", host), "check the host name and DNS"))
+            } else {
+                Ok(addrs)
+            }
+        }
+        Err(e) => Err(CheckResult::fail(name, format!("cannot resolve {}: {}", host, e),
+            "check the host name and the machine's DNS settings (/etc/resolv.conf)")),
+    }
+}
+
+/// List the compute devices the built-in backends can see.
+pub fn check_devices() -> Vec<CheckResult> {
+    let mut checks = Vec::new();
+    #[cfg(feature = "gpu")]
+    checks.push(device_check("devices opencl", crate::gpu::list_devices(),
+        "install the vendor OpenCL ICD (e.g. nvidia-opencl-icd, rocm-opencl, intel-opencl-icd)"));
+    #[cfg(feature = "cuda")]
+    checks.push(device_check("devices cuda", crate::gpu_cuda::list_devices(),
+        "install the NVIDIA driver and check that nvidia-smi lists the card"));
+    #[cfg(feature = "metal")]
+    checks.push(device_check("devices metal", crate::gpu_metal::list_devices(),
+        "needs macOS with a Metal GPU (or Direct3D 12 / Vulkan drivers elsewhere)"));
+    let cpu = crate::cpu::dispatch();
+    checks.push(CheckResult::pass("devices cpu", format!("{} [{}], {} kernel", cpu.arch, cpu.features.join(", "), cpu.kernel)));
+    checks
+}
+
+#[cfg(any(feature = "gpu", feature = "cuda", feature = "metal"))]
+fn device_check(name: &str, devices: anyhow::Result<Vec<DeviceInfo>>, hint: &str) -> CheckResult {
+    match devices {
+        Ok(devices) if devices.is_empty() => CheckResult::fail(name, "no devices found", hint),
+        Ok(devices) => CheckResult::pass(name, devices.iter()
+            .map(|d| format!("{} (driver {})", d.device_name, d.driver_version))
+            .collect::<Vec<_>>()
+            .join("; ")),
+        Err(e) => CheckResult::fail(name, e.to_string(), hint),
+    }
+}
+
+/// Run attempts at a small fixed size for `duration` on the selected backend.
+pub fn check_benchmark(executor: &dyn Executor, config: &Config, duration: Duration) -> CheckResult {
+    let info: DeviceInfo = executor.device_info();
+    let name = format!("benchmark {}", info.backend);
+    let workload = config.get_workload();
+    let prev_hash = [0xaau8; 32];
+    let start = Instant::now();
+    let mut attempts: u32 = 0;
+    while attempts == 0 || start.elapsed() < duration {
+        if let Err(e) = run_workload_attempt(executor, workload, None, &prev_hash, attempts, None, &BENCH_SIZES) {
+            return CheckResult::fail(name, format!("attempt failed: {}", e),
+                "the backend initialised but cannot run kernels; update the driver or try the CPU build");
+        }
+        attempts += 1;
+    }
+    let secs = start.elapsed().as_secs_f64();
+    let tops = workload.tera_ops(&BENCH_SIZES) * attempts as f64 / secs;
+    let detail = format!("{} attempts of {}x{}x{} in {:.2}s ({:.1} ms each, {:.3} TOPS) on {}",
+        attempts, BENCH_SIZES.m, BENCH_SIZES.n, BENCH_SIZES.k, secs, secs * 1000.0 / attempts as f64, tops, info.device_name);
+    // A GPU build that ended up on the CPU works, but far below what the card would do
+    if info.backend == "CPU" && cfg!(any(feature = "gpu", feature = "cuda", feature = "metal")) {
+        return CheckResult::warn(name, detail, "the GPU backend failed to start and the CPU fallback is in use; see the devices check");
+    }
+    CheckResult::pass(name, detail)
+}
+
+/// Every compiled backend must produce byte-identical output to the scalar CPU kernel.
+pub fn check_cross_backend() -> CheckResult {
+    let name = "cross-backend";
+    let report = match crate::crosscheck::run_cross_check() {
+        Ok(report) => report,
+        Err(e) => return CheckResult::fail(name, format!("cross-check failed to run: {}", e),
+            "run `tops-worker cross-check` for details"),
+    };
+    let deviating: Vec<String> = report.deviating().map(|b| b.label()).collect();
+    if !deviating.is_empty() {
+        return CheckResult::fail(name, format!("output differs from the CPU reference on {}", deviating.join("; ")),
+            "receipts from this backend will not verify; update the driver or switch backends, and run `tops-worker cross-check` for the failing cases");
+    }
+    let detail = format!("{} cases byte-identical on {}", report.cases,
+        report.backends.iter().map(|b| b.backend.as_str()).collect::<Vec<_>>().join(", "));
+    if !report.unavailable.is_empty() {
+        let skipped: Vec<&str> = report.unavailable.iter().map(|(b, _)| b.as_str()).collect();
+        return CheckResult::warn(name, format!("{}; not checked: {}", detail, skipped.join(", ")),
+            "a compiled-in backend failed to initialise; see the devices check");
+    }
+    CheckResult::pass(name, detail)
+}
+
+/// The health server binds 127.0.0.1:`port`; make sure nothing else holds it.
+pub fn check_port(port: u16, needed: bool) -> CheckResult {
+    let name = format!("port {}", port);
+    if !needed {
+        return CheckResult::pass(name, "not needed (METRICS_ENABLED=0)");
+    }
+    match std::net::TcpListener::bind(("127.0.0.1", port)) {
+        Ok(_) => CheckResult::pass(name, "bindable"),
+        Err(e) => CheckResult::fail(name, e.to_string(),
+            "another process (maybe a running worker) holds the port; stop it or set METRICS_ENABLED=0"),
+    }
+}
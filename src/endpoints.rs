@@ -0,0 +1,217 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::{log_error, log_info};
+
+// Smoothing factor for the per-endpoint latency / error-rate moving averages
+const EWMA_ALPHA: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EndpointMode {
+    /// Always use the first healthy endpoint in configured order.
+    PrimaryBackup,
+    /// Rotate across all healthy endpoints.
+    RoundRobin,
+}
+
+impl std::str::FromStr for EndpointMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "primary-backup" | "primary" | "failover" => Ok(EndpointMode::PrimaryBackup),
+            "round-robin" | "roundrobin" | "rr" => Ok(EndpointMode::RoundRobin),
+            other => Err(format!("unknown endpoint mode '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for EndpointMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EndpointMode::PrimaryBackup => write!(f, "primary-backup"),
+            EndpointMode::RoundRobin => write!(f, "round-robin"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct EndpointStats {
+    successes: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    avg_latency_ms: f64,
+    error_rate: f64,
+    unhealthy_since: Option<Instant>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    stats: Mutex<EndpointStats>,
+}
+
+/// Serializable per-endpoint view for /status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointStatus {
+    pub url: String,
+    pub healthy: bool,
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    pub avg_latency_ms: f64,
+    pub error_rate: f64,
+    pub last_error: Option<String>,
+}
+
+/// Tracks health of the configured aggregator endpoints and picks one per submission.
+///
+/// An endpoint is marked unhealthy after `failover_threshold` consecutive failures and
+/// is skipped until `cooldown` has elapsed, after which it is probed again.
+#[derive(Debug)]
+pub struct EndpointManager {
+    endpoints: Vec<Endpoint>,
+    mode: EndpointMode,
+    failover_threshold: u32,
+    cooldown: Duration,
+    cursor: AtomicUsize,
+}
+
+impl EndpointManager {
+    pub fn new(urls: Vec<String>, mode: EndpointMode, failover_threshold: u32, cooldown: Duration) -> Self {
+        let endpoints = urls
+            .into_iter()
+            .map(|url| Endpoint { url, stats: Mutex::new(EndpointStats::default()) })
+            .collect();
+        Self {
+            endpoints,
+            mode,
+            failover_threshold: failover_threshold.max(1),
+            cooldown,
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn mode(&self) -> EndpointMode {
+        self.mode
+    }
+
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    /// Pick the endpoint for the next submission. Returns its index and URL.
+    pub fn select(&self) -> Option<(usize, String)> {
+        if self.endpoints.is_empty() {
+            return None;
+        }
+        let eligible: Vec<usize> = (0..self.endpoints.len()).filter(|&i| self.is_eligible(i)).collect();
+
+        let idx = if eligible.is_empty() {
+            // Everything is failing: fall back to whichever looks least bad
+            self.healthiest(0..self.endpoints.len())
+        } else {
+            match self.mode {
+                EndpointMode::PrimaryBackup => {
+                    if eligible[0] == 0 {
+                        0
+                    } else {
+                        // Primary is down: fail over to the healthiest backup
+                        self.healthiest(eligible.iter().copied())
+                    }
+                }
+                EndpointMode::RoundRobin => {
+                    let n = self.cursor.fetch_add(1, Ordering::Relaxed);
+                    eligible[n % eligible.len()]
+                }
+            }
+        };
+        Some((idx, self.endpoints[idx].url.clone()))
+    }
+
+    pub fn record_success(&self, idx: usize, latency: Duration) {
+        let Some(ep) = self.endpoints.get(idx) else { return };
+        if let Ok(mut s) = ep.stats.lock() {
+            if s.unhealthy_since.is_some() {
+                log_info!("[endpoints] {} recovered", ep.url);
+            }
+            s.successes += 1;
+            s.consecutive_failures = 0;
+            s.unhealthy_since = None;
+            s.error_rate *= 1.0 - EWMA_ALPHA;
+            s.avg_latency_ms = ewma(s.avg_latency_ms, latency.as_secs_f64() * 1000.0, s.successes + s.failures);
+        }
+    }
+
+    pub fn record_failure(&self, idx: usize, latency: Duration, error: &str) {
+        let Some(ep) = self.endpoints.get(idx) else { return };
+        if let Ok(mut s) = ep.stats.lock() {
+            s.failures += 1;
+            s.consecutive_failures += 1;
+            s.error_rate = (1.0 - EWMA_ALPHA) * s.error_rate + EWMA_ALPHA;
+            s.avg_latency_ms = ewma(s.avg_latency_ms, latency.as_secs_f64() * 1000.0, s.successes + s.failures);
+            s.last_error = Some(error.to_string());
+            if s.consecutive_failures >= self.failover_threshold {
+                if s.unhealthy_since.is_none() && self.endpoints.len() > 1 {
+                    log_error!("[endpoints] {} marked unhealthy after {} consecutive failures, failing over",
+                        ep.url, s.consecutive_failures);
+                }
+                // Restart the cooldown so a failed probe keeps it out of rotation
+                s.unhealthy_since = Some(Instant::now());
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<EndpointStatus> {
+        self.endpoints
+            .iter()
+            .enumerate()
+            .map(|(i, ep)| {
+                let healthy = self.is_eligible(i);
+                let s = ep.stats.lock().unwrap_or_else(|e| e.into_inner());
+                EndpointStatus {
+                    url: ep.url.clone(),
+                    healthy,
+                    successes: s.successes,
+                    failures: s.failures,
+                    consecutive_failures: s.consecutive_failures,
+                    avg_latency_ms: s.avg_latency_ms,
+                    error_rate: s.error_rate,
+                    last_error: s.last_error.clone(),
+                }
+            })
+            .collect()
+    }
+
+    fn is_eligible(&self, idx: usize) -> bool {
+        let Ok(s) = self.endpoints[idx].stats.lock() else { return false };
+        match s.unhealthy_since {
+            None => true,
+            Some(since) => since.elapsed() >= self.cooldown,
+        }
+    }
+
+    fn healthiest(&self, candidates: impl Iterator<Item = usize>) -> usize {
+        candidates
+            .min_by(|&a, &b| self.score(a).total_cmp(&self.score(b)))
+            .unwrap_or(0)
+    }
+
+    // Lower is better: error rate dominates, latency breaks ties
+    fn score(&self, idx: usize) -> f64 {
+        match self.endpoints[idx].stats.lock() {
+            Ok(s) => s.error_rate * 10_000.0 + s.avg_latency_ms,
+            Err(_) => f64::MAX,
+        }
+    }
+}
+
+fn ewma(prev: f64, sample: f64, count: u64) -> f64 {
+    if count <= 1 { sample } else { (1.0 - EWMA_ALPHA) * prev + EWMA_ALPHA * sample }
+}
@@ -0,0 +1,298 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::devices::selected_pci_address;
+use crate::thermal::selected_drm_hwmon_dirs;
+use crate::{log_info, log_warn};
+
+const RAPL_ROOT: &str = "/sys/class/powercap";
+const DRM_ROOT: &str = "/sys/class/drm";
+
+/// Where attempt energy is read from (`ENERGY_METER`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnergyMeterKind {
+    /// RAPL for the CPU backend; NVML (with the `nvml` feature), then the GPU's hwmon otherwise.
+    Auto,
+    Off,
+    /// Intel/AMD RAPL package counters (`/sys/class/powercap/intel-rapl:N/energy_uj`).
+    Rapl,
+    /// The GPU's hwmon energy counter, or its power reading sampled and integrated.
+    Hwmon,
+    /// NVIDIA's energy counter through NVML (Volta or newer; needs the `nvml` feature).
+    Nvml,
+}
+
+impl std::str::FromStr for EnergyMeterKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(EnergyMeterKind::Auto),
+            "off" | "none" => Ok(EnergyMeterKind::Off),
+            "rapl" => Ok(EnergyMeterKind::Rapl),
+            "hwmon" | "sysfs" => Ok(EnergyMeterKind::Hwmon),
+            "nvml" => Ok(EnergyMeterKind::Nvml),
+            _ => Err(format!("unknown energy meter: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for EnergyMeterKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnergyMeterKind::Auto => write!(f, "auto"),
+            EnergyMeterKind::Off => write!(f, "off"),
+            EnergyMeterKind::Rapl => write!(f, "rapl"),
+            EnergyMeterKind::Hwmon => write!(f, "hwmon"),
+            EnergyMeterKind::Nvml => write!(f, "nvml"),
+        }
+    }
+}
+
+// A cumulative microjoule counter that wraps after `max_uj`
+struct Counter {
+    path: PathBuf,
+    max_uj: Option<u64>,
+}
+
+enum Sensor {
+    /// Energy counters in microjoules (RAPL packages, hwmon `energy1_input`), summed.
+    Counters(Vec<Counter>),
+    /// Power readings in microwatts (hwmon `power1_average` / `power1_input`), summed
+    /// and integrated between samples.
+    Power(Vec<PathBuf>),
+    /// Energy counter in millijoules of the NVIDIA device with this index.
+    #[cfg(feature = "nvml")]
+    Nvml(Box<nvml_wrapper::Nvml>, u32),
+}
+
+enum Reading {
+    /// Per-counter microjoules, in the order of `Sensor::Counters`.
+    Counters(Vec<u64>),
+    Watts(f64),
+}
+
+impl Sensor {
+    fn read(&self) -> anyhow::Result<Reading> {
+        match self {
+            Sensor::Counters(counters) => Ok(Reading::Counters(counters.iter()
+                .map(|c| read_u64(&c.path))
+                .collect::<anyhow::Result<_>>()?)),
+            Sensor::Power(paths) => {
+                let microwatts = paths.iter().map(|p| read_u64(p)).sum::<anyhow::Result<u64>>()?;
+                Ok(Reading::Watts(microwatts as f64 / 1e6))
+            }
+            #[cfg(feature = "nvml")]
+            Sensor::Nvml(nvml, index) => {
+                Ok(Reading::Counters(vec![nvml.device_by_index(*index)?.total_energy_consumption()? * 1000]))
+            }
+        }
+    }
+
+    // Wrap limits per counter, where known
+    fn max_uj(&self, index: usize) -> Option<u64> {
+        match self {
+            Sensor::Counters(counters) => counters.get(index).and_then(|c| c.max_uj),
+            _ => None,
+        }
+    }
+}
+
+struct State {
+    sensor: Sensor,
+    last: Option<(Instant, Reading)>,
+    total_j: f64,
+    lap_j: f64,
+    // Logged once per run of failed reads
+    failing: bool,
+}
+
+impl State {
+    // Read the sensor and add the energy since the previous reading
+    fn sample(&mut self) {
+        let now = Instant::now();
+        let reading = match self.sensor.read() {
+            Ok(reading) => reading,
+            Err(e) => {
+                if !std::mem::replace(&mut self.failing, true) {
+                    log_warn!("[energy] could not read the sensor: {}", e);
+                }
+                return;
+            }
+        };
+        self.failing = false;
+        if let Some((then, previous)) = &self.last {
+            self.total_j += match (previous, &reading) {
+                (Reading::Counters(before), Reading::Counters(after)) => before.iter().zip(after).enumerate()
+                    .map(|(i, (&before, &after))| match (after >= before, self.sensor.max_uj(i)) {
+                        (true, _) => after - before,
+                        (false, Some(max)) => max - before + after,
+                        // Reset without a known range: count from zero
+                        (false, None) => after,
+                    })
+                    .sum::<u64>() as f64 / 1e6,
+                // Trapezoid between the two readings
+                (Reading::Watts(before), Reading::Watts(after)) => (before + after) / 2.0 * now.duration_since(*then).as_secs_f64(),
+                _ => 0.0,
+            };
+        }
+        self.last = Some((now, reading));
+    }
+}
+
+/// Estimates the energy each attempt costs from the host's power sensors.
+///
+/// A sampler thread reads the sensor every `ENERGY_SAMPLE_MS` and integrates it; the
+/// main loop takes a lap per attempt, so an attempt is charged the energy since the
+/// previous one. With several streams and a pipeline in flight that is the device's
+/// energy per receipt at steady state. Pauses are skipped, not charged.
+pub struct EnergyMeter {
+    source: String,
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+    sampler: Option<JoinHandle<()>>,
+}
+
+impl EnergyMeter {
+    /// Open the sensor `kind` names for a `backend` (`CPU`, `OpenCL`, `CUDA`, ...) and start
+    /// sampling. `None` when metering is off, or `Auto` found nothing readable.
+    pub fn start(kind: EnergyMeterKind, backend: &str, interval: Duration) -> anyhow::Result<Option<Self>> {
+        let (source, sensor) = match kind {
+            EnergyMeterKind::Off => return Ok(None),
+            EnergyMeterKind::Rapl => open_rapl(Path::new(RAPL_ROOT))?,
+            EnergyMeterKind::Hwmon => open_hwmon(Path::new(DRM_ROOT))?,
+            EnergyMeterKind::Nvml => open_nvml()?,
+            EnergyMeterKind::Auto => {
+                let found = if backend.eq_ignore_ascii_case("cpu") {
+                    open_rapl(Path::new(RAPL_ROOT))
+                } else {
+                    open_nvml().or_else(|_| open_hwmon(Path::new(DRM_ROOT)))
+                };
+                match found {
+                    Ok(found) => found,
+                    Err(e) => {
+                        log_info!("[energy] no power sensor for the {} backend, attempts are not metered ({})", backend, e);
+                        return Ok(None);
+                    }
+                }
+            }
+        };
+        let mut state = State { sensor, last: None, total_j: 0.0, lap_j: 0.0, failing: false };
+        state.sample();
+        let state = Arc::new(Mutex::new(state));
+        let stop = Arc::new(AtomicBool::new(false));
+        let sampler = {
+            let state = Arc::clone(&state);
+            let stop = Arc::clone(&stop);
+            std::thread::Builder::new()
+                .name("energy-sampler".into())
+                .spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        std::thread::sleep(interval);
+                        if let Ok(mut state) = state.lock() {
+                            state.sample();
+                        }
+                    }
+                })?
+        };
+        Ok(Some(Self { source, state, stop, sampler: Some(sampler) }))
+    }
+
+    pub fn describe(&self) -> &str {
+        &self.source
+    }
+
+    /// Joules since the previous lap (or skip).
+    pub fn lap(&self) -> f64 {
+        let Ok(mut state) = self.state.lock() else { return 0.0 };
+        state.sample();
+        let lap = state.total_j - state.lap_j;
+        state.lap_j = state.total_j;
+        lap
+    }
+
+    /// Drop the energy since the previous lap, e.g. after the loop was idle.
+    pub fn skip(&self) {
+        self.lap();
+    }
+}
+
+impl Drop for EnergyMeter {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.sampler.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+fn read_u64(path: &Path) -> anyhow::Result<u64> {
+    let raw = std::fs::read_to_string(path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    raw.trim().parse().map_err(|_| anyhow::anyhow!("{}: not a number: {:?}", path.display(), raw.trim()))
+}
+
+// Package domains only (intel-rapl:N); their subzones (intel-rapl:N:M) are already included
+fn open_rapl(root: &Path) -> anyhow::Result<(String, Sensor)> {
+    let mut counters = Vec::new();
+    for entry in std::fs::read_dir(root).map_err(|e| anyhow::anyhow!("{}: {}", root.display(), e))?.flatten() {
+        let name = entry.file_name();
+        let Some(index) = name.to_str().and_then(|n| n.strip_prefix("intel-rapl:")) else { continue };
+        if index.parse::<u32>().is_err() {
+            continue;
+        }
+        let path = entry.path().join("energy_uj");
+        // Root-only on kernels since 5.10; fail here rather than on every sample
+        read_u64(&path)?;
+        counters.push(Counter { max_uj: read_u64(&entry.path().join("max_energy_range_uj")).ok(), path });
+    }
+    if counters.is_empty() {
+        anyhow::bail!("no RAPL package domains under {}", root.display());
+    }
+    Ok((format!("RAPL ({} package(s))", counters.len()), Sensor::Counters(counters)))
+}
+
+// An energy counter where the driver has one (amdgpu on recent kernels), power readings otherwise.
+// Only the selected GPU: the one at the backend's PCI address, or the host's only GPU
+fn open_hwmon(drm: &Path) -> anyhow::Result<(String, Sensor)> {
+    let dirs = selected_drm_hwmon_dirs(drm, selected_pci_address().as_deref());
+    let counters: Vec<Counter> = dirs.iter()
+        .map(|dir| dir.join("energy1_input"))
+        .filter(|path| read_u64(path).is_ok())
+        .map(|path| Counter { path, max_uj: None })
+        .collect();
+    if !counters.is_empty() {
+        return Ok((format!("hwmon energy ({})", counters[0].path.display()), Sensor::Counters(counters)));
+    }
+    let power: Vec<PathBuf> = dirs.iter()
+        .filter_map(|dir| ["power1_average", "power1_input"].iter().map(|file| dir.join(file)).find(|path| read_u64(path).is_ok()))
+        .collect();
+    if power.is_empty() {
+        anyhow::bail!("no hwmon energy or power readings for the selected GPU under {}", drm.display());
+    }
+    Ok((format!("hwmon power ({})", power[0].display()), Sensor::Power(power)))
+}
+
+#[cfg(feature = "nvml")]
+fn open_nvml() -> anyhow::Result<(String, Sensor)> {
+    let nvml = nvml_wrapper::Nvml::init()?;
+    let count = nvml.device_count()?;
+    let index = match selected_pci_address() {
+        Some(address) => nvml.device_by_pci_bus_id(address.as_str())?.index()?,
+        None if count == 1 => 0,
+        None if count == 0 => anyhow::bail!("NVML reports no devices"),
+        None => anyhow::bail!("NVML reports {} devices and the backend did not say which one it runs on", count),
+    };
+    let sensor = Sensor::Nvml(Box::new(nvml), index);
+    // Pre-Volta devices have no energy counter
+    sensor.read()?;
+    Ok((format!("NVML (device {})", index), sensor))
+}
+
+#[cfg(not(feature = "nvml"))]
+fn open_nvml() -> anyhow::Result<(String, Sensor)> {
+    anyhow::bail!("built without the nvml feature")
+}
@@ -0,0 +1,200 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::attempt::{run_workload_attempt, Executor};
+use crate::integrity::write_atomic;
+use crate::signing::Secp;
+use crate::types::{DeviceInfo, Sizes};
+use crate::workload::{ProofWorkload, Workload};
+use crate::log_info;
+
+/// Square sizes the capability sweep measures, smallest first.
+const SWEEP_SIDES: [usize; 4] = [256, 512, 1024, 2048];
+/// Attempts per sweep size; the fastest counts.
+const SWEEP_REPEATS: u32 = 3;
+/// Buffer copied by the host memory bandwidth estimate.
+const HOST_COPY_BYTES: usize = 64 << 20;
+
+/// One size of the capability sweep.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepPoint {
+    pub sizes: Sizes,
+    pub best_ms: f64,
+    pub tops: f64,
+}
+
+/// Result of the standardized capability benchmark; the same for every identity of the worker.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityBenchmark {
+    pub measured_at: String,
+    pub workload: String,
+    pub sweep: Vec<SweepPoint>,
+    pub peak_tops: f64,
+    pub peak_sizes: Sizes,
+    /// TOPS over the sustained run at `peak_sizes`, including fill and hashing.
+    pub sustained_tops: f64,
+    pub sustained_seconds: f64,
+    pub sustained_attempts: u64,
+    pub memory_bandwidth_gbps: f64,
+    /// `transfers` (host-device copies during the sweep) or `host_copy` (backends without transfers).
+    pub memory_bandwidth_source: String,
+}
+
+/// Signed capability report POSTed to `ENROLL_URL`, one per signing identity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityReport {
+    pub device_did: String,
+    pub pubkey_hex: String,
+    pub network_id: Option<String>,
+    pub timestamp: String,
+    pub worker_version: String,
+    pub device_info: DeviceInfo,
+    pub benchmark: CapabilityBenchmark,
+    pub sig_hex: String,
+}
+
+impl CapabilityReport {
+    pub fn new(device_did: &str, network_id: Option<String>, device_info: DeviceInfo, benchmark: CapabilityBenchmark, secp: &Secp) -> anyhow::Result<Self> {
+        let mut report = CapabilityReport {
+            device_did: device_did.to_string(),
+            pubkey_hex: secp.pubkey_hex_compressed(),
+            network_id,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            worker_version: env!("CARGO_PKG_VERSION").to_string(),
+            device_info,
+            benchmark,
+            sig_hex: String::new(),
+        };
+        report.sign(secp)?;
+        Ok(report)
+    }
+
+    /// JSON of the report with an empty signature, which is what gets signed.
+    pub fn signing_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut unsigned = self.clone();
+        unsigned.sig_hex = String::new();
+        Ok(serde_json::to_vec(&unsigned)?)
+    }
+
+    pub fn sign(&mut self, secp: &Secp) -> anyhow::Result<()> {
+        self.sig_hex = secp.sign_payload(&self.signing_bytes()?)?;
+        Ok(())
+    }
+}
+
+/// Run the capability benchmark: a sweep of square sizes, then back-to-back attempts
+/// at the fastest one for `sustained`. Inputs chain from a fixed prev_hash so every
+/// worker measures the same attempts.
+pub fn run_benchmark<E: Executor + ?Sized>(executor: &E, workload: Workload, sustained: Duration) -> anyhow::Result<CapabilityBenchmark> {
+    let prev_hash = *blake3::hash(b"tops-worker capability benchmark v1").as_bytes();
+    // Nonces count down from the top, like warm-up attempts
+    let mut nonce = u32::MAX;
+    let mut sweep = Vec::with_capacity(SWEEP_SIDES.len());
+    let (mut transfer_bytes, mut transfer_secs) = (0.0, 0.0);
+    for side in SWEEP_SIDES {
+        let sizes = Sizes { m: side, n: side, k: side, batch: 1 };
+        let mut best = f64::MAX;
+        for _ in 0..SWEEP_REPEATS {
+            let start = Instant::now();
+            let out = run_workload_attempt(executor, workload, None, &prev_hash, nonce, None, &sizes)?;
+            best = best.min(start.elapsed().as_secs_f64());
+            nonce = nonce.wrapping_sub(1);
+            let secs = (out.phases.h2d_ms + out.phases.d2h_ms) / 1000.0;
+            if secs > 0.0 {
+                transfer_bytes += (side * side * 3) as f64;
+                transfer_secs += secs;
+            }
+        }
+        let tops = workload.tera_ops(&sizes) / best;
+        log_info!("[enroll] m,n,k=({},{},{}) -> {:.2} ms, {:.3} TOPS", side, side, side, best * 1000.0, tops);
+        sweep.push(SweepPoint { sizes, best_ms: best * 1000.0, tops });
+    }
+    let peak = sweep.iter()
+        .max_by(|a, b| a.tops.total_cmp(&b.tops))
+        .ok_or_else(|| anyhow::anyhow!("capability sweep measured nothing"))?
+        .clone();
+
+    log_info!("[enroll] sustained run at m,n,k=({},{},{}) for {}s", peak.sizes.m, peak.sizes.n, peak.sizes.k, sustained.as_secs());
+    let start = Instant::now();
+    let mut attempts = 0u64;
+    while attempts == 0 || start.elapsed() < sustained {
+        run_workload_attempt(executor, workload, None, &prev_hash, nonce, None, &peak.sizes)?;
+        nonce = nonce.wrapping_sub(1);
+        attempts += 1;
+    }
+    let sustained_seconds = start.elapsed().as_secs_f64();
+
+    let (memory_bandwidth_gbps, source) = if transfer_secs > 0.0 {
+        (transfer_bytes / transfer_secs / 1e9, "transfers")
+    } else {
+        (host_copy_bandwidth_gbps(), "host_copy")
+    };
+
+    Ok(CapabilityBenchmark {
+        measured_at: chrono::Utc::now().to_rfc3339(),
+        workload: workload.kernel_ver(),
+        peak_tops: peak.tops,
+        peak_sizes: peak.sizes.clone(),
+        sustained_tops: workload.tera_ops(&peak.sizes) * attempts as f64 / sustained_seconds,
+        sustained_seconds,
+        sustained_attempts: attempts,
+        memory_bandwidth_gbps,
+        memory_bandwidth_source: source.to_string(),
+        sweep,
+    })
+}
+
+// Best of a few large copies; each one reads and writes the buffer once
+fn host_copy_bandwidth_gbps() -> f64 {
+    let src = vec![1u8; HOST_COPY_BYTES];
+    let mut dst = vec![0u8; HOST_COPY_BYTES];
+    let mut best = f64::MAX;
+    for _ in 0..5 {
+        let start = Instant::now();
+        dst.copy_from_slice(&src);
+        std::hint::black_box(&mut dst);
+        best = best.min(start.elapsed().as_secs_f64());
+    }
+    2.0 * HOST_COPY_BYTES as f64 / best / 1e9
+}
+
+/// Enrollment progress in `$STATE_DIR/enrollment.json`: the benchmark survives a
+/// failed POST, so a restart only resubmits it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnrollmentState {
+    pub benchmark: CapabilityBenchmark,
+    /// Set once every identity's report was accepted.
+    pub enrolled_at: Option<String>,
+}
+
+impl EnrollmentState {
+    /// The saved state, or `None` if the worker never benchmarked.
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)
+                .map_err(|e| anyhow::anyhow!("enrollment state {} is corrupt: {}", path.display(), e))?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("reading enrollment state {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_atomic(path, &serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// POST a signed report to the enrollment endpoint.
+pub async fn submit(client: &reqwest::Client, url: &str, report: &CapabilityReport) -> anyhow::Result<()> {
+    let resp = client.post(url)
+        .timeout(Duration::from_secs(30))
+        .json(report)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        anyhow::bail!("HTTP {}", resp.status());
+    }
+    Ok(())
+}
@@ -0,0 +1,212 @@
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use crate::size_distribution::{SizeDistribution, WeightedSizes};
+use crate::submit::{hex32, EpochInfo, SubmitResponse, Submitter};
+use crate::types::{parse_scale, RequantParams};
+use crate::work_hash::HashKind;
+use crate::log_error;
+
+/// Where an epoch change came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EpochSource {
+    /// The aggregator's verdict on a receipt (`next_prev_hash` / `next_epoch_salt`).
+    Response,
+    /// The periodic epoch feed (`EPOCH_URL`, gRPC `GetEpoch`).
+    Feed,
+}
+
+impl std::fmt::Display for EpochSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EpochSource::Response => write!(f, "response"),
+            EpochSource::Feed => write!(f, "feed"),
+        }
+    }
+}
+
+/// Everything attempts depend on that changes with the epoch; swapped as a whole.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochParams {
+    pub epoch_id: u64,
+    pub prev_hash: [u8; 32],
+    pub salt: Option<[u8; 32]>,
+    /// Memory-hard stage buffer size the epoch asks for, if any.
+    pub memhard_kib: Option<u32>,
+    /// Minimum work per receipt in TOPS-seconds, if the epoch sets one.
+    pub min_tops_seconds: Option<f64>,
+    /// Requantization scale and activation the epoch sets, if any.
+    pub requant: RequantParams,
+    /// Sizes attempts draw from instead of the tuned sizes, if the epoch sets a distribution.
+    pub size_distribution: Option<SizeDistribution>,
+    /// Hash of the work_root the epoch's receipts are checked against.
+    pub hash_kind: HashKind,
+}
+
+impl EpochParams {
+    /// Parameters used until an aggregator tells us otherwise.
+    pub fn placeholder() -> Self {
+        Self { epoch_id: 1, prev_hash: [0xaa; 32], salt: None, memhard_kib: None, min_tops_seconds: None, requant: RequantParams::default(), size_distribution: None, hash_kind: HashKind::Blake3 }
+    }
+
+    pub fn from_info(info: &EpochInfo) -> Self {
+        Self {
+            epoch_id: info.epoch_id,
+            prev_hash: info.prev_hash,
+            salt: info.salt,
+            memhard_kib: info.memhard_kib,
+            min_tops_seconds: info.min_tops_seconds,
+            requant: info.requant,
+            size_distribution: info.size_distribution.clone(),
+            hash_kind: info.hash_kind,
+        }
+    }
+
+    pub fn prev_hash_hex(&self) -> String {
+        hex::encode(self.prev_hash)
+    }
+
+    pub fn salt_hex(&self) -> Option<String> {
+        self.salt.map(hex::encode)
+    }
+
+    /// Parameters after the aggregator's verdict on a receipt, or `None` if it keeps us
+    /// where we are. Verdicts only move prev_hash, salt and epoch id.
+    pub fn after_response(&self, response: &SubmitResponse) -> Option<Self> {
+        let next_hash = response.next_prev_hash_bytes().filter(|next| *next != self.prev_hash);
+        let next_salt = response.next_epoch_salt_bytes().filter(|next| Some(*next) != self.salt);
+        if next_hash.is_none() && next_salt.is_none() {
+            return None;
+        }
+        Some(Self {
+            epoch_id: response.next_epoch_id.unwrap_or(self.epoch_id),
+            prev_hash: next_hash.unwrap_or(self.prev_hash),
+            salt: next_salt.or(self.salt),
+            ..self.clone()
+        })
+    }
+
+    /// Parameters announced by the epoch feed, or `None` if they match the current ones or
+    /// the entry is not newer than the current epoch, so a stale or replayed feed cannot
+    /// roll attempts back. Any entry replaces the placeholder.
+    pub fn after_feed(&self, info: &EpochInfo) -> Option<Self> {
+        if info.epoch_id <= self.epoch_id && *self != Self::placeholder() {
+            return None;
+        }
+        let next = Self::from_info(info);
+        (next != *self).then_some(next)
+    }
+
+    /// Whether attempt sizes have to be chosen again for `next`.
+    pub fn needs_retune(&self, next: &Self) -> bool {
+        self.memhard_kib != next.memhard_kib
+            || self.min_tops_seconds != next.min_tops_seconds
+            || self.size_distribution != next.size_distribution
+    }
+}
+
+/// Epoch descriptor served at `EPOCH_URL`, e.g.
+/// `{"epoch_id":7,"prev_hash":"<64 hex>","salt":"<64 hex>","memhard_kib":4096,"min_tops_seconds":0.5,
+/// "requant_scale":"3/1024","activation":"relu6","size_distribution":[{"m":1024,"n":1024,"k":1024,"weight":3},
+/// {"m":2048,"n":512,"k":1024,"weight":1}],"hash_kind":"poseidon"}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpochDocument {
+    pub epoch_id: u64,
+    pub prev_hash: String,
+    #[serde(default)]
+    pub salt: Option<String>,
+    #[serde(default)]
+    pub memhard_kib: Option<u32>,
+    #[serde(default)]
+    pub min_tops_seconds: Option<f64>,
+    /// `num/den`
+    #[serde(default)]
+    pub requant_scale: Option<String>,
+    #[serde(default)]
+    pub activation: Option<String>,
+    /// `toward-zero` (the default), `floor` or `half-away-from-zero`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requant_rounding: Option<String>,
+    /// `saturate` (the default) or `wrap`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requant_overflow: Option<String>,
+    #[serde(default)]
+    pub size_distribution: Option<Vec<WeightedSizes>>,
+    /// `blake3` (the default), `sha3-256` or `poseidon`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_kind: Option<String>,
+}
+
+impl EpochDocument {
+    pub fn into_info(self) -> anyhow::Result<EpochInfo> {
+        Ok(EpochInfo {
+            epoch_id: self.epoch_id,
+            prev_hash: hex32(&self.prev_hash).ok_or_else(|| anyhow::anyhow!("prev_hash is not 32 bytes of hex"))?,
+            salt: match &self.salt {
+                Some(salt) => Some(hex32(salt).ok_or_else(|| anyhow::anyhow!("salt is not 32 bytes of hex"))?),
+                None => None,
+            },
+            memhard_kib: self.memhard_kib.filter(|&kib| kib > 0),
+            min_tops_seconds: self.min_tops_seconds.filter(|&s| s > 0.0),
+            requant: RequantParams {
+                scale: match &self.requant_scale {
+                    Some(scale) => Some(parse_scale(scale).ok_or_else(|| anyhow::anyhow!("requant_scale is not num/den"))?),
+                    None => None,
+                },
+                activation: match &self.activation {
+                    Some(activation) => Some(activation.parse().map_err(|e: String| anyhow::anyhow!(e))?),
+                    None => None,
+                },
+                rounding: match &self.requant_rounding {
+                    Some(rounding) => Some(rounding.parse().map_err(|e: String| anyhow::anyhow!(e))?),
+                    None => None,
+                },
+                overflow: match &self.requant_overflow {
+                    Some(overflow) => Some(overflow.parse().map_err(|e: String| anyhow::anyhow!(e))?),
+                    None => None,
+                },
+            },
+            size_distribution: match self.size_distribution {
+                Some(entries) if !entries.is_empty() => Some(SizeDistribution::new(entries)
+                    .map_err(|e| anyhow::anyhow!("size_distribution is invalid: {}", e))?),
+                _ => None,
+            },
+            hash_kind: match &self.hash_kind {
+                Some(kind) => kind.parse().map_err(|e: String| anyhow::anyhow!(e))?,
+                None => HashKind::default(),
+            },
+        })
+    }
+}
+
+/// Polls the transport for the current epoch and publishes each answer.
+///
+/// The main loop picks the latest value up between attempts, so the attempt in
+/// progress finishes under the parameters it started with.
+pub struct EpochFeed;
+
+impl EpochFeed {
+    pub fn spawn(submitter: Arc<dyn Submitter>, interval: Duration) -> watch::Receiver<Option<EpochInfo>> {
+        let (tx, rx) = watch::channel(None);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // The startup fetch already covered the first tick
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match submitter.current_epoch().await {
+                    Ok(Some(epoch)) => {
+                        if tx.send(Some(epoch)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => log_error!("[epoch] epoch feed poll failed: {}", e),
+                }
+            }
+        });
+        rx
+    }
+}
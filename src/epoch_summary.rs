@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use crate::metrics::EpochStats;
+use crate::signing::{verify_payload, Secp};
+
+/// Signed end-of-epoch totals, sent once per epoch transition alongside the
+/// individual receipts.
+///
+/// Attempts are shared across identities, so the totals cover the whole worker
+/// and are signed by the identity named in `device_did`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpochSummary {
+    pub device_did: String,
+    pub pubkey_hex: String,
+    pub network_id: Option<String>,
+    pub epoch_id: u64,
+    pub duration_seconds: u64,
+    pub attempts: u64,
+    pub successful_attempts: u64,
+    pub failed_attempts: u64,
+    /// Receipts the aggregator accepted during the epoch.
+    pub accepted_receipts: u64,
+    /// Mean attempt time over the epoch.
+    pub average_time_ms: f64,
+    /// Mean time the aggregator took to answer an accepted receipt.
+    pub average_submit_latency_ms: f64,
+    /// Energy measured over the epoch's attempts, when a meter is available (`ENERGY_METER`).
+    pub energy_joules: Option<f64>,
+    pub timestamp: String,
+    pub sig_hex: String,
+}
+
+impl EpochSummary {
+    /// Unsigned summary of `stats` for `device_did`; the submitter signs it.
+    pub fn new(device_did: &str, network_id: Option<String>, stats: &EpochStats) -> Self {
+        Self {
+            device_did: device_did.to_string(),
+            pubkey_hex: String::new(),
+            network_id,
+            epoch_id: stats.epoch_id,
+            duration_seconds: stats.duration_seconds,
+            attempts: stats.attempts,
+            successful_attempts: stats.successful_attempts,
+            failed_attempts: stats.failed_attempts,
+            accepted_receipts: stats.accepted_receipts,
+            average_time_ms: stats.average_time_ms,
+            average_submit_latency_ms: stats.average_submit_latency_ms,
+            energy_joules: stats.energy_joules,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            sig_hex: String::new(),
+        }
+    }
+
+    /// JSON of the summary with an empty signature, which is what gets signed.
+    pub fn signing_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut unsigned = self.clone();
+        unsigned.sig_hex = String::new();
+        Ok(serde_json::to_vec(&unsigned)?)
+    }
+
+    /// Sign with `secp`, which also becomes `pubkey_hex`.
+    pub fn sign(&mut self, secp: &Secp) -> anyhow::Result<()> {
+        self.pubkey_hex = secp.pubkey_hex_compressed();
+        self.sig_hex = secp.sign_payload(&self.signing_bytes()?)?;
+        Ok(())
+    }
+
+    /// Whether the signature verifies against `pubkey_hex`.
+    pub fn verify(&self, pubkey_hex: &str) -> anyhow::Result<bool> {
+        verify_payload(&self.signing_bytes()?, &self.sig_hex, pubkey_hex)
+    }
+}
@@ -1,6 +1,10 @@
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
 use crate::metrics::{ErrorType, MetricsCollector};
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::{log_error, log_warn};
 
 #[derive(Debug, Clone)]
 pub struct RetryConfig {
@@ -21,11 +25,66 @@ impl Default for RetryConfig {
     }
 }
 
-#[derive(Debug)]
+// Transitions kept for `/status`
+const RECENT_TRANSITIONS: usize = 16;
+
+/// State of a `CircuitBreaker`, as reported in `/status` and metric labels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    /// The recovery timeout has passed and a single probe is in flight.
+    HalfOpen,
+}
+
+impl std::fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitState::Closed => write!(f, "closed"),
+            CircuitState::Open => write!(f, "open"),
+            CircuitState::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+/// What `CircuitBreaker::acquire` allows the caller to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitPermit {
+    /// The circuit is closed: go ahead.
+    Closed,
+    /// The caller is the one probe of a half-open circuit; its result decides
+    /// whether the circuit closes or opens again.
+    Probe,
+    /// Open, or half-open with the probe still in flight.
+    Refused,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitTransition {
+    pub from: CircuitState,
+    pub to: CircuitState,
+    /// Unix time in milliseconds.
+    pub at_ms: u64,
+}
+
+/// Circuit breaker state and history for `/status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerStatus {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub failure_threshold: u32,
+    pub recovery_timeout_secs: u64,
+    /// Most recent transitions, oldest first.
+    pub transitions: Vec<CircuitTransition>,
+}
+
 pub struct CircuitBreaker {
     failure_threshold: u32,
     recovery_timeout: Duration,
     state: Arc<Mutex<CircuitBreakerState>>,
+    transitions: Mutex<VecDeque<CircuitTransition>>,
+    metrics: Option<Arc<PrometheusMetrics>>,
 }
 
 #[derive(Debug, Clone)]
@@ -35,32 +94,54 @@ enum CircuitBreakerState {
     HalfOpen,
 }
 
+impl CircuitBreakerState {
+    fn kind(&self) -> CircuitState {
+        match self {
+            CircuitBreakerState::Closed { .. } => CircuitState::Closed,
+            CircuitBreakerState::Open { .. } => CircuitState::Open,
+            CircuitBreakerState::HalfOpen => CircuitState::HalfOpen,
+        }
+    }
+}
+
 impl CircuitBreaker {
     pub fn new(failure_threshold: u32, recovery_timeout: Duration) -> Self {
         Self {
             failure_threshold,
             recovery_timeout,
             state: Arc::new(Mutex::new(CircuitBreakerState::Closed { failure_count: 0 })),
+            transitions: Mutex::new(VecDeque::new()),
+            metrics: None,
         }
     }
     
-    pub fn can_execute(&self) -> bool {
-        if let Ok(state) = self.state.lock() {
-            match &*state {
-                CircuitBreakerState::Closed { .. } => true,
-                CircuitBreakerState::Open { opened_at } => {
-                    opened_at.elapsed() >= self.recovery_timeout
-                }
-                CircuitBreakerState::HalfOpen => true,
+    /// Count transitions in `tops_worker_circuit_transitions_total`.
+    pub fn with_metrics(mut self, metrics: Option<Arc<PrometheusMetrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+    
+    /// Ask to make a call. Once the recovery timeout has passed, exactly one caller
+    /// gets `Probe` and everyone else is refused until it reports its result.
+    pub fn acquire(&self) -> CircuitPermit {
+        let Ok(mut state) = self.state.lock() else { return CircuitPermit::Refused };
+        match &*state {
+            CircuitBreakerState::Closed { .. } => CircuitPermit::Closed,
+            CircuitBreakerState::Open { opened_at } if opened_at.elapsed() >= self.recovery_timeout => {
+                self.transition(&mut state, CircuitBreakerState::HalfOpen);
+                CircuitPermit::Probe
             }
-        } else {
-            false
+            CircuitBreakerState::Open { .. } | CircuitBreakerState::HalfOpen => CircuitPermit::Refused,
         }
     }
     
+    pub fn can_execute(&self) -> bool {
+        self.acquire() != CircuitPermit::Refused
+    }
+    
     pub fn record_success(&self) {
         if let Ok(mut state) = self.state.lock() {
-            *state = CircuitBreakerState::Closed { failure_count: 0 };
+            self.transition(&mut state, CircuitBreakerState::Closed { failure_count: 0 });
         }
     }
     
@@ -70,21 +151,59 @@ impl CircuitBreaker {
                 CircuitBreakerState::Closed { failure_count } => {
                     *failure_count += 1;
                     if *failure_count >= self.failure_threshold {
-                        *state = CircuitBreakerState::Open { opened_at: Instant::now() };
-                    }
-                }
-                CircuitBreakerState::Open { opened_at } => {
-                    if opened_at.elapsed() >= self.recovery_timeout {
-                        *state = CircuitBreakerState::HalfOpen;
+                        self.transition(&mut state, CircuitBreakerState::Open { opened_at: Instant::now() });
                     }
                 }
+                // Calls let through before the circuit opened do not extend it
+                CircuitBreakerState::Open { .. } => {}
+                // The probe failed: wait out another recovery timeout
                 CircuitBreakerState::HalfOpen => {
-                    *state = CircuitBreakerState::Open { opened_at: Instant::now() };
+                    self.transition(&mut state, CircuitBreakerState::Open { opened_at: Instant::now() });
                 }
             }
         }
     }
     
+    // Move to `next`, recording the transition when the kind of state changes
+    fn transition(&self, state: &mut CircuitBreakerState, next: CircuitBreakerState) {
+        let (from, to) = (state.kind(), next.kind());
+        *state = next;
+        if from == to {
+            return;
+        }
+        log_warn!("[circuit] {} -> {}", from, to);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_circuit_transition(from, to);
+        }
+        if let Ok(mut transitions) = self.transitions.lock() {
+            let at_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default();
+            transitions.push_back(CircuitTransition { from, to, at_ms });
+            while transitions.len() > RECENT_TRANSITIONS {
+                transitions.pop_front();
+            }
+        }
+    }
+    
+    pub fn state(&self) -> CircuitState {
+        self.state.lock().map(|state| state.kind()).unwrap_or(CircuitState::Open)
+    }
+    
+    pub fn status(&self) -> CircuitBreakerStatus {
+        let consecutive_failures = match self.state.lock().as_deref() {
+            Ok(CircuitBreakerState::Closed { failure_count }) => *failure_count,
+            _ => self.failure_threshold,
+        };
+        CircuitBreakerStatus {
+            state: self.state(),
+            consecutive_failures,
+            failure_threshold: self.failure_threshold,
+            recovery_timeout_secs: self.recovery_timeout.as_secs(),
+            transitions: self.transitions.lock().map(|t| t.iter().cloned().collect()).unwrap_or_default(),
+        }
+    }
+    
     pub fn get_state(&self) -> String {
         if let Ok(state) = self.state.lock() {
             match &*state {
@@ -105,7 +224,7 @@ impl CircuitBreaker {
 
 pub struct ErrorHandler {
     retry_config: RetryConfig,
-    circuit_breaker: CircuitBreaker,
+    circuit_breaker: Arc<CircuitBreaker>,
     metrics: Arc<MetricsCollector>,
 }
 
@@ -113,7 +232,7 @@ impl ErrorHandler {
     pub fn new(metrics: Arc<MetricsCollector>) -> Self {
         Self {
             retry_config: RetryConfig::default(),
-            circuit_breaker: CircuitBreaker::new(5, Duration::from_secs(60)),
+            circuit_breaker: Arc::new(CircuitBreaker::new(5, Duration::from_secs(60))),
             metrics,
         }
     }
@@ -124,10 +243,27 @@ impl ErrorHandler {
     }
     
     pub fn with_circuit_breaker(mut self, failure_threshold: u32, recovery_timeout: Duration) -> Self {
-        self.circuit_breaker = CircuitBreaker::new(failure_threshold, recovery_timeout);
+        let metrics = self.circuit_breaker.metrics.clone();
+        self.circuit_breaker = Arc::new(CircuitBreaker::new(failure_threshold, recovery_timeout).with_metrics(metrics));
+        self
+    }
+    
+    /// Count the circuit breaker's transitions in Prometheus.
+    pub fn with_circuit_metrics(mut self, metrics: Arc<PrometheusMetrics>) -> Self {
+        let breaker = &self.circuit_breaker;
+        self.circuit_breaker = Arc::new(CircuitBreaker::new(breaker.failure_threshold, breaker.recovery_timeout).with_metrics(Some(metrics)));
         self
     }
     
+    pub fn retry_config(&self) -> &RetryConfig {
+        &self.retry_config
+    }
+    
+    /// The breaker shared by gRPC calls and the submission path's `CircuitSubmitter`.
+    pub fn circuit_breaker(&self) -> &Arc<CircuitBreaker> {
+        &self.circuit_breaker
+    }
+    
     pub async fn execute_with_retry<F, T, E>(&self, operation: F) -> Result<T, E>
     where
         F: Fn() -> Result<T, E>,
@@ -170,23 +306,70 @@ impl ErrorHandler {
         Err(last_error.unwrap())
     }
     
+    /// Async variant of `execute_with_retry` sharing the same retry policy and circuit breaker.
+    /// Only errors for which `retryable` returns true are retried; others are returned at once.
+    pub async fn execute_async_with_retry<F, Fut, T, E>(&self, operation: F, retryable: impl Fn(&E) -> bool) -> Result<T, E>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = Result<T, E>>,
+        E: std::fmt::Debug + std::convert::From<std::string::String>,
+    {
+        if !self.circuit_breaker.can_execute() {
+            return Err(format!("Circuit breaker is open: {}", self.circuit_breaker.get_state()).into());
+        }
+        let result = self.retry_async(operation, &retryable).await;
+        match &result {
+            // Non-retryable errors are about the request: the peer answered it. Every
+            // path records an outcome, or a half-open probe would never be resolved
+            Err(error) if retryable(error) => self.circuit_breaker.record_failure(),
+            _ => self.circuit_breaker.record_success(),
+        }
+        result
+    }
+
+    /// The retry policy of `execute_async_with_retry` without the circuit breaker,
+    /// for calls whose caller applies the breaker itself (receipt submissions, through
+    /// `CircuitSubmitter`).
+    pub async fn retry_async<F, Fut, T, E>(&self, operation: F, retryable: impl Fn(&E) -> bool) -> Result<T, E>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = Result<T, E>>,
+    {
+        let mut delay = self.retry_config.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match operation().await {
+                Err(error) if attempt < self.retry_config.max_retries && retryable(&error) => {
+                    attempt += 1;
+                    self.metrics.record_error(ErrorType::Network);
+                    tokio::time::sleep(delay).await;
+                    delay = Duration::from_secs_f64(
+                        (delay.as_secs_f64() * self.retry_config.backoff_multiplier)
+                            .min(self.retry_config.max_retry_delay.as_secs_f64())
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+
     pub fn handle_gpu_error(&self, error: &str) {
-        eprintln!("GPU Error: {}", error);
+        log_error!("GPU Error: {}", error);
         self.metrics.record_error(ErrorType::Gpu);
     }
     
     pub fn handle_network_error(&self, error: &str) {
-        eprintln!("Network Error: {}", error);
+        log_error!("Network Error: {}", error);
         self.metrics.record_error(ErrorType::Network);
     }
     
     pub fn handle_signature_error(&self, error: &str) {
-        eprintln!("Signature Error: {}", error);
+        log_error!("Signature Error: {}", error);
         self.metrics.record_error(ErrorType::Signature);
     }
     
     pub fn handle_validation_error(&self, error: &str) {
-        eprintln!("Validation Error: {}", error);
+        log_error!("Validation Error: {}", error);
         self.metrics.record_error(ErrorType::Validation);
     }
     
@@ -197,34 +380,36 @@ impl ErrorHandler {
 
 // Rate limiting
 pub struct RateLimiter {
-    tokens: Arc<Mutex<u32>>,
+    tokens: Arc<Mutex<f64>>,
     max_tokens: u32,
-    refill_rate: f64, // tokens per second
+    refill_rate: Arc<Mutex<f64>>, // tokens per second
     last_refill: Arc<Mutex<Instant>>,
 }
 
 impl RateLimiter {
     pub fn new(max_tokens: u32, refill_rate: f64) -> Self {
         Self {
-            tokens: Arc::new(Mutex::new(max_tokens)),
+            tokens: Arc::new(Mutex::new(max_tokens as f64)),
             max_tokens,
-            refill_rate,
+            refill_rate: Arc::new(Mutex::new(refill_rate)),
             last_refill: Arc::new(Mutex::new(Instant::now())),
         }
     }
     
     pub fn try_acquire(&self) -> bool {
-        if let (Ok(mut tokens), Ok(mut last_refill)) = (self.tokens.lock(), self.last_refill.lock()) {
-            // Refill tokens based on time elapsed
+        if let (Ok(mut tokens), Ok(mut last_refill), Ok(refill_rate)) =
+            (self.tokens.lock(), self.last_refill.lock(), self.refill_rate.lock())
+        {
+            // Refill tokens based on time elapsed (fractional, so slow rates still refill)
             let now = Instant::now();
             let elapsed = now.duration_since(*last_refill);
-            let tokens_to_add = (elapsed.as_secs_f64() * self.refill_rate) as u32;
+            let tokens_to_add = elapsed.as_secs_f64() * *refill_rate;
             
-            *tokens = (*tokens + tokens_to_add).min(self.max_tokens);
+            *tokens = (*tokens + tokens_to_add).min(self.max_tokens as f64);
             *last_refill = now;
             
-            if *tokens > 0 {
-                *tokens -= 1;
+            if *tokens >= 1.0 {
+                *tokens -= 1.0;
                 true
             } else {
                 false
@@ -239,4 +424,14 @@ impl RateLimiter {
             std::thread::sleep(Duration::from_millis(10));
         }
     }
-}
+    
+    pub fn set_refill_rate(&self, refill_rate: f64) {
+        if let Ok(mut rate) = self.refill_rate.lock() {
+            *rate = refill_rate;
+        }
+    }
+    
+    pub fn refill_rate(&self) -> f64 {
+        self.refill_rate.lock().map(|r| *r).unwrap_or(0.0)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::integrity::write_atomic;
+use crate::types::Sizes;
+use crate::log_warn;
+
+const INDEX_FILE: &str = "index.json";
+const ZSTD_LEVEL: i32 = 3;
+
+/// Which attempts keep their full output as dispute evidence.
+///
+/// Roughly one attempt in `one_in` is sampled at random (`0` never samples on
+/// its own); the aggregator can also ask for the next attempt to be kept.
+#[derive(Debug)]
+pub struct EvidencePolicy {
+    one_in: u32,
+    requested: AtomicBool,
+}
+
+impl EvidencePolicy {
+    pub fn new(one_in: u32) -> Self {
+        Self { one_in, requested: AtomicBool::new(false) }
+    }
+
+    /// Keep the next attempt regardless of the sampling rate.
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Decide for one attempt; consumes a pending request.
+    pub fn should_sample(&self) -> bool {
+        if self.requested.swap(false, Ordering::Relaxed) {
+            return true;
+        }
+        self.one_in > 0 && rand::thread_rng().gen_range(0..self.one_in) == 0
+    }
+}
+
+/// One stored output, as listed in the evidence index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceEntry {
+    pub epoch_id: u64,
+    pub nonce: u32,
+    /// File name of the zstd-compressed output, relative to the evidence directory.
+    pub file: String,
+    /// BLAKE3 of the uncompressed output; receipts reference evidence by this hash.
+    pub output_hash_hex: String,
+    pub sizes: Sizes,
+    pub output_bytes: u64,
+    pub stored_bytes: u64,
+    pub created_at: String,
+}
+
+/// Full attempt outputs kept for dispute resolution, keyed by (epoch, nonce).
+///
+/// Each output is compressed with zstd into its own file and listed in
+/// `index.json`. Once the stored files exceed the quota the oldest entries
+/// are deleted, so the directory never grows past `max_bytes` plus one entry.
+pub struct EvidenceStore {
+    dir: PathBuf,
+    max_bytes: u64,
+    entries: Mutex<VecDeque<EvidenceEntry>>,
+}
+
+impl EvidenceStore {
+    /// Open the store at `dir`; a missing or unreadable index starts empty.
+    pub fn open(dir: impl AsRef<Path>, max_bytes: u64) -> Self {
+        let dir = dir.as_ref().to_path_buf();
+        let entries = match fs::read(dir.join(INDEX_FILE)) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                log_warn!("[evidence] ignoring unreadable index in {}: {}", dir.display(), e);
+                VecDeque::new()
+            }),
+            Err(_) => VecDeque::new(),
+        };
+        Self { dir, max_bytes, entries: Mutex::new(entries) }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Compress and store the full output of one attempt, evicting the oldest
+    /// entries beyond the quota.
+    pub fn store(&self, epoch_id: u64, nonce: u32, sizes: &Sizes, y: &[i8]) -> anyhow::Result<EvidenceEntry> {
+        let raw: Vec<u8> = y.iter().map(|&v| v as u8).collect();
+        let compressed = zstd::encode_all(raw.as_slice(), ZSTD_LEVEL)?;
+        fs::create_dir_all(&self.dir)?;
+        let file = format!("{}-{}.y.zst", epoch_id, nonce);
+        write_atomic(&self.dir.join(&file), &compressed)?;
+
+        let entry = EvidenceEntry {
+            epoch_id,
+            nonce,
+            file,
+            output_hash_hex: blake3::hash(&raw).to_hex().to_string(),
+            sizes: sizes.clone(),
+            output_bytes: raw.len() as u64,
+            stored_bytes: compressed.len() as u64,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        let mut entries = self.entries.lock().unwrap();
+        // A retried (epoch, nonce) overwrote the same file
+        entries.retain(|e| e.file != entry.file);
+        entries.push_back(entry.clone());
+        let mut total: u64 = entries.iter().map(|e| e.stored_bytes).sum();
+        while total > self.max_bytes && entries.len() > 1 {
+            let Some(oldest) = entries.pop_front() else { break };
+            total -= oldest.stored_bytes;
+            let _ = fs::remove_file(self.dir.join(&oldest.file));
+        }
+        write_atomic(&self.dir.join(INDEX_FILE), &serde_json::to_vec_pretty(&*entries)?)?;
+        Ok(entry)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes currently used by the compressed outputs.
+    pub fn stored_bytes(&self) -> u64 {
+        self.entries.lock().unwrap().iter().map(|e| e.stored_bytes).sum()
+    }
+}
@@ -0,0 +1,460 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use crate::idempotency::idempotency_key;
+use crate::submit::{Submission, SubmitOutcome};
+use crate::types::WorkReceipt;
+use crate::{log_info, log_warn};
+
+// Rows buffered before they go out as one Parquet row group: what the exporter holds in memory
+#[cfg(feature = "parquet-export")]
+const ROW_GROUP_ROWS: usize = 1024;
+// Row groups per Parquet part before it is closed and readable; bounds what a crash loses
+#[cfg(feature = "parquet-export")]
+const ROW_GROUPS_PER_PART: usize = 64;
+// Parts still being written; a leftover one is from a worker that did not shut down
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// File format of the receipt export (`EXPORT_FORMAT`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    /// Needs the `parquet-export` feature.
+    Parquet,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(format!("unknown export format: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Csv => write!(f, "csv"),
+            ExportFormat::Parquet => write!(f, "parquet"),
+        }
+    }
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// One exported row: a receipt as it went out and what became of it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRow {
+    pub recorded_at: String,
+    pub device_did: String,
+    pub network_id: Option<String>,
+    pub epoch_id: u64,
+    pub nonce: u32,
+    pub seq: Option<u64>,
+    pub prev_hash_hex: String,
+    pub work_root_hex: String,
+    pub m: u64,
+    pub n: u64,
+    pub k: u64,
+    pub batch: u64,
+    pub time_ms: u64,
+    pub kernel_ver: String,
+    pub backend: Option<String>,
+    pub device_name: Option<String>,
+    pub receipt_version: u16,
+    pub key_epoch: Option<u32>,
+    pub issued_at_ms: Option<u64>,
+    pub energy_estimate_j: Option<f64>,
+    /// The `Idempotency-Key` the aggregator saw, and the `attempt` of `/logs`.
+    pub idempotency_key: String,
+    /// Empty when the receipt was parked before signing.
+    pub sig_hex: String,
+    pub target: String,
+    /// `accepted`, `queued`, `throttled`, `rejected` or `failed`; `published` once
+    /// the MQTT broker acknowledges a queued receipt.
+    pub outcome: String,
+    /// Failure kind, for outcomes other than accepted and queued.
+    pub failure: Option<String>,
+    /// HTTP status (or its gRPC equivalent) of a throttled or rejected receipt.
+    pub status: Option<u16>,
+    pub retries: u32,
+    pub latency_ms: f64,
+}
+
+impl ExportRow {
+    /// The row for `receipt`, submitted as `submission` says.
+    pub fn new(receipt: &WorkReceipt, submission: &Submission) -> Self {
+        let sent = submission.signed.as_ref().unwrap_or(receipt);
+        let (outcome, status) = match &submission.outcome {
+            SubmitOutcome::Accepted { .. } => ("accepted", None),
+            SubmitOutcome::Queued => ("queued", None),
+            SubmitOutcome::Throttled { status, .. } => ("throttled", Some(*status)),
+            SubmitOutcome::Rejected { status, .. } => ("rejected", Some(*status)),
+            SubmitOutcome::Failed { .. } => ("failed", None),
+        };
+        Self {
+            recorded_at: Utc::now().to_rfc3339(),
+            device_did: sent.device_did.clone(),
+            network_id: sent.network_id.clone(),
+            epoch_id: sent.epoch_id,
+            nonce: sent.nonce,
+            seq: sent.seq,
+            prev_hash_hex: sent.prev_hash_hex.clone(),
+            work_root_hex: sent.work_root_hex.clone(),
+            m: sent.sizes.m as u64,
+            n: sent.sizes.n as u64,
+            k: sent.sizes.k as u64,
+            batch: sent.sizes.batch as u64,
+            time_ms: sent.time_ms,
+            kernel_ver: sent.kernel_ver.clone(),
+            backend: sent.device_info.as_ref().map(|info| info.backend.clone()),
+            device_name: sent.device_info.as_ref().map(|info| info.device_name.clone()),
+            receipt_version: sent.receipt_version,
+            key_epoch: sent.key_epoch,
+            issued_at_ms: sent.issued_at_ms,
+            energy_estimate_j: sent.energy_estimate_j,
+            idempotency_key: idempotency_key(sent),
+            sig_hex: sent.sig_hex.clone(),
+            target: submission.target.clone(),
+            outcome: outcome.to_string(),
+            failure: submission.failure_kind().map(|kind| kind.to_string()),
+            status,
+            retries: submission.retries,
+            latency_ms: submission.latency.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+/// Mirrors every submitted receipt into `<dir>/date=YYYY-MM-DD/part-<ms>.<csv|parquet>`
+/// (`EXPORT_FORMAT`), one part per worker run and UTC day.
+///
+/// Rows are streamed out: CSV parts are flushed row by row, Parquet parts a row
+/// group at a time, so memory stays flat however long the worker runs. A part is
+/// written as `<part>.partial` and renamed once complete. Day directories older than
+/// `retention_days`, and the oldest ones past `max_bytes` (0 for no limit), are
+/// deleted whenever a new day starts; today's is always kept.
+pub struct ReceiptExporter {
+    dir: PathBuf,
+    format: ExportFormat,
+    retention_days: u32,
+    max_bytes: u64,
+    part: Mutex<Option<Part>>,
+}
+
+impl ReceiptExporter {
+    pub fn open(dir: impl AsRef<Path>, format: ExportFormat, retention_days: u32, max_bytes: u64) -> anyhow::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let exporter = Self { dir, format, retention_days, max_bytes, part: Mutex::new(None) };
+        exporter.sweep(Utc::now().date_naive());
+        Ok(exporter)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    pub fn record(&self, row: &ExportRow) -> anyhow::Result<()> {
+        let today = Utc::now().date_naive();
+        let mut part = self.part.lock().map_err(|_| anyhow::anyhow!("export lock poisoned"))?;
+        if part.as_ref().is_some_and(|p| p.day != today) {
+            if let Some(done) = part.take() {
+                done.finish()?;
+            }
+            self.sweep(today);
+        }
+        if part.is_none() {
+            *part = Some(Part::create(&self.dir, self.format, today)?);
+        }
+        let Some(current) = part.as_mut() else { return Ok(()) };
+        current.write(row)?;
+        #[cfg(feature = "parquet-export")]
+        if current.is_full() {
+            if let Some(done) = part.take() {
+                done.finish()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Complete the current part; the next row starts a new one.
+    pub fn close(&self) -> anyhow::Result<()> {
+        let mut part = self.part.lock().map_err(|_| anyhow::anyhow!("export lock poisoned"))?;
+        match part.take() {
+            Some(done) => done.finish(),
+            None => Ok(()),
+        }
+    }
+
+    // Enforce retention and the size limit, and clear parts a crashed run left unfinished
+    fn sweep(&self, today: NaiveDate) {
+        let mut days: Vec<(NaiveDate, PathBuf, u64)> = Vec::new();
+        let Ok(entries) = fs::read_dir(&self.dir) else { return };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(day) = path.file_name().and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix("date="))
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()) else { continue };
+            let mut bytes = 0;
+            for file in fs::read_dir(&path).into_iter().flatten().flatten() {
+                let file = file.path();
+                // Only called with no part open
+                if file.to_string_lossy().ends_with(PARTIAL_SUFFIX) {
+                    log_warn!("[export] removing {}, left unfinished by an earlier run", file.display());
+                    let _ = fs::remove_file(&file);
+                    continue;
+                }
+                bytes += fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+            }
+            days.push((day, path, bytes));
+        }
+        days.sort();
+        let mut total: u64 = days.iter().map(|(_, _, bytes)| bytes).sum();
+        let oldest_kept = today - chrono::Days::new(u64::from(self.retention_days));
+        for (day, path, bytes) in days {
+            if day >= today {
+                break;
+            }
+            let expired = day < oldest_kept;
+            let over_quota = self.max_bytes > 0 && total > self.max_bytes;
+            if !expired && !over_quota {
+                continue;
+            }
+            match fs::remove_dir_all(&path) {
+                Ok(()) => {
+                    total = total.saturating_sub(bytes);
+                    log_info!("[export] removed {} ({})", path.display(), if expired { "retention" } else { "size limit" });
+                }
+                Err(e) => log_warn!("[export] could not remove {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+impl Drop for ReceiptExporter {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            log_warn!("[export] could not complete the last part: {}", e);
+        }
+    }
+}
+
+// The part being written for one UTC day
+struct Part {
+    day: NaiveDate,
+    // Where it goes once complete; it is written to `path` + PARTIAL_SUFFIX
+    path: PathBuf,
+    writer: PartWriter,
+}
+
+enum PartWriter {
+    Csv(csv::Writer<File>),
+    #[cfg(feature = "parquet-export")]
+    Parquet(parquet_part::ParquetPart),
+}
+
+impl Part {
+    fn create(dir: &Path, format: ExportFormat, day: NaiveDate) -> anyhow::Result<Self> {
+        let day_dir = dir.join(format!("date={}", day.format("%Y-%m-%d")));
+        fs::create_dir_all(&day_dir)?;
+        let path = day_dir.join(format!("part-{}.{}", Utc::now().timestamp_millis(), format.extension()));
+        let file = File::create(partial(&path))?;
+        let writer = match format {
+            ExportFormat::Csv => PartWriter::Csv(csv::Writer::from_writer(file)),
+            #[cfg(feature = "parquet-export")]
+            ExportFormat::Parquet => PartWriter::Parquet(parquet_part::ParquetPart::new(file)?),
+            #[cfg(not(feature = "parquet-export"))]
+            ExportFormat::Parquet => anyhow::bail!("EXPORT_FORMAT=parquet needs the `parquet-export` feature"),
+        };
+        Ok(Self { day, path, writer })
+    }
+
+    fn write(&mut self, row: &ExportRow) -> anyhow::Result<()> {
+        match &mut self.writer {
+            PartWriter::Csv(writer) => {
+                writer.serialize(row)?;
+                writer.flush()?;
+            }
+            #[cfg(feature = "parquet-export")]
+            PartWriter::Parquet(part) => part.write(row)?,
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "parquet-export")]
+    fn is_full(&self) -> bool {
+        match &self.writer {
+            PartWriter::Parquet(part) => part.row_groups() >= ROW_GROUPS_PER_PART,
+            PartWriter::Csv(_) => false,
+        }
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        match self.writer {
+            PartWriter::Csv(mut writer) => writer.flush()?,
+            #[cfg(feature = "parquet-export")]
+            PartWriter::Parquet(part) => part.finish()?,
+        }
+        fs::rename(partial(&self.path), &self.path)?;
+        Ok(())
+    }
+}
+
+fn partial(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(PARTIAL_SUFFIX);
+    PathBuf::from(name)
+}
+
+#[cfg(feature = "parquet-export")]
+mod parquet_part {
+    use std::fs::File;
+    use std::sync::Arc;
+    use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt16Array, UInt32Array, UInt64Array};
+    use arrow_schema::{DataType, Field, Schema, SchemaRef};
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+    use super::{ExportRow, ROW_GROUP_ROWS};
+
+    pub(super) struct ParquetPart {
+        schema: SchemaRef,
+        writer: ArrowWriter<File>,
+        pending: Vec<ExportRow>,
+        row_groups: usize,
+    }
+
+    impl ParquetPart {
+        pub(super) fn new(file: File) -> anyhow::Result<Self> {
+            let schema = schema();
+            let props = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .set_max_row_group_size(ROW_GROUP_ROWS)
+                .build();
+            let writer = ArrowWriter::try_new(file, Arc::clone(&schema), Some(props))?;
+            Ok(Self { schema, writer, pending: Vec::with_capacity(ROW_GROUP_ROWS), row_groups: 0 })
+        }
+
+        pub(super) fn write(&mut self, row: &ExportRow) -> anyhow::Result<()> {
+            self.pending.push(row.clone());
+            if self.pending.len() >= ROW_GROUP_ROWS {
+                self.flush()?;
+            }
+            Ok(())
+        }
+
+        pub(super) fn row_groups(&self) -> usize {
+            self.row_groups
+        }
+
+        pub(super) fn finish(mut self) -> anyhow::Result<()> {
+            self.flush()?;
+            self.writer.close()?;
+            Ok(())
+        }
+
+        // Write the buffered rows out as one row group
+        fn flush(&mut self) -> anyhow::Result<()> {
+            if self.pending.is_empty() {
+                return Ok(());
+            }
+            let batch = batch(&self.schema, &self.pending)?;
+            self.writer.write(&batch)?;
+            self.writer.flush()?;
+            self.pending.clear();
+            self.row_groups += 1;
+            Ok(())
+        }
+    }
+
+    fn schema() -> SchemaRef {
+        let text = |name: &str, nullable| Field::new(name, DataType::Utf8, nullable);
+        Arc::new(Schema::new(vec![
+            text("recorded_at", false),
+            text("device_did", false),
+            text("network_id", true),
+            Field::new("epoch_id", DataType::UInt64, false),
+            Field::new("nonce", DataType::UInt32, false),
+            Field::new("seq", DataType::UInt64, true),
+            text("prev_hash_hex", false),
+            text("work_root_hex", false),
+            Field::new("m", DataType::UInt64, false),
+            Field::new("n", DataType::UInt64, false),
+            Field::new("k", DataType::UInt64, false),
+            Field::new("batch", DataType::UInt64, false),
+            Field::new("time_ms", DataType::UInt64, false),
+            text("kernel_ver", false),
+            text("backend", true),
+            text("device_name", true),
+            Field::new("receipt_version", DataType::UInt16, false),
+            Field::new("key_epoch", DataType::UInt32, true),
+            Field::new("issued_at_ms", DataType::UInt64, true),
+            Field::new("energy_estimate_j", DataType::Float64, true),
+            text("idempotency_key", false),
+            text("sig_hex", false),
+            text("target", false),
+            text("outcome", false),
+            text("failure", true),
+            Field::new("status", DataType::UInt16, true),
+            Field::new("retries", DataType::UInt32, false),
+            Field::new("latency_ms", DataType::Float64, false),
+        ]))
+    }
+
+    // Columns in the order of `schema()`
+    fn batch(schema: &SchemaRef, rows: &[ExportRow]) -> anyhow::Result<RecordBatch> {
+        let text = |f: fn(&ExportRow) -> &str| -> ArrayRef { Arc::new(StringArray::from_iter_values(rows.iter().map(f))) };
+        let opt_text = |f: fn(&ExportRow) -> Option<&str>| -> ArrayRef { Arc::new(rows.iter().map(f).collect::<StringArray>()) };
+        let int = |f: fn(&ExportRow) -> u64| -> ArrayRef { Arc::new(UInt64Array::from_iter_values(rows.iter().map(f))) };
+        let opt_int = |f: fn(&ExportRow) -> Option<u64>| -> ArrayRef { Arc::new(rows.iter().map(f).collect::<UInt64Array>()) };
+        let columns: Vec<ArrayRef> = vec![
+            text(|r| &r.recorded_at),
+            text(|r| &r.device_did),
+            opt_text(|r| r.network_id.as_deref()),
+            int(|r| r.epoch_id),
+            Arc::new(UInt32Array::from_iter_values(rows.iter().map(|r| r.nonce))),
+            opt_int(|r| r.seq),
+            text(|r| &r.prev_hash_hex),
+            text(|r| &r.work_root_hex),
+            int(|r| r.m),
+            int(|r| r.n),
+            int(|r| r.k),
+            int(|r| r.batch),
+            int(|r| r.time_ms),
+            text(|r| &r.kernel_ver),
+            opt_text(|r| r.backend.as_deref()),
+            opt_text(|r| r.device_name.as_deref()),
+            Arc::new(UInt16Array::from_iter_values(rows.iter().map(|r| r.receipt_version))),
+            Arc::new(rows.iter().map(|r| r.key_epoch).collect::<UInt32Array>()),
+            opt_int(|r| r.issued_at_ms),
+            Arc::new(rows.iter().map(|r| r.energy_estimate_j).collect::<Float64Array>()),
+            text(|r| &r.idempotency_key),
+            text(|r| &r.sig_hex),
+            text(|r| &r.target),
+            text(|r| &r.outcome),
+            opt_text(|r| r.failure.as_deref()),
+            Arc::new(rows.iter().map(|r| r.status).collect::<UInt16Array>()),
+            Arc::new(UInt32Array::from_iter_values(rows.iter().map(|r| r.retries))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.latency_ms))),
+        ];
+        Ok(RecordBatch::try_new(Arc::clone(schema), columns)?)
+    }
+}
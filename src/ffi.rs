@@ -0,0 +1,318 @@
+#![cfg(feature = "ffi")]
+//! C ABI for embedding the attempt engine (see `include/tops_worker.h`).
+//!
+//! Every function returns `TOPS_OK` or an error code; the code and a message for the
+//! last failure on the calling thread are available from `tops_last_error` and
+//! `tops_last_error_message`. Handles are opaque and must be released with the
+//! matching `*_free` function. Panics never cross the boundary.
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use crate::attempt::run_attempt;
+use crate::cpu::CpuExec;
+use crate::signing::Secp;
+use crate::streams::SharedExecutor;
+use crate::types::{Sizes, WorkReceipt};
+
+pub const TOPS_OK: c_int = 0;
+pub const TOPS_ERR_NULL_POINTER: c_int = 1;
+pub const TOPS_ERR_INVALID_ARGUMENT: c_int = 2;
+pub const TOPS_ERR_BACKEND_UNAVAILABLE: c_int = 3;
+pub const TOPS_ERR_EXECUTION: c_int = 4;
+pub const TOPS_ERR_SIGNING: c_int = 5;
+pub const TOPS_ERR_BUFFER_TOO_SMALL: c_int = 6;
+pub const TOPS_ERR_PANIC: c_int = 7;
+
+pub const TOPS_BACKEND_AUTO: c_int = 0;
+pub const TOPS_BACKEND_CPU: c_int = 1;
+pub const TOPS_BACKEND_OPENCL: c_int = 2;
+pub const TOPS_BACKEND_CUDA: c_int = 3;
+
+/// Opaque executor handle.
+pub struct TopsExecutor {
+    executor: SharedExecutor,
+}
+
+/// Opaque signing key handle.
+pub struct TopsSigner {
+    secp: Secp,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TopsSizes {
+    pub m: u32,
+    pub n: u32,
+    pub k: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TopsAttemptResult {
+    pub work_root: [u8; 32],
+    pub elapsed_ms: u64,
+}
+
+struct FfiError {
+    code: c_int,
+    message: String,
+}
+
+impl FfiError {
+    fn new(code: c_int, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<(c_int, CString)> = RefCell::new((TOPS_OK, CString::default()));
+}
+
+fn set_last_error(code: c_int, message: &str) {
+    // Interior NULs would truncate the C string anyway
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = (code, message));
+}
+
+// Run `f`, turning errors and panics into a code plus the thread's last error
+fn ffi_call(f: impl FnOnce() -> Result<(), FfiError>) -> c_int {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => {
+            set_last_error(TOPS_OK, "");
+            TOPS_OK
+        }
+        Ok(Err(e)) => {
+            set_last_error(e.code, &e.message);
+            e.code
+        }
+        Err(_) => {
+            set_last_error(TOPS_ERR_PANIC, "panic inside tops-worker");
+            TOPS_ERR_PANIC
+        }
+    }
+}
+
+fn non_null<T>(ptr: *const T, name: &str) -> Result<(), FfiError> {
+    if ptr.is_null() {
+        Err(FfiError::new(TOPS_ERR_NULL_POINTER, format!("{} is NULL", name)))
+    } else {
+        Ok(())
+    }
+}
+
+// Copy `s` plus a NUL into `buf`; the required size goes to `required` either way
+unsafe fn write_c_string(s: &str, buf: *mut c_char, len: usize, required: *mut usize) -> Result<(), FfiError> {
+    if !required.is_null() {
+        *required = s.len() + 1;
+    }
+    if buf.is_null() || len < s.len() + 1 {
+        return Err(FfiError::new(TOPS_ERR_BUFFER_TOO_SMALL, format!("need a buffer of {} bytes", s.len() + 1)));
+    }
+    std::ptr::copy_nonoverlapping(s.as_ptr() as *const c_char, buf, s.len());
+    *buf.add(s.len()) = 0;
+    Ok(())
+}
+
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, FfiError> {
+    non_null(ptr, name)?;
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| FfiError::new(TOPS_ERR_INVALID_ARGUMENT, format!("{} is not valid UTF-8", name)))
+}
+
+fn open_executor(backend: c_int) -> Result<SharedExecutor, FfiError> {
+    let unavailable = |what: &str, e: String| FfiError::new(TOPS_ERR_BACKEND_UNAVAILABLE, format!("{}: {}", what, e));
+    match backend {
+        TOPS_BACKEND_CPU => Ok(Arc::new(CpuExec::new().map_err(|e| unavailable("CPU", e.to_string()))?)),
+        TOPS_BACKEND_OPENCL => {
+            #[cfg(feature = "gpu")]
+            { Ok(Arc::new(crate::gpu::GpuExec::new().map_err(|e| unavailable("OpenCL", e.to_string()))?)) }
+            #[cfg(not(feature = "gpu"))]
+            { Err(unavailable("OpenCL", "built without the `gpu` feature".into())) }
+        }
+        TOPS_BACKEND_CUDA => {
+            #[cfg(feature = "cuda")]
+            { Ok(Arc::new(crate::gpu_cuda::CudaExec::new().map_err(|e| unavailable("CUDA", e.to_string()))?)) }
+            #[cfg(not(feature = "cuda"))]
+            { Err(unavailable("CUDA", "built without the `cuda` feature".into())) }
+        }
+        // Same preference as the worker binary: CUDA, then OpenCL, then CPU
+        TOPS_BACKEND_AUTO => open_executor(TOPS_BACKEND_CUDA)
+            .or_else(|_| open_executor(TOPS_BACKEND_OPENCL))
+            .or_else(|_| open_executor(TOPS_BACKEND_CPU)),
+        other => Err(FfiError::new(TOPS_ERR_INVALID_ARGUMENT, format!("unknown backend {}", other))),
+    }
+}
+
+/// Code of the last failure on this thread (`TOPS_OK` after a successful call).
+#[no_mangle]
+pub extern "C" fn tops_last_error() -> c_int {
+    LAST_ERROR.with(|e| e.borrow().0)
+}
+
+/// Copy the last error message on this thread into `buf` (NUL-terminated).
+///
+/// Returns the message length including the NUL; if that exceeds `len`, nothing is written.
+///
+/// # Safety
+/// `buf` must be NULL or point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tops_last_error_message(buf: *mut c_char, len: usize) -> usize {
+    LAST_ERROR.with(|e| {
+        let e = e.borrow();
+        let bytes = e.1.as_bytes_with_nul();
+        if !buf.is_null() && len >= bytes.len() {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr() as *const c_char, buf, bytes.len());
+        }
+        bytes.len()
+    })
+}
+
+/// Version of the library, e.g. "0.1.0". The string is static.
+#[no_mangle]
+pub extern "C" fn tops_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Open an execution backend (`TOPS_BACKEND_*`) and store its handle in `*out`.
+///
+/// # Safety
+/// `out` must point to writable storage for a handle.
+#[no_mangle]
+pub unsafe extern "C" fn tops_executor_new(backend: c_int, out: *mut *mut TopsExecutor) -> c_int {
+    ffi_call(|| {
+        non_null(out, "out")?;
+        let executor = open_executor(backend)?;
+        *out = Box::into_raw(Box::new(TopsExecutor { executor }));
+        Ok(())
+    })
+}
+
+/// Release an executor handle. NULL is ignored.
+///
+/// # Safety
+/// `executor` must come from `tops_executor_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn tops_executor_free(executor: *mut TopsExecutor) {
+    if !executor.is_null() {
+        drop(Box::from_raw(executor));
+    }
+}
+
+/// Backend and device name of an executor, as "backend/device", into `buf`.
+///
+/// # Safety
+/// `executor` must be a live handle; `buf` NULL or `len` writable bytes; `required` NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn tops_executor_describe(
+    executor: *const TopsExecutor,
+    buf: *mut c_char,
+    len: usize,
+    required: *mut usize,
+) -> c_int {
+    ffi_call(|| {
+        non_null(executor, "executor")?;
+        let info = (*executor).executor.device_info();
+        write_c_string(&format!("{}/{}", info.backend, info.device_name), buf, len, required)
+    })
+}
+
+/// Run one deterministic attempt: inputs from `prev_hash` (32 bytes) and `nonce`, the
+/// GEMM on the executor, and the blake3 work root over committed output samples.
+///
+/// # Safety
+/// `executor` must be a live handle, `prev_hash` point to 32 bytes, `sizes` and `out` be valid.
+#[no_mangle]
+pub unsafe extern "C" fn tops_run_attempt(
+    executor: *const TopsExecutor,
+    prev_hash: *const u8,
+    nonce: u32,
+    sizes: *const TopsSizes,
+    out: *mut TopsAttemptResult,
+) -> c_int {
+    ffi_call(|| {
+        non_null(executor, "executor")?;
+        non_null(prev_hash, "prev_hash")?;
+        non_null(sizes, "sizes")?;
+        non_null(out, "out")?;
+        let s = *sizes;
+        if s.m == 0 || s.n == 0 || s.k == 0 {
+            return Err(FfiError::new(TOPS_ERR_INVALID_ARGUMENT, "sizes must be non-zero"));
+        }
+        let prev_hash = &*(prev_hash as *const [u8; 32]);
+        let sizes = Sizes { m: s.m as usize, n: s.n as usize, k: s.k as usize, batch: 1 };
+        let attempt = run_attempt(&*(*executor).executor, prev_hash, nonce, &sizes)
+            .map_err(|e| FfiError::new(TOPS_ERR_EXECUTION, e.to_string()))?;
+        *out = TopsAttemptResult { work_root: attempt.work_root, elapsed_ms: attempt.elapsed_ms };
+        Ok(())
+    })
+}
+
+/// Load a secp256k1 signing key from 64 hex characters.
+///
+/// # Safety
+/// `sk_hex` must be a NUL-terminated string and `out` point to writable storage for a handle.
+#[no_mangle]
+pub unsafe extern "C" fn tops_signer_new(sk_hex: *const c_char, out: *mut *mut TopsSigner) -> c_int {
+    ffi_call(|| {
+        non_null(out, "out")?;
+        let secp = Secp::from_hex(str_arg(sk_hex, "sk_hex")?)
+            .map_err(|e| FfiError::new(TOPS_ERR_INVALID_ARGUMENT, format!("invalid signing key: {}", e)))?;
+        *out = Box::into_raw(Box::new(TopsSigner { secp }));
+        Ok(())
+    })
+}
+
+/// Release a signer handle. NULL is ignored.
+///
+/// # Safety
+/// `signer` must come from `tops_signer_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn tops_signer_free(signer: *mut TopsSigner) {
+    if !signer.is_null() {
+        drop(Box::from_raw(signer));
+    }
+}
+
+/// Compressed public key of the signer as hex into `buf`.
+///
+/// # Safety
+/// `signer` must be a live handle; `buf` NULL or `len` writable bytes; `required` NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn tops_signer_pubkey(
+    signer: *const TopsSigner,
+    buf: *mut c_char,
+    len: usize,
+    required: *mut usize,
+) -> c_int {
+    ffi_call(|| {
+        non_null(signer, "signer")?;
+        write_c_string(&(*signer).secp.pubkey_hex_compressed(), buf, len, required)
+    })
+}
+
+/// Sign a work receipt given as JSON (the v1 shape, optionally with `receipt_version`
+/// and `device_info`) and write the signature hex into `buf`. The signature covers the
+/// receipt's wire encoding for its version, exactly as the worker signs it.
+///
+/// # Safety
+/// `signer` must be a live handle, `receipt_json` NUL-terminated, `buf` NULL or `len`
+/// writable bytes, `required` NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn tops_sign_receipt(
+    signer: *const TopsSigner,
+    receipt_json: *const c_char,
+    buf: *mut c_char,
+    len: usize,
+    required: *mut usize,
+) -> c_int {
+    ffi_call(|| {
+        non_null(signer, "signer")?;
+        let receipt: WorkReceipt = serde_json::from_str(str_arg(receipt_json, "receipt_json")?)
+            .map_err(|e| FfiError::new(TOPS_ERR_INVALID_ARGUMENT, format!("invalid receipt JSON: {}", e)))?;
+        let sig = (*signer).secp.sign_receipt(&receipt)
+            .map_err(|e| FfiError::new(TOPS_ERR_SIGNING, e.to_string()))?;
+        write_c_string(&sig, buf, len, required)
+    })
+}
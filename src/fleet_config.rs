@@ -0,0 +1,410 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use crate::config::{Config, ConfigError};
+use crate::signing::{fleet_config_message, parse_pubkey, verify_payload};
+use crate::{log_error, log_warn};
+
+/// HTTP header carrying the management endpoint's signature of a config document.
+pub const SIGNATURE_HEADER: &str = "x-fleet-config-signature";
+
+/// Settings the main loop applies between attempts as soon as a document changes
+/// them. Every other setting is staged: it takes effect at the next start.
+pub const RELOADABLE_SETTINGS: &[&str] = &[
+    "AUTOTUNE_DISABLE",
+    "AUTOTUNE_RETUNE_DRIFT_PCT",
+    "DRAIN_TIMEOUT_SECS",
+    "PACING",
+    "RECEIPT_ENERGY_ESTIMATE",
+    "RECEIPT_PERF_CONTEXT",
+    "SELFTEST_ENABLED",
+    "SELFTEST_INTERVAL",
+    "SELFTEST_ON_MISMATCH",
+    "SHADOW_ENABLED",
+    "SUBMIT_JITTER_MS",
+    "TIMING_DRIFT_PCT",
+    "WORKER_DEBUG_RECEIPT",
+];
+
+/// Settings a fleet config document may set: tunables only. Identity, keys, trust
+/// anchors, endpoints, paths and host safety limits stay with the host, so a document
+/// naming any other setting is refused. Includes every `RELOADABLE_SETTINGS` entry.
+pub const FLEET_SETTINGS: &[&str] = &[
+    "ACTIVATION",
+    "AGGREGATOR_CONNECT_TIMEOUT_SECS",
+    "AGGREGATOR_REQUEST_TIMEOUT_SECS",
+    "ATTEMPTS_IN_FLIGHT",
+    "AUTOTUNE_BATCH_UTILIZATION_PCT",
+    "AUTOTUNE_DISABLE",
+    "AUTOTUNE_MAX_BATCH",
+    "AUTOTUNE_PRESETS",
+    "AUTOTUNE_RETUNE_DRIFT_PCT",
+    "AUTOTUNE_TARGET_MS",
+    "BACKPRESSURE_MAX_DELAY_MS",
+    "BACKPRESSURE_PAUSE_AT",
+    "BACKPRESSURE_RESUME_AT",
+    "BACKPRESSURE_SLOW_AT",
+    "CIRCUIT_FAILURE_THRESHOLD",
+    "CIRCUIT_RECOVERY_TIMEOUT_SECS",
+    "CUDA_ALGO_CANDIDATES",
+    "CUDA_ALGO_TUNING",
+    "DEVICE_SAMPLING",
+    "DRAIN_TIMEOUT_SECS",
+    "ENERGY_METER",
+    "ENERGY_SAMPLE_MS",
+    "EPOCH_POLL_SECS",
+    "EVIDENCE_SAMPLE_RATE",
+    "GRPC_DEADLINE_MS",
+    "HEALTH_CHECK_INTERVAL_MS",
+    "HEALTH_CRITICAL_CONSECUTIVE_FAILURES",
+    "HEALTH_DEGRADED_CONSECUTIVE_FAILURES",
+    "HEALTH_DEGRADED_FAILURE_RATE",
+    "HEALTH_DEGRADED_GPU_TEMP_C",
+    "HEALTH_FAILURE_WINDOW",
+    "HEALTH_STALE_SUCCESS_SECS",
+    "HEALTH_UNHEALTHY_CONSECUTIVE_FAILURES",
+    "HEALTH_UNHEALTHY_FAILURE_RATE",
+    "HEALTH_UNHEALTHY_GPU_TEMP_C",
+    "HYBRID_CPU",
+    "LIVENESS_INTERVAL_SECS",
+    "LOG_LEVEL",
+    "MAX_RETRIES",
+    "MEMHARD_KIB",
+    "MEMHARD_PASSES",
+    "METRICS_PUSH_INTERVAL_SECS",
+    "PACING",
+    "PERSISTENT_KERNEL_RANGE",
+    "PIPELINE_DEPTH",
+    "POWER_FULL_WATTS",
+    "POWER_MAX_PRICE",
+    "POWER_MIN_DUTY",
+    "POWER_PAUSE_WATTS",
+    "POWER_POLL_INTERVAL_SECS",
+    "POWER_RESUME_PRICE",
+    "POWER_RESUME_WATTS",
+    "POWER_STALE_ACTION",
+    "POWER_STALE_SECS",
+    "RATE_DECREASE_FACTOR",
+    "RATE_INCREASE_STEP",
+    "RATE_LIMIT_MIN_PER_SECOND",
+    "RATE_LIMIT_PER_SECOND",
+    "RECEIPT_ENERGY_ESTIMATE",
+    "RECEIPT_PERF_CONTEXT",
+    "RECEIPT_VERSION_MAX",
+    "RECEIPT_WIRE_FORMAT",
+    "REQUANT_OVERFLOW",
+    "REQUANT_ROUNDING",
+    "REQUANT_SCALE",
+    "RETRY_DELAY_MS",
+    "SELFTEST_ENABLED",
+    "SELFTEST_INTERVAL",
+    "SELFTEST_ON_MISMATCH",
+    "SHADOW_ENABLED",
+    "SHADOW_MAX_IN_FLIGHT",
+    "SPMM_DENSITY",
+    "SPOTCHECK_ELEMENTS",
+    "STARTUP_JITTER_MS",
+    "SUBMIT_COMPRESSION",
+    "SUBMIT_COMPRESSION_MIN_BYTES",
+    "SUBMIT_JITTER_MS",
+    "TARIFF_SCHEDULE",
+    "TIMING_DRIFT_PCT",
+    "WARMUP_ATTEMPTS",
+    "WARMUP_SECS",
+    "WORKER_DEBUG_RECEIPT",
+    "WORKLOAD_KIND",
+    "WORK_ROOT_SAMPLING",
+];
+
+/// A fleet config document: environment variables by name, overriding the host's.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FleetConfigDocument {
+    /// Only a document with a higher version than the one in effect is applied.
+    pub version: u64,
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+}
+
+impl FleetConfigDocument {
+    /// The environment (and `CONFIG_FILE`) overlaid with the document's settings, validated.
+    /// Refused if the document names a setting outside `FLEET_SETTINGS`.
+    pub fn overlay_env(&self) -> Result<Config, ConfigError> {
+        if let Some(name) = self.settings.keys().find(|name| !FLEET_SETTINGS.contains(&name.as_str())) {
+            return Err(ConfigError::ValidationError(format!("{} cannot be set by a fleet config document", name)));
+        }
+        let base = crate::config_file::env_lookup()?;
+        let config = Config::from_lookup(|name| match self.settings.get(name) {
+            Some(value) => Ok(value.clone()),
+            None => base(name),
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Settings whose value differs from `other`'s, including ones only one of them sets
+    fn changed_from(&self, other: &FleetConfigDocument) -> BTreeSet<String> {
+        self.settings.keys().chain(other.settings.keys())
+            .filter(|name| self.settings.get(*name) != other.settings.get(*name))
+            .cloned()
+            .collect()
+    }
+}
+
+// A document as stored: the body exactly as received, so its signature checks again at startup
+#[derive(Serialize, Deserialize)]
+struct SignedDocument {
+    body: String,
+    signature: String,
+}
+
+/// A newer document for the main loop to apply.
+#[derive(Debug, Clone)]
+pub struct FleetConfigUpdate {
+    pub version: u64,
+    /// The environment overlaid with the document.
+    pub config: Config,
+    /// Reloadable settings the document changed.
+    pub reload: Vec<String>,
+    /// Settings that differ from the ones the process started with and wait for a restart.
+    pub staged: Vec<String>,
+    /// The document's settings.
+    pub settings: BTreeMap<String, String>,
+}
+
+impl FleetConfigUpdate {
+    /// Copy the reloaded settings into the running configuration.
+    pub fn apply(&self, config: &mut Config) {
+        let next = &self.config;
+        for name in &self.reload {
+            match name.as_str() {
+                "AUTOTUNE_DISABLE" => config.autotune_disable = next.autotune_disable,
+                "AUTOTUNE_RETUNE_DRIFT_PCT" => config.autotune_retune_drift_pct = next.autotune_retune_drift_pct,
+                "DRAIN_TIMEOUT_SECS" => config.drain_timeout_secs = next.drain_timeout_secs,
+                "PACING" => config.pacing = next.pacing,
+                "RECEIPT_ENERGY_ESTIMATE" => config.receipt_energy_estimate = next.receipt_energy_estimate,
+                "RECEIPT_PERF_CONTEXT" => config.receipt_perf_context = next.receipt_perf_context,
+                "SELFTEST_ENABLED" => config.selftest_enabled = next.selftest_enabled,
+                "SELFTEST_INTERVAL" => config.selftest_interval = next.selftest_interval,
+                "SELFTEST_ON_MISMATCH" => config.selftest_policy = next.selftest_policy,
+                "SHADOW_ENABLED" => config.shadow_enabled = next.shadow_enabled,
+                "SUBMIT_JITTER_MS" => config.submit_jitter_ms = next.submit_jitter_ms,
+                "TIMING_DRIFT_PCT" => config.timing_drift_pct = next.timing_drift_pct,
+                "WORKER_DEBUG_RECEIPT" => config.worker_debug_receipt = next.worker_debug_receipt,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn reloads(&self, name: &str) -> bool {
+        self.reload.iter().any(|n| n == name)
+    }
+}
+
+/// Fleet config sync reported in /status.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FleetConfigStatus {
+    pub url: String,
+    /// Version of the document in effect; `None` before the first.
+    pub applied_version: Option<u64>,
+    pub applied_at: Option<String>,
+    /// Settings a document changed that take effect at the next restart.
+    pub staged_for_restart: Vec<String>,
+    pub last_poll: Option<String>,
+    /// Why the latest poll brought nothing new (unreachable, unsigned, invalid).
+    pub last_error: Option<String>,
+}
+
+/// Pulls signed config documents from `FLEET_CONFIG_URL` every `FLEET_CONFIG_POLL_SECS`.
+///
+/// A document is accepted only if the `FLEET_CONFIG_PUBKEY` signature in its
+/// `x-fleet-config-signature` header verifies over `signing::fleet_config_message`,
+/// its version is higher than the one in effect and the configuration it yields
+/// validates. Accepted documents are stored in the state directory and overlaid on
+/// the environment at the next start, which is when staged settings take effect.
+pub struct FleetConfigSync {
+    url: String,
+    pubkey_hex: String,
+    network_id: Option<String>,
+    path: PathBuf,
+    interval: Duration,
+    client: reqwest::Client,
+    // The document the process started with (empty without one)
+    startup: FleetConfigDocument,
+    // The latest document the main loop applied since
+    latest: Mutex<Option<FleetConfigUpdate>>,
+    status: Mutex<FleetConfigStatus>,
+}
+
+impl FleetConfigSync {
+    /// `None` without `FLEET_CONFIG_URL`. A stored document that still verifies is
+    /// overlaid on `config`; one that does not is ignored.
+    pub fn open(config: &mut Config) -> anyhow::Result<Option<Self>> {
+        let (Some(url), Some(pubkey_hex)) = (config.fleet_config_url.clone(), config.fleet_config_pubkey.clone()) else {
+            return Ok(None);
+        };
+        parse_pubkey(&pubkey_hex)?;
+        let mut sync = Self {
+            status: Mutex::new(FleetConfigStatus { url: url.clone(), ..FleetConfigStatus::default() }),
+            url,
+            pubkey_hex,
+            network_id: config.network_id.clone(),
+            path: config.get_fleet_config_path(),
+            interval: config.get_fleet_config_poll_interval(),
+            client: reqwest::Client::builder().timeout(Duration::from_secs(10)).build()?,
+            startup: FleetConfigDocument::default(),
+            latest: Mutex::new(None),
+        };
+        match sync.load() {
+            Ok(None) => {}
+            Ok(Some(document)) => match document.overlay_env() {
+                Ok(overlaid) => {
+                    *config = overlaid;
+                    if let Ok(mut status) = sync.status.lock() {
+                        status.applied_version = Some(document.version);
+                        status.applied_at = Some(chrono::Utc::now().to_rfc3339());
+                    }
+                    sync.startup = document;
+                }
+                Err(e) => log_warn!("[fleet-config] ignoring stored version {}: {}", document.version, e),
+            },
+            Err(e) => log_warn!("[fleet-config] ignoring {}: {}", sync.path.display(), e),
+        }
+        Ok(Some(sync))
+    }
+
+    pub fn status(&self) -> FleetConfigStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// Record that the main loop applied `update`.
+    pub fn applied(&self, update: &FleetConfigUpdate) {
+        if let Ok(mut status) = self.status.lock() {
+            status.applied_version = Some(update.version);
+            status.applied_at = Some(chrono::Utc::now().to_rfc3339());
+            status.staged_for_restart = update.staged.clone();
+        }
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = Some(update.clone());
+        }
+    }
+
+    /// Settings of the document the process started with, read from the state directory.
+    pub fn startup_settings(&self) -> &BTreeMap<String, String> {
+        &self.startup.settings
+    }
+
+    /// Reloadable settings the latest applied document sets; empty before one is applied.
+    pub fn reloaded_settings(&self) -> BTreeMap<String, String> {
+        let Ok(latest) = self.latest.lock() else { return BTreeMap::new() };
+        latest.iter()
+            .flat_map(|update| &update.settings)
+            .filter(|(name, _)| RELOADABLE_SETTINGS.contains(&name.as_str()))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    /// `startup` with the reloadable settings of the latest applied document, as the main loop runs it.
+    pub fn running_config(&self, startup: &Config) -> Config {
+        let mut config = startup.clone();
+        if let Ok(latest) = self.latest.lock() {
+            if let Some(update) = latest.as_ref() {
+                let all = FleetConfigUpdate {
+                    reload: RELOADABLE_SETTINGS.iter().map(|name| name.to_string()).collect(),
+                    ..update.clone()
+                };
+                all.apply(&mut config);
+            }
+        }
+        config
+    }
+
+    /// Poll in the background and publish each accepted document.
+    pub fn spawn(self: &Arc<Self>) -> watch::Receiver<Option<Arc<FleetConfigUpdate>>> {
+        let (tx, rx) = watch::channel(None);
+        let sync = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut current = sync.startup.clone();
+            let mut ticker = tokio::time::interval(sync.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let result = sync.poll(&mut current).await;
+                if let Ok(mut status) = sync.status.lock() {
+                    status.last_poll = Some(chrono::Utc::now().to_rfc3339());
+                    status.last_error = result.as_ref().err().map(|e| e.to_string());
+                }
+                match result {
+                    Ok(Some(update)) => {
+                        if tx.send(Some(Arc::new(update))).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => log_error!("[fleet-config] poll failed: {}", e),
+                }
+            }
+        });
+        rx
+    }
+
+    // Fetch the document; `None` when it is not newer than `current`
+    async fn poll(&self, current: &mut FleetConfigDocument) -> anyhow::Result<Option<FleetConfigUpdate>> {
+        let response = self.client.get(&self.url).send().await?.error_for_status()?;
+        let signature = response.headers().get(SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("document is not signed ({} missing)", SIGNATURE_HEADER))?;
+        let signed = SignedDocument { body: response.text().await?, signature };
+        let document = self.verify(&signed)?;
+        if document.version <= current.version {
+            if document.version < current.version {
+                anyhow::bail!("document version {} is older than version {} in effect", document.version, current.version);
+            }
+            return Ok(None);
+        }
+        let config = document.overlay_env()
+            .map_err(|e| anyhow::anyhow!("document version {} is invalid: {}", document.version, e))?;
+        self.store(&signed)?;
+
+        let reload = document.changed_from(current).into_iter()
+            .filter(|name| RELOADABLE_SETTINGS.contains(&name.as_str()))
+            .collect();
+        let staged = document.changed_from(&self.startup).into_iter()
+            .filter(|name| !RELOADABLE_SETTINGS.contains(&name.as_str()))
+            .collect();
+        let update = FleetConfigUpdate { version: document.version, config, reload, staged, settings: document.settings.clone() };
+        *current = document;
+        Ok(Some(update))
+    }
+
+    fn verify(&self, signed: &SignedDocument) -> anyhow::Result<FleetConfigDocument> {
+        let message = fleet_config_message(signed.body.as_bytes(), self.network_id.as_deref())?;
+        if !verify_payload(&message, signed.signature.trim(), &self.pubkey_hex).unwrap_or(false) {
+            anyhow::bail!("document signature does not verify against FLEET_CONFIG_PUBKEY");
+        }
+        Ok(serde_json::from_str(&signed.body)?)
+    }
+
+    fn load(&self) -> anyhow::Result<Option<FleetConfigDocument>> {
+        let raw = match std::fs::read_to_string(&self.path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(self.verify(&serde_json::from_str(&raw)?)?))
+    }
+
+    // Written aside and renamed, so a crash never leaves half a document
+    fn store(&self, signed: &SignedDocument) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(signed)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
@@ -1,29 +1,300 @@
 #[cfg(feature = "gpu")]
 use anyhow::{Result, anyhow};
 #[cfg(feature = "gpu")]
-use ocl::{Buffer, Context, Device, Kernel, Platform, Program, Queue};
+use ocl::{Buffer, Context, Device, Event, Kernel, OclPrm, Platform, Program, Queue};
 #[cfg(feature = "gpu")]
-use crate::cl_kernels::GEMM_INT8;
-use crate::types::Sizes;
+use ocl::flags::MemFlags;
+#[cfg(feature = "gpu")]
+use crate::cl_kernels::{BLAKE3, CLBLAST_GLUE, GEMM_INT8, MEMHARD_ROMIX, PERSISTENT_GEMM, REQUANT, SPMM_CSR_INT8, WORK_ROOT};
+#[cfg(feature = "gpu")]
+use crate::persistent::{NonceRange, RangeRoot};
+#[cfg(feature = "gpu")]
+use crate::work_hash::{OutputSampling, SampledRoot, WorkSampling, WORK_ROOT_SAMPLES};
+#[cfg(feature = "gpu")]
+use crate::types::{DeviceInfo, Requant, Sizes};
+#[cfg(feature = "gpu")]
+use crate::sparse::CsrMatrix;
+#[cfg(feature = "gpu")]
+use crate::memhard::{MemHardParams, MEMHARD_BLOCK_WORDS};
+#[cfg(feature = "gpu")]
+use crate::program_cache::ProgramCache;
+#[cfg(feature = "gpu")]
+use crate::phases;
+#[cfg(feature = "gpu")]
+use crate::device_memory::{DeviceMemory, OutOfDeviceMemory};
+#[cfg(feature = "gpu")]
+use crate::devices::ProbedDevice;
+#[cfg(feature = "gpu")]
+use crate::cpu::{CpuExec, CpuKernel};
+#[cfg(feature = "clblast")]
+use crate::attempt::gemm_per_item;
+#[cfg(feature = "gpu")]
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "gpu")]
+use crate::{log_info, log_warn};
+
+// Work-group side of `gemm_int8_relu_q_tiled`; must match TILE in the kernel source
+const GEMM_TILE: usize = 16;
+// Work-items per group aimed for; the naive kernel uses no local memory, so larger groups rarely help
+const LOCAL_SIZE_TARGET: usize = 256;
+// Buffer the transfer strategies are timed with at startup, about one default-size input
+#[cfg(feature = "gpu")]
+const TRANSFER_CALIBRATION_BYTES: usize = 4 << 20;
+// Timed round trips per strategy; the best one counts
+#[cfg(feature = "gpu")]
+const TRANSFER_CALIBRATION_ROUNDS: usize = 3;
+// `cl_khr_pci_bus_info` device query
+#[cfg(feature = "gpu")]
+const CL_DEVICE_PCI_BUS_INFO_KHR: u32 = 0x410F;
+
+/// What the device and driver allow for the naive GEMM kernel's work-groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkGroupLimits {
+    /// `CL_KERNEL_WORK_GROUP_SIZE`, capped by `CL_DEVICE_MAX_WORK_GROUP_SIZE`.
+    pub max_work_items: usize,
+    /// `CL_KERNEL_PREFERRED_WORK_GROUP_SIZE_MULTIPLE`: the warp or wavefront width.
+    pub preferred_multiple: usize,
+    /// `CL_DEVICE_MAX_WORK_ITEM_SIZES` of the first two dimensions.
+    pub max_item_sizes: [usize; 2],
+}
+
+impl WorkGroupLimits {
+    /// Local size for an m x n output: a full warp along rows (dimension 0, which
+    /// varies fastest within a group), then as many columns as the budget leaves,
+    /// each side no larger than the output needs.
+    pub fn local_work_size(&self, m: usize, n: usize) -> [usize; 2] {
+        let budget = self.max_work_items.clamp(1, LOCAL_SIZE_TARGET);
+        let fit = |side: usize, cap: usize| side.max(1).next_power_of_two().min(cap.max(1));
+        let wm = fit(m, self.preferred_multiple.min(budget).min(self.max_item_sizes[0]));
+        let wn = fit(n, (budget / wm).min(self.max_item_sizes[1]));
+        [wm, wn]
+    }
+
+    /// Whether the device can launch groups of `local`.
+    pub fn admits(&self, local: [usize; 2]) -> bool {
+        local[0] > 0 && local[1] > 0
+            && local[0] * local[1] <= self.max_work_items
+            && local[0] <= self.max_item_sizes[0] && local[1] <= self.max_item_sizes[1]
+    }
+}
+
+/// A local size the naive GEMM kernel ran with, reported at `/devices`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalWorkSize {
+    pub m: usize,
+    pub n: usize,
+    pub local: [usize; 2],
+    /// Set by `WG_M`/`WG_N` rather than derived from the limits.
+    pub overridden: bool,
+}
+
+/// How OpenCL inputs reach the device and outputs come back (`OPENCL_TRANSFER`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransferStrategy {
+    /// Device buffers filled from the host slice at creation and read back into a vector.
+    Copy,
+    /// Driver-allocated, host-visible staging (`CL_MEM_ALLOC_HOST_PTR`) written and read through map/unmap.
+    Mapped,
+    /// Inputs wrap the host slices (`CL_MEM_USE_HOST_PTR`); outputs come back as with `Mapped`.
+    HostPtr,
+}
+
+impl TransferStrategy {
+    pub const ALL: [TransferStrategy; 3] = [TransferStrategy::Copy, TransferStrategy::Mapped, TransferStrategy::HostPtr];
+}
+
+impl std::str::FromStr for TransferStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "copy" => Ok(TransferStrategy::Copy),
+            "mapped" => Ok(TransferStrategy::Mapped),
+            "host-ptr" => Ok(TransferStrategy::HostPtr),
+            _ => Err(format!("invalid OpenCL transfer strategy: {} (expected auto, copy, mapped or host-ptr)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for TransferStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransferStrategy::Copy => write!(f, "copy"),
+            TransferStrategy::Mapped => write!(f, "mapped"),
+            TransferStrategy::HostPtr => write!(f, "host-ptr"),
+        }
+    }
+}
+
+/// Best of the startup calibration rounds of one transfer strategy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferTiming {
+    pub strategy: TransferStrategy,
+    pub h2d_ms: f64,
+    pub d2h_ms: f64,
+    /// Set when the round trip came back wrong or failed; the strategy is then never chosen.
+    pub error: Option<String>,
+}
+
+/// The transfer strategy the OpenCL backend uses, reported at `/devices`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferReport {
+    pub strategy: TransferStrategy,
+    /// Set by `OPENCL_TRANSFER` rather than calibrated.
+    pub overridden: bool,
+    /// `CL_DEVICE_HOST_UNIFIED_MEMORY`: the device shares memory with the host.
+    pub host_unified_memory: Option<bool>,
+    pub calibration_bytes: usize,
+    /// Empty when overridden.
+    pub timings: Vec<TransferTiming>,
+}
+
+/// OpenCL GEMM kernel variant (`OPENCL_GEMM_KERNEL`). All produce bit-identical output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GemmKernel {
+    /// One work-item per output element reading A and B from global memory.
+    Naive,
+    /// 16x16 work-groups staging blocks of A and B in local memory.
+    Tiled,
+    /// CLBlast's tuned SGEMM over K panels small enough to be exact (`clblast` feature).
+    Clblast,
+}
+
+impl GemmKernel {
+    /// Tag appended to `kernel_ver` when GEMMs do not run on the built-in kernels.
+    pub fn kernel_ver_tag(&self) -> Option<&'static str> {
+        match self {
+            GemmKernel::Clblast => Some("gemm_path=clblast"),
+            GemmKernel::Naive | GemmKernel::Tiled => None,
+        }
+    }
+
+    /// Side multiple that fills whole work-groups (or CLBlast tiles).
+    pub fn alignment(&self) -> usize {
+        match self {
+            GemmKernel::Naive => 1,
+            GemmKernel::Tiled | GemmKernel::Clblast => GEMM_TILE,
+        }
+    }
+}
+
+// CLBlast when it is compiled in, the naive kernel otherwise
+impl Default for GemmKernel {
+    fn default() -> Self {
+        if cfg!(feature = "clblast") { GemmKernel::Clblast } else { GemmKernel::Naive }
+    }
+}
+
+impl std::str::FromStr for GemmKernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "naive" => Ok(GemmKernel::Naive),
+            "tiled" => Ok(GemmKernel::Tiled),
+            "clblast" if cfg!(feature = "clblast") => Ok(GemmKernel::Clblast),
+            "clblast" => Err("OPENCL_GEMM_KERNEL=clblast needs a build with the clblast feature".to_string()),
+            _ => Err(format!("invalid OpenCL GEMM kernel: {} (expected naive, tiled or clblast)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for GemmKernel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GemmKernel::Naive => write!(f, "naive"),
+            GemmKernel::Tiled => write!(f, "tiled"),
+            GemmKernel::Clblast => write!(f, "clblast"),
+        }
+    }
+}
 
 #[cfg(feature = "gpu")]
 pub struct GpuExec {
     ctx: Context,
-    q: Queue,
+    device: Device,
+    // One in-order command queue per attempt stream; queue 0 serves `run_gemm`
+    queues: Vec<Queue>,
     prog: Program,
+    info: DeviceInfo,
+    gemm_kernel: GemmKernel,
+    work_group: WorkGroupLimits,
+    // `WG_M`/`WG_N`, when the device admits them
+    local_override: Option<[usize; 2]>,
+    transfer: TransferStrategy,
+}
+
+/// Every OpenCL device on every platform, GPU or not.
+#[cfg(feature = "gpu")]
+pub fn list_devices() -> Result<Vec<DeviceInfo>> {
+    let mut out = Vec::new();
+    for platform in Platform::list() {
+        for device in Device::list_all(platform)? {
+            out.push(DeviceInfo {
+                backend: "OpenCL".into(),
+                device_name: device.name().unwrap_or_default(),
+                driver_version: device.info(ocl::enums::DeviceInfo::DriverVersion)
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// Every OpenCL device with its platform, global memory and compute units, for `/devices`.
+#[cfg(feature = "gpu")]
+pub fn probe_devices() -> Result<Vec<ProbedDevice>> {
+    use ocl::enums::{DeviceInfo as Info, DeviceInfoResult};
+    let mut out = Vec::new();
+    for platform in Platform::list() {
+        for device in Device::list_all(platform)? {
+            out.push(ProbedDevice {
+                backend: "OpenCL".into(),
+                platform: platform.name().ok(),
+                name: device.name().unwrap_or_default(),
+                driver_version: device.info(Info::DriverVersion).map(|v| v.to_string()).unwrap_or_default(),
+                memory_bytes: match device.info(Info::GlobalMemSize) {
+                    Ok(DeviceInfoResult::GlobalMemSize(bytes)) => Some(bytes),
+                    _ => None,
+                },
+                compute_units: match device.info(Info::MaxComputeUnits) {
+                    Ok(DeviceInfoResult::MaxComputeUnits(units)) => Some(units),
+                    _ => None,
+                },
+                selected: false,
+            });
+        }
+    }
+    Ok(out)
 }
 
 #[cfg(feature = "gpu")]
 impl GpuExec {
     pub fn new() -> Result<Self> {
+        Self::new_cached(None)
+    }
+
+    /// Like `new`, but reuse a compiled program binary from `cache` when one
+    /// matches this device, driver, build options and kernel sources.
+    pub fn new_cached(cache: Option<&ProgramCache>) -> Result<Self> {
         // Choose a GPU device if available, else error (caller may CPU-fallback)
         let platform = Platform::default();
         let devices = Device::list(platform, Some(ocl::flags::DEVICE_TYPE_GPU))?;
         let device = devices.into_iter()
             .next()
             .ok_or_else(|| anyhow!("No GPU device found"))?;
+        let info = DeviceInfo {
+            backend: "OpenCL".into(),
+            device_name: device.name().unwrap_or_default(),
+            driver_version: device.info(ocl::enums::DeviceInfo::DriverVersion)
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+        };
         let ctx = Context::builder().platform(platform).devices(device.clone()).build()?;
-        let q = Queue::new(&ctx, device, None)?;
+        let q = Queue::new(&ctx, device.clone(), Some(ocl::flags::QUEUE_PROFILING_ENABLE))?;
         // Optional kernel build options for tuning (TM,TN,TK)
         let tm = std::env::var("TM").ok();
         let tn = std::env::var("TN").ok();
@@ -32,21 +303,275 @@ impl GpuExec {
         if let Some(v) = tm.as_deref() { opts.push_str(&format!(" -D TM={} ", v)); }
         if let Some(v) = tn.as_deref() { opts.push_str(&format!(" -D TN={} ", v)); }
         if let Some(v) = tk.as_deref() { opts.push_str(&format!(" -D TK={} ", v)); }
-        let prog = Program::builder().src(GEMM_INT8).cmplr_opt(opts).build(&ctx)?;
-        Ok(Self { ctx, q, prog })
+        let prog = match cache {
+            Some(cache) => build_program_cached(&ctx, &device, &info, &opts, cache)?,
+            None => build_program(&ctx, &opts)?,
+        };
+        let gemm_kernel = match std::env::var("OPENCL_GEMM_KERNEL") {
+            Ok(v) => v.parse().map_err(|e: String| anyhow!(e))?,
+            Err(_) => GemmKernel::default(),
+        };
+        let work_group = query_work_group_limits(&prog, &device)?;
+        log_info!("[opencl] work-groups of up to {} work-items, preferred multiple {}, item sizes {}x{}",
+            work_group.max_work_items, work_group.preferred_multiple, work_group.max_item_sizes[0], work_group.max_item_sizes[1]);
+        let local_override = match (
+            std::env::var("WG_M").ok().and_then(|v| v.parse::<usize>().ok()),
+            std::env::var("WG_N").ok().and_then(|v| v.parse::<usize>().ok()),
+        ) {
+            (Some(wm), Some(wn)) if work_group.admits([wm, wn]) => Some([wm, wn]),
+            (Some(wm), Some(wn)) => {
+                log_warn!("[opencl] WG_M={} WG_N={} exceeds the device's work-group limits, deriving the local size instead", wm, wn);
+                None
+            }
+            _ => None,
+        };
+        crate::devices::record_work_group(work_group);
+        let transfer_override = match std::env::var("OPENCL_TRANSFER") {
+            Ok(v) if !v.eq_ignore_ascii_case("auto") => Some(v.parse::<TransferStrategy>().map_err(|e| anyhow!(e))?),
+            _ => None,
+        };
+        let mut exec = Self {
+            ctx, device, queues: vec![q], prog, info, gemm_kernel, work_group, local_override,
+            transfer: transfer_override.unwrap_or(TransferStrategy::Copy),
+        };
+        exec.select_transfer(transfer_override);
+        // CLBlast has no kernels for some devices; find out now so kernel_ver is right from the start
+        if gemm_kernel == GemmKernel::Clblast {
+            if let Err(e) = exec.probe_gemm() {
+                log_warn!("[opencl] CLBlast GEMM unavailable on {} ({}), using the built-in kernel", exec.info.device_name, e);
+                exec.gemm_kernel = GemmKernel::Naive;
+            }
+        }
+        Ok(exec)
+    }
+
+    // A small GEMM with a known result through the current kernel
+    fn probe_gemm(&self) -> Result<()> {
+        let (m, n, k) = (2, 2, 3);
+        let a = [1i8, 2, 3, -4, -5, -6];
+        let b = [7i8, -8, 9, -10, 11, -12];
+        let y = self.gemm_int8_relu_q(&a, &b, m, n, k, Requant::IDENTITY)?;
+        let expected = CpuExec::with_kernel(CpuKernel::Scalar)?.gemm_int8_relu_q(&a, &b, m, n, k, Requant::IDENTITY);
+        if y != expected {
+            return Err(anyhow!("probe GEMM returned {:?}, expected {:?}", y, expected));
+        }
+        Ok(())
+    }
+
+    /// Run GEMMs with `kernel` instead of the `OPENCL_GEMM_KERNEL` choice.
+    pub fn with_gemm_kernel(mut self, kernel: GemmKernel) -> Self {
+        self.gemm_kernel = kernel;
+        self
+    }
+
+    pub fn gemm_kernel(&self) -> GemmKernel {
+        self.gemm_kernel
+    }
+
+    pub fn work_group_limits(&self) -> WorkGroupLimits {
+        self.work_group
+    }
+
+    /// Local size of the naive GEMM kernel for an m x n output; logged the first time a size is seen.
+    pub fn local_work_size(&self, m: usize, n: usize) -> [usize; 2] {
+        let choice = LocalWorkSize {
+            m,
+            n,
+            local: self.local_override.unwrap_or_else(|| self.work_group.local_work_size(m, n)),
+            overridden: self.local_override.is_some(),
+        };
+        let local = choice.local;
+        if crate::devices::record_local_work_size(choice) {
+            log_info!("[opencl] local work size {}x{} for m,n=({},{}){}",
+                local[0], local[1], m, n, if self.local_override.is_some() { " from WG_M/WG_N" } else { "" });
+        }
+        local
+    }
+
+    /// Create `streams` command queues on the device so that many attempts can be in flight.
+    pub fn with_streams(mut self, streams: usize) -> Result<Self> {
+        while self.queues.len() < streams.max(1) {
+            self.queues.push(Queue::new(&self.ctx, self.device.clone(), Some(ocl::flags::QUEUE_PROFILING_ENABLE))?);
+        }
+        Ok(self)
+    }
+
+    pub fn transfer_strategy(&self) -> TransferStrategy {
+        self.transfer
+    }
+
+    // Use `forced`, or time a round trip through each strategy and keep the fastest that comes back intact
+    fn select_transfer(&mut self, forced: Option<TransferStrategy>) {
+        let host_unified_memory = match self.device.info(ocl::enums::DeviceInfo::HostUnifiedMemory) {
+            Ok(ocl::enums::DeviceInfoResult::HostUnifiedMemory(unified)) => Some(unified),
+            _ => None,
+        };
+        let timings = match forced {
+            Some(_) => Vec::new(),
+            None => TransferStrategy::ALL.iter().map(|&strategy| self.calibrate_transfer(strategy)).collect(),
+        };
+        let fastest = timings.iter()
+            .filter(|t| t.error.is_none())
+            .min_by(|a, b| (a.h2d_ms + a.d2h_ms).total_cmp(&(b.h2d_ms + b.d2h_ms)))
+            .map(|t| t.strategy);
+        self.transfer = forced.or(fastest).unwrap_or(TransferStrategy::Copy);
+        for t in &timings {
+            match &t.error {
+                Some(e) => log_warn!("[opencl] {} transfers unusable: {}", t.strategy, e),
+                None => log_info!("[opencl] {} transfers of {} KiB: h2d {:.3} ms, d2h {:.3} ms",
+                    t.strategy, TRANSFER_CALIBRATION_BYTES >> 10, t.h2d_ms, t.d2h_ms),
+            }
+        }
+        log_info!("[opencl] using {} host transfers{}{}", self.transfer,
+            if forced.is_some() { " from OPENCL_TRANSFER" } else { "" },
+            if host_unified_memory == Some(true) { " (host-unified memory)" } else { "" });
+        crate::devices::record_transfer(TransferReport {
+            strategy: self.transfer,
+            overridden: forced.is_some(),
+            host_unified_memory,
+            calibration_bytes: TRANSFER_CALIBRATION_BYTES,
+            timings,
+        });
+    }
+
+    // Best of a few uploads (finished by a device-side copy, so lazy drivers really move the data)
+    // and read-backs through `strategy`, by the phase timers
+    fn calibrate_transfer(&self, strategy: TransferStrategy) -> TransferTiming {
+        let q = &self.queues[0];
+        let data: Vec<i8> = (0..TRANSFER_CALIBRATION_BYTES).map(|i| (i % 251) as i8).collect();
+        let mut best = (Duration::MAX, Duration::MAX);
+        let mut round = || -> Result<()> {
+            phases::reset();
+            let h2d = Instant::now();
+            let input = self.upload(q, strategy, &data)?;
+            let resident: Buffer<i8> = self.output(q, strategy, data.len())?;
+            input.copy(&resident, None, None).enq().map_err(alloc_error)?;
+            q.finish().map_err(alloc_error)?;
+            phases::record_h2d(h2d.elapsed());
+            let d2h = Instant::now();
+            let back = self.download(strategy, &resident)?;
+            phases::record_d2h(d2h.elapsed());
+            if back != data {
+                return Err(anyhow!("data came back altered"));
+            }
+            let (h2d, d2h) = phases::take_transfers();
+            best = (best.0.min(h2d), best.1.min(d2h));
+            Ok(())
+        };
+        let error = (0..TRANSFER_CALIBRATION_ROUNDS).try_for_each(|_| round()).err().map(|e| e.to_string());
+        phases::reset();
+        let ms = |d: Duration| if error.is_some() { 0.0 } else { d.as_secs_f64() * 1000.0 };
+        TransferTiming { strategy, h2d_ms: ms(best.0), d2h_ms: ms(best.1), error }
+    }
+
+    // A device buffer holding `data`, read-only to kernels
+    fn upload<T: OclPrm>(&self, q: &Queue, strategy: TransferStrategy, data: &[T]) -> Result<Buffer<T>> {
+        let builder = Buffer::builder().queue(q.clone()).len(data.len());
+        match strategy {
+            TransferStrategy::Copy => builder.copy_host_slice(data).build().map_err(alloc_error),
+            TransferStrategy::Mapped => {
+                let buf = builder.flags(MemFlags::new().read_only().alloc_host_ptr()).build().map_err(alloc_error)?;
+                let mut map = unsafe { buf.map().write_invalidate().enq().map_err(alloc_error)? };
+                map.copy_from_slice(data);
+                map.unmap().enq().map_err(alloc_error)?;
+                Ok(buf)
+            }
+            // Every caller drops the buffer, after finishing its queue, before `data` goes out of scope
+            TransferStrategy::HostPtr => unsafe {
+                builder.flags(MemFlags::new().read_only()).use_host_slice(data).build().map_err(alloc_error)
+            },
+        }
+    }
+
+    // A device buffer of `len` elements for kernels to write
+    fn output<T: OclPrm>(&self, q: &Queue, strategy: TransferStrategy, len: usize) -> Result<Buffer<T>> {
+        let builder = Buffer::builder().queue(q.clone()).len(len);
+        match strategy {
+            TransferStrategy::Copy => builder.build().map_err(alloc_error),
+            TransferStrategy::Mapped | TransferStrategy::HostPtr =>
+                builder.flags(MemFlags::new().read_write().alloc_host_ptr()).build().map_err(alloc_error),
+        }
+    }
+
+    // The contents of `buf`, once the queue has finished writing it
+    fn download<T: OclPrm>(&self, strategy: TransferStrategy, buf: &Buffer<T>) -> Result<Vec<T>> {
+        match strategy {
+            TransferStrategy::Copy => {
+                let mut out = vec![T::default(); buf.len()];
+                buf.read(&mut out).enq()?;
+                Ok(out)
+            }
+            TransferStrategy::Mapped | TransferStrategy::HostPtr => {
+                let mut map = unsafe { buf.map().read().enq().map_err(alloc_error)? };
+                let out = map.to_vec();
+                map.unmap().enq().map_err(alloc_error)?;
+                Ok(out)
+            }
+        }
     }
 
     pub fn gemm_int8_relu_q(
         &self,
         a: &[i8], b: &[i8], m: usize, n: usize, k: usize,
-        scale_num: i32, scale_den: i32,
+        scale: Requant,
     ) -> Result<Vec<i8>> {
+        self.gemm_int8_relu_q_on(0, a, b, m, n, k, scale)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn gemm_int8_relu_q_on(
+        &self,
+        stream: usize,
+        a: &[i8], b: &[i8], m: usize, n: usize, k: usize,
+        scale: Requant,
+    ) -> Result<Vec<i8>> {
+        self.gemm_int8_relu_q_batched_on(stream, a, b, m, n, k, 1, scale)
+    }
+
+    /// `batch` GEMMs of the same shape in one launch: the third NDRange dimension
+    /// picks the item, whose A, B and Y follow the previous item's in the buffers.
+    #[allow(clippy::too_many_arguments)]
+    pub fn gemm_int8_relu_q_batched_on(
+        &self,
+        stream: usize,
+        a: &[i8], b: &[i8], m: usize, n: usize, k: usize, batch: usize,
+        scale: Requant,
+    ) -> Result<Vec<i8>> {
+        let batch = batch.max(1);
+        #[cfg(feature = "clblast")]
+        if self.gemm_kernel == GemmKernel::Clblast {
+            // CLBlast runs the items one after another
+            return gemm_per_item(a, b, &Sizes { m, n, k, batch }, |a, b, item| {
+                self.gemm_clblast_on(stream, a, b, item.m, item.n, item.k, scale)
+            });
+        }
+        let q = &self.queues[stream % self.queues.len()];
+        let buf_y = self.gemm_batched_on_device(q, a, b, m, n, k, batch, scale)?;
+
+        let d2h = Instant::now();
+        let y = self.download(self.transfer, &buf_y)?;
+        phases::record_d2h(d2h.elapsed());
+        Ok(y)
+    }
+
+    // The built-in batched GEMM kernels up to and including the launch, leaving the
+    // output in a device buffer
+    #[allow(clippy::too_many_arguments)]
+    fn gemm_batched_on_device(
+        &self,
+        q: &Queue,
+        a: &[i8], b: &[i8], m: usize, n: usize, k: usize, batch: usize,
+        scale: Requant,
+    ) -> Result<Buffer<i8>> {
         let lda = k; let ldb = n; let ldy = n;
-        let len_a = m*k; let len_b = k*n; let len_y = m*n;
+        let len_a = batch*m*k; let len_b = batch*k*n; let len_y = batch*m*n;
 
-        let buf_a: Buffer<i8> = Buffer::builder().queue(self.q.clone()).len(len_a).copy_host_slice(a).build()?;
-        let buf_b: Buffer<i8> = Buffer::builder().queue(self.q.clone()).len(len_b).copy_host_slice(b).build()?;
-        let buf_y: Buffer<i8> = Buffer::builder().queue(self.q.clone()).len(len_y).build()?;
+        // Uploads complete before the buffers are returned
+        let h2d = Instant::now();
+        let buf_a = self.upload(q, self.transfer, &a[..len_a])?;
+        let buf_b = self.upload(q, self.transfer, &b[..len_b])?;
+        let buf_y: Buffer<i8> = self.output(q, self.transfer, len_y)?;
+        phases::record_h2d(h2d.elapsed());
 
         let mi = m as i32;
         let ni = n as i32;
@@ -54,33 +579,421 @@ impl GpuExec {
         let ldai = lda as i32;
         let ldbi = ldb as i32;
         let ldyi = ldy as i32;
+        let (scale_num, scale_den, mode) = (scale.num, scale.den, scale.mode_code() as i32);
 
         let mut kb = Kernel::builder();
-        kb.program(&self.prog).name("gemm_int8_relu_q");
-        kb.queue(self.q.clone());
-        kb.global_work_size([m, n]);
+        kb.queue(q.clone());
+        match self.gemm_kernel {
+            GemmKernel::Naive => {
+                // The kernel skips work-items past the output, so the global size rounds up to whole groups
+                let [wm, wn] = self.local_work_size(m, n);
+                kb.program(&self.prog).name("gemm_int8_relu_q");
+                kb.global_work_size([m.next_multiple_of(wm), n.next_multiple_of(wn), batch]);
+                kb.local_work_size([wm, wn, 1]);
+            }
+            GemmKernel::Tiled => {
+                kb.program(&self.prog).name("gemm_int8_relu_q_tiled");
+                kb.global_work_size([m.next_multiple_of(GEMM_TILE), n.next_multiple_of(GEMM_TILE), batch]);
+                kb.local_work_size([GEMM_TILE, GEMM_TILE, 1]);
+            }
+            GemmKernel::Clblast => return Err(anyhow!("CLBlast support not compiled in")),
+        }
         kb.arg(&buf_a).arg(&buf_b).arg(&buf_y);
         kb.arg(&mi).arg(&ni).arg(&ki);
         kb.arg(&ldai).arg(&ldbi).arg(&ldyi);
-        kb.arg(&scale_num).arg(&scale_den);
-        if let (Some(wm), Some(wn)) = (
-            std::env::var("WG_M").ok().and_then(|v| v.parse::<usize>().ok()),
-            std::env::var("WG_N").ok().and_then(|v| v.parse::<usize>().ok()),
-        ) { kb.local_work_size([wm, wn]); }
+        kb.arg(&scale_num).arg(&scale_den).arg(&mode);
+        let kernel = kb.build()?;
+
+        enq_timed(q, &kernel)?;
+        Ok(buf_y)
+    }
+
+    /// `gemm_int8_relu_q_batched_on` without reading the output back: `sample_work_root`
+    /// picks the work_root samples on the device and hashes them, and only those and
+    /// the root are read. CLBlast GEMMs, outputs past `u32` indexing and samplings
+    /// that need the whole output are sampled on the host instead.
+    pub fn run_gemm_sampled_on(&self, stream: usize, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant, sample: &OutputSampling) -> Result<SampledRoot> {
+        let batch = sizes.batch.max(1);
+        let len_y = batch * sizes.m * sizes.n;
+        if self.gemm_kernel == GemmKernel::Clblast || len_y == 0 || len_y > u32::MAX as usize || sample.sampling.needs_output() {
+            return Ok(sample.root_of(&self.run_gemm_batched_on(stream, a, b, sizes, scale)?));
+        }
+        let q = &self.queues[stream % self.queues.len()];
+        let buf_y = self.gemm_batched_on_device(q, a, b, sizes.m, sizes.n, sizes.k, batch, scale)?;
+        let sample_count = WORK_ROOT_SAMPLES.min(len_y);
+
+        let h2d = Instant::now();
+        let buf_prev: Buffer<u8> = Buffer::builder().queue(q.clone()).len(32).copy_host_slice(&sample.prev_hash[..]).build().map_err(alloc_error)?;
+        phases::record_h2d(h2d.elapsed());
+        let buf_samples: Buffer<i8> = Buffer::builder().queue(q.clone()).len(sample_count).build().map_err(alloc_error)?;
+        let buf_root: Buffer<u32> = Buffer::builder().queue(q.clone()).len(8).build().map_err(alloc_error)?;
+
+        let total = len_y as u32;
+        let seeded = i32::from(sample.sampling == WorkSampling::Seeded);
+        let samples_per = sample_count as u32;
+
+        let mut kb = Kernel::builder();
+        kb.program(&self.prog).name("sample_work_root");
+        kb.queue(q.clone());
+        kb.global_work_size(1);
+        kb.arg(&buf_y).arg(&total);
+        kb.arg(&buf_prev).arg(&sample.nonce).arg(&seeded);
+        kb.arg(&samples_per).arg(&buf_samples).arg(&buf_root);
         let kernel = kb.build()?;
 
-        unsafe { kernel.enq()?; }
-        self.q.finish()?;
+        enq_timed(q, &kernel)?;
+
+        let d2h = Instant::now();
+        let mut words = [0u32; 8];
+        buf_root.read(&mut words[..]).enq()?;
+        let mut samples = vec![0i8; sample_count];
+        buf_samples.read(&mut samples[..]).enq()?;
+        phases::record_d2h(d2h.elapsed());
+
+        let mut work_root = [0u8; 32];
+        for (w, word) in words.iter().enumerate() {
+            work_root[w * 4..(w + 1) * 4].copy_from_slice(&word.to_le_bytes());
+        }
+        Ok(SampledRoot { work_root, samples })
+    }
+
+    /// The int8 GEMM through CLBlast: A and B are widened to float on the device,
+    /// each K panel of at most `MAX_EXACT_K` is one SGEMM whose exact integer result
+    /// is added into an int32 accumulator, and the accumulator is requantized.
+    #[cfg(feature = "clblast")]
+    #[allow(clippy::too_many_arguments)]
+    fn gemm_clblast_on(
+        &self,
+        stream: usize,
+        a: &[i8], b: &[i8], m: usize, n: usize, k: usize,
+        scale: Requant,
+    ) -> Result<Vec<i8>> {
+        use crate::clblast::{sgemm, MAX_EXACT_K};
+        let q = &self.queues[stream % self.queues.len()];
+        let len_y = m * n;
+
+        let h2d = Instant::now();
+        let buf_a = self.upload(q, self.transfer, &a[..m * k])?;
+        let buf_b = self.upload(q, self.transfer, &b[..k * n])?;
+        phases::record_h2d(h2d.elapsed());
+        let buf_af: Buffer<f32> = Buffer::builder().queue(q.clone()).len(m * k).build().map_err(alloc_error)?;
+        let buf_bf: Buffer<f32> = Buffer::builder().queue(q.clone()).len(k * n).build().map_err(alloc_error)?;
+        let buf_panel: Buffer<f32> = Buffer::builder().queue(q.clone()).len(len_y).build().map_err(alloc_error)?;
+        let buf_acc: Buffer<i32> = Buffer::builder().queue(q.clone()).len(len_y).fill_val(0).build().map_err(alloc_error)?;
+        let buf_y: Buffer<i8> = self.output(q, self.transfer, len_y)?;
+
+        for (src, dst, len) in [(&buf_a, &buf_af, m * k), (&buf_b, &buf_bf, k * n)] {
+            let len_i = len as i32;
+            let kernel = self.elementwise_kernel(q, "int8_to_f32", len).arg(src).arg(dst).arg(&len_i).build()?;
+            unsafe { kernel.enq().map_err(alloc_error)?; }
+        }
+        let len_yi = len_y as i32;
+        let accumulate = self.elementwise_kernel(q, "accumulate_f32_i32", len_y).arg(&buf_panel).arg(&buf_acc).arg(&len_yi).build()?;
+        for t0 in (0..k).step_by(MAX_EXACT_K) {
+            let panel = MAX_EXACT_K.min(k - t0);
+            sgemm(q, m, n, panel, &buf_af, t0, k, &buf_bf, t0 * n, n, &buf_panel, n)?;
+            unsafe { accumulate.enq().map_err(alloc_error)?; }
+        }
+        let (scale_num, scale_den, mode) = (scale.num, scale.den, scale.mode_code() as i32);
+        let requantize = self.elementwise_kernel(q, "requantize_i32", len_y)
+            .arg(&buf_acc).arg(&buf_y).arg(&len_yi)
+            .arg(&scale_num).arg(&scale_den).arg(&mode)
+            .build()?;
+        unsafe { requantize.enq().map_err(alloc_error)?; }
+        q.finish().map_err(alloc_error)?;
 
-        let mut y = vec![0i8; len_y];
-        buf_y.read(&mut y).enq()?;
+        let d2h = Instant::now();
+        let y = self.download(self.transfer, &buf_y)?;
+        phases::record_d2h(d2h.elapsed());
         Ok(y)
     }
 
-    pub fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes) -> anyhow::Result<Vec<i8>> {
-        let result = self.gemm_int8_relu_q(a, b, sizes.m, sizes.n, sizes.k, 1, 1)?;
+    // One work-item per element of a `len`-element buffer
+    #[cfg(feature = "clblast")]
+    fn elementwise_kernel(&self, q: &Queue, name: &str, len: usize) -> ocl::builders::KernelBuilder<'_> {
+        let mut kb = Kernel::builder();
+        kb.program(&self.prog).name(name).queue(q.clone()).global_work_size(len);
+        kb
+    }
+
+    pub fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        let result = self.gemm_int8_relu_q(a, b, sizes.m, sizes.n, sizes.k, scale)?;
         Ok(result)
     }
+
+    pub fn run_gemm_on(&self, stream: usize, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.gemm_int8_relu_q_on(stream, a, b, sizes.m, sizes.n, sizes.k, scale)
+    }
+
+    /// All `sizes.batch` items of a batched attempt in one kernel launch.
+    pub fn run_gemm_batched_on(&self, stream: usize, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        self.gemm_int8_relu_q_batched_on(stream, a, b, sizes.m, sizes.n, sizes.k, sizes.batch, scale)
+    }
+
+    /// CSR x dense SpMM with requantization on one of the queues.
+    pub fn run_spmm_on(&self, stream: usize, a: &CsrMatrix, b: &[i8], sizes: &Sizes, scale: Requant) -> Result<Vec<i8>> {
+        let q = &self.queues[stream % self.queues.len()];
+        let len_y = sizes.m * sizes.n;
+        // OpenCL rejects zero-sized buffers, which an all-zero A would need
+        let nnz = a.nnz().max(1);
+        let mut col_idx = a.col_idx.clone();
+        let mut vals = a.values.clone();
+        col_idx.resize(nnz, 0);
+        vals.resize(nnz, 0);
+
+        let h2d = Instant::now();
+        let buf_ptr = self.upload(q, self.transfer, &a.row_ptr)?;
+        let buf_idx = self.upload(q, self.transfer, &col_idx)?;
+        let buf_val = self.upload(q, self.transfer, &vals)?;
+        let buf_b = self.upload(q, self.transfer, b)?;
+        let buf_y: Buffer<i8> = self.output(q, self.transfer, len_y)?;
+        phases::record_h2d(h2d.elapsed());
+
+        let mi = sizes.m as i32;
+        let ni = sizes.n as i32;
+        let (scale_num, scale_den, mode) = (scale.num, scale.den, scale.mode_code() as i32);
+
+        let mut kb = Kernel::builder();
+        kb.program(&self.prog).name("spmm_csr_int8_relu_q");
+        kb.queue(q.clone());
+        kb.global_work_size([sizes.m, sizes.n]);
+        kb.arg(&buf_ptr).arg(&buf_idx).arg(&buf_val).arg(&buf_b).arg(&buf_y);
+        kb.arg(&mi).arg(&ni);
+        kb.arg(&scale_num).arg(&scale_den).arg(&mode);
+        let kernel = kb.build()?;
+
+        enq_timed(q, &kernel)?;
+
+        let d2h = Instant::now();
+        let y = self.download(self.transfer, &buf_y)?;
+        phases::record_d2h(d2h.elapsed());
+        Ok(y)
+    }
+
+    /// Memory-hard ROMix stage on the device; the scratch buffer lives in device memory.
+    pub fn run_memhard_on(&self, stream: usize, block: &[u32; MEMHARD_BLOCK_WORDS], params: &MemHardParams) -> Result<[u32; MEMHARD_BLOCK_WORDS]> {
+        let q = &self.queues[stream % self.queues.len()];
+        let blocks = params.blocks().max(1);
+        let buf_v: Buffer<u32> = Buffer::builder().queue(q.clone()).len(blocks * MEMHARD_BLOCK_WORDS).build().map_err(alloc_error)?;
+        let buf_x: Buffer<u32> = Buffer::builder().queue(q.clone()).len(MEMHARD_BLOCK_WORDS).copy_host_slice(block).build().map_err(alloc_error)?;
+
+        let n = blocks as u32;
+        let iters = params.iterations() as u64;
+
+        let mut kb = Kernel::builder();
+        kb.program(&self.prog).name("memhard_romix");
+        kb.queue(q.clone());
+        kb.global_work_size(1);
+        kb.arg(&buf_v).arg(&buf_x);
+        kb.arg(&n).arg(&iters);
+        let kernel = kb.build()?;
+
+        enq_timed(q, &kernel)?;
+
+        let mut x = [0u32; MEMHARD_BLOCK_WORDS];
+        buf_x.read(&mut x[..]).enq()?;
+        Ok(x)
+    }
+
+    /// Persistent-kernel attempts for a whole nonce range in one launch: one work-group
+    /// per compute unit, each walking its share of the range with its own scratch
+    /// for A, B and Y. Only the prev_hash and salt go up; only the roots and their
+    /// samples come back.
+    pub fn run_nonce_range_on(&self, stream: usize, range: &NonceRange) -> Result<Vec<RangeRoot>> {
+        let q = &self.queues[stream % self.queues.len()];
+        let sizes = &range.sizes;
+        let batch = sizes.batch.max(1);
+        let (len_a, len_b, len_y) = (batch * sizes.m * sizes.k, batch * sizes.k * sizes.n, range.output_len());
+        if len_y == 0 || range.count == 0 {
+            return Ok(Vec::new());
+        }
+        let count = range.count as usize;
+        let sample_count = WORK_ROOT_SAMPLES.min(len_y);
+        let groups = self.compute_units().clamp(1, count);
+        let local = self.persistent_local_size()?;
+
+        let h2d = Instant::now();
+        let buf_prev: Buffer<u8> = Buffer::builder().queue(q.clone()).len(32).copy_host_slice(&range.prev_hash[..]).build().map_err(alloc_error)?;
+        let buf_salt: Buffer<u8> = Buffer::builder().queue(q.clone()).len(32).copy_host_slice(&range.salt.unwrap_or_default()[..]).build().map_err(alloc_error)?;
+        phases::record_h2d(h2d.elapsed());
+        let buf_a: Buffer<i8> = Buffer::builder().queue(q.clone()).len(groups * len_a).build().map_err(alloc_error)?;
+        let buf_b: Buffer<i8> = Buffer::builder().queue(q.clone()).len(groups * len_b).build().map_err(alloc_error)?;
+        let buf_y: Buffer<i8> = Buffer::builder().queue(q.clone()).len(groups * len_y).build().map_err(alloc_error)?;
+        let buf_roots: Buffer<u32> = Buffer::builder().queue(q.clone()).len(count * 8).build().map_err(alloc_error)?;
+        let buf_samples: Buffer<i8> = Buffer::builder().queue(q.clone()).len(count * sample_count).build().map_err(alloc_error)?;
+
+        let has_salt = i32::from(range.salt.is_some());
+        let (mi, ni, ki, bi) = (sizes.m as i32, sizes.n as i32, sizes.k as i32, batch as i32);
+        let scale = range.scale;
+        let (scale_num, scale_den, mode) = (scale.num, scale.den, scale.mode_code() as i32);
+        let samples_per = sample_count as u32;
+
+        let mut kb = Kernel::builder();
+        kb.program(&self.prog).name("persistent_gemm_int8_relu_q");
+        kb.queue(q.clone());
+        kb.global_work_size(groups * local);
+        kb.local_work_size(local);
+        kb.arg(&buf_prev).arg(&buf_salt).arg(&has_salt);
+        kb.arg(&range.first_nonce).arg(&range.stride).arg(&range.count);
+        kb.arg(&mi).arg(&ni).arg(&ki).arg(&bi);
+        kb.arg(&scale_num).arg(&scale_den).arg(&mode);
+        kb.arg(&buf_a).arg(&buf_b).arg(&buf_y);
+        kb.arg(&buf_roots).arg(&buf_samples).arg(&samples_per);
+        let kernel = kb.build()?;
+
+        enq_timed(q, &kernel)?;
+
+        let d2h = Instant::now();
+        let mut words = vec![0u32; count * 8];
+        buf_roots.read(&mut words[..]).enq()?;
+        let mut samples = vec![0i8; count * sample_count];
+        buf_samples.read(&mut samples[..]).enq()?;
+        phases::record_d2h(d2h.elapsed());
+
+        Ok(range.nonces().enumerate().map(|(i, nonce)| {
+            let mut work_root = [0u8; 32];
+            for (w, word) in words[i * 8..(i + 1) * 8].iter().enumerate() {
+                work_root[w * 4..(w + 1) * 4].copy_from_slice(&word.to_le_bytes());
+            }
+            RangeRoot { nonce, work_root, samples: samples[i * sample_count..(i + 1) * sample_count].to_vec() }
+        }).collect())
+    }
+
+    // Work-groups the persistent kernel runs at once: one per compute unit
+    fn compute_units(&self) -> usize {
+        match self.device.info(ocl::enums::DeviceInfo::MaxComputeUnits) {
+            Ok(ocl::enums::DeviceInfoResult::MaxComputeUnits(units)) => units as usize,
+            _ => 1,
+        }
+    }
+
+    // The persistent kernel keeps a BLAKE3 state in private memory, so it may allow
+    // smaller groups than the GEMM kernels
+    fn persistent_local_size(&self) -> Result<usize> {
+        use ocl::enums::{KernelWorkGroupInfo, KernelWorkGroupInfoResult};
+        let kernel = ocl::core::create_kernel(&self.prog, "persistent_gemm_int8_relu_q")?;
+        let kernel_max = match ocl::core::get_kernel_work_group_info(&kernel, &self.device, KernelWorkGroupInfo::WorkGroupSize)? {
+            KernelWorkGroupInfoResult::WorkGroupSize(size) => size,
+            _ => return Err(anyhow!("driver did not report CL_KERNEL_WORK_GROUP_SIZE")),
+        };
+        Ok(kernel_max.min(self.work_group.max_work_items).clamp(1, LOCAL_SIZE_TARGET))
+    }
+
+    pub fn device_info(&self) -> DeviceInfo {
+        self.info.clone()
+    }
+
+    /// Global memory and the largest single allocation; OpenCL has no portable free-memory query.
+    pub fn memory_info(&self) -> Option<DeviceMemory> {
+        let total_bytes = match self.device.info(ocl::enums::DeviceInfo::GlobalMemSize).ok()? {
+            ocl::enums::DeviceInfoResult::GlobalMemSize(bytes) => bytes,
+            _ => return None,
+        };
+        let max_alloc_bytes = match self.device.info(ocl::enums::DeviceInfo::MaxMemAllocSize) {
+            Ok(ocl::enums::DeviceInfoResult::MaxMemAllocSize(bytes)) => Some(bytes),
+            _ => None,
+        };
+        Some(DeviceMemory { total_bytes, free_bytes: None, max_alloc_bytes })
+    }
+
+    /// PCI address from `cl_khr_pci_bus_info`, on drivers that implement it.
+    pub fn pci_address(&self) -> Option<String> {
+        // CL_DEVICE_PCI_BUS_INFO_KHR: four cl_uint, domain, bus, device and function
+        let raw = ocl::core::get_device_info_raw(&self.device, CL_DEVICE_PCI_BUS_INFO_KHR).ok()?;
+        let field = |i: usize| raw.get(i * 4..i * 4 + 4).map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]));
+        Some(format!("{:04x}:{:02x}:{:02x}.{:x}", field(0)?, field(1)?, field(2)?, field(3)?))
+    }
+}
+
+// Work-group limits of the naive GEMM kernel on `device`
+#[cfg(feature = "gpu")]
+fn query_work_group_limits(prog: &Program, device: &Device) -> Result<WorkGroupLimits> {
+    use ocl::enums::{DeviceInfo as Info, DeviceInfoResult, KernelWorkGroupInfo, KernelWorkGroupInfoResult};
+    let kernel = ocl::core::create_kernel(prog, "gemm_int8_relu_q")?;
+    let kernel_max = match ocl::core::get_kernel_work_group_info(&kernel, device, KernelWorkGroupInfo::WorkGroupSize)? {
+        KernelWorkGroupInfoResult::WorkGroupSize(size) => size,
+        _ => return Err(anyhow!("driver did not report CL_KERNEL_WORK_GROUP_SIZE")),
+    };
+    let preferred_multiple = match ocl::core::get_kernel_work_group_info(&kernel, device, KernelWorkGroupInfo::PreferredWorkGroupSizeMultiple) {
+        Ok(KernelWorkGroupInfoResult::PreferredWorkGroupSizeMultiple(multiple)) => multiple.max(1),
+        _ => 1,
+    };
+    let device_max = match device.info(Info::MaxWorkGroupSize) {
+        Ok(DeviceInfoResult::MaxWorkGroupSize(size)) => size,
+        _ => kernel_max,
+    };
+    let max_work_items = kernel_max.min(device_max).max(1);
+    let max_item_sizes = match device.info(Info::MaxWorkItemSizes) {
+        Ok(DeviceInfoResult::MaxWorkItemSizes(sizes)) if sizes.len() >= 2 => [sizes[0], sizes[1]],
+        _ => [max_work_items, max_work_items],
+    };
+    Ok(WorkGroupLimits { max_work_items, preferred_multiple, max_item_sizes })
+}
+
+// Enqueue `kernel` and wait for it, recording its time by the queue's profiling counters
+#[cfg(feature = "gpu")]
+fn enq_timed(q: &Queue, kernel: &Kernel) -> Result<()> {
+    let mut event = Event::empty();
+    unsafe { kernel.cmd().enew(&mut event).enq().map_err(alloc_error)?; }
+    q.finish().map_err(alloc_error)?;
+    let time = |info| event.profiling_info(info).ok().and_then(|r| r.time().ok());
+    if let (Some(start), Some(end)) = (time(ocl::enums::ProfilingInfo::Start), time(ocl::enums::ProfilingInfo::End)) {
+        phases::record_device_kernel(Duration::from_nanos(end.saturating_sub(start)));
+    }
+    Ok(())
+}
+
+/// Allocation failures come back from buffer creation or, with lazy allocation,
+/// from the first enqueue that touches the buffer.
+#[cfg(feature = "gpu")]
+fn alloc_error(e: ocl::Error) -> anyhow::Error {
+    use ocl::core::Status;
+    match e.api_status() {
+        Some(status @ (Status::CL_MEM_OBJECT_ALLOCATION_FAILURE | Status::CL_OUT_OF_RESOURCES | Status::CL_INVALID_BUFFER_SIZE)) =>
+            OutOfDeviceMemory(format!("{:?}", status)).into(),
+        _ => e.into(),
+    }
+}
+
+#[cfg(feature = "gpu")]
+fn build_program(ctx: &Context, opts: &str) -> Result<Program> {
+    Ok(Program::builder().src(REQUANT).src(GEMM_INT8).src(CLBLAST_GLUE).src(SPMM_CSR_INT8).src(MEMHARD_ROMIX).src(BLAKE3).src(WORK_ROOT).src(PERSISTENT_GEMM).cmplr_opt(opts).build(ctx)?)
+}
+
+// Load the binary for this build if cached; on a miss, or when the driver
+// rejects it, compile from source and cache the result
+#[cfg(feature = "gpu")]
+fn build_program_cached(ctx: &Context, device: &Device, info: &DeviceInfo, opts: &str, cache: &ProgramCache) -> Result<Program> {
+    let key = ProgramCache::key(&info.device_name, &info.driver_version, opts, &[REQUANT, GEMM_INT8, CLBLAST_GLUE, SPMM_CSR_INT8, MEMHARD_ROMIX, BLAKE3, WORK_ROOT, PERSISTENT_GEMM]);
+    if let Some(binary) = cache.load(&key) {
+        let loaded = Program::builder()
+            .devices(device.clone())
+            .binaries(&[binary.as_slice()])
+            .cmplr_opt(opts)
+            .build(ctx);
+        match loaded {
+            Ok(prog) => {
+                log_info!("[opencl] loaded cached program binary {}", key);
+                return Ok(prog);
+            }
+            Err(e) => {
+                log_warn!("[opencl] cached program binary rejected, rebuilding from source: {}", e);
+                cache.remove(&key);
+            }
+        }
+    }
+
+    let prog = build_program(ctx, opts)?;
+    match prog.info(ocl::enums::ProgramInfo::Binaries) {
+        Ok(ocl::enums::ProgramInfoResult::Binaries(binaries)) if !binaries.is_empty() => {
+            if let Err(e) = cache.store(&key, &binaries[0]) {
+                log_warn!("[opencl] could not cache program binary in {}: {}", cache.dir().display(), e);
+            }
+        }
+        Ok(_) => log_warn!("[opencl] driver returned no program binary, not caching"),
+        Err(e) => log_warn!("[opencl] could not read program binary: {}", e),
+    }
+    Ok(prog)
 }
 
 #[cfg(not(feature = "gpu"))]
@@ -1,54 +1,531 @@
 #![cfg(feature = "cuda")]
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use anyhow::{anyhow, Result};
-use cudarc::cublaslt::{CublasLt, Gemm, MatLayout, Scale, TypeI8};
-use cudarc::driver::{CudaDevice, DeviceRepr, LaunchAsync};
+use cudarc::cublaslt::{CublasLt, Gemm, MatLayout, MatmulAlgo, Scale, TypeI32, TypeI8};
+use cudarc::driver::{sys, CudaDevice, CudaSlice, CudaStream, DriverError, LaunchAsync, LaunchConfig};
+use crate::algo_cache::{AlgoCache, CachedAlgo};
+use crate::cl_kernels;
+use crate::device_memory::{is_out_of_memory, DeviceMemory, OutOfDeviceMemory};
+use crate::devices::ProbedDevice;
+use crate::phases;
+use crate::types::{DeviceInfo, Requant, Sizes};
+use crate::{log_info, log_warn};
+
+// Number of buffer sets per shape: one computing while the next is being filled
+const SLOTS_PER_SHAPE: usize = 2;
+// Timed runs per heuristic candidate during algorithm tuning (after one warm-up)
+const TUNE_ITERS: usize = 5;
+
+const REQUANT_MODULE: &str = "requant";
+
+// The OpenCL requantization compiled by NVRTC, so cuBLASLt's int32 accumulators are
+// requantized with exactly the integer arithmetic of the other backends
+const CUDA_REQUANT_PRELUDE: &str = r#"
+#define REQUANT_FN __device__
+#define clamp(x, lo, hi) min(max((x), (lo)), (hi))
+"#;
+
+const CUDA_REQUANTIZE_I32: &str = r#"
+extern "C" __global__ void requantize_i32(
+    const int* acc, signed char* Y, const int len,
+    const int scale_num, const int scale_den, const int mode
+) {
+    int i = blockIdx.x * blockDim.x + threadIdx.x;
+    if (i < len) Y[i] = requantize(acc[i], scale_num, scale_den, mode);
+}
+"#;
+
+/// Page-locked host buffer so H2D/D2H copies can run asynchronously on a stream.
+struct PinnedBuf {
+    ptr: *mut i8,
+    len: usize,
+}
+
+unsafe impl Send for PinnedBuf {}
+
+impl PinnedBuf {
+    fn new(len: usize) -> Result<Self> {
+        let mut ptr: *mut std::ffi::c_void = std::ptr::null_mut();
+        let rc = unsafe { sys::cuMemAllocHost_v2(&mut ptr, len.max(1)) };
+        if rc == sys::CUresult::CUDA_ERROR_OUT_OF_MEMORY {
+            return Err(OutOfDeviceMemory(format!("cuMemAllocHost of {} bytes", len)).into());
+        }
+        if rc != sys::CUresult::CUDA_SUCCESS {
+            return Err(anyhow!("cuMemAllocHost failed: {:?}", rc));
+        }
+        Ok(Self { ptr: ptr as *mut i8, len })
+    }
+
+    fn as_slice(&self) -> &[i8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [i8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for PinnedBuf {
+    fn drop(&mut self) {
+        unsafe { sys::cuMemFreeHost(self.ptr as *mut std::ffi::c_void); }
+    }
+}
+
+/// Block the host until everything enqueued on `stream` has finished. Unlike
+/// `CudaDevice::wait_for`, which only orders the stream against the default one,
+/// this is what makes pinned host buffers safe to read or overwrite.
+fn synchronize(stream: &CudaStream) -> Result<()> {
+    let rc = unsafe { sys::cuStreamSynchronize(stream.stream) };
+    if rc != sys::CUresult::CUDA_SUCCESS {
+        return Err(anyhow!("cuStreamSynchronize failed: {:?}", rc));
+    }
+    Ok(())
+}
+
+/// Pair of events around work on a stream, timing it by the device's clock.
+struct KernelTimer {
+    start: sys::CUevent,
+    end: sys::CUevent,
+}
+
+impl KernelTimer {
+    fn new() -> Result<Self> {
+        let mut events = [std::ptr::null_mut(); 2];
+        for event in events.iter_mut() {
+            let rc = unsafe { sys::cuEventCreate(event, sys::CUevent_flags::CU_EVENT_DEFAULT as u32) };
+            if rc != sys::CUresult::CUDA_SUCCESS {
+                return Err(anyhow!("cuEventCreate failed: {:?}", rc));
+            }
+        }
+        Ok(Self { start: events[0], end: events[1] })
+    }
+
+    fn record(event: sys::CUevent, stream: &CudaStream) -> Result<()> {
+        let rc = unsafe { sys::cuEventRecord(event, stream.stream) };
+        if rc != sys::CUresult::CUDA_SUCCESS {
+            return Err(anyhow!("cuEventRecord failed: {:?}", rc));
+        }
+        Ok(())
+    }
+
+    // Time between the two events, waiting for the stream to pass `end` first
+    fn elapsed(&self) -> Option<Duration> {
+        if unsafe { sys::cuEventSynchronize(self.end) } != sys::CUresult::CUDA_SUCCESS {
+            return None;
+        }
+        let mut ms = 0f32;
+        let rc = unsafe { sys::cuEventElapsedTime(&mut ms, self.start, self.end) };
+        (rc == sys::CUresult::CUDA_SUCCESS).then(|| Duration::from_secs_f32(ms.max(0.0) / 1000.0))
+    }
+}
+
+impl Drop for KernelTimer {
+    fn drop(&mut self) {
+        unsafe {
+            sys::cuEventDestroy_v2(self.start);
+            sys::cuEventDestroy_v2(self.end);
+        }
+    }
+}
+
+/// Persistent host+device buffers for one shape, bound to their own stream.
+struct Slot {
+    stream: CudaStream,
+    h_a: PinnedBuf,
+    h_b: PinnedBuf,
+    h_y: PinnedBuf,
+    d_a: CudaSlice<i8>,
+    d_b: CudaSlice<i8>,
+    // int32 accumulators cuBLASLt writes, requantized into `d_y`
+    d_acc: CudaSlice<i32>,
+    d_y: CudaSlice<i8>,
+}
+
+struct ShapeBuffers {
+    slots: Vec<Arc<Mutex<Slot>>>,
+    next: usize,
+}
+
+/// Handle to a GEMM that has been enqueued but not yet read back.
+pub struct PendingGemm {
+    slot: Arc<Mutex<Slot>>,
+    len_y: usize,
+}
 
 pub struct CudaExec {
-    dev: CudaDevice,
+    dev: Arc<CudaDevice>,
     lt: CublasLt,
+    // Device allocations are reused across attempts, keyed by (m, n, k)
+    buffers: Mutex<HashMap<(usize, usize, usize), ShapeBuffers>>,
+    slots_per_shape: usize,
+    // cuBLASLt algorithm per shape; None means the library default
+    algos: Mutex<HashMap<(usize, usize, usize), Option<MatmulAlgo>>>,
+    algo_cache: Option<AlgoCache>,
+    algo_candidates: usize,
+}
+
+/// Every CUDA device visible to the driver, in ordinal order.
+pub fn list_devices() -> Result<Vec<DeviceInfo>> {
+    let driver_version = cudarc::driver::result::driver_version()
+        .map(|v| format!("{}.{}", v / 1000, (v % 1000) / 10))
+        .unwrap_or_default();
+    (0..CudaDevice::count()? as usize)
+        .map(|ordinal| {
+            Ok(DeviceInfo {
+                backend: "CUDA".into(),
+                device_name: CudaDevice::new(ordinal)?.name().unwrap_or_default(),
+                driver_version: driver_version.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Every CUDA device with its memory and multiprocessor count, for `/devices`.
+/// Uses the driver API directly so no context is created.
+pub fn probe_devices() -> Result<Vec<ProbedDevice>> {
+    use cudarc::driver::result::device;
+    cudarc::driver::result::init()?;
+    let driver_version = cudarc::driver::result::driver_version()
+        .map(|v| format!("{}.{}", v / 1000, (v % 1000) / 10))
+        .unwrap_or_default();
+    (0..device::get_count()?)
+        .map(|ordinal| {
+            let dev = device::get(ordinal)?;
+            Ok(ProbedDevice {
+                backend: "CUDA".into(),
+                platform: None,
+                name: device::get_name(dev).unwrap_or_default(),
+                driver_version: driver_version.clone(),
+                memory_bytes: unsafe { device::total_mem(dev) }.ok().map(|bytes| bytes as u64),
+                compute_units: unsafe { device::get_attribute(dev, sys::CUdevice_attribute::CU_DEVICE_ATTRIBUTE_MULTIPROCESSOR_COUNT) }
+                    .ok()
+                    .map(|count| count as u32),
+                selected: false,
+            })
+        })
+        .collect()
 }
 
 impl CudaExec {
     pub fn new() -> Result<Self> {
         let dev = CudaDevice::new(0)?;
         let lt = CublasLt::new()?;
-        Ok(Self { dev, lt })
+        let source = format!("{}{}{}", CUDA_REQUANT_PRELUDE, cl_kernels::REQUANT, CUDA_REQUANTIZE_I32);
+        let ptx = cudarc::nvrtc::compile_ptx(source).map_err(|e| anyhow!("NVRTC failed to compile requantize_i32: {:?}", e))?;
+        dev.load_ptx(ptx, REQUANT_MODULE, &["requantize_i32"])?;
+        Ok(Self {
+            dev,
+            lt,
+            buffers: Mutex::new(HashMap::new()),
+            slots_per_shape: SLOTS_PER_SHAPE,
+            algos: Mutex::new(HashMap::new()),
+            algo_cache: None,
+            algo_candidates: 0,
+        })
+    }
+
+    /// Keep at least one slot (stream + buffers) per attempt stream.
+    pub fn with_streams(mut self, streams: usize) -> Self {
+        self.slots_per_shape = streams.max(SLOTS_PER_SHAPE);
+        self
+    }
+
+    /// Benchmark up to `candidates` cuBLASLt heuristic algorithms the first time a
+    /// shape is seen and remember the fastest in `cache`, so later runs (and
+    /// restarts) go straight to it.
+    pub fn with_algo_tuning(mut self, cache: AlgoCache, candidates: usize) -> Self {
+        log_info!("[cuda] {} tuned GEMM algorithm(s) loaded from {}", cache.len(), cache.path().display());
+        self.algo_cache = Some(cache);
+        self.algo_candidates = candidates;
+        self
+    }
+
+    fn gemm_desc(m: usize, n: usize, k: usize) -> Gemm {
+        // Row-major int8 inputs, row-major int32 accumulators
+        let a_layout = MatLayout::row_major::<TypeI8>(m as i32, k as i32, k as i32);
+        let b_layout = MatLayout::row_major::<TypeI8>(k as i32, n as i32, n as i32);
+        let acc_layout = MatLayout::row_major::<TypeI32>(m as i32, n as i32, n as i32);
+
+        // Plain int8 x int8 -> int32 GEMM: the scale, rounding, overflow and activation
+        // are applied by `requantize_i32`, never by a floating-point alpha or epilogue
+        Gemm::new_i8_i8_i32(a_layout, b_layout, acc_layout)
+            .with_alpha(Scale::from_i32(1))
+            .with_beta(Scale::from_i32(0))
+    }
+
+    // Tuned algorithm for a shape: memory, then the disk cache, then a tuning pass
+    fn algo_for(&self, m: usize, n: usize, k: usize) -> Result<Option<MatmulAlgo>> {
+        let Some(cache) = &self.algo_cache else { return Ok(None) };
+        let mut algos = self.algos.lock().map_err(|_| anyhow!("CUDA algorithm cache poisoned"))?;
+        if let Some(algo) = algos.get(&(m, n, k)) {
+            return Ok(algo.clone());
+        }
+
+        let sizes = Sizes { m, n, k, batch: 1 };
+        let device = self.dev.name().unwrap_or_default();
+        let cached = cache.get(&device, &sizes)
+            .and_then(|entry| hex::decode(&entry.algo_hex).ok())
+            .and_then(|bytes| MatmulAlgo::from_bytes(&bytes).ok());
+        let algo = match cached {
+            Some(algo) => Some(algo),
+            None => match self.tune_shape(m, n, k)? {
+                Some((algo, elapsed_us, candidates)) => {
+                    log_info!("[cuda] tuned {}x{}x{}: best of {} cuBLASLt candidates at {:.1}us", m, n, k, candidates, elapsed_us);
+                    let entry = CachedAlgo {
+                        algo_hex: hex::encode(algo.to_bytes()),
+                        elapsed_us,
+                        candidates,
+                        tuned_at: chrono::Utc::now().to_rfc3339(),
+                    };
+                    if let Err(e) = cache.insert(&device, &sizes, entry) {
+                        log_warn!("[cuda] could not persist tuned algorithm: {}", e);
+                    }
+                    Some(algo)
+                }
+                None => None,
+            },
+        };
+        algos.insert((m, n, k), algo.clone());
+        Ok(algo)
+    }
+
+    // Time each heuristic candidate on scratch buffers; candidates that fail to run are skipped
+    fn tune_shape(&self, m: usize, n: usize, k: usize) -> Result<Option<(MatmulAlgo, f64, usize)>> {
+        let gemm = Self::gemm_desc(m, n, k);
+        let heuristics = self.lt.matmul_heuristics(&gemm, self.algo_candidates)?;
+        if heuristics.is_empty() {
+            return Ok(None);
+        }
+        let mut s = self.alloc_slot(m, n, k)?;
+        for (i, v) in s.h_a.as_mut_slice().iter_mut().enumerate() { *v = (i % 251) as i8; }
+        for (i, v) in s.h_b.as_mut_slice().iter_mut().enumerate() { *v = (i % 241) as i8; }
+        unsafe {
+            self.dev.htod_copy_into_async(s.h_a.as_slice(), &mut s.d_a, &s.stream)?;
+            self.dev.htod_copy_into_async(s.h_b.as_slice(), &mut s.d_b, &s.stream)?;
+        }
+        synchronize(&s.stream)?;
+
+        let mut best: Option<(MatmulAlgo, f64)> = None;
+        for heuristic in &heuristics {
+            let candidate = gemm.clone().with_algo(heuristic.algo.clone());
+            let mut run = || -> Result<()> {
+                unsafe { self.lt.run_on_stream(&s.stream, &candidate, &s.d_a, &s.d_b, &mut s.d_acc)?; }
+                synchronize(&s.stream)?;
+                Ok(())
+            };
+            if run().is_err() {
+                continue;
+            }
+            let start = Instant::now();
+            if (0..TUNE_ITERS).try_for_each(|_| run()).is_err() {
+                continue;
+            }
+            let elapsed_us = start.elapsed().as_secs_f64() * 1e6 / TUNE_ITERS as f64;
+            if best.as_ref().is_none_or(|(_, t)| elapsed_us < *t) {
+                best = Some((heuristic.algo.clone(), elapsed_us));
+            }
+        }
+        Ok(best.map(|(algo, elapsed_us)| (algo, elapsed_us, heuristics.len())))
+    }
+
+    fn alloc_slot(&self, m: usize, n: usize, k: usize) -> Result<Slot> {
+        Ok(Slot {
+            stream: self.dev.fork_default_stream()?,
+            h_a: PinnedBuf::new(m * k)?,
+            h_b: PinnedBuf::new(k * n)?,
+            h_y: PinnedBuf::new(m * n)?,
+            d_a: unsafe { self.dev.alloc::<i8>(m * k).map_err(alloc_error)? },
+            d_b: unsafe { self.dev.alloc::<i8>(k * n).map_err(alloc_error)? },
+            d_acc: unsafe { self.dev.alloc::<i32>(m * n).map_err(alloc_error)? },
+            d_y: self.dev.alloc_zeros::<i8>(m * n).map_err(alloc_error)?,
+        })
+    }
+
+    fn alloc_slots(&self, m: usize, n: usize, k: usize) -> Result<Vec<Arc<Mutex<Slot>>>> {
+        (0..self.slots_per_shape)
+            .map(|_| Ok(Arc::new(Mutex::new(self.alloc_slot(m, n, k)?))))
+            .collect()
+    }
+
+    // Slot `stream` for this shape, or the next one round-robin when `stream` is None
+    fn slot(&self, m: usize, n: usize, k: usize, stream: Option<usize>) -> Result<Arc<Mutex<Slot>>> {
+        let mut buffers = self.buffers.lock().map_err(|_| anyhow!("CUDA buffer cache poisoned"))?;
+        if !buffers.contains_key(&(m, n, k)) {
+            let slots = match self.alloc_slots(m, n, k) {
+                // Buffers of shapes the worker moved away from (after a retune or a
+                // downscale) may be what is filling the device; free them and retry once
+                Err(e) if is_out_of_memory(&e) && !buffers.is_empty() => {
+                    buffers.clear();
+                    self.alloc_slots(m, n, k)?
+                }
+                result => result?,
+            };
+            buffers.insert((m, n, k), ShapeBuffers { slots, next: 0 });
+        }
+        let shape = buffers.get_mut(&(m, n, k)).expect("inserted above");
+        let idx = match stream {
+            Some(s) => s,
+            None => {
+                let idx = shape.next;
+                shape.next = shape.next.wrapping_add(1);
+                idx
+            }
+        };
+        Ok(Arc::clone(&shape.slots[idx % shape.slots.len()]))
+    }
+
+    /// Stage inputs into pinned memory and enqueue H2D copy, GEMM and D2H copy on the
+    /// slot's stream without waiting. The host is free to prepare the next attempt
+    /// while this one is in flight; call `finish` to collect the output.
+    pub fn enqueue_gemm_int8_relu_q(
+        &self,
+        a: &[i8], b: &[i8], m: usize, n: usize, k: usize,
+        scale: Requant,
+    ) -> Result<PendingGemm> {
+        let slot = self.slot(m, n, k, None)?;
+        {
+            let mut guard = slot.lock().map_err(|_| anyhow!("CUDA slot poisoned"))?;
+            self.enqueue_into(&mut guard, a, b, m, n, k, scale)?;
+        }
+        Ok(PendingGemm { slot, len_y: m * n })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn enqueue_into(
+        &self,
+        s: &mut Slot,
+        a: &[i8], b: &[i8], m: usize, n: usize, k: usize,
+        scale: Requant,
+    ) -> Result<()> {
+        self.enqueue_h2d(s, a, b)?;
+        self.enqueue_gemm(s, m, n, k, scale)?;
+        self.enqueue_d2h(s)
+    }
+
+    fn enqueue_h2d(&self, s: &mut Slot, a: &[i8], b: &[i8]) -> Result<()> {
+        // A slot is only reused after its previous work has drained
+        synchronize(&s.stream)?;
+
+        s.h_a.as_mut_slice().copy_from_slice(a);
+        s.h_b.as_mut_slice().copy_from_slice(b);
+        unsafe {
+            self.dev.htod_copy_into_async(s.h_a.as_slice(), &mut s.d_a, &s.stream)?;
+            self.dev.htod_copy_into_async(s.h_b.as_slice(), &mut s.d_b, &s.stream)?;
+        }
+        Ok(())
+    }
+
+    fn enqueue_gemm(&self, s: &mut Slot, m: usize, n: usize, k: usize, scale: Requant) -> Result<()> {
+        let mut gemm = Self::gemm_desc(m, n, k);
+        if let Some(algo) = self.algo_for(m, n, k)? {
+            gemm = gemm.with_algo(algo);
+        }
+        let requantize = self.dev.get_func(REQUANT_MODULE, "requantize_i32")
+            .ok_or_else(|| anyhow!("requantize_i32 is not loaded"))?;
+        let len = (m * n) as u32;
+        unsafe {
+            self.lt.run_on_stream(&s.stream, &gemm, &s.d_a, &s.d_b, &mut s.d_acc)?;
+            requantize.launch_on_stream(
+                &s.stream,
+                LaunchConfig::for_num_elems(len),
+                (&s.d_acc, &mut s.d_y, len as i32, scale.num, scale.den, scale.mode_code() as i32),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn enqueue_d2h(&self, s: &mut Slot) -> Result<()> {
+        unsafe {
+            self.dev.dtoh_copy_into_async(&s.d_y, s.h_y.as_mut_slice(), &s.stream)?;
+        }
+        Ok(())
+    }
+
+    /// Wait for an enqueued GEMM and copy its output out of pinned memory.
+    pub fn finish(&self, pending: PendingGemm) -> Result<Vec<i8>> {
+        let guard = pending.slot.lock().map_err(|_| anyhow!("CUDA slot poisoned"))?;
+        synchronize(&guard.stream)?;
+        Ok(guard.h_y.as_slice()[..pending.len_y].to_vec())
     }
 
     // Interface mirrors GpuExec::gemm_int8_relu_q
     pub fn gemm_int8_relu_q(
         &self,
         a: &[i8], b: &[i8], m: usize, n: usize, k: usize,
-        scale_num: i32, scale_den: i32,
+        scale: Requant,
     ) -> Result<Vec<i8>> {
-        // Allocate device buffers
-        let d_a = self.dev.htod_copy(a)?;
-        let d_b = self.dev.htod_copy(b)?;
-        let mut d_y = self.dev.alloc_zeros::<i8>(m * n)?;
+        let pending = self.enqueue_gemm_int8_relu_q(a, b, m, n, k, scale)?;
+        self.finish(pending)
+    }
 
-        // Set layouts (row-major int8)
-        let a_layout = MatLayout::row_major::<TypeI8>(m as i32, k as i32, k as i32);
-        let b_layout = MatLayout::row_major::<TypeI8>(k as i32, n as i32, n as i32);
-        let y_layout = MatLayout::row_major::<TypeI8>(m as i32, n as i32, n as i32);
+    pub fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> Result<Vec<i8>> {
+        self.gemm_int8_relu_q(a, b, sizes.m, sizes.n, sizes.k, scale)
+    }
 
-        // Scale factor as rational -> convert to f32 alpha/beta
-        let alpha = (scale_num as f32) / (scale_den as f32);
-        let beta = 0.0f32;
+    /// Synchronous GEMM on the slot owned by attempt stream `stream`. The slot stays
+    /// locked from staging to read-back, so concurrent streams never share buffers.
+    pub fn run_gemm_on(&self, stream: usize, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> Result<Vec<i8>> {
+        let (m, n, k) = (sizes.m, sizes.n, sizes.k);
+        let slot = self.slot(m, n, k, Some(stream))?;
+        let mut guard = slot.lock().map_err(|_| anyhow!("CUDA slot poisoned"))?;
+        // Synchronizing between the stages costs little here (the call blocks anyway)
+        // and attributes the time to transfers and the kernel
+        let h2d = Instant::now();
+        self.enqueue_h2d(&mut guard, a, b)?;
+        synchronize(&guard.stream)?;
+        phases::record_h2d(h2d.elapsed());
+        let timer = KernelTimer::new()?;
+        KernelTimer::record(timer.start, &guard.stream)?;
+        self.enqueue_gemm(&mut guard, m, n, k, scale)?;
+        KernelTimer::record(timer.end, &guard.stream)?;
+        synchronize(&guard.stream)?;
+        if let Some(elapsed) = timer.elapsed() {
+            phases::record_device_kernel(elapsed);
+        }
+        let d2h = Instant::now();
+        self.enqueue_d2h(&mut guard)?;
+        synchronize(&guard.stream)?;
+        let y = guard.h_y.as_slice()[..m * n].to_vec();
+        phases::record_d2h(d2h.elapsed());
+        Ok(y)
+    }
 
-        // Run int8 GEMM with ReLU epilogue using cuBLASLt (if available in crate)
-        // Fallback: plain GEMM + clamp on host
-        let gemm = Gemm::new_i8_i8_i32(a_layout, b_layout, y_layout)
-            .with_alpha(Scale::from_f32(alpha))
-            .with_beta(Scale::from_f32(beta))
-            .with_relu(true);
+    pub fn device_info(&self) -> DeviceInfo {
+        DeviceInfo {
+            backend: "CUDA".into(),
+            device_name: self.dev.name().unwrap_or_default(),
+            driver_version: cudarc::driver::result::driver_version()
+                .map(|v| format!("{}.{}", v / 1000, (v % 1000) / 10))
+                .unwrap_or_default(),
+        }
+    }
 
-        unsafe { self.lt.run(&self.dev, &gemm, &d_a, &d_b, &mut d_y)?; }
-        self.dev.synchronize()?;
+    /// Free and total memory from `cuMemGetInfo`; the worker's own buffers count as used.
+    pub fn memory_info(&self) -> Option<DeviceMemory> {
+        self.dev.bind_to_thread().ok()?;
+        let (free, total) = cudarc::driver::result::mem_get_info().ok()?;
+        Some(DeviceMemory { total_bytes: total as u64, free_bytes: Some(free as u64), max_alloc_bytes: None })
+    }
 
-        let mut y = vec![0i8; m * n];
-        self.dev.dtoh_sync_copy_into(&d_y, &mut y)?;
-        Ok(y)
+    /// PCI address from the device's PCI domain, bus and device attributes.
+    pub fn pci_address(&self) -> Option<String> {
+        use cudarc::driver::result::device;
+        use sys::CUdevice_attribute::*;
+        let dev = device::get(self.dev.ordinal() as i32).ok()?;
+        let attribute = |attr| unsafe { device::get_attribute(dev, attr) }.ok();
+        Some(format!("{:04x}:{:02x}:{:02x}.0",
+            attribute(CU_DEVICE_ATTRIBUTE_PCI_DOMAIN_ID)?,
+            attribute(CU_DEVICE_ATTRIBUTE_PCI_BUS_ID)?,
+            attribute(CU_DEVICE_ATTRIBUTE_PCI_DEVICE_ID)?))
     }
 }
 
-
+fn alloc_error(e: DriverError) -> anyhow::Error {
+    if e.0 == sys::CUresult::CUDA_ERROR_OUT_OF_MEMORY {
+        OutOfDeviceMemory(e.to_string()).into()
+    } else {
+        e.into()
+    }
+}
@@ -0,0 +1,329 @@
+#![cfg(feature = "metal")]
+use std::sync::mpsc;
+use std::time::Instant;
+use anyhow::{anyhow, Result};
+use wgpu::util::DeviceExt;
+use crate::device_memory::OutOfDeviceMemory;
+use crate::devices::ProbedDevice;
+use crate::phases;
+use crate::types::{DeviceInfo, Requant, Sizes};
+
+// Invocations per workgroup; each writes one u32 word, i.e. four outputs
+const WORKGROUP_SIZE: u32 = 64;
+// Per-dimension cap on dispatched workgroups (`max_compute_workgroups_per_dimension` default)
+const MAX_GROUPS_PER_DIM: u32 = 65_535;
+
+/// Int8 GEMM in WGSL, which has no 8-bit or 64-bit integers: matrices are packed
+/// four int8 to a u32 and every product is formed exactly in i32. Requantization
+/// builds `|acc| * num` in two u32 halves and divides it by `den` one bit at a
+/// time, which is exact for any positive i32 scale; the remainder decides the
+/// rounding and the sign the overflow, like `Requant::apply`. There is no floating
+/// point anywhere.
+pub const GEMM_INT8_WGSL: &str = r#"
+struct Params {
+    m: u32,
+    n: u32,
+    k: u32,
+    num: u32,
+    den: u32,
+    // `Requant::mode_code`: bits 0-3 activation (0 relu, 1 identity, 2 relu6 (Q3.4: 96 = 6.0),
+    // 3 leaky (slope 1/8)), bits 4-5 rounding (0 toward zero, 1 floor, 2 half away), bit 6 wrap
+    mode: u32,
+    // Words of Y each row of workgroups covers
+    row_words: u32,
+    _pad: u32,
+}
+
+@group(0) @binding(0) var<storage, read> a: array<u32>;
+@group(0) @binding(1) var<storage, read> b: array<u32>;
+@group(0) @binding(2) var<storage, read_write> y: array<u32>;
+@group(0) @binding(3) var<uniform> params: Params;
+
+fn byte_a(i: u32) -> i32 {
+    return extractBits(bitcast<i32>(a[i >> 2u]), (i & 3u) * 8u, 8u);
+}
+
+fn byte_b(i: u32) -> i32 {
+    return extractBits(bitcast<i32>(b[i >> 2u]), (i & 3u) * 8u, 8u);
+}
+
+fn requantize(acc: i32) -> i32 {
+    let negative = acc < 0;
+    let bits = bitcast<u32>(acc);
+    let mag = select(bits, 0u - bits, negative);
+    // mag * num as hi:lo from 16-bit limbs; any positive i32 scale fits in 62 bits
+    let a0 = mag & 0xFFFFu;
+    let a1 = mag >> 16u;
+    let b0 = params.num & 0xFFFFu;
+    let b1 = params.num >> 16u;
+    let p00 = a0 * b0;
+    let p01 = a0 * b1;
+    let mid = p01 + a1 * b0;
+    let lo = p00 + (mid << 16u);
+    let hi = a1 * b1 + (mid >> 16u) + select(0u, 0x10000u, mid < p01) + select(0u, 1u, lo < p00);
+    // Bitwise long division; den < 2^31 keeps the shifted remainder within u32
+    var rem = 0u;
+    var quotient = array<u32, 2>(0u, 0u);
+    let words = array<u32, 2>(hi, lo);
+    for (var i = 0u; i < 64u; i++) {
+        let w = i >> 5u;
+        rem = (rem << 1u) | ((words[w] >> (31u - (i & 31u))) & 1u);
+        quotient[w] = quotient[w] << 1u;
+        if (rem >= params.den) {
+            rem -= params.den;
+            quotient[w] |= 1u;
+        }
+    }
+    // Rounding works on the magnitude: floor takes negative quotients away from zero
+    let rounding = (params.mode >> 4u) & 3u;
+    var inc = 0u;
+    if ((rounding == 1u && negative && rem != 0u) || (rounding == 2u && 2u * rem >= params.den)) {
+        inc = 1u;
+    }
+    var q = 0i;
+    if (((params.mode >> 6u) & 1u) == 1u) {
+        // Wrap: the low 8 bits of the signed quotient, which no carry out of the low limb reaches
+        let low = (quotient[1] + inc) & 0xFFu;
+        let bits = select(low, (256u - low) & 0xFFu, negative);
+        q = i32(bits) - select(0, 256, bits > 127u);
+    } else {
+        // Saturate: anything at or past 129 is beyond the int8 range either way
+        var q_mag = 129u;
+        if (quotient[0] == 0u && quotient[1] < 129u) {
+            q_mag = min(quotient[1] + inc, 129u);
+        }
+        q = select(i32(min(q_mag, 127u)), -i32(min(q_mag, 128u)), negative);
+    }
+    switch params.mode & 15u {
+        case 1u: {}
+        case 2u: { q = clamp(q, 0, 96); }
+        case 3u: { if (q < 0) { q = q / 8; } }
+        default: { q = max(q, 0); }
+    }
+    return q;
+}
+
+@compute @workgroup_size(64)
+fn gemm_int8_relu_q(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let word = gid.x + gid.y * params.row_words;
+    let total = params.m * params.n;
+    if (word * 4u >= total) {
+        return;
+    }
+    var packed = 0u;
+    for (var j = 0u; j < 4u; j++) {
+        let e = word * 4u + j;
+        if (e >= total) {
+            break;
+        }
+        let row = e / params.n;
+        let col = e % params.n;
+        var acc = 0i;
+        for (var t = 0u; t < params.k; t++) {
+            acc += byte_a(row * params.k + t) * byte_b(t * params.n + col);
+        }
+        packed |= (bitcast<u32>(requantize(acc)) & 0xFFu) << (j * 8u);
+    }
+    y[word] = packed;
+}
+"#;
+
+/// GEMM executor on the platform's native GPU API through wgpu: Metal on macOS
+/// (Apple Silicon), Direct3D 12 or Vulkan elsewhere.
+pub struct MetalExec {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    info: wgpu::AdapterInfo,
+    limits: wgpu::Limits,
+}
+
+fn instance() -> wgpu::Instance {
+    wgpu::Instance::new(&wgpu::InstanceDescriptor { backends: wgpu::Backends::PRIMARY, ..Default::default() })
+}
+
+// Receipts name the API the kernels ran on
+fn backend_name(backend: wgpu::Backend) -> &'static str {
+    match backend {
+        wgpu::Backend::Metal => "Metal",
+        wgpu::Backend::Dx12 => "DX12",
+        wgpu::Backend::Vulkan => "Vulkan",
+        _ => "wgpu",
+    }
+}
+
+/// Every hardware adapter wgpu can use, in enumeration order.
+pub fn list_devices() -> Result<Vec<DeviceInfo>> {
+    Ok(probe_devices()?
+        .into_iter()
+        .map(|d| DeviceInfo { backend: d.backend, device_name: d.name, driver_version: d.driver_version })
+        .collect())
+}
+
+/// Every hardware adapter, for `/devices`. wgpu reports neither memory nor compute units.
+pub fn probe_devices() -> Result<Vec<ProbedDevice>> {
+    Ok(instance()
+        .enumerate_adapters(wgpu::Backends::PRIMARY)
+        .into_iter()
+        .map(|adapter| adapter.get_info())
+        .filter(|info| info.device_type != wgpu::DeviceType::Cpu)
+        .map(|info| ProbedDevice {
+            backend: backend_name(info.backend).into(),
+            platform: None,
+            name: info.name,
+            driver_version: info.driver_info,
+            memory_bytes: None,
+            compute_units: None,
+            selected: false,
+        })
+        .collect())
+}
+
+impl MetalExec {
+    pub fn new() -> Result<Self> {
+        let adapter = pollster::block_on(instance().request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        }))
+        .ok_or_else(|| anyhow!("no Metal, Direct3D 12 or Vulkan adapter found"))?;
+        let info = adapter.get_info();
+        // A software rasterizer is slower than the CPU kernels
+        if info.device_type == wgpu::DeviceType::Cpu {
+            return Err(anyhow!("only a software adapter is available ({})", info.name));
+        }
+        let limits = adapter.limits();
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("tops-worker"),
+            required_features: wgpu::Features::empty(),
+            required_limits: limits.clone(),
+            memory_hints: wgpu::MemoryHints::Performance,
+        }, None))?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("gemm_int8_relu_q"),
+            source: wgpu::ShaderSource::Wgsl(GEMM_INT8_WGSL.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("gemm_int8_relu_q"),
+            layout: None,
+            module: &module,
+            entry_point: Some("gemm_int8_relu_q"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+        Ok(Self { device, queue, pipeline, bind_group_layout, info, limits })
+    }
+
+    /// Largest side whose matrices fit in one storage buffer binding.
+    pub fn max_side(&self) -> usize {
+        let max_binding = u64::from(self.limits.max_storage_buffer_binding_size).min(self.limits.max_buffer_size);
+        (max_binding as f64).sqrt() as usize / 4 * 4
+    }
+
+    // int8 packed four to a word, zero-padded to whole words
+    fn packed_buffer(&self, label: &str, values: &[i8]) -> wgpu::Buffer {
+        let mut bytes: Vec<u8> = values.iter().map(|&v| v as u8).collect();
+        bytes.resize(values.len().div_ceil(4).max(1) * 4, 0);
+        self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: &bytes,
+            usage: wgpu::BufferUsages::STORAGE,
+        })
+    }
+
+    pub fn gemm_int8_relu_q(&self, a: &[i8], b: &[i8], m: usize, n: usize, k: usize, scale: Requant) -> Result<Vec<i8>> {
+        let words = (m * n).div_ceil(4);
+        let y_bytes = (words.max(1) * 4) as u64;
+        // Keep validation and allocation failures as errors instead of wgpu's default panic
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+
+        let h2d = Instant::now();
+        let a_buf = self.packed_buffer("a", a);
+        let b_buf = self.packed_buffer("b", b);
+        let y_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("y"),
+            size: y_bytes,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let read_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("y readback"),
+            size: y_bytes,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let groups = (words as u32).div_ceil(WORKGROUP_SIZE).max(1);
+        let (groups_x, groups_y) = (groups.min(MAX_GROUPS_PER_DIM), groups.div_ceil(MAX_GROUPS_PER_DIM));
+        let params: [u32; 8] = [
+            m as u32, n as u32, k as u32,
+            scale.num as u32, scale.den as u32, u32::from(scale.mode_code()),
+            groups_x * WORKGROUP_SIZE, 0,
+        ];
+        let params_bytes: Vec<u8> = params.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let params_buf = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("params"),
+            contents: &params_bytes,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        phases::record_h2d(h2d.elapsed());
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gemm_int8_relu_q"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: a_buf.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: b_buf.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: y_buf.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: params_buf.as_entire_binding() },
+            ],
+        });
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("gemm") });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("gemm"), timestamp_writes: None });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(groups_x, groups_y, 1);
+        }
+        encoder.copy_buffer_to_buffer(&y_buf, 0, &read_buf, 0, y_bytes);
+        self.queue.submit(Some(encoder.finish()));
+
+        if let Some(e) = pollster::block_on(self.device.pop_error_scope()) {
+            let _ = pollster::block_on(self.device.pop_error_scope());
+            return Err(OutOfDeviceMemory(e.to_string()).into());
+        }
+        if let Some(e) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(anyhow!("Metal GEMM failed: {}", e));
+        }
+
+        let d2h = Instant::now();
+        let slice = read_buf.slice(..);
+        let (tx, rx) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        // Blocks until the GEMM and the copy have completed
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()
+            .map_err(|_| anyhow!("Metal read-back was dropped"))?
+            .map_err(|e| anyhow!("Metal read-back failed: {}", e))?;
+        let y = slice.get_mapped_range()[..m * n].iter().map(|&v| v as i8).collect();
+        read_buf.unmap();
+        phases::record_d2h(d2h.elapsed());
+        Ok(y)
+    }
+
+    pub fn run_gemm(&self, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant) -> Result<Vec<i8>> {
+        self.gemm_int8_relu_q(a, b, sizes.m, sizes.n, sizes.k, scale)
+    }
+
+    pub fn device_info(&self) -> DeviceInfo {
+        DeviceInfo {
+            backend: backend_name(self.info.backend).into(),
+            device_name: self.info.name.clone(),
+            driver_version: self.info.driver_info.clone(),
+        }
+    }
+}
@@ -0,0 +1,387 @@
+#![cfg(feature = "grpc")]
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use prost::bytes::Buf;
+use prost::Message;
+use tonic::codec::{Codec, DecodeBuf, Decoder, ProstCodec};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Request, Response, Status};
+use crate::challenge::Challenge;
+use crate::config::Config;
+use crate::error_handling::ErrorHandler;
+use crate::rate_control;
+use crate::identity::KeyRing;
+use crate::idempotency::{idempotency_key, IDEMPOTENCY_KEY_HEADER};
+use crate::response_auth::{ResponseKind, ResponseVerifier, SIGNATURE_HEADER};
+use crate::size_distribution::{SizeDistribution, WeightedSizes};
+use crate::submit::{sign_and_encode, EpochInfo, FailureKind, RejectReason, SubmitError, SubmitOutcome, SubmitResponse, Submission, Submitter};
+use crate::types::{parse_scale, select_receipt_version, RequantParams, WorkReceipt, RECEIPT_VERSION_V1};
+use crate::work_hash::HashKind;
+
+/// Client and messages generated from `proto/aggregator.proto`.
+pub mod proto {
+    tonic::include_proto!("tops.aggregator.v1");
+}
+
+use proto::{GetEpochRequest, GetEpochResponse, SubmitReceiptRequest, SubmitReceiptResponse};
+
+const SUBMIT_RECEIPT_PATH: &str = "/tops.aggregator.v1.Aggregator/SubmitReceipt";
+const GET_EPOCH_PATH: &str = "/tops.aggregator.v1.Aggregator/GetEpoch";
+
+// A decoded response with the protobuf bytes it was decoded from, which are what
+// the aggregator signed: a re-encoding can differ (unknown fields, field order)
+struct Raw<M> {
+    message: M,
+    bytes: Vec<u8>,
+}
+
+// Prost encoding for requests; responses keep their wire bytes next to the message
+struct RawCodec<Req, Resp>(PhantomData<(Req, Resp)>);
+
+impl<Req, Resp> Default for RawCodec<Req, Resp> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<Req, Resp> Codec for RawCodec<Req, Resp>
+where
+    Req: Message + Send + 'static,
+    Resp: Message + Default + Send + 'static,
+{
+    type Encode = Req;
+    type Decode = Raw<Resp>;
+    type Encoder = <ProstCodec<Req, Resp> as Codec>::Encoder;
+    type Decoder = RawDecoder<Resp>;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        ProstCodec::<Req, Resp>::default().encoder()
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        RawDecoder(PhantomData)
+    }
+}
+
+struct RawDecoder<M>(PhantomData<M>);
+
+impl<M: Message + Default> Decoder for RawDecoder<M> {
+    type Item = Raw<M>;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Raw<M>>, Status> {
+        let bytes = src.copy_to_bytes(src.remaining()).to_vec();
+        let message = M::decode(bytes.as_slice()).map_err(|e| Status::internal(format!("undecodable response: {}", e)))?;
+        Ok(Some(Raw { message, bytes }))
+    }
+}
+
+// Proto3 leaves unset fields at their zero value; those mean "no feedback"
+fn submit_response(resp: &SubmitReceiptResponse) -> SubmitResponse {
+    SubmitResponse {
+        accepted: Some(resp.accepted),
+        reason: (!resp.reason.is_empty()).then(|| RejectReason::from_code(&resp.reason)),
+        message: (!resp.message.is_empty()).then(|| resp.message.clone()),
+        next_prev_hash: (resp.next_prev_hash.len() == 32).then(|| hex::encode(&resp.next_prev_hash)),
+        next_epoch_id: (resp.next_epoch_id > 0).then_some(resp.next_epoch_id),
+        suggested_rate: (resp.suggested_rate > 0.0).then_some(resp.suggested_rate),
+        next_epoch_salt: (resp.next_epoch_salt.len() == 32).then(|| hex::encode(&resp.next_epoch_salt)),
+        request_evidence: resp.request_evidence.then_some(true),
+        challenge: resp.challenge.as_ref().filter(|c| c.prev_hash.len() == 32).map(|c| Challenge {
+            id: c.id.clone(),
+            prev_hash: hex::encode(&c.prev_hash),
+            deadline_ms: c.deadline_ms,
+        }),
+    }
+}
+
+// The message with the signature the aggregator sent in its metadata
+fn signed<T>(response: Response<T>) -> (T, Option<String>) {
+    let signature = response.metadata().get(SIGNATURE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    (response.into_inner(), signature)
+}
+
+// A failed call, or the error handler refusing to make one
+#[derive(Debug)]
+enum CallError {
+    Status(Status),
+    CircuitOpen(String),
+}
+
+impl From<String> for CallError {
+    fn from(msg: String) -> Self {
+        CallError::CircuitOpen(msg)
+    }
+}
+
+impl std::fmt::Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallError::Status(s) => write!(f, "gRPC {:?}: {}", s.code(), s.message()),
+            CallError::CircuitOpen(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+// Transient failures worth another attempt; everything else is the aggregator's verdict
+fn is_retryable(e: &CallError) -> bool {
+    matches!(e, CallError::Status(s) if matches!(s.code(), Code::Unavailable | Code::DeadlineExceeded))
+}
+
+// Usual gRPC -> HTTP status mapping, so outcomes read the same as for the HTTP transport
+fn http_equivalent(code: Code) -> u16 {
+    match code {
+        Code::Ok => 200,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => 400,
+        Code::Unauthenticated => 401,
+        Code::PermissionDenied => 403,
+        Code::NotFound => 404,
+        Code::AlreadyExists | Code::Aborted => 409,
+        Code::ResourceExhausted => 429,
+        Code::Cancelled => 499,
+        Code::Unimplemented => 501,
+        Code::Unavailable => 503,
+        Code::DeadlineExceeded => 504,
+        _ => 500,
+    }
+}
+
+/// Receipt delivery over the aggregator's gRPC service.
+///
+/// Every call carries GRPC_DEADLINE_MS as its deadline. Unavailable and
+/// DeadlineExceeded are retried through the shared `ErrorHandler`, so gRPC calls use
+/// the same MAX_RETRIES/RETRY_DELAY_MS backoff and circuit breaker as the rest of the
+/// worker. The receipt version comes from the versions listed in GetEpoch.
+pub struct GrpcSubmitter {
+    channel: Channel,
+    target: String,
+    deadline: Duration,
+    device_did: String,
+    max_version: u16,
+    // Negotiated receipt version, once GetEpoch has answered
+    receipt_version: Mutex<Option<u16>>,
+    keys: Arc<KeyRing>,
+    error_handler: Arc<ErrorHandler>,
+    verifier: Option<Arc<ResponseVerifier>>,
+}
+
+impl GrpcSubmitter {
+    /// Set up a lazily connected channel to GRPC_URL; https:// URLs use TLS with the system roots.
+    pub fn new(config: &Config, keys: Arc<KeyRing>, error_handler: Arc<ErrorHandler>) -> anyhow::Result<Self> {
+        let url = config.grpc_url.clone()
+            .ok_or_else(|| anyhow::anyhow!("GRPC_URL is required for AGGREGATOR_PROTOCOL=grpc"))?;
+        let deadline = config.get_grpc_deadline();
+        let mut endpoint = Endpoint::from_shared(url.clone())?
+            .connect_timeout(deadline)
+            .timeout(deadline);
+        if url.starts_with("https://") {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new().with_native_roots())?;
+        }
+        Ok(Self {
+            channel: endpoint.connect_lazy(),
+            target: url,
+            deadline,
+            device_did: config.device_did.clone(),
+            max_version: config.receipt_version_max,
+            receipt_version: Mutex::new(None),
+            keys,
+            error_handler,
+            verifier: None,
+        })
+    }
+
+    /// Only act on responses `verifier` authenticates; the aggregator signs the
+    /// protobuf bytes of each response as sent and sends the signature as
+    /// `x-aggregator-signature` metadata.
+    pub fn with_response_verifier(mut self, verifier: Option<Arc<ResponseVerifier>>) -> Self {
+        self.verifier = verifier;
+        self
+    }
+
+    fn authenticate<M>(&self, kind: ResponseKind, request_key: &str, response: &Raw<M>, signature: Option<&str>) -> anyhow::Result<()> {
+        let Some(verifier) = &self.verifier else { return Ok(()) };
+        Ok(verifier.verify(kind, request_key, &response.bytes, signature)?)
+    }
+
+    // One unary call whose response keeps its wire bytes
+    async fn call<Req, Resp>(&self, path: &'static str, request: Request<Req>) -> Result<Response<Raw<Resp>>, Status>
+    where
+        Req: Message + Send + Sync + 'static,
+        Resp: Message + Default + Send + Sync + 'static,
+    {
+        let mut grpc = tonic::client::Grpc::new(self.channel.clone());
+        grpc.ready().await.map_err(|e| Status::unknown(format!("Service was not ready: {}", e)))?;
+        grpc.unary(request, PathAndQuery::from_static(path), RawCodec::default()).await
+    }
+
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        // Sent as grpc-timeout so the aggregator can give up on our behalf too
+        request.set_timeout(self.deadline);
+        request
+    }
+
+    async fn get_epoch(&self) -> Result<(Raw<GetEpochResponse>, Option<String>), CallError> {
+        self.error_handler.execute_async_with_retry(|| {
+            let request = self.request(GetEpochRequest { device_did: self.device_did.clone() });
+            async move {
+                self.call(GET_EPOCH_PATH, request).await
+                    .map(signed)
+                    .map_err(CallError::Status)
+            }
+        }, is_retryable).await
+    }
+
+    fn remember_versions(&self, versions: &[u32]) -> u16 {
+        let remote: Vec<u16> = versions.iter().filter_map(|v| u16::try_from(*v).ok()).collect();
+        let version = select_receipt_version(&remote, self.max_version);
+        if let Ok(mut negotiated) = self.receipt_version.lock() {
+            *negotiated = Some(version);
+        }
+        version
+    }
+
+    async fn negotiated_version(&self) -> u16 {
+        if let Some(version) = self.receipt_version.lock().ok().and_then(|negotiated| *negotiated) {
+            return version;
+        }
+        match self.get_epoch().await {
+            Ok((epoch, _)) => self.remember_versions(&epoch.message.receipt_versions),
+            // An aggregator without GetEpoch only speaks v1
+            Err(CallError::Status(s)) if s.code() == Code::Unimplemented => self.remember_versions(&[]),
+            // Unreachable: send v1 now and ask again next time
+            Err(_) => RECEIPT_VERSION_V1,
+        }
+    }
+}
+
+#[async_trait]
+impl Submitter for GrpcSubmitter {
+    fn describe(&self) -> String {
+        format!("grpc ({}, deadline {:?})", self.target, self.deadline)
+    }
+
+    async fn submit(&self, mut receipt: WorkReceipt) -> Result<Submission, SubmitError> {
+        receipt.receipt_version = self.negotiated_version().await;
+        let (body, _) = sign_and_encode(&self.keys, &mut receipt).await?;
+        let message = SubmitReceiptRequest {
+            receipt_version: receipt.receipt_version as u32,
+            receipt: body,
+        };
+
+        // Every retry carries the same key, so the aggregator can collapse them
+        let request_key = idempotency_key(&receipt);
+        let key = MetadataValue::try_from(request_key.as_str()).ok();
+
+        let submit_start = Instant::now();
+        // The circuit breaker is applied around the whole submission by `CircuitSubmitter`
+        let result = self.error_handler.retry_async(|| {
+            let mut request = self.request(message.clone());
+            if let Some(key) = &key {
+                request.metadata_mut().insert(IDEMPOTENCY_KEY_HEADER, key.clone());
+            }
+            async move {
+                self.call::<_, SubmitReceiptResponse>(SUBMIT_RECEIPT_PATH, request).await
+                    .map(signed)
+                    .map_err(CallError::Status)
+            }
+        }, is_retryable).await;
+
+        let authenticated = match &result {
+            Ok((resp, signature)) => self.authenticate(ResponseKind::Submit, &request_key, resp, signature.as_deref()),
+            Err(_) => Ok(()),
+        };
+        let response = result.as_ref().ok().filter(|_| authenticated.is_ok()).map(|(resp, _)| submit_response(&resp.message));
+        let outcome = match result.map(|(resp, _)| resp.message) {
+            // Nothing in an unauthenticated answer is acted on, not even its verdict
+            Ok(_) if authenticated.is_err() => SubmitOutcome::Failed {
+                kind: FailureKind::Unauthenticated,
+                error: authenticated.unwrap_err().to_string(),
+            },
+            Ok(resp) if resp.accepted => SubmitOutcome::Accepted { body: resp.message },
+            Ok(resp) => SubmitOutcome::Rejected { status: 400, body: resp.message },
+            Err(CallError::Status(s)) if s.code() == Code::ResourceExhausted => SubmitOutcome::Throttled {
+                status: http_equivalent(s.code()),
+                retry_after: s.metadata().get("retry-after")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(rate_control::parse_retry_after),
+                body: s.message().to_string(),
+            },
+            Err(CallError::Status(s)) if s.code() == Code::InvalidArgument && receipt.receipt_version != RECEIPT_VERSION_V1 => {
+                // Possibly a version the aggregator no longer accepts; renegotiate on the next receipt
+                if let Ok(mut negotiated) = self.receipt_version.lock() {
+                    *negotiated = None;
+                }
+                SubmitOutcome::Rejected { status: http_equivalent(s.code()), body: s.message().to_string() }
+            }
+            // Server-side and transport failures, as with HTTP 5xx
+            Err(CallError::Status(s)) if http_equivalent(s.code()) >= 500 => {
+                let kind = match s.code() {
+                    Code::Unavailable => FailureKind::Connect,
+                    Code::DeadlineExceeded => FailureKind::Timeout,
+                    _ => FailureKind::ServerError,
+                };
+                SubmitOutcome::Failed { kind, error: CallError::Status(s).to_string() }
+            }
+            Err(CallError::Status(s)) => SubmitOutcome::Rejected {
+                status: http_equivalent(s.code()),
+                body: s.message().to_string(),
+            },
+            Err(e @ CallError::CircuitOpen(_)) => SubmitOutcome::Failed { kind: FailureKind::Network, error: e.to_string() },
+        };
+
+        Ok(Submission { target: self.target.clone(), latency: submit_start.elapsed(), outcome, compression: None, response, retries: 0, signed: Some(receipt) })
+    }
+
+    async fn current_epoch(&self) -> anyhow::Result<Option<EpochInfo>> {
+        let (raw, signature) = self.get_epoch().await.map_err(|e| anyhow::anyhow!("GetEpoch failed: {}", e))?;
+        self.authenticate(ResponseKind::Epoch, "", &raw, signature.as_deref())?;
+        let epoch = raw.message;
+        self.remember_versions(&epoch.receipt_versions);
+        let prev_hash: [u8; 32] = epoch.prev_hash.as_slice().try_into()
+            .map_err(|_| anyhow::anyhow!("GetEpoch returned a {}-byte prev_hash", epoch.prev_hash.len()))?;
+        let memhard_kib = (epoch.memhard_kib > 0).then_some(epoch.memhard_kib);
+        let min_tops_seconds = (epoch.min_tops_seconds > 0.0).then_some(epoch.min_tops_seconds);
+        let salt = match epoch.salt.len() {
+            0 => None,
+            _ => Some(epoch.salt.as_slice().try_into()
+                .map_err(|_| anyhow::anyhow!("GetEpoch returned a {}-byte salt", epoch.salt.len()))?),
+        };
+        let requant = RequantParams {
+            scale: match epoch.requant_scale.as_str() {
+                "" => None,
+                scale => Some(parse_scale(scale).ok_or_else(|| anyhow::anyhow!("GetEpoch returned an invalid requant_scale {:?}", scale))?),
+            },
+            activation: match epoch.activation.as_str() {
+                "" => None,
+                activation => Some(activation.parse().map_err(|e: String| anyhow::anyhow!("GetEpoch returned an {}", e))?),
+            },
+            rounding: match epoch.requant_rounding.as_str() {
+                "" => None,
+                rounding => Some(rounding.parse().map_err(|e: String| anyhow::anyhow!("GetEpoch returned an {}", e))?),
+            },
+            overflow: match epoch.requant_overflow.as_str() {
+                "" => None,
+                overflow => Some(overflow.parse().map_err(|e: String| anyhow::anyhow!("GetEpoch returned an {}", e))?),
+            },
+        };
+        let size_distribution = match epoch.size_distribution.len() {
+            0 => None,
+            _ => Some(SizeDistribution::new(epoch.size_distribution.iter()
+                .map(|s| WeightedSizes { m: s.m as usize, n: s.n as usize, k: s.k as usize, weight: s.weight })
+                .collect())
+                .map_err(|e| anyhow::anyhow!("GetEpoch returned an invalid size_distribution: {}", e))?),
+        };
+        let hash_kind = match epoch.hash_kind.as_str() {
+            "" => HashKind::default(),
+            kind => kind.parse().map_err(|e: String| anyhow::anyhow!("GetEpoch returned an {}", e))?,
+        };
+        Ok(Some(EpochInfo { epoch_id: epoch.epoch_id, prev_hash, memhard_kib, min_tops_seconds, salt, requant, size_distribution, hash_kind }))
+    }
+}
@@ -1,6 +1,27 @@
 use std::sync::Arc;
-use crate::metrics::{MetricsCollector, HealthStatus};
+use crate::metrics::{EpochStats, MetricsCollector, HealthStatus};
 use crate::config::Config;
+use crate::config_report::ConfigReport;
+use crate::health_policy::HealthPolicy;
+use crate::endpoints::{EndpointManager, EndpointStatus};
+use crate::did::DidVerification;
+use crate::power::{PowerController, PowerState};
+use crate::pause::{PauseSwitch, PauseState};
+use crate::limits::ResourceLimits;
+use crate::cpu::CpuDispatch;
+use crate::watchdog::{Heartbeat, HeartbeatStatus};
+use crate::warmup::{Warmup, WarmupStatus};
+use crate::error_handling::{CircuitBreaker, CircuitBreakerStatus};
+use crate::fleet_config::{FleetConfigStatus, FleetConfigSync};
+use crate::watch_only::{WatchEstimate, WatchSummary};
+use crate::net::{ConnectionStats, ConnectionSummary};
+use crate::backpressure::{BackPressure, BackPressureStatus};
+use crate::clock::{ClockStatus, ClockSync};
+use crate::challenge::{ChallengeQueue, ChallengeStatus};
+use crate::update::{UpdateChecker, UpdateStatus};
+use crate::shadow::{ShadowSink, ShadowStatus};
+use crate::loop_state::{LoopSnapshot, LoopState};
+use crate::integrity::{self, IntegrityStatus};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -9,10 +30,14 @@ pub struct HealthResponse {
     pub uptime_seconds: u64,
     pub version: String,
     pub timestamp: String,
+    pub did_verified: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MetricsResponse {
+    /// Layout version, see `metrics_schema`.
+    #[serde(default = "crate::metrics_schema::legacy_schema_version")]
+    pub schema_version: u32,
     pub metrics: crate::metrics::Metrics,
     pub health_status: String,
     pub circuit_breaker_status: Option<String>,
@@ -22,6 +47,23 @@ pub struct HealthChecker {
     metrics: Arc<MetricsCollector>,
     config: Config,
     start_time: std::time::Instant,
+    endpoints: Option<Arc<EndpointManager>>,
+    did_verifications: Vec<DidVerification>,
+    power: Option<Arc<PowerController>>,
+    pause: Option<Arc<PauseSwitch>>,
+    heartbeat: Option<Arc<Heartbeat>>,
+    loop_state: Option<Arc<LoopState>>,
+    warmup: Option<Arc<Warmup>>,
+    limits: Option<ResourceLimits>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    fleet_config: Option<Arc<FleetConfigSync>>,
+    watch_only: Option<Arc<WatchEstimate>>,
+    aggregator_connections: Option<Arc<ConnectionStats>>,
+    backpressure: Option<Arc<BackPressure>>,
+    clock: Option<Arc<ClockSync>>,
+    challenges: Option<Arc<ChallengeQueue>>,
+    update: Option<Arc<UpdateChecker>>,
+    shadow: Option<Arc<ShadowSink>>,
 }
 
 impl HealthChecker {
@@ -30,11 +72,129 @@ impl HealthChecker {
             metrics,
             config,
             start_time: std::time::Instant::now(),
+            endpoints: None,
+            did_verifications: Vec::new(),
+            power: None,
+            pause: None,
+            heartbeat: None,
+            loop_state: None,
+            warmup: None,
+            limits: None,
+            circuit_breaker: None,
+            fleet_config: None,
+            watch_only: None,
+            aggregator_connections: None,
+            backpressure: None,
+            clock: None,
+            challenges: None,
+            update: None,
+            shadow: None,
         }
     }
     
+    /// Add the verification result for one signing identity.
+    pub fn with_did_verification(mut self, verification: DidVerification) -> Self {
+        self.did_verifications.push(verification);
+        self
+    }
+    
+    pub fn with_heartbeat(mut self, heartbeat: Arc<Heartbeat>) -> Self {
+        self.heartbeat = Some(heartbeat);
+        self
+    }
+    
+    pub fn with_loop_state(mut self, loop_state: Arc<LoopState>) -> Self {
+        self.loop_state = Some(loop_state);
+        self
+    }
+    
+    pub fn with_warmup(mut self, warmup: Arc<Warmup>) -> Self {
+        self.warmup = Some(warmup);
+        self
+    }
+    
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+    
+    pub fn with_circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.circuit_breaker = Some(breaker);
+        self
+    }
+    
+    pub fn with_fleet_config(mut self, fleet_config: Arc<FleetConfigSync>) -> Self {
+        self.fleet_config = Some(fleet_config);
+        self
+    }
+    
+    pub fn with_watch_only(mut self, estimate: Arc<WatchEstimate>) -> Self {
+        self.watch_only = Some(estimate);
+        self
+    }
+    
+    pub fn with_aggregator_connections(mut self, connections: Arc<ConnectionStats>) -> Self {
+        self.aggregator_connections = Some(connections);
+        self
+    }
+    
+    pub fn with_backpressure(mut self, backpressure: Arc<BackPressure>) -> Self {
+        self.backpressure = Some(backpressure);
+        self
+    }
+    
+    pub fn with_clock(mut self, clock: Arc<ClockSync>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+    
+    pub fn with_challenges(mut self, challenges: Arc<ChallengeQueue>) -> Self {
+        self.challenges = Some(challenges);
+        self
+    }
+    
+    pub fn with_update_checker(mut self, update: Arc<UpdateChecker>) -> Self {
+        self.update = Some(update);
+        self
+    }
+    
+    pub fn with_shadow(mut self, shadow: Arc<ShadowSink>) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+    
+    // A stalled main loop is critical whatever the counters say; any DID that
+    // doesn't vouch for its key, or a tampered state file, caps health at degraded
+    fn effective_status(&self) -> HealthStatus {
+        if self.heartbeat.as_ref().is_some_and(|h| h.is_stalled()) {
+            return HealthStatus::Critical;
+        }
+        let status = self.metrics.get_health_status();
+        let tampered = integrity::installed().is_some_and(|integrity| integrity.tampered() > 0);
+        if status == HealthStatus::Healthy && (tampered || self.did_verifications.iter().any(|did| !did.is_ok())) {
+            HealthStatus::Degraded
+        } else {
+            status
+        }
+    }
+    
+    pub fn with_power_controller(mut self, power: Arc<PowerController>) -> Self {
+        self.power = Some(power);
+        self
+    }
+    
+    pub fn with_pause_switch(mut self, pause: Arc<PauseSwitch>) -> Self {
+        self.pause = Some(pause);
+        self
+    }
+    
+    pub fn with_endpoint_manager(mut self, endpoints: Arc<EndpointManager>) -> Self {
+        self.endpoints = Some(endpoints);
+        self
+    }
+    
     pub fn get_health(&self) -> HealthResponse {
-        let health_status = self.metrics.get_health_status();
+        let health_status = self.effective_status();
         let uptime_seconds = self.start_time.elapsed().as_secs();
         
         HealthResponse {
@@ -42,27 +202,35 @@ impl HealthChecker {
             uptime_seconds,
             version: env!("CARGO_PKG_VERSION").to_string(),
             timestamp: chrono::Utc::now().to_rfc3339(),
+            did_verified: (!self.did_verifications.is_empty())
+                .then(|| self.did_verifications.iter().all(|d| d.is_ok())),
         }
     }
     
     pub fn get_metrics(&self) -> MetricsResponse {
         let metrics = self.metrics.get_metrics();
-        let health_status = self.metrics.get_health_status();
+        let health_status = self.effective_status();
         
         MetricsResponse {
+            schema_version: crate::metrics_schema::METRICS_SCHEMA_VERSION,
             metrics,
             health_status: health_status.to_string(),
-            circuit_breaker_status: None, // Will be set by main if available
+            circuit_breaker_status: self.circuit_breaker.as_ref().map(|b| b.get_state()),
         }
     }
     
     pub fn is_healthy(&self) -> bool {
-        matches!(self.metrics.get_health_status(), HealthStatus::Healthy)
+        matches!(self.effective_status(), HealthStatus::Healthy)
+    }
+    
+    /// Every configuration field with its effective value and where it came from.
+    pub fn get_config_report(&self) -> ConfigReport {
+        ConfigReport::new(&self.config, self.fleet_config.as_deref())
     }
     
     pub fn get_detailed_status(&self) -> DetailedStatus {
         let metrics = self.metrics.get_metrics();
-        let health_status = self.metrics.get_health_status();
+        let health_status = self.effective_status();
         
         DetailedStatus {
             health: health_status.to_string(),
@@ -80,7 +248,7 @@ impl HealthChecker {
             receipts_per_second: metrics.receipts_per_second,
             consecutive_failures: metrics.consecutive_failures,
             error_counts: ErrorCounts {
-                gpu_errors: metrics.gpu_errors,
+                gpu_errors: metrics.device_errors,
                 network_errors: metrics.network_errors,
                 signature_errors: metrics.signature_errors,
                 validation_errors: metrics.validation_errors,
@@ -91,7 +259,31 @@ impl HealthChecker {
                 device_did: self.config.device_did.clone(),
                 max_retries: self.config.max_retries,
                 rate_limit_per_second: self.config.rate_limit_per_second,
+                aggregator_mode: self.config.aggregator_mode.to_string(),
             },
+            endpoints: self.endpoints.as_ref().map(|e| e.snapshot()).unwrap_or_default(),
+            did: self.did_verifications.first().cloned(),
+            identities: self.did_verifications.clone(),
+            power: self.power.as_ref().map(|p| p.state()),
+            pause: self.pause.as_ref().map(|p| p.state()),
+            cpu: crate::cpu::dispatch().clone(),
+            main_loop: self.heartbeat.as_ref().map(|h| h.status()),
+            loop_state: self.loop_state.as_ref().map(|l| l.snapshot()),
+            warmup: self.warmup.as_ref().map(|w| w.status()),
+            limits: self.limits.clone(),
+            epoch: metrics.epoch.clone(),
+            health_policy: self.metrics.health_policy().clone(),
+            gpu_temperature_c: metrics.gpu_temperature_c,
+            circuit_breaker: self.circuit_breaker.as_ref().map(|b| b.status()),
+            fleet_config: self.fleet_config.as_ref().map(|f| f.status()),
+            watch_only: self.watch_only.as_ref().map(|w| w.summary()),
+            aggregator_connections: self.aggregator_connections.as_ref().map(|c| c.summary()),
+            backpressure: self.backpressure.as_ref().map(|b| b.status()),
+            clock: self.clock.as_ref().map(|c| c.status()),
+            state_integrity: integrity::installed().map(|integrity| integrity.status()),
+            challenges: self.challenges.as_ref().map(|c| c.status()),
+            update: self.update.as_ref().map(|u| u.status()),
+            shadow: self.shadow.as_ref().map(|s| s.status()),
         }
     }
 }
@@ -110,6 +302,44 @@ pub struct DetailedStatus {
     pub consecutive_failures: u32,
     pub error_counts: ErrorCounts,
     pub config_summary: ConfigSummary,
+    pub endpoints: Vec<EndpointStatus>,
+    pub did: Option<DidVerification>,
+    /// Verification of every signing identity (`did` is the first).
+    pub identities: Vec<DidVerification>,
+    pub power: Option<PowerState>,
+    /// Operator pause through `/admin/pause`.
+    pub pause: Option<PauseState>,
+    pub cpu: CpuDispatch,
+    pub main_loop: Option<HeartbeatStatus>,
+    /// Nonce, epoch, sizes, queues and latest submission of the attempt loop.
+    pub loop_state: Option<LoopSnapshot>,
+    pub warmup: Option<WarmupStatus>,
+    pub limits: Option<ResourceLimits>,
+    /// The epoch attempts are chained to, with its counters so far.
+    pub epoch: EpochStats,
+    /// Thresholds `health` was classified with.
+    pub health_policy: HealthPolicy,
+    pub gpu_temperature_c: Option<f64>,
+    /// Submission circuit breaker with its recent transitions.
+    pub circuit_breaker: Option<CircuitBreakerStatus>,
+    /// Config document version in effect and settings staged for the next restart.
+    pub fleet_config: Option<FleetConfigStatus>,
+    /// Estimated contribution under `WATCH_ONLY=1`, where nothing is submitted.
+    pub watch_only: Option<WatchSummary>,
+    /// New vs reused connections of the HTTP submission client.
+    pub aggregator_connections: Option<ConnectionSummary>,
+    /// Attempt throttling on the submission backlog (`BACKPRESSURE_*`).
+    pub backpressure: Option<BackPressureStatus>,
+    /// Measured offset of the local clock (`CLOCK_*`).
+    pub clock: Option<ClockStatus>,
+    /// State files that failed their integrity check (`STATE_INTEGRITY=1`).
+    pub state_integrity: Option<IntegrityStatus>,
+    /// Aggregator liveness challenges waiting and answered (`CHALLENGE_QUEUE_MAX`).
+    pub challenges: Option<ChallengeStatus>,
+    /// Latest release and any staged binary (`UPDATE_MANIFEST_URL`).
+    pub update: Option<UpdateStatus>,
+    /// Copies to the shadow aggregator (`SHADOW_AGGREGATOR_URL`).
+    pub shadow: Option<ShadowStatus>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -127,4 +357,5 @@ pub struct ConfigSummary {
     pub device_did: String,
     pub max_retries: u32,
     pub rate_limit_per_second: u32,
+    pub aggregator_mode: String,
 }
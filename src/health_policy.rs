@@ -0,0 +1,98 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::config::Config;
+use crate::metrics::HealthStatus;
+
+/// Thresholds that turn the worker's counters into a health status.
+///
+/// Defaults keep the worker's former consecutive-failure and failure-rate thresholds,
+/// but the rate is now measured over the last `failure_window` attempts rather than
+/// since startup, so a long-running worker recovers from an early bad streak. Every
+/// field has a `HEALTH_*` variable. The active policy is shown in `/status`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthPolicy {
+    pub degraded_consecutive_failures: u32,
+    pub unhealthy_consecutive_failures: u32,
+    pub critical_consecutive_failures: u32,
+    /// Attempts the failure rate is measured over.
+    pub failure_window: usize,
+    pub degraded_failure_rate: f64,
+    pub unhealthy_failure_rate: f64,
+    /// No successful attempt for this long is unhealthy; `None` never checks.
+    pub stale_success_secs: Option<u64>,
+    pub degraded_gpu_temp_c: Option<f64>,
+    pub unhealthy_gpu_temp_c: Option<f64>,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            degraded_consecutive_failures: 2,
+            unhealthy_consecutive_failures: 5,
+            critical_consecutive_failures: 10,
+            failure_window: 100,
+            degraded_failure_rate: 0.2,
+            unhealthy_failure_rate: 0.5,
+            stale_success_secs: None,
+            degraded_gpu_temp_c: None,
+            unhealthy_gpu_temp_c: None,
+        }
+    }
+}
+
+/// What the policy looks at, read from the metrics collector.
+#[derive(Debug, Clone, Default)]
+pub struct HealthSample {
+    pub consecutive_failures: u32,
+    /// Failed share of the last `failure_window` attempts.
+    pub window_failure_rate: f64,
+    /// Time since the last successful attempt, or since start if there was none.
+    pub since_success: Duration,
+    pub gpu_temp_c: Option<f64>,
+    /// Spot-check, self-test and other checks outside the policy that already
+    /// settled on a status.
+    pub floor: HealthStatus,
+}
+
+impl HealthPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            degraded_consecutive_failures: config.health_degraded_consecutive_failures,
+            unhealthy_consecutive_failures: config.health_unhealthy_consecutive_failures,
+            critical_consecutive_failures: config.health_critical_consecutive_failures,
+            failure_window: config.health_failure_window,
+            degraded_failure_rate: config.health_degraded_failure_rate,
+            unhealthy_failure_rate: config.health_unhealthy_failure_rate,
+            stale_success_secs: (config.health_stale_success_secs > 0).then_some(config.health_stale_success_secs),
+            degraded_gpu_temp_c: config.health_degraded_gpu_temp_c,
+            unhealthy_gpu_temp_c: config.health_unhealthy_gpu_temp_c,
+        }
+    }
+
+    /// Whether any threshold needs a GPU temperature reading.
+    pub fn watches_temperature(&self) -> bool {
+        self.degraded_gpu_temp_c.is_some() || self.unhealthy_gpu_temp_c.is_some()
+    }
+
+    /// The worst status any threshold (or the sample's floor) calls for.
+    pub fn classify(&self, sample: &HealthSample) -> HealthStatus {
+        let above = |limit: Option<f64>| matches!((limit, sample.gpu_temp_c), (Some(limit), Some(t)) if t >= limit);
+        let stale = self.stale_success_secs.is_some_and(|secs| sample.since_success >= Duration::from_secs(secs));
+
+        let status = if sample.consecutive_failures >= self.critical_consecutive_failures {
+            HealthStatus::Critical
+        } else if sample.consecutive_failures >= self.unhealthy_consecutive_failures
+            || sample.window_failure_rate > self.unhealthy_failure_rate
+            || stale
+            || above(self.unhealthy_gpu_temp_c) {
+            HealthStatus::Unhealthy
+        } else if sample.consecutive_failures >= self.degraded_consecutive_failures
+            || sample.window_failure_rate > self.degraded_failure_rate
+            || above(self.degraded_gpu_temp_c) {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+        status.max(sample.floor)
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use crate::epoch_summary::EpochSummary;
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::submit::{hex32, EpochInfo, FailureKind, SubmitError, SubmitOutcome, Submission, Submitter};
+use crate::types::WorkReceipt;
+use crate::log_warn;
+
+/// Header (HTTP) and metadata key (gRPC) carrying a receipt's idempotency key.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+// Domain of the hash that names a receipt for deduplication
+const KEY_DOMAIN: &[u8] = b"tops-worker/idempotency/v2";
+
+/// Stable name of the attempt a receipt reports: hex of the first 16 bytes of
+/// `BLAKE3("tops-worker/idempotency/v2" || u32 LE len || device_did || epoch_id LE || prev_hash || nonce LE)`,
+/// with prev_hash as its 32 decoded bytes (one that does not decode to 32 bytes is
+/// hashed as its text, length-prefixed like device_did).
+///
+/// prev_hash is part of the key because nonces restart whenever the chain moves,
+/// which can happen more than once within an epoch. The key does not depend on the
+/// signature or encoding, so a resend after renegotiation still carries the same key.
+pub fn idempotency_key(receipt: &WorkReceipt) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(KEY_DOMAIN);
+    hasher.update(&(receipt.device_did.len() as u32).to_le_bytes());
+    hasher.update(receipt.device_did.as_bytes());
+    hasher.update(&receipt.epoch_id.to_le_bytes());
+    match hex32(&receipt.prev_hash_hex) {
+        Some(prev_hash) => hasher.update(&prev_hash),
+        None => hasher
+            .update(&(receipt.prev_hash_hex.len() as u32).to_le_bytes())
+            .update(receipt.prev_hash_hex.as_bytes()),
+    };
+    hasher.update(&receipt.nonce.to_le_bytes());
+    hex::encode(&hasher.finalize().as_bytes()[..16])
+}
+
+/// Bounded set of recently delivered keys; the oldest key is forgotten first.
+pub struct RecentKeys {
+    capacity: usize,
+    inner: Mutex<(HashSet<String>, VecDeque<String>)>,
+}
+
+impl RecentKeys {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, inner: Mutex::new((HashSet::new(), VecDeque::new())) }
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.inner.lock().unwrap().0.contains(key)
+    }
+
+    pub fn insert(&self, key: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let (set, order) = &mut *inner;
+        if !set.insert(key.clone()) {
+            return;
+        }
+        order.push_back(key);
+        while order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                set.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Wraps a transport so a receipt it already delivered is not sent again.
+///
+/// A key is remembered once the transport reports the receipt accepted, queued or
+/// refused as a duplicate; failed and throttled submissions may be retried.
+pub struct DedupSubmitter {
+    inner: Arc<dyn Submitter>,
+    recent: RecentKeys,
+    metrics: Option<Arc<PrometheusMetrics>>,
+}
+
+impl DedupSubmitter {
+    pub fn new(inner: Arc<dyn Submitter>, capacity: usize) -> Self {
+        Self { inner, recent: RecentKeys::new(capacity), metrics: None }
+    }
+
+    pub fn with_metrics(mut self, metrics: Option<Arc<PrometheusMetrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+}
+
+#[async_trait]
+impl Submitter for DedupSubmitter {
+    fn describe(&self) -> String {
+        self.inner.describe()
+    }
+
+    async fn submit(&self, receipt: WorkReceipt) -> Result<Submission, SubmitError> {
+        let key = idempotency_key(&receipt);
+        if self.recent.contains(&key) {
+            log_warn!("[submit] suppressing duplicate of nonce {} (idempotency key {})", receipt.nonce, key);
+            if let Some(metrics) = &self.metrics {
+                metrics.record_duplicate_suppressed();
+            }
+            return Err(SubmitError::Duplicate(key));
+        }
+        let submission = self.inner.submit(receipt).await?;
+        let duplicate = submission.failure_kind() == Some(FailureKind::Duplicate);
+        match submission.outcome {
+            SubmitOutcome::Accepted { .. } | SubmitOutcome::Queued => self.recent.insert(key),
+            SubmitOutcome::Rejected { .. } if duplicate => self.recent.insert(key),
+            _ => {}
+        }
+        Ok(submission)
+    }
+
+    async fn current_epoch(&self) -> anyhow::Result<Option<EpochInfo>> {
+        self.inner.current_epoch().await
+    }
+
+    fn pending(&self) -> usize {
+        self.inner.pending()
+    }
+
+    async fn drain_one(&self) -> bool {
+        self.inner.drain_one().await
+    }
+    async fn submit_summary(&self, summary: EpochSummary) -> anyhow::Result<bool> {
+        self.inner.submit_summary(summary).await
+    }
+}
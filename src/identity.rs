@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use crate::config::Config;
+use crate::did::derive_signing_key_hex;
+use crate::integrity::write_atomic;
+use crate::signer::{RemoteSigner, SignerIdentity};
+use crate::signing::Secp;
+use crate::{log_error, log_info, log_warn};
+
+/// Where an identity's secp256k1 signing key comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyRef {
+    /// `hex:<64 hex chars>`, the key inline.
+    Hex(String),
+    /// `env:<VAR>`, hex key read from another environment variable.
+    Env(String),
+    /// `file:<path>`, hex key read from a file.
+    File(PathBuf),
+    /// `seed`, derived from `DID_KEY_SEED_HEX` and the identity's DID.
+    Seed,
+    /// Held by the remote signer (`REMOTE_SIGNER_URL`); never configured directly.
+    Remote,
+}
+
+impl std::str::FromStr for KeyRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "seed" {
+            return Ok(KeyRef::Seed);
+        }
+        match s.split_once(':') {
+            Some(("hex", key)) if !key.is_empty() => Ok(KeyRef::Hex(key.to_string())),
+            Some(("env", var)) if !var.is_empty() => Ok(KeyRef::Env(var.to_string())),
+            Some(("file", path)) if !path.is_empty() => Ok(KeyRef::File(PathBuf::from(path))),
+            _ => Err(format!("unknown key reference '{}'", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for KeyRef {
+    // Never prints inline key material
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyRef::Hex(_) => write!(f, "hex:<redacted>"),
+            KeyRef::Env(var) => write!(f, "env:{}", var),
+            KeyRef::File(path) => write!(f, "file:{}", path.display()),
+            KeyRef::Seed => write!(f, "seed"),
+            KeyRef::Remote => write!(f, "remote"),
+        }
+    }
+}
+
+impl KeyRef {
+    /// Load the key for `did`; `seed_hex` is `DID_KEY_SEED_HEX`.
+    pub fn load(&self, did: &str, seed_hex: Option<&str>) -> anyhow::Result<Secp> {
+        match self {
+            KeyRef::Hex(key) => Secp::from_hex(key),
+            KeyRef::Env(var) => {
+                let key = std::env::var(var).map_err(|_| anyhow::anyhow!("key variable {} for {} is not set", var, did))?;
+                Secp::from_hex(key.trim())
+            }
+            KeyRef::File(path) => {
+                let key = std::fs::read_to_string(path)
+                    .map_err(|e| anyhow::anyhow!("reading key file {} for {}: {}", path.display(), did, e))?;
+                Secp::from_hex(key.trim())
+            }
+            KeyRef::Seed => {
+                let seed_hex = seed_hex.ok_or_else(|| anyhow::anyhow!("key for {} is seed-derived but DID_KEY_SEED_HEX is not set", did))?;
+                Secp::from_hex(&derive_signing_key_hex(&hex::decode(seed_hex)?, did)?)
+            }
+            KeyRef::Remote => anyhow::bail!("the key of {} is held by the remote signer", did),
+        }
+    }
+}
+
+/// One configured `(device_did, key)` pair and its share of the attempts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentitySpec {
+    pub device_did: String,
+    pub key: KeyRef,
+    pub weight: u32,
+}
+
+/// Parse `WORKER_IDENTITIES`: comma-separated `<did>=<keyref>[@<weight>]` entries.
+pub fn parse_identities(s: &str) -> Result<Vec<IdentitySpec>, String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (did, key) = entry.split_once('=').ok_or_else(|| format!("identity '{}' is not <did>=<keyref>", entry))?;
+            // A trailing @N is a weight; anything else after '@' belongs to the key reference
+            let (key, weight) = key.rsplit_once('@')
+                .and_then(|(key, weight)| Some((key, weight.parse::<u32>().ok()?)))
+                .unwrap_or((key, 1));
+            Ok(IdentitySpec { device_did: did.trim().to_string(), key: key.trim().parse()?, weight })
+        })
+        .collect()
+}
+
+/// A signing key and its rotation count for one identity.
+#[derive(Clone)]
+pub struct ActiveKey {
+    pub secp: Arc<Secp>,
+    /// 0 for the first key the identity ever signed with, +1 per rotation.
+    pub epoch: u32,
+}
+
+/// A loaded identity, ready to sign.
+///
+/// The key can be swapped while attempts are running. The key it replaced is
+/// kept so receipts stamped with the old epoch still get signed with it.
+pub struct Identity {
+    pub device_did: String,
+    pub key_ref: KeyRef,
+    pub weight: u32,
+    keys: RwLock<(ActiveKey, Option<ActiveKey>)>,
+}
+
+impl Identity {
+    pub fn new(device_did: String, key_ref: KeyRef, secp: Secp, key_epoch: u32, weight: u32) -> Self {
+        let current = ActiveKey { secp: Arc::new(secp), epoch: key_epoch };
+        Self { device_did, key_ref, weight, keys: RwLock::new((current, None)) }
+    }
+
+    /// The key new receipts are signed with.
+    pub fn active_key(&self) -> ActiveKey {
+        self.keys.read().unwrap().0.clone()
+    }
+
+    pub fn secp(&self) -> Arc<Secp> {
+        self.active_key().secp
+    }
+
+    pub fn key_epoch(&self) -> u32 {
+        self.active_key().epoch
+    }
+
+    /// Key of `epoch`, if it is the current or the previous one.
+    pub fn key_for_epoch(&self, epoch: u32) -> Option<Arc<Secp>> {
+        let keys = self.keys.read().unwrap();
+        std::iter::once(&keys.0)
+            .chain(keys.1.as_ref())
+            .find(|k| k.epoch == epoch)
+            .map(|k| Arc::clone(&k.secp))
+    }
+
+    // Swap in `secp` as the next epoch; the replaced key is kept for in-flight receipts
+    fn rotate(&self, secp: Secp) -> u32 {
+        let mut keys = self.keys.write().unwrap();
+        let next = ActiveKey { secp: Arc::new(secp), epoch: keys.0.epoch + 1 };
+        let epoch = next.epoch;
+        let previous = std::mem::replace(&mut keys.0, next);
+        keys.1 = Some(previous);
+        epoch
+    }
+}
+
+/// A completed key rotation.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyRotation {
+    pub device_did: String,
+    pub key_epoch: u32,
+    pub pubkey_hex: String,
+}
+
+// Last known key per identity, so key epochs keep counting across restarts
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeyEpochState {
+    identities: HashMap<String, KeyEpochEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyEpochEntry {
+    key_epoch: u32,
+    pubkey_hex: String,
+}
+
+/// Signing keys for every identity this process mines for, plus the schedule
+/// that spreads attempts across them.
+pub struct KeyRing {
+    identities: Vec<Identity>,
+    // Smooth weighted round-robin state, one entry per identity
+    current: Mutex<Vec<i64>>,
+    // Where key epochs are persisted; `None` keeps them in memory only
+    state_path: Option<PathBuf>,
+    // Serializes rotations so epochs and the state file stay consistent
+    rotation: Mutex<()>,
+    // Set when the keys live on a remote signer and only their public halves are here
+    remote: Option<Arc<RemoteSigner>>,
+}
+
+impl KeyRing {
+    pub fn new(identities: Vec<Identity>) -> anyhow::Result<Self> {
+        if identities.is_empty() {
+            anyhow::bail!("no signing identities configured");
+        }
+        let current = Mutex::new(vec![0; identities.len()]);
+        Ok(Self { identities, current, state_path: None, rotation: Mutex::new(()), remote: None })
+    }
+
+    /// A ring without identities, for `WATCH_ONLY`: nothing can be signed with it.
+    pub fn watch_only() -> Self {
+        Self { identities: Vec::new(), current: Mutex::new(Vec::new()), state_path: None, rotation: Mutex::new(()), remote: None }
+    }
+
+    /// The identities `signer` holds (its `GET /identities`), with public keys only:
+    /// receipts and epoch summaries are sent to it for signing.
+    pub fn remote(signer: RemoteSigner, identities: Vec<SignerIdentity>) -> anyhow::Result<Self> {
+        let identities = identities.into_iter()
+            .map(|identity| {
+                let secp = Secp::public_only(&identity.pubkey_hex)?;
+                Ok(Identity::new(identity.device_did, KeyRef::Remote, secp, identity.key_epoch, identity.weight))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut ring = Self::new(identities)?;
+        ring.remote = Some(Arc::new(signer));
+        Ok(ring)
+    }
+
+    /// The signer holding the keys, when they are not held here.
+    pub fn remote_signer(&self) -> Option<&RemoteSigner> {
+        self.remote.as_deref()
+    }
+
+    /// Load every identity from `WORKER_IDENTITIES`, or the single `DEVICE_DID` one.
+    ///
+    /// Key epochs continue from `key_epochs.json` in the state directory; a key
+    /// that changed while the worker was stopped counts as a rotation.
+    pub fn load(config: &Config) -> anyhow::Result<Self> {
+        let state_path = config.get_key_epochs_path();
+        let mut state = read_key_epochs(&state_path);
+        let identities = config.get_identities()
+            .into_iter()
+            .map(|spec| {
+                let secp = spec.key.load(&spec.device_did, config.did_key_seed_hex.as_deref())?;
+                let pubkey_hex = secp.pubkey_hex_compressed();
+                let key_epoch = match state.identities.get(&spec.device_did) {
+                    Some(entry) if entry.pubkey_hex == pubkey_hex => entry.key_epoch,
+                    Some(entry) => entry.key_epoch + 1,
+                    None => 0,
+                };
+                state.identities.insert(spec.device_did.clone(), KeyEpochEntry { key_epoch, pubkey_hex });
+                Ok(Identity::new(spec.device_did, spec.key, secp, key_epoch, spec.weight))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if let Err(e) = write_key_epochs(&state_path, &state) {
+            log_warn!("[identity] could not persist key epochs to {}: {}", state_path.display(), e);
+        }
+        let mut ring = Self::new(identities)?;
+        ring.state_path = Some(state_path);
+        Ok(ring)
+    }
+
+    pub fn identities(&self) -> &[Identity] {
+        &self.identities
+    }
+
+    pub fn len(&self) -> usize {
+        self.identities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.identities.is_empty()
+    }
+
+    pub fn identity(&self, did: &str) -> Option<&Identity> {
+        self.identities.iter().find(|i| i.device_did == did)
+    }
+
+    /// Current signing key for `did`, if it is one of ours.
+    pub fn signer_for(&self, did: &str) -> Option<Arc<Secp>> {
+        self.identity(did).map(Identity::secp)
+    }
+
+    /// Make `secp` the signing key of `did`. New receipts use it right away;
+    /// receipts stamped with the replaced key's epoch are still signed with that key.
+    pub fn rotate(&self, did: &str, secp: Secp) -> anyhow::Result<KeyRotation> {
+        let identity = self.identity(did).ok_or_else(|| anyhow::anyhow!("{} is not one of our identities", did))?;
+        if let Some(signer) = &self.remote {
+            anyhow::bail!("the keys are held by the remote signer at {}; rotate them there", signer.url());
+        }
+        let _guard = self.rotation.lock().unwrap();
+        let pubkey_hex = secp.pubkey_hex_compressed();
+        if identity.secp().pubkey_hex_compressed() == pubkey_hex {
+            anyhow::bail!("{} already signs with key {}", did, pubkey_hex);
+        }
+        let key_epoch = identity.rotate(secp);
+        if let Some(path) = &self.state_path {
+            let mut state = read_key_epochs(path);
+            state.identities.insert(did.to_string(), KeyEpochEntry { key_epoch, pubkey_hex: pubkey_hex.clone() });
+            if let Err(e) = write_key_epochs(path, &state) {
+                log_warn!("[identity] could not persist key epochs to {}: {}", path.display(), e);
+            }
+        }
+        log_info!("[identity] rotated signing key of {}: key epoch {} pubkey(compressed)={}", did, key_epoch, pubkey_hex);
+        Ok(KeyRotation { device_did: did.to_string(), key_epoch, pubkey_hex })
+    }
+
+    /// Rotate to a key delivered out of band (hex). A `file:` identity also gets
+    /// the key written to its file, so the rotation survives a restart.
+    pub fn rotate_hex(&self, did: &str, sk_hex: &str) -> anyhow::Result<KeyRotation> {
+        let identity = self.identity(did).ok_or_else(|| anyhow::anyhow!("{} is not one of our identities", did))?;
+        let secp = Secp::from_hex(sk_hex.trim())?;
+        match &identity.key_ref {
+            KeyRef::File(path) => write_key_file(path, sk_hex.trim())?,
+            key_ref => log_warn!("[identity] key of {} comes from {}; the rotated key is lost on restart", did, key_ref),
+        }
+        self.rotate(did, secp)
+    }
+
+    /// Re-read every `file:` key and rotate the identities whose file changed.
+    pub fn reload_changed(&self) -> Vec<KeyRotation> {
+        let mut rotations = Vec::new();
+        for identity in &self.identities {
+            if !matches!(identity.key_ref, KeyRef::File(_)) {
+                continue;
+            }
+            let secp = match identity.key_ref.load(&identity.device_did, None) {
+                Ok(secp) => secp,
+                Err(e) => {
+                    // Mid-write or briefly missing: keep signing with the current key
+                    log_warn!("[identity] ignoring unreadable key for {}: {}", identity.device_did, e);
+                    continue;
+                }
+            };
+            if secp.pubkey_hex_compressed() == identity.secp().pubkey_hex_compressed() {
+                continue;
+            }
+            match self.rotate(&identity.device_did, secp) {
+                Ok(rotation) => rotations.push(rotation),
+                Err(e) => log_error!("[identity] key rotation of {} failed: {}", identity.device_did, e),
+            }
+        }
+        rotations
+    }
+
+    /// Identity the next attempt is credited to. Equal weights round-robin;
+    /// otherwise each identity gets its weight's share, evenly interleaved.
+    pub fn next(&self) -> &Identity {
+        if self.identities.len() == 1 {
+            return &self.identities[0];
+        }
+        let mut current = self.current.lock().unwrap();
+        let total: i64 = self.identities.iter().map(|i| i.weight as i64).sum();
+        let mut best = 0;
+        for (idx, identity) in self.identities.iter().enumerate() {
+            current[idx] += identity.weight as i64;
+            if current[idx] > current[best] {
+                best = idx;
+            }
+        }
+        current[best] -= total;
+        &self.identities[best]
+    }
+}
+
+fn read_key_epochs(path: &Path) -> KeyEpochState {
+    std::fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn write_key_epochs(path: &Path, state: &KeyEpochState) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    write_atomic(path, &serde_json::to_vec_pretty(state)?)
+}
+
+/// Write a hex key to `path` (mode 0600 on Unix), replacing it atomically.
+pub fn write_key_file(path: &Path, sk_hex: &str) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let write = || -> std::io::Result<()> {
+        let mut file = options.open(&tmp)?;
+        writeln!(file, "{}", sk_hex)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
+    };
+    write().map_err(|e| anyhow::anyhow!("writing key file {}: {}", path.display(), e))
+}
@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::signing::Secp;
+use crate::log_error;
+
+// Context of the state key derived from a signing key
+const KEY_CONTEXT: &str = "tops-worker 2026-10 state integrity v1";
+// The tag is appended to a sealed JSON object as this last field
+const TAG_FIELD: &[u8] = b"\"state_hmac\":\"";
+// Tamper events kept for /status
+const RECENT_EVENTS: usize = 16;
+
+static GLOBAL: OnceLock<StateIntegrity> = OnceLock::new();
+
+/// Kinds of state file that carry a seal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateFile {
+    /// Entries of the persistent queues (MQTT buffer, circuit backlog, quarantine).
+    Queue,
+    /// Lines of the attempt journal.
+    Journal,
+    /// The cuBLASLt algorithm cache filled by autotuning.
+    AlgoCache,
+}
+
+impl std::fmt::Display for StateFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateFile::Queue => write!(f, "queue"),
+            StateFile::Journal => write!(f, "journal"),
+            StateFile::AlgoCache => write!(f, "algo_cache"),
+        }
+    }
+}
+
+/// A state file (or journal line) whose seal did not verify.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TamperEvent {
+    pub file: StateFile,
+    pub path: String,
+    pub detail: String,
+    pub detected_at: String,
+}
+
+/// Tampering detected since startup, served in `/status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityStatus {
+    pub tampered: u64,
+    /// The latest events, oldest first.
+    pub recent: Vec<TamperEvent>,
+}
+
+/// HMAC-SHA256 seals on state files (`STATE_INTEGRITY=1`).
+///
+/// A sealed file is the JSON object it would otherwise be, with a last field
+/// `state_hmac` holding the tag over the object and the name it was written
+/// under, so an entry copied to another name does not verify either.
+pub struct StateIntegrity {
+    key: [u8; 32],
+    tampered: AtomicU64,
+    recent: Mutex<VecDeque<TamperEvent>>,
+    metrics: Option<Arc<PrometheusMetrics>>,
+}
+
+impl StateIntegrity {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self { key, tampered: AtomicU64::new(0), recent: Mutex::new(VecDeque::new()), metrics: None }
+    }
+
+    /// Key from `STATE_KEY_HEX`.
+    pub fn from_key_hex(key_hex: &str) -> anyhow::Result<Self> {
+        Ok(Self::new(blake3::derive_key(KEY_CONTEXT, &hex::decode(key_hex.trim())?)))
+    }
+
+    /// Key derived from a signing key; None when a remote signer holds it.
+    pub fn derived(secp: &Secp) -> Option<Self> {
+        secp.derive_key(KEY_CONTEXT).map(Self::new)
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<PrometheusMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// `body`, a JSON object, sealed for `name`.
+    pub fn seal(&self, name: &str, body: &[u8]) -> Vec<u8> {
+        let body = body.trim_ascii_end();
+        debug_assert!(body.ends_with(b"}"), "only JSON objects can be sealed");
+        let tag = hex::encode(self.mac(name, body).finalize().into_bytes());
+        let head = body.strip_suffix(b"}").unwrap_or(body);
+        let mut out = head.to_vec();
+        if !head.trim_ascii_end().ends_with(b"{") {
+            out.push(b',');
+        }
+        out.extend_from_slice(TAG_FIELD);
+        out.extend_from_slice(tag.as_bytes());
+        out.extend_from_slice(b"\"}");
+        out
+    }
+
+    /// The body of `bytes` if its seal verifies for `name`.
+    pub fn verify(&self, name: &str, bytes: &[u8]) -> Result<Vec<u8>, String> {
+        let (body, tag) = split(bytes).ok_or_else(|| "not sealed".to_string())?;
+        let tag = hex::decode(tag).map_err(|_| "malformed seal".to_string())?;
+        self.mac(name, &body).verify_slice(&tag).map_err(|_| "seal does not match".to_string())?;
+        Ok(body)
+    }
+
+    /// Count and log a file that failed `verify`.
+    pub fn report(&self, file: StateFile, path: &Path, detail: &str) {
+        log_error!("[integrity] {} {} failed its integrity check: {}", file, path.display(), detail);
+        self.tampered.fetch_add(1, Ordering::Relaxed);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_state_tampering(&file.to_string());
+        }
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == RECENT_EVENTS {
+            recent.pop_front();
+        }
+        recent.push_back(TamperEvent {
+            file,
+            path: path.display().to_string(),
+            detail: detail.to_string(),
+            detected_at: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+
+    pub fn tampered(&self) -> u64 {
+        self.tampered.load(Ordering::Relaxed)
+    }
+
+    pub fn status(&self) -> IntegrityStatus {
+        IntegrityStatus { tampered: self.tampered(), recent: self.recent.lock().unwrap().iter().cloned().collect() }
+    }
+
+    fn mac(&self, name: &str, body: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes keys of any length");
+        mac.update(name.as_bytes());
+        mac.update(&[0]);
+        mac.update(body);
+        mac
+    }
+}
+
+/// Make `integrity` the one state files are sealed with. Returns false if one is already installed.
+pub fn install(integrity: StateIntegrity) -> bool {
+    GLOBAL.set(integrity).is_ok()
+}
+
+pub fn installed() -> Option<&'static StateIntegrity> {
+    GLOBAL.get()
+}
+
+/// Replace `path` with `bytes` through a synced temporary file and a rename, so a
+/// crash leaves either the old contents or the new ones.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// `body` sealed for `name` when integrity is installed, else unchanged.
+pub fn seal(name: &str, body: Vec<u8>) -> Vec<u8> {
+    match GLOBAL.get() {
+        Some(integrity) => integrity.seal(name, &body),
+        None => body,
+    }
+}
+
+/// The body of a state file read from `path` under `name`. With integrity
+/// installed the seal must verify, and a failure is reported before it is
+/// returned; without it a seal is stripped unchecked, so files written with
+/// integrity on still read.
+pub fn open(file: StateFile, path: &Path, name: &str, bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    match GLOBAL.get() {
+        Some(integrity) => integrity.verify(name, &bytes).inspect_err(|e| integrity.report(file, path, e)),
+        None => Ok(split(&bytes).map(|(body, _)| body).unwrap_or(bytes)),
+    }
+}
+
+// The object without its seal, and the hex tag
+fn split(bytes: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    let bytes = bytes.trim_ascii_end();
+    let start = bytes.windows(TAG_FIELD.len()).rposition(|w| w == TAG_FIELD)?;
+    let tag = bytes[start + TAG_FIELD.len()..].strip_suffix(b"\"}")?;
+    let mut body = bytes[..start].to_vec();
+    if body.last() == Some(&b',') {
+        body.pop();
+    }
+    body.push(b'}');
+    Some((body, tag))
+}
@@ -0,0 +1,57 @@
+use std::time::Duration;
+use rand::Rng;
+use crate::config::Config;
+
+/// Time each worker of a fleet gets to flush its backlog after a reconnect.
+pub const FLUSH_SLOT: Duration = Duration::from_millis(50);
+
+/// Spreads a fleet's traffic so workers started (or reconnected) together do not
+/// hit the aggregator in step.
+///
+/// The startup delay and the flush slot come from a hash of the device DID, so they
+/// are stable per device and uniform across a fleet; the per-attempt jitter is random.
+#[derive(Debug, Clone)]
+pub struct Jitter {
+    /// This device's place in [0, 1).
+    position: f64,
+    submit_max: Duration,
+    startup_max: Duration,
+    fleet_size: u32,
+}
+
+impl Jitter {
+    pub fn new(device_did: &str, submit_max: Duration, startup_max: Duration, fleet_size: u32) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"tops-worker/jitter/v1");
+        hasher.update(device_did.as_bytes());
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&hasher.finalize().as_bytes()[..8]);
+        // 53 bits are all an f64 holds
+        let position = (u64::from_le_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64;
+        Self { position, submit_max, startup_max, fleet_size: fleet_size.max(1) }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(&config.device_did, config.get_submit_jitter(), config.get_startup_jitter(), config.fleet_size)
+    }
+
+    /// Wait before the first contact with the aggregator, within `STARTUP_JITTER_MS`.
+    pub fn startup_delay(&self) -> Duration {
+        self.startup_max.mul_f64(self.position)
+    }
+
+    /// Random extra pause before an attempt, within `SUBMIT_JITTER_MS`.
+    pub fn submit_jitter(&self) -> Duration {
+        if self.submit_max.is_zero() {
+            return Duration::ZERO;
+        }
+        self.submit_max.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+
+    /// When to start flushing a backlog after reconnecting: this device's slot
+    /// among `FLEET_SIZE` workers.
+    pub fn flush_delay(&self) -> Duration {
+        let slot = (self.position * self.fleet_size as f64).floor();
+        FLUSH_SLOT.mul_f64(slot)
+    }
+}
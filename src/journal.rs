@@ -0,0 +1,180 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::attempt::AttemptOutput;
+use crate::integrity::{self, StateFile};
+use crate::queue::seal_name;
+use crate::phases::PhaseTimings;
+use crate::submit::{FailureKind, RetryPolicy, Submission, SubmitOutcome};
+use crate::types::WorkReceipt;
+
+/// One attempt as the worker ran it, enough to run it again (`tops-worker replay`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The receipt as built, before signing: seed, sizes, kernel and work_root.
+    pub receipt: WorkReceipt,
+    /// Backend and device that ran the attempt.
+    #[serde(default)]
+    pub backend: String,
+    #[serde(default)]
+    pub phases: PhaseTimings,
+    /// BLAKE3 of the full output Y; `None` when it never left the device (persistent kernel).
+    #[serde(default)]
+    pub output_hash_hex: Option<String>,
+    /// BLAKE3 of the sampled outputs the work_root is built from.
+    #[serde(default)]
+    pub samples_hash_hex: Option<String>,
+    #[serde(default)]
+    pub recorded_at: String,
+}
+
+impl JournalEntry {
+    pub fn new(receipt: &WorkReceipt, out: &AttemptOutput) -> Self {
+        let backend = receipt.device_info.as_ref()
+            .map(|info| format!("{} ({})", info.backend, info.device_name))
+            .unwrap_or_default();
+        Self {
+            receipt: WorkReceipt { sig_hex: String::new(), ..receipt.clone() },
+            backend,
+            phases: out.phases,
+            output_hash_hex: (!out.y1.is_empty()).then(|| output_digest(&out.y1)),
+            samples_hash_hex: Some(output_digest(&out.y2_samples)),
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Whether this entry is the attempt `nonce`, of `prev_hash_hex` when given.
+    pub fn is_attempt(&self, nonce: u32, prev_hash_hex: Option<&str>) -> bool {
+        self.receipt.nonce == nonce
+            && prev_hash_hex.is_none_or(|hex| self.receipt.prev_hash_hex.eq_ignore_ascii_case(hex))
+    }
+}
+
+/// A submission that did not get through, journaled after the attempt it belongs to.
+/// Not an attempt: `read_journal` skips these lines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitFailureEntry {
+    pub nonce: u32,
+    pub prev_hash_hex: String,
+    pub target: String,
+    pub failure: FailureKind,
+    /// What was done about it.
+    pub retry: RetryPolicy,
+    /// Resends before giving up.
+    pub retries: u32,
+    pub message: String,
+    pub recorded_at: String,
+}
+
+impl SubmitFailureEntry {
+    /// The failure in `submission` of `receipt`; None if it got through.
+    pub fn new(receipt: &WorkReceipt, submission: &Submission) -> Option<Self> {
+        let failure = submission.failure_kind()?;
+        let detail = match &submission.outcome {
+            SubmitOutcome::Throttled { status, body, .. } | SubmitOutcome::Rejected { status, body } => format!("HTTP {}: {}", status, body.trim()),
+            SubmitOutcome::Failed { error, .. } => error.clone(),
+            SubmitOutcome::Accepted { .. } | SubmitOutcome::Queued => String::new(),
+        };
+        Some(Self {
+            nonce: receipt.nonce,
+            prev_hash_hex: receipt.prev_hash_hex.clone(),
+            target: submission.target.clone(),
+            failure,
+            retry: failure.retry_policy(),
+            retries: submission.retries,
+            message: format!("{} ({})", failure.explain(), detail),
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+/// BLAKE3 (hex) of an int8 output.
+pub fn output_digest(values: &[i8]) -> String {
+    let bytes: Vec<u8> = values.iter().map(|&v| v as u8).collect();
+    blake3::hash(&bytes).to_hex().to_string()
+}
+
+// What journal lines are sealed under, whichever file they end up in after rotation
+const SEAL_NAME: &str = "journal";
+
+/// Append-only JSON-lines journal of attempts (`ATTEMPT_JOURNAL=1`).
+///
+/// With `STATE_INTEGRITY=1` every line is sealed on its own. Once the file passes `max_bytes` it is renamed to `<file>.1`, replacing the
+/// previous one, so the journal never takes more than twice the quota.
+pub struct AttemptJournal {
+    path: PathBuf,
+    max_bytes: u64,
+    file: Mutex<Option<File>>,
+}
+
+impl AttemptJournal {
+    pub fn open(path: impl AsRef<Path>, max_bytes: u64) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, max_bytes, file: Mutex::new(Some(file)) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, entry: &JournalEntry) -> anyhow::Result<()> {
+        self.write_line(serde_json::to_vec(entry)?)
+    }
+
+    pub fn append_failure(&self, entry: &SubmitFailureEntry) -> anyhow::Result<()> {
+        self.write_line(serde_json::to_vec(entry)?)
+    }
+
+    fn write_line(&self, json: Vec<u8>) -> anyhow::Result<()> {
+        let mut line = integrity::seal(SEAL_NAME, json);
+        line.push(b'\n');
+        let mut file = self.file.lock().map_err(|_| anyhow::anyhow!("journal lock poisoned"))?;
+        if file.as_ref().map(|f| f.metadata().map(|m| m.len()).unwrap_or(0)).unwrap_or(0) >= self.max_bytes {
+            // Close before renaming, then start a fresh file
+            *file = None;
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(".1");
+            fs::rename(&self.path, &rotated)?;
+        }
+        if file.is_none() {
+            *file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        }
+        if let Some(file) = file.as_mut() {
+            file.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the attempts in a journal file.
+///
+/// Lines that are not journal entries (such as submission failures), or fail the integrity check, are skipped;
+/// a quarantined receipt (`$STATE_DIR/quarantine/*.json`) reads as an entry
+/// without timings.
+pub fn read_journal(path: impl AsRef<Path>) -> anyhow::Result<Vec<JournalEntry>> {
+    let path = path.as_ref();
+    let reader = File::open(path).map_err(|e| anyhow::anyhow!("cannot open journal {}: {}", path.display(), e))?;
+    // A quarantined receipt is sealed as a queue entry
+    let (file, name) = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => (StateFile::Queue, seal_name(path)),
+        _ => (StateFile::Journal, SEAL_NAME.to_string()),
+    };
+    let mut entries = Vec::new();
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(body) = integrity::open(file, path, &name, line.into_bytes()) else { continue };
+        if let Ok(entry) = serde_json::from_slice::<JournalEntry>(&body) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
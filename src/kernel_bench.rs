@@ -0,0 +1,185 @@
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::attempt::{compute_work_root, Executor};
+use crate::work_hash::{HashKind, WorkSampling};
+use crate::cpu::{CpuExec, CpuKernel};
+use crate::phases;
+use crate::types::{Requant, Sizes};
+use crate::workload::{generate_workload_inputs, ProofWorkload, Workload, WorkloadInput};
+
+/// Sizes `bench-kernels` uses without `--sizes`.
+pub const DEFAULT_SIZES: Sizes = Sizes { m: 1024, n: 1024, k: 1024, batch: 1 };
+
+/// One kernel implementation's timing and agreement with the scalar CPU kernel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelResult {
+    pub kernel: String,
+    pub device_name: String,
+    pub driver_version: String,
+    /// Fastest run including host/device transfers.
+    pub best_ms: Option<f64>,
+    /// Fastest run without the transfers the backend recorded.
+    pub kernel_ms: Option<f64>,
+    /// Billions of int8 operations (two per multiply-accumulate) per second over `kernel_ms`.
+    pub gops: Option<f64>,
+    /// Output and work_root are bit-identical to the scalar CPU kernel.
+    pub matches_reference: bool,
+    /// Set when the kernel failed to run.
+    pub error: Option<String>,
+}
+
+/// Result of `tops-worker bench-kernels`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelBenchReport {
+    pub sizes: Sizes,
+    pub iterations: usize,
+    pub kernels: Vec<KernelResult>,
+    /// Compiled-in kernels that could not be initialised, with the reason.
+    pub unavailable: Vec<(String, String)>,
+}
+
+impl KernelBenchReport {
+    pub fn passed(&self) -> bool {
+        self.kernels.iter().all(|k| k.matches_reference)
+    }
+
+    /// The kernel with the highest throughput among those that match the reference.
+    pub fn fastest(&self) -> Option<&KernelResult> {
+        self.kernels.iter()
+            .filter(|k| k.matches_reference)
+            .filter_map(|k| k.gops.map(|gops| (k, gops)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(k, _)| k)
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = format!("bench-kernels: m,n,k=({},{},{}), best of {} run(s) per kernel\n",
+            self.sizes.m, self.sizes.n, self.sizes.k, self.iterations);
+        out.push_str(&format!("{:<5} {:<20} {:>10} {:>10} {:>10}  {}\n", "", "kernel", "total ms", "kernel ms", "GOP/s", "device"));
+        for k in &self.kernels {
+            let status = if k.matches_reference { "OK" } else { "FAIL" };
+            let num = |v: Option<f64>, prec: usize| v.map_or("-".to_string(), |v| format!("{:.*}", prec, v));
+            out.push_str(&format!("{:<5} {:<20} {:>10} {:>10} {:>10}  {}\n",
+                status, k.kernel, num(k.best_ms, 2), num(k.kernel_ms, 2), num(k.gops, 1), k.device_name));
+            if let Some(e) = &k.error {
+                out.push_str(&format!("      error: {}\n", e));
+            } else if !k.matches_reference {
+                out.push_str("      output differs from the scalar CPU kernel\n");
+            }
+        }
+        for (kernel, reason) in &self.unavailable {
+            out.push_str(&format!("{:<5} {}: {}\n", "SKIP", kernel, reason));
+        }
+        if let Some(fastest) = self.fastest() {
+            out.push_str(&format!("fastest: {} ({:.1} GOP/s)\n", fastest.kernel, fastest.gops.unwrap_or(0.0)));
+        }
+        out
+    }
+}
+
+type NamedExecutor = (String, Box<dyn Executor>);
+
+// Every GEMM implementation compiled into this build that initialises here,
+// the scalar CPU reference first
+fn compiled_kernels() -> (Vec<NamedExecutor>, Vec<(String, String)>) {
+    let mut kernels: Vec<NamedExecutor> = Vec::new();
+    let mut unavailable = Vec::new();
+    for kernel in [CpuKernel::Scalar, CpuKernel::Avx2, CpuKernel::Avx512Vnni, CpuKernel::Neon] {
+        if kernel.is_supported() {
+            match CpuExec::with_kernel(kernel) {
+                Ok(exec) => kernels.push((format!("CPU {}", kernel), Box::new(exec))),
+                Err(e) => unavailable.push((format!("CPU {}", kernel), e.to_string())),
+            }
+        }
+    }
+    #[cfg(feature = "gpu")]
+    {
+        use crate::gpu::GemmKernel;
+        #[allow(unused_mut)]
+        let mut variants = vec![GemmKernel::Naive, GemmKernel::Tiled];
+        #[cfg(feature = "clblast")]
+        variants.push(GemmKernel::Clblast);
+        for variant in variants {
+            match crate::gpu::GpuExec::new() {
+                Ok(exec) => kernels.push((format!("OpenCL {}", variant), Box::new(exec.with_gemm_kernel(variant)))),
+                Err(e) => unavailable.push((format!("OpenCL {}", variant), e.to_string())),
+            }
+        }
+    }
+    #[cfg(feature = "cuda")]
+    match crate::gpu_cuda::CudaExec::new() {
+        Ok(exec) => kernels.push(("CUDA cuBLASLt".to_string(), Box::new(exec))),
+        Err(e) => unavailable.push(("CUDA cuBLASLt".to_string(), e.to_string())),
+    }
+    #[cfg(feature = "metal")]
+    match crate::gpu_metal::MetalExec::new() {
+        Ok(exec) => kernels.push(("Metal WGSL".to_string(), Box::new(exec))),
+        Err(e) => unavailable.push(("Metal WGSL".to_string(), e.to_string())),
+    }
+    (kernels, unavailable)
+}
+
+/// Run one GEMM of `sizes` from a fixed seed through every compiled kernel: a
+/// warm-up run, then the best of `iterations`, each output compared bit-exactly
+/// against the scalar CPU kernel.
+pub fn run_kernel_bench(sizes: &Sizes, iterations: usize) -> anyhow::Result<KernelBenchReport> {
+    let iterations = iterations.max(1);
+    let prev_hash = *blake3::hash(b"tops-worker bench-kernels v1").as_bytes();
+    let (a, b) = match generate_workload_inputs(Workload::Gemm, &prev_hash, 0, None, sizes) {
+        WorkloadInput::Dense { a, b } => (a, b),
+        _ => unreachable!("the GEMM workload generates dense inputs"),
+    };
+    let scale = Requant::from_salt(None);
+    let tera_ops = Workload::Gemm.tera_ops(sizes);
+
+    let (kernels, unavailable) = compiled_kernels();
+    let mut reference: Option<(Vec<i8>, [u8; 32])> = None;
+    let mut results = Vec::with_capacity(kernels.len());
+    for (name, executor) in &kernels {
+        let info = executor.device_info();
+        let mut result = KernelResult {
+            kernel: name.clone(),
+            device_name: info.device_name,
+            driver_version: info.driver_version,
+            best_ms: None,
+            kernel_ms: None,
+            gops: None,
+            matches_reference: false,
+            error: None,
+        };
+        match time_kernel(&**executor, &a, &b, sizes, scale, iterations) {
+            Ok((y, best_ms, kernel_ms)) => {
+                let root = compute_work_root(&y, HashKind::default(), WorkSampling::Seeded, &prev_hash, 0).0;
+                // The scalar kernel runs first and is the reference
+                let (y_ref, root_ref) = reference.get_or_insert_with(|| (y.clone(), root));
+                result.matches_reference = *y_ref == y && *root_ref == root;
+                result.best_ms = Some(best_ms);
+                result.kernel_ms = Some(kernel_ms);
+                result.gops = (kernel_ms > 0.0).then(|| tera_ops * 1000.0 / (kernel_ms / 1000.0));
+            }
+            Err(e) => result.error = Some(e.to_string()),
+        }
+        results.push(result);
+    }
+
+    Ok(KernelBenchReport { sizes: sizes.clone(), iterations, kernels: results, unavailable })
+}
+
+// Output of the last run, and the fastest total and kernel-only times in ms
+fn time_kernel(executor: &dyn Executor, a: &[i8], b: &[i8], sizes: &Sizes, scale: Requant, iterations: usize) -> anyhow::Result<(Vec<i8>, f64, f64)> {
+    // Absorbs program compilation, algorithm selection and buffer allocation
+    executor.run_gemm(a, b, sizes, scale)?;
+    let mut best = (f64::INFINITY, f64::INFINITY);
+    let mut y = Vec::new();
+    for _ in 0..iterations {
+        phases::reset();
+        let start = Instant::now();
+        y = executor.run_gemm(a, b, sizes, scale)?;
+        let total = start.elapsed();
+        let (h2d, d2h) = phases::take_transfers();
+        let total_ms = total.as_secs_f64() * 1000.0;
+        let kernel_ms = total.saturating_sub(h2d + d2h).as_secs_f64() * 1000.0;
+        best = (best.0.min(total_ms), best.1.min(kernel_ms));
+    }
+    Ok((y, best.0, best.1))
+}
@@ -2,13 +2,104 @@ pub mod types;
 pub mod prng;
 pub mod cl_kernels;
 pub mod gpu;
-#[cfg(feature="cpu-fallback")]
+pub mod program_cache;
+#[cfg(feature = "clblast")]
+pub mod clblast;
+#[cfg(feature = "cuda")]
+pub mod gpu_cuda;
+#[cfg(feature = "metal")]
+pub mod gpu_metal;
+pub mod algo_cache;
 pub mod cpu;
 pub mod attempt;
+pub mod work_hash;
+pub mod capabilities;
+pub mod phases;
 pub mod signing;
 pub mod config;
+pub mod config_file;
+pub mod config_report;
+pub mod fleet_config;
 pub mod metrics;
+pub mod metrics_schema;
 pub mod error_handling;
 pub mod health;
+pub mod health_policy;
+pub mod thermal;
+pub mod energy;
+pub mod watchdog;
+pub mod loop_state;
+pub mod shutdown;
+pub mod lifecycle;
+pub mod logs;
+pub mod watch_only;
+pub mod warmup;
+pub mod evidence;
+pub mod liveness;
+pub mod enroll;
 pub mod server;
-pub mod prometheus_metrics;
\ No newline at end of file
+pub mod prometheus_metrics;
+pub mod metrics_push;
+pub mod autotune;
+pub mod rate_control;
+pub mod pacing;
+pub mod backpressure;
+pub mod clock;
+pub mod jitter;
+pub mod tariff;
+pub mod endpoints;
+pub mod negotiation;
+pub mod compression;
+pub mod net;
+pub mod dns;
+pub mod submit;
+pub mod idempotency;
+pub mod shadow;
+pub mod circuit;
+pub mod mock_aggregator;
+pub mod response_auth;
+pub mod queue;
+pub mod quarantine;
+pub mod journal;
+pub mod export;
+pub mod replay;
+pub mod runtime;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod selftest;
+pub mod requant_vectors;
+pub mod spotcheck;
+pub mod stress;
+pub mod crosscheck;
+pub mod kernel_bench;
+pub mod doctor;
+pub mod pipeline;
+pub mod persistent;
+pub mod sparse;
+pub mod memhard;
+pub mod workload;
+pub mod matrix_cache;
+pub mod epoch;
+pub mod epoch_summary;
+pub mod size_distribution;
+pub mod streams;
+pub mod challenge;
+pub mod update;
+pub mod did;
+pub mod identity;
+pub mod integrity;
+pub mod signer;
+pub mod sequence;
+pub mod power;
+pub mod pause;
+pub mod limits;
+pub mod device_memory;
+pub mod devices;
@@ -0,0 +1,224 @@
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use crate::config::Config;
+use crate::shutdown::ExitReason;
+use crate::types::DeviceInfo;
+use crate::work_hash::HashKind;
+
+/// Layout version of the startup and shutdown events. Fields may be added within a
+/// version; one is only renamed, retyped or removed with a new version.
+pub const LIFECYCLE_SCHEMA_VERSION: u32 = 1;
+
+/// How the worker announces itself (`LOG_FORMAT`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// The `[config]` / `[startup]` banner for people reading the console.
+    #[default]
+    Text,
+    /// One JSON `startup` event in place of the banner, and a `shutdown` event at exit.
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+static FORMAT: OnceLock<LogFormat> = OnceLock::new();
+
+/// Set the format for the rest of the process. Returns false if one is already set.
+pub fn install(format: LogFormat) -> bool {
+    FORMAT.set(format).is_ok()
+}
+
+/// Whether lifecycle events are emitted (and the text banner left out).
+pub fn json() -> bool {
+    FORMAT.get() == Some(&LogFormat::Json)
+}
+
+/// Settings worth knowing when reading a worker's logs, and none of its secrets:
+/// keys, tokens and proxy credentials are left out, and URLs lose their user info
+/// and query string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigSummary {
+    pub device_did: String,
+    pub network_id: Option<String>,
+    pub aggregator_urls: Vec<String>,
+    pub aggregator_mode: String,
+    pub aggregator_protocol: String,
+    /// Gets a copy of every receipt (`SHADOW_AGGREGATOR_URL`).
+    pub shadow_aggregator_url: Option<String>,
+    pub autotune_target_ms: u64,
+    pub tariff_schedule: Option<String>,
+    pub max_retries: u32,
+    pub rate_limit_per_second: u32,
+    pub attempts_in_flight: usize,
+    pub pipeline_depth: usize,
+    pub pacing: String,
+    /// Port of the health server, when it listens on HTTP.
+    pub health_port: Option<u16>,
+    pub control_socket: Option<String>,
+    pub fleet_config_url: Option<String>,
+    pub update_manifest_url: Option<String>,
+    /// Nothing is signed or submitted (`WATCH_ONLY`).
+    pub watch_only: bool,
+}
+
+impl ConfigSummary {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            device_did: config.device_did.clone(),
+            network_id: config.network_id.clone(),
+            aggregator_urls: config.aggregator_urls.iter().map(|url| redact_url(url)).collect(),
+            aggregator_mode: config.aggregator_mode.to_string(),
+            aggregator_protocol: config.aggregator_protocol.to_string(),
+            shadow_aggregator_url: config.shadow_aggregator_url.as_deref().map(redact_url),
+            autotune_target_ms: config.autotune_target_ms,
+            tariff_schedule: (!config.tariff_schedule.is_empty()).then(|| config.tariff_schedule.to_string()),
+            max_retries: config.max_retries,
+            rate_limit_per_second: config.rate_limit_per_second,
+            attempts_in_flight: config.attempts_in_flight,
+            pipeline_depth: config.pipeline_depth,
+            pacing: config.pacing.to_string(),
+            health_port: config.metrics_enabled.then_some(8082),
+            control_socket: config.control_socket.clone(),
+            fleet_config_url: config.fleet_config_url.as_deref().map(redact_url),
+            update_manifest_url: config.update_manifest_url.as_deref().map(redact_url),
+            watch_only: config.watch_only,
+        }
+    }
+}
+
+/// A signing identity, by the fingerprint of its active key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentitySummary {
+    pub device_did: String,
+    pub key_fingerprint: String,
+    pub key_epoch: u32,
+    pub weight: u32,
+}
+
+/// Emitted once the worker is about to start its main loop.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartupEvent {
+    /// Always `startup`.
+    pub event: String,
+    pub schema_version: u32,
+    pub timestamp: String,
+    /// Worker version.
+    pub version: String,
+    pub config: ConfigSummary,
+    pub device: DeviceInfo,
+    /// The extra CPU attempt stream's device, in hybrid mode.
+    pub assist_device: Option<DeviceInfo>,
+    pub identities: Vec<IdentitySummary>,
+    /// The workload's `kernel_ver`.
+    pub workload: String,
+    pub epoch_id: u64,
+    pub hash_kind: HashKind,
+    /// Version of the fleet config document in effect.
+    pub fleet_config_version: Option<u64>,
+}
+
+impl StartupEvent {
+    pub fn new(config: ConfigSummary, device: DeviceInfo, workload: String, epoch_id: u64, hash_kind: HashKind) -> Self {
+        Self {
+            event: "startup".to_string(),
+            schema_version: LIFECYCLE_SCHEMA_VERSION,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            config,
+            device,
+            assist_device: None,
+            identities: Vec::new(),
+            workload,
+            epoch_id,
+            hash_kind,
+            fleet_config_version: None,
+        }
+    }
+}
+
+/// Emitted as the worker exits, after draining or on a fatal error.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShutdownEvent {
+    /// Always `shutdown`.
+    pub event: String,
+    pub schema_version: u32,
+    pub timestamp: String,
+    pub version: String,
+    pub exit_code: u8,
+    pub reason: String,
+    /// The fatal error, when there was one.
+    pub error: Option<String>,
+    pub uptime_seconds: Option<u64>,
+    pub total_attempts: Option<u64>,
+    pub successful_attempts: Option<u64>,
+    /// Highest nonce attempted on the current prev_hash.
+    pub highest_nonce: Option<u32>,
+    /// Receipts left queued on disk for the next start.
+    pub pending_receipts: Option<usize>,
+}
+
+impl ShutdownEvent {
+    pub fn new(reason: ExitReason) -> Self {
+        Self {
+            event: "shutdown".to_string(),
+            schema_version: LIFECYCLE_SCHEMA_VERSION,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            exit_code: reason.code(),
+            reason: reason.to_string(),
+            error: None,
+            uptime_seconds: None,
+            total_attempts: None,
+            successful_attempts: None,
+            highest_nonce: None,
+            pending_receipts: None,
+        }
+    }
+}
+
+/// Write `event` as one JSON line on stdout, where the rest of the log goes.
+pub fn emit(event: &impl Serialize) {
+    match serde_json::to_string(event) {
+        Ok(line) => println!("{}", line),
+        Err(e) => eprintln!("[lifecycle] could not encode event: {}", e),
+    }
+}
+
+/// First 8 bytes of the BLAKE3 hash of a hex public key's bytes, as hex.
+pub fn key_fingerprint(pubkey_hex: &str) -> String {
+    let bytes = hex::decode(pubkey_hex.trim_start_matches("0x")).unwrap_or_default();
+    hex::encode(&blake3::hash(&bytes).as_bytes()[..8])
+}
+
+/// `url` without user info or query string, which may carry credentials.
+pub fn redact_url(url: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(url) else { return "<unparseable>".to_string() };
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        let _ = parsed.set_username("redacted");
+        let _ = parsed.set_password(None);
+    }
+    if parsed.query().is_some() {
+        parsed.set_query(Some("redacted"));
+    }
+    parsed.to_string()
+}
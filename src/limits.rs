@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use crate::config::Config;
+use crate::log_warn;
+
+// cpu.max period; the quota is CPU_MAX_CORES of it
+const CPU_MAX_PERIOD_US: u64 = 100_000;
+
+/// I/O scheduling class for `WORKER_IONICE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IoPriority {
+    /// Only gets disk time when nobody else wants it.
+    Idle,
+    /// Best-effort class at level 0 (highest) to 7 (lowest).
+    BestEffort(u8),
+}
+
+impl std::str::FromStr for IoPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "idle" => Ok(IoPriority::Idle),
+            "best-effort" => Ok(IoPriority::BestEffort(4)),
+            other => match other.strip_prefix("best-effort:").and_then(|level| level.parse::<u8>().ok()) {
+                Some(level) if level <= 7 => Ok(IoPriority::BestEffort(level)),
+                _ => Err(format!("unknown I/O priority '{}' (idle, best-effort[:0-7])", s)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for IoPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoPriority::Idle => write!(f, "idle"),
+            IoPriority::BestEffort(level) => write!(f, "best-effort:{}", level),
+        }
+    }
+}
+
+/// Resource limits in effect, as reported in /status.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Threads the CPU GEMM runs on.
+    pub cpu_threads: usize,
+    pub nice: Option<i32>,
+    pub ionice: Option<String>,
+    /// cgroup v2 directory the process moved itself into.
+    pub cgroup: Option<String>,
+    /// The `cpu.max` written to that cgroup (`<quota> <period>`).
+    pub cpu_max: Option<String>,
+    /// Limits that were configured but could not be applied.
+    pub errors: Vec<String>,
+}
+
+/// Apply `CPU_THREADS`, `WORKER_NICE`, `WORKER_IONICE` and `CPU_CGROUP`/`CPU_MAX_CORES`.
+///
+/// Call once at startup, before any CPU work. Every limit is best effort: one
+/// that cannot be applied (missing privileges, no cgroup delegation) is logged
+/// and listed in `errors` instead of stopping the worker.
+pub fn apply(config: &Config) -> ResourceLimits {
+    let mut limits = ResourceLimits::default();
+
+    let mut pool = rayon::ThreadPoolBuilder::new().thread_name(|i| format!("cpu-gemm-{}", i));
+    if config.cpu_threads > 0 {
+        pool = pool.num_threads(config.cpu_threads);
+    }
+    if let Err(e) = pool.build_global() {
+        limits.errors.push(format!("CPU_THREADS: {}", e));
+    }
+    limits.cpu_threads = rayon::current_num_threads();
+
+    if let Some(nice) = config.worker_nice {
+        match set_nice(nice) {
+            Ok(()) => limits.nice = Some(nice),
+            Err(e) => limits.errors.push(format!("WORKER_NICE={}: {}", nice, e)),
+        }
+    }
+    if let Some(priority) = config.worker_ionice {
+        match set_ionice(priority) {
+            Ok(()) => limits.ionice = Some(priority.to_string()),
+            Err(e) => limits.errors.push(format!("WORKER_IONICE={}: {}", priority, e)),
+        }
+    }
+    if let Some(dir) = &config.cpu_cgroup {
+        match enter_cgroup(dir, config.cpu_max_cores) {
+            Ok(cpu_max) => {
+                limits.cgroup = Some(dir.clone());
+                limits.cpu_max = cpu_max;
+            }
+            Err(e) => limits.errors.push(format!("CPU_CGROUP={}: {}", dir, e)),
+        }
+    }
+
+    for error in &limits.errors {
+        log_warn!("[limits] could not apply {}", error);
+    }
+    limits
+}
+
+// Thread IDs of this process; Linux keeps nice and I/O priority per thread
+#[cfg(target_os = "linux")]
+fn threads() -> anyhow::Result<Vec<libc::id_t>> {
+    Ok(std::fs::read_dir("/proc/self/task")?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect())
+}
+
+// Threads spawned later inherit the value from their creator, so every existing
+// thread (including the async runtime's) is updated
+#[cfg(target_os = "linux")]
+fn set_nice(nice: i32) -> anyhow::Result<()> {
+    for tid in threads()? {
+        // SAFETY: plain syscall on one of our own threads
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_ionice(priority: IoPriority) -> anyhow::Result<()> {
+    // linux/ioprio.h
+    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+    const IOPRIO_CLASS_SHIFT: u32 = 13;
+    let value = match priority {
+        IoPriority::BestEffort(level) => (2 << IOPRIO_CLASS_SHIFT) | level as libc::c_long,
+        IoPriority::Idle => 3 << IOPRIO_CLASS_SHIFT,
+    };
+    for tid in threads()? {
+        // SAFETY: plain syscall on one of our own threads
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid as libc::c_long, value) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+// Create `dir` if needed, set its cpu.max and move the whole process into it.
+// The parent must have the cpu controller in its cgroup.subtree_control.
+#[cfg(target_os = "linux")]
+fn enter_cgroup(dir: &str, cpu_max_cores: Option<f64>) -> anyhow::Result<Option<String>> {
+    let dir = std::path::Path::new(dir);
+    std::fs::create_dir_all(dir)?;
+    let cpu_max = match cpu_max_cores {
+        Some(cores) => {
+            let quota = ((cores * CPU_MAX_PERIOD_US as f64) as u64).max(1000);
+            let cpu_max = format!("{} {}", quota, CPU_MAX_PERIOD_US);
+            write_control(dir, "cpu.max", &cpu_max)
+                .map_err(|e| anyhow::anyhow!("writing cpu.max (is the cpu controller enabled for it?): {}", e))?;
+            Some(cpu_max)
+        }
+        None => None,
+    };
+    write_control(dir, "cgroup.procs", &std::process::id().to_string())
+        .map_err(|e| anyhow::anyhow!("moving into the cgroup: {}", e))?;
+    Ok(cpu_max)
+}
+
+// Control files exist only on cgroupfs, so a plain directory fails here instead of silently "working"
+#[cfg(target_os = "linux")]
+fn write_control(dir: &std::path::Path, file: &str, value: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    std::fs::OpenOptions::new().write(true).open(dir.join(file))?.write_all(value.as_bytes())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_nice(_nice: i32) -> anyhow::Result<()> {
+    anyhow::bail!("only supported on Linux")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_ionice(_priority: IoPriority) -> anyhow::Result<()> {
+    anyhow::bail!("only supported on Linux")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn enter_cgroup(_dir: &str, _cpu_max_cores: Option<f64>) -> anyhow::Result<Option<String>> {
+    anyhow::bail!("only supported on Linux")
+}
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::health::HealthChecker;
+use crate::identity::KeyRing;
+use crate::metrics::MetricsCollector;
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::signing::Secp;
+use crate::types::DeviceInfo;
+use crate::log_error;
+
+// First retry delay after a failed report; doubles up to the configured cap
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Signed proof-of-liveness, sent whether or not any receipt qualified.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LivenessReport {
+    pub device_did: String,
+    pub pubkey_hex: String,
+    pub timestamp: String,
+    pub uptime_seconds: u64,
+    pub total_attempts: u64,
+    pub attempts_per_second: f64,
+    pub receipts_per_second: f64,
+    pub health: String,
+    pub device_info: Option<DeviceInfo>,
+    pub sig_hex: String,
+}
+
+impl LivenessReport {
+    /// JSON of the report with an empty signature, which is what gets signed.
+    pub fn signing_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut unsigned = self.clone();
+        unsigned.sig_hex = String::new();
+        Ok(serde_json::to_vec(&unsigned)?)
+    }
+
+    pub fn sign(&mut self, secp: &Secp) -> anyhow::Result<()> {
+        self.sig_hex = secp.sign_payload(&self.signing_bytes()?)?;
+        Ok(())
+    }
+}
+
+/// Periodically POSTs a signed report per identity to `LIVENESS_URL`. Runs on its
+/// own task with its own backoff, so a slow receipt path never delays it and a
+/// failing liveness endpoint never slows receipts.
+pub struct LivenessReporter {
+    client: reqwest::Client,
+    url: String,
+    interval: Duration,
+    max_backoff: Duration,
+    keys: Arc<KeyRing>,
+    metrics: Arc<MetricsCollector>,
+    health: Arc<HealthChecker>,
+    prometheus: Arc<PrometheusMetrics>,
+    device_info: Option<DeviceInfo>,
+}
+
+impl LivenessReporter {
+    pub fn new(
+        client: reqwest::Client,
+        url: String,
+        interval: Duration,
+        keys: Arc<KeyRing>,
+        metrics: Arc<MetricsCollector>,
+        health: Arc<HealthChecker>,
+        prometheus: Arc<PrometheusMetrics>,
+    ) -> Self {
+        Self {
+            client,
+            url,
+            interval,
+            max_backoff: interval,
+            keys,
+            metrics,
+            health,
+            prometheus,
+            device_info: None,
+        }
+    }
+
+    /// Cap for the delay between retries after a failed report.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn with_device_info(mut self, device_info: DeviceInfo) -> Self {
+        self.device_info = Some(device_info);
+        self
+    }
+
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                let wait = match self.report_all().await {
+                    Ok(()) => {
+                        backoff = INITIAL_BACKOFF;
+                        self.interval
+                    }
+                    Err(e) => {
+                        log_error!("[liveness] report to {} failed, retrying in {}s: {}", self.url, backoff.as_secs(), e);
+                        let wait = backoff;
+                        backoff = (backoff * 2).min(self.max_backoff.max(INITIAL_BACKOFF));
+                        wait
+                    }
+                };
+                tokio::time::sleep(wait).await;
+            }
+        })
+    }
+
+    pub fn build_report(&self, device_did: &str, secp: &Secp) -> anyhow::Result<LivenessReport> {
+        let metrics = self.metrics.get_metrics();
+        let mut report = LivenessReport {
+            device_did: device_did.to_string(),
+            pubkey_hex: secp.pubkey_hex_compressed(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            uptime_seconds: metrics.uptime_seconds,
+            total_attempts: metrics.total_attempts,
+            attempts_per_second: metrics.attempts_per_second,
+            receipts_per_second: metrics.receipts_per_second,
+            health: self.health.get_health().status,
+            device_info: self.device_info.clone(),
+            sig_hex: String::new(),
+        };
+        report.sign(secp)?;
+        Ok(report)
+    }
+
+    // One report per identity; the round fails if any of them does
+    async fn report_all(&self) -> anyhow::Result<()> {
+        let mut failure = None;
+        for identity in self.keys.identities() {
+            let result = self.report(&identity.device_did, &identity.secp()).await;
+            self.prometheus.record_liveness_report(result.is_ok());
+            if let Err(e) = result {
+                failure = Some(e);
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    async fn report(&self, device_did: &str, secp: &Secp) -> anyhow::Result<()> {
+        let report = self.build_report(device_did, secp)?;
+        let resp = self.client.post(&self.url)
+            .timeout(Duration::from_secs(10))
+            .json(&report)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            anyhow::bail!("HTTP {}", resp.status());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use serde::{Deserialize, Serialize};
+
+/// Severity of a log line. Each level includes the ones above it when filtering:
+/// `warn` selects warnings and errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(LogLevel::Error),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            _ => Err(format!("unknown log level: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogLevel::Error => write!(f, "error"),
+            LogLevel::Warn => write!(f, "warn"),
+            LogLevel::Info => write!(f, "info"),
+        }
+    }
+}
+
+/// One log line as kept in the ring buffer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEvent {
+    /// Increases by one per recorded line, so a reader can ask for what came after it.
+    pub seq: u64,
+    pub timestamp: String,
+    pub level: LogLevel,
+    /// The line's `[tag]` (`submit`, `epoch`, ...), when it has one.
+    pub target: Option<String>,
+    pub message: String,
+    /// Idempotency key of the attempt the line is about.
+    pub attempt_id: Option<String>,
+}
+
+/// What `/logs` returns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogPage {
+    pub capacity: usize,
+    /// Lines recorded since startup; those beyond `capacity` have been dropped.
+    pub recorded: u64,
+    pub events: Vec<LogEvent>,
+}
+
+/// Filter for `LogRing::query`.
+#[derive(Debug, Clone)]
+pub struct LogQuery {
+    pub level: LogLevel,
+    pub limit: usize,
+    pub after: Option<u64>,
+    pub target: Option<String>,
+    pub attempt_id: Option<String>,
+}
+
+impl Default for LogQuery {
+    fn default() -> Self {
+        Self { level: LogLevel::Info, limit: 200, after: None, target: None, attempt_id: None }
+    }
+}
+
+impl LogQuery {
+    /// `level=`, `limit=`, `after=`, `target=` and `attempt=` from a query string.
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        for (name, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            let value = percent_decode(value).ok_or_else(|| format!("invalid encoding for {}: {}", name, value))?;
+            let value = value.as_str();
+            match name {
+                "level" => parsed.level = value.parse()?,
+                "limit" => parsed.limit = value.parse().map_err(|_| format!("invalid limit: {}", value))?,
+                "after" => parsed.after = Some(value.parse().map_err(|_| format!("invalid after: {}", value))?),
+                "target" => parsed.target = Some(value.to_string()),
+                "attempt" => parsed.attempt_id = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Decode a query-string value: `+` is a space and `%XX` a byte. None for a
+/// malformed escape or a result that isn't UTF-8.
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.bytes();
+    while let Some(b) = rest.next() {
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hi = (rest.next()? as char).to_digit(16)?;
+                let lo = (rest.next()? as char).to_digit(16)?;
+                bytes.push((hi * 16 + lo) as u8);
+            }
+            _ => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// The last `capacity` log lines.
+pub struct LogRing {
+    capacity: usize,
+    state: Mutex<RingState>,
+}
+
+#[derive(Default)]
+struct RingState {
+    next_seq: u64,
+    events: VecDeque<LogEvent>,
+}
+
+impl LogRing {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, state: Mutex::new(RingState::default()) }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn push(&self, level: LogLevel, attempt_id: Option<&str>, line: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        let (target, message) = split_target(line);
+        let Ok(mut state) = self.state.lock() else { return };
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        if state.events.len() == self.capacity {
+            state.events.pop_front();
+        }
+        state.events.push_back(LogEvent {
+            seq,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            level,
+            target: target.map(str::to_string),
+            message: message.to_string(),
+            attempt_id: attempt_id.map(str::to_string),
+        });
+    }
+
+    /// The latest `query.limit` matching lines, oldest first.
+    pub fn query(&self, query: &LogQuery) -> LogPage {
+        let Ok(state) = self.state.lock() else {
+            return LogPage { capacity: self.capacity, recorded: 0, events: Vec::new() };
+        };
+        let mut events: Vec<LogEvent> = state.events.iter().rev()
+            .filter(|e| e.level <= query.level)
+            .filter(|e| query.after.is_none_or(|after| e.seq > after))
+            .filter(|e| query.target.as_ref().is_none_or(|t| e.target.as_ref() == Some(t)))
+            .filter(|e| query.attempt_id.as_ref().is_none_or(|id| e.attempt_id.as_ref() == Some(id)))
+            .take(query.limit)
+            .cloned()
+            .collect();
+        events.reverse();
+        LogPage { capacity: self.capacity, recorded: state.next_seq, events }
+    }
+}
+
+// `[submit] accepted` -> (`submit`, `accepted`)
+fn split_target(line: &str) -> (Option<&str>, &str) {
+    line.strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .filter(|(tag, _)| !tag.is_empty() && !tag.contains(char::is_whitespace))
+        .map_or((None, line), |(tag, message)| (Some(tag), message.trim_start()))
+}
+
+static RING: OnceLock<LogRing> = OnceLock::new();
+
+/// Keep the last `capacity` lines for `/logs`. Returns false if a ring is already installed.
+pub fn install(capacity: usize) -> bool {
+    RING.set(LogRing::new(capacity)).is_ok()
+}
+
+/// The installed ring, if any.
+pub fn ring() -> Option<&'static LogRing> {
+    RING.get()
+}
+
+/// Print `line` (info to stdout, warnings and errors to stderr) and keep it in the ring.
+/// Use the `log_info!` / `log_warn!` / `log_error!` macros rather than calling this.
+pub fn record(level: LogLevel, attempt_id: Option<&str>, line: String) {
+    match level {
+        LogLevel::Info => println!("{}", line),
+        LogLevel::Warn | LogLevel::Error => eprintln!("{}", line),
+    }
+    if let Some(ring) = RING.get() {
+        ring.push(level, attempt_id, &line);
+    }
+}
+
+/// Log at info level: `log_info!("[tag] ...", args)`, or
+/// `log_info!(attempt: &key, "[tag] ...", args)` to tie the line to an attempt.
+#[macro_export]
+macro_rules! log_info {
+    (attempt: $attempt:expr, $($arg:tt)+) => {
+        $crate::logs::record($crate::logs::LogLevel::Info, Some($attempt), format!($($arg)+))
+    };
+    ($($arg:tt)+) => {
+        $crate::logs::record($crate::logs::LogLevel::Info, None, format!($($arg)+))
+    };
+}
+
+/// Log at warn level, like `log_info!`.
+#[macro_export]
+macro_rules! log_warn {
+    (attempt: $attempt:expr, $($arg:tt)+) => {
+        $crate::logs::record($crate::logs::LogLevel::Warn, Some($attempt), format!($($arg)+))
+    };
+    ($($arg:tt)+) => {
+        $crate::logs::record($crate::logs::LogLevel::Warn, None, format!($($arg)+))
+    };
+}
+
+/// Log at error level, like `log_info!`.
+#[macro_export]
+macro_rules! log_error {
+    (attempt: $attempt:expr, $($arg:tt)+) => {
+        $crate::logs::record($crate::logs::LogLevel::Error, Some($attempt), format!($($arg)+))
+    };
+    ($($arg:tt)+) => {
+        $crate::logs::record($crate::logs::LogLevel::Error, None, format!($($arg)+))
+    };
+}
@@ -0,0 +1,127 @@
+use std::sync::Mutex;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::types::Sizes;
+
+/// What the attempt loop is doing at the moment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopStage {
+    /// Before the first iteration: warm-up, autotune, enrollment.
+    #[default]
+    Starting,
+    /// Between attempts: pacing, pauses, back-off, back-pressure.
+    Waiting,
+    /// Waiting for the next finished attempt from the streams (or a challenge's).
+    Attempting,
+    /// Signing and delivering a receipt.
+    Submitting,
+    /// Left the loop and draining.
+    Stopping,
+}
+
+impl std::fmt::Display for LoopStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoopStage::Starting => write!(f, "starting"),
+            LoopStage::Waiting => write!(f, "waiting"),
+            LoopStage::Attempting => write!(f, "attempting"),
+            LoopStage::Submitting => write!(f, "submitting"),
+            LoopStage::Stopping => write!(f, "stopping"),
+        }
+    }
+}
+
+/// Items waiting in the queues the loop feeds or drains.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueueDepths {
+    /// Receipts in the transport's offline buffer.
+    pub submission: usize,
+    /// Aggregator liveness challenges not yet answered.
+    pub challenges: usize,
+}
+
+/// The attempt loop's live state, served under `loop_state` in `/status`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoopSnapshot {
+    pub stage: LoopStage,
+    /// Nonce of the latest attempt handed to the loop.
+    pub current_nonce: Option<u32>,
+    pub current_epoch: Option<u64>,
+    /// Sizes of the latest attempt, or the chosen sizes before the first.
+    pub current_sizes: Option<Sizes>,
+    /// Backend of the latest attempt (`CPU` for the `HYBRID_CPU` stream).
+    pub backend: Option<String>,
+    /// Outcome of the latest submission: `accepted`, `queued`, `throttled`, `rejected` or `failed`.
+    pub last_submission_result: Option<String>,
+    pub queue_depths: QueueDepths,
+    /// Since the latest accepted receipt, `None` before the first.
+    pub seconds_since_last_accepted: Option<f64>,
+    /// Loop iterations since startup.
+    pub iterations: u64,
+}
+
+/// Shared, cheaply updated copy of the loop's state for debugging stuck workers.
+///
+/// The loop updates it every iteration and at each stage change; `/status` reads
+/// a snapshot. Nothing here feeds back into the loop.
+#[derive(Debug, Default)]
+pub struct LoopState {
+    snapshot: Mutex<LoopSnapshot>,
+    last_accepted: Mutex<Option<Instant>>,
+}
+
+impl LoopState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply `f` to the live state.
+    pub fn update(&self, f: impl FnOnce(&mut LoopSnapshot)) {
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            f(&mut snapshot);
+        }
+    }
+
+    pub fn set_stage(&self, stage: LoopStage) {
+        self.update(|s| s.stage = stage);
+    }
+
+    /// Start of a loop iteration, with the epoch attempts are chained to and the queues.
+    pub fn begin_iteration(&self, epoch_id: u64, queue_depths: QueueDepths) {
+        self.update(|s| {
+            s.stage = LoopStage::Waiting;
+            s.current_epoch = Some(epoch_id);
+            s.queue_depths = queue_depths;
+            s.iterations += 1;
+        });
+    }
+
+    /// An attempt came back from the streams.
+    pub fn record_attempt(&self, nonce: u32, sizes: &Sizes, backend: &str) {
+        self.update(|s| {
+            s.current_nonce = Some(nonce);
+            s.current_sizes = Some(sizes.clone());
+            if s.backend.as_deref() != Some(backend) {
+                s.backend = Some(backend.to_string());
+            }
+        });
+    }
+
+    /// A submission finished with `outcome`; `accepted` restarts the clock.
+    pub fn record_submission(&self, outcome: &str) {
+        if outcome == "accepted" {
+            if let Ok(mut last) = self.last_accepted.lock() {
+                *last = Some(Instant::now());
+            }
+        }
+        self.update(|s| s.last_submission_result = Some(outcome.to_string()));
+    }
+
+    pub fn snapshot(&self) -> LoopSnapshot {
+        let mut snapshot = self.snapshot.lock().map(|s| s.clone()).unwrap_or_default();
+        snapshot.seconds_since_last_accepted = self.last_accepted.lock().ok()
+            .and_then(|last| last.map(|at| at.elapsed().as_secs_f64()));
+        snapshot
+    }
+}
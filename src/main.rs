@@ -1,119 +1,1451 @@
-mod types; mod prng; mod cl_kernels; mod gpu; mod attempt; mod signing;
-mod config; mod metrics; mod error_handling; mod health; mod server;
-mod prometheus_metrics;
-#[cfg(feature = "cuda")] mod gpu_cuda;
-#[cfg(feature = "cpu-fallback")] mod cpu;
-
 use std::sync::Arc;
+use anyhow::Context;
 use hex::ToHex;
-use types::{WorkReceipt, Sizes};
-use attempt::{run_attempt, Executor};
-use gpu::GpuExec;
-#[cfg(feature = "cuda")] use gpu_cuda::CudaExec;
-#[cfg(feature = "cpu-fallback")] use cpu::CpuExec;
-use signing::Secp;
-use config::Config;
-use metrics::MetricsCollector;
-use error_handling::{ErrorHandler, RateLimiter};
-use health::HealthChecker;
-use server::HealthServer;
-use prometheus_metrics::PrometheusMetrics;
-
-fn parse_target_ms() -> u64 {
-    std::env::var("AUTOTUNE_TARGET_MS")
-        .ok()
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(300)
-}
-
-fn candidate_sizes() -> Vec<Sizes> {
-    if let Ok(preset) = std::env::var("AUTOTUNE_PRESETS") {
-        // Format: "m1,n1,k1;m2,n2,k2;..."
-        let mut v = Vec::new();
-        for triplet in preset.split(';') {
-            let parts: Vec<_> = triplet.split(',').collect();
-            if parts.len() == 3 {
-                if let (Ok(m), Ok(n), Ok(k)) = (parts[0].parse(), parts[1].parse(), parts[2].parse()) {
-                    v.push(Sizes { m, n, k, batch: 1 });
+use tops_worker::types::{DeviceInfo, PerfContext, RequantParams, TimingConfidence, WorkReceipt, Sizes, RECEIPT_VERSION_V1};
+use tops_worker::attempt::{run_workload_attempt, Executor};
+use tops_worker::work_hash::HashKind;
+use tops_worker::energy::EnergyMeter;
+#[cfg(feature = "gpu")] use tops_worker::gpu::GpuExec;
+#[cfg(feature = "gpu")] use tops_worker::program_cache::ProgramCache;
+#[cfg(feature = "cuda")] use tops_worker::gpu_cuda::CudaExec;
+#[cfg(feature = "cuda")] use tops_worker::algo_cache::AlgoCache;
+#[cfg(all(feature = "metal", not(feature = "cuda")))] use tops_worker::gpu_metal::MetalExec;
+#[cfg(feature = "cpu-fallback")] use tops_worker::cpu::CpuExec;
+use tops_worker::did::{self, DidVerificationState};
+use tops_worker::identity::KeyRing;
+use tops_worker::integrity::{self, StateIntegrity};
+use tops_worker::signer::{RemoteSigner, SignerAllowlist, SignerService};
+use tops_worker::sequence::{NonceCheckpoint, ReceiptSequencer};
+use tops_worker::power::{self, PowerController, PowerMode, PowerPolicy};
+use tops_worker::pause::PauseSwitch;
+use tops_worker::jitter::Jitter;
+use tops_worker::limits;
+use tops_worker::config::{Config, ConfigError};
+use tops_worker::fleet_config::FleetConfigSync;
+use tops_worker::lifecycle::{self, ConfigSummary, IdentitySummary, ShutdownEvent, StartupEvent};
+use tops_worker::watch_only::WatchEstimate;
+use tops_worker::metrics::{EpochStats, MetricsCollector};
+use tops_worker::error_handling::{self, ErrorHandler, RateLimiter};
+use tops_worker::health::HealthChecker;
+use tops_worker::health_policy::HealthPolicy;
+use tops_worker::thermal;
+use tops_worker::server::{AdminApi, HealthServer};
+use tops_worker::prometheus_metrics::PrometheusMetrics;
+use tops_worker::rate_control::AdaptiveRateController;
+use tops_worker::pacing::Pacer;
+use tops_worker::endpoints::EndpointManager;
+use tops_worker::negotiation::ReceiptNegotiator;
+use tops_worker::compression::CompressionMode;
+use tops_worker::net::{self, ConnectionStats};
+use tops_worker::response_auth::ResponseVerifier;
+use tops_worker::idempotency::{idempotency_key, DedupSubmitter};
+use tops_worker::circuit::CircuitSubmitter;
+use tops_worker::submit::{AggregatorProtocol, FailureKind, HttpSubmitter, RejectReason, SubmitError, SubmitOutcome, Submitter};
+use tops_worker::queue::PersistentQueue;
+#[cfg(feature = "mqtt")] use tops_worker::mqtt::MqttSubmitter;
+#[cfg(feature = "grpc")] use tops_worker::grpc::GrpcSubmitter;
+#[cfg(feature = "stats")] use tops_worker::stats::StatsStore;
+use tops_worker::selftest::{self, SelfTestPolicy};
+use tops_worker::stress::{self, StressParams, StressReport};
+use tops_worker::streams::{AttemptStreams, SharedExecutor, StreamAttempt, StreamBackends};
+use tops_worker::pipeline::AttemptPipeline;
+use tops_worker::challenge::{ChallengeQueue, CHALLENGE_NONCE};
+use tops_worker::update::UpdateChecker;
+use tops_worker::logs;
+use tops_worker::{log_error, log_info, log_warn};
+use tops_worker::autotune::{self, DriftMonitor};
+use tops_worker::device_memory::{self, Footprint};
+use tops_worker::devices;
+use tops_worker::memhard::MemHardParams;
+use tops_worker::workload::{ProofWorkload, Workload};
+use tops_worker::epoch::{EpochFeed, EpochParams, EpochSource};
+use tops_worker::epoch_summary::EpochSummary;
+use tops_worker::size_distribution::{AttemptSizes, SizeDistribution};
+use tops_worker::watchdog::{Heartbeat, Watchdog};
+use tops_worker::loop_state::{LoopStage, LoopState, QueueDepths};
+use tops_worker::shutdown::{ExitReason, Shutdown};
+use tops_worker::warmup::Warmup;
+use tops_worker::liveness::LivenessReporter;
+use tops_worker::metrics_push::MetricsPusher;
+use tops_worker::enroll::{self, CapabilityReport, EnrollmentState};
+use tops_worker::evidence::{EvidencePolicy, EvidenceStore};
+use tops_worker::backpressure::{BackPressure, ThrottleMode};
+use tops_worker::clock::{self, ClockSync};
+use tops_worker::journal::{AttemptJournal, JournalEntry, SubmitFailureEntry};
+use tops_worker::export::{ExportRow, ReceiptExporter};
+use tops_worker::shadow::{ShadowSink, ShadowSubmitter};
+use tops_worker::quarantine::{self, Quarantine, QuarantinedReceipt};
+use tops_worker::doctor::{self, CheckResult, DoctorReport};
+use tops_worker::matrix_cache::{self, MatrixCache};
+
+// Initialize execution backend
+#[cfg(feature = "cuda")]
+fn init_executor(error_handler: &ErrorHandler, config: &Config) -> anyhow::Result<SharedExecutor> {
+    match CudaExec::new() {
+        Ok(g) => {
+            let mut g = g.with_streams(config.attempts_in_flight);
+            if config.cuda_algo_tuning {
+                g = g.with_algo_tuning(AlgoCache::open(config.get_algo_cache_path()), config.cuda_algo_candidates);
+            }
+            Ok(Arc::new(g))
+        }
+        Err(e) => {
+            error_handler.handle_gpu_error(&format!("CUDA initialization failed: {}", e));
+            devices::record_init_error("CUDA", &e.to_string());
+            #[cfg(feature="cpu-fallback")]
+            {
+                log_warn!("[WARN] GPU not found, falling back to CPU.");
+                Ok(Arc::new(CpuExec::new()?))
+            }
+            #[cfg(not(feature="cpu-fallback"))]
+            { Err(e) }
+        }
+    }
+}
+
+#[cfg(all(not(feature = "cuda"), not(feature = "cpu-fallback")))]
+fn init_executor(error_handler: &ErrorHandler, config: &Config) -> anyhow::Result<SharedExecutor> {
+    let streams = config.attempts_in_flight;
+    // Metal first where it is compiled in: OpenCL on macOS has no int8 path worth using
+    #[cfg(feature = "metal")]
+    match MetalExec::new() {
+        Ok(g) => return Ok(Arc::new(g)),
+        Err(e) => {
+            error_handler.handle_gpu_error(&format!("Metal initialization failed: {}", e));
+            devices::record_init_error("Metal", &e.to_string());
+        }
+    }
+    #[cfg(feature = "gpu")]
+    {
+        let cache = config.get_program_cache_dir().map(ProgramCache::new);
+        match GpuExec::new_cached(cache.as_ref()).and_then(|g| g.with_streams(streams)) {
+            Ok(g) => Ok(Arc::new(g)),
+            Err(e) => {
+                error_handler.handle_gpu_error(&format!("OpenCL initialization failed: {}", e));
+                devices::record_init_error("OpenCL", &e.to_string());
+                log_error!("[ERROR] No GPU backend available and no CPU fallback enabled.");
+                Err(e)
+            }
+        }
+    }
+    #[cfg(not(feature = "gpu"))]
+    {
+        let _ = (error_handler, streams);
+        log_error!("[ERROR] No GPU backend available and no CPU fallback enabled.");
+        Err(anyhow::anyhow!("No execution backend available"))
+    }
+}
+
+#[cfg(all(not(feature = "cuda"), feature = "cpu-fallback"))]
+fn init_executor(error_handler: &ErrorHandler, config: &Config) -> anyhow::Result<SharedExecutor> {
+    let streams = config.attempts_in_flight;
+    // Metal first where it is compiled in: OpenCL on macOS has no int8 path worth using
+    #[cfg(feature = "metal")]
+    match MetalExec::new() {
+        Ok(g) => return Ok(Arc::new(g)),
+        Err(e) => {
+            error_handler.handle_gpu_error(&format!("Metal initialization failed: {}", e));
+            devices::record_init_error("Metal", &e.to_string());
+        }
+    }
+    #[cfg(feature = "gpu")]
+    {
+        let cache = config.get_program_cache_dir().map(ProgramCache::new);
+        match GpuExec::new_cached(cache.as_ref()).and_then(|g| g.with_streams(streams)) {
+            Ok(g) => Ok(Arc::new(g)),
+            Err(e) => {
+                error_handler.handle_gpu_error(&format!("OpenCL initialization failed: {}", e));
+                devices::record_init_error("OpenCL", &e.to_string());
+                log_warn!("[WARN] GPU not found, falling back to CPU.");
+                Ok(Arc::new(CpuExec::new()?))
+            }
+        }
+    }
+    #[cfg(not(feature = "gpu"))]
+    {
+        let _ = (error_handler, streams);
+        Ok(Arc::new(CpuExec::new()?))
+    }
+}
+
+// Benchmark on first start (or with --enroll) and POST a signed capability report per identity.
+// The benchmark is saved before submitting, so a restart after a failed POST only resubmits it.
+#[allow(clippy::too_many_arguments)]
+async fn run_enrollment(
+    executor: &dyn Executor,
+    config: &Config,
+    url: &str,
+    workload: Workload,
+    force: bool,
+    keyring: &KeyRing,
+    device_info: &DeviceInfo,
+    shutdown: &Shutdown,
+) -> anyhow::Result<()> {
+    let path = config.get_enrollment_path();
+    let mut state = match EnrollmentState::load(&path)? {
+        Some(state) if !force => state,
+        _ => {
+            // An unstable overclock is not enrolled: `tops-worker stress` has to pass on this device first
+            if config.enroll_require_stress {
+                let reason = match StressReport::load(&config.get_stress_path())? {
+                    Some(report) => report.disqualifies(device_info),
+                    None => Some("no stress run recorded".to_string()),
+                };
+                if let Some(reason) = reason {
+                    anyhow::bail!("ENROLL_REQUIRE_STRESS: {}; run `tops-worker stress` on this device first", reason);
                 }
             }
+            log_info!("[enroll] running the capability benchmark");
+            let benchmark = enroll::run_benchmark(executor, workload, config.get_enroll_sustained_duration())?;
+            log_info!("[enroll] peak {:.3} TOPS, sustained {:.3} TOPS over {:.0}s, memory {:.1} GB/s ({})",
+                benchmark.peak_tops, benchmark.sustained_tops, benchmark.sustained_seconds,
+                benchmark.memory_bandwidth_gbps, benchmark.memory_bandwidth_source);
+            let state = EnrollmentState { benchmark, enrolled_at: None };
+            state.save(&path)?;
+            state
         }
-        if !v.is_empty() { return v; }
+    };
+    if let Some(enrolled_at) = &state.enrolled_at {
+        log_info!("[enroll] enrolled at {} (run with --enroll to benchmark again)", enrolled_at);
+        return Ok(());
     }
-    vec![
-        Sizes { m: 512, n: 512, k: 512, batch: 1 },
-        Sizes { m: 768, n: 768, k: 768, batch: 1 },
-        Sizes { m: 1024, n: 1024, k: 1024, batch: 1 },
-        Sizes { m: 1280, n: 1280, k: 1280, batch: 1 },
-        Sizes { m: 1536, n: 1536, k: 1536, batch: 1 },
-    ]
+
+    let client = net::aggregator_client(config)?;
+    let mut delay = config.get_retry_delay();
+    for attempt in 0..=config.max_retries {
+        let mut result = Ok(());
+        for identity in keyring.identities() {
+            let report = CapabilityReport::new(&identity.device_did, config.network_id.clone(),
+                device_info.clone(), state.benchmark.clone(), &identity.secp())?;
+            result = enroll::submit(&client, url, &report).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        match result {
+            Ok(()) => {
+                state.enrolled_at = Some(chrono::Utc::now().to_rfc3339());
+                state.save(&path)?;
+                log_info!("[enroll] capability report accepted by {}", url);
+                return Ok(());
+            }
+            Err(e) if attempt < config.max_retries => {
+                log_warn!("[enroll] submitting to {} failed, retrying in {}s: {}", url, delay.as_secs_f64(), e);
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    // The main loop drains right away; the saved benchmark is submitted on the next start
+                    _ = shutdown.wait() => return Ok(()),
+                }
+                delay *= 2;
+            }
+            Err(e) => return Err(anyhow::anyhow!("enrollment at {} failed: {}", url, e)),
+        }
+    }
+    Ok(())
 }
 
-#[cfg(feature = "gpu")]
-fn autotune_sizes(gpu: &GpuExec, prev_hash_bytes: &[u8;32]) -> anyhow::Result<Sizes> {
-    let target_ms = parse_target_ms();
-    let mut best_sizes: Option<Sizes> = None;
-    let mut best_score: u64 = u64::MAX;
-    let mut nonce: u32 = 0;
-    for s in candidate_sizes() {
-        // Run one attempt to gauge time
-        let out = crate::attempt::run_attempt(gpu, prev_hash_bytes, nonce, &s)?;
-        let dt = out.elapsed_ms;
-        let score = dt.abs_diff(target_ms);
-        println!("[autotune] m,n,k=({},{},{}) -> {} ms (|diff|={})", s.m, s.n, s.k, dt, score);
-        if score < best_score { best_score = score; best_sizes = Some(s); }
-        // Increase nonce so each run is unique yet deterministic
-        nonce = nonce.wrapping_add(1);
+// Compare the active executor against the CPU reference and apply the configured policy
+fn run_selftest(
+    executor: &dyn Executor,
+    round: u32,
+    policy: SelfTestPolicy,
+    metrics: &MetricsCollector,
+    prometheus_metrics: &PrometheusMetrics,
+) -> anyhow::Result<()> {
+    let passed = match selftest::run_gemm_selftest(executor, round) {
+        Ok(result) if result.passed => {
+            log_info!("[selftest] GEMM matches CPU reference ({} cases, {} ms)", result.cases, result.elapsed_ms);
+            true
+        }
+        Ok(result) => {
+            log_warn!("[selftest] GEMM mismatch: {}/{} elements differ (first: {:?})",
+                result.mismatched_elements, result.total_elements, result.first_mismatch);
+            false
+        }
+        Err(e) => {
+            log_warn!("[selftest] GEMM self-test could not run: {}", e);
+            false
+        }
+    };
+    metrics.record_selftest(passed);
+    prometheus_metrics.record_selftest(passed);
+    if passed {
+        return Ok(());
+    }
+    match policy {
+        SelfTestPolicy::Refuse => Err(anyhow::anyhow!(
+            "GEMM self-test failed; refusing to run (set SELFTEST_ON_MISMATCH=degrade to continue)")
+            .context(ExitReason::SelfTest)),
+        SelfTestPolicy::Degrade => {
+            log_warn!("[selftest] continuing with degraded health");
+            Ok(())
+        }
     }
-    best_sizes.ok_or_else(|| anyhow::anyhow!("autotune produced no candidates"))
 }
 
-#[cfg(feature = "cpu-fallback")]
-fn autotune_sizes(_cpu: &CpuExec, _prev_hash_bytes: &[u8;32]) -> anyhow::Result<Sizes> {
-    // For CPU fallback, use a fixed size since autotuning is less critical
-    Ok(Sizes { m: 1024, n: 1024, k: 1024, batch: 1 })
+// A kernel that rounds requantization differently from the integer contract would
+// sign receipts no verifier reproduces, whatever SELFTEST_ON_MISMATCH says
+fn run_requant_sweep(executor: &dyn Executor, backend: &str) -> anyhow::Result<()> {
+    let result = selftest::run_requant_sweep(executor)
+        .map_err(|e| e.context(ExitReason::SelfTest))?;
+    if result.passed {
+        log_info!("[selftest] {} requantization matches the integer contract ({} scales, {} accumulators, {} ms)",
+            backend, result.scales, result.accumulators, result.elapsed_ms);
+        return Ok(());
+    }
+    log_warn!("[selftest] {} requantization deviates: {}/{} accumulators (first: {:?})",
+        backend, result.mismatches, result.accumulators, result.first_mismatch);
+    Err(anyhow::anyhow!("{} kernel rounds requantization differently from the reference; refusing to use it", backend)
+        .context(ExitReason::SelfTest))
+}
+
+// Receipts record the workload and any memory-hard stage so attempts can be replayed
+fn kernel_ver_for(workload: Workload, memhard: Option<&MemHardParams>, executor: &dyn Executor) -> String {
+    let mut parts = vec![workload.kernel_ver()];
+    // Only the dense GEMM goes through a library
+    if let (Workload::Gemm, Some(tag)) = (workload, executor.kernel_ver_tag()) {
+        parts.push(tag.to_string());
+    }
+    if let Some(params) = memhard {
+        parts.push(params.tag());
+    }
+    parts.join(";")
+}
+
+// HYBRID_CPU: a CPU executor for one extra attempt stream, when the main backend is a GPU
+fn init_assist(config: &Config, primary: &DeviceInfo) -> Option<SharedExecutor> {
+    if !config.hybrid_cpu {
+        return None;
+    }
+    if primary.backend == "CPU" {
+        log_warn!("[hybrid] HYBRID_CPU has no effect, attempts already run on the CPU");
+        return None;
+    }
+    match tops_worker::cpu::CpuExec::new() {
+        Ok(cpu) => Some(Arc::new(cpu)),
+        Err(e) => {
+            log_warn!("[hybrid] CPU executor unavailable, running on {} only: {}", primary.backend, e);
+            None
+        }
+    }
+}
+
+// Sizes for the main loop: the fastest candidate meeting the epoch's TOPS-seconds
+// requirement, or the one closest to AUTOTUNE_TARGET_MS without a requirement
+// Sizes used when autotune is off: 1024³, or the smallest square size meeting the requirement
+fn default_sizes(workload: &Workload, min_tops_seconds: Option<f64>) -> Sizes {
+    match min_tops_seconds {
+        Some(required) => autotune::minimum_square_sizes(workload, required),
+        None => Sizes { m: 1024, n: 1024, k: 1024, batch: 1 },
+    }
+}
+
+// Run throwaway attempts until the warm-up phase is over; none of them are recorded or submitted
+fn run_warmup(
+    executor: &dyn Executor,
+    warmup: &Warmup,
+    workload: Workload,
+    memhard: Option<&MemHardParams>,
+    prev_hash: &[u8;32],
+    sizes: &Sizes,
+) -> anyhow::Result<()> {
+    if !warmup.is_active() {
+        return Ok(());
+    }
+    log_info!("[warmup] running warm-up attempts at m,n,k=({},{},{})", sizes.m, sizes.n, sizes.k);
+    // Nonces count down from the top so they never coincide with a submitted attempt
+    let mut nonce = u32::MAX;
+    loop {
+        let out = run_workload_attempt(executor, workload, memhard, prev_hash, nonce, None, sizes)?;
+        if !warmup.record_attempt(out.elapsed_ms) {
+            break;
+        }
+        nonce = nonce.wrapping_sub(1);
+    }
+    let status = warmup.status();
+    log_info!("[warmup] done after {} attempt(s), {} ms", status.attempts, status.elapsed_ms);
+    Ok(())
+}
+
+fn choose_sizes(
+    executor: &dyn Executor,
+    config: &Config,
+    workload: Workload,
+    memhard: Option<&MemHardParams>,
+    prev_hash: &[u8;32],
+    min_tops_seconds: Option<f64>,
+    target_ms: u64,
+) -> anyhow::Result<Sizes> {
+    if config.autotune_disable {
+        return Ok(fit_to_device(executor, config, workload, memhard, default_sizes(&workload, min_tops_seconds), min_tops_seconds));
+    }
+    let results = autotune::measure_candidates(executor, workload, memhard, prev_hash, min_tops_seconds)?;
+    let chosen = autotune::select_sizes(&results, &workload, min_tops_seconds, target_ms)
+        .ok_or_else(|| anyhow::anyhow!("autotune produced no candidates"))?;
+    let scaled = autotune::scale_batch(executor, workload, memhard, prev_hash, chosen, target_ms,
+        config.autotune_max_batch, config.autotune_batch_utilization_pct)?;
+    let chosen = &scaled.result;
+    log_info!("[autotune] using m,n,k=({},{},{}) batch {}: {} ms, {:.6} TOPS-s per receipt",
+        chosen.sizes.m, chosen.sizes.n, chosen.sizes.k, chosen.sizes.batch.max(1), chosen.elapsed_ms, workload.tera_ops(&chosen.sizes));
+    Ok(fit_to_device(executor, config, workload, memhard, chosen.sizes.clone(), min_tops_seconds))
+}
+
+// Sizes for the epoch's attempts: with a size distribution every attempt draws its
+// own, so nothing is tuned and the largest entry stands in for memory accounting
+fn epoch_sizes(
+    executor: &dyn Executor,
+    config: &Config,
+    workload: Workload,
+    memhard: Option<&MemHardParams>,
+    epoch: &EpochParams,
+    min_tops_seconds: Option<f64>,
+    target_ms: u64,
+) -> anyhow::Result<Sizes> {
+    match &epoch.size_distribution {
+        Some(distribution) => Ok(distribution_sizes(executor, config, workload, memhard, distribution)),
+        None => choose_sizes(executor, config, workload, memhard, &epoch.prev_hash, min_tops_seconds, target_ms),
+    }
+}
+
+fn distribution_sizes(
+    executor: &dyn Executor,
+    config: &Config,
+    workload: Workload,
+    memhard: Option<&MemHardParams>,
+    distribution: &SizeDistribution,
+) -> Sizes {
+    let entries: Vec<String> = distribution.entries().iter()
+        .map(|e| format!("({},{},{})x{}", e.m, e.n, e.k, e.weight))
+        .collect();
+    log_info!("[sizes] epoch size distribution, drawn per attempt: {}", entries.join(" "));
+    let largest = distribution.largest();
+    if let Some(memory) = executor.memory_info() {
+        if !memory.fits(&Footprint::of(workload, memhard, &largest), memory_copies(config), 0) {
+            log_warn!("[memory] WARNING: m,n,k=({},{},{}) from the size distribution may not fit in {} MiB of device memory",
+                largest.m, largest.n, largest.k, memory.total_bytes >> 20);
+        }
+    }
+    largest
+}
+
+// What the attempt streams run: the epoch's distribution, or the tuned sizes
+fn attempt_sizes(epoch: &EpochParams, sizes: &Sizes) -> AttemptSizes {
+    match &epoch.size_distribution {
+        Some(distribution) => AttemptSizes::Drawn(distribution.clone()),
+        None => AttemptSizes::Fixed(sizes.clone()),
+    }
+}
+
+// Clamp sides to what the backend's kernels index, then step them down until every
+// attempt stream's buffers fit in device memory. Backends that do not report their
+// memory get the sizes otherwise unchanged.
+// Nonces per persistent-kernel launch, unless the epoch asks for something the
+// persistent kernel does not implement
+fn persistent_range(config: &Config, epoch: &EpochParams, workload: Workload, memhard: Option<&MemHardParams>) -> Option<u32> {
+    if config.persistent_kernel_range == 0 {
+        return None;
+    }
+    let unsupported = if workload != Workload::Gemm {
+        Some(format!("the {} workload", workload.kind()))
+    } else if memhard.is_some() {
+        Some("the memory-hard stage".to_string())
+    } else if epoch.hash_kind != HashKind::Blake3 {
+        Some(format!("{} work roots", epoch.hash_kind))
+    } else if epoch.size_distribution.is_some() {
+        Some("sizes drawn per attempt".to_string())
+    } else {
+        None
+    };
+    match unsupported {
+        Some(what) => {
+            log_info!("[persistent] the persistent kernel does not implement {}, running one launch per attempt", what);
+            None
+        }
+        None => {
+            log_info!("[persistent] up to {} nonce(s) per launch; work roots are verified on the host before signing", config.persistent_kernel_range);
+            Some(config.persistent_kernel_range)
+        }
+    }
+}
+
+// The attempt streams for `epoch` from `first_nonce`: every (re)start goes through
+// here, so the epoch's hash, size distribution and persistent range always apply
+#[allow(clippy::too_many_arguments)]
+fn restart_streams(
+    config: &Config,
+    epoch: &EpochParams,
+    backends: &StreamBackends,
+    workload: Workload,
+    memhard: Option<MemHardParams>,
+    requant: RequantParams,
+    first_nonce: u32,
+    sizes: &Sizes,
+    device_sampling: bool,
+) -> AttemptStreams {
+    AttemptStreams::start(
+        backends.clone(),
+        workload,
+        memhard,
+        epoch.prev_hash,
+        epoch.salt,
+        requant,
+        epoch.hash_kind,
+        config.work_root_sampling,
+        first_nonce,
+        attempt_sizes(epoch, sizes),
+        config.attempts_in_flight,
+        config.pipeline_depth,
+        config.spotcheck_elements,
+        persistent_range(config, epoch, workload, memhard.as_ref()),
+        device_sampling,
+    )
+}
+
+fn fit_to_device(
+    executor: &dyn Executor,
+    config: &Config,
+    workload: Workload,
+    memhard: Option<&MemHardParams>,
+    sizes: Sizes,
+    min_tops_seconds: Option<f64>,
+) -> Sizes {
+    let capabilities = executor.capabilities();
+    let clamped = capabilities.clamp_sides(&sizes);
+    if (clamped.m, clamped.n, clamped.k) != (sizes.m, sizes.n, sizes.k) {
+        log_info!("[capabilities] m,n,k=({},{},{}) exceeds the backend's largest side, using ({},{},{})",
+            sizes.m, sizes.n, sizes.k, clamped.m, clamped.n, clamped.k);
+        warn_below_requirement(workload, &clamped, min_tops_seconds);
+    }
+    let sizes = clamped;
+    let Some(memory) = capabilities.memory else { return sizes };
+    let fitted = device_memory::fit_sizes(&sizes, workload, memhard, &memory, memory_copies(config));
+    if (fitted.m, fitted.n, fitted.k) != (sizes.m, sizes.n, sizes.k) {
+        log_info!("[memory] m,n,k=({},{},{}) does not fit in {} MiB of device memory, using ({},{},{})",
+            sizes.m, sizes.n, sizes.k, memory.total_bytes >> 20, fitted.m, fitted.n, fitted.k);
+        warn_below_requirement(workload, &fitted, min_tops_seconds);
+    }
+    fitted
+}
+
+// Log what the backend can run and refuse a workload it does not accept, before
+// anything is sized or timed
+fn check_capabilities(executor: &dyn Executor, config: &Config, backend: &str, workload: Workload, memhard: Option<&MemHardParams>) -> anyhow::Result<()> {
+    let capabilities = executor.capabilities();
+    log_info!("[capabilities] {}: {}", backend, capabilities.describe());
+    let kind = workload.kind();
+    if !capabilities.supports(kind) {
+        anyhow::bail!("the {} backend does not support the {} workload", backend, kind);
+    }
+    if !capabilities.is_native(kind) {
+        log_warn!("[capabilities] WARNING: the {} backend has no {} kernel; attempts run it on the CPU reference", backend, kind);
+    }
+    if memhard.is_some() && !capabilities.native_memhard {
+        log_info!("[capabilities] the memory-hard stage runs on the CPU reference");
+    }
+    if let Some(side) = capabilities.max_square_side(workload, memhard, memory_copies(config)) {
+        log_info!("[capabilities] largest square side for {} attempt stream(s): {}", memory_copies(config), side);
+    }
+    Ok(())
+}
+
+// Attempts whose buffers are on the device at once: one per stream, and at
+// least two because the next attempt is staged while one computes
+fn memory_copies(config: &Config) -> usize {
+    config.attempts_in_flight.max(2)
+}
+
+fn warn_below_requirement(workload: Workload, sizes: &Sizes, min_tops_seconds: Option<f64>) {
+    if let Some(required) = min_tops_seconds {
+        if workload.tera_ops(sizes) < required {
+            log_warn!("[memory] WARNING: {:.6} TOPS-s per attempt is below the required {:.6}; receipts may be rejected",
+                workload.tera_ops(sizes), required);
+        }
+    }
+}
+
+// Re-classify the submission backlog, logging and exporting mode changes
+fn observe_backpressure(backpressure: &BackPressure, submitter: &dyn Submitter, prometheus_metrics: &PrometheusMetrics) {
+    let depth = submitter.pending();
+    if let Some(mode) = backpressure.observe(depth) {
+        let status = backpressure.status();
+        match mode {
+            ThrottleMode::Running => log_info!("[backpressure] {} receipt(s) buffered, running at full rate", depth),
+            ThrottleMode::Slowed => log_info!("[backpressure] {} receipt(s) buffered (slow at {}), delaying attempts up to {} ms",
+                depth, status.slow_at, backpressure.max_delay().as_millis()),
+            ThrottleMode::Paused => log_info!("[backpressure] {} receipt(s) buffered (pause at {}), pausing attempts until {} or fewer",
+                depth, status.pause_at, status.resume_at),
+        }
+        prometheus_metrics.set_backpressure_mode(mode);
+    }
+}
+
+// Receipt transport for AGGREGATOR_PROTOCOL
+#[allow(clippy::too_many_arguments)]
+fn build_submitter(
+    config: &Config,
+    endpoints: &Arc<EndpointManager>,
+    keyring: &Arc<KeyRing>,
+    error_handler: &Arc<ErrorHandler>,
+    verifier: Option<Arc<ResponseVerifier>>,
+    metrics: Option<Arc<PrometheusMetrics>>,
+    connections: Arc<ConnectionStats>,
+    clock: Option<Arc<ClockSync>>,
+    exporter: Option<Arc<ReceiptExporter>>,
+) -> anyhow::Result<Arc<dyn Submitter>> {
+    let submitter: Arc<dyn Submitter> = match config.aggregator_protocol {
+        AggregatorProtocol::Http => {
+            // Receipt schema version is negotiated per aggregator on first contact
+            let negotiator = ReceiptNegotiator::new(config.aggregator_urls.len(), config.receipt_version_max)
+                .with_encoding_discovery(config.submit_compression == CompressionMode::Auto);
+            // One pooled client for the life of the worker, so connections and TLS sessions are reused
+            let client = net::aggregator_client_with_stats(config, Arc::clone(&connections))?;
+            Arc::new(HttpSubmitter::new(Arc::clone(endpoints), negotiator, Arc::clone(keyring))
+                .with_client(client)
+                .with_connection_stats(connections)
+                .with_clock(clock)
+                .with_epoch_url(config.epoch_url.clone())
+                .with_summary_url(config.epoch_summary_url.clone())
+                .with_compression(config.submit_compression, config.submit_compression_min_bytes)
+                .with_wire_format(config.receipt_wire_format)
+                .with_retry(error_handler.retry_config().clone())
+                .with_metrics(metrics.clone())
+                .with_response_verifier(verifier))
+        }
+        AggregatorProtocol::Mqtt => {
+            // Receipts are buffered on disk until the broker acknowledges them
+            let queue = Arc::new(PersistentQueue::open(config.get_queue_dir())?);
+            #[cfg(feature = "mqtt")]
+            { Arc::new(MqttSubmitter::start(config, Arc::clone(keyring), queue, exporter)?) }
+            #[cfg(not(feature = "mqtt"))]
+            {
+                let _ = (queue, exporter);
+                return Err(anyhow::anyhow!("AGGREGATOR_PROTOCOL=mqtt needs the `mqtt` feature"));
+            }
+        }
+        AggregatorProtocol::Grpc => {
+            #[cfg(feature = "grpc")]
+            { Arc::new(GrpcSubmitter::new(config, Arc::clone(keyring), Arc::clone(error_handler))?.with_response_verifier(verifier)) }
+            #[cfg(not(feature = "grpc"))]
+            {
+                let _ = error_handler;
+                return Err(anyhow::anyhow!("AGGREGATOR_PROTOCOL=grpc needs the `grpc` feature"));
+            }
+        }
+    };
+    // Receipts already delivered are not sent again, whichever path resends them
+    Ok(Arc::new(DedupSubmitter::new(submitter, config.idempotency_cache_size).with_metrics(metrics)))
+}
+
+// Signed totals of the epoch that just ended, delivered off the attempt loop
+fn submit_epoch_summary(
+    submitter: &Arc<dyn Submitter>,
+    keyring: &KeyRing,
+    network_id: Option<String>,
+    stats: &EpochStats,
+    prometheus_metrics: &Arc<PrometheusMetrics>,
+) {
+    // The totals cover every identity; the first one signs them
+    let Some(identity) = keyring.identities().first() else { return };
+    let summary = EpochSummary::new(&identity.device_did, network_id, stats);
+    let submitter = Arc::clone(submitter);
+    let prometheus_metrics = Arc::clone(prometheus_metrics);
+    tokio::spawn(async move {
+        let epoch_id = summary.epoch_id;
+        match submitter.submit_summary(summary).await {
+            Ok(true) => {
+                prometheus_metrics.record_epoch_summary(true);
+                log_info!("[epoch] summary of epoch {} delivered", epoch_id);
+            }
+            Ok(false) => {}
+            Err(e) => {
+                prometheus_metrics.record_epoch_summary(false);
+                log_warn!("[epoch] summary of epoch {} could not be delivered: {}", epoch_id, e);
+            }
+        }
+    });
+}
+
+// `tops-worker resubmit [--dry-run]`: re-validate quarantined receipts and post them again
+async fn run_resubmit() -> anyhow::Result<()> {
+    let config = Config::from_env()?;
+    config.validate()?;
+    let dry_run = std::env::args().skip(2).any(|arg| arg == "--dry-run");
+    install_matrix_cache(&config);
+    let quarantine = Quarantine::open(config.get_quarantine_dir(), config.quarantine_max_entries)?;
+    let entries = quarantine.entries()?;
+    if entries.is_empty() {
+        println!("[resubmit] nothing quarantined in {}", quarantine.dir().display());
+        return Ok(());
+    }
+    let metrics = Arc::new(MetricsCollector::new());
+    let error_handler = Arc::new(ErrorHandler::new(Arc::clone(&metrics)));
+    let endpoints = Arc::new(EndpointManager::new(
+        config.aggregator_urls.clone(),
+        config.aggregator_mode,
+        config.aggregator_failover_threshold,
+        config.get_failover_cooldown(),
+    ));
+    let keyring = Arc::new(load_keyring(&config).await?);
+    install_state_integrity(&config, Some(&keyring), None)?;
+    let verifier = config.aggregator_pubkey.as_deref()
+        .map(|key| ResponseVerifier::new(key, config.network_id.as_deref()).map(Arc::new))
+        .transpose()?;
+    let submitter = build_submitter(&config, &endpoints, &keyring, &error_handler, verifier, None, Arc::new(ConnectionStats::default()), None, None)?;
+    println!("[resubmit] {} quarantined receipt(s), delivering via {}{}", entries.len(), submitter.describe(),
+        if dry_run { " (dry run)" } else { "" });
+
+    let mut accepted = quarantine.accepted()?;
+    let mut seen = std::collections::HashSet::new();
+    let (mut resent, mut dropped, mut kept) = (0, 0, 0);
+    let pace = std::time::Duration::from_secs_f64(1.0 / config.rate_limit_per_second.max(1) as f64);
+    for (seq, entry) in entries {
+        let key = entry.key();
+        // The same attempt quarantined twice, or already accepted on an earlier run
+        if accepted.contains(&key) || !seen.insert(key.clone()) {
+            println!("[resubmit] {} already accepted or queued, dropping", key);
+            if !dry_run {
+                quarantine.remove(seq)?;
+            }
+            dropped += 1;
+            continue;
+        }
+        if let Err(e) = quarantine::revalidate(&entry.receipt, config.network_id.as_deref()) {
+            println!("[resubmit] {} kept, does not re-validate: {}", key, e);
+            kept += 1;
+            continue;
+        }
+        if dry_run {
+            println!("[resubmit] {} would be resubmitted (rejected {} as {})", key, entry.rejected_at, entry.reason_label());
+            continue;
+        }
+        let submission = match submitter.submit(entry.receipt.clone()).await {
+            Ok(submission) => submission,
+            Err(e @ SubmitError::NoEndpoint) => return Err(e.into()),
+            Err(SubmitError::Duplicate(_)) => {
+                println!("[resubmit] {} already delivered this run, dropping", key);
+                quarantine.remove(seq)?;
+                dropped += 1;
+                continue;
+            }
+            Err(e) => {
+                println!("[resubmit] {} kept, could not be sent: {}", key, e);
+                kept += 1;
+                continue;
+            }
+        };
+        let duplicate = submission.failure_kind() == Some(FailureKind::Duplicate);
+        match submission.outcome {
+            SubmitOutcome::Accepted { .. } => {
+                println!("[resubmit] {} accepted by {}", key, submission.target);
+                quarantine.remove(seq)?;
+                accepted.push_back(key);
+                resent += 1;
+            }
+            SubmitOutcome::Queued => {
+                println!("[resubmit] {} handed to {}", key, submission.target);
+                quarantine.remove(seq)?;
+                resent += 1;
+            }
+            SubmitOutcome::Rejected { .. } if duplicate => {
+                println!("[resubmit] {} is a duplicate at {}, dropping", key, submission.target);
+                quarantine.remove(seq)?;
+                accepted.push_back(key);
+                dropped += 1;
+            }
+            SubmitOutcome::Rejected { status, body } => {
+                let mut again = QuarantinedReceipt::new(entry.receipt, &submission.target, status, &body, submission.response.as_ref());
+                again.resubmissions = entry.resubmissions + 1;
+                println!("[resubmit] {} rejected again ({}, {})", key, status, again.reason_label());
+                quarantine.replace(seq, &again)?;
+                kept += 1;
+            }
+            // The aggregator is busy or unreachable; what is left stays for the next run
+            SubmitOutcome::Throttled { status, .. } => {
+                println!("[resubmit] aggregator throttled ({}), stopping", status);
+                break;
+            }
+            SubmitOutcome::Failed { error, .. } => {
+                println!("[resubmit] {} failed ({}), stopping", submission.target, error);
+                break;
+            }
+        }
+        tokio::time::sleep(pace).await;
+    }
+    if !dry_run {
+        quarantine.save_accepted(&accepted)?;
+    }
+    println!("[resubmit] {} resubmitted, {} dropped as duplicates, {} kept, {} left in {}",
+        resent, dropped, kept, quarantine.len(), quarantine.dir().display());
+    Ok(())
+}
+
+// Keys from the environment, or the public keys of the identities REMOTE_SIGNER_URL holds
+async fn load_keyring(config: &Config) -> anyhow::Result<KeyRing> {
+    let Some(signer) = RemoteSigner::from_config(config)? else { return KeyRing::load(config) };
+    let identities = signer.identities().await
+        .map_err(|e| anyhow::anyhow!("fetching identities from remote signer {}: {}", signer.url(), e))?;
+    if identities.is_empty() {
+        anyhow::bail!("remote signer {} holds no identities", signer.url());
+    }
+    log_info!("[signer] receipts are signed by {} ({} identit{})", signer.url(), identities.len(),
+        if identities.len() == 1 { "y" } else { "ies" });
+    KeyRing::remote(signer, identities)
+}
+
+// Seal state files (STATE_INTEGRITY=1) with STATE_KEY_HEX, or a key derived from the first identity's
+fn install_state_integrity(config: &Config, keyring: Option<&KeyRing>, metrics: Option<Arc<PrometheusMetrics>>) -> anyhow::Result<()> {
+    if !config.state_integrity {
+        return Ok(());
+    }
+    let integrity = match &config.state_key_hex {
+        Some(key) => StateIntegrity::from_key_hex(key)?,
+        None => keyring.and_then(|keyring| keyring.identities().first())
+            .and_then(|identity| StateIntegrity::derived(&identity.secp()))
+            .ok_or_else(|| anyhow::anyhow!("STATE_INTEGRITY needs STATE_KEY_HEX when no signing key is held here"))?,
+    };
+    integrity::install(match metrics {
+        Some(metrics) => integrity.with_metrics(metrics),
+        None => integrity,
+    });
+    log_info!("[integrity] state files are sealed with {}",
+        if config.state_key_hex.is_some() { "STATE_KEY_HEX" } else { "a key derived from the signing key" });
+    Ok(())
+}
+
+// `tops-worker signer`: hold the keys and sign for allowlisted compute nodes (REMOTE_SIGNER_URL)
+async fn run_signer() -> anyhow::Result<()> {
+    let config = Config::from_env()?;
+    config.validate()?;
+    if config.remote_signer_url.is_some() {
+        return Err(ConfigError::ValidationError("the signer holds the keys itself; unset REMOTE_SIGNER_URL".to_string()).into());
+    }
+    let allowlist = SignerAllowlist::parse(&config.signer_allowlist).map_err(ConfigError::ValidationError)?;
+    if allowlist.is_empty() {
+        return Err(ConfigError::ValidationError("the signer needs SIGNER_ALLOWLIST".to_string()).into());
+    }
+    let listen: std::net::SocketAddr = config.signer_listen.parse()?;
+    let keyring = Arc::new(KeyRing::load(&config)?);
+    for identity in keyring.identities() {
+        let key = identity.active_key();
+        println!("[signer] {} pubkey(compressed)={} key_epoch={}", identity.device_did, key.secp.pubkey_hex_compressed(), key.epoch);
+    }
+    
+    // Pick up rotated file: keys without a restart
+    if let Some(interval) = config.get_key_rotation_poll_interval() {
+        let keyring = Arc::clone(&keyring);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                for rotation in keyring.reload_changed() {
+                    println!("[signer] {} rotated to key epoch {}", rotation.device_did, rotation.key_epoch);
+                }
+            }
+        });
+    }
+    
+    println!("[signer] listening on {} for {}", listen, config.network_id.as_deref().unwrap_or("<no network>"));
+    let service = Arc::new(SignerService::new(keyring, allowlist, config.network_id.clone()));
+    service.serve(listen).await
+}
+
+// `tops-worker doctor`: preflight checks for support, printed as a table
+async fn run_doctor() -> anyhow::Result<()> {
+    let mut report = DoctorReport::default();
+    let (config, check) = doctor::check_config();
+    report.push(check);
+    if let Some(config) = &config {
+        if config.remote_signer_url.is_some() {
+            report.push(doctor::check_remote_signer(config).await);
+        } else {
+            report.extend(doctor::check_keys(config));
+        }
+        report.extend(doctor::check_aggregator(config).await);
+    }
+    report.extend(doctor::check_devices());
+    // The backend is still worth checking with defaults when the config is broken
+    let config = config.unwrap_or_default();
+    let error_handler = ErrorHandler::new(Arc::new(MetricsCollector::new()));
+    match init_executor(&error_handler, &config) {
+        Ok(executor) => report.push(doctor::check_benchmark(&*executor, &config, std::time::Duration::from_secs(1))),
+        Err(e) => report.push(CheckResult::fail("backend", e.to_string(),
+            "no usable GPU backend; install the driver or run a build with the cpu-fallback feature")),
+    }
+    report.push(doctor::check_cross_backend());
+    report.push(doctor::check_port(8082, config.metrics_enabled));
+    print!("{}", report.render());
+    if !report.passed() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// Serve regenerated matrices from MATRIX_CACHE_MAX_MB's cache; a cache that
+// cannot be opened only costs speed
+fn install_matrix_cache(config: &Config) {
+    let Some(max_bytes) = config.get_matrix_cache_max_bytes() else { return };
+    match MatrixCache::open(config.get_matrix_cache_dir(), max_bytes) {
+        Ok(cache) => {
+            let (entries, bytes) = cache.usage();
+            log_info!("[matrix-cache] {} entr{} ({} MiB) in {}", entries, if entries == 1 { "y" } else { "ies" },
+                bytes >> 20, cache.dir().display());
+            matrix_cache::install(cache);
+        }
+        Err(e) => log_warn!("[matrix-cache] disabled, could not open {}: {}", config.get_matrix_cache_dir().display(), e),
+    }
+}
+
+// `tops-worker cross-check`: bit-exact comparison of every compiled backend
+fn run_cross_check() -> anyhow::Result<()> {
+    // The cross-check needs no worker configuration; the cache is used when there is one
+    if let Ok(config) = Config::from_env() {
+        install_matrix_cache(&config);
+    }
+    let report = tops_worker::crosscheck::run_cross_check()?;
+    print!("{}", report.render());
+    if !report.passed() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// `tops-worker bench-kernels [--sizes m,n,k] [--iterations N] [--json]`: every
+// compiled GEMM kernel on the same inputs, timed and compared against the CPU
+fn run_bench_kernels() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(2).collect();
+    let value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1));
+    let sizes = match value("--sizes") {
+        Some(spec) => {
+            let sides: Vec<usize> = spec.split(',').map(|v| v.trim().parse()).collect::<Result<_, _>>()
+                .map_err(|_| anyhow::anyhow!("--sizes expects m,n,k, got {}", spec))?;
+            match sides[..] {
+                [m, n, k] if m > 0 && n > 0 && k > 0 => Sizes { m, n, k, batch: 1 },
+                _ => anyhow::bail!("--sizes expects three positive sides m,n,k, got {}", spec),
+            }
+        }
+        None => tops_worker::kernel_bench::DEFAULT_SIZES,
+    };
+    let iterations = match value("--iterations") {
+        Some(v) => v.parse().map_err(|_| anyhow::anyhow!("--iterations expects a number, got {}", v))?,
+        None => 5,
+    };
+    let report = tops_worker::kernel_bench::run_kernel_bench(&sizes, iterations)?;
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.render());
+    }
+    if !report.passed() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// `tops-worker stress [--duration SECS] [--size N] [--check N] [--max-temp C] [--json]`:
+// back-to-back GEMMs on the worker's backend, cross-checked on the CPU, to qualify
+// an overclock; the report is kept for ENROLL_REQUIRE_STRESS
+fn run_stress() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(2).collect();
+    let value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1));
+    let number = |flag: &str, default: u64| -> anyhow::Result<u64> {
+        match value(flag) {
+            Some(v) => v.parse().map_err(|_| anyhow::anyhow!("{} expects a number, got {}", flag, v)),
+            None => Ok(default),
+        }
+    };
+    let duration = std::time::Duration::from_secs(number("--duration", 600)?);
+    let check_elements = number("--check", 256)? as usize;
+    let max_temperature_c = match value("--max-temp") {
+        Some(v) => Some(v.parse::<f64>().map_err(|_| anyhow::anyhow!("--max-temp expects degrees Celsius, got {}", v))?),
+        None => None,
+    };
+    if duration.is_zero() || check_elements == 0 {
+        anyhow::bail!("--duration and --check must be greater than 0");
+    }
+
+    // The worker's configuration: the same backend it would pick, and its state directory for the report
+    let config = Config::from_env()?;
+    let error_handler = ErrorHandler::new(Arc::new(MetricsCollector::new()));
+    let executor = init_executor(&error_handler, &config)?;
+    let side = match value("--size") {
+        Some(v) => v.parse::<usize>().ok().filter(|&side| side > 0)
+            .ok_or_else(|| anyhow::anyhow!("--size expects a positive side, got {}", v))?,
+        None => stress::default_side(&*executor),
+    };
+    let params = StressParams { duration, sizes: Sizes { m: side, n: side, k: side, batch: 1 }, check_elements, max_temperature_c };
+    eprintln!("[stress] m,n,k=({},{},{}) on {} for {}s, {} element(s) cross-checked per GEMM",
+        side, side, side, executor.device_info().device_name, duration.as_secs(), check_elements);
+    let report = stress::run_stress(&*executor, &params);
+    report.save(&config.get_stress_path())?;
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.render());
+    }
+    if !report.passed() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// `tops-worker replay --journal FILE --nonce N [--prev-hash HEX] [--backend NAME] [--json]`:
+// run a journaled attempt again and diff it against what was recorded
+fn run_replay() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(2).collect();
+    let value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1));
+    let journal = value("--journal").ok_or_else(|| anyhow::anyhow!("replay needs --journal FILE"))?;
+    let nonce: u32 = match value("--nonce") {
+        Some(v) => v.parse().map_err(|_| anyhow::anyhow!("--nonce expects a number, got {}", v))?,
+        None => anyhow::bail!("replay needs --nonce N"),
+    };
+    let prev_hash = value("--prev-hash").map(String::as_str);
+    let backend = value("--backend").map_or("cpu", String::as_str);
+
+    // The worker's configuration, when available, brings the matrix cache and the state key
+    if let Ok(config) = Config::from_env() {
+        install_matrix_cache(&config);
+        if config.state_integrity {
+            let keyring = if config.state_key_hex.is_none() { KeyRing::load(&config).ok() } else { None };
+            install_state_integrity(&config, keyring.as_ref(), None)?;
+        }
+    }
+    
+    // Nonces restart with every prev_hash; without --prev-hash the latest attempt wins
+    let matching: Vec<JournalEntry> = tops_worker::journal::read_journal(journal)?
+        .into_iter()
+        .filter(|entry| entry.is_attempt(nonce, prev_hash))
+        .collect();
+    let Some(entry) = matching.last() else {
+        anyhow::bail!("no attempt with nonce {} in {}", nonce, journal);
+    };
+    if matching.len() > 1 {
+        eprintln!("[replay] {} attempts with nonce {}, replaying the latest (prev_hash {}); pass --prev-hash to pick another",
+            matching.len(), nonce, entry.receipt.prev_hash_hex);
+    }
+    let executor = tops_worker::replay::replay_executor(backend)?;
+    let out = tops_worker::replay::replay_attempt(&*executor, &entry.receipt)?;
+    let report = tops_worker::replay::ReplayReport::new(entry, backend, executor.device_info().device_name, &out);
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.render());
+    }
+    if !report.passed() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// `tops-worker migrate-config [--output FILE] [--keystore DIR] [--force]`: write the
+// environment's configuration to a tops-worker.toml, secrets moved to keystore files
+fn run_migrate_config() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(2).collect();
+    let value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1));
+    let output = std::path::PathBuf::from(value("--output").map_or(tops_worker::config_file::DEFAULT_FILE_NAME, String::as_str));
+    if output.exists() && !args.iter().any(|a| a == "--force") {
+        anyhow::bail!("{} already exists; pass --force to overwrite it", output.display());
+    }
+    let lookup = tops_worker::config_file::env_lookup()?;
+    let keystore = match value("--keystore") {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => std::path::Path::new(&lookup("STATE_DIR").unwrap_or_else(|_| "state".to_string())).join("keystore"),
+    };
+
+    let migration = tops_worker::config_file::migrate(lookup, &keystore)?;
+    print!("{}", migration.render());
+    if !migration.equivalent() {
+        eprintln!("[migrate-config] {} not written: loading it would change the fields above", output.display());
+        std::process::exit(1);
+    }
+    let header = format!("tops-worker configuration, migrated from the environment on {}\nLoad it with CONFIG_FILE={}; variables set in the environment still win",
+        chrono::Utc::now().format("%Y-%m-%d"), output.display());
+    std::fs::write(&output, migration.file.to_toml(&header)?)?;
+    println!("[migrate-config] wrote {}; start the worker with CONFIG_FILE={} and remove the migrated variables from its environment",
+        output.display(), output.display());
+    Ok(())
+}
+
+// `tops-worker once [--count N]`: attempts to run before exiting (default 1)
+fn once_count() -> anyhow::Result<u64> {
+    let args: Vec<String> = std::env::args().skip(2).collect();
+    let count = match args.iter().position(|a| a == "--count").map(|i| args.get(i + 1)) {
+        Some(Some(v)) => v.parse().map_err(|_| ConfigError::ValidationError(format!("--count expects a number, got {}", v)))?,
+        Some(None) => return Err(ConfigError::ValidationError("--count needs a number".to_string()).into()),
+        None => 1,
+    };
+    if count == 0 {
+        return Err(ConfigError::ValidationError("--count must be at least 1".to_string()).into());
+    }
+    Ok(count)
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() -> std::process::ExitCode {
+    let result = match std::env::args().nth(1).as_deref() {
+        Some("doctor") => run_doctor().await.map(|_| ExitReason::Stopped),
+        Some("cross-check") => run_cross_check().map(|_| ExitReason::Stopped),
+        Some("bench-kernels") => run_bench_kernels().map(|_| ExitReason::Stopped),
+        Some("stress") => run_stress().map(|_| ExitReason::Stopped),
+        Some("resubmit") => run_resubmit().await.map(|_| ExitReason::Stopped),
+        Some("replay") => run_replay().map(|_| ExitReason::Stopped),
+        Some("migrate-config") => run_migrate_config().map(|_| ExitReason::Stopped),
+        Some("signer") => run_signer().await.map(|_| ExitReason::Stopped),
+        Some("once") => match once_count() {
+            Ok(count) => run(Some(count)).await,
+            Err(e) => Err(e),
+        },
+        _ => run(None).await,
+    };
+    match result {
+        Ok(reason) => reason.into(),
+        Err(e) => {
+            let reason = ExitReason::of(&e);
+            eprintln!("Error: {:?}", e);
+            if lifecycle::json() {
+                let mut event = ShutdownEvent::new(reason);
+                event.error = Some(format!("{:#}", e));
+                lifecycle::emit(&event);
+            } else {
+                eprintln!("[shutdown] exiting with code {} ({})", reason.code(), reason);
+            }
+            reason.into()
+        }
+    }
+}
+
+/// The worker: the main loop until shut down, or `once` attempts for `tops-worker once`.
+async fn run(once: Option<u64>) -> anyhow::Result<ExitReason> {
+
     // Load and validate configuration
-    let config = Config::from_env()?;
+    let mut config = Config::from_env()?;
     config.validate()?;
+    // A fleet config document accepted before the restart overrides the environment
+    let fleet_config = FleetConfigSync::open(&mut config)?.map(Arc::new);
+    // `--enroll` re-runs the capability benchmark even if the worker enrolled before
+    let force_enroll = std::env::args().skip(1).any(|arg| arg == "--enroll");
+    if force_enroll && config.enroll_url.is_none() {
+        return Err(ConfigError::ValidationError("--enroll needs ENROLL_URL".to_string()).into());
+    }
     
-    println!("[config] Loaded configuration:");
-    println!("  - Device DID: {}", config.device_did);
-    println!("  - Aggregator URL: {}", config.aggregator_url);
-    println!("  - Autotune target: {}ms", config.autotune_target_ms);
-    println!("  - Max retries: {}", config.max_retries);
-    println!("  - Rate limit: {}/s", config.rate_limit_per_second);
+    // LOG_FORMAT=json replaces the banner with one startup event, emitted once the backend is up
+    lifecycle::install(config.log_format);
+    // The latest log lines, for /logs
+    logs::install(config.log_buffer_size);
+    if !lifecycle::json() {
+        log_info!("[config] Loaded configuration:");
+        log_info!("  - Device DID: {}", config.device_did);
+        log_info!("  - Network: {}", config.network_id.as_deref().unwrap_or("unset (receipts are not bound to a network)"));
+        log_info!("  - Aggregator URLs: {} ({})", config.aggregator_urls.join(", "), config.aggregator_mode);
+        log_info!("  - Autotune target: {}ms", config.autotune_target_ms);
+        if !config.tariff_schedule.is_empty() {
+            log_info!("  - Tariff schedule: {}", config.tariff_schedule);
+        }
+        log_info!("  - Max retries: {}", config.max_retries);
+        log_info!("  - Rate limit: {}/s", config.rate_limit_per_second);
+        if config.watch_only {
+            log_info!("  - Watch-only: no keys are loaded, nothing is signed or submitted");
+        }
+        if let Some(sync) = &fleet_config {
+            log_info!("  - Fleet config: {} every {}s (document version {})", sync.status().url, config.fleet_config_poll_secs,
+                sync.status().applied_version.map(|v| v.to_string()).unwrap_or_else(|| "none yet".to_string()));
+        }
+        if let Some(url) = &config.update_manifest_url {
+            log_info!("  - Update check: {} every {}s for {}{}", url, config.update_check_secs, config.update_target,
+                if config.update_download { ", staging downloads" } else { "" });
+        }
+    }
     
-    // Initialize metrics collector
-    let metrics = Arc::new(MetricsCollector::new());
+    // Thread pool, nice/ionice and cgroup limits, before any CPU work starts
+    let resource_limits = limits::apply(&config);
+    log_info!("[limits] CPU GEMM on {} thread(s){}{}{}", resource_limits.cpu_threads,
+        resource_limits.nice.map(|n| format!(", nice {}", n)).unwrap_or_default(),
+        resource_limits.ionice.as_ref().map(|p| format!(", ionice {}", p)).unwrap_or_default(),
+        resource_limits.cgroup.as_ref().map(|c| format!(", cgroup {} (cpu.max {})", c,
+            resource_limits.cpu_max.as_deref().unwrap_or("max"))).unwrap_or_default());
+    
+    // SIGTERM/SIGINT and POST /admin/restart drain the main loop instead of killing it
+    let shutdown = Arc::new(Shutdown::new());
+    shutdown.listen_for_signals();
+    
+    // Initialize metrics collector; health is classified with the configured thresholds
+    let health_policy = HealthPolicy::from_config(&config);
+    let metrics = Arc::new(MetricsCollector::new().with_health_policy(health_policy.clone()));
+    if health_policy.watches_temperature() {
+        let metrics = Arc::clone(&metrics);
+        let interval = config.get_health_check_interval();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            let mut reported_missing = false;
+            loop {
+                ticker.tick().await;
+                let celsius = tokio::task::spawn_blocking(thermal::gpu_temperature_c).await.unwrap_or(None);
+                if celsius.is_none() && !reported_missing {
+                    log_warn!("[health] no GPU temperature sensor found; temperature thresholds are not applied");
+                    reported_missing = true;
+                }
+                metrics.record_gpu_temperature(celsius);
+            }
+        });
+    }
     
     // Initialize Prometheus metrics
     let prometheus_metrics = Arc::new(PrometheusMetrics::new());
+    if let Some(version) = fleet_config.as_ref().and_then(|sync| sync.status().applied_version) {
+        prometheus_metrics.set_fleet_config_version(version);
+    }
     
     // Initialize error handler
-    let error_handler = ErrorHandler::new(Arc::clone(&metrics))
+    let error_handler = Arc::new(ErrorHandler::new(Arc::clone(&metrics))
         .with_retry_config(error_handling::RetryConfig {
             max_retries: config.max_retries,
             retry_delay: config.get_retry_delay(),
             backoff_multiplier: 2.0,
             max_retry_delay: std::time::Duration::from_secs(30),
-        });
+        })
+        .with_circuit_breaker(config.circuit_failure_threshold, config.get_circuit_recovery_timeout())
+        .with_circuit_metrics(Arc::clone(&prometheus_metrics)));
     
     // Initialize rate limiter
     let rate_limiter = RateLimiter::new(config.max_concurrent_requests, config.rate_limit_per_second as f64);
     
+    // Adaptive rate control driven by aggregator throttling responses
+    let rate_controller = AdaptiveRateController::new(
+        config.rate_limit_per_second as f64,
+        config.rate_limit_min_per_second,
+        config.rate_increase_step,
+        config.rate_decrease_factor,
+    );
+    prometheus_metrics.set_effective_rate(rate_controller.current_rate());
+    
+    // Aggregator endpoints with per-endpoint health tracking and failover
+    let endpoints = Arc::new(EndpointManager::new(
+        config.aggregator_urls.clone(),
+        config.aggregator_mode,
+        config.aggregator_failover_threshold,
+        config.get_failover_cooldown(),
+    ));
+    
+    // Signing keys, one per identity (WORKER_IDENTITIES, else DEVICE_DID); none when only watching,
+    // public keys only when a remote signer holds them
+    let keyring = Arc::new(if config.watch_only { KeyRing::watch_only() } else { load_keyring(&config).await? });
+    install_state_integrity(&config, Some(&keyring), Some(Arc::clone(&prometheus_metrics)))?;
+    let mut did_verifications = Vec::with_capacity(keyring.len());
+    for identity in keyring.identities() {
+        let key = identity.active_key();
+        log_info!("[identity] {} pubkey(compressed)={} key_epoch={} weight={}",
+            identity.device_did, key.secp.pubkey_hex_compressed(), key.epoch, identity.weight);
+        prometheus_metrics.set_key_epoch(&identity.device_did, key.epoch);
+        
+        // Check that the peaq DID document vouches for the identity's key
+        let did_verification = did::verify_did(&config, &identity.device_did, &key.secp).await;
+        match did_verification.state {
+            DidVerificationState::Verified => log_info!("[did] {} lists our pubkey", identity.device_did),
+            DidVerificationState::Skipped => log_info!("[did] PEAQ_RPC_URL not set, skipping DID verification"),
+            state => {
+                log_error!("[did] verification of {} failed ({:?}): {}", identity.device_did, state,
+                    did_verification.detail.as_deref().unwrap_or(""));
+                // The payload carries a proof signed by the key, which only the remote signer holds
+                if state == DidVerificationState::Missing && keyring.remote_signer().is_none() {
+                    let registration = did::registration_payload(&key.secp, &identity.device_did, &config.did_key_attribute)?;
+                    log_info!("[did] register this attribute on the DID: {}", serde_json::to_string(&registration)?);
+                }
+                if config.did_verify_required {
+                    return Err(anyhow::anyhow!("DID verification of {} failed and DID_VERIFY_REQUIRED=1", identity.device_did));
+                }
+            }
+        }
+        did_verifications.push(did_verification);
+    }
+    
+    // Pick up rotated file: keys without a restart
+    if let Some(interval) = config.get_key_rotation_poll_interval() {
+        let keyring = Arc::clone(&keyring);
+        let prometheus_metrics = Arc::clone(&prometheus_metrics);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                for rotation in keyring.reload_changed() {
+                    prometheus_metrics.record_key_rotation(&rotation.device_did, "file", rotation.key_epoch);
+                }
+            }
+        });
+    }
+    
+    // Receipt transport; with AGGREGATOR_PUBKEY only signed verdicts and epochs are acted on
+    let verifier = config.aggregator_pubkey.as_deref()
+        .map(|key| ResponseVerifier::new(key, config.network_id.as_deref())
+            .map(|verifier| Arc::new(verifier.with_metrics(Arc::clone(&prometheus_metrics)))))
+        .transpose()?;
+    let connections = Arc::new(ConnectionStats::new(Some(Arc::clone(&prometheus_metrics))));
+    // Receipt timestamps are only as good as the local clock; aggregator responses and NTP measure it
+    let clock = Arc::new(ClockSync::from_config(&config).with_metrics(Some(Arc::clone(&prometheus_metrics))));
+    // Receipts are mirrored as they are submitted, and again when a parked one is finally delivered
+    let exporter = match config.export_format {
+        Some(format) => {
+            let exporter = ReceiptExporter::open(config.get_export_dir(), format, config.export_retention_days, config.get_export_max_bytes())?;
+            log_info!("[export] mirroring receipts as {} to {} (retention {} days)", format, exporter.dir().display(), config.export_retention_days);
+            Some(Arc::new(exporter))
+        }
+        None => None,
+    };
+    let submitter = build_submitter(&config, &endpoints, &keyring, &error_handler, verifier, Some(Arc::clone(&prometheus_metrics)),
+        Arc::clone(&connections), Some(Arc::clone(&clock)), exporter.clone())?;
+    if let Some(server) = config.clock_ntp_server.clone() {
+        let clock = Arc::clone(&clock);
+        let interval = config.get_clock_check_interval();
+        log_info!("[clock] checking the local clock against {} every {:?}", server, interval);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match clock::sntp_offset(&server, std::time::Duration::from_secs(5)).await {
+                    Ok((offset_ms, round_trip_ms)) => clock.observe_ntp(offset_ms, round_trip_ms),
+                    Err(e) => log_error!("[clock] NTP query to {} failed: {}", server, e),
+                }
+            }
+        });
+    }
+    // Rejected receipts are kept for `tops-worker resubmit`
+    let quarantine = Arc::new(Quarantine::open(config.get_quarantine_dir(), config.quarantine_max_entries)?);
+    // While the submission circuit is open receipts are parked, then replayed after a canary;
+    // MQTT buffers on disk and reconnects by itself
+    let submitter: Arc<dyn Submitter> = match config.aggregator_protocol {
+        AggregatorProtocol::Mqtt => submitter,
+        _ => Arc::new(CircuitSubmitter::new(submitter, Arc::clone(error_handler.circuit_breaker()), config.get_circuit_backlog_dir())?
+            .with_quarantine(Some(Arc::clone(&quarantine)))
+            .with_exporter(exporter.clone())),
+    };
+    // Dual-writing during an aggregator migration: a best-effort copy of every receipt,
+    // through its own endpoint and client so nothing it does counts against the primary
+    let shadow = match config.shadow_aggregator_url.clone().filter(|_| !config.watch_only) {
+        Some(url) => {
+            let shadow_endpoints = Arc::new(EndpointManager::new(vec![url.clone()], config.aggregator_mode,
+                config.aggregator_failover_threshold, config.get_failover_cooldown()));
+            let copies = HttpSubmitter::new(shadow_endpoints, ReceiptNegotiator::new(1, config.receipt_version_max), Arc::clone(&keyring))
+                .with_client(net::aggregator_client(&config)?)
+                .with_compression(config.submit_compression, config.submit_compression_min_bytes);
+            let sink = Arc::new(ShadowSink::new(url, Arc::new(copies), config.shadow_enabled, config.shadow_max_in_flight)
+                .with_metrics(Some(Arc::clone(&prometheus_metrics))));
+            log_info!("[shadow] copying receipts to {} ({}, at most {} in flight)", sink.url(),
+                if sink.is_enabled() { "on" } else { "off until SHADOW_ENABLED=1 or /admin/shadow/on" }, config.shadow_max_in_flight);
+            Some(sink)
+        }
+        None => None,
+    };
+    let submitter: Arc<dyn Submitter> = match &shadow {
+        Some(sink) => Arc::new(ShadowSubmitter::new(submitter, Arc::clone(sink))),
+        None => submitter,
+    };
+    if config.watch_only {
+        log_info!("[submit] watch-only: epochs come from {}, no receipt is delivered", submitter.describe());
+    } else {
+        log_info!("[submit] delivering receipts via {}", submitter.describe());
+    }
+    if config.aggregator_pubkey.is_some() {
+        log_info!("[submit] aggregator responses must be signed by AGGREGATOR_PUBKEY");
+    }
+    if let Some(url) = config.epoch_summary_url.as_ref().filter(|_| !config.watch_only) {
+        match config.aggregator_protocol {
+            AggregatorProtocol::Http => log_info!("[epoch] signed epoch summaries go to {}", url),
+            protocol => log_warn!("[epoch] EPOCH_SUMMARY_URL is ignored with AGGREGATOR_PROTOCOL={}", protocol),
+        }
+    }
+    
+    // Optional power policy fed by an external solar/price signal
+    let power_controller = match power::source_from_config(&config)? {
+        Some(source) => {
+            log_info!("[power] following power signal from {}", source.describe());
+            Some(PowerController::spawn(PowerPolicy::from_config(&config), source))
+        }
+        None => None,
+    };
+    
     // Initialize health checker
-    let health_checker = Arc::new(HealthChecker::new(Arc::clone(&metrics), config.clone()));
+    let heartbeat = Arc::new(Heartbeat::new());
+    let loop_state = Arc::new(LoopState::new());
+    let warmup = Arc::new(Warmup::new(config.warmup_attempts, config.get_warmup_duration()));
+    let pause = Arc::new(PauseSwitch::default());
+    let watch = config.watch_only.then(|| Arc::new(WatchEstimate::new()));
+    let mut health_checker = HealthChecker::new(Arc::clone(&metrics), config.clone())
+        .with_endpoint_manager(Arc::clone(&endpoints))
+        .with_pause_switch(Arc::clone(&pause))
+        .with_heartbeat(Arc::clone(&heartbeat))
+        .with_loop_state(Arc::clone(&loop_state))
+        .with_warmup(Arc::clone(&warmup))
+        .with_resource_limits(resource_limits)
+        .with_circuit_breaker(Arc::clone(error_handler.circuit_breaker()));
+    for did_verification in did_verifications {
+        health_checker = health_checker.with_did_verification(did_verification);
+    }
+    if let Some(power) = &power_controller {
+        health_checker = health_checker.with_power_controller(Arc::clone(power));
+    }
+    if let Some(sync) = &fleet_config {
+        health_checker = health_checker.with_fleet_config(Arc::clone(sync));
+    }
+    if let Some(estimate) = &watch {
+        health_checker = health_checker.with_watch_only(Arc::clone(estimate));
+    }
+    if config.aggregator_protocol == AggregatorProtocol::Http {
+        health_checker = health_checker.with_aggregator_connections(Arc::clone(&connections));
+    }
+    let backpressure = Arc::new(BackPressure::from_config(&config));
+    if backpressure.enabled() && !config.watch_only {
+        health_checker = health_checker.with_backpressure(Arc::clone(&backpressure));
+    }
+    health_checker = health_checker.with_clock(Arc::clone(&clock));
+    // Liveness challenges from the aggregator, answered ahead of the streams
+    let challenges = Arc::new(ChallengeQueue::new(if config.watch_only { 0 } else { config.challenge_queue_max })
+        .with_metrics(Arc::clone(&prometheus_metrics)));
+    if challenges.enabled() {
+        health_checker = health_checker.with_challenges(Arc::clone(&challenges));
+    }
+    // Newer releases are reported, and with UPDATE_DOWNLOAD staged, but never run from here
+    if let Some(checker) = UpdateChecker::from_config(&config)? {
+        let checker = Arc::new(checker.with_metrics(Some(Arc::clone(&prometheus_metrics))));
+        checker.spawn();
+        health_checker = health_checker.with_update_checker(checker);
+    }
+    if let Some(sink) = &shadow {
+        health_checker = health_checker.with_shadow(Arc::clone(sink));
+    }
+    let health_checker = Arc::new(health_checker);
+    
+    // Hourly statistics, kept across restarts
+    #[cfg(feature = "stats")]
+    let stats = if config.stats_enabled {
+        let store = Arc::new(StatsStore::open(config.get_stats_db_path(), Arc::clone(&metrics), config.stats_retention_days)?);
+        store.spawn_flusher();
+        log_info!("[stats] hourly statistics in {} (retention {} days)", config.get_stats_db_path().display(), config.stats_retention_days);
+        Some(store)
+    } else {
+        None
+    };
     
-    // Start health server if metrics are enabled
-    let _health_server_handle = if config.metrics_enabled {
-        let health_server = HealthServer::new(Arc::clone(&health_checker), Arc::clone(&prometheus_metrics), 8082);
+    // Start health server if metrics are enabled; the control socket serves the same endpoints
+    let _health_server_handle = if config.metrics_enabled || config.control_socket.is_some() {
+        let mut health_server = HealthServer::new(Arc::clone(&health_checker), Arc::clone(&prometheus_metrics), 8082);
+        if !config.metrics_enabled {
+            health_server = health_server.without_http();
+        }
+        if let Some(path) = &config.control_socket {
+            health_server = health_server.with_control_socket(path.into(), config.control_socket_mode);
+        }
+        if config.admin_token.is_some() || config.control_socket.is_some() {
+            health_server = health_server.with_admin(AdminApi::new(
+                config.admin_token.clone(), Arc::clone(&keyring), Arc::clone(&shutdown), Arc::clone(&pause))
+                .with_shadow(shadow.clone()));
+        }
+        #[cfg(feature = "stats")]
+        if let Some(store) = &stats {
+            health_server = health_server.with_stats(Arc::clone(store));
+        }
         let handle = tokio::spawn(async move {
             if let Err(e) = health_server.start().await {
-                eprintln!("[health] Health server error: {}", e);
+                log_error!("[health] Health server error: {}", e);
             }
         });
         Some(handle)
@@ -121,172 +1453,763 @@ async fn main() -> anyhow::Result<()> {
         None
     };
     
+    // Workers started together (a fleet rollout, a power cut) reach the aggregator spread out
+    let mut jitter = Jitter::from_config(&config);
+    let startup_delay = jitter.startup_delay();
+    if !startup_delay.is_zero() {
+        log_info!("[jitter] waiting {:.1}s before contacting the aggregator", startup_delay.as_secs_f64());
+        tokio::select! {
+            _ = tokio::time::sleep(startup_delay) => {}
+            _ = shutdown.wait() => {}
+        }
+        if let Some(reason) = shutdown.requested() {
+            return Ok(reason);
+        }
+    }
+    
     // ---- Config (replace with real values / CLI flags) ----
-    let device_did = config.device_did;
-    let epoch_id: u64 = 1;
-    let prev_hash_hex = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"; // 64 hex
-    let prev_hash_bytes: [u8;32] = hex::decode(prev_hash_hex)?.try_into().unwrap();
+    let workload = config.get_workload();
+    let mut epoch = EpochParams::placeholder();
+    // Transports that can ask the aggregator for the epoch override the placeholder
+    match submitter.current_epoch().await {
+        Ok(Some(info)) => {
+            log_info!("[epoch] aggregator reports epoch {}", info.epoch_id);
+            epoch = EpochParams::from_info(&info);
+        }
+        Ok(None) => {}
+        Err(e) => log_warn!("[epoch] could not fetch the current epoch, using the placeholder: {}", e),
+    }
+    metrics.begin_epoch(epoch.epoch_id);
+    prometheus_metrics.set_epoch(epoch.epoch_id);
+    // Later epochs arrive through aggregator verdicts and, if the transport has one, the epoch feed
+    let mut epoch_feed = config.get_epoch_poll_interval()
+        .map(|interval| EpochFeed::spawn(Arc::clone(&submitter), interval));
+    let mut memhard = config.get_memhard(epoch.memhard_kib);
+    let mut requant = config.get_requant(epoch.requant);
     let mut nonce: u32 = 0;
+    // A one-shot run carries on from the nonces the previous run used on the same chain
+    if once.is_some() {
+        if let Some(highest) = NonceCheckpoint::load(config.get_nonce_checkpoint_path())?
+            .and_then(|checkpoint| checkpoint.resume_after(&epoch.prev_hash_hex()))
+        {
+            log_info!("[once] resuming after nonce {} on prev_hash {}", highest, epoch.prev_hash_hex());
+            nonce = highest;
+        }
+    }
+    let mut attempts_done: u64 = 0;
 
     // Initialize execution backend
-    #[cfg(feature = "cuda")]
-    let executor: Box<dyn Executor> = match CudaExec::new() {
-        Ok(g) => Box::new(g),
-        Err(e) => {
-            error_handler.handle_gpu_error(&format!("CUDA initialization failed: {}", e));
-            #[cfg(feature="cpu-fallback")]
-            {
-                eprintln!("[WARN] GPU not found, falling back to CPU.");
-                Box::new(CpuExec::new()?)
-            }
-            #[cfg(not(feature="cpu-fallback"))]
-            { return Err(e); }
+    let executor = init_executor(&error_handler, &config).context(ExitReason::BackendInit)?;
+    let device_info = executor.device_info();
+    devices::record_selected(&device_info);
+    devices::record_selected_pci_address(executor.pci_address());
+    let mut kernel_ver = kernel_ver_for(workload, memhard.as_ref(), &*executor);
+    // The CPU stream's receipts carry its own device info and kernel_ver
+    let assist = init_assist(&config, &device_info);
+    let assist_device_info = assist.as_ref().map(|cpu| cpu.device_info());
+    let mut assist_kernel_ver = assist.as_ref().map(|cpu| kernel_ver_for(workload, memhard.as_ref(), &**cpu));
+    let backends = StreamBackends { primary: Arc::clone(&executor), assist };
+    if device_info.backend == "CPU" {
+        let cpu = tops_worker::cpu::dispatch();
+        log_info!("[cpu] {} features [{}], using the {} kernel", cpu.arch, cpu.features.join(", "), cpu.kernel);
+    }
+    check_capabilities(&*executor, &config, &device_info.backend, workload, memhard.as_ref()).context(ExitReason::BackendInit)?;
+
+    // Validate the executor against the CPU reference before producing receipts
+    let mut selftest_round: u32 = 0;
+    if config.selftest_enabled {
+        run_selftest(&*executor, selftest_round, config.selftest_policy, &metrics, &prometheus_metrics)?;
+        run_requant_sweep(&*executor, &device_info.backend)?;
+        if let Some(cpu) = &backends.assist {
+            run_requant_sweep(&**cpu, "CPU")?;
         }
+    }
+
+    // Register the device's capabilities with the aggregator before the first receipt (a signed report)
+    if let Some(url) = config.enroll_url.as_ref().filter(|_| !config.watch_only) {
+        run_enrollment(&*executor, &config, url, workload, force_enroll, &keyring, &device_info, &shutdown).await?;
+    }
+
+    // The epoch's work requirement wins over MIN_TOPS_SECONDS
+    let mut min_tops_seconds = epoch.min_tops_seconds.or(config.min_tops_seconds);
+    // Absorb kernel compilation and driver warm-up before anything is timed
+    let warmup_sizes = fit_to_device(&*executor, &config, workload, memhard.as_ref(), default_sizes(&workload, min_tops_seconds), min_tops_seconds);
+    run_warmup(&*executor, &warmup, workload, memhard.as_ref(), &epoch.prev_hash, &warmup_sizes)?;
+    // The tariff window in force sets the latency target sizes are tuned to
+    let mut tariff = config.tariff_schedule.current(config.autotune_target_ms);
+    if !config.tariff_schedule.is_empty() {
+        log_info!("[tariff] starting in {}", config.tariff_schedule.describe(&tariff));
+    }
+    let mut sizes = epoch_sizes(&*executor, &config, workload, memhard.as_ref(), &epoch, min_tops_seconds, tariff.target_ms)?;
+    let mut drift = DriftMonitor::new(config.autotune_retune_drift_pct);
+    let evidence_policy = EvidencePolicy::new(config.evidence_sample_rate);
+    let evidence = EvidenceStore::open(config.get_evidence_dir(), config.get_evidence_max_bytes());
+    let journal = if config.attempt_journal {
+        let journal = AttemptJournal::open(config.get_journal_path(), config.get_journal_max_bytes())?;
+        log_info!("[journal] recording attempts to {}", journal.path().display());
+        Some(journal)
+    } else {
+        None
     };
+    // Replay protection: issued_at and a per-device sequence that survives restarts
+    let sequencer = ReceiptSequencer::open(config.get_sequence_path())?.with_clock(Arc::clone(&clock));
 
-    #[cfg(all(not(feature = "cuda"), not(feature = "cpu-fallback")))]
-    let executor: Box<dyn Executor> = {
-        #[cfg(feature = "gpu")]
-        {
-            match GpuExec::new() {
-                Ok(g) => Box::new(g),
-                Err(e) => {
-                    error_handler.handle_gpu_error(&format!("OpenCL initialization failed: {}", e));
-                    eprintln!("[ERROR] No GPU backend available and no CPU fallback enabled.");
-                    return Err(e);
-                }
+    // Print startup information
+    if lifecycle::json() {
+        let mut event = StartupEvent::new(ConfigSummary::from_config(&config), device_info.clone(), kernel_ver.clone(), epoch.epoch_id, epoch.hash_kind);
+        event.assist_device = assist_device_info.clone();
+        event.identities = keyring.identities().iter().map(|identity| {
+            let key = identity.active_key();
+            IdentitySummary {
+                device_did: identity.device_did.clone(),
+                key_fingerprint: lifecycle::key_fingerprint(&key.secp.pubkey_hex_compressed()),
+                key_epoch: key.epoch,
+                weight: identity.weight,
             }
+        }).collect();
+        event.fleet_config_version = fleet_config.as_ref().and_then(|sync| sync.status().applied_version);
+        lifecycle::emit(&event);
+    } else {
+        log_info!("[startup] Worker initialized successfully");
+        log_info!("[startup] Health endpoints available at http://localhost:8082");
+        log_info!("[startup] Prometheus metrics available at http://localhost:8082/prometheus");
+        log_info!("[startup] Workload: {}", kernel_ver);
+        if !requant.is_default() {
+            let scale = requant.resolve(epoch.salt.as_ref());
+            log_info!("[startup] Requantization: scale {}/{}, activation {}, rounding {}, overflow {}",
+                scale.num, scale.den, scale.activation, scale.rounding, scale.overflow);
         }
-        #[cfg(not(feature = "gpu"))]
-        {
-            eprintln!("[ERROR] No GPU backend available and no CPU fallback enabled.");
-            return Err(anyhow::anyhow!("No execution backend available"));
+        if epoch.hash_kind != HashKind::Blake3 {
+            log_info!("[startup] work_root hash: {}", epoch.hash_kind);
         }
-    };
+        log_info!("[startup] Starting main loop ({} attempt stream(s), pipeline depth {}, pacing {})...",
+            config.attempts_in_flight, config.pipeline_depth, config.pacing);
+        if config.watch_only {
+            log_info!("[watch-only] attempts are counted towards an estimate at /status; none is signed or submitted");
+        }
+        if let Some(cpu) = &assist_device_info {
+            log_info!("[hybrid] one more attempt stream on the CPU ({}), after the {} {} stream(s)",
+                cpu.device_name, config.attempts_in_flight, device_info.backend);
+        }
+    }
+    let mut pacer = Pacer::new(config.pacing);
+    // perf_context flags attempts finished at or above the lowest GPU temperature threshold
+    let thermal_limit = health_policy.degraded_gpu_temp_c.or(health_policy.unhealthy_gpu_temp_c);
 
-    #[cfg(all(not(feature = "cuda"), feature = "cpu-fallback"))]
-    let executor: Box<dyn Executor> = {
-        #[cfg(feature = "gpu")]
-        {
-            match GpuExec::new() {
-                Ok(g) => Box::new(g),
-                Err(e) => {
-                    error_handler.handle_gpu_error(&format!("OpenCL initialization failed: {}", e));
-                    eprintln!("[WARN] GPU not found, falling back to CPU.");
-                    Box::new(CpuExec::new()?)
+    // Joules per attempt for TOPS/W, from RAPL, NVML or the GPU's hwmon
+    let energy = EnergyMeter::start(config.energy_meter, &device_info.backend, config.get_energy_sample_interval())?;
+    if let Some(meter) = &energy {
+        log_info!("[energy] metering attempts with {}{}", meter.describe(),
+            if config.receipt_energy_estimate { ", estimates go into receipts" } else { "" });
+    }
+
+    // Each stream fills, computes and hashes its own interleaved nonces off-thread
+    let mut highest_nonce = nonce;
+    // Without a sampling kernel the output is read back anyway, so it is kept for evidence
+    let device_sampling = config.device_sampling && executor.capabilities().device_sampling
+        && !config.work_root_sampling.needs_output();
+    if device_sampling {
+        log_info!("[device-sampling] work_root samples are taken and hashed on the device; outputs are not read back");
+    } else if config.device_sampling && config.work_root_sampling.needs_output() {
+        log_info!("[device-sampling] {} sampling draws from the whole output, sampling on the host", config.work_root_sampling);
+    } else if config.device_sampling {
+        log_info!("[device-sampling] the {} backend has no sampling kernel, sampling on the host", device_info.backend);
+    }
+    let mut streams = restart_streams(&config, &epoch, &backends, workload, memhard, requant, nonce.wrapping_add(1), &sizes, device_sampling);
+
+    // Signed proof-of-liveness on its own schedule, independent of receipts
+    if let Some(url) = config.liveness_url.as_ref().filter(|_| !config.watch_only) {
+        log_info!("[liveness] reporting every {}s to {}", config.liveness_interval_secs, url);
+        LivenessReporter::new(
+            net::aggregator_client(&config)?,
+            url.clone(),
+            config.get_liveness_interval(),
+            Arc::clone(&keyring),
+            Arc::clone(&metrics),
+            Arc::clone(&health_checker),
+            Arc::clone(&prometheus_metrics),
+        )
+        .with_max_backoff(config.get_liveness_max_backoff())
+        .with_device_info(device_info.clone())
+        .spawn();
+    }
+
+    // Metrics pushed out for devices that cannot be scraped, next to the pull endpoint
+    if let Some(url) = &config.metrics_push_url {
+        let pusher = MetricsPusher::new(
+            net::aggregator_client(&config)?,
+            url,
+            &config.metrics_push_job,
+            &config.device_did,
+            config.get_metrics_push_interval(),
+            Arc::clone(&health_checker),
+            Arc::clone(&prometheus_metrics),
+        )?;
+        log_info!("[metrics-push] pushing every {}s to {}", config.metrics_push_interval_secs, pusher.url());
+        pusher.spawn();
+    }
+
+    // A loop that stops advancing turns health critical (and optionally restarts us)
+    heartbeat.beat();
+    if let Some(stall_after) = config.get_main_loop_stall() {
+        Watchdog::spawn(Arc::clone(&heartbeat), stall_after, config.main_loop_stall_restart);
+    }
+
+    // Newer fleet config documents, picked up between attempts like epochs
+    let mut fleet_config_feed = fleet_config.as_ref().map(FleetConfigSync::spawn);
+
+    loop_state.update(|s| {
+        s.backend = Some(device_info.backend.clone());
+        s.current_sizes = Some(sizes.clone());
+    });
+    let exit_reason = loop {
+        heartbeat.beat();
+        loop_state.begin_iteration(epoch.epoch_id, QueueDepths {
+            submission: submitter.pending(),
+            challenges: challenges.status().pending,
+        });
+
+        // The previous attempt has been submitted; stop before starting another
+        if let Some(reason) = shutdown.requested() {
+            break reason;
+        }
+        if once.is_some_and(|count| attempts_done >= count) {
+            // Through the shutdown path, so the drain below is bounded by DRAIN_TIMEOUT_SECS
+            shutdown.request(ExitReason::Stopped);
+            break ExitReason::Stopped;
+        }
+
+        // Reloadable settings of a newer fleet config document apply now, the rest at the next start
+        if let Some(feed) = fleet_config_feed.as_mut() {
+            if feed.has_changed().unwrap_or(false) {
+                if let Some(update) = feed.borrow_and_update().clone() {
+                    update.apply(&mut config);
+                    if update.reloads("PACING") {
+                        pacer = Pacer::new(config.pacing);
+                    }
+                    if update.reloads("SUBMIT_JITTER_MS") {
+                        jitter = Jitter::from_config(&config);
+                    }
+                    if update.reloads("AUTOTUNE_RETUNE_DRIFT_PCT") {
+                        drift = DriftMonitor::new(config.autotune_retune_drift_pct);
+                    }
+                    if let Some(sink) = shadow.as_ref().filter(|_| update.reloads("SHADOW_ENABLED")) {
+                        sink.set_enabled(config.shadow_enabled);
+                    }
+                    log_info!("[fleet-config] applied version {}: reloaded [{}], staged for restart [{}]",
+                        update.version, update.reload.join(", "), update.staged.join(", "));
+                    prometheus_metrics.set_fleet_config_version(update.version);
+                    if let Some(sync) = &fleet_config {
+                        sync.applied(&update);
+                    }
                 }
             }
         }
-        #[cfg(not(feature = "gpu"))]
-        {
-            Box::new(CpuExec::new()?)
+
+        // Honor any Retry-After the aggregator sent us
+        if let Some(wait) = rate_controller.retry_after_remaining() {
+            log_info!("[rate] honoring Retry-After, pausing {:.1}s", wait.as_secs_f64());
+            heartbeat.set_idle(true);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = shutdown.wait() => {}
+            }
+            heartbeat.set_idle(false);
+            if let Some(meter) = &energy { meter.skip(); }
+            continue;
         }
-    };
 
-    // If autotune is enabled, compute sizes now using the initialized executor
-    let sizes = if config.autotune_disable {
-        Sizes { m: 1024, n: 1024, k: 1024, batch: 1 }
-    } else {
-        // For trait objects, we need to handle autotuning differently
-        // For now, use a fixed size
-        Sizes { m: 1024, n: 1024, k: 1024, batch: 1 }
-    };
+        // An operator pause holds everything until /admin/resume
+        if pause.is_paused() {
+            log_info!("[admin] paused, waiting for /admin/resume");
+            heartbeat.set_idle(true);
+            tokio::select! {
+                _ = pause.wait_until_resumed() => {}
+                _ = shutdown.wait() => {}
+            }
+            heartbeat.set_idle(false);
+            if let Some(meter) = &energy { meter.skip(); }
+            continue;
+        }
 
-    // Signing key (hex) – in production, derive from peaq DID key or HSM
-    let sk_hex = config.worker_sk_hex;
-    let secp = Secp::from_hex(&sk_hex)?;
-    println!("pubkey(compressed)={}", secp.pubkey_hex_compressed());
+        // Hold off entirely while the power policy says so
+        if let Some(power) = &power_controller {
+            let paused = power.state().mode == PowerMode::Paused;
+            heartbeat.set_idle(true);
+            tokio::select! {
+                _ = power.wait_until_running() => {}
+                _ = shutdown.wait() => {
+                    heartbeat.set_idle(false);
+                    continue;
+                }
+            }
+            heartbeat.set_idle(false);
+            if paused {
+                if let Some(meter) = &energy { meter.skip(); }
+            }
+        }
 
-    // Print startup information
-    println!("[startup] Worker initialized successfully");
-    println!("[startup] Health endpoints available at http://localhost:8082");
-    println!("[startup] Prometheus metrics available at http://localhost:8082/prometheus");
-    println!("[startup] Starting main loop...");
+        // A growing submission backlog slows, then stops, the attempts feeding it
+        if backpressure.enabled() && !config.watch_only {
+            observe_backpressure(&backpressure, &*submitter, &prometheus_metrics);
+            if backpressure.mode() == ThrottleMode::Paused {
+                heartbeat.set_idle(true);
+                // Parked receipts only drain as something is sent, so the backlog is fed out meanwhile
+                while backpressure.mode() == ThrottleMode::Paused && shutdown.requested().is_none() {
+                    if !submitter.drain_one().await {
+                        tokio::select! {
+                            _ = tokio::time::sleep(std::time::Duration::from_secs(1)) => {}
+                            _ = shutdown.wait() => {}
+                        }
+                    }
+                    observe_backpressure(&backpressure, &*submitter, &prometheus_metrics);
+                }
+                heartbeat.set_idle(false);
+                if let Some(meter) = &energy { meter.skip(); }
+                continue;
+            }
+        }
 
-    loop {
-        nonce = nonce.wrapping_add(1);
+        // Entering another tariff window re-tunes to its target and applies its duty cycle
+        if !config.tariff_schedule.is_empty() {
+            let profile = config.tariff_schedule.current(config.autotune_target_ms);
+            if profile.window != tariff.window {
+                log_info!("[tariff] {} -> {}",
+                    config.tariff_schedule.describe(&tariff), config.tariff_schedule.describe(&profile));
+                let retarget = profile.target_ms != tariff.target_ms;
+                tariff = profile;
+                if retarget && !config.autotune_disable && epoch.size_distribution.is_none() {
+                    drop(streams);
+                    sizes = choose_sizes(&*executor, &config, workload, memhard.as_ref(), &epoch.prev_hash, min_tops_seconds, tariff.target_ms)?;
+                    drift = DriftMonitor::new(config.autotune_retune_drift_pct);
+                    streams = restart_streams(&config, &epoch, &backends, workload, memhard, requant, highest_nonce.wrapping_add(1), &sizes, device_sampling);
+                }
+            }
+            // Duty 0 sits the window out, looking again every second
+            if tariff.duty_cycle <= 0.0 {
+                heartbeat.set_idle(true);
+                tokio::select! {
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(1)) => {}
+                    _ = shutdown.wait() => {}
+                }
+                heartbeat.set_idle(false);
+                if let Some(meter) = &energy { meter.skip(); }
+                continue;
+            }
+        }
+
+        // Hold the loop at the PACING target; the pause can be long at low receipt rates.
+        // Jitter keeps a fleet on the same cadence from submitting in step. A pending challenge does not wait
+        let delay = if challenges.has_pending() {
+            std::time::Duration::ZERO
+        } else {
+            pacer.next_delay() + jitter.submit_jitter() + backpressure.delay()
+        };
+        prometheus_metrics.set_pacing_delay(delay);
+        if !delay.is_zero() {
+            heartbeat.set_idle(true);
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutdown.wait() => {}
+            }
+            heartbeat.set_idle(false);
+            if let Some(meter) = &energy { meter.skip(); }
+            if shutdown.requested().is_some() {
+                continue;
+            }
+        }
 
         // Rate limiting
         rate_limiter.wait_for_token();
 
+        // A due challenge goes first: the streams hold while one attempt chained
+        // from the challenge's prev_hash runs on its own
+        let mut challenge = challenges.next_due();
+        let challenged = challenge.is_some();
+        loop_state.set_stage(LoopStage::Attempting);
+        let next = match &challenge {
+            Some(answer) => {
+                log_info!("[challenge] answering {} ({} ms left)", answer.id(), answer.remaining().as_millis());
+                streams.hold();
+                let attempt = AttemptPipeline::start(workload, memhard, answer.prev_hash(), epoch.salt, CHALLENGE_NONCE, attempt_sizes(&epoch, &sizes), 1)
+                    .with_requant(requant)
+                    .with_hash_kind(epoch.hash_kind)
+                    .with_work_sampling(config.work_root_sampling)
+                    .with_spot_check(config.spotcheck_elements)
+                    .next(&*executor)
+                    .map(|(nonce, out)| StreamAttempt { stream: 0, assist: false, nonce, out });
+                streams.resume();
+                attempt
+            }
+            None => streams.next(),
+        };
+
         // Run attempt with error handling
-        let out = match run_attempt(&*executor, &prev_hash_bytes, nonce, &sizes) {
-            Ok(out) => out,
+        let (assisted, attempt_nonce, out) = match next {
+            Ok(attempt) => {
+                // The challenge's nonce belongs to the challenge's prev_hash, not the epoch's
+                if !challenged {
+                    attempts_done += 1;
+                    nonce = attempt.nonce;
+                    highest_nonce = highest_nonce.max(nonce);
+                }
+                let backend = match (&assist_device_info, attempt.assist) {
+                    (Some(cpu), true) => &cpu.backend,
+                    _ => &device_info.backend,
+                };
+                loop_state.record_attempt(attempt.nonce, &attempt.out.sizes, backend);
+                metrics.record_stream_attempt(attempt.stream, backend, attempt.out.elapsed_ms);
+                prometheus_metrics.record_stream_attempt(attempt.stream, backend, attempt.out.elapsed_ms);
+                prometheus_metrics.record_attempt_phases(backend, &attempt.out.phases);
+                if attempt.assist {
+                    // Device memory figures are about the GPU
+                } else if let Some(memory) = executor.memory_info() {
+                    let used = Footprint::of(workload, memhard.as_ref(), &attempt.out.sizes).total_bytes * memory_copies(&config) as u64;
+                    prometheus_metrics.set_device_memory(&memory, used);
+                }
+                pacer.on_attempt();
+                (attempt.assist, attempt.nonce, attempt.out)
+            }
+            // The epoch dictates the sizes; there is nothing to step down
+            Err(e) if device_memory::is_out_of_memory(&e) && epoch.size_distribution.is_some() => {
+                error_handler.handle_gpu_error(&format!("Attempt at a size drawn from the epoch's distribution failed: {}", e));
+                continue;
+            }
+            // The device ran out of memory at these sizes: step down and restart the streams
+            Err(e) if device_memory::is_out_of_memory(&e) => {
+                let Some(smaller) = device_memory::step_down(&sizes) else {
+                    error_handler.handle_gpu_error(&format!("Attempt failed at the smallest sizes: {}", e));
+                    continue;
+                };
+                log_info!("[memory] {} at m,n,k=({},{},{}), stepping down to ({},{},{})",
+                    e, sizes.m, sizes.n, sizes.k, smaller.m, smaller.n, smaller.k);
+                prometheus_metrics.record_memory_downscale();
+                warn_below_requirement(workload, &smaller, min_tops_seconds);
+                drop(streams);
+                sizes = smaller;
+                drift = DriftMonitor::new(config.autotune_retune_drift_pct);
+                streams = restart_streams(&config, &epoch, &backends, workload, memhard, requant, highest_nonce.wrapping_add(1), &sizes, device_sampling);
+                continue;
+            }
             Err(e) => {
                 error_handler.handle_gpu_error(&format!("Attempt failed: {}", e));
                 continue;
             }
         };
+        let energy_j = energy.as_ref().map(EnergyMeter::lap);
+        if let Some(joules) = energy_j {
+            metrics.record_epoch_energy(joules);
+            prometheus_metrics.record_attempt_energy(joules, workload.tera_ops(&out.sizes));
+        }
 
-        let work_root_hex = out.work_root.encode_hex::<String>();
+        // An output that disagrees with the CPU is never submitted
+        if let Some(check) = &out.spot_check {
+            metrics.record_spot_check(check.passed());
+            prometheus_metrics.record_spot_check(check);
+            if !check.passed() {
+                metrics.record_attempt(out.elapsed_ms, false);
+                error_handler.handle_gpu_error(&format!(
+                    "silent corruption in nonce {}: {}/{} spot-checked elements differ from the CPU (first: {:?})",
+                    attempt_nonce, check.mismatches, check.checked, check.first_mismatch));
+                continue;
+            }
+        }
 
-        let mut receipt = WorkReceipt {
-            device_did: device_did.clone(),
-            epoch_id,
-            prev_hash_hex: prev_hash_hex.to_string(),
-            nonce,
-            work_root_hex: work_root_hex.clone(),
-            sizes: sizes.clone(),
-            time_ms: out.elapsed_ms,
-            kernel_ver: "gemm_int8_relu_q_v1".into(),
-            driver_hint: "OpenCL".into(),
-            sig_hex: String::new(),
-        };
-        
-        // debug: print full receipt if needed
-        if config.worker_debug_receipt {
-            println!("Receipt: {:?}", receipt);
+        // Periodic re-check so a card that drifts (thermals, clocks) gets caught
+        if !challenged && config.selftest_enabled && config.selftest_interval > 0 && nonce.is_multiple_of(config.selftest_interval) {
+            selftest_round = selftest_round.wrapping_add(1);
+            run_selftest(&*executor, selftest_round, config.selftest_policy, &metrics, &prometheus_metrics)?;
         }
-        
-        // Sign the receipt
-        let sig = match secp.sign_receipt(&receipt) {
-            Ok(sig) => sig,
-            Err(e) => {
-                error_handler.handle_signature_error(&format!("Signing failed: {}", e));
-                continue;
+
+        let work_root_hex = out.work_root.encode_hex::<String>();
+        // Watch-only: the attempt only counts towards the estimate; no evidence, receipt or signature
+        let response = if let Some(estimate) = &watch {
+            metrics.record_attempt(out.elapsed_ms, true);
+            prometheus_metrics.record_attempt(out.elapsed_ms, true);
+            estimate.record(&out.sizes, workload.tera_ops(&out.sizes), out.elapsed_ms);
+            let summary = estimate.summary();
+            prometheus_metrics.set_watch_estimated_tops(summary.estimated_tops);
+            pacer.on_receipt();
+            log_info!("watch nonce={} ms={} work_root={} est_tops={:.3} receipts/s={:.2}",
+                attempt_nonce, out.elapsed_ms, work_root_hex, summary.estimated_tops, summary.receipts_per_second);
+            None
+        } else {
+            // Occasionally keep the whole output so disputes can be settled from the receipt;
+            // a persistent kernel's output never leaves the device
+            let evidence_hash_hex = if !out.y1.is_empty() && evidence_policy.should_sample() {
+                match evidence.store(epoch.epoch_id, attempt_nonce, &out.sizes, &out.y1) {
+                    Ok(entry) => {
+                        prometheus_metrics.record_evidence(evidence.stored_bytes());
+                        log_info!("[evidence] stored output of epoch {} nonce {} ({} bytes)", epoch.epoch_id, attempt_nonce, entry.stored_bytes);
+                        Some(entry.output_hash_hex)
+                    }
+                    Err(e) => {
+                        log_warn!("[evidence] could not store output of nonce {}: {}", attempt_nonce, e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            // Attempts are shared across identities by weight; the submitter signs with the matching key.
+            // The key epoch is fixed here, so a rotation mid-submission still signs with the old key.
+            let identity = keyring.next();
+            let device_did = identity.device_did.clone();
+            let key_epoch = Some(identity.key_epoch()).filter(|&epoch| epoch > 0);
+            // Without a persisted sequence number the receipt could repeat one, so it is not sent
+            let stamp = match sequencer.next(&device_did) {
+                Ok(stamp) => stamp,
+                Err(e) => {
+                    error_handler.handle_validation_error(&format!("could not reserve a receipt sequence number: {}", e));
+                    continue;
+                }
+            };
+
+            // A driver that reports completion early (or late) skews time_ms; let the aggregator know
+            let timing_confidence = out.phases.timing_confidence(config.timing_drift_pct);
+            prometheus_metrics.record_timing_confidence(timing_confidence);
+            if timing_confidence == TimingConfidence::Drift {
+                log_warn!("[timing] nonce {}: wall-clock kernel time {:.2} ms but the device timed {:.2} ms, receipt flagged",
+                    attempt_nonce, out.phases.kernel_ms, out.phases.device_kernel_ms.unwrap_or_default());
             }
-        };
-        receipt.sig_hex = sig;
 
-        // Submit to aggregator with retry logic
-        let url = config.aggregator_url.clone();
-        let client = reqwest::Client::new();
+            let receipt = WorkReceipt {
+                receipt_version: RECEIPT_VERSION_V1,
+                device_did: device_did.clone(),
+                epoch_id: epoch.epoch_id,
+                prev_hash_hex: challenge.as_ref().map_or_else(|| epoch.prev_hash_hex(), |answer| answer.prev_hash_hex()),
+                nonce: attempt_nonce,
+                work_root_hex: work_root_hex.clone(),
+                sizes: out.sizes.clone(),
+                time_ms: out.elapsed_ms,
+                kernel_ver: match (&assist_kernel_ver, assisted) {
+                    (Some(cpu_kernel_ver), true) => cpu_kernel_ver.clone(),
+                    _ => kernel_ver.clone(),
+                },
+                driver_hint: match (&assist_device_info, assisted) {
+                    (Some(cpu), true) => cpu.backend.clone(),
+                    _ => device_info.backend.clone(),
+                },
+                device_info: match (&assist_device_info, assisted) {
+                    (Some(cpu), true) => Some(cpu.clone()),
+                    _ => Some(device_info.clone()),
+                },
+                epoch_salt_hex: epoch.salt_hex(),
+                evidence_hash_hex,
+                key_epoch,
+                issued_at_ms: Some(stamp.issued_at_ms),
+                seq: Some(stamp.seq),
+                network_id: config.network_id.clone(),
+                requant: requant.receipt_field(epoch.salt.as_ref()),
+                timing_confidence: Some(timing_confidence),
+                hash_kind: epoch.hash_kind.receipt_field(),
+                // Millijoule resolution is all the sensors give
+                energy_estimate_j: energy_j.filter(|_| config.receipt_energy_estimate).map(|j| (j * 1000.0).round() / 1000.0),
+                work_sampling: config.work_root_sampling.receipt_field(),
+                perf_context: config.receipt_perf_context.then(|| PerfContext::new(
+                    config.attempts_in_flight + usize::from(assist_device_info.is_some()),
+                    config.pipeline_depth,
+                    thermal_limit.zip(metrics.gpu_temperature()).is_some_and(|(limit, celsius)| celsius >= limit),
+                    power_controller.as_ref().map(|power| power.state()).as_ref(),
+                )),
+                sig_hex: String::new(),
+            };
+            // Ties this attempt's log lines together in /logs
+            let attempt_id = idempotency_key(&receipt);
+
+            // debug: print full receipt if needed
+            if config.worker_debug_receipt {
+                log_info!(attempt: &attempt_id, "Receipt: {:?}", receipt);
+            }
+            if let Some(journal) = &journal {
+                if let Err(e) = journal.append(&JournalEntry::new(&receipt, &out)) {
+                    log_warn!(attempt: &attempt_id, "[journal] could not record nonce {}: {}", attempt_nonce, e);
+                }
+            }
         
-        let submission_result = client.post(&url).json(&receipt).send().await;
+            // Sign and deliver; the transport picks the receipt encoding
+            loop_state.set_stage(LoopStage::Submitting);
+            let submission = match submitter.submit(receipt.clone()).await {
+                Ok(submission) => submission,
+                Err(e @ SubmitError::Signing(_)) => {
+                    error_handler.handle_signature_error(&e.to_string());
+                    continue;
+                }
+                Err(e @ SubmitError::SignerUnavailable(_)) => {
+                    error_handler.handle_network_error(&e.to_string());
+                    continue;
+                }
+                Err(e @ (SubmitError::Encoding(_) | SubmitError::Queue(_))) => {
+                    error_handler.handle_validation_error(&e.to_string());
+                    continue;
+                }
+                // Already logged and counted by the submitter
+                Err(SubmitError::Duplicate(_)) => continue,
+                Err(e @ SubmitError::NoEndpoint) => return Err(e.into()),
+            };
+            pacer.on_receipt();
+            prometheus_metrics.set_queue_depth(submitter.pending());
+            if let Some(stats) = &submission.compression {
+                metrics.record_compression(stats);
+                prometheus_metrics.record_compression(stats);
+            }
+            let failure = submission.failure_kind();
+            if let Some(exporter) = &exporter {
+                if let Err(e) = exporter.record(&ExportRow::new(&receipt, &submission)) {
+                    log_warn!(attempt: &attempt_id, "[export] could not record nonce {}: {}", attempt_nonce, e);
+                }
+            }
+            if let Some(entry) = SubmitFailureEntry::new(&receipt, &submission) {
+                prometheus_metrics.record_submit_failure(&entry.failure.to_string());
+                if let Some(journal) = &journal {
+                    if let Err(e) = journal.append_failure(&entry) {
+                        log_error!(attempt: &attempt_id, "[journal] could not record the failed submission of nonce {}: {}", attempt_nonce, e);
+                    }
+                }
+            }
+            let target = submission.target;
+            let latency = submission.latency;
+            let response = submission.response;
+            let outcome_label = match &submission.outcome {
+                SubmitOutcome::Accepted { .. } => "accepted",
+                SubmitOutcome::Queued => "queued",
+                SubmitOutcome::Throttled { .. } => "throttled",
+                SubmitOutcome::Rejected { .. } => "rejected",
+                SubmitOutcome::Failed { .. } => "failed",
+            };
+            prometheus_metrics.record_identity_receipt(&device_did, outcome_label);
+            loop_state.record_submission(outcome_label);
+            if let Some(answer) = challenge.take() {
+                answer.finish(outcome_label == "accepted");
+            }
         
-        match submission_result {
-            Ok(resp) => {
-                let status = resp.status();
-                let body = resp.text().await.unwrap_or_default();
-                
-                if status.is_success() {
+            match submission.outcome {
+                SubmitOutcome::Accepted { body } => {
                     // Record successful attempt
+                    metrics.record_attempt(out.elapsed_ms, true);
+                    metrics.record_epoch_receipt(latency);
+                    prometheus_metrics.record_attempt(out.elapsed_ms, true);
+                    let rate = rate_controller.on_success();
+                    rate_limiter.set_refill_rate(rate);
+                    prometheus_metrics.set_effective_rate(rate);
+                    log_info!(attempt: &attempt_id, "submit ok ({}): {}", target, body);
+                    log_info!(attempt: &attempt_id, "ok nonce={} ms={} work_root={}", attempt_nonce, out.elapsed_ms, work_root_hex);
+                }
+                SubmitOutcome::Queued => {
                     metrics.record_attempt(out.elapsed_ms, true);
                     prometheus_metrics.record_attempt(out.elapsed_ms, true);
-                    println!("submit ok ({}): {}", url, body);
-                    println!("ok nonce={} ms={} work_root={}", nonce, out.elapsed_ms, work_root_hex);
-                } else {
+                    log_info!(attempt: &attempt_id, "queued nonce={} ms={} work_root={} for {} ({} pending)",
+                        attempt_nonce, out.elapsed_ms, work_root_hex, target, submitter.pending());
+                }
+                SubmitOutcome::Throttled { status, body, retry_after } => {
+                    metrics.record_attempt(out.elapsed_ms, false);
+                    prometheus_metrics.record_attempt(out.elapsed_ms, false);
+                    error_handler.handle_network_error(&format!("HTTP {}: {}", status, body));
+                    log_error!(attempt: &attempt_id, "submit failed ({}): {}", status, body);
+                    let rate = rate_controller.on_throttle(retry_after);
+                    rate_limiter.set_refill_rate(rate);
+                    prometheus_metrics.set_effective_rate(rate);
+                    log_warn!("[rate] aggregator throttled ({}), effective rate now {:.2}/s", status, rate);
+                }
+                // The aggregator already has it, from an earlier resend: nothing to quarantine
+                SubmitOutcome::Rejected { status, .. } if failure == Some(FailureKind::Duplicate) => {
+                    metrics.record_attempt(out.elapsed_ms, true);
+                    prometheus_metrics.record_attempt(out.elapsed_ms, true);
+                    log_info!(attempt: &attempt_id, "duplicate nonce={} ms={} work_root={}: {} already has it ({})", attempt_nonce, out.elapsed_ms, work_root_hex, target, status);
+                }
+                SubmitOutcome::Rejected { status, body } => {
                     // Record failed attempt
                     metrics.record_attempt(out.elapsed_ms, false);
                     prometheus_metrics.record_attempt(out.elapsed_ms, false);
                     error_handler.handle_network_error(&format!("HTTP {}: {}", status, body));
-                    eprintln!("submit failed ({}): {}", status, body);
+                    log_error!(attempt: &attempt_id, "submit failed ({}): {}", status, body);
+                    let reason = response.as_ref().and_then(|r| r.reason);
+                    prometheus_metrics.record_rejection(&reason.map_or("unspecified".to_string(), |r| r.to_string()));
+                    if let Err(e) = quarantine.store(&QuarantinedReceipt::new(receipt, &target, status, &body, response.as_ref())) {
+                        log_warn!(attempt: &attempt_id, "[quarantine] could not keep rejected nonce {}: {}", attempt_nonce, e);
+                    }
+                    // A rate rejection is throttling by another name
+                    if reason == Some(RejectReason::Rate) {
+                        let rate = rate_controller.on_throttle(None);
+                        rate_limiter.set_refill_rate(rate);
+                        prometheus_metrics.set_effective_rate(rate);
+                        log_warn!("[rate] aggregator rejected for rate, effective rate now {:.2}/s", rate);
+                    }
+                }
+                SubmitOutcome::Failed { kind, error } => {
+                    // Record failed attempt
+                    metrics.record_attempt(out.elapsed_ms, false);
+                    prometheus_metrics.record_attempt(out.elapsed_ms, false);
+                    error_handler.handle_network_error(&format!("Network error ({}): {}", kind, error));
+                    log_error!(attempt: &attempt_id, "submit failed ({}): {}: {}", target, kind.explain(), error);
                 }
             }
-            Err(e) => {
-                // Record failed attempt
-                metrics.record_attempt(out.elapsed_ms, false);
-                prometheus_metrics.record_attempt(out.elapsed_ms, false);
-                error_handler.handle_network_error(&format!("Network error: {}", e));
-                eprintln!("submit failed: {}", e);
+            response
+        };
+
+        // Follow the aggregator's feedback: its preferred rate and the hash to chain from
+        let mut next_epoch = None;
+        if let Some(response) = &response {
+            if response.request_evidence == Some(true) {
+                evidence_policy.request();
+            }
+            if let Some(next) = &response.challenge {
+                challenges.push(next.clone());
+            }
+            if let Some(suggested) = response.suggested_rate {
+                let rate = rate_controller.on_suggested_rate(suggested);
+                rate_limiter.set_refill_rate(rate);
+                prometheus_metrics.set_effective_rate(rate);
+            }
+            next_epoch = epoch.after_response(response).map(|next| (next, EpochSource::Response));
+        }
+        // The epoch feed only counts when the verdict did not already move us
+        if let Some(feed) = epoch_feed.as_mut().filter(|_| next_epoch.is_none()) {
+            if feed.has_changed().unwrap_or(false) {
+                let announced = feed.borrow_and_update().clone();
+                next_epoch = announced.and_then(|info| epoch.after_feed(&info)).map(|next| (next, EpochSource::Feed));
             }
         }
 
+        // Epoch transition: the attempt above is done; swap every epoch parameter at once
+        if let Some((next, source)) = next_epoch {
+            drop(streams);
+            let retune = epoch.needs_retune(&next);
+            if next.prev_hash != epoch.prev_hash {
+                // Nonces are only unique per prev_hash, so the new chain starts over
+                highest_nonce = 0;
+            }
+            let finished = metrics.begin_epoch(next.epoch_id);
+            prometheus_metrics.record_epoch_transition(&source.to_string(), next.epoch_id);
+            log_info!("[epoch] transition ({}): epoch {} -> {}, prev_hash {}{}; epoch {} ran {}s, {} attempt(s), {} successful",
+                source, epoch.epoch_id, next.epoch_id, next.prev_hash_hex(),
+                if next.salt != epoch.salt { ", new salt" } else { "" },
+                finished.epoch_id, finished.duration_seconds, finished.attempts, finished.successful_attempts);
+            if !config.watch_only {
+                submit_epoch_summary(&submitter, &keyring, config.network_id.clone(), &finished, &prometheus_metrics);
+            }
+            if next.hash_kind != epoch.hash_kind {
+                log_info!("[epoch] work_root hash {} -> {}", epoch.hash_kind, next.hash_kind);
+            }
+            epoch = next;
+            requant = config.get_requant(epoch.requant);
+            if retune {
+                memhard = config.get_memhard(epoch.memhard_kib);
+                kernel_ver = kernel_ver_for(workload, memhard.as_ref(), &*executor);
+                assist_kernel_ver = backends.assist.as_ref().map(|cpu| kernel_ver_for(workload, memhard.as_ref(), &**cpu));
+                min_tops_seconds = epoch.min_tops_seconds.or(config.min_tops_seconds);
+                log_info!("[epoch] work parameters changed, workload now {}", kernel_ver);
+                sizes = epoch_sizes(&*executor, &config, workload, memhard.as_ref(), &epoch, min_tops_seconds, tariff.target_ms)?;
+                drift = DriftMonitor::new(config.autotune_retune_drift_pct);
+            }
+            streams = restart_streams(&config, &epoch, &backends, workload, memhard, requant, highest_nonce.wrapping_add(1), &sizes, device_sampling);
+        }
+
         // Print periodic status
-        if nonce % 100 == 0 {
+        if !challenged && nonce.is_multiple_of(100) {
             let current_metrics = metrics.get_metrics();
             let health_status = metrics.get_health_status();
-            println!("[status] nonce={}, attempts={}, success_rate={:.2}%, avg_time={:.1}ms, health={}", 
+            log_info!("[status] nonce={}, attempts={}, success_rate={:.2}%, avg_time={:.1}ms, health={}", 
                 nonce, 
                 current_metrics.total_attempts,
                 if current_metrics.total_attempts > 0 { 
@@ -297,7 +2220,67 @@ async fn main() -> anyhow::Result<()> {
             );
         }
 
-        // Backoff a hair to keep the loop friendly; adjust or remove for pure PoW
-        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        // Re-tune once the device has settled well below its post-tuning speed (drawn sizes
+        // vary attempt to attempt, so there is no speed to drift from; the CPU stream is not the device)
+        if !config.autotune_disable && epoch.size_distribution.is_none() && !assisted && !challenged && drift.observe(out.elapsed_ms) {
+            log_info!("[autotune] attempts are {}%+ slower than after tuning, re-tuning", config.autotune_retune_drift_pct);
+            drop(streams);
+            sizes = choose_sizes(&*executor, &config, workload, memhard.as_ref(), &epoch.prev_hash, min_tops_seconds, tariff.target_ms)?;
+            drift = DriftMonitor::new(config.autotune_retune_drift_pct);
+            streams = restart_streams(&config, &epoch, &backends, workload, memhard, requant, highest_nonce.wrapping_add(1), &sizes, device_sampling);
+        }
+
+        // Stretch the loop to the lower of the power policy's and the tariff window's duty cycle
+        let duty = power_controller.as_ref().map_or(1.0, |power| power.state().duty_cycle).min(tariff.duty_cycle);
+        let idle = power::idle_for(duty, std::time::Duration::from_millis(out.elapsed_ms));
+        if !idle.is_zero() {
+            tokio::select! {
+                _ = tokio::time::sleep(idle) => {}
+                _ = shutdown.wait() => {}
+            }
+            if let Some(meter) = &energy { meter.skip(); }
+        }
+    };
+
+    // Drain: attempts still in the streams are discarded, everything submitted stays submitted
+    shutdown.arm_drain_deadline(config.get_drain_timeout());
+    heartbeat.set_idle(true);
+    loop_state.set_stage(LoopStage::Stopping);
+    drop(streams);
+    #[cfg(feature = "stats")]
+    if let Some(Err(e)) = stats.as_ref().map(|store| store.flush()) {
+        log_warn!("[stats] could not write statistics: {}", e);
+    }
+    if let Some(count) = once {
+        // Parked receipts have no later attempt to carry them out; send what the aggregator takes now
+        while submitter.pending() > 0 && submitter.drain_one().await {}
+        let checkpoint = NonceCheckpoint::new(epoch.prev_hash_hex(), highest_nonce);
+        if let Err(e) = checkpoint.save(config.get_nonce_checkpoint_path()) {
+            log_warn!("[once] could not record the nonces used: {}", e);
+        }
+        log_info!("[once] {} of {} attempt(s) done, highest nonce {}", attempts_done, count, highest_nonce);
+    }
+    let pending = submitter.pending();
+    if lifecycle::json() {
+        let totals = metrics.get_metrics();
+        let mut event = ShutdownEvent::new(exit_reason);
+        event.uptime_seconds = Some(totals.uptime_seconds);
+        event.total_attempts = Some(totals.total_attempts);
+        event.successful_attempts = Some(totals.successful_attempts);
+        event.highest_nonce = Some(highest_nonce);
+        event.pending_receipts = Some(pending);
+        lifecycle::emit(&event);
+    } else {
+        if pending > 0 {
+            log_info!("[shutdown] {} receipt(s) stay queued on disk for the next start", pending);
+        }
+        log_info!("[shutdown] drained after nonce {}, exiting with code {} ({})", highest_nonce, exit_reason.code(), exit_reason);
+    }
+    // Background deliveries may still hold the exporter; complete its part now
+    if let Some(exporter) = &exporter {
+        if let Err(e) = exporter.close() {
+            log_warn!("[export] could not complete the last part: {}", e);
+        }
     }
+    Ok(exit_reason)
 }
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use memmap2::Mmap;
+use crate::prng::derive_salted_seed;
+use crate::sparse::CsrMatrix;
+use crate::types::Sizes;
+use crate::workload::{generate_workload_inputs, ProofWorkload, Workload, WorkloadInput};
+use crate::log_warn;
+
+const MAGIC: &[u8; 4] = b"TWMC";
+const VERSION: u8 = 1;
+// Magic, version, kind, two bytes of padding, nnz as u64 LE
+const HEADER_BYTES: usize = 16;
+const EXTENSION: &str = "mat";
+
+/// Generated inputs of recent (seed, sizes), kept in files under `MATRIX_CACHE_MAX_MB`
+/// and read back through a memory map.
+///
+/// Verifying a receipt or cross-checking a backend regenerates the same matrices
+/// over and over; copying them out of the page cache is far cheaper than running
+/// the PRNG again. The least recently used files are removed to stay within budget,
+/// and an entry that cannot be read back is simply generated again.
+pub struct MatrixCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<Index>,
+}
+
+#[derive(Default)]
+struct Index {
+    entries: HashMap<String, Entry>,
+    total_bytes: u64,
+    tick: u64,
+}
+
+struct Entry {
+    bytes: u64,
+    last_used: u64,
+}
+
+static GLOBAL: OnceLock<MatrixCache> = OnceLock::new();
+
+/// Make `cache` the one `generate_cached` uses. Returns false if one is already installed.
+pub fn install(cache: MatrixCache) -> bool {
+    GLOBAL.set(cache).is_ok()
+}
+
+/// `generate_workload_inputs`, served from the installed cache when there is one.
+pub fn generate_cached(workload: Workload, prev_hash_bytes: &[u8;32], nonce: u32, salt: Option<&[u8;32]>, sizes: &Sizes) -> WorkloadInput {
+    match GLOBAL.get() {
+        Some(cache) => cache.get_or_generate(workload, prev_hash_bytes, nonce, salt, sizes),
+        None => generate_workload_inputs(workload, prev_hash_bytes, nonce, salt, sizes),
+    }
+}
+
+impl MatrixCache {
+    /// Open (or create) the cache in `dir`, picking up the files already there
+    /// oldest-first and trimming them to `max_bytes`.
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> std::io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut found = Vec::new();
+        for dir_entry in fs::read_dir(&dir)? {
+            let path = dir_entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            let (Some(key), Ok(meta)) = (path.file_stem().and_then(|s| s.to_str()), fs::metadata(&path)) else { continue };
+            found.push((meta.modified().ok(), key.to_string(), meta.len()));
+        }
+        found.sort();
+        let mut index = Index::default();
+        for (_, key, bytes) in found {
+            index.tick += 1;
+            index.total_bytes += bytes;
+            index.entries.insert(key, Entry { bytes, last_used: index.tick });
+        }
+        let cache = Self { dir, max_bytes, index: Mutex::new(index) };
+        if let Ok(mut index) = cache.index.lock() {
+            cache.evict(&mut index);
+        }
+        Ok(cache)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Entries and bytes currently cached.
+    pub fn usage(&self) -> (usize, u64) {
+        self.index.lock().map(|index| (index.entries.len(), index.total_bytes)).unwrap_or((0, 0))
+    }
+
+    /// The inputs for (prev_hash, nonce, salt) at `sizes`, read from the cache or
+    /// generated and stored.
+    pub fn get_or_generate(&self, workload: Workload, prev_hash_bytes: &[u8;32], nonce: u32, salt: Option<&[u8;32]>, sizes: &Sizes) -> WorkloadInput {
+        let seed = derive_salted_seed(prev_hash_bytes, nonce, salt);
+        let key = cache_key(&seed, workload, sizes);
+        if self.touch(&key) {
+            match self.read(&key, workload, sizes) {
+                Ok(input) => return input,
+                Err(e) => {
+                    log_warn!("[matrix-cache] dropping unreadable entry {}: {}", key, e);
+                    self.remove(&key);
+                }
+            }
+        }
+        let input = generate_workload_inputs(workload, prev_hash_bytes, nonce, salt, sizes);
+        if let Err(e) = self.store(&key, &input) {
+            log_warn!("[matrix-cache] could not store entry {}: {}", key, e);
+        }
+        input
+    }
+
+    // Mark `key` as just used; false when it is not cached
+    fn touch(&self, key: &str) -> bool {
+        let Ok(mut index) = self.index.lock() else { return false };
+        index.tick += 1;
+        let tick = index.tick;
+        match index.entries.get_mut(key) {
+            Some(entry) => {
+                entry.last_used = tick;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", key, EXTENSION))
+    }
+
+    fn read(&self, key: &str, workload: Workload, sizes: &Sizes) -> anyhow::Result<WorkloadInput> {
+        let file = File::open(self.path(key))?;
+        // SAFETY: the cache only ever replaces files through a rename, never
+        // rewrites them in place, so the mapped bytes do not change under us
+        let map = unsafe { Mmap::map(&file)? };
+        decode(&map, workload, sizes)
+    }
+
+    fn store(&self, key: &str, input: &WorkloadInput) -> std::io::Result<()> {
+        let bytes = encode(input);
+        if bytes.len() as u64 > self.max_bytes {
+            return Ok(());
+        }
+        // Written aside and renamed, so a reader never maps a partial file
+        let tmp = self.dir.join(format!("{}.tmp", key));
+        let mut file = File::create(&tmp)?;
+        file.write_all(&bytes)?;
+        fs::rename(&tmp, self.path(key))?;
+
+        let Ok(mut index) = self.index.lock() else { return Ok(()) };
+        index.tick += 1;
+        let last_used = index.tick;
+        if let Some(previous) = index.entries.insert(key.to_string(), Entry { bytes: bytes.len() as u64, last_used }) {
+            index.total_bytes -= previous.bytes;
+        }
+        index.total_bytes += bytes.len() as u64;
+        self.evict(&mut index);
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = fs::remove_file(self.path(key));
+        if let Ok(mut index) = self.index.lock() {
+            if let Some(entry) = index.entries.remove(key) {
+                index.total_bytes -= entry.bytes;
+            }
+        }
+    }
+
+    // Remove least recently used entries until the cache is within budget
+    fn evict(&self, index: &mut Index) {
+        while index.total_bytes > self.max_bytes {
+            let Some(oldest) = index.entries.iter().min_by_key(|(_, e)| e.last_used).map(|(k, _)| k.clone()) else { break };
+            if let Some(entry) = index.entries.remove(&oldest) {
+                index.total_bytes -= entry.bytes;
+            }
+            let _ = fs::remove_file(self.path(&oldest));
+        }
+    }
+}
+
+// The seed already covers prev_hash, nonce and salt; the workload and sizes decide the layout
+fn cache_key(seed: &[u8;16], workload: Workload, sizes: &Sizes) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"tops-worker/matrix-cache/v1");
+    hasher.update(seed);
+    hasher.update(workload.kernel_ver().as_bytes());
+    for side in [sizes.m, sizes.n, sizes.k] {
+        hasher.update(&(side as u64).to_le_bytes());
+    }
+    // Unbatched keys predate batching and stay as they were
+    if sizes.batch > 1 {
+        hasher.update(&(sizes.batch as u64).to_le_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+fn encode(input: &WorkloadInput) -> Vec<u8> {
+    let (kind, nnz) = match input {
+        WorkloadInput::Dense { .. } => (0u8, 0u64),
+        WorkloadInput::Sparse { a, .. } => (1u8, a.nnz() as u64),
+    };
+    let mut bytes = Vec::with_capacity(HEADER_BYTES);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&[VERSION, kind, 0, 0]);
+    bytes.extend_from_slice(&nnz.to_le_bytes());
+    match input {
+        WorkloadInput::Dense { a, b } => {
+            bytes.extend(a.iter().map(|&v| v as u8));
+            bytes.extend(b.iter().map(|&v| v as u8));
+        }
+        WorkloadInput::Sparse { a, b } => {
+            bytes.extend(a.row_ptr.iter().flat_map(|v| v.to_le_bytes()));
+            bytes.extend(a.col_idx.iter().flat_map(|v| v.to_le_bytes()));
+            bytes.extend(a.values.iter().map(|&v| v as u8));
+            bytes.extend(b.iter().map(|&v| v as u8));
+        }
+    }
+    bytes
+}
+
+fn decode(bytes: &[u8], workload: Workload, sizes: &Sizes) -> anyhow::Result<WorkloadInput> {
+    if bytes.len() < HEADER_BYTES || &bytes[..4] != MAGIC || bytes[4] != VERSION {
+        anyhow::bail!("not a version {} cache file", VERSION);
+    }
+    let nnz = u64::from_le_bytes(bytes[8..16].try_into()?) as usize;
+    let mut body = Reader { bytes: &bytes[HEADER_BYTES..] };
+    let input = match (workload, bytes[5]) {
+        (Workload::Gemm, 0) => WorkloadInput::Dense {
+            a: body.i8s(sizes.batch.max(1) * sizes.m * sizes.k)?,
+            b: body.i8s(sizes.batch.max(1) * sizes.k * sizes.n)?,
+        },
+        (Workload::Spmm { .. }, 1) => {
+            let row_ptr = body.u32s(sizes.m + 1)?;
+            let col_idx = body.u32s(nnz)?;
+            let values = body.i8s(nnz)?;
+            let a = CsrMatrix { rows: sizes.m, cols: sizes.k, row_ptr, col_idx, values };
+            WorkloadInput::Sparse { a, b: body.i8s(sizes.k * sizes.n)? }
+        }
+        (_, kind) => anyhow::bail!("cached kind {} does not match the {} workload", kind, workload.kind()),
+    };
+    if !body.bytes.is_empty() {
+        anyhow::bail!("{} trailing bytes", body.bytes.len());
+    }
+    Ok(input)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&[u8]> {
+        if self.bytes.len() < len {
+            anyhow::bail!("truncated: {} bytes left, {} needed", self.bytes.len(), len);
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn i8s(&mut self, len: usize) -> anyhow::Result<Vec<i8>> {
+        Ok(self.take(len)?.iter().map(|&v| v as i8).collect())
+    }
+
+    fn u32s(&mut self, len: usize) -> anyhow::Result<Vec<u32>> {
+        Ok(self.take(len * 4)?.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect())
+    }
+}
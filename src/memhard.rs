@@ -0,0 +1,10 @@
+use crate::attempt::Executor;
+
+// The stage's parameters and reference ROMix are shared with the receipt verification core
+pub use tops_verify_core::memhard::{finalize, initial_block, romix, MemHardParams, MEMHARD_BLOCK_WORDS, MEMHARD_MAX_KIB, MEMHARD_MAX_PASSES};
+
+/// Run the stage for `seed` on `executor` and return its digest.
+pub fn run_memhard_stage<E: Executor + ?Sized>(executor: &E, seed: &[u8; 16], params: &MemHardParams) -> anyhow::Result<[u8; 32]> {
+    let block = executor.run_memhard(&initial_block(seed), params)?;
+    Ok(finalize(seed, &block))
+}
@@ -1,7 +1,14 @@
-use std::sync::atomic::{AtomicU64, AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU32, Ordering};
 use std::sync::Arc;
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 use serde::{Deserialize, Serialize};
+use crate::health_policy::{HealthPolicy, HealthSample};
+
+/// Health stays Degraded this long after a spot-check mismatch.
+const CORRUPTION_DEGRADED_FOR: Duration = Duration::from_secs(600);
+/// Back-to-back corrupted attempts that make health Unhealthy.
+const CORRUPTION_UNHEALTHY_STREAK: u32 = 3;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metrics {
@@ -10,11 +17,14 @@ pub struct Metrics {
     pub successful_attempts: u64,
     pub failed_attempts: u64,
     pub average_time_ms: f64,
+    pub total_time_ms: u64,
     pub min_time_ms: u64,
     pub max_time_ms: u64,
     
     // Error metrics
-    pub gpu_errors: u64,
+    /// Compute backend errors, GPU or CPU; `gpu_errors` before schema version 2.
+    #[serde(alias = "gpu_errors")]
+    pub device_errors: u64,
     pub network_errors: u64,
     pub signature_errors: u64,
     pub validation_errors: u64,
@@ -23,10 +33,58 @@ pub struct Metrics {
     pub uptime_seconds: u64,
     pub last_successful_attempt: Option<u64>,
     pub consecutive_failures: u32,
+    /// Failed share of the attempts in the health policy's window.
+    pub window_failure_rate: f64,
+    /// Latest reading of the hottest GPU, when the health policy watches temperature.
+    pub gpu_temperature_c: Option<f64>,
+    
+    // Correctness self-test
+    pub selftest_failures: u64,
+    pub selftest_failing: bool,
+    
+    // Per-attempt CPU spot-check of the output
+    pub silent_corruptions: u64,
+    pub consecutive_corruptions: u32,
+    
+    // Submission body compression
+    pub compressed_submissions: u64,
+    pub submit_bytes_saved: u64,
     
     // Throughput metrics
     pub attempts_per_second: f64,
     pub receipts_per_second: f64,
+    
+    // Per attempt-stream breakdown (ATTEMPTS_IN_FLIGHT)
+    pub streams: Vec<StreamMetrics>,
+    
+    // Counters of the current epoch, reset on every transition
+    pub epoch: EpochStats,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EpochStats {
+    pub epoch_id: u64,
+    pub transitions: u64,
+    pub duration_seconds: u64,
+    pub attempts: u64,
+    pub successful_attempts: u64,
+    pub failed_attempts: u64,
+    pub accepted_receipts: u64,
+    pub average_time_ms: f64,
+    /// Mean submission latency of the accepted receipts.
+    pub average_submit_latency_ms: f64,
+    /// Energy over the epoch's attempts; unset without an energy meter.
+    pub energy_joules: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamMetrics {
+    pub stream: usize,
+    /// Backend the stream runs on (`device_info.backend`).
+    pub backend: String,
+    pub attempts: u64,
+    pub average_time_ms: f64,
+    pub last_time_ms: u64,
 }
 
 #[derive(Debug)]
@@ -40,10 +98,37 @@ pub struct MetricsCollector {
     signature_errors: AtomicU64,
     validation_errors: AtomicU64,
     consecutive_failures: AtomicU32,
+    selftest_failures: AtomicU64,
+    selftest_failing: AtomicBool,
+    silent_corruptions: AtomicU64,
+    consecutive_corruptions: AtomicU32,
+    last_corruption: std::sync::Mutex<Option<Instant>>,
+    compressed_submissions: AtomicU64,
+    submit_bytes_saved: AtomicU64,
+    
+    // Health classification and the recent outcomes it measures the failure rate over
+    health_policy: HealthPolicy,
+    recent_outcomes: std::sync::Mutex<VecDeque<bool>>,
+    gpu_temperature_c: std::sync::Mutex<Option<f64>>,
     
     // Timing data
     start_time: Instant,
     last_success_time: Arc<std::sync::Mutex<Option<Instant>>>,
+    streams: std::sync::Mutex<Vec<StreamMetrics>>,
+    
+    // Current epoch
+    epoch_id: AtomicU64,
+    epoch_transitions: AtomicU64,
+    epoch_start: std::sync::Mutex<Instant>,
+    epoch_attempts: AtomicU64,
+    epoch_successful: AtomicU64,
+    epoch_failed: AtomicU64,
+    epoch_time_ms: AtomicU64,
+    epoch_accepted: AtomicU64,
+    epoch_submit_latency_ms: AtomicU64,
+    // Millijoules, so the total fits an atomic
+    epoch_energy_mj: AtomicU64,
+    epoch_energy_metered: AtomicBool,
     
     // Performance tracking
     total_time_ms: AtomicU64,
@@ -52,6 +137,12 @@ pub struct MetricsCollector {
     attempt_count: AtomicU64,
 }
 
+impl Default for MetricsCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MetricsCollector {
     pub fn new() -> Self {
         Self {
@@ -63,8 +154,30 @@ impl MetricsCollector {
             signature_errors: AtomicU64::new(0),
             validation_errors: AtomicU64::new(0),
             consecutive_failures: AtomicU32::new(0),
+            selftest_failures: AtomicU64::new(0),
+            selftest_failing: AtomicBool::new(false),
+            silent_corruptions: AtomicU64::new(0),
+            consecutive_corruptions: AtomicU32::new(0),
+            last_corruption: std::sync::Mutex::new(None),
+            compressed_submissions: AtomicU64::new(0),
+            submit_bytes_saved: AtomicU64::new(0),
+            health_policy: HealthPolicy::default(),
+            recent_outcomes: std::sync::Mutex::new(VecDeque::new()),
+            gpu_temperature_c: std::sync::Mutex::new(None),
             start_time: Instant::now(),
             last_success_time: Arc::new(std::sync::Mutex::new(None)),
+            streams: std::sync::Mutex::new(Vec::new()),
+            epoch_id: AtomicU64::new(0),
+            epoch_transitions: AtomicU64::new(0),
+            epoch_start: std::sync::Mutex::new(Instant::now()),
+            epoch_attempts: AtomicU64::new(0),
+            epoch_successful: AtomicU64::new(0),
+            epoch_failed: AtomicU64::new(0),
+            epoch_time_ms: AtomicU64::new(0),
+            epoch_accepted: AtomicU64::new(0),
+            epoch_submit_latency_ms: AtomicU64::new(0),
+            epoch_energy_mj: AtomicU64::new(0),
+            epoch_energy_metered: AtomicBool::new(false),
             total_time_ms: AtomicU64::new(0),
             min_time_ms: AtomicU64::new(u64::MAX),
             max_time_ms: AtomicU64::new(0),
@@ -72,11 +185,23 @@ impl MetricsCollector {
         }
     }
     
+    pub fn with_health_policy(mut self, policy: HealthPolicy) -> Self {
+        self.health_policy = policy;
+        self
+    }
+    
+    pub fn health_policy(&self) -> &HealthPolicy {
+        &self.health_policy
+    }
+    
     pub fn record_attempt(&self, time_ms: u64, success: bool) {
         self.total_attempts.fetch_add(1, Ordering::Relaxed);
+        self.epoch_attempts.fetch_add(1, Ordering::Relaxed);
+        self.epoch_time_ms.fetch_add(time_ms, Ordering::Relaxed);
         
         if success {
             self.successful_attempts.fetch_add(1, Ordering::Relaxed);
+            self.epoch_successful.fetch_add(1, Ordering::Relaxed);
             self.consecutive_failures.store(0, Ordering::Relaxed);
             
             // Update last success time
@@ -85,8 +210,15 @@ impl MetricsCollector {
             }
         } else {
             self.failed_attempts.fetch_add(1, Ordering::Relaxed);
+            self.epoch_failed.fetch_add(1, Ordering::Relaxed);
             self.consecutive_failures.fetch_add(1, Ordering::Relaxed);
         }
+        if let Ok(mut recent) = self.recent_outcomes.lock() {
+            recent.push_back(success);
+            while recent.len() > self.health_policy.failure_window.max(1) {
+                recent.pop_front();
+            }
+        }
         
         // Update timing statistics
         self.total_time_ms.fetch_add(time_ms, Ordering::Relaxed);
@@ -123,6 +255,125 @@ impl MetricsCollector {
         };
     }
     
+    pub fn record_stream_attempt(&self, stream: usize, backend: &str, time_ms: u64) {
+        if let Ok(mut streams) = self.streams.lock() {
+            while streams.len() <= stream {
+                let next = streams.len();
+                streams.push(StreamMetrics { stream: next, ..Default::default() });
+            }
+            let s = &mut streams[stream];
+            if s.backend != backend {
+                s.backend = backend.to_string();
+            }
+            s.attempts += 1;
+            s.average_time_ms += (time_ms as f64 - s.average_time_ms) / s.attempts as f64;
+            s.last_time_ms = time_ms;
+        }
+    }
+    
+    /// Start counting `epoch_id` from zero and return the totals of the epoch that ended.
+    /// The first call (at startup) is not counted as a transition.
+    pub fn begin_epoch(&self, epoch_id: u64) -> EpochStats {
+        let finished = self.epoch_stats();
+        if let Ok(mut start) = self.epoch_start.lock() {
+            *start = Instant::now();
+        }
+        if self.epoch_id.swap(epoch_id, Ordering::Relaxed) != 0 {
+            self.epoch_transitions.fetch_add(1, Ordering::Relaxed);
+        }
+        self.epoch_attempts.store(0, Ordering::Relaxed);
+        self.epoch_successful.store(0, Ordering::Relaxed);
+        self.epoch_failed.store(0, Ordering::Relaxed);
+        self.epoch_time_ms.store(0, Ordering::Relaxed);
+        self.epoch_accepted.store(0, Ordering::Relaxed);
+        self.epoch_submit_latency_ms.store(0, Ordering::Relaxed);
+        self.epoch_energy_mj.store(0, Ordering::Relaxed);
+        self.epoch_energy_metered.store(false, Ordering::Relaxed);
+        finished
+    }
+    
+    /// An accepted receipt of the current epoch and how long the aggregator took to answer it.
+    pub fn record_epoch_receipt(&self, latency: Duration) {
+        self.epoch_accepted.fetch_add(1, Ordering::Relaxed);
+        self.epoch_submit_latency_ms.fetch_add(latency.as_millis() as u64, Ordering::Relaxed);
+    }
+    
+    /// Energy one attempt of the current epoch drew.
+    pub fn record_epoch_energy(&self, joules: f64) {
+        self.epoch_energy_mj.fetch_add((joules.max(0.0) * 1000.0).round() as u64, Ordering::Relaxed);
+        self.epoch_energy_metered.store(true, Ordering::Relaxed);
+    }
+    
+    fn epoch_stats(&self) -> EpochStats {
+        let attempts = self.epoch_attempts.load(Ordering::Relaxed);
+        let accepted = self.epoch_accepted.load(Ordering::Relaxed);
+        EpochStats {
+            epoch_id: self.epoch_id.load(Ordering::Relaxed),
+            transitions: self.epoch_transitions.load(Ordering::Relaxed),
+            duration_seconds: self.epoch_start.lock().map(|s| s.elapsed().as_secs()).unwrap_or(0),
+            attempts,
+            successful_attempts: self.epoch_successful.load(Ordering::Relaxed),
+            failed_attempts: self.epoch_failed.load(Ordering::Relaxed),
+            accepted_receipts: accepted,
+            average_time_ms: if attempts > 0 { self.epoch_time_ms.load(Ordering::Relaxed) as f64 / attempts as f64 } else { 0.0 },
+            average_submit_latency_ms: if accepted > 0 { self.epoch_submit_latency_ms.load(Ordering::Relaxed) as f64 / accepted as f64 } else { 0.0 },
+            energy_joules: self.epoch_energy_metered.load(Ordering::Relaxed)
+                .then(|| self.epoch_energy_mj.load(Ordering::Relaxed) as f64 / 1000.0),
+        }
+    }
+    
+    pub fn record_selftest(&self, passed: bool) {
+        if !passed {
+            self.selftest_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        self.selftest_failing.store(!passed, Ordering::Relaxed);
+    }
+    
+    /// Result of an attempt's output spot-check; a corrupted attempt also counts as failed.
+    pub fn record_spot_check(&self, passed: bool) {
+        if passed {
+            self.consecutive_corruptions.store(0, Ordering::Relaxed);
+            return;
+        }
+        self.silent_corruptions.fetch_add(1, Ordering::Relaxed);
+        self.consecutive_corruptions.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut last) = self.last_corruption.lock() {
+            *last = Some(Instant::now());
+        }
+    }
+    
+    fn recent_corruption(&self) -> bool {
+        self.last_corruption.lock()
+            .map(|last| last.is_some_and(|t| t.elapsed() < CORRUPTION_DEGRADED_FOR))
+            .unwrap_or(false)
+    }
+    
+    pub fn record_gpu_temperature(&self, celsius: Option<f64>) {
+        if let Ok(mut temperature) = self.gpu_temperature_c.lock() {
+            *temperature = celsius;
+        }
+    }
+    
+    fn window_failure_rate(&self) -> f64 {
+        self.recent_outcomes.lock()
+            .map(|recent| if recent.is_empty() {
+                0.0
+            } else {
+                recent.iter().filter(|&&ok| !ok).count() as f64 / recent.len() as f64
+            })
+            .unwrap_or(0.0)
+    }
+    
+    /// Latest GPU temperature, read only while a health threshold watches it.
+    pub fn gpu_temperature(&self) -> Option<f64> {
+        self.gpu_temperature_c.lock().map(|t| *t).unwrap_or(None)
+    }
+    
+    pub fn record_compression(&self, stats: &crate::compression::CompressionStats) {
+        self.compressed_submissions.fetch_add(1, Ordering::Relaxed);
+        self.submit_bytes_saved.fetch_add(stats.bytes_saved(), Ordering::Relaxed);
+    }
+    
     pub fn get_metrics(&self) -> Metrics {
         let total_attempts = self.total_attempts.load(Ordering::Relaxed);
         let successful_attempts = self.successful_attempts.load(Ordering::Relaxed);
@@ -164,40 +415,53 @@ impl MetricsCollector {
             successful_attempts,
             failed_attempts,
             average_time_ms,
+            total_time_ms,
             min_time_ms: if min_time_ms == u64::MAX { 0 } else { min_time_ms },
             max_time_ms,
-            gpu_errors: self.gpu_errors.load(Ordering::Relaxed),
+            device_errors: self.gpu_errors.load(Ordering::Relaxed),
             network_errors: self.network_errors.load(Ordering::Relaxed),
             signature_errors: self.signature_errors.load(Ordering::Relaxed),
             validation_errors: self.validation_errors.load(Ordering::Relaxed),
             uptime_seconds,
             last_successful_attempt,
             consecutive_failures,
+            window_failure_rate: self.window_failure_rate(),
+            gpu_temperature_c: self.gpu_temperature(),
+            selftest_failures: self.selftest_failures.load(Ordering::Relaxed),
+            selftest_failing: self.selftest_failing.load(Ordering::Relaxed),
+            silent_corruptions: self.silent_corruptions.load(Ordering::Relaxed),
+            consecutive_corruptions: self.consecutive_corruptions.load(Ordering::Relaxed),
+            compressed_submissions: self.compressed_submissions.load(Ordering::Relaxed),
+            submit_bytes_saved: self.submit_bytes_saved.load(Ordering::Relaxed),
             attempts_per_second,
             receipts_per_second,
+            streams: self.streams.lock().map(|s| s.clone()).unwrap_or_default(),
+            epoch: self.epoch_stats(),
         }
     }
     
     pub fn get_health_status(&self) -> HealthStatus {
-        let consecutive_failures = self.consecutive_failures.load(Ordering::Relaxed);
-        let total_attempts = self.total_attempts.load(Ordering::Relaxed);
-        let failed_attempts = self.failed_attempts.load(Ordering::Relaxed);
+        let consecutive_corruptions = self.consecutive_corruptions.load(Ordering::Relaxed);
         
-        let failure_rate = if total_attempts > 0 {
-            failed_attempts as f64 / total_attempts as f64
-        } else {
-            0.0
-        };
-        
-        if consecutive_failures >= 10 {
-            HealthStatus::Critical
-        } else if consecutive_failures >= 5 || failure_rate > 0.5 {
+        // Correctness signals are not part of the configurable policy
+        let floor = if consecutive_corruptions >= CORRUPTION_UNHEALTHY_STREAK {
             HealthStatus::Unhealthy
-        } else if consecutive_failures >= 2 || failure_rate > 0.2 {
+        } else if self.selftest_failing.load(Ordering::Relaxed) || self.recent_corruption() {
             HealthStatus::Degraded
         } else {
             HealthStatus::Healthy
-        }
+        };
+        let since_success = self.last_success_time.lock().ok()
+            .and_then(|last| last.map(|t| t.elapsed()))
+            .unwrap_or_else(|| self.start_time.elapsed());
+        
+        self.health_policy.classify(&HealthSample {
+            consecutive_failures: self.consecutive_failures.load(Ordering::Relaxed),
+            window_failure_rate: self.window_failure_rate(),
+            since_success,
+            gpu_temp_c: self.gpu_temperature(),
+            floor,
+        })
     }
 }
 
@@ -209,8 +473,10 @@ pub enum ErrorType {
     Validation,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Ordered from best to worst.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub enum HealthStatus {
+    #[default]
     Healthy,
     Degraded,
     Unhealthy,
@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::health::HealthChecker;
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::{log_error, log_info};
+
+// Exposition format the Pushgateway parses
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Pushes the `/prometheus` metrics to a Pushgateway (`METRICS_PUSH_URL`) every
+/// interval, for devices behind NAT that cannot be scraped on :8082. The pull
+/// endpoint keeps working alongside it.
+///
+/// Every push replaces the group `job/<METRICS_PUSH_JOB>/device_did/<DID>`, so a
+/// fleet shares one gateway with one series per device.
+pub struct MetricsPusher {
+    client: reqwest::Client,
+    url: reqwest::Url,
+    interval: Duration,
+    health: Arc<HealthChecker>,
+    prometheus: Arc<PrometheusMetrics>,
+}
+
+impl MetricsPusher {
+    pub fn new(
+        client: reqwest::Client,
+        gateway_url: &str,
+        job: &str,
+        device_did: &str,
+        interval: Duration,
+        health: Arc<HealthChecker>,
+        prometheus: Arc<PrometheusMetrics>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self { client, url: grouping_url(gateway_url, job, device_did)?, interval, health, prometheus })
+    }
+
+    pub fn url(&self) -> &reqwest::Url {
+        &self.url
+    }
+
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // Log the first failure of a streak and the recovery, not every push
+            let mut failing = false;
+            loop {
+                ticker.tick().await;
+                let result = self.push().await;
+                self.prometheus.record_metrics_push(result.is_ok());
+                match result {
+                    Ok(()) if failing => {
+                        log_info!("[metrics-push] pushing to {} again", self.url);
+                        failing = false;
+                    }
+                    Ok(()) => {}
+                    Err(e) if !failing => {
+                        log_error!("[metrics-push] push to {} failed, retrying every {}s: {}", self.url, self.interval.as_secs(), e);
+                        failing = true;
+                    }
+                    Err(_) => {}
+                }
+            }
+        })
+    }
+
+    async fn push(&self) -> anyhow::Result<()> {
+        // Same refresh as a scrape of /prometheus
+        self.prometheus.update_from_metrics(&self.health.get_metrics().metrics);
+        let body = self.prometheus.export_metrics().map_err(|e| anyhow::anyhow!("cannot encode metrics: {}", e))?;
+        let resp = self.client.put(self.url.clone())
+            .timeout(Duration::from_secs(10))
+            .header(reqwest::header::CONTENT_TYPE, TEXT_FORMAT)
+            .body(to_text_format(&body))
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            anyhow::bail!("HTTP {}", resp.status());
+        }
+        Ok(())
+    }
+}
+
+/// `<gateway>/metrics/job/<job>/device_did/<did>`, each label value percent-encoded.
+pub fn grouping_url(gateway_url: &str, job: &str, device_did: &str) -> anyhow::Result<reqwest::Url> {
+    let mut url = reqwest::Url::parse(gateway_url)?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow::anyhow!("{} cannot take a path", gateway_url))?
+        .pop_if_empty()
+        .extend(["metrics", "job", job, "device_did", device_did]);
+    Ok(url)
+}
+
+/// The registry's OpenMetrics text as the Pushgateway's text format: counter
+/// families named with their `_total` suffix, and no `# UNIT` or `# EOF` lines.
+pub fn to_text_format(openmetrics: &str) -> String {
+    let counters: HashSet<&str> = openmetrics.lines()
+        .filter_map(|line| line.strip_prefix("# TYPE "))
+        .filter_map(|rest| rest.strip_suffix(" counter"))
+        .collect();
+    let mut out = String::with_capacity(openmetrics.len());
+    for line in openmetrics.lines() {
+        if line == "# EOF" || line.starts_with("# UNIT ") {
+            continue;
+        }
+        let renamed = ["# HELP ", "# TYPE "].iter().find_map(|prefix| {
+            let rest = line.strip_prefix(prefix)?;
+            let (name, tail) = rest.split_once(' ')?;
+            counters.contains(name).then(|| format!("{}{}_total {}", prefix, name, tail))
+        });
+        out.push_str(renamed.as_deref().unwrap_or(line));
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,135 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// Version of the `/metrics` JSON layout. Responses without a `schema_version`
+/// are version 1.
+///
+/// Bump it whenever a field is renamed, retyped or removed; adding a field is
+/// compatible and needs no bump. A renamed field is listed in [`DEPRECATED`],
+/// keeps a `#[serde(alias)]` with its old name so older payloads still parse,
+/// and is served under both names until `removed_in`.
+pub const METRICS_SCHEMA_VERSION: u32 = 2;
+
+pub fn legacy_schema_version() -> u32 {
+    1
+}
+
+/// A field served under its old name alongside the new one.
+#[derive(Debug, Clone, Serialize)]
+pub struct Deprecation {
+    /// Dotted path of the old field, e.g. `metrics.gpu_errors`.
+    pub field: &'static str,
+    pub replaced_by: &'static str,
+    pub deprecated_in: u32,
+    /// First schema version that no longer serves the old name.
+    pub removed_in: u32,
+}
+
+pub const DEPRECATED: &[Deprecation] = &[
+    Deprecation { field: "metrics.gpu_errors", replaced_by: "metrics.device_errors", deprecated_in: 2, removed_in: 3 },
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub ty: &'static str,
+    pub description: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<&'static Deprecation>,
+}
+
+/// What `/metrics/schema` serves.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSchema {
+    pub schema_version: u32,
+    pub fields: Vec<FieldSchema>,
+}
+
+// Keep in step with `MetricsResponse` and `metrics::Metrics`
+const FIELDS: &[(&str, &str, &str)] = &[
+    ("schema_version", "u32", "Layout version of this response"),
+    ("health_status", "string", "healthy, degraded, unhealthy or critical"),
+    ("circuit_breaker_status", "string?", "State of the submission circuit breaker, when known"),
+    ("metrics.total_attempts", "u64", "Attempts made"),
+    ("metrics.successful_attempts", "u64", "Attempts that produced a receipt"),
+    ("metrics.failed_attempts", "u64", "Attempts that failed"),
+    ("metrics.average_time_ms", "f64", "Mean attempt time"),
+    ("metrics.total_time_ms", "u64", "Summed attempt time"),
+    ("metrics.min_time_ms", "u64", "Fastest attempt"),
+    ("metrics.max_time_ms", "u64", "Slowest attempt"),
+    ("metrics.device_errors", "u64", "Errors of the compute backend (GPU or CPU)"),
+    ("metrics.network_errors", "u64", "Errors talking to the aggregator"),
+    ("metrics.signature_errors", "u64", "Errors signing receipts"),
+    ("metrics.validation_errors", "u64", "Receipts or inputs that failed validation"),
+    ("metrics.uptime_seconds", "u64", "Time since the worker started"),
+    ("metrics.last_successful_attempt", "u64?", "Unix time of the last successful attempt"),
+    ("metrics.consecutive_failures", "u32", "Failed attempts since the last success"),
+    ("metrics.window_failure_rate", "f64", "Failed share of the health policy's window"),
+    ("metrics.gpu_temperature_c", "f64?", "Hottest GPU, when the health policy watches temperature"),
+    ("metrics.selftest_failures", "u64", "Failed INT8 GEMM self-tests"),
+    ("metrics.selftest_failing", "bool", "Whether the latest self-test failed"),
+    ("metrics.silent_corruptions", "u64", "Attempts whose output failed the CPU spot-check"),
+    ("metrics.consecutive_corruptions", "u32", "Corrupted attempts in a row"),
+    ("metrics.compressed_submissions", "u64", "Submissions sent with a Content-Encoding"),
+    ("metrics.submit_bytes_saved", "u64", "Bytes compression saved on submissions"),
+    ("metrics.attempts_per_second", "f64", "Attempt throughput"),
+    ("metrics.receipts_per_second", "f64", "Receipt throughput"),
+    ("metrics.streams[].stream", "usize", "Attempt stream index"),
+    ("metrics.streams[].backend", "string", "Backend the stream runs on"),
+    ("metrics.streams[].attempts", "u64", "Attempts of the stream"),
+    ("metrics.streams[].average_time_ms", "f64", "Mean attempt time of the stream"),
+    ("metrics.streams[].last_time_ms", "u64", "Latest attempt time of the stream"),
+    ("metrics.epoch.epoch_id", "u64", "Current epoch"),
+    ("metrics.epoch.transitions", "u64", "Epoch changes seen"),
+    ("metrics.epoch.duration_seconds", "u64", "Time spent in the current epoch"),
+    ("metrics.epoch.attempts", "u64", "Attempts in the current epoch"),
+    ("metrics.epoch.successful_attempts", "u64", "Successful attempts in the current epoch"),
+    ("metrics.epoch.failed_attempts", "u64", "Failed attempts in the current epoch"),
+    ("metrics.epoch.accepted_receipts", "u64", "Receipts accepted in the current epoch"),
+    ("metrics.epoch.average_time_ms", "f64", "Mean attempt time in the current epoch"),
+    ("metrics.epoch.average_submit_latency_ms", "f64", "Mean submission latency of the current epoch's accepted receipts"),
+    ("metrics.epoch.energy_joules", "f64?", "Energy drawn in the current epoch, when metered"),
+];
+
+impl MetricsSchema {
+    pub fn current() -> Self {
+        let mut fields: Vec<FieldSchema> = FIELDS.iter()
+            .map(|&(name, ty, description)| FieldSchema { name, ty, description, deprecated: None })
+            .collect();
+        for deprecation in DEPRECATED.iter().filter(|d| d.removed_in > METRICS_SCHEMA_VERSION) {
+            let ty = FIELDS.iter().find(|(name, _, _)| *name == deprecation.replaced_by).map_or("", |f| f.1);
+            fields.push(FieldSchema {
+                name: deprecation.field,
+                ty,
+                description: "Deprecated; same value as its replacement",
+                deprecated: Some(deprecation),
+            });
+        }
+        Self { schema_version: METRICS_SCHEMA_VERSION, fields }
+    }
+}
+
+/// Serialize a response and copy each field still in its transition period
+/// under its old name.
+pub fn with_deprecated_fields<T: Serialize>(response: &T) -> serde_json::Result<Value> {
+    let mut value = serde_json::to_value(response)?;
+    for deprecation in DEPRECATED.iter().filter(|d| d.removed_in > METRICS_SCHEMA_VERSION) {
+        let (parent, old) = split_path(deprecation.field);
+        let (_, new) = split_path(deprecation.replaced_by);
+        if let Some(Value::Object(object)) = value.pointer_mut(&parent) {
+            if let Some(current) = object.get(new).cloned() {
+                object.entry(old.to_string()).or_insert(current);
+            }
+        }
+    }
+    Ok(value)
+}
+
+// `metrics.gpu_errors` -> ("/metrics", "gpu_errors")
+fn split_path(path: &str) -> (String, &str) {
+    match path.rsplit_once('.') {
+        Some((parent, leaf)) => (format!("/{}", parent.replace('.', "/")), leaf),
+        None => (String::new(), path),
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::{BTreeMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use crate::compression::{decompress, ContentEncoding};
+use crate::epoch::EpochDocument;
+use crate::epoch_summary::EpochSummary;
+use crate::idempotency::{idempotency_key, IDEMPOTENCY_KEY_HEADER};
+use crate::negotiation::RECEIPT_VERSIONS_HEADER;
+use crate::quarantine::recompute_work_root;
+use crate::response_auth::SIGNATURE_HEADER;
+use crate::signing::{response_message, verify_receipt, Secp};
+use crate::submit::{RejectReason, SubmitResponse};
+use crate::types::{WorkReceipt, SUPPORTED_RECEIPT_VERSIONS};
+use crate::work_hash::HashKind;
+
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Failure a mock aggregator injects instead of its normal verdict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureMode {
+    /// Verify and answer normally.
+    None,
+    /// `400` with `{"accepted":false,"reason":...}`.
+    Reject(RejectReason),
+    /// `429` with `Retry-After: 1`.
+    Throttle,
+    /// `500`.
+    ServerError,
+    /// Hold the connection open without answering, so the client times out.
+    Hang,
+    /// Close the connection without answering.
+    Close,
+}
+
+impl std::str::FromStr for FailureMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(FailureMode::None),
+            "throttle" => Ok(FailureMode::Throttle),
+            "error" => Ok(FailureMode::ServerError),
+            "hang" => Ok(FailureMode::Hang),
+            "close" => Ok(FailureMode::Close),
+            other => match other.strip_prefix("reject:") {
+                Some(code) => match RejectReason::from_code(code) {
+                    RejectReason::Other if code != "other" => Err(format!("unknown reject reason '{}'", code)),
+                    reason => Ok(FailureMode::Reject(reason)),
+                },
+                None => Err(format!("unknown failure mode '{}' (none, reject:<reason>, throttle, error, hang, close)", other)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for FailureMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureMode::None => write!(f, "none"),
+            FailureMode::Reject(reason) => write!(f, "reject:{}", reason),
+            FailureMode::Throttle => write!(f, "throttle"),
+            FailureMode::ServerError => write!(f, "error"),
+            FailureMode::Hang => write!(f, "hang"),
+            FailureMode::Close => write!(f, "close"),
+        }
+    }
+}
+
+/// How a mock aggregator verifies receipts and what it injects.
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    pub listen: SocketAddr,
+    /// Receipt signatures must verify against this key (hex SEC1); unchecked when unset.
+    pub pubkey: Option<String>,
+    /// Receipts must carry this `network_id`.
+    pub network_id: Option<String>,
+    /// Recompute the work_root of receipts with at most this many multiply-accumulates (m*n*k*batch); 0 never does.
+    pub recompute_max_macs: u64,
+    pub failure: FailureMode,
+    /// Inject `failure` into every Nth submission (1: all of them).
+    pub fail_every: u64,
+    /// Served at `GET /epoch`.
+    pub epoch: EpochDocument,
+    /// Sign verdicts and epoch documents with this key (hex), as `AGGREGATOR_PUBKEY` expects.
+    pub response_sk_hex: Option<String>,
+    /// Keep the matrices of recomputed receipts here, up to `matrix_cache_mb`.
+    pub matrix_cache_dir: Option<std::path::PathBuf>,
+    pub matrix_cache_mb: u64,
+    /// Added to the `Date` header of every response, to stand in for a worker with a wrong clock.
+    pub clock_skew_ms: i64,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([127, 0, 0, 1], 8081)),
+            pubkey: None,
+            network_id: None,
+            recompute_max_macs: 1 << 21,
+            failure: FailureMode::None,
+            fail_every: 1,
+            clock_skew_ms: 0,
+            epoch: EpochDocument {
+                epoch_id: 1,
+                prev_hash: hex::encode(blake3::hash(b"tops-worker mock-aggregator").as_bytes()),
+                salt: None,
+                memhard_kib: None,
+                min_tops_seconds: None,
+                requant_scale: None,
+                activation: None,
+                requant_rounding: None,
+                requant_overflow: None,
+                size_distribution: None,
+                hash_kind: None,
+            },
+            response_sk_hex: None,
+            matrix_cache_dir: None,
+            matrix_cache_mb: 1024,
+        }
+    }
+}
+
+/// Counters served at `GET /stats`, for tests to assert on.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MockStats {
+    pub received: u64,
+    pub accepted: u64,
+    /// Refusals per reason code, injected ones included.
+    pub rejected: BTreeMap<String, u64>,
+    /// Submissions answered with an injected throttle, error, hang or close.
+    pub injected: u64,
+    /// Accepted receipts whose work_root was recomputed.
+    pub recomputed: u64,
+    /// Submissions that carried an `Idempotency-Key` header.
+    pub idempotency_keys: u64,
+    /// Epoch summaries whose signature verified.
+    pub epoch_summaries: u64,
+}
+
+// A parsed HTTP/1.1 request
+/// One HTTP request as `read_request` parses it.
+pub struct Request {
+    pub method: String,
+    /// Request target without the query string.
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+// What to write back, if anything
+enum Reply {
+    Response { status: u16, headers: Vec<(&'static str, String)>, body: Vec<u8> },
+    Hang,
+    Close,
+}
+
+/// A stand-in aggregator for end-to-end tests of the worker.
+///
+/// Serves `POST /verify` (receipt v1 and v2, gzip/zstd bodies), the `OPTIONS /verify`
+/// handshake, `POST /epoch-summary`, `GET /epoch`, `GET /stats` and `GET /healthz`. Verification uses the
+/// worker's own decoding, signature and CPU kernel code.
+pub struct MockAggregator {
+    config: MockConfig,
+    signer: Option<Secp>,
+    hash_kind: HashKind,
+    submissions: AtomicU64,
+    seen: Mutex<HashSet<String>>,
+    stats: Mutex<MockStats>,
+}
+
+impl MockAggregator {
+    pub fn new(config: MockConfig) -> anyhow::Result<Self> {
+        let signer = config.response_sk_hex.as_deref().map(Secp::from_hex).transpose()?;
+        let hash_kind = config.epoch.clone().into_info()?.hash_kind;
+        Ok(Self { config, signer, hash_kind, submissions: AtomicU64::new(0), seen: Mutex::new(HashSet::new()), stats: Mutex::new(MockStats::default()) })
+    }
+
+    pub fn stats(&self) -> MockStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Bind `config.listen` and serve until the task is dropped.
+    pub async fn serve(self: Arc<Self>) -> anyhow::Result<()> {
+        let listener = TcpListener::bind(self.config.listen).await?;
+        self.serve_on(listener).await
+    }
+
+    /// Serve on an already bound listener (e.g. port 0 in a test).
+    pub async fn serve_on(self: Arc<Self>, listener: TcpListener) -> anyhow::Result<()> {
+        loop {
+            let (socket, _) = listener.accept().await?;
+            let this = Arc::clone(&self);
+            tokio::spawn(async move {
+                let _ = this.handle_connection(socket).await;
+            });
+        }
+    }
+
+    // Connections are kept alive until the client asks to close them (clients do not pipeline)
+    async fn handle_connection(&self, mut socket: TcpStream) -> anyhow::Result<()> {
+        while let Some(request) = read_request(&mut socket).await? {
+            let close = request.header("Connection").is_some_and(|v| v.eq_ignore_ascii_case("close"));
+            match self.route(&request) {
+                Reply::Response { status, headers, body } => {
+                    let date = chrono::Utc::now() + chrono::Duration::milliseconds(self.config.clock_skew_ms);
+                    let mut head = format!("HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: {}\r\nDate: {}\r\n",
+                        status, reason_phrase(status), body.len(), if close { "close" } else { "keep-alive" },
+                        date.format("%a, %d %b %Y %H:%M:%S GMT"));
+                    for (name, value) in headers {
+                        head.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    head.push_str("\r\n");
+                    socket.write_all(head.as_bytes()).await?;
+                    socket.write_all(&body).await?;
+                    if close {
+                        socket.shutdown().await?;
+                        break;
+                    }
+                }
+                Reply::Hang => {
+                    tokio::time::sleep(Duration::from_secs(3600)).await;
+                    break;
+                }
+                Reply::Close => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn route(&self, request: &Request) -> Reply {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("OPTIONS", "/verify") => {
+                let versions = SUPPORTED_RECEIPT_VERSIONS.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+                Reply::Response { status: 204, headers: vec![
+                    (RECEIPT_VERSIONS_HEADER, versions),
+                    ("Accept-Encoding", "gzip, zstd".to_string()),
+                ], body: Vec::new() }
+            }
+            ("POST", "/verify") => self.verify(request),
+            ("POST", "/epoch-summary") => self.epoch_summary(request),
+            ("GET", "/epoch") => match serde_json::to_vec(&self.config.epoch) {
+                Ok(body) => self.json(200, body),
+                Err(e) => self.json(500, format!("{{\"error\":\"{}\"}}", e).into_bytes()),
+            },
+            ("GET", "/stats") => self.json(200, serde_json::to_vec(&self.stats()).unwrap_or_default()),
+            ("GET", "/healthz") => self.json(200, b"{\"ok\":true}".to_vec()),
+            _ => self.json(404, b"{\"error\":\"not found\"}".to_vec()),
+        }
+    }
+
+    fn verify(&self, request: &Request) -> Reply {
+        let n = self.submissions.fetch_add(1, Ordering::Relaxed) + 1;
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.received += 1;
+            if request.header(IDEMPOTENCY_KEY_HEADER).is_some() {
+                stats.idempotency_keys += 1;
+            }
+        }
+
+        if self.config.failure != FailureMode::None && n.is_multiple_of(self.config.fail_every.max(1)) {
+            let injected = match self.config.failure {
+                FailureMode::Reject(reason) => return self.reject(request, reason, "injected failure"),
+                FailureMode::Throttle => Reply::Response { status: 429, headers: vec![("Retry-After", "1".to_string())],
+                    body: b"{\"error\":\"throttled\"}".to_vec() },
+                FailureMode::ServerError => Reply::Response { status: 500, headers: Vec::new(),
+                    body: b"{\"error\":\"injected failure\"}".to_vec() },
+                FailureMode::Hang => Reply::Hang,
+                FailureMode::Close | FailureMode::None => Reply::Close,
+            };
+            self.stats.lock().unwrap().injected += 1;
+            return injected;
+        }
+
+        let receipt = match decode_receipt(request) {
+            Ok(receipt) => receipt,
+            Err(e) => return self.reject(request, RejectReason::Other, &format!("undecodable receipt: {}", e)),
+        };
+        if receipt.network_id != self.config.network_id {
+            return self.reject(request, RejectReason::Other, &format!("wrong network_id {}", receipt.network_id.as_deref().unwrap_or("(none)")));
+        }
+        if let Some(pubkey) = &self.config.pubkey {
+            if !verify_receipt(&receipt, pubkey).unwrap_or(false) {
+                return self.reject(request, RejectReason::BadSignature, "signature does not verify");
+            }
+        }
+        let key = idempotency_key(&receipt);
+        if self.seen.lock().unwrap().contains(&key) {
+            return self.reject(request, RejectReason::Duplicate, "receipt already accepted");
+        }
+        let hash_kind = receipt.hash_kind.unwrap_or_default();
+        if hash_kind != self.hash_kind {
+            return self.reject(request, RejectReason::BadWork, &format!("work_root hashed with {}, the epoch uses {}", hash_kind, self.hash_kind));
+        }
+        let s = &receipt.sizes;
+        let macs = (s.m as u64) * (s.n as u64) * (s.k as u64) * (s.batch.max(1) as u64);
+        let recomputed = macs <= self.config.recompute_max_macs;
+        if recomputed {
+            match recompute_work_root(&receipt) {
+                Ok(root) if root == receipt.work_root_hex => {}
+                Ok(root) => return self.reject(request, RejectReason::BadWork, &format!("work_root does not recompute (CPU {})", root)),
+                Err(e) => return self.reject(request, RejectReason::BadWork, &e.to_string()),
+            }
+        }
+
+        self.seen.lock().unwrap().insert(key);
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.accepted += 1;
+            if recomputed {
+                stats.recomputed += 1;
+            }
+        }
+        let verdict = SubmitResponse { accepted: Some(true), ..Default::default() };
+        self.verdict(request, 200, serde_json::to_vec(&verdict).unwrap_or_default())
+    }
+
+    fn epoch_summary(&self, request: &Request) -> Reply {
+        let summary: EpochSummary = match serde_json::from_slice(&request.body) {
+            Ok(summary) => summary,
+            Err(e) => return self.json(400, format!("{{\"error\":\"undecodable summary: {}\"}}", e).into_bytes()),
+        };
+        let pubkey = self.config.pubkey.as_deref().unwrap_or(&summary.pubkey_hex);
+        if !summary.verify(pubkey).unwrap_or(false) {
+            return self.json(400, b"{\"error\":\"signature does not verify\"}".to_vec());
+        }
+        self.stats.lock().unwrap().epoch_summaries += 1;
+        self.json(200, b"{\"ok\":true}".to_vec())
+    }
+
+    fn reject(&self, request: &Request, reason: RejectReason, message: &str) -> Reply {
+        *self.stats.lock().unwrap().rejected.entry(reason.to_string()).or_default() += 1;
+        let verdict = SubmitResponse {
+            accepted: Some(false),
+            reason: Some(reason),
+            message: Some(message.to_string()),
+            ..Default::default()
+        };
+        self.verdict(request, 400, serde_json::to_vec(&verdict).unwrap_or_default())
+    }
+
+    // A verdict on the receipt in `request`, its signature bound to the receipt's idempotency key
+    fn verdict(&self, request: &Request, status: u16, body: Vec<u8>) -> Reply {
+        self.signed_json(status, body, request.header(IDEMPOTENCY_KEY_HEADER).unwrap_or(""))
+    }
+
+    fn json(&self, status: u16, body: Vec<u8>) -> Reply {
+        self.signed_json(status, body, "")
+    }
+
+    // A JSON response, signed when the mock has a response key
+    fn signed_json(&self, status: u16, body: Vec<u8>, request_key: &str) -> Reply {
+        let mut headers = vec![("Content-Type", "application/json".to_string())];
+        if let Some(signer) = &self.signer {
+            if let Ok(sig) = response_message(&body, self.config.network_id.as_deref(), request_key).and_then(|m| signer.sign_payload(&m)) {
+                headers.push((SIGNATURE_HEADER, sig));
+            }
+        }
+        Reply::Response { status, headers, body }
+    }
+}
+
+fn decode_receipt(request: &Request) -> anyhow::Result<WorkReceipt> {
+    let encoding: ContentEncoding = match request.header("content-encoding") {
+        Some(value) => value.trim().to_ascii_lowercase().parse().map_err(|e: String| anyhow::anyhow!(e))?,
+        None => ContentEncoding::Identity,
+    };
+    let body = decompress(encoding, &request.body)?;
+    WorkReceipt::decode(&body, request.header("content-type").unwrap_or("application/json"))
+}
+
+/// Read one request; `None` if the peer closed before sending one.
+pub async fn read_request(socket: &mut TcpStream) -> anyhow::Result<Option<Request>> {
+    read_request_with(socket, MAX_BODY_BYTES, |_| true).await
+}
+
+/// `read_request` with a body limit, and a check of the request line and headers
+/// before any of the body is read: a request `admit` refuses comes back without it.
+pub async fn read_request_with(
+    socket: &mut TcpStream,
+    max_body: usize,
+    admit: impl Fn(&Request) -> bool,
+) -> anyhow::Result<Option<Request>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buf.len() > MAX_HEADER_BYTES {
+            anyhow::bail!("request headers too large");
+        }
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default();
+    let path = target.split_once('?').map_or(target, |(path, _)| path).to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let mut request = Request { method, path, headers, body: Vec::new() };
+    if !admit(&request) {
+        return Ok(Some(request));
+    }
+
+    let content_length = request.header("content-length")
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    if content_length > max_body {
+        anyhow::bail!("request body too large");
+    }
+    let mut body = buf.split_off(header_end + 4);
+    while body.len() < content_length {
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            anyhow::bail!("connection closed mid-body");
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+    request.body = body;
+    Ok(Some(request))
+}
+
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        _ => "Internal Server Error",
+    }
+}
@@ -0,0 +1,243 @@
+#![cfg(feature = "mqtt")]
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, Event, MqttOptions, Outgoing, Packet, QoS, TlsConfiguration, Transport};
+use tokio::sync::mpsc;
+use crate::config::Config;
+use crate::export::{ExportRow, ReceiptExporter};
+use crate::queue::PersistentQueue;
+use crate::identity::KeyRing;
+use crate::jitter::Jitter;
+use crate::submit::{sign_and_encode, SubmitError, SubmitOutcome, Submission, Submitter};
+use crate::types::WorkReceipt;
+use crate::{log_error, log_info, log_warn};
+
+// Delay before polling the event loop again after a connection error
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+enum PublishEvent {
+    Sent(u16),
+    Acked(u16),
+}
+
+/// Store-and-forward receipt delivery over MQTT.
+///
+/// `submit` signs the receipt and appends it to the persistent queue; a background
+/// task publishes the oldest queued receipt with QoS 1 and only removes it from
+/// disk once the broker's PUBACK arrives. While the broker is unreachable receipts
+/// accumulate on disk and are flushed in order after reconnecting, in this device's
+/// slot among `FLEET_SIZE` workers so a fleet does not flush all at once.
+///
+/// There is no version handshake over a broker, so receipts use RECEIPT_VERSION_MAX.
+/// With an exporter, each receipt is exported as `queued` when it is submitted and
+/// as `published` once the broker acknowledges it.
+pub struct MqttSubmitter {
+    broker: String,
+    topic: String,
+    receipt_version: u16,
+    keys: Arc<KeyRing>,
+    queue: Arc<PersistentQueue>,
+}
+
+impl MqttSubmitter {
+    /// Connect to the broker in MQTT_URL and start the event loop and publisher tasks.
+    pub fn start(config: &Config, keys: Arc<KeyRing>, queue: Arc<PersistentQueue>, exporter: Option<Arc<ReceiptExporter>>) -> anyhow::Result<Self> {
+        let url = config.mqtt_url.as_deref()
+            .ok_or_else(|| anyhow::anyhow!("MQTT_URL is required for AGGREGATOR_PROTOCOL=mqtt"))?;
+        let (tls, rest) = if let Some(rest) = url.strip_prefix("mqtts://") {
+            (true, rest)
+        } else if let Some(rest) = url.strip_prefix("mqtt://") {
+            (false, rest)
+        } else {
+            return Err(anyhow::anyhow!("MQTT_URL must start with mqtt:// or mqtts://"));
+        };
+        let authority = rest.trim_end_matches('/');
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((h, p)) => (h, p.parse::<u16>()?),
+            None => (authority, if tls { 8883 } else { 1883 }),
+        };
+
+        let mut options = MqttOptions::new(config.mqtt_client_id(), host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_clean_session(false);
+        if let Some(user) = &config.mqtt_username {
+            options.set_credentials(user.clone(), config.mqtt_password.clone().unwrap_or_default());
+        }
+        if tls {
+            options.set_transport(Transport::tls_with_config(tls_configuration(config)?));
+        }
+
+        let (client, mut eventloop) = AsyncClient::new(options, 16);
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+
+        let broker = format!("{}:{}", host, port);
+        // Publishing of a backlog holds until this device's flush slot after a (re)connect
+        let flush_hold: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
+        let flush_delay = Jitter::from_config(config).flush_delay();
+        {
+            let broker = broker.clone();
+            let queue = Arc::clone(&queue);
+            let flush_hold = Arc::clone(&flush_hold);
+            tokio::spawn(async move {
+                // Report each outage once rather than on every reconnect attempt
+                let mut outage_reported = false;
+                loop {
+                    match eventloop.poll().await {
+                        Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                            outage_reported = false;
+                            log_info!("[mqtt] connected to {}", broker);
+                            let backlog = queue.len();
+                            if backlog > 1 && !flush_delay.is_zero() {
+                                log_info!("[mqtt] flushing {} buffered receipt(s) in {:.1}s", backlog, flush_delay.as_secs_f64());
+                                if let Ok(mut hold) = flush_hold.lock() {
+                                    *hold = Some(Instant::now() + flush_delay);
+                                }
+                            }
+                        }
+                        Ok(Event::Incoming(Packet::PubAck(ack))) => {
+                            let _ = events_tx.send(PublishEvent::Acked(ack.pkid));
+                        }
+                        Ok(Event::Outgoing(Outgoing::Publish(pkid))) => {
+                            let _ = events_tx.send(PublishEvent::Sent(pkid));
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            if !outage_reported {
+                                log_warn!("[mqtt] broker {} unreachable, buffering receipts: {}", broker, e);
+                                outage_reported = true;
+                            }
+                            // rumqttc keeps unacknowledged publishes and resends them on reconnect
+                            tokio::time::sleep(RECONNECT_DELAY).await;
+                        }
+                    }
+                }
+            });
+        }
+
+        let target = format!("mqtt://{}/{}", broker, config.mqtt_topic);
+        tokio::spawn(publish_queued(client, config.mqtt_topic.clone(), target, Arc::clone(&queue), events_rx, flush_hold, exporter));
+
+        Ok(Self {
+            broker,
+            topic: config.mqtt_topic.clone(),
+            receipt_version: config.receipt_version_max,
+            keys,
+            queue,
+        })
+    }
+}
+
+fn tls_configuration(config: &Config) -> anyhow::Result<TlsConfiguration> {
+    let client_auth = match (&config.mqtt_client_cert_file, &config.mqtt_client_key_file) {
+        (Some(cert), Some(key)) => Some((std::fs::read(cert)?, std::fs::read(key)?)),
+        (None, None) => None,
+        _ => return Err(anyhow::anyhow!("MQTT_CLIENT_CERT_FILE and MQTT_CLIENT_KEY_FILE must be set together")),
+    };
+    match &config.mqtt_ca_file {
+        Some(ca) => Ok(TlsConfiguration::Simple { ca: std::fs::read(ca)?, alpn: None, client_auth }),
+        None if client_auth.is_none() => Ok(TlsConfiguration::default()),
+        None => Err(anyhow::anyhow!("MQTT client certificates need MQTT_CA_FILE")),
+    }
+}
+
+// Publish queued receipts one at a time, oldest first, deleting each only after its PUBACK
+async fn publish_queued(
+    client: AsyncClient,
+    topic: String,
+    target: String,
+    queue: Arc<PersistentQueue>,
+    mut events: mpsc::UnboundedReceiver<PublishEvent>,
+    flush_hold: Arc<Mutex<Option<Instant>>>,
+    exporter: Option<Arc<ReceiptExporter>>,
+) {
+    loop {
+        let hold = flush_hold.lock().ok().and_then(|mut hold| hold.take());
+        if let Some(until) = hold {
+            tokio::time::sleep_until(until.into()).await;
+        }
+        let (seq, receipt) = match queue.peek::<WorkReceipt>() {
+            Ok(Some(entry)) => entry,
+            Ok(None) => {
+                queue.wait_for_item().await;
+                continue;
+            }
+            Err(e) => {
+                log_error!("[mqtt] reading queue failed: {}", e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        let body = match receipt.encode() {
+            Ok((body, _)) => body,
+            Err(e) => {
+                log_warn!("[mqtt] dropping queued receipt {}: {}", seq, e);
+                let _ = queue.remove(seq);
+                continue;
+            }
+        };
+        let published_at = Instant::now();
+        if let Err(e) = client.publish(topic.clone(), QoS::AtLeastOnce, false, body).await {
+            log_error!("[mqtt] publish failed: {}", e);
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            continue;
+        }
+
+        // Only one publish is outstanding, so the next Sent is ours; then wait for its ack
+        let mut pkid = None;
+        loop {
+            match (events.recv().await, pkid) {
+                // Event loop gone: leave the receipt queued for the next run
+                (None, _) => return,
+                (Some(PublishEvent::Sent(id)), None) => pkid = Some(id),
+                (Some(PublishEvent::Acked(id)), Some(expected)) if id == expected => break,
+                _ => {}
+            }
+        }
+        if let Err(e) = queue.remove(seq) {
+            log_error!("[mqtt] removing delivered receipt {} failed: {}", seq, e);
+        }
+        if let Some(exporter) = &exporter {
+            let acked = Submission {
+                target: target.clone(),
+                latency: published_at.elapsed(),
+                outcome: SubmitOutcome::Queued,
+                compression: None,
+                response: None,
+                retries: 0,
+                signed: None,
+            };
+            let row = ExportRow { outcome: "published".to_string(), ..ExportRow::new(&receipt, &acked) };
+            if let Err(e) = exporter.record(&row) {
+                log_warn!("[export] could not record published nonce {}: {}", receipt.nonce, e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Submitter for MqttSubmitter {
+    fn describe(&self) -> String {
+        format!("mqtt ({}, topic {})", self.broker, self.topic)
+    }
+
+    async fn submit(&self, mut receipt: WorkReceipt) -> Result<Submission, SubmitError> {
+        let start = Instant::now();
+        receipt.receipt_version = self.receipt_version;
+        sign_and_encode(&self.keys, &mut receipt).await?;
+        self.queue.push(&receipt).map_err(|e| SubmitError::Queue(e.to_string()))?;
+        Ok(Submission {
+            target: format!("mqtt://{}/{}", self.broker, self.topic),
+            latency: start.elapsed(),
+            outcome: SubmitOutcome::Queued,
+            compression: None,
+            response: None,
+            retries: 0,
+            signed: Some(receipt),
+        })
+    }
+
+    fn pending(&self) -> usize {
+        self.queue.len()
+    }
+}
@@ -0,0 +1,152 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use reqwest::header::HeaderMap;
+use crate::compression::{parse_accept_encoding, CompressionMode, ContentEncoding};
+use crate::types::{select_receipt_version, RECEIPT_VERSION_V1, SUPPORTED_RECEIPT_VERSIONS};
+
+/// Header listing receipt versions, sent by the worker on every request and
+/// echoed by aggregators with the versions they accept (e.g. `1,2`).
+pub const RECEIPT_VERSIONS_HEADER: &str = "x-receipt-versions";
+
+/// What an endpoint told us it accepts.
+#[derive(Debug, Clone, Default)]
+struct EndpointCaps {
+    version: Option<u16>,
+    encodings: Vec<ContentEncoding>,
+    compression_refused: bool,
+    compact_refused: bool,
+}
+
+/// Per-endpoint receipt version and content-encoding negotiation.
+///
+/// The first submission to an endpoint is preceded by an `OPTIONS` handshake on the
+/// submit URL. Aggregators that answer with `X-Receipt-Versions` get the highest
+/// version both sides support; anything else (no header, error, timeout) is treated
+/// as a legacy v1-only aggregator. Headers on later responses refresh the choice, and
+/// a 415 drops the endpoint back to v1.
+///
+/// The same handshake records the `Accept-Encoding` the aggregator advertises for
+/// request bodies. A 415 to a compressed body stops compression for that endpoint
+/// instead of touching the receipt version, and a 415 to a compact receipt stops
+/// the compact format.
+pub struct ReceiptNegotiator {
+    max_local: u16,
+    discover_encodings: bool,
+    endpoints: Mutex<Vec<EndpointCaps>>,
+}
+
+impl ReceiptNegotiator {
+    pub fn new(endpoint_count: usize, max_local: u16) -> Self {
+        Self {
+            max_local,
+            discover_encodings: false,
+            endpoints: Mutex::new(vec![EndpointCaps::default(); endpoint_count]),
+        }
+    }
+
+    /// Also handshake with v1-only configurations so `Accept-Encoding` is learned.
+    pub fn with_encoding_discovery(mut self, enabled: bool) -> Self {
+        self.discover_encodings = enabled;
+        self
+    }
+
+    /// Value for the `X-Receipt-Versions` request header.
+    pub fn offered_versions(&self) -> String {
+        SUPPORTED_RECEIPT_VERSIONS
+            .iter()
+            .filter(|v| **v <= self.max_local)
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Version to use for endpoint `idx`, performing the handshake on first use.
+    pub async fn version_for(&self, idx: usize, client: &reqwest::Client, url: &str) -> u16 {
+        if let Some(v) = self.cached(idx).and_then(|caps| caps.version) {
+            return v;
+        }
+        let (version, encodings) = self.handshake(client, url).await;
+        self.update(idx, |caps| {
+            caps.version = Some(version);
+            caps.encodings = encodings;
+        });
+        version
+    }
+
+    /// Body encoding to use for endpoint `idx` under `mode`.
+    pub fn encoding_for(&self, idx: usize, mode: CompressionMode) -> ContentEncoding {
+        match self.cached(idx) {
+            Some(caps) if !caps.compression_refused => mode.choose(&caps.encodings),
+            _ => ContentEncoding::Identity,
+        }
+    }
+
+    /// Whether endpoint `idx` may still be sent compact receipts.
+    pub fn compact_for(&self, idx: usize) -> bool {
+        !self.cached(idx).is_some_and(|caps| caps.compact_refused)
+    }
+
+    /// Update the endpoint's capabilities from a submission response to a body sent
+    /// with `sent`, in the compact format if `compact`.
+    pub fn observe_response(&self, idx: usize, status: u16, headers: &HeaderMap, sent: ContentEncoding, compact: bool) {
+        let accepted = parse_accept_encoding(headers);
+        let remote_versions = parse_versions_header(headers);
+        self.update(idx, |caps| {
+            if status == 415 && compact {
+                caps.compact_refused = true;
+            } else if status == 415 && sent != ContentEncoding::Identity {
+                caps.compression_refused = true;
+            } else if status == 415 {
+                caps.version = Some(RECEIPT_VERSION_V1);
+            } else if let Some(remote) = remote_versions {
+                caps.version = Some(select_receipt_version(&remote, self.max_local));
+            }
+            if let Some(accepted) = accepted {
+                caps.encodings = accepted;
+            }
+        });
+    }
+
+    async fn handshake(&self, client: &reqwest::Client, url: &str) -> (u16, Vec<ContentEncoding>) {
+        if self.max_local <= RECEIPT_VERSION_V1 && !self.discover_encodings {
+            return (RECEIPT_VERSION_V1, Vec::new());
+        }
+        let response = client
+            .request(reqwest::Method::OPTIONS, url)
+            .header(RECEIPT_VERSIONS_HEADER, self.offered_versions())
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await;
+        match response {
+            Ok(resp) => {
+                let version = match parse_versions_header(resp.headers()) {
+                    Some(remote) => select_receipt_version(&remote, self.max_local),
+                    None => RECEIPT_VERSION_V1,
+                };
+                (version, parse_accept_encoding(resp.headers()).unwrap_or_default())
+            }
+            Err(_) => (RECEIPT_VERSION_V1, Vec::new()),
+        }
+    }
+
+    fn cached(&self, idx: usize) -> Option<EndpointCaps> {
+        self.endpoints.lock().ok().and_then(|e| e.get(idx).cloned())
+    }
+
+    fn update(&self, idx: usize, f: impl FnOnce(&mut EndpointCaps)) {
+        if let Ok(mut endpoints) = self.endpoints.lock() {
+            if let Some(caps) = endpoints.get_mut(idx) {
+                f(caps);
+            }
+        }
+    }
+}
+
+fn parse_versions_header(headers: &HeaderMap) -> Option<Vec<u16>> {
+    let value = headers.get(RECEIPT_VERSIONS_HEADER)?.to_str().ok()?;
+    let versions: Vec<u16> = value
+        .split(',')
+        .filter_map(|v| v.trim().parse().ok())
+        .collect();
+    if versions.is_empty() { None } else { Some(versions) }
+}
@@ -0,0 +1,400 @@
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tower_layer::Layer;
+use tower_service::Service;
+use crate::config::Config;
+use crate::dns::AggregatorResolver;
+use crate::prometheus_metrics::PrometheusMetrics;
+
+// Recent submission requests the /status latency percentiles cover
+const LATENCY_WINDOW: usize = 512;
+
+/// Address family used for outbound aggregator connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    Any,
+    Ipv4,
+    Ipv6,
+}
+
+impl IpFamily {
+    pub fn allows(&self, ip: &IpAddr) -> bool {
+        match self {
+            IpFamily::Any => true,
+            IpFamily::Ipv4 => ip.is_ipv4(),
+            IpFamily::Ipv6 => ip.is_ipv6(),
+        }
+    }
+
+    fn unspecified(&self) -> Option<IpAddr> {
+        match self {
+            IpFamily::Any => None,
+            IpFamily::Ipv4 => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            IpFamily::Ipv6 => Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        }
+    }
+}
+
+impl std::str::FromStr for IpFamily {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(IpFamily::Any),
+            "ipv4" => Ok(IpFamily::Ipv4),
+            "ipv6" => Ok(IpFamily::Ipv6),
+            other => Err(format!("unknown IP family '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpFamily::Any => write!(f, "any"),
+            IpFamily::Ipv4 => write!(f, "ipv4"),
+            IpFamily::Ipv6 => write!(f, "ipv6"),
+        }
+    }
+}
+
+/// HTTP protocol spoken to the aggregator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpVersion {
+    /// HTTP/2 where TLS negotiates it (ALPN), HTTP/1.1 otherwise.
+    Auto,
+    Http1,
+    /// HTTP/2 only, also over plain `http://` (prior knowledge).
+    Http2,
+}
+
+impl std::str::FromStr for HttpVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(HttpVersion::Auto),
+            "http1" => Ok(HttpVersion::Http1),
+            "http2" => Ok(HttpVersion::Http2),
+            other => Err(format!("unknown HTTP version '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HttpVersion::Auto => write!(f, "auto"),
+            HttpVersion::Http1 => write!(f, "http1"),
+            HttpVersion::Http2 => write!(f, "http2"),
+        }
+    }
+}
+
+/// TLS implementation for aggregator connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsBackend {
+    /// rustls with the system's root certificates; resumes TLS sessions on reconnect.
+    Rustls,
+    /// The platform library (OpenSSL on Linux); every reconnect is a full handshake.
+    Native,
+}
+
+impl std::str::FromStr for TlsBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rustls" => Ok(TlsBackend::Rustls),
+            "native" => Ok(TlsBackend::Native),
+            other => Err(format!("unknown TLS backend '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for TlsBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsBackend::Rustls => write!(f, "rustls"),
+            TlsBackend::Native => write!(f, "native"),
+        }
+    }
+}
+
+/// Connection accounting for the aggregator client.
+///
+/// The client's connector reports every connection it opens; the submitter
+/// reports every request. Requests that did not open a connection went over
+/// a pooled one.
+#[derive(Default)]
+pub struct ConnectionStats {
+    requests: AtomicU64,
+    opened: AtomicU64,
+    failed: AtomicU64,
+    handshake_us: AtomicU64,
+    // Host name answers by where they came from (see `record_dns`)
+    dns_lookups: AtomicU64,
+    dns_cached: AtomicU64,
+    dns_stale: AtomicU64,
+    dns_failures: AtomicU64,
+    // Time to headers and total time of recent answered submissions, in milliseconds
+    latencies: Mutex<VecDeque<(f64, f64)>>,
+    metrics: Option<Arc<PrometheusMetrics>>,
+}
+
+/// Connection counters reported in /status.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConnectionSummary {
+    pub requests: u64,
+    pub new_connections: u64,
+    /// Requests sent over a pooled connection.
+    pub reused_connections: u64,
+    pub failed_connections: u64,
+    /// Mean time to open a connection: TCP connect, proxy and TLS handshake.
+    pub mean_handshake_ms: f64,
+    /// 95th percentile time to the response headers over recent answered submissions.
+    pub p95_ttfb_ms: Option<f64>,
+    /// 95th percentile time to the full response over recent answered submissions.
+    pub p95_latency_ms: Option<f64>,
+    pub latency_samples: usize,
+    /// Host names looked up with the system resolver.
+    pub dns_lookups: u64,
+    /// Host names answered from the DNS cache or `AGGREGATOR_RESOLVE`.
+    pub dns_cached: u64,
+    /// Failed lookups answered with an expired cache entry.
+    pub dns_stale: u64,
+    /// Host names that did not resolve at all.
+    pub dns_failures: u64,
+}
+
+impl ConnectionStats {
+    pub fn new(metrics: Option<Arc<PrometheusMetrics>>) -> Self {
+        Self { metrics, ..Self::default() }
+    }
+
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_aggregator_request();
+        }
+    }
+
+    /// Latency of one submission request: `status` and `ttfb` are `None` when no
+    /// response came back. Only answered requests count towards the percentiles.
+    pub fn record_latency(&self, status: Option<u16>, ttfb: Option<Duration>, total: Duration) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_network_latency(status_class(status), ttfb, total);
+        }
+        let Some(ttfb) = ttfb else { return };
+        if let Ok(mut latencies) = self.latencies.lock() {
+            if latencies.len() == LATENCY_WINDOW {
+                latencies.pop_front();
+            }
+            latencies.push_back((ttfb.as_secs_f64() * 1000.0, total.as_secs_f64() * 1000.0));
+        }
+    }
+
+    fn record_connect(&self, elapsed: Duration, ok: bool) {
+        if ok {
+            self.opened.fetch_add(1, Ordering::Relaxed);
+            self.handshake_us.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        } else {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_aggregator_connection(ok, elapsed);
+        }
+    }
+
+    /// One answer of the client's resolver: `resolved`, `cached`, `override`, `stale` or `failed`.
+    pub fn record_dns(&self, outcome: &'static str) {
+        let counter = match outcome {
+            "resolved" => &self.dns_lookups,
+            "stale" => &self.dns_stale,
+            "failed" => &self.dns_failures,
+            _ => &self.dns_cached,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_aggregator_dns(outcome);
+        }
+    }
+
+    pub fn summary(&self) -> ConnectionSummary {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let opened = self.opened.load(Ordering::Relaxed);
+        let handshake_us = self.handshake_us.load(Ordering::Relaxed);
+        let (ttfb, total): (Vec<f64>, Vec<f64>) = self.latencies.lock()
+            .map(|latencies| latencies.iter().copied().unzip())
+            .unwrap_or_default();
+        ConnectionSummary {
+            requests,
+            new_connections: opened,
+            // Requests the submitter does not see (version probes) open connections too
+            reused_connections: requests.saturating_sub(opened),
+            failed_connections: self.failed.load(Ordering::Relaxed),
+            mean_handshake_ms: if opened > 0 { handshake_us as f64 / opened as f64 / 1000.0 } else { 0.0 },
+            latency_samples: total.len(),
+            p95_ttfb_ms: p95(ttfb),
+            p95_latency_ms: p95(total),
+            dns_lookups: self.dns_lookups.load(Ordering::Relaxed),
+            dns_cached: self.dns_cached.load(Ordering::Relaxed),
+            dns_stale: self.dns_stale.load(Ordering::Relaxed),
+            dns_failures: self.dns_failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// `2xx` ... `5xx` for a response status, `error` when there was no response.
+pub fn status_class(status: Option<u16>) -> &'static str {
+    match status.map(|s| s / 100) {
+        Some(1) => "1xx",
+        Some(2) => "2xx",
+        Some(3) => "3xx",
+        Some(4) => "4xx",
+        Some(5) => "5xx",
+        Some(_) => "other",
+        None => "error",
+    }
+}
+
+// Nearest-rank percentile
+fn p95(mut samples: Vec<f64>) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_by(f64::total_cmp);
+    let rank = (samples.len() * 95).div_ceil(100);
+    Some(samples[rank.saturating_sub(1)])
+}
+
+// Wraps the client's connector, which only runs when the pool has no usable connection
+#[derive(Clone)]
+struct ConnectionMetricsLayer(Arc<ConnectionStats>);
+
+impl<S> Layer<S> for ConnectionMetricsLayer {
+    type Service = ConnectionMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectionMetricsService { inner, stats: Arc::clone(&self.0) }
+    }
+}
+
+#[derive(Clone)]
+struct ConnectionMetricsService<S> {
+    inner: S,
+    stats: Arc<ConnectionStats>,
+}
+
+impl<S, R> Service<R> for ConnectionMetricsService<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let started = Instant::now();
+        let stats = Arc::clone(&self.stats);
+        let connecting = self.inner.call(request);
+        Box::pin(async move {
+            let result = connecting.await;
+            stats.record_connect(started.elapsed(), result.is_ok());
+            result
+        })
+    }
+}
+
+/// HTTP client for aggregator traffic.
+///
+/// Without `AGGREGATOR_PROXY` the standard `HTTPS_PROXY` / `HTTP_PROXY` / `ALL_PROXY`
+/// / `NO_PROXY` variables apply. Proxies may be `http://`, `https://`, `socks5://`
+/// (names resolved locally) or `socks5h://` (names resolved by the proxy).
+///
+/// Connections are pooled and kept alive for the life of the client, so build
+/// it once and share it.
+pub fn aggregator_client(config: &Config) -> anyhow::Result<reqwest::Client> {
+    build_client(config, None)
+}
+
+/// [`aggregator_client`] that reports the connections it opens to `stats`.
+pub fn aggregator_client_with_stats(config: &Config, stats: Arc<ConnectionStats>) -> anyhow::Result<reqwest::Client> {
+    build_client(config, Some(stats))
+}
+
+fn build_client(config: &Config, stats: Option<Arc<ConnectionStats>>) -> anyhow::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .pool_max_idle_per_host(config.aggregator_pool_max_idle)
+        .pool_idle_timeout(config.get_aggregator_pool_idle_timeout())
+        .connect_timeout(config.get_aggregator_connect_timeout())
+        .timeout(config.get_aggregator_request_timeout());
+
+    // Keep-alive probes stop idle pooled connections from being dropped by NAT and load balancers
+    if let Some(interval) = config.get_aggregator_keepalive() {
+        builder = builder
+            .tcp_keepalive(interval)
+            .http2_keep_alive_interval(interval)
+            .http2_keep_alive_timeout(config.get_aggregator_connect_timeout())
+            .http2_keep_alive_while_idle(true);
+    }
+
+    builder = match config.aggregator_http_version {
+        HttpVersion::Auto => builder,
+        HttpVersion::Http1 => builder.http1_only(),
+        HttpVersion::Http2 => builder.http2_prior_knowledge(),
+    };
+    builder = match config.aggregator_tls {
+        TlsBackend::Rustls => builder.use_rustls_tls(),
+        TlsBackend::Native => builder.use_native_tls(),
+    };
+
+    if let Some(stats) = &stats {
+        builder = builder.connector_layer(ConnectionMetricsLayer(Arc::clone(stats)));
+    }
+
+    if let Some(proxy) = &config.aggregator_proxy {
+        // An explicit proxy replaces the environment's, but NO_PROXY is still honored
+        let proxy = reqwest::Proxy::all(proxy)
+            .with_context(|| format!("invalid AGGREGATOR_PROXY '{}'", proxy))?
+            .no_proxy(reqwest::NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(addr) = config.aggregator_bind_address.or(config.aggregator_ip_family.unspecified()) {
+        builder = builder.local_address(addr);
+    }
+    let resolver = AggregatorResolver::from_config(config);
+    let resolver = match &stats {
+        Some(stats) => resolver.with_stats(Arc::clone(stats)),
+        None => resolver,
+    };
+    builder = builder.dns_resolver(Arc::new(resolver));
+
+    if let Some(interface) = &config.aggregator_bind_interface {
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+        {
+            builder = builder.interface(interface);
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+        anyhow::bail!("AGGREGATOR_BIND_INTERFACE ({}) is not supported on this platform", interface);
+    }
+
+    Ok(builder.build()?)
+}
@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+/// Throughput is measured over this much recent history, or more at low rates.
+const PACING_WINDOW: Duration = Duration::from_secs(600);
+// Intervals the window covers at least. An event leaving the window frees a slot at
+// once, so the rate overshoots by about one event per window; this bounds it to 5%
+const MIN_WINDOW_INTERVALS: u32 = 20;
+// Bounds the history at high rates; the window then covers the last events only
+const MAX_EVENTS: usize = 4096;
+// Longest interval a rate target asks for, so a vanishing rate cannot overflow the schedule
+const MAX_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+
+/// How fast the main loop should run (`PACING`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PacingTarget {
+    /// No pause at all, for benchmarking.
+    Unlimited,
+    /// A fixed pause after every attempt.
+    Delay(Duration),
+    /// Receipts handed to the transport per hour.
+    ReceiptsPerHour(f64),
+    /// Attempts computed per minute, whether or not they became receipts.
+    AttemptsPerMinute(f64),
+}
+
+impl PacingTarget {
+    /// The pause the worker always used: 10 ms after every attempt.
+    pub const DEFAULT: PacingTarget = PacingTarget::Delay(Duration::from_millis(10));
+
+    /// Target time between counted events, for the rate targets.
+    fn interval(&self) -> Option<Duration> {
+        let secs = match self {
+            PacingTarget::ReceiptsPerHour(rate) => 3600.0 / rate,
+            PacingTarget::AttemptsPerMinute(rate) => 60.0 / rate,
+            _ => return None,
+        };
+        Some(Duration::try_from_secs_f64(secs).map_or(MAX_INTERVAL, |interval| interval.min(MAX_INTERVAL)))
+    }
+}
+
+impl std::str::FromStr for PacingTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rate = |n: &str| match n.trim().parse::<f64>() {
+            Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+            _ => Err(format!("invalid pacing rate '{}'", n)),
+        };
+        match s.trim() {
+            "unlimited" => Ok(PacingTarget::Unlimited),
+            s if s.ends_with("ms") => s.trim_end_matches("ms").trim().parse()
+                .map(|ms| PacingTarget::Delay(Duration::from_millis(ms)))
+                .map_err(|_| format!("invalid pacing delay '{}'", s)),
+            s => match s.split_once('/') {
+                Some((n, "h" | "hour")) => rate(n).map(PacingTarget::ReceiptsPerHour),
+                Some((n, "min")) => rate(n).map(PacingTarget::AttemptsPerMinute),
+                _ => Err(format!("unknown pacing '{}' (expected unlimited, <ms>ms, <n>/hour or <n>/min)", s)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for PacingTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PacingTarget::Unlimited => write!(f, "unlimited"),
+            PacingTarget::Delay(delay) => write!(f, "{}ms", delay.as_millis()),
+            PacingTarget::ReceiptsPerHour(rate) => write!(f, "{}/hour", rate),
+            PacingTarget::AttemptsPerMinute(rate) => write!(f, "{}/min", rate),
+        }
+    }
+}
+
+impl TryFrom<String> for PacingTarget {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PacingTarget> for String {
+    fn from(target: PacingTarget) -> Self {
+        target.to_string()
+    }
+}
+
+/// Inter-attempt delay that holds the main loop at a `PacingTarget`.
+///
+/// For rate targets it keeps the times of recent receipts (or attempts) and, before
+/// the next attempt, waits as long as it takes for the measured rate over the
+/// window to come down to the target. Slow attempts shrink the delay on their
+/// own, and a device that cannot reach the target runs without any delay.
+#[derive(Debug)]
+pub struct Pacer {
+    target: PacingTarget,
+    events: VecDeque<Instant>,
+    started: Instant,
+}
+
+impl Pacer {
+    pub fn new(target: PacingTarget) -> Self {
+        Self { target, events: VecDeque::new(), started: Instant::now() }
+    }
+
+    /// An attempt finished computing.
+    pub fn on_attempt(&mut self) {
+        if matches!(self.target, PacingTarget::AttemptsPerMinute(_)) {
+            self.record(Instant::now());
+        }
+    }
+
+    /// A receipt went to the transport, whatever the aggregator made of it.
+    pub fn on_receipt(&mut self) {
+        if matches!(self.target, PacingTarget::ReceiptsPerHour(_)) {
+            self.record(Instant::now());
+        }
+    }
+
+    fn record(&mut self, at: Instant) {
+        self.events.push_back(at);
+        while self.events.len() > MAX_EVENTS {
+            self.events.pop_front();
+        }
+    }
+
+    /// Pause before the next attempt.
+    pub fn next_delay(&mut self) -> Duration {
+        self.delay_at(Instant::now())
+    }
+
+    fn delay_at(&mut self, now: Instant) -> Duration {
+        let interval = match self.target {
+            PacingTarget::Unlimited => return Duration::ZERO,
+            PacingTarget::Delay(delay) => return delay,
+            target => target.interval().unwrap_or_default(),
+        };
+        let start = self.window_start(now, PACING_WINDOW.max(interval * MIN_WINDOW_INTERVALS));
+        // n events at the target rate take n intervals; wait out whatever is missing
+        let due = start + interval * self.events.len() as u32;
+        due.saturating_duration_since(now)
+    }
+
+    // Drops events older than the window and returns where it begins: the start of
+    // the run until the window has filled, then the window's edge
+    fn window_start(&mut self, now: Instant, window: Duration) -> Instant {
+        let edge = now.checked_sub(window).unwrap_or(self.started);
+        while self.events.front().is_some_and(|&t| t < edge) {
+            self.events.pop_front();
+        }
+        if self.events.len() == MAX_EVENTS {
+            return self.events.front().copied().unwrap_or(edge);
+        }
+        edge.max(self.started)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Receipts per hour a pacer lets through over `hours` of simulated time, after
+    // a day to settle, with every attempt taking `attempt`
+    fn simulated_rate(target: PacingTarget, attempt: Duration, hours: u32) -> f64 {
+        let mut pacer = Pacer::new(target);
+        let mut now = pacer.started;
+        let settled = now + Duration::from_secs(24 * 3600);
+        let end = settled + Duration::from_secs(u64::from(hours) * 3600);
+        let mut counted = 0;
+        while now < end {
+            now += pacer.delay_at(now) + attempt;
+            pacer.record(now);
+            if now > settled && now <= end {
+                counted += 1;
+            }
+        }
+        counted as f64 / f64::from(hours)
+    }
+
+    #[test]
+    fn low_rates_hold_their_target() {
+        for rate in [1.0, 6.0, 12.0, 60.0, 600.0] {
+            let measured = simulated_rate(PacingTarget::ReceiptsPerHour(rate), Duration::from_secs(2), 30 * 24);
+            assert!(measured <= rate * 1.06 && measured >= rate * 0.94, "target {}/hour, measured {:.3}/hour", rate, measured);
+        }
+    }
+}
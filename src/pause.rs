@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+/// Operator pause of the attempt loop, set through `/admin/pause` and `/admin/resume`.
+#[derive(Debug, Default)]
+pub struct PauseSwitch {
+    paused: AtomicBool,
+    since: Mutex<Option<String>>,
+    resumed: Notify,
+}
+
+/// Pause state reported in /status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PauseState {
+    pub paused: bool,
+    pub since: Option<String>,
+}
+
+impl PauseSwitch {
+    /// Returns false when already paused.
+    pub fn pause(&self) -> bool {
+        if self.paused.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        if let Ok(mut since) = self.since.lock() {
+            *since = Some(chrono::Utc::now().to_rfc3339());
+        }
+        true
+    }
+
+    /// Returns false when not paused.
+    pub fn resume(&self) -> bool {
+        if !self.paused.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        if let Ok(mut since) = self.since.lock() {
+            *since = None;
+        }
+        self.resumed.notify_waiters();
+        true
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn state(&self) -> PauseState {
+        PauseState {
+            paused: self.is_paused(),
+            since: self.since.lock().ok().and_then(|s| s.clone()),
+        }
+    }
+
+    /// Block while paused.
+    pub async fn wait_until_resumed(&self) {
+        loop {
+            let resumed = self.resumed.notified();
+            if !self.is_paused() {
+                return;
+            }
+            resumed.await;
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use anyhow::anyhow;
+use crate::attempt::{compute_work_root, AttemptOutput, Executor};
+use crate::phases::{self, PhaseTimings};
+use crate::prng::derive_salted_seed;
+use crate::spotcheck::{spot_check_samples, SpotCheckResult};
+use crate::types::{Requant, RequantParams, Sizes};
+use crate::work_hash::{HashKind, SampledRoot, WorkSampling};
+use crate::workload::{execute_workload, generate_workload_inputs, Workload};
+use crate::log_warn;
+
+/// Largest `PERSISTENT_KERNEL_RANGE`: the roots and samples of a range are read back in one go.
+pub const MAX_PERSISTENT_RANGE: u32 = 65536;
+// Ranges handed over and not yet returned: one on the device while the previous one is verified
+const RANGES_IN_FLIGHT: usize = 2;
+/// Longest a single launch is planned to run. A kernel that holds a display GPU for
+/// about two seconds is reset by the driver's watchdog (Windows TDR), so launches are
+/// sized from the measured time per nonce to stay well below that.
+pub const MAX_LAUNCH_TIME: Duration = Duration::from_millis(500);
+
+/// Attempts `first_nonce`, `first_nonce + stride`, ... (`count` of them) of one
+/// persistent-kernel launch. Only the dense GEMM with BLAKE3 over seeded samples
+/// runs this way; everything else goes through the `AttemptPipeline`.
+#[derive(Debug, Clone)]
+pub struct NonceRange {
+    pub prev_hash: [u8; 32],
+    pub salt: Option<[u8; 32]>,
+    pub first_nonce: u32,
+    pub stride: u32,
+    pub count: u32,
+    pub sizes: Sizes,
+    pub scale: Requant,
+}
+
+impl NonceRange {
+    pub fn nonces(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.count).map(|i| self.first_nonce.wrapping_add(i.wrapping_mul(self.stride)))
+    }
+
+    /// Output elements of one attempt, every batch item included.
+    pub fn output_len(&self) -> usize {
+        self.sizes.batch.max(1) * self.sizes.m * self.sizes.n
+    }
+}
+
+/// What a persistent kernel returns for one nonce: the work root and the samples it
+/// was hashed from, which the host needs for the receipt and to verify the root.
+#[derive(Debug, Clone)]
+pub struct RangeRoot {
+    pub nonce: u32,
+    pub work_root: [u8; 32],
+    pub samples: Vec<i8>,
+}
+
+/// The range attempt by attempt on `executor`'s GEMM, for backends without a
+/// persistent kernel; produces exactly what the kernel would.
+pub fn run_range_on_host<E: Executor + ?Sized>(executor: &E, range: &NonceRange) -> anyhow::Result<Vec<RangeRoot>> {
+    // The fill and hash run here, outside anything a device timer covers
+    phases::record_host_compute();
+    range.nonces()
+        .map(|nonce| {
+            let input = generate_workload_inputs(Workload::Gemm, &range.prev_hash, nonce, range.salt.as_ref(), &range.sizes);
+            let y = execute_workload(executor, &input, &range.sizes, range.scale)?;
+            let (work_root, samples) = compute_work_root(&y, HashKind::Blake3, WorkSampling::Seeded, &range.prev_hash, nonce);
+            Ok(RangeRoot { nonce, work_root, samples })
+        })
+        .collect()
+}
+
+// One range's roots on their way to the verifier
+struct RangeBatch {
+    roots: Vec<RangeRoot>,
+    // The range's compute stage shared out evenly over its attempts
+    per_attempt: PhaseTimings,
+}
+
+/// Attempt driver for persistent kernels (`PERSISTENT_KERNEL_RANGE`).
+///
+/// Instead of one launch (and one output read back) per attempt, the executor runs
+/// a whole range of nonces per call (`Executor::run_nonce_range`) and returns just
+/// the work roots and their samples. A verifier thread checks every root before it
+/// is handed out: the samples must hash to the root, and `spot_check` of them are
+/// recomputed on the CPU from inputs regenerated on the host (with `spot_check` 0
+/// only the hash is checked). An attempt that fails either is handed out with a
+/// failed spot check, so it is never signed.
+///
+/// `range` is an upper bound: the first launch covers one nonce, and each later one
+/// as many as fit in `MAX_LAUNCH_TIME` at the time per nonce of the one before,
+/// growing at most twofold per launch.
+///
+/// Attempts come out in nonce order with an empty `y1`: the output never left the
+/// device. Their `elapsed_ms` and `phases` are the range's shared out evenly.
+pub struct NonceRangeDriver {
+    prev_hash: [u8; 32],
+    salt: Option<[u8; 32]>,
+    scale: Requant,
+    sizes: Sizes,
+    next_nonce: u32,
+    stride: u32,
+    range: u32,
+    // Nonces in the next launch, at most `range`
+    launch: u32,
+    // Attempts of each outstanding launch not yet returned, oldest first
+    in_flight: VecDeque<u32>,
+    verify_tx: Option<SyncSender<RangeBatch>>,
+    finished_rx: Receiver<(u32, AttemptOutput)>,
+    verifier: Option<JoinHandle<()>>,
+}
+
+impl NonceRangeDriver {
+    /// Start at `first_nonce`, stepping by `stride` like `AttemptPipeline::start_strided`,
+    /// `range` nonces per launch.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start(
+        prev_hash: [u8; 32],
+        salt: Option<[u8; 32]>,
+        requant: RequantParams,
+        first_nonce: u32,
+        stride: u32,
+        sizes: Sizes,
+        range: u32,
+        spot_check: usize,
+    ) -> Self {
+        let range = range.clamp(1, MAX_PERSISTENT_RANGE);
+        let scale = requant.resolve(salt.as_ref());
+        let (verify_tx, verify_rx) = sync_channel::<RangeBatch>(RANGES_IN_FLIGHT);
+        let (finished_tx, finished_rx) = sync_channel::<(u32, AttemptOutput)>(RANGES_IN_FLIGHT * range as usize);
+
+        let verifier = {
+            let sizes = sizes.clone();
+            std::thread::Builder::new()
+                .name("attempt-verify".into())
+                .spawn(move || {
+                    for batch in verify_rx {
+                        for root in batch.roots {
+                            let nonce = root.nonce;
+                            let out = verify_root(root, &prev_hash, salt.as_ref(), &sizes, scale, spot_check, batch.per_attempt);
+                            if finished_tx.send((nonce, out)).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                })
+                .expect("failed to spawn attempt-verify thread")
+        };
+
+        Self {
+            prev_hash,
+            salt,
+            scale,
+            sizes,
+            next_nonce: first_nonce,
+            stride: stride.max(1),
+            range,
+            launch: 1,
+            in_flight: VecDeque::with_capacity(RANGES_IN_FLIGHT),
+            verify_tx: Some(verify_tx),
+            finished_rx,
+            verifier: Some(verifier),
+        }
+    }
+
+    /// Launch ranges until `RANGES_IN_FLIGHT` are outstanding, then return the oldest verified attempt.
+    pub fn next<E: Executor + ?Sized>(&mut self, executor: &E) -> anyhow::Result<(u32, AttemptOutput)> {
+        while self.in_flight.len() < RANGES_IN_FLIGHT {
+            let range = NonceRange {
+                prev_hash: self.prev_hash,
+                salt: self.salt,
+                first_nonce: self.next_nonce,
+                stride: self.stride,
+                count: self.launch,
+                sizes: self.sizes.clone(),
+                scale: self.scale,
+            };
+            let start = Instant::now();
+            phases::reset();
+            let roots = executor.run_nonce_range(&range)?;
+            let compute = start.elapsed();
+            if roots.len() != range.count as usize || roots.iter().zip(range.nonces()).any(|(root, nonce)| root.nonce != nonce) {
+                anyhow::bail!("persistent kernel returned {} root(s) for the {} nonce(s) from {}", roots.len(), range.count, range.first_nonce);
+            }
+            let per_attempt = per_attempt(PhaseTimings::from_stages(Duration::ZERO, compute, Duration::ZERO), range.count);
+            self.verify_tx.as_ref()
+                .ok_or_else(|| anyhow!("persistent driver stopped"))?
+                .send(RangeBatch { roots, per_attempt })
+                .map_err(|_| anyhow!("attempt verifier exited"))?;
+            self.next_nonce = self.next_nonce.wrapping_add(range.count.wrapping_mul(self.stride));
+            self.in_flight.push_back(range.count);
+            self.launch = next_launch(range.count, compute, self.range);
+        }
+        let finished = self.finished_rx.recv().map_err(|_| anyhow!("attempt verifier exited"))?;
+        if let Some(left) = self.in_flight.front_mut() {
+            *left -= 1;
+            if *left == 0 {
+                self.in_flight.pop_front();
+            }
+        }
+        Ok(finished)
+    }
+}
+
+// Nonces for the launch after one of `count` that took `elapsed`: what fits in
+// MAX_LAUNCH_TIME at its time per nonce, at most twice `count` and `range`
+fn next_launch(count: u32, elapsed: Duration, range: u32) -> u32 {
+    let per_nonce = elapsed.as_secs_f64() / count.max(1) as f64;
+    let fit = if per_nonce > 0.0 { MAX_LAUNCH_TIME.as_secs_f64() / per_nonce } else { f64::INFINITY };
+    (fit.min(count.saturating_mul(2) as f64) as u32).clamp(1, range)
+}
+
+impl Drop for NonceRangeDriver {
+    fn drop(&mut self) {
+        // At most `RANGES_IN_FLIGHT` ranges are outstanding and the output channel holds
+        // that many attempts, so the verifier never blocks on send and exits once its
+        // input is closed
+        self.verify_tx.take();
+        if let Some(h) = self.verifier.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+// Check one root the device returned and turn it into an attempt. A root that is
+// not the hash of its samples fails the spot check outright.
+fn verify_root(
+    root: RangeRoot,
+    prev_hash: &[u8; 32],
+    salt: Option<&[u8; 32]>,
+    sizes: &Sizes,
+    scale: Requant,
+    spot_check: usize,
+    phases: PhaseTimings,
+) -> AttemptOutput {
+    let len = sizes.batch.max(1) * sizes.m * sizes.n;
+    let RangeRoot { nonce, work_root, samples } = root;
+    let root = SampledRoot { work_root, samples };
+    let check = if !root.is_intact(len) {
+        log_warn!("[persistent] nonce {}: the work_root is not the hash of the {} sample(s) returned with it", nonce, root.samples.len());
+        Some(SpotCheckResult { checked: 1, mismatches: 1, first_mismatch: None })
+    } else if spot_check == 0 {
+        None
+    } else {
+        let seed = derive_salted_seed(prev_hash, nonce, salt);
+        let input = generate_workload_inputs(Workload::Gemm, prev_hash, nonce, salt, sizes);
+        let indices = WorkSampling::Seeded.indices(len, prev_hash, nonce, None);
+        Some(spot_check_samples(&seed, &input, &root.samples, &indices, sizes, scale, spot_check))
+    };
+    let elapsed_ms = phases.fill_ms + phases.h2d_ms + phases.kernel_ms + phases.d2h_ms + phases.hash_ms;
+    AttemptOutput {
+        work_root: root.work_root,
+        y1: Vec::new(),
+        y2_samples: root.samples,
+        elapsed_ms: elapsed_ms.round() as u64,
+        phases,
+        spot_check: check,
+        sizes: sizes.clone(),
+    }
+}
+
+// `phases` of a whole range as the share of one of its `count` attempts
+fn per_attempt(phases: PhaseTimings, count: u32) -> PhaseTimings {
+    let share = |ms: f64| ms / count.max(1) as f64;
+    PhaseTimings {
+        fill_ms: share(phases.fill_ms),
+        h2d_ms: share(phases.h2d_ms),
+        kernel_ms: share(phases.kernel_ms),
+        d2h_ms: share(phases.d2h_ms),
+        hash_ms: share(phases.hash_ms),
+        device_kernel_ms: phases.device_kernel_ms.map(share),
+    }
+}
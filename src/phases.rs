@@ -0,0 +1,154 @@
+use std::cell::Cell;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::types::TimingConfidence;
+
+/// Absolute gap, in ms, always tolerated between the wall-clock and device kernel
+/// times: launch overhead and timer resolution dominate very short kernels.
+pub const DRIFT_SLACK_MS: f64 = 2.0;
+
+/// One stage of an attempt, as exported in `tops_worker_attempt_phase_ms{phase}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// PRNG input generation on the host.
+    Fill,
+    /// Host-to-device copies of the inputs.
+    H2d,
+    /// Everything else on the compute stage: the kernel, its launch and any memory-hard stage.
+    Kernel,
+    /// Device-to-host copy of the output.
+    D2h,
+    /// Output sampling and hashing into the work root.
+    Hash,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [Phase::Fill, Phase::H2d, Phase::Kernel, Phase::D2h, Phase::Hash];
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Phase::Fill => write!(f, "fill"),
+            Phase::H2d => write!(f, "h2d"),
+            Phase::Kernel => write!(f, "kernel"),
+            Phase::D2h => write!(f, "d2h"),
+            Phase::Hash => write!(f, "hash"),
+        }
+    }
+}
+
+/// Where an attempt's time went, in milliseconds. Backends without transfers
+/// (the CPU) report zero for `h2d_ms` and `d2h_ms`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhaseTimings {
+    pub fill_ms: f64,
+    pub h2d_ms: f64,
+    pub kernel_ms: f64,
+    pub d2h_ms: f64,
+    pub hash_ms: f64,
+    /// The kernels' own time by the device's timer (OpenCL profiling, CUDA events);
+    /// `None` when the backend has none or part of the compute stage ran on the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_kernel_ms: Option<f64>,
+}
+
+impl PhaseTimings {
+    /// Split the three pipeline stages into phases, taking the transfer times the
+    /// executor recorded on this thread while the compute stage ran.
+    pub fn from_stages(fill: Duration, compute: Duration, hash: Duration) -> Self {
+        let (h2d, d2h) = take_transfers();
+        PhaseTimings {
+            fill_ms: ms(fill),
+            h2d_ms: ms(h2d),
+            kernel_ms: ms(compute.saturating_sub(h2d + d2h)),
+            d2h_ms: ms(d2h),
+            hash_ms: ms(hash),
+            device_kernel_ms: take_device_kernel().map(ms),
+        }
+    }
+
+    /// Compare the wall-clock kernel phase with the device timer. A driver that
+    /// reports completion before the kernels finish (or long after) shows up as a
+    /// gap beyond `max_drift_pct` of the longer of the two, plus `DRIFT_SLACK_MS`.
+    pub fn timing_confidence(&self, max_drift_pct: f64) -> TimingConfidence {
+        let Some(device_ms) = self.device_kernel_ms else { return TimingConfidence::Unverified };
+        let allowed = self.kernel_ms.max(device_ms) * max_drift_pct / 100.0 + DRIFT_SLACK_MS;
+        if (self.kernel_ms - device_ms).abs() > allowed {
+            TimingConfidence::Drift
+        } else {
+            TimingConfidence::Verified
+        }
+    }
+
+    pub fn get(&self, phase: Phase) -> f64 {
+        match phase {
+            Phase::Fill => self.fill_ms,
+            Phase::H2d => self.h2d_ms,
+            Phase::Kernel => self.kernel_ms,
+            Phase::D2h => self.d2h_ms,
+            Phase::Hash => self.hash_ms,
+        }
+    }
+}
+
+thread_local! {
+    // (h2d, d2h) accumulated by the executor since the last `take_transfers`
+    static TRANSFERS: Cell<(Duration, Duration)> = const { Cell::new((Duration::ZERO, Duration::ZERO)) };
+    // Device-timed kernel time since the last `reset`, and whether any compute ran on the host meanwhile
+    static DEVICE_KERNEL: Cell<(Option<Duration>, bool)> = const { Cell::new((None, false)) };
+}
+
+/// Clear everything executors recorded on this thread, before an attempt's compute stage.
+pub fn reset() {
+    take_transfers();
+    take_device_kernel();
+}
+
+/// Called by executors, on the thread running the attempt, after copying inputs to the device.
+pub fn record_h2d(elapsed: Duration) {
+    TRANSFERS.with(|t| {
+        let (h2d, d2h) = t.get();
+        t.set((h2d + elapsed, d2h));
+    });
+}
+
+/// Called by executors, on the thread running the attempt, after copying the output back.
+pub fn record_d2h(elapsed: Duration) {
+    TRANSFERS.with(|t| {
+        let (h2d, d2h) = t.get();
+        t.set((h2d, d2h + elapsed));
+    });
+}
+
+/// Called by executors, on the thread running the attempt, with a kernel's time by the device's timer.
+pub fn record_device_kernel(elapsed: Duration) {
+    DEVICE_KERNEL.with(|t| {
+        let (device, host) = t.get();
+        t.set((Some(device.unwrap_or_default() + elapsed), host));
+    });
+}
+
+/// Called when part of the compute stage falls back to the CPU reference, which
+/// no device timer covers.
+pub fn record_host_compute() {
+    DEVICE_KERNEL.with(|t| t.set((t.get().0, true)));
+}
+
+/// Device-timed kernel time recorded on this thread since the last call, or `None`
+/// when nothing was device-timed or some compute ran on the host; resets it.
+pub fn take_device_kernel() -> Option<Duration> {
+    match DEVICE_KERNEL.with(|t| t.replace((None, false))) {
+        (Some(device), false) => Some(device),
+        _ => None,
+    }
+}
+
+/// Transfer times recorded on this thread since the last call, which resets them.
+pub fn take_transfers() -> (Duration, Duration) {
+    TRANSFERS.with(|t| t.replace((Duration::ZERO, Duration::ZERO)))
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,311 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use anyhow::anyhow;
+use crate::attempt::{compute_work_root, AttemptOutput, Executor};
+use crate::memhard::{run_memhard_stage, MemHardParams};
+use crate::phases::{self, PhaseTimings};
+use crate::prng::derive_salted_seed;
+use crate::size_distribution::AttemptSizes;
+use crate::spotcheck::{spot_check, spot_check_samples, SpotCheckResult};
+use crate::types::{Requant, RequantParams, Sizes};
+use crate::work_hash::{HashKind, OutputSampling, SampledRoot, WorkSampling};
+use crate::workload::{execute_workload, generate_workload_inputs, Workload, WorkloadInput};
+use crate::log_warn;
+
+struct PreparedInput {
+    nonce: u32,
+    sizes: Sizes,
+    input: WorkloadInput,
+    fill: Duration,
+}
+
+// What the compute stage hands the hasher: the whole output, or only the work_root
+// and samples when they were taken on the device
+enum ComputedY {
+    Full(Vec<i8>),
+    Sampled(SampledRoot),
+}
+
+struct ComputedOutput {
+    nonce: u32,
+    sizes: Sizes,
+    y: ComputedY,
+    fill: Duration,
+    compute: Duration,
+    phases: PhaseTimings,
+    spot_check: Option<SpotCheckResult>,
+    hash: HashKind,
+    sampling: WorkSampling,
+}
+
+/// Pipelined attempt driver.
+///
+/// A generator thread fills the next attempts' matrices from the PRNG and a hasher
+/// thread samples/hashes finished outputs, while the caller's thread keeps the
+/// executor busy with the workload's kernel (GEMM or SpMM). Up to `depth` attempts are in flight at once; with
+/// depth 1 the stages still run on separate threads but never overlap.
+///
+/// With a memory-hard stage configured it runs on the executor right before the
+/// kernel and counts towards the compute stage. An epoch salt, when set, goes into
+/// every seed and sets the kernel's requantization scale unless `with_requant` sets one.
+///
+/// The work_root is BLAKE3 of output samples drawn across the whole output by a
+/// commitment to all of it (see `WorkSampling::Committed`) unless `with_hash_kind`
+/// picks the epoch's hash or `with_work_sampling` seeded or the legacy prefix.
+///
+/// With `with_spot_check` a few output elements of every attempt are recomputed on
+/// the CPU right after the kernel (outside the timed compute stage).
+///
+/// With `with_device_sampling` dense attempts with a BLAKE3 work_root (and a
+/// sampling that does not need the whole output) leave their output on the device (`Executor::run_gemm_sampled`): only the samples and the
+/// root come back, the hasher checks that the samples hash to the root, and the
+/// spot check recomputes samples instead of output elements. Such attempts have an
+/// empty `y1`.
+///
+/// With `AttemptSizes::Drawn` every attempt draws its own sizes from its seed;
+/// `AttemptOutput::sizes` reports the sizes each attempt ran at.
+///
+/// Each attempt's `elapsed_ms` is the sum of its own fill, compute and hash stages,
+/// so it stays comparable with the serial `run_attempt`; `phases` splits the compute
+/// stage further into transfers and kernel time.
+pub struct AttemptPipeline {
+    depth: usize,
+    prev_hash: [u8;32],
+    salt: Option<[u8;32]>,
+    scale: Requant,
+    hash: HashKind,
+    sampling: WorkSampling,
+    memhard: Option<MemHardParams>,
+    spot_check: usize,
+    device_sampling: bool,
+    in_flight: usize,
+    stop: Arc<AtomicBool>,
+    prepared_rx: Option<Receiver<PreparedInput>>,
+    computed_tx: Option<SyncSender<ComputedOutput>>,
+    finished_rx: Receiver<(u32, AttemptOutput)>,
+    generator: Option<JoinHandle<()>>,
+    hasher: Option<JoinHandle<()>>,
+}
+
+impl AttemptPipeline {
+    /// Start generating attempts for `prev_hash` beginning at `first_nonce`.
+    pub fn start(
+        workload: Workload,
+        memhard: Option<MemHardParams>,
+        prev_hash: [u8;32],
+        salt: Option<[u8;32]>,
+        first_nonce: u32,
+        sizes: impl Into<AttemptSizes>,
+        depth: usize,
+    ) -> Self {
+        Self::start_strided(workload, memhard, prev_hash, salt, first_nonce, 1, sizes, depth)
+    }
+
+    /// Like `start`, but step the nonce by `stride` so several pipelines can share a nonce space.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_strided(
+        workload: Workload,
+        memhard: Option<MemHardParams>,
+        prev_hash: [u8;32],
+        salt: Option<[u8;32]>,
+        first_nonce: u32,
+        stride: u32,
+        sizes: impl Into<AttemptSizes>,
+        depth: usize,
+    ) -> Self {
+        let sizes = sizes.into();
+        let stride = stride.max(1);
+        let depth = depth.max(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        let (prepared_tx, prepared_rx) = sync_channel::<PreparedInput>(depth);
+        let (computed_tx, computed_rx) = sync_channel::<ComputedOutput>(depth);
+        let (finished_tx, finished_rx) = sync_channel::<(u32, AttemptOutput)>(depth);
+
+        let generator = {
+            let stop = Arc::clone(&stop);
+            std::thread::Builder::new()
+                .name("attempt-fill".into())
+                .spawn(move || {
+                    let mut nonce = first_nonce;
+                    while !stop.load(Ordering::Relaxed) {
+                        let start = Instant::now();
+                        let sizes = sizes.for_seed(&derive_salted_seed(&prev_hash, nonce, salt.as_ref()));
+                        let input = generate_workload_inputs(workload, &prev_hash, nonce, salt.as_ref(), &sizes);
+                        let input = PreparedInput { nonce, sizes, input, fill: start.elapsed() };
+                        // Blocks while the pipeline is full; errors once the consumer is gone
+                        if prepared_tx.send(input).is_err() {
+                            break;
+                        }
+                        nonce = nonce.wrapping_add(stride);
+                    }
+                })
+                .expect("failed to spawn attempt-fill thread")
+        };
+
+        let hasher = std::thread::Builder::new()
+            .name("attempt-hash".into())
+            .spawn(move || {
+                for computed in computed_rx {
+                    let start = Instant::now();
+                    let mut spot_check = computed.spot_check;
+                    let (work_root, y1, y2_samples) = match computed.y {
+                        ComputedY::Full(y1) => {
+                            let (work_root, y2_samples) = compute_work_root(&y1, computed.hash, computed.sampling, &prev_hash, computed.nonce);
+                            (work_root, y1, y2_samples)
+                        }
+                        ComputedY::Sampled(root) => {
+                            // The host's side of the device hash: a root that is not the hash
+                            // of its samples fails the spot check outright
+                            if !root.is_intact(computed.sizes.batch.max(1) * computed.sizes.m * computed.sizes.n) {
+                                log_warn!("[pipeline] nonce {}: the device work_root is not the hash of the {} sample(s) returned with it", computed.nonce, root.samples.len());
+                                spot_check = Some(SpotCheckResult { checked: 1, mismatches: 1, first_mismatch: None });
+                            }
+                            (root.work_root, Vec::new(), root.samples)
+                        }
+                    };
+                    let hash = start.elapsed();
+                    let total = computed.fill + computed.compute + hash;
+                    let out = AttemptOutput {
+                        work_root,
+                        y1,
+                        y2_samples,
+                        elapsed_ms: total.as_millis() as u64,
+                        phases: PhaseTimings { hash_ms: hash.as_secs_f64() * 1000.0, ..computed.phases },
+                        spot_check,
+                        sizes: computed.sizes,
+                    };
+                    if finished_tx.send((computed.nonce, out)).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn attempt-hash thread");
+
+        Self {
+            depth,
+            prev_hash,
+            salt,
+            scale: Requant::from_salt(salt.as_ref()),
+            hash: HashKind::default(),
+            sampling: WorkSampling::Committed,
+            memhard,
+            spot_check: 0,
+            device_sampling: false,
+            in_flight: 0,
+            stop,
+            prepared_rx: Some(prepared_rx),
+            computed_tx: Some(computed_tx),
+            finished_rx,
+            generator: Some(generator),
+            hasher: Some(hasher),
+        }
+    }
+
+    /// Requantize with the workload's configured scale and activation instead of the salt-derived ReLU.
+    pub fn with_requant(mut self, requant: RequantParams) -> Self {
+        self.scale = requant.resolve(self.salt.as_ref());
+        self
+    }
+
+    /// Hash the output samples into the work_root with the epoch's `hash` instead of BLAKE3.
+    pub fn with_hash_kind(mut self, hash: HashKind) -> Self {
+        self.hash = hash;
+        self
+    }
+
+    /// Pick the output samples hashed into the work_root with `sampling` instead of across the whole output.
+    pub fn with_work_sampling(mut self, sampling: WorkSampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Recompute `elements` seed-chosen output elements of each attempt on the CPU; 0 disables.
+    pub fn with_spot_check(mut self, elements: usize) -> Self {
+        self.spot_check = elements;
+        self
+    }
+
+    /// Take and hash the work_root samples of dense attempts on the device, reading
+    /// back only them and the root; applies to BLAKE3 work roots only.
+    pub fn with_device_sampling(mut self, enabled: bool) -> Self {
+        self.device_sampling = enabled;
+        self
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Run kernels until `depth` attempts are in flight, then return the oldest finished one.
+    pub fn next<E: Executor + ?Sized>(&mut self, executor: &E) -> anyhow::Result<(u32, AttemptOutput)> {
+        while self.in_flight < self.depth {
+            let input = self.prepared_rx.as_ref()
+                .ok_or_else(|| anyhow!("attempt pipeline stopped"))?
+                .recv()
+                .map_err(|_| anyhow!("attempt generator exited"))?;
+            let PreparedInput { nonce, sizes, input: mut workload_input, fill } = input;
+            let seed = derive_salted_seed(&self.prev_hash, nonce, self.salt.as_ref());
+            let start = Instant::now();
+            phases::reset();
+            if let Some(params) = &self.memhard {
+                workload_input.perturb(&run_memhard_stage(executor, &seed, params)?);
+            }
+            let y = match &workload_input {
+                WorkloadInput::Dense { a, b } if self.device_sampling && self.hash == HashKind::Blake3 && !self.sampling.needs_output() => {
+                    let sample = OutputSampling { sampling: self.sampling, prev_hash: self.prev_hash, nonce };
+                    ComputedY::Sampled(executor.run_gemm_sampled(a, b, &sizes, self.scale, &sample)?)
+                }
+                _ => ComputedY::Full(execute_workload(executor, &workload_input, &sizes, self.scale)?),
+            };
+            let compute = start.elapsed();
+            let spot_check = (self.spot_check > 0).then(|| match &y {
+                ComputedY::Full(y1) => spot_check(&seed, &workload_input, y1, &sizes, self.scale, self.spot_check),
+                ComputedY::Sampled(root) => {
+                    let len = sizes.batch.max(1) * sizes.m * sizes.n;
+                    let indices = self.sampling.indices(len, &self.prev_hash, nonce, None);
+                    spot_check_samples(&seed, &workload_input, &root.samples, &indices, &sizes, self.scale, self.spot_check)
+                }
+            });
+            // Transfers were recorded on this thread; the hash phase is filled in by the hasher
+            let computed = ComputedOutput {
+                nonce,
+                sizes,
+                y,
+                fill,
+                compute,
+                phases: PhaseTimings::from_stages(fill, compute, Duration::ZERO),
+                spot_check,
+                hash: self.hash,
+                sampling: self.sampling,
+            };
+            self.computed_tx.as_ref()
+                .ok_or_else(|| anyhow!("attempt pipeline stopped"))?
+                .send(computed)
+                .map_err(|_| anyhow!("attempt hasher exited"))?;
+            self.in_flight += 1;
+        }
+        let finished = self.finished_rx.recv().map_err(|_| anyhow!("attempt hasher exited"))?;
+        self.in_flight -= 1;
+        Ok(finished)
+    }
+}
+
+impl Drop for AttemptPipeline {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Dropping our channel ends unblocks the worker threads
+        self.prepared_rx.take();
+        self.computed_tx.take();
+        if let Some(h) = self.generator.take() {
+            let _ = h.join();
+        }
+        // At most `depth` results are in flight and the output channel holds `depth`,
+        // so the hasher never blocks on send and exits once its input is closed
+        if let Some(h) = self.hasher.take() {
+            let _ = h.join();
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use crate::config::Config;
+use crate::{log_error, log_info, log_warn};
+
+/// One reading from the site's power controller. Either field may be absent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PowerSignal {
+    #[serde(default, alias = "watts")]
+    pub available_watts: Option<f64>,
+    #[serde(default, alias = "price_per_kwh")]
+    pub price: Option<f64>,
+}
+
+impl PowerSignal {
+    /// Accepts a JSON object (`{"available_watts": 420, "price": 0.12}`) or a bare number of watts.
+    pub fn parse(payload: &[u8]) -> anyhow::Result<Self> {
+        let text = std::str::from_utf8(payload)?.trim();
+        if let Ok(watts) = text.parse::<f64>() {
+            return Ok(PowerSignal { available_watts: Some(watts), price: None });
+        }
+        Ok(serde_json::from_str(text)?)
+    }
+}
+
+/// A pluggable feed of power signals (HTTP poll, MQTT subscription, ...).
+#[async_trait]
+pub trait PowerSignalSource: Send {
+    fn describe(&self) -> String;
+    /// Wait for and return the next signal.
+    async fn next_signal(&mut self) -> anyhow::Result<PowerSignal>;
+}
+
+/// Polls an HTTP endpoint that returns a power signal.
+pub struct HttpPowerSource {
+    url: String,
+    interval: Duration,
+    client: reqwest::Client,
+    first: bool,
+}
+
+impl HttpPowerSource {
+    pub fn new(url: String, interval: Duration) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder().timeout(Duration::from_secs(10)).build()?;
+        Ok(Self { url, interval, client, first: true })
+    }
+}
+
+#[async_trait]
+impl PowerSignalSource for HttpPowerSource {
+    fn describe(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn next_signal(&mut self) -> anyhow::Result<PowerSignal> {
+        if !std::mem::take(&mut self.first) {
+            tokio::time::sleep(self.interval).await;
+        }
+        let body = self.client.get(&self.url).send().await?.error_for_status()?.bytes().await?;
+        PowerSignal::parse(&body)
+    }
+}
+
+/// Subscribes to an MQTT topic carrying power signals (`mqtt://host:port/topic`).
+#[cfg(feature = "mqtt")]
+pub struct MqttPowerSource {
+    url: String,
+    topic: String,
+    client: rumqttc::AsyncClient,
+    eventloop: rumqttc::EventLoop,
+}
+
+#[cfg(feature = "mqtt")]
+impl MqttPowerSource {
+    pub fn new(url: &str, client_id: &str) -> anyhow::Result<Self> {
+        let rest = url.strip_prefix("mqtt://")
+            .ok_or_else(|| anyhow::anyhow!("MQTT power source must be mqtt://host[:port]/topic"))?;
+        let (authority, topic) = rest.split_once('/')
+            .filter(|(_, t)| !t.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("MQTT power source URL has no topic"))?;
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((h, p)) => (h, p.parse::<u16>()?),
+            None => (authority, 1883),
+        };
+        let mut options = rumqttc::MqttOptions::new(client_id, host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        let (client, eventloop) = rumqttc::AsyncClient::new(options, 16);
+        Ok(Self { url: url.to_string(), topic: topic.to_string(), client, eventloop })
+    }
+}
+
+#[cfg(feature = "mqtt")]
+#[async_trait]
+impl PowerSignalSource for MqttPowerSource {
+    fn describe(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn next_signal(&mut self) -> anyhow::Result<PowerSignal> {
+        loop {
+            match self.eventloop.poll().await? {
+                // (Re)subscribe on every connect; sessions are not persistent
+                rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) => {
+                    self.client.subscribe(self.topic.clone(), rumqttc::QoS::AtLeastOnce).await?;
+                }
+                rumqttc::Event::Incoming(rumqttc::Packet::Publish(p)) if p.topic == self.topic => {
+                    return PowerSignal::parse(&p.payload);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Build the signal source selected by the URL scheme of POWER_SIGNAL_URL.
+pub fn source_from_config(config: &Config) -> anyhow::Result<Option<Box<dyn PowerSignalSource>>> {
+    let Some(url) = &config.power_signal_url else { return Ok(None) };
+    if url.starts_with("mqtt://") {
+        #[cfg(feature = "mqtt")]
+        {
+            let client_id = format!("tops-worker-power-{}", std::process::id());
+            Ok(Some(Box::new(MqttPowerSource::new(url, &client_id)?)))
+        }
+        #[cfg(not(feature = "mqtt"))]
+        Err(anyhow::anyhow!("mqtt:// power signals need the `mqtt` feature"))
+    } else {
+        Ok(Some(Box::new(HttpPowerSource::new(url.clone(), config.get_power_poll_interval())?)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerMode {
+    /// Enough power: run flat out.
+    Full,
+    /// Running a fraction of the time.
+    Throttled,
+    /// Not enough power or too expensive.
+    Paused,
+}
+
+impl PowerMode {
+    /// v2 receipt encoding (`perf_context`).
+    pub fn code(&self) -> u8 {
+        match self {
+            PowerMode::Full => 0,
+            PowerMode::Throttled => 1,
+            PowerMode::Paused => 2,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(PowerMode::Full),
+            1 => Some(PowerMode::Throttled),
+            2 => Some(PowerMode::Paused),
+            _ => None,
+        }
+    }
+}
+
+/// What to do when the signal source goes quiet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerStaleAction {
+    Run,
+    Pause,
+}
+
+impl std::str::FromStr for PowerStaleAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "run" => Ok(PowerStaleAction::Run),
+            "pause" => Ok(PowerStaleAction::Pause),
+            other => Err(format!("unknown power stale action '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for PowerStaleAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PowerStaleAction::Run => write!(f, "run"),
+            PowerStaleAction::Pause => write!(f, "pause"),
+        }
+    }
+}
+
+/// Thresholds for turning signals into a duty cycle.
+///
+/// Watts and price each have a pause threshold and a (looser) resume threshold, so
+/// a signal hovering around one value does not flap the worker on and off.
+#[derive(Debug, Clone)]
+pub struct PowerPolicy {
+    pub pause_below_watts: f64,
+    pub resume_above_watts: f64,
+    pub full_duty_watts: f64,
+    pub pause_above_price: Option<f64>,
+    pub resume_below_price: Option<f64>,
+    pub min_duty: f64,
+    pub stale_after: Duration,
+    pub stale_action: PowerStaleAction,
+}
+
+impl PowerPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            pause_below_watts: config.power_pause_watts,
+            resume_above_watts: config.power_resume_watts,
+            full_duty_watts: config.power_full_watts,
+            pause_above_price: config.power_max_price,
+            resume_below_price: config.power_resume_price.or(config.power_max_price),
+            min_duty: config.power_min_duty,
+            stale_after: Duration::from_secs(config.power_stale_secs),
+            stale_action: config.power_stale_action,
+        }
+    }
+
+    /// Duty cycle in [0, 1] for `signal`, given whether we are currently paused.
+    pub fn evaluate(&self, signal: &PowerSignal, paused: bool) -> (f64, String) {
+        if let (Some(price), Some(pause_above)) = (signal.price, self.pause_above_price) {
+            let limit = if paused { self.resume_below_price.unwrap_or(pause_above) } else { pause_above };
+            if price > limit {
+                return (0.0, format!("price {:.4} above {:.4}", price, limit));
+            }
+        }
+        let Some(watts) = signal.available_watts else {
+            return (1.0, "no watts reported".to_string());
+        };
+        let floor = if paused { self.resume_above_watts } else { self.pause_below_watts };
+        if watts < floor {
+            return (0.0, format!("{:.0} W below {:.0} W", watts, floor));
+        }
+        let span = (self.full_duty_watts - self.pause_below_watts).max(f64::EPSILON);
+        let duty = ((watts - self.pause_below_watts) / span).clamp(self.min_duty, 1.0);
+        (duty, format!("{:.0} W available", watts))
+    }
+}
+
+/// Current power state, reported in /status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerState {
+    pub source: String,
+    pub mode: PowerMode,
+    pub duty_cycle: f64,
+    pub reason: String,
+    pub last_signal: Option<PowerSignal>,
+    pub stale: bool,
+    pub updated_at: String,
+}
+
+/// Applies a power policy to a signal source and paces the attempt loop.
+pub struct PowerController {
+    policy: PowerPolicy,
+    state: Mutex<PowerState>,
+    last_signal_at: Mutex<Option<Instant>>,
+}
+
+impl PowerController {
+    pub fn new(policy: PowerPolicy, source: String) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(PowerState {
+                source,
+                mode: PowerMode::Full,
+                duty_cycle: 1.0,
+                reason: "waiting for first signal".to_string(),
+                last_signal: None,
+                stale: false,
+                updated_at: chrono::Utc::now().to_rfc3339(),
+            }),
+            last_signal_at: Mutex::new(None),
+        }
+    }
+
+    /// Spawn a task feeding `source` into a new controller.
+    pub fn spawn(policy: PowerPolicy, mut source: Box<dyn PowerSignalSource>) -> Arc<Self> {
+        let controller = Arc::new(Self::new(policy, source.describe()));
+        let feed = Arc::clone(&controller);
+        tokio::spawn(async move {
+            loop {
+                match source.next_signal().await {
+                    Ok(signal) => feed.apply(signal),
+                    Err(e) => {
+                        log_error!("[power] signal source error: {}", e);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+        controller
+    }
+
+    pub fn apply(&self, signal: PowerSignal) {
+        if let Ok(mut at) = self.last_signal_at.lock() {
+            *at = Some(Instant::now());
+        }
+        let Ok(mut state) = self.state.lock() else { return };
+        let (duty, reason) = self.policy.evaluate(&signal, state.mode == PowerMode::Paused);
+        let mode = mode_for(duty);
+        if mode != state.mode {
+            log_info!("[power] {:?} -> {:?} ({})", state.mode, mode, reason);
+        }
+        *state = PowerState {
+            source: state.source.clone(),
+            mode,
+            duty_cycle: duty,
+            reason,
+            last_signal: Some(signal),
+            stale: false,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        };
+    }
+
+    pub fn state(&self) -> PowerState {
+        self.refresh_staleness();
+        self.state.lock().map(|s| s.clone()).unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    // Past `stale_after` without a signal, fall back to the configured stale action
+    fn refresh_staleness(&self) {
+        let last = self.last_signal_at.lock().ok().and_then(|l| *l);
+        let stale = match last {
+            Some(at) => at.elapsed() > self.policy.stale_after,
+            None => false,
+        };
+        if !stale {
+            return;
+        }
+        if let Ok(mut state) = self.state.lock() {
+            if !state.stale {
+                log_warn!("[power] no signal for {}s, applying stale action '{}'",
+                    self.policy.stale_after.as_secs(), self.policy.stale_action);
+                state.stale = true;
+                state.duty_cycle = match self.policy.stale_action {
+                    PowerStaleAction::Run => 1.0,
+                    PowerStaleAction::Pause => 0.0,
+                };
+                state.mode = mode_for(state.duty_cycle);
+                state.reason = "signal stale".to_string();
+                state.updated_at = chrono::Utc::now().to_rfc3339();
+            }
+        }
+    }
+
+    /// Block while paused, checking again every second.
+    pub async fn wait_until_running(&self) {
+        let mut announced = false;
+        while self.state().mode == PowerMode::Paused {
+            if !announced {
+                log_info!("[power] paused, waiting for power");
+                announced = true;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+}
+
+/// Idle time after an attempt that took `busy` so the long-run busy fraction matches `duty`.
+pub fn idle_for(duty: f64, busy: Duration) -> Duration {
+    if duty > 0.0 && duty < 1.0 {
+        busy.mul_f64((1.0 - duty) / duty)
+    } else {
+        Duration::ZERO
+    }
+}
+
+fn mode_for(duty: f64) -> PowerMode {
+    if duty <= 0.0 {
+        PowerMode::Paused
+    } else if duty >= 1.0 {
+        PowerMode::Full
+    } else {
+        PowerMode::Throttled
+    }
+}
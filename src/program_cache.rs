@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::integrity::write_atomic;
+use crate::log_warn;
+
+// Bump when the cache key or file layout changes so old binaries are never picked up
+const KEY_CONTEXT: &str = "tops-worker opencl program cache v2";
+// Each file starts with the BLAKE3 digest of the binary that follows it
+const DIGEST_LEN: usize = 32;
+
+/// Compiled OpenCL program binaries, one file per build.
+///
+/// The key covers everything that changes the compiler's output: device,
+/// driver, build options and kernel sources. Every file carries a BLAKE3
+/// digest of its binary, so a truncated or corrupted one is discarded before
+/// it reaches the driver; a stale binary is detected when the driver refuses
+/// it. Either way the caller rebuilds from source.
+pub struct ProgramCache {
+    dir: PathBuf,
+}
+
+impl ProgramCache {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self { dir: dir.as_ref().to_path_buf() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn key(device_name: &str, driver_version: &str, build_options: &str, sources: &[&str]) -> String {
+        let mut hasher = blake3::Hasher::new_derive_key(KEY_CONTEXT);
+        for part in [device_name, driver_version, build_options].into_iter().chain(sources.iter().copied()) {
+            // Length-prefix each part so boundaries cannot shift between fields
+            hasher.update(&(part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// The cached binary for `key`, or `None` on a miss. A file whose digest
+    /// does not match its contents is deleted and reported as a miss.
+    pub fn load(&self, key: &str) -> Option<Vec<u8>> {
+        let mut file = fs::read(self.path_for(key)).ok()?;
+        if file.len() > DIGEST_LEN && blake3::hash(&file[DIGEST_LEN..]).as_bytes()[..] == file[..DIGEST_LEN] {
+            return Some(file.split_off(DIGEST_LEN));
+        }
+        log_warn!("[opencl] cached program binary {} failed its checksum, discarding", key);
+        self.remove(key);
+        None
+    }
+
+    pub fn store(&self, key: &str, binary: &[u8]) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut file = Vec::with_capacity(DIGEST_LEN + binary.len());
+        file.extend_from_slice(blake3::hash(binary).as_bytes());
+        file.extend_from_slice(binary);
+        write_atomic(&self.path_for(key), &file)
+    }
+
+    pub fn remove(&self, key: &str) {
+        let _ = fs::remove_file(self.path_for(key));
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.bin", key))
+    }
+}
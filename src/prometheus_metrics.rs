@@ -1,10 +1,104 @@
 
 use prometheus_client::{
-    encoding::text::encode,
-    metrics::{counter::Counter, gauge::Gauge, histogram::Histogram},
+    encoding::{text::encode, EncodeLabelSet},
+    metrics::{counter::Counter, family::Family, gauge::Gauge, histogram::Histogram},
     registry::Registry,
 };
+use std::sync::atomic::AtomicU64;
+use crate::device_memory::DeviceMemory;
 use crate::metrics::ErrorType;
+use crate::spotcheck::SpotCheckResult;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct StreamLabels {
+    pub stream: String,
+    pub backend: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct EncodingLabels {
+    pub encoding: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RejectionLabels {
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct PhaseLabels {
+    pub phase: String,
+    pub backend: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct KeyRotationLabels {
+    pub device_did: String,
+    pub source: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct DidLabels {
+    pub device_did: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct SourceLabels {
+    pub source: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ResponseLabels {
+    pub kind: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CircuitLabels {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct TimingLabels {
+    pub confidence: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ConnectionLabels {
+    pub outcome: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct StateFileLabels {
+    pub file: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ChallengeLabels {
+    pub outcome: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ShadowLabels {
+    pub outcome: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct FailureLabels {
+    pub kind: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct LatencyLabels {
+    pub phase: String,
+    pub status_class: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct IdentityLabels {
+    pub device_did: String,
+    pub outcome: String,
+}
 
 pub struct PrometheusMetrics {
     registry: Registry,
@@ -17,15 +111,77 @@ pub struct PrometheusMetrics {
     network_errors: Counter,
     signature_errors: Counter,
     validation_errors: Counter,
+    selftest_failures: Counter,
+    spot_checked_elements: Counter,
+    silent_corruptions: Counter,
+    liveness_reports: Counter,
+    liveness_failures: Counter,
+    metrics_pushes: Counter,
+    metrics_push_failures: Counter,
+    epoch_summaries: Counter,
+    epoch_summary_failures: Counter,
+    evidence_samples: Counter,
+    stream_attempts: Family<StreamLabels, Counter>,
+    compressed_submissions: Family<EncodingLabels, Counter>,
+    submit_bytes_saved: Family<EncodingLabels, Counter>,
+    identity_receipts: Family<IdentityLabels, Counter>,
+    rejections: Family<RejectionLabels, Counter>,
+    key_rotations: Family<KeyRotationLabels, Counter>,
+    epoch_transitions: Family<SourceLabels, Counter>,
+    memory_downscales: Counter,
+    unauthenticated_responses: Family<ResponseLabels, Counter>,
+    duplicates_suppressed: Counter,
+    receipt_timing: Family<TimingLabels, Counter>,
+    circuit_transitions: Family<CircuitLabels, Counter>,
+    aggregator_requests: Counter,
+    aggregator_connections: Family<ConnectionLabels, Counter>,
+    aggregator_dns: Family<ConnectionLabels, Counter>,
+    state_tampering: Family<StateFileLabels, Counter>,
+    challenges: Family<ChallengeLabels, Counter>,
+    submit_failures: Family<FailureLabels, Counter>,
+    submit_retries: Family<FailureLabels, Counter>,
+    shadow_receipts: Family<ShadowLabels, Counter>,
     
     // Gauges
     uptime_seconds: Gauge<i64>,
     consecutive_failures: Gauge<i64>,
     success_rate: Gauge<i64>,
+    effective_rate_per_second: Gauge<f64, AtomicU64>,
+    pacing_delay_seconds: Gauge<f64, AtomicU64>,
+    receipts_per_second: Gauge<f64, AtomicU64>,
+    stream_last_duration_ms: Family<StreamLabels, Gauge<i64>>,
+    queue_depth: Gauge<i64>,
+    evidence_bytes: Gauge<i64>,
+    key_epoch: Family<DidLabels, Gauge<i64>>,
+    epoch_id: Gauge<i64>,
+    epoch_attempts: Gauge<i64>,
+    epoch_successful_attempts: Gauge<i64>,
+    device_memory_total_bytes: Gauge<i64>,
+    device_memory_free_bytes: Gauge<i64>,
+    device_memory_used_bytes: Gauge<i64>,
+    tops_per_watt: Gauge<f64, AtomicU64>,
+    fleet_config_version: Gauge<i64>,
+    watch_estimated_tops: Gauge<f64, AtomicU64>,
+    backpressure_mode: Gauge<i64>,
+    clock_offset_ms: Gauge<i64>,
+    challenges_pending: Gauge<i64>,
+    update_available: Gauge<i64>,
+    shadow_enabled: Gauge<i64>,
     
     // Histograms
     attempt_duration_ms: Histogram,
-    network_latency_ms: Histogram,
+    network_latency_ms: Family<LatencyLabels, Histogram, fn() -> Histogram>,
+    attempt_phase_ms: Family<PhaseLabels, Histogram, fn() -> Histogram>,
+    attempt_energy_joules: Histogram,
+    aggregator_handshake_ms: Histogram,
+    challenge_response_ms: Histogram,
+    shadow_latency_ms: Histogram,
+}
+
+impl Default for PrometheusMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PrometheusMetrics {
@@ -40,18 +196,90 @@ impl PrometheusMetrics {
         let network_errors = Counter::default();
         let signature_errors = Counter::default();
         let validation_errors = Counter::default();
+        let selftest_failures = Counter::default();
+        let spot_checked_elements = Counter::default();
+        let silent_corruptions = Counter::default();
+        let liveness_reports = Counter::default();
+        let liveness_failures = Counter::default();
+        let metrics_pushes = Counter::default();
+        let metrics_push_failures = Counter::default();
+        let epoch_summaries = Counter::default();
+        let epoch_summary_failures = Counter::default();
+        let evidence_samples = Counter::default();
+        let stream_attempts = Family::<StreamLabels, Counter>::default();
+        let compressed_submissions = Family::<EncodingLabels, Counter>::default();
+        let submit_bytes_saved = Family::<EncodingLabels, Counter>::default();
+        let identity_receipts = Family::<IdentityLabels, Counter>::default();
+        let rejections = Family::<RejectionLabels, Counter>::default();
+        let key_rotations = Family::<KeyRotationLabels, Counter>::default();
+        let epoch_transitions = Family::<SourceLabels, Counter>::default();
+        let memory_downscales = Counter::default();
+        let unauthenticated_responses = Family::<ResponseLabels, Counter>::default();
+        let duplicates_suppressed = Counter::default();
+        let receipt_timing = Family::<TimingLabels, Counter>::default();
+        let circuit_transitions = Family::<CircuitLabels, Counter>::default();
+        let aggregator_requests = Counter::default();
+        let aggregator_connections = Family::<ConnectionLabels, Counter>::default();
+        let aggregator_dns = Family::<ConnectionLabels, Counter>::default();
+        let state_tampering = Family::<StateFileLabels, Counter>::default();
+        let challenges = Family::<ChallengeLabels, Counter>::default();
+        let submit_failures = Family::<FailureLabels, Counter>::default();
+        let submit_retries = Family::<FailureLabels, Counter>::default();
+        let shadow_receipts = Family::<ShadowLabels, Counter>::default();
         
         // Initialize gauges
         let uptime_seconds = Gauge::default();
         let consecutive_failures = Gauge::default();
         let success_rate = Gauge::default();
+        let effective_rate_per_second = Gauge::<f64, AtomicU64>::default();
+        let pacing_delay_seconds = Gauge::<f64, AtomicU64>::default();
+        let receipts_per_second = Gauge::<f64, AtomicU64>::default();
+        let stream_last_duration_ms = Family::<StreamLabels, Gauge<i64>>::default();
+        let queue_depth = Gauge::default();
+        let evidence_bytes = Gauge::default();
+        let key_epoch = Family::<DidLabels, Gauge<i64>>::default();
+        let epoch_id = Gauge::default();
+        let epoch_attempts = Gauge::default();
+        let epoch_successful_attempts = Gauge::default();
+        let device_memory_total_bytes = Gauge::default();
+        let device_memory_free_bytes = Gauge::default();
+        let device_memory_used_bytes = Gauge::default();
+        let tops_per_watt = Gauge::<f64, AtomicU64>::default();
+        let fleet_config_version = Gauge::default();
+        let watch_estimated_tops = Gauge::<f64, AtomicU64>::default();
+        let backpressure_mode = Gauge::default();
+        let clock_offset_ms = Gauge::default();
+        let challenges_pending = Gauge::default();
+        let update_available = Gauge::default();
+        let shadow_enabled = Gauge::default();
         
         // Initialize histograms with custom buckets
         let attempt_duration_ms = Histogram::new(
             [10.0, 25.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0].into_iter()
         );
-        let network_latency_ms = Histogram::new(
-            [1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0].into_iter()
+        // A LAN aggregator answers in a few milliseconds, a congested uplink in seconds
+        let network_latency_ms = Family::<LatencyLabels, Histogram, fn() -> Histogram>::new_with_constructor(|| {
+            Histogram::new([1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0].into_iter())
+        });
+        // Phases range from sub-millisecond copies to multi-second kernels
+        let attempt_phase_ms = Family::<PhaseLabels, Histogram, fn() -> Histogram>::new_with_constructor(|| {
+            Histogram::new([0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0].into_iter())
+        });
+        // From a small CPU attempt to a large GPU one at a few hundred watts
+        let attempt_energy_joules = Histogram::new(
+            [0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0].into_iter()
+        );
+        // From a LAN connect to a full TLS handshake across continents
+        let aggregator_handshake_ms = Histogram::new(
+            [1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0].into_iter()
+        );
+        // Challenge deadlines run from a few seconds to a minute
+        let challenge_response_ms = Histogram::new(
+            [100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0, 30000.0, 60000.0].into_iter()
+        );
+        // Same buckets as the primary's network latency, so the two can be compared
+        let shadow_latency_ms = Histogram::new(
+            [1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0].into_iter()
         );
         
         // Register metrics
@@ -90,6 +318,156 @@ impl PrometheusMetrics {
             "Total number of validation errors",
             validation_errors.clone(),
         );
+        registry.register(
+            "tops_worker_selftest_failures",
+            "Total number of GEMM self-tests that disagreed with the CPU reference",
+            selftest_failures.clone(),
+        );
+        registry.register(
+            "tops_worker_spot_checked_elements",
+            "Total number of attempt output elements recomputed on the CPU",
+            spot_checked_elements.clone(),
+        );
+        registry.register(
+            "tops_worker_silent_corruptions",
+            "Total number of attempts whose output disagreed with the CPU spot-check",
+            silent_corruptions.clone(),
+        );
+        registry.register(
+            "tops_worker_liveness_reports",
+            "Total number of signed liveness reports accepted by the liveness endpoint",
+            liveness_reports.clone(),
+        );
+        registry.register(
+            "tops_worker_liveness_failures",
+            "Total number of liveness reports that could not be delivered",
+            liveness_failures.clone(),
+        );
+        registry.register(
+            "tops_worker_metrics_pushes",
+            "Total number of metric pushes accepted by the Pushgateway",
+            metrics_pushes.clone(),
+        );
+        registry.register(
+            "tops_worker_metrics_push_failures",
+            "Total number of metric pushes that could not be delivered",
+            metrics_push_failures.clone(),
+        );
+        registry.register(
+            "tops_worker_epoch_summaries",
+            "Total number of signed epoch summaries accepted by the aggregator",
+            epoch_summaries.clone(),
+        );
+        registry.register(
+            "tops_worker_epoch_summary_failures",
+            "Total number of epoch summaries that could not be delivered",
+            epoch_summary_failures.clone(),
+        );
+        registry.register(
+            "tops_worker_evidence_samples",
+            "Total number of attempt outputs stored as audit evidence",
+            evidence_samples.clone(),
+        );
+        registry.register(
+            "tops_worker_stream_attempts",
+            "Total number of attempts computed per attempt stream",
+            stream_attempts.clone(),
+        );
+        registry.register(
+            "tops_worker_compressed_submissions",
+            "Receipt submissions sent with a compressed body, per Content-Encoding",
+            compressed_submissions.clone(),
+        );
+        registry.register(
+            "tops_worker_submit_bytes_saved",
+            "Request body bytes saved by submission compression, per Content-Encoding",
+            submit_bytes_saved.clone(),
+        );
+        registry.register(
+            "tops_worker_identity_receipts",
+            "Receipts submitted per signing identity and outcome",
+            identity_receipts.clone(),
+        );
+        registry.register(
+            "tops_worker_rejections",
+            "Receipts the aggregator rejected, per reason code",
+            rejections.clone(),
+        );
+        registry.register(
+            "tops_worker_key_rotations",
+            "Signing key rotations per identity and source (file, admin)",
+            key_rotations.clone(),
+        );
+        registry.register(
+            "tops_worker_epoch_transitions",
+            "Epoch changes, per source (response, feed)",
+            epoch_transitions.clone(),
+        );
+        registry.register(
+            "tops_worker_memory_downscales",
+            "Times the attempt sizes were stepped down after a device allocation failure",
+            memory_downscales.clone(),
+        );
+        registry.register(
+            "tops_worker_unauthenticated_responses",
+            "Aggregator responses ignored for a missing or invalid signature, per kind (submit, epoch)",
+            unauthenticated_responses.clone(),
+        );
+        registry.register(
+            "tops_worker_duplicate_submissions_suppressed",
+            "Receipt resends dropped before sending because their idempotency key was already delivered",
+            duplicates_suppressed.clone(),
+        );
+        registry.register(
+            "tops_worker_receipt_timing",
+            "Receipts per timing confidence (verified, unverified, drift) from comparing wall-clock and device kernel times",
+            receipt_timing.clone(),
+        );
+        registry.register(
+            "tops_worker_circuit_transitions",
+            "Submission circuit breaker state changes, per previous and new state (closed, open, half-open)",
+            circuit_transitions.clone(),
+        );
+        registry.register(
+            "tops_worker_aggregator_requests",
+            "HTTP requests sent to the aggregator, over new or pooled connections",
+            aggregator_requests.clone(),
+        );
+        registry.register(
+            "tops_worker_aggregator_connections",
+            "Aggregator connections opened (new) or that failed to open (failed)",
+            aggregator_connections.clone(),
+        );
+        registry.register(
+            "tops_worker_aggregator_dns",
+            "Aggregator host name answers per outcome (resolved, cached, override, stale, failed)",
+            aggregator_dns.clone(),
+        );
+        registry.register(
+            "tops_worker_state_tampering",
+            "State files that failed their integrity check, per file (queue, journal, algo_cache)",
+            state_tampering.clone(),
+        );
+        registry.register(
+            "tops_worker_challenges",
+            "Aggregator liveness challenges per outcome (met, late, expired, failed, dropped)",
+            challenges.clone(),
+        );
+        registry.register(
+            "tops_worker_submit_failures",
+            "Receipt submissions that did not get through, per failure kind (dns, connect_timeout, connect, tls, timeout, rejected, duplicate, throttled, server_error, unauthenticated, network)",
+            submit_failures.clone(),
+        );
+        registry.register(
+            "tops_worker_submit_retries",
+            "Receipt resends by the HTTP transport's retry policy, per failure kind of the try before",
+            submit_retries.clone(),
+        );
+        registry.register(
+            "tops_worker_shadow_receipts",
+            "Receipt copies to the shadow aggregator per outcome (accepted, rejected, throttled, failed, error, dropped)",
+            shadow_receipts.clone(),
+        );
         registry.register(
             "tops_worker_uptime_seconds",
             "Worker uptime in seconds",
@@ -105,6 +483,111 @@ impl PrometheusMetrics {
             "Success rate as a percentage (multiplied by 100)",
             success_rate.clone(),
         );
+        registry.register(
+            "tops_worker_effective_rate_per_second",
+            "Current effective attempt rate after adaptive back-off",
+            effective_rate_per_second.clone(),
+        );
+        registry.register(
+            "tops_worker_pacing_delay_seconds",
+            "Pause before the next attempt chosen by the pacing controller",
+            pacing_delay_seconds.clone(),
+        );
+        registry.register(
+            "tops_worker_receipts_per_second",
+            "Accepted receipts per second across all attempt streams",
+            receipts_per_second.clone(),
+        );
+        registry.register(
+            "tops_worker_stream_last_duration_ms",
+            "Duration of the latest attempt per attempt stream in milliseconds",
+            stream_last_duration_ms.clone(),
+        );
+        registry.register(
+            "tops_worker_queue_depth",
+            "Receipts buffered on disk awaiting delivery",
+            queue_depth.clone(),
+        );
+        registry.register(
+            "tops_worker_evidence_bytes",
+            "Bytes of compressed audit evidence currently kept on disk",
+            evidence_bytes.clone(),
+        );
+        registry.register(
+            "tops_worker_key_epoch",
+            "Key epoch (rotation count) of the active signing key per identity",
+            key_epoch.clone(),
+        );
+        registry.register(
+            "tops_worker_epoch_id",
+            "Epoch the current attempts are chained to",
+            epoch_id.clone(),
+        );
+        registry.register(
+            "tops_worker_epoch_attempts",
+            "Attempts in the current epoch",
+            epoch_attempts.clone(),
+        );
+        registry.register(
+            "tops_worker_epoch_successful_attempts",
+            "Successful attempts in the current epoch",
+            epoch_successful_attempts.clone(),
+        );
+        registry.register(
+            "tops_worker_device_memory_total_bytes",
+            "Device memory reported by the backend",
+            device_memory_total_bytes.clone(),
+        );
+        registry.register(
+            "tops_worker_device_memory_free_bytes",
+            "Free device memory, where the backend reports it (CUDA)",
+            device_memory_free_bytes.clone(),
+        );
+        registry.register(
+            "tops_worker_device_memory_used_bytes",
+            "Device memory the in-flight attempts allocate at the current sizes",
+            device_memory_used_bytes.clone(),
+        );
+        registry.register(
+            "tops_worker_tops_per_watt",
+            "Energy efficiency of the latest metered attempt in TOPS per watt (tera-operations per joule)",
+            tops_per_watt.clone(),
+        );
+        registry.register(
+            "tops_worker_fleet_config_version",
+            "Version of the fleet config document in effect (0 before the first)",
+            fleet_config_version.clone(),
+        );
+        registry.register(
+            "tops_worker_watch_estimated_tops",
+            "Estimated TOPS the worker would contribute, under WATCH_ONLY=1",
+            watch_estimated_tops.clone(),
+        );
+        registry.register(
+            "tops_worker_backpressure_mode",
+            "Attempt throttling on the submission backlog: 0 running, 1 slowed, 2 paused",
+            backpressure_mode.clone(),
+        );
+        registry.register(
+            "tops_worker_clock_offset_ms",
+            "Aggregator or NTP time minus local time in milliseconds; positive when the local clock is behind",
+            clock_offset_ms.clone(),
+        );
+        registry.register(
+            "tops_worker_challenges_pending",
+            "Aggregator liveness challenges waiting to be answered",
+            challenges_pending.clone(),
+        );
+        registry.register(
+            "tops_worker_update_available",
+            "1 when the signed release manifest names a newer version than the running one",
+            update_available.clone(),
+        );
+        registry.register(
+            "tops_worker_shadow_enabled",
+            "1 while receipts are copied to the shadow aggregator, 0 when switched off",
+            shadow_enabled.clone(),
+        );
         registry.register(
             "tops_worker_attempt_duration_ms",
             "Duration of attempts in milliseconds",
@@ -112,9 +595,34 @@ impl PrometheusMetrics {
         );
         registry.register(
             "tops_worker_network_latency_ms",
-            "Network request latency in milliseconds",
+            "Submission request latency in milliseconds, to the response headers (ttfb) and the full response (total), by status class",
             network_latency_ms.clone(),
         );
+        registry.register(
+            "tops_worker_attempt_phase_ms",
+            "Attempt time per phase (fill, h2d, kernel, d2h, hash) in milliseconds",
+            attempt_phase_ms.clone(),
+        );
+        registry.register(
+            "tops_worker_attempt_energy_joules",
+            "Estimated energy per attempt in joules, from the power sensor",
+            attempt_energy_joules.clone(),
+        );
+        registry.register(
+            "tops_worker_aggregator_handshake_ms",
+            "Time to open an aggregator connection (TCP connect, proxy and TLS handshake) in milliseconds",
+            aggregator_handshake_ms.clone(),
+        );
+        registry.register(
+            "tops_worker_challenge_response_ms",
+            "Time from receiving a liveness challenge to the aggregator accepting its answer in milliseconds",
+            challenge_response_ms.clone(),
+        );
+        registry.register(
+            "tops_worker_shadow_latency_ms",
+            "Time to deliver a receipt copy to the shadow aggregator in milliseconds",
+            shadow_latency_ms.clone(),
+        );
         
         Self {
             registry,
@@ -125,11 +633,67 @@ impl PrometheusMetrics {
             network_errors,
             signature_errors,
             validation_errors,
+            selftest_failures,
+            spot_checked_elements,
+            silent_corruptions,
+            liveness_reports,
+            liveness_failures,
+            metrics_pushes,
+            metrics_push_failures,
+            epoch_summaries,
+            epoch_summary_failures,
+            evidence_samples,
+            stream_attempts,
+            compressed_submissions,
+            submit_bytes_saved,
+            identity_receipts,
+            rejections,
+            key_rotations,
+            epoch_transitions,
+            memory_downscales,
+            unauthenticated_responses,
+            duplicates_suppressed,
+            receipt_timing,
+            circuit_transitions,
+            aggregator_requests,
+            aggregator_connections,
+            aggregator_dns,
+            state_tampering,
+            challenges,
+            submit_failures,
+            submit_retries,
+            shadow_receipts,
             uptime_seconds,
             consecutive_failures,
             success_rate,
+            effective_rate_per_second,
+            pacing_delay_seconds,
+            receipts_per_second,
+            stream_last_duration_ms,
+            queue_depth,
+            evidence_bytes,
+            key_epoch,
+            epoch_id,
+            epoch_attempts,
+            epoch_successful_attempts,
+            device_memory_total_bytes,
+            device_memory_free_bytes,
+            device_memory_used_bytes,
+            tops_per_watt,
+            fleet_config_version,
+            watch_estimated_tops,
+            backpressure_mode,
+            clock_offset_ms,
+            challenges_pending,
+            update_available,
+            shadow_enabled,
             attempt_duration_ms,
             network_latency_ms,
+            attempt_phase_ms,
+            attempt_energy_joules,
+            aggregator_handshake_ms,
+            challenge_response_ms,
+            shadow_latency_ms,
         }
     }
     
@@ -147,6 +711,11 @@ impl PrometheusMetrics {
             0
         };
         self.success_rate.set(rate);
+        
+        self.receipts_per_second.set(metrics.receipts_per_second);
+        
+        self.epoch_attempts.set(metrics.epoch.attempts as i64);
+        self.epoch_successful_attempts.set(metrics.epoch.successful_attempts as i64);
     }
     
     pub fn record_attempt(&self, duration_ms: u64, success: bool) {
@@ -161,6 +730,19 @@ impl PrometheusMetrics {
         self.attempt_duration_ms.observe(duration_ms as f64);
     }
     
+    pub fn record_attempt_phases(&self, backend: &str, phases: &crate::phases::PhaseTimings) {
+        for phase in crate::phases::Phase::ALL {
+            let labels = PhaseLabels { phase: phase.to_string(), backend: backend.to_string() };
+            self.attempt_phase_ms.get_or_create(&labels).observe(phases.get(phase));
+        }
+    }
+    
+    pub fn record_stream_attempt(&self, stream: usize, backend: &str, duration_ms: u64) {
+        let labels = StreamLabels { stream: stream.to_string(), backend: backend.to_string() };
+        self.stream_attempts.get_or_create(&labels).inc();
+        self.stream_last_duration_ms.get_or_create(&labels).set(duration_ms as i64);
+    }
+    
     pub fn record_error(&self, error_type: ErrorType) {
         match error_type {
             ErrorType::Gpu => self.gpu_errors.inc(),
@@ -170,8 +752,224 @@ impl PrometheusMetrics {
         };
     }
     
-    pub fn record_network_latency(&self, latency_ms: f64) {
-        self.network_latency_ms.observe(latency_ms);
+    pub fn record_selftest(&self, passed: bool) {
+        if !passed {
+            self.selftest_failures.inc();
+        }
+    }
+    
+    pub fn record_spot_check(&self, result: &SpotCheckResult) {
+        self.spot_checked_elements.inc_by(result.checked as u64);
+        if !result.passed() {
+            self.silent_corruptions.inc();
+        }
+    }
+    
+    pub fn record_liveness_report(&self, delivered: bool) {
+        if delivered {
+            self.liveness_reports.inc();
+        } else {
+            self.liveness_failures.inc();
+        }
+    }
+    
+    pub fn record_metrics_push(&self, delivered: bool) {
+        if delivered {
+            self.metrics_pushes.inc();
+        } else {
+            self.metrics_push_failures.inc();
+        }
+    }
+    
+    pub fn record_epoch_summary(&self, delivered: bool) {
+        if delivered {
+            self.epoch_summaries.inc();
+        } else {
+            self.epoch_summary_failures.inc();
+        }
+    }
+    
+    /// An attempt's full output was stored; `stored_bytes` is the evidence directory's new total.
+    pub fn record_evidence(&self, stored_bytes: u64) {
+        self.evidence_samples.inc();
+        self.evidence_bytes.set(stored_bytes as i64);
+    }
+    
+    pub fn record_compression(&self, stats: &crate::compression::CompressionStats) {
+        let labels = EncodingLabels { encoding: stats.encoding.to_string() };
+        self.compressed_submissions.get_or_create(&labels).inc();
+        self.submit_bytes_saved.get_or_create(&labels).inc_by(stats.bytes_saved());
+    }
+    
+    /// `outcome` is one of accepted, queued, throttled, rejected or failed.
+    pub fn record_identity_receipt(&self, device_did: &str, outcome: &str) {
+        let labels = IdentityLabels { device_did: device_did.to_string(), outcome: outcome.to_string() };
+        self.identity_receipts.get_or_create(&labels).inc();
+    }
+    
+    pub fn set_key_epoch(&self, device_did: &str, key_epoch: u32) {
+        self.key_epoch.get_or_create(&DidLabels { device_did: device_did.to_string() }).set(key_epoch as i64);
+    }
+    
+    /// `source` is `file` for a changed key file, `admin` for the admin endpoint.
+    pub fn record_key_rotation(&self, device_did: &str, source: &str, key_epoch: u32) {
+        let labels = KeyRotationLabels { device_did: device_did.to_string(), source: source.to_string() };
+        self.key_rotations.get_or_create(&labels).inc();
+        self.set_key_epoch(device_did, key_epoch);
+    }
+    
+    pub fn set_epoch(&self, epoch_id: u64) {
+        self.epoch_id.set(epoch_id as i64);
+    }
+    
+    /// `source` is `response` for an aggregator verdict, `feed` for the epoch feed.
+    pub fn record_epoch_transition(&self, source: &str, epoch_id: u64) {
+        self.epoch_transitions.get_or_create(&SourceLabels { source: source.to_string() }).inc();
+        self.set_epoch(epoch_id);
+        self.epoch_attempts.set(0);
+        self.epoch_successful_attempts.set(0);
+    }
+    
+    /// `reason` is the aggregator's reason code, or `unspecified` when it gave none.
+    pub fn record_rejection(&self, reason: &str) {
+        self.rejections.get_or_create(&RejectionLabels { reason: reason.to_string() }).inc();
+    }
+    
+    pub fn set_device_memory(&self, memory: &DeviceMemory, used_bytes: u64) {
+        self.device_memory_total_bytes.set(memory.total_bytes as i64);
+        if let Some(free) = memory.free_bytes {
+            self.device_memory_free_bytes.set(free as i64);
+        }
+        self.device_memory_used_bytes.set(used_bytes as i64);
+    }
+    
+    pub fn record_attempt_energy(&self, joules: f64, tera_ops: f64) {
+        self.attempt_energy_joules.observe(joules);
+        if joules > 0.0 {
+            self.tops_per_watt.set(tera_ops / joules);
+        }
+    }
+    
+    pub fn set_fleet_config_version(&self, version: u64) {
+        self.fleet_config_version.set(version as i64);
+    }
+    
+    pub fn set_update_available(&self, available: bool) {
+        self.update_available.set(i64::from(available));
+    }
+    
+    pub fn set_watch_estimated_tops(&self, tops: f64) {
+        self.watch_estimated_tops.set(tops);
+    }
+    
+    pub fn set_backpressure_mode(&self, mode: crate::backpressure::ThrottleMode) {
+        use crate::backpressure::ThrottleMode;
+        self.backpressure_mode.set(match mode {
+            ThrottleMode::Running => 0,
+            ThrottleMode::Slowed => 1,
+            ThrottleMode::Paused => 2,
+        });
+    }
+    
+    pub fn set_clock_offset_ms(&self, offset_ms: i64) {
+        self.clock_offset_ms.set(offset_ms);
+    }
+    
+    pub fn record_memory_downscale(&self) {
+        self.memory_downscales.inc();
+    }
+    
+    /// `kind` is `submit` for a receipt verdict, `epoch` for an epoch descriptor.
+    pub fn record_unauthenticated_response(&self, kind: &str) {
+        self.unauthenticated_responses.get_or_create(&ResponseLabels { kind: kind.to_string() }).inc();
+    }
+    
+    pub fn record_duplicate_suppressed(&self) {
+        self.duplicates_suppressed.inc();
+    }
+    
+    pub fn record_timing_confidence(&self, confidence: crate::types::TimingConfidence) {
+        self.receipt_timing.get_or_create(&TimingLabels { confidence: confidence.to_string() }).inc();
+    }
+    
+    pub fn record_circuit_transition(&self, from: crate::error_handling::CircuitState, to: crate::error_handling::CircuitState) {
+        self.circuit_transitions.get_or_create(&CircuitLabels { from: from.to_string(), to: to.to_string() }).inc();
+    }
+    
+    pub fn record_aggregator_request(&self) {
+        self.aggregator_requests.inc();
+    }
+    
+    pub fn record_state_tampering(&self, file: &str) {
+        self.state_tampering.get_or_create(&StateFileLabels { file: file.to_string() }).inc();
+    }
+    
+    pub fn record_challenge(&self, outcome: &str, response: Option<std::time::Duration>) {
+        self.challenges.get_or_create(&ChallengeLabels { outcome: outcome.to_string() }).inc();
+        if let Some(response) = response {
+            self.challenge_response_ms.observe(response.as_secs_f64() * 1000.0);
+        }
+    }
+    
+    pub fn record_submit_failure(&self, kind: &str) {
+        self.submit_failures.get_or_create(&FailureLabels { kind: kind.to_string() }).inc();
+    }
+    
+    pub fn record_submit_retry(&self, kind: &str) {
+        self.submit_retries.get_or_create(&FailureLabels { kind: kind.to_string() }).inc();
+    }
+    
+    pub fn record_shadow_receipt(&self, outcome: &str, latency: Option<std::time::Duration>) {
+        self.shadow_receipts.get_or_create(&ShadowLabels { outcome: outcome.to_string() }).inc();
+        if let Some(latency) = latency {
+            self.shadow_latency_ms.observe(latency.as_secs_f64() * 1000.0);
+        }
+    }
+    
+    pub fn set_shadow_enabled(&self, enabled: bool) {
+        self.shadow_enabled.set(i64::from(enabled));
+    }
+    
+    pub fn set_challenges_pending(&self, pending: usize) {
+        self.challenges_pending.set(pending as i64);
+    }
+    
+    pub fn record_aggregator_connection(&self, opened: bool, elapsed: std::time::Duration) {
+        let outcome = if opened { "new" } else { "failed" };
+        self.aggregator_connections.get_or_create(&ConnectionLabels { outcome: outcome.to_string() }).inc();
+        if opened {
+            self.aggregator_handshake_ms.observe(elapsed.as_secs_f64() * 1000.0);
+        }
+    }
+    
+    pub fn record_aggregator_dns(&self, outcome: &str) {
+        self.aggregator_dns.get_or_create(&ConnectionLabels { outcome: outcome.to_string() }).inc();
+    }
+    
+    pub fn set_effective_rate(&self, rate_per_second: f64) {
+        self.effective_rate_per_second.set(rate_per_second);
+    }
+    
+    pub fn set_pacing_delay(&self, delay: std::time::Duration) {
+        self.pacing_delay_seconds.set(delay.as_secs_f64());
+    }
+    
+    pub fn set_queue_depth(&self, depth: usize) {
+        self.queue_depth.set(depth as i64);
+    }
+    
+    /// One submission request: time to the response headers (none without a
+    /// response) and to the end of the body.
+    pub fn record_network_latency(&self, status_class: &str, ttfb: Option<std::time::Duration>, total: std::time::Duration) {
+        let observe = |phase: &str, latency: std::time::Duration| {
+            self.network_latency_ms
+                .get_or_create(&LatencyLabels { phase: phase.to_string(), status_class: status_class.to_string() })
+                .observe(latency.as_secs_f64() * 1000.0);
+        };
+        if let Some(ttfb) = ttfb {
+            observe("ttfb", ttfb);
+        }
+        observe("total", total);
     }
     
     pub fn export_metrics(&self) -> Result<String, Box<dyn std::error::Error>> {
@@ -197,20 +995,77 @@ tops_worker_gpu_errors - Total number of GPU errors
 tops_worker_network_errors - Total number of network errors
 tops_worker_signature_errors - Total number of signature errors
 tops_worker_validation_errors - Total number of validation errors
+tops_worker_selftest_failures - Total number of GEMM self-tests that disagreed with the CPU reference
+tops_worker_spot_checked_elements - Total number of attempt output elements recomputed on the CPU
+tops_worker_silent_corruptions - Total number of attempts whose output disagreed with the CPU spot-check
+tops_worker_liveness_reports - Total number of signed liveness reports accepted by the liveness endpoint
+tops_worker_liveness_failures - Total number of liveness reports that could not be delivered
+tops_worker_metrics_pushes - Total number of metric pushes accepted by the Pushgateway
+tops_worker_metrics_push_failures - Total number of metric pushes that could not be delivered
+tops_worker_epoch_summaries - Total number of signed epoch summaries accepted by the aggregator
+tops_worker_epoch_summary_failures - Total number of epoch summaries that could not be delivered
+tops_worker_evidence_samples - Total number of attempt outputs stored as audit evidence
+tops_worker_stream_attempts{stream,backend} - Total number of attempts computed per attempt stream and its backend
+tops_worker_compressed_submissions{encoding} - Receipt submissions sent with a compressed body, per Content-Encoding
+tops_worker_submit_bytes_saved{encoding} - Request body bytes saved by submission compression, per Content-Encoding
+tops_worker_identity_receipts{device_did,outcome} - Receipts submitted per signing identity and outcome
+tops_worker_rejections{reason} - Receipts the aggregator rejected, per reason code
+tops_worker_key_rotations{device_did,source} - Signing key rotations per identity and source (file, admin)
+tops_worker_epoch_transitions{source} - Epoch changes, per source (response, feed)
+tops_worker_memory_downscales - Times the attempt sizes were stepped down after a device allocation failure
+tops_worker_unauthenticated_responses{kind} - Aggregator responses ignored for a missing or invalid signature, per kind (submit, epoch)
+tops_worker_duplicate_submissions_suppressed - Receipt resends dropped before sending because their idempotency key was already delivered
+tops_worker_receipt_timing{confidence} - Receipts per timing confidence (verified, unverified, drift) from comparing wall-clock and device kernel times
+tops_worker_circuit_transitions{from,to} - Submission circuit breaker state changes, per previous and new state (closed, open, half-open)
+tops_worker_aggregator_requests - HTTP requests sent to the aggregator, over new or pooled connections
+tops_worker_aggregator_connections{outcome} - Aggregator connections opened (new) or that failed to open (failed)
+tops_worker_aggregator_dns{outcome} - Aggregator host name answers per outcome (resolved, cached, override, stale, failed)
+tops_worker_state_tampering{file} - State files that failed their integrity check, per file (queue, journal, algo_cache)
+tops_worker_challenges{outcome} - Aggregator liveness challenges per outcome (met, late, expired, failed, dropped)
+tops_worker_submit_failures{kind} - Receipt submissions that did not get through, per failure kind (dns, connect_timeout, connect, tls, timeout, rejected, duplicate, throttled, server_error, unauthenticated, network)
+tops_worker_submit_retries{kind} - Receipt resends by the HTTP transport's retry policy, per failure kind of the try before
+tops_worker_shadow_receipts{outcome} - Receipt copies to the shadow aggregator per outcome (accepted, rejected, throttled, failed, error, dropped)
 
 # Gauges
 tops_worker_uptime_seconds - Worker uptime in seconds
 tops_worker_consecutive_failures - Number of consecutive failures
 tops_worker_success_rate - Success rate as a percentage (multiplied by 100)
+tops_worker_effective_rate_per_second - Current effective attempt rate after adaptive back-off
+tops_worker_pacing_delay_seconds - Pause before the next attempt chosen by the pacing controller
+tops_worker_receipts_per_second - Accepted receipts per second across all attempt streams
+tops_worker_stream_last_duration_ms{stream,backend} - Duration of the latest attempt per attempt stream in milliseconds
+tops_worker_queue_depth - Receipts buffered on disk awaiting delivery
+tops_worker_evidence_bytes - Bytes of compressed audit evidence currently kept on disk
+tops_worker_key_epoch{device_did} - Key epoch (rotation count) of the active signing key per identity
+tops_worker_epoch_id - Epoch the current attempts are chained to
+tops_worker_epoch_attempts - Attempts in the current epoch
+tops_worker_epoch_successful_attempts - Successful attempts in the current epoch
+tops_worker_device_memory_total_bytes - Device memory reported by the backend
+tops_worker_device_memory_free_bytes - Free device memory, where the backend reports it (CUDA)
+tops_worker_device_memory_used_bytes - Device memory the in-flight attempts allocate at the current sizes
+tops_worker_tops_per_watt - Energy efficiency of the latest metered attempt in TOPS per watt (tera-operations per joule)
+tops_worker_fleet_config_version - Version of the fleet config document in effect (0 before the first)
+tops_worker_watch_estimated_tops - Estimated TOPS the worker would contribute, under WATCH_ONLY=1
+tops_worker_backpressure_mode - Attempt throttling on the submission backlog: 0 running, 1 slowed, 2 paused
+tops_worker_clock_offset_ms - Aggregator or NTP time minus local time in milliseconds; positive when the local clock is behind
+tops_worker_challenges_pending - Aggregator liveness challenges waiting to be answered
+tops_worker_update_available - 1 when the signed release manifest names a newer version than the running one
+tops_worker_shadow_enabled - 1 while receipts are copied to the shadow aggregator, 0 when switched off
 
 # Histograms
 tops_worker_attempt_duration_ms - Duration of attempts in milliseconds
-tops_worker_network_latency_ms - Network request latency in milliseconds
+tops_worker_network_latency_ms{phase,status_class} - Submission request latency (ttfb, total) in milliseconds by status class (2xx, 4xx, 5xx, error)
+tops_worker_attempt_phase_ms{phase,backend} - Attempt time per phase (fill, h2d, kernel, d2h, hash) in milliseconds
+tops_worker_attempt_energy_joules - Estimated energy per attempt in joules, from the power sensor
+tops_worker_aggregator_handshake_ms - Time to open an aggregator connection (TCP connect, proxy and TLS handshake) in milliseconds
+tops_worker_challenge_response_ms - Time from receiving a liveness challenge to the aggregator accepting its answer in milliseconds
+tops_worker_shadow_latency_ms - Time to deliver a receipt copy to the shadow aggregator in milliseconds
 
 # Example queries:
 # - Success rate: tops_worker_success_rate / 100
 # - Average attempt duration: histogram_quantile(0.5, tops_worker_attempt_duration_ms_bucket)
 # - Error rate: rate(tops_worker_gpu_errors[5m]) + rate(tops_worker_network_errors[5m])
 # - Throughput: rate(tops_worker_successful_attempts[1m])
+# - Timing drift: rate(tops_worker_receipt_timing{confidence="drift"}[5m])
 "#
 }
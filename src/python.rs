@@ -0,0 +1,102 @@
+#![cfg(feature = "python")]
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use crate::attempt;
+use crate::cpu::CpuExec;
+use crate::prng;
+use crate::types::{Sizes, WorkReceipt, CONTENT_TYPE_RECEIPT_V1};
+
+fn value_error(e: impl std::fmt::Display) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+fn prev_hash_arg(prev_hash: &[u8]) -> PyResult<[u8; 32]> {
+    prev_hash.try_into().map_err(|_| value_error(format!("prev_hash must be 32 bytes, got {}", prev_hash.len())))
+}
+
+fn sizes_arg(m: usize, n: usize, k: usize) -> PyResult<Sizes> {
+    if m == 0 || n == 0 || k == 0 {
+        return Err(value_error("m, n and k must be non-zero"));
+    }
+    Ok(Sizes { m, n, k, batch: 1 })
+}
+
+fn i8_bytes<'py>(py: Python<'py>, v: &[i8]) -> Bound<'py, PyBytes> {
+    PyBytes::new(py, &v.iter().map(|&x| x as u8).collect::<Vec<u8>>())
+}
+
+/// derive_seed(prev_hash, nonce) -> bytes
+///
+/// 16-byte PRNG seed for an attempt (blake3 of prev_hash || nonce_le).
+#[pyfunction]
+fn derive_seed<'py>(py: Python<'py>, prev_hash: &[u8], nonce: u32) -> PyResult<Bound<'py, PyBytes>> {
+    Ok(PyBytes::new(py, &prng::derive_seed(&prev_hash_arg(prev_hash)?, nonce)))
+}
+
+/// generate_inputs(prev_hash, nonce, m, n, k) -> (a, b)
+///
+/// The attempt's int8 matrices as bytes, A row-major m x k and B row-major k x n;
+/// `numpy.frombuffer(a, dtype=numpy.int8)` gives the signed values.
+#[pyfunction]
+fn generate_inputs<'py>(
+    py: Python<'py>,
+    prev_hash: &[u8],
+    nonce: u32,
+    m: usize,
+    n: usize,
+    k: usize,
+) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+    let prev_hash = prev_hash_arg(prev_hash)?;
+    let sizes = sizes_arg(m, n, k)?;
+    let (a, b) = py.allow_threads(|| attempt::generate_inputs(&prev_hash, nonce, &sizes));
+    Ok((i8_bytes(py, &a), i8_bytes(py, &b)))
+}
+
+/// run_attempt(prev_hash, nonce, m, n, k) -> dict
+///
+/// Full attempt on the CPU backend. Returns `work_root` (bytes), `work_root_hex`,
+/// `y2_samples` (bytes, int8, drawn across the output from prev_hash, nonce and
+/// a commitment to the whole output)
+/// and `elapsed_ms`.
+#[pyfunction]
+fn run_attempt<'py>(py: Python<'py>, prev_hash: &[u8], nonce: u32, m: usize, n: usize, k: usize) -> PyResult<Bound<'py, PyDict>> {
+    let prev_hash = prev_hash_arg(prev_hash)?;
+    let sizes = sizes_arg(m, n, k)?;
+    let out = py.allow_threads(|| {
+        let executor = CpuExec::new()?;
+        attempt::run_attempt(&executor, &prev_hash, nonce, &sizes)
+    }).map_err(value_error)?;
+    let result = PyDict::new(py);
+    result.set_item("work_root", PyBytes::new(py, &out.work_root))?;
+    result.set_item("work_root_hex", hex::encode(out.work_root))?;
+    result.set_item("y2_samples", i8_bytes(py, &out.y2_samples))?;
+    result.set_item("elapsed_ms", out.elapsed_ms)?;
+    Ok(result)
+}
+
+/// verify_receipt(receipt, pubkey_hex, content_type=None) -> bool
+///
+/// Check a receipt's signature. `receipt` is the submitted body (str or bytes);
+/// `content_type` selects the decoding (JSON by default, or the v2 binary type).
+#[pyfunction]
+#[pyo3(signature = (receipt, pubkey_hex, content_type = None))]
+fn verify_receipt(receipt: &Bound<'_, PyAny>, pubkey_hex: &str, content_type: Option<&str>) -> PyResult<bool> {
+    let body: Vec<u8> = match receipt.extract::<String>() {
+        Ok(s) => s.into_bytes(),
+        Err(_) => receipt.extract::<Vec<u8>>()?,
+    };
+    let receipt = WorkReceipt::decode(&body, content_type.unwrap_or(CONTENT_TYPE_RECEIPT_V1)).map_err(value_error)?;
+    crate::signing::verify_receipt(&receipt, pubkey_hex).map_err(value_error)
+}
+
+/// Python module `tops_worker`, built with maturin (`python` feature).
+#[pymodule]
+fn tops_worker(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add_function(wrap_pyfunction!(derive_seed, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_inputs, m)?)?;
+    m.add_function(wrap_pyfunction!(run_attempt, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_receipt, m)?)?;
+    Ok(())
+}
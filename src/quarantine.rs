@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::cpu::CpuExec;
+use crate::integrity::write_atomic;
+use crate::queue::PersistentQueue;
+use crate::submit::{RejectReason, SubmitResponse};
+use crate::types::WorkReceipt;
+use crate::replay::replay_attempt;
+
+const ACCEPTED_FILE: &str = "accepted.json";
+// Keys of resubmitted receipts remembered for dedup; older ones fall out
+const ACCEPTED_KEEP: usize = 10000;
+// Aggregator bodies can be whole error pages
+const MAX_BODY_BYTES: usize = 4096;
+
+/// A receipt the aggregator refused, with its verdict.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedReceipt {
+    /// The receipt as built, before signing; resubmission signs it again.
+    pub receipt: WorkReceipt,
+    pub target: String,
+    pub status: u16,
+    pub reason: Option<RejectReason>,
+    pub message: Option<String>,
+    pub body: String,
+    pub rejected_at: String,
+    /// How often `tops-worker resubmit` was refused again.
+    #[serde(default)]
+    pub resubmissions: u32,
+}
+
+impl QuarantinedReceipt {
+    pub fn new(receipt: WorkReceipt, target: &str, status: u16, body: &str, response: Option<&SubmitResponse>) -> Self {
+        let mut end = body.len().min(MAX_BODY_BYTES);
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        Self {
+            receipt,
+            target: target.to_string(),
+            status,
+            reason: response.and_then(|r| r.reason),
+            message: response.and_then(|r| r.message.clone()),
+            body: body[..end].to_string(),
+            rejected_at: chrono::Utc::now().to_rfc3339(),
+            resubmissions: 0,
+        }
+    }
+
+    /// Identifies the attempt: nonces are only unique per device and prev_hash.
+    pub fn key(&self) -> String {
+        format!("{}/{}/{}", self.receipt.device_did, self.receipt.prev_hash_hex, self.receipt.nonce)
+    }
+
+    pub fn reason_label(&self) -> String {
+        self.reason.map_or("unspecified".to_string(), |r| r.to_string())
+    }
+}
+
+/// Rejected receipts in `$STATE_DIR/quarantine`, one file each, plus the keys of
+/// the ones a later resubmission got accepted.
+///
+/// Past `max_entries` the oldest receipts are dropped.
+pub struct Quarantine {
+    queue: PersistentQueue,
+    max_entries: usize,
+}
+
+impl Quarantine {
+    pub fn open(dir: impl AsRef<Path>, max_entries: usize) -> anyhow::Result<Self> {
+        Ok(Self { queue: PersistentQueue::open(dir)?, max_entries })
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.queue.dir()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn store(&self, entry: &QuarantinedReceipt) -> anyhow::Result<()> {
+        if self.max_entries == 0 {
+            return Ok(());
+        }
+        self.queue.push(entry)?;
+        while self.queue.len() > self.max_entries {
+            match self.queue.peek::<QuarantinedReceipt>()? {
+                Some((seq, _)) => self.queue.remove(seq)?,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Every quarantined receipt with its sequence number, oldest first.
+    pub fn entries(&self) -> anyhow::Result<Vec<(u64, QuarantinedReceipt)>> {
+        self.queue.items()
+    }
+
+    pub fn remove(&self, seq: u64) -> anyhow::Result<()> {
+        self.queue.remove(seq)
+    }
+
+    /// Replace an entry after another rejection; it moves to the back.
+    pub fn replace(&self, seq: u64, entry: &QuarantinedReceipt) -> anyhow::Result<()> {
+        self.queue.push(entry)?;
+        self.queue.remove(seq)
+    }
+
+    fn accepted_path(&self) -> PathBuf {
+        self.dir().join(ACCEPTED_FILE)
+    }
+
+    /// Keys of receipts accepted on resubmission, oldest first.
+    pub fn accepted(&self) -> anyhow::Result<VecDeque<String>> {
+        match std::fs::read(self.accepted_path()) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| anyhow::anyhow!("{} is corrupt: {}", self.accepted_path().display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(VecDeque::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save_accepted(&self, accepted: &VecDeque<String>) -> anyhow::Result<()> {
+        let skip = accepted.len().saturating_sub(ACCEPTED_KEEP);
+        let keep: Vec<&String> = accepted.iter().skip(skip).collect();
+        write_atomic(&self.accepted_path(), &serde_json::to_vec(&keep)?)
+    }
+}
+
+/// Check a quarantined receipt before it goes out again: it must be for this
+/// network and its work_root must still recompute from its own fields on the CPU.
+pub fn revalidate(receipt: &WorkReceipt, network_id: Option<&str>) -> anyhow::Result<()> {
+    if receipt.network_id.as_deref() != network_id {
+        anyhow::bail!("receipt is for network {}, worker is on {}",
+            receipt.network_id.as_deref().unwrap_or("unset"), network_id.unwrap_or("unset"));
+    }
+    let work_root = recompute_work_root(receipt)?;
+    if work_root != receipt.work_root_hex {
+        anyhow::bail!("work_root does not recompute (receipt {}, CPU {})", receipt.work_root_hex, work_root);
+    }
+    Ok(())
+}
+
+/// Recompute a receipt's work_root (hex) on the CPU from its prev_hash, nonce, salt,
+/// sizes, `kernel_ver`, requantization, hash kind and output sampling.
+pub fn recompute_work_root(receipt: &WorkReceipt) -> anyhow::Result<String> {
+    Ok(hex::encode(replay_attempt(&CpuExec::new()?, receipt)?.work_root))
+}
@@ -0,0 +1,157 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::integrity::{self, StateFile};
+use crate::log_warn;
+
+/// Durable FIFO of JSON items, one file per item.
+///
+/// Items survive restarts until they are explicitly removed, which is what the
+/// offline buffering of store-and-forward transports relies on. Writes go to a
+/// temporary file that is fsynced and renamed into place, so a crash never leaves
+/// a half-written entry behind. With `STATE_INTEGRITY=1` every entry is sealed
+/// under its directory and file name.
+pub struct PersistentQueue {
+    dir: PathBuf,
+    next_seq: AtomicU64,
+    notify: tokio::sync::Notify,
+}
+
+impl PersistentQueue {
+    pub fn open(dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut max_seq = 0;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            match path.extension().and_then(|e| e.to_str()) {
+                // Leftover from a write interrupted before the rename
+                Some("tmp") => { let _ = fs::remove_file(&path); }
+                Some("json") => {
+                    if let Some(seq) = seq_of(&path) {
+                        max_seq = max_seq.max(seq);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            dir,
+            next_seq: AtomicU64::new(max_seq + 1),
+            notify: tokio::sync::Notify::new(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Append an item; returns its sequence number.
+    pub fn push<T: Serialize>(&self, item: &T) -> anyhow::Result<u64> {
+        let seq = self.next_seq.fetch_add(1, Ordering::SeqCst);
+        let tmp = self.dir.join(format!("{:020}.tmp", seq));
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&integrity::seal(&self.name_for(seq), serde_json::to_vec(item)?))?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.path_for(seq))?;
+        self.notify.notify_one();
+        Ok(seq)
+    }
+
+    /// Oldest item without removing it. Unreadable entries are renamed to `.corrupt`,
+    /// ones that fail the integrity check to `.tampered`, and skipped.
+    pub fn peek<T: DeserializeOwned>(&self) -> anyhow::Result<Option<(u64, T)>> {
+        for seq in self.sequence_numbers()? {
+            if let Some(item) = self.read(seq) {
+                return Ok(Some((seq, item)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Every item, oldest first, skipping unreadable entries like `peek`.
+    pub fn items<T: DeserializeOwned>(&self) -> anyhow::Result<Vec<(u64, T)>> {
+        Ok(self.sequence_numbers()?.into_iter()
+            .filter_map(|seq| self.read(seq).map(|item| (seq, item)))
+            .collect())
+    }
+
+    pub fn remove(&self, seq: u64) -> anyhow::Result<()> {
+        match fs::remove_file(self.path_for(seq)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sequence_numbers().map(|s| s.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Wait until an item is pushed (returns immediately if one was pushed since the last wait).
+    pub async fn wait_for_item(&self) {
+        self.notify.notified().await;
+    }
+
+    fn read<T: DeserializeOwned>(&self, seq: u64) -> Option<T> {
+        let path = self.path_for(seq);
+        let body = match fs::read(&path) {
+            Ok(bytes) => match integrity::open(StateFile::Queue, &path, &self.name_for(seq), bytes) {
+                Ok(body) => body,
+                Err(_) => {
+                    let _ = fs::rename(&path, path.with_extension("tampered"));
+                    return None;
+                }
+            },
+            Err(e) => {
+                log_warn!("[queue] skipping unreadable entry {}: {}", path.display(), e);
+                let _ = fs::rename(&path, path.with_extension("corrupt"));
+                return None;
+            }
+        };
+        match serde_json::from_slice(&body) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                log_warn!("[queue] skipping unreadable entry {}: {}", path.display(), e);
+                let _ = fs::rename(&path, path.with_extension("corrupt"));
+                None
+            }
+        }
+    }
+
+    fn path_for(&self, seq: u64) -> PathBuf {
+        self.dir.join(format!("{:020}.json", seq))
+    }
+
+    fn name_for(&self, seq: u64) -> String {
+        seal_name(&self.path_for(seq))
+    }
+
+    fn sequence_numbers(&self) -> anyhow::Result<Vec<u64>> {
+        let mut seqs: Vec<u64> = fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json"))
+            .filter_map(|p| seq_of(&p))
+            .collect();
+        seqs.sort_unstable();
+        Ok(seqs)
+    }
+}
+
+/// What the entry at `path` is sealed under, its queue and file name: an entry
+/// moved to another queue or sequence number does not verify.
+pub fn seal_name(path: &Path) -> String {
+    let name = |p: Option<&Path>| p.and_then(|p| p.file_name()).map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    format!("{}/{}", name(path.parent()), name(Some(path)))
+}
+
+fn seq_of(path: &Path) -> Option<u64> {
+    path.file_stem()?.to_str()?.parse().ok()
+}
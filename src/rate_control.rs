@@ -0,0 +1,110 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// AIMD-style rate controller driven by aggregator responses.
+///
+/// Throttling responses (429/503) cut the effective rate multiplicatively and
+/// honor any Retry-After hint; accepted submissions ramp it back up additively
+/// towards the configured ceiling. An aggregator-suggested rate replaces the
+/// ceiling until the next suggestion.
+#[derive(Debug)]
+pub struct AdaptiveRateController {
+    max_rate: f64,
+    min_rate: f64,
+    increase_step: f64,
+    decrease_factor: f64,
+    state: Mutex<RateState>,
+}
+
+#[derive(Debug)]
+struct RateState {
+    current_rate: f64,
+    blocked_until: Option<Instant>,
+    suggested_rate: Option<f64>,
+}
+
+impl AdaptiveRateController {
+    pub fn new(max_rate: f64, min_rate: f64, increase_step: f64, decrease_factor: f64) -> Self {
+        let min_rate = min_rate.min(max_rate);
+        Self {
+            max_rate,
+            min_rate,
+            increase_step,
+            decrease_factor,
+            state: Mutex::new(RateState { current_rate: max_rate, blocked_until: None, suggested_rate: None }),
+        }
+    }
+
+    /// Additive increase after an accepted submission. Returns the new rate.
+    pub fn on_success(&self) -> f64 {
+        if let Ok(mut state) = self.state.lock() {
+            let ceiling = state.suggested_rate.unwrap_or(self.max_rate);
+            state.current_rate = (state.current_rate + self.increase_step).min(ceiling);
+            state.current_rate
+        } else {
+            self.max_rate
+        }
+    }
+
+    /// Multiplicative decrease after a throttling response. Returns the new rate.
+    pub fn on_throttle(&self, retry_after: Option<Duration>) -> f64 {
+        if let Ok(mut state) = self.state.lock() {
+            state.current_rate = (state.current_rate * self.decrease_factor).max(self.min_rate);
+            if let Some(wait) = retry_after {
+                let until = Instant::now() + wait;
+                // Never shorten an existing back-off window
+                if state.blocked_until.is_none_or(|b| until > b) {
+                    state.blocked_until = Some(until);
+                }
+            }
+            state.current_rate
+        } else {
+            self.min_rate
+        }
+    }
+
+    /// Adopt the rate the aggregator suggested, within the configured bounds. Returns the new rate.
+    pub fn on_suggested_rate(&self, rate: f64) -> f64 {
+        if let Ok(mut state) = self.state.lock() {
+            let rate = rate.clamp(self.min_rate, self.max_rate);
+            state.suggested_rate = Some(rate);
+            state.current_rate = rate;
+            state.current_rate
+        } else {
+            self.min_rate
+        }
+    }
+
+    pub fn current_rate(&self) -> f64 {
+        self.state.lock().map(|s| s.current_rate).unwrap_or(self.min_rate)
+    }
+
+    /// Time left before the aggregator asked us to resume, if any.
+    pub fn retry_after_remaining(&self) -> Option<Duration> {
+        let mut state = self.state.lock().ok()?;
+        let until = state.blocked_until?;
+        let now = Instant::now();
+        if until > now {
+            Some(until - now)
+        } else {
+            state.blocked_until = None;
+            None
+        }
+    }
+}
+
+/// Returns true for responses that signal the aggregator wants us to slow down.
+pub fn is_throttle_status(status: u16) -> bool {
+    status == 429 || status == 503
+}
+
+/// Parse a Retry-After header value: either delta-seconds or an HTTP-date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let when = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delta = when.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delta.to_std().unwrap_or(Duration::ZERO))
+}
@@ -0,0 +1,183 @@
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::attempt::{AttemptOutput, Executor};
+use crate::cpu::{CpuExec, CpuKernel};
+use crate::journal::{output_digest, JournalEntry};
+use crate::matrix_cache;
+use crate::memhard::{run_memhard_stage, MemHardParams};
+use crate::phases::{self, PhaseTimings};
+use crate::prng::derive_salted_seed;
+use crate::submit::hex32;
+use crate::types::{Requant, Sizes, WorkReceipt};
+use crate::workload::{Workload, WorkloadRegistry};
+use crate::work_hash::WorkSampling;
+
+/// Executor for `tops-worker replay --backend`: `cpu` (the best CPU kernel), a
+/// CPU kernel by name (`scalar`, `avx2`, `avx512-vnni`, `neon`), or a compiled
+/// GPU backend (`opencl`, `cuda`, `metal`).
+pub fn replay_executor(backend: &str) -> anyhow::Result<Box<dyn Executor>> {
+    if backend == "cpu" {
+        return Ok(Box::new(CpuExec::new()?));
+    }
+    let kernels = [CpuKernel::Scalar, CpuKernel::Avx2, CpuKernel::Avx512Vnni, CpuKernel::Neon];
+    if let Some(kernel) = kernels.into_iter().find(|k| k.to_string() == backend) {
+        return Ok(Box::new(CpuExec::with_kernel(kernel)?));
+    }
+    match backend {
+        #[cfg(feature = "gpu")]
+        "opencl" => Ok(Box::new(crate::gpu::GpuExec::new()?)),
+        #[cfg(feature = "cuda")]
+        "cuda" => Ok(Box::new(crate::gpu_cuda::CudaExec::new()?)),
+        #[cfg(feature = "metal")]
+        "metal" => Ok(Box::new(crate::gpu_metal::MetalExec::new()?)),
+        #[cfg(not(feature = "gpu"))]
+        "opencl" => anyhow::bail!("backend {} is not compiled into this build", backend),
+        #[cfg(not(feature = "cuda"))]
+        "cuda" => anyhow::bail!("backend {} is not compiled into this build", backend),
+        #[cfg(not(feature = "metal"))]
+        "metal" => anyhow::bail!("backend {} is not compiled into this build", backend),
+        other => anyhow::bail!("unknown backend '{}' (cpu, scalar, avx2, avx512-vnni, neon, opencl, cuda or metal)", other),
+    }
+}
+
+/// Run the attempt `receipt` describes again on `executor`: the inputs from its
+/// prev_hash, nonce, salt, sizes and `kernel_ver`, then its requantization, hash
+/// and sampling.
+pub fn replay_attempt(executor: &dyn Executor, receipt: &WorkReceipt) -> anyhow::Result<AttemptOutput> {
+    replay_attempt_with(&WorkloadRegistry::new(), executor, receipt)
+}
+
+/// `replay_attempt` for receipts of workloads registered in `workloads`.
+pub fn replay_attempt_with(workloads: &WorkloadRegistry, executor: &dyn Executor, receipt: &WorkReceipt) -> anyhow::Result<AttemptOutput> {
+    let prev_hash = hex32(&receipt.prev_hash_hex)
+        .ok_or_else(|| anyhow::anyhow!("prev_hash_hex is not 32 bytes of hex"))?;
+    let salt = match &receipt.epoch_salt_hex {
+        Some(hex) => Some(hex32(hex).ok_or_else(|| anyhow::anyhow!("epoch_salt_hex is not 32 bytes of hex"))?),
+        None => None,
+    };
+    let workload = workloads.resolve(&receipt.kernel_ver)
+        .ok_or_else(|| anyhow::anyhow!("unknown kernel_ver '{}'", receipt.kernel_ver))?;
+    let memhard = match receipt.kernel_ver.split(';').find(|part| part.starts_with("memhard=")) {
+        Some(tag) => Some(MemHardParams::from_tag(tag).ok_or_else(|| anyhow::anyhow!("unknown memory-hard stage '{}'", tag))?),
+        None => None,
+    };
+
+    let start = Instant::now();
+    let seed = derive_salted_seed(&prev_hash, receipt.nonce, salt.as_ref());
+    // Built-in inputs may come from the matrix cache
+    let mut input = match Workload::from_kernel_ver(&receipt.kernel_ver) {
+        Some(builtin) => matrix_cache::generate_cached(builtin, &prev_hash, receipt.nonce, salt.as_ref(), &receipt.sizes),
+        None => workload.generate_inputs(seed, &receipt.sizes),
+    };
+    let fill = start.elapsed();
+    phases::reset();
+    if let Some(params) = &memhard {
+        input.perturb(&run_memhard_stage(executor, &seed, params)?);
+    }
+    let scale = receipt.requant.unwrap_or_else(|| Requant::from_salt(salt.as_ref()));
+    let y1 = workload.execute(executor, &input, &receipt.sizes, scale)?;
+    let compute = start.elapsed() - fill;
+    let sampling = receipt.work_sampling.unwrap_or(WorkSampling::Prefix);
+    let (work_root, y2_samples) = workload.work_root(&y1, receipt.hash_kind.unwrap_or_default(), sampling, &prev_hash, receipt.nonce);
+    let elapsed = start.elapsed();
+    Ok(AttemptOutput {
+        work_root,
+        y1,
+        y2_samples,
+        elapsed_ms: elapsed.as_millis() as u64,
+        phases: PhaseTimings::from_stages(fill, compute, elapsed - fill - compute),
+        spot_check: None,
+        sizes: receipt.sizes.clone(),
+    })
+}
+
+/// Result of `tops-worker replay`: the recorded attempt next to the replayed one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub epoch_id: u64,
+    pub prev_hash_hex: String,
+    pub nonce: u32,
+    pub sizes: Sizes,
+    pub kernel_ver: String,
+    pub recorded_backend: String,
+    pub backend: String,
+    pub device_name: String,
+    pub recorded_phases: PhaseTimings,
+    pub phases: PhaseTimings,
+    pub recorded_time_ms: u64,
+    pub elapsed_ms: u64,
+    pub recorded_work_root_hex: String,
+    pub work_root_hex: String,
+    /// Digests the journal did not record (quarantined receipts) are `None`.
+    pub recorded_output_hash_hex: Option<String>,
+    pub output_hash_hex: String,
+    pub recorded_samples_hash_hex: Option<String>,
+    pub samples_hash_hex: String,
+}
+
+impl ReplayReport {
+    pub fn new(entry: &JournalEntry, backend: &str, device_name: String, out: &AttemptOutput) -> Self {
+        let receipt = &entry.receipt;
+        Self {
+            epoch_id: receipt.epoch_id,
+            prev_hash_hex: receipt.prev_hash_hex.clone(),
+            nonce: receipt.nonce,
+            sizes: receipt.sizes.clone(),
+            kernel_ver: receipt.kernel_ver.clone(),
+            recorded_backend: entry.backend.clone(),
+            backend: backend.to_string(),
+            device_name,
+            recorded_phases: entry.phases,
+            phases: out.phases,
+            recorded_time_ms: receipt.time_ms,
+            elapsed_ms: out.elapsed_ms,
+            recorded_work_root_hex: receipt.work_root_hex.clone(),
+            work_root_hex: hex::encode(out.work_root),
+            recorded_output_hash_hex: entry.output_hash_hex.clone(),
+            output_hash_hex: output_digest(&out.y1),
+            recorded_samples_hash_hex: entry.samples_hash_hex.clone(),
+            samples_hash_hex: output_digest(&out.y2_samples),
+        }
+    }
+
+    pub fn work_root_matches(&self) -> bool {
+        self.recorded_work_root_hex.eq_ignore_ascii_case(&self.work_root_hex)
+    }
+
+    pub fn passed(&self) -> bool {
+        let same = |recorded: &Option<String>, replayed: &str| recorded.as_deref().is_none_or(|r| r == replayed);
+        self.work_root_matches()
+            && same(&self.recorded_output_hash_hex, &self.output_hash_hex)
+            && same(&self.recorded_samples_hash_hex, &self.samples_hash_hex)
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = format!("replay: epoch {} nonce {} prev_hash {} m,n,k=({},{},{}) batch {} kernel {}\n",
+            self.epoch_id, self.nonce, self.prev_hash_hex, self.sizes.m, self.sizes.n, self.sizes.k,
+            self.sizes.batch.max(1), self.kernel_ver);
+        out.push_str(&format!("{:<10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}  {}\n",
+            "", "fill ms", "h2d ms", "kernel ms", "d2h ms", "hash ms", "total ms", "backend"));
+        let recorded = if self.recorded_backend.is_empty() { "(unknown)" } else { &self.recorded_backend };
+        let replayed = format!("{} ({})", self.backend, self.device_name);
+        for (label, p, total, backend) in [
+            ("recorded", &self.recorded_phases, self.recorded_time_ms, recorded),
+            ("replayed", &self.phases, self.elapsed_ms, replayed.as_str()),
+        ] {
+            out.push_str(&format!("{:<10} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10}  {}\n",
+                label, p.fill_ms, p.h2d_ms, p.kernel_ms, p.d2h_ms, p.hash_ms, total, backend));
+        }
+        let row = |name: &str, recorded: Option<&str>, replayed: &str| {
+            let status = match recorded {
+                Some(r) if r.eq_ignore_ascii_case(replayed) => "OK",
+                Some(_) => "DIFF",
+                None => "-",
+            };
+            format!("{:<5} {:<10} recorded {}\n      {:<10} replayed {}\n", status, name, recorded.unwrap_or("(not recorded)"), "", replayed)
+        };
+        out.push_str(&row("work_root", Some(&self.recorded_work_root_hex), &self.work_root_hex));
+        out.push_str(&row("output", self.recorded_output_hash_hex.as_deref(), &self.output_hash_hex));
+        out.push_str(&row("samples", self.recorded_samples_hash_hex.as_deref(), &self.samples_hash_hex));
+        out.push_str(if self.passed() { "replay matches the recorded attempt\n" } else { "replay DIVERGES from the recorded attempt\n" });
+        out
+    }
+}
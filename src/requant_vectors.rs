@@ -0,0 +1,243 @@
+//! The requantization contract, as test vectors every backend is checked against.
+//!
+//! An int8 output is `activation(overflow(rounding((acc * num) / den)))` where `acc`
+//! is the exact integer dot product and the product `acc * num` is taken in 64 bits.
+//! By default the quotient truncates towards zero and saturates to `[-128, 127]`
+//! before the activation; a workload can ask for floor or half-away-from-zero
+//! rounding and for wrapping instead (`Rounding`, `Overflow`). There is no floating
+//! point anywhere: a backend that divides through a float reciprocal, or rounds or
+//! narrows differently from the workload's semantics, produces different receipts.
+//! `Requant::apply` is the reference implementation; the vectors below pin down the
+//! cases that reference and kernels have been seen to disagree on.
+
+use crate::types::{Activation, Overflow, Requant, Rounding};
+
+/// One accumulator pushed through a requantization, with the output the contract requires.
+#[derive(Debug, Clone, Copy)]
+pub struct RequantVector {
+    pub num: i32,
+    pub den: i32,
+    pub activation: Activation,
+    pub rounding: Rounding,
+    pub overflow: Overflow,
+    pub acc: i64,
+    pub expected: i8,
+}
+
+impl RequantVector {
+    pub fn scale(&self) -> Requant {
+        Requant { num: self.num, den: self.den, activation: self.activation, rounding: self.rounding, overflow: self.overflow }
+    }
+}
+
+// Default semantics: truncation and saturation
+const fn v(num: i32, den: i32, activation: Activation, acc: i64, expected: i8) -> RequantVector {
+    RequantVector { num, den, activation, rounding: TowardZero, overflow: Saturate, acc, expected }
+}
+
+// Identity activation under the given rounding and overflow
+const fn m(num: i32, den: i32, rounding: Rounding, overflow: Overflow, acc: i64, expected: i8) -> RequantVector {
+    RequantVector { num, den, activation: Identity, rounding, overflow, acc, expected }
+}
+
+use Activation::{Identity, Leaky, Relu, Relu6};
+use Overflow::{Saturate, Wrap};
+use Rounding::{Floor, HalfAwayFromZero, TowardZero};
+
+pub const CONTRACT_VECTORS: &[RequantVector] = &[
+    v(1, 1, Identity, 0, 0),
+    // Saturation at both ends
+    v(1, 1, Identity, 127, 127),
+    v(1, 1, Identity, 128, 127),
+    v(1, 1, Identity, -128, -128),
+    v(1, 1, Identity, -129, -128),
+    // Halves truncate rather than round
+    v(1, 2, Identity, 3, 1),
+    v(1, 2, Identity, -3, -1),
+    // Negative quotients go towards zero, not down
+    v(1, 3, Identity, -5, -1),
+    v(2, 3, Identity, 5, 3),
+    v(7, 3, Identity, -11, -25),
+    // The smallest and largest scales
+    v(256, 65536, Identity, 255, 0),
+    v(256, 65536, Identity, 256, 1),
+    v(256, 65536, Identity, -257, -1),
+    // acc * num overflows 32 bits before the division
+    v(256, 1, Identity, 8_388_608, 127),
+    v(256, 1, Identity, -8_388_609, -128),
+    // One below an exact multiple, where a float reciprocal rounds up
+    v(1, 65535, Identity, 8_322_944, 126),
+    v(1, 65535, Identity, 8_322_945, 127),
+    v(1, 65535, Identity, -8_322_944, -126),
+    // Activations apply to the clamped value; leaky divides towards zero too
+    v(1, 2, Relu, -3, 0),
+    v(1, 1, Relu6, 100, 96),
+    v(1, 1, Leaky, -7, 0),
+    v(1, 1, Leaky, -9, -1),
+    v(1, 1, Leaky, -200, -16),
+    // Floor only differs from truncation on negative quotients with a remainder
+    m(1, 2, Floor, Saturate, 3, 1),
+    m(1, 2, Floor, Saturate, -3, -2),
+    m(1, 3, Floor, Saturate, -6, -2),
+    m(1, 65535, Floor, Saturate, -1, -1),
+    // Halves go away from zero in both directions; just below a half does not
+    m(1, 2, HalfAwayFromZero, Saturate, 3, 2),
+    m(1, 2, HalfAwayFromZero, Saturate, -3, -2),
+    m(1, 3, HalfAwayFromZero, Saturate, 4, 1),
+    m(1, 3, HalfAwayFromZero, Saturate, -5, -2),
+    m(1, 65535, HalfAwayFromZero, Saturate, 32767, 0),
+    m(1, 65535, HalfAwayFromZero, Saturate, 32768, 1),
+    // Rounding happens before saturation: 126.5 rounds to 127, -128.5 saturates
+    m(1, 2, HalfAwayFromZero, Saturate, 253, 127),
+    m(1, 2, HalfAwayFromZero, Saturate, -257, -128),
+    // Wrapping keeps the low 8 bits of the rounded quotient
+    m(1, 1, TowardZero, Wrap, 127, 127),
+    m(1, 1, TowardZero, Wrap, 128, -128),
+    m(1, 1, TowardZero, Wrap, 255, -1),
+    m(1, 1, TowardZero, Wrap, 256, 0),
+    m(1, 1, TowardZero, Wrap, -129, 127),
+    m(1, 1, TowardZero, Wrap, -256, 0),
+    m(256, 1, TowardZero, Wrap, 8_388_609, 0),
+    m(1, 2, Floor, Wrap, -257, 127),
+    m(1, 2, HalfAwayFromZero, Wrap, 255, -128),
+];
+
+/// Scales the startup sweep runs, besides those of `CONTRACT_VECTORS`: both ends
+/// of the range and denominators with no power-of-two shortcut.
+pub const SWEEP_SCALES: &[(i32, i32)] = &[
+    (1, 1),
+    (1, 3),
+    (7, 3),
+    (1, 65535),
+    (255, 65536),
+    (256, 1),
+    (256, 65521),
+    (193, 24577),
+];
+
+/// Accumulators around every point where `scale`'s output changes in a way a
+/// backend can get wrong: the clamp limits, where wrapping comes round again, zero,
+/// and the halfway points between them, each with its neighbours. Values beyond
+/// `max_abs` are left out.
+pub fn edge_accumulators(num: i32, den: i32, max_abs: i64) -> Vec<i64> {
+    let (num, den) = (num as i64, den as i64);
+    let mut accs = Vec::new();
+    for q in [-257i64, -256, -129, -128, -127, -2, -1, 0, 1, 2, 126, 127, 128, 255, 256] {
+        // First accumulator whose quotient reaches q, and halfway to q + 1
+        for boundary in [q * den / num, (2 * q + 1) * den / (2 * num)] {
+            accs.extend([boundary - 1, boundary, boundary + 1]);
+        }
+    }
+    // Products just past 32 bits
+    let overflow = (1i64 << 31) / num;
+    accs.extend([overflow, overflow + 1, -overflow - 1, -overflow - 2]);
+    accs.retain(|acc| acc.abs() <= max_abs);
+    accs.sort_unstable();
+    accs.dedup();
+    accs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{CpuExec, CpuKernel};
+    use crate::selftest::run_requant_sweep;
+
+    // The contract written out independently of `Requant::apply`, in 128 bits
+    fn contract(scale: &Requant, acc: i64) -> i8 {
+        let n = acc as i128 * scale.num as i128;
+        let d = scale.den as i128;
+        let q = match scale.rounding {
+            Rounding::TowardZero => n.signum() * (n.abs() / d),
+            Rounding::Floor => if n >= 0 { n / d } else { -((-n + d - 1) / d) },
+            Rounding::HalfAwayFromZero => n.signum() * ((2 * n.abs() + d) / (2 * d)),
+        };
+        let q = match scale.overflow {
+            Overflow::Saturate => q.clamp(-128, 127),
+            Overflow::Wrap => (q.rem_euclid(256) + 128).rem_euclid(256) - 128,
+        } as i8;
+        match scale.activation {
+            Activation::Relu => q.max(0),
+            Activation::Relu6 => q.clamp(0, 96),
+            Activation::Identity => q,
+            Activation::Leaky => if q < 0 { -(-(q as i16) / 8) as i8 } else { q },
+        }
+    }
+
+    fn every_scale(num: i32, den: i32) -> Vec<Requant> {
+        let mut scales = Vec::new();
+        for activation in Activation::ALL {
+            for rounding in Rounding::ALL {
+                for overflow in Overflow::ALL {
+                    scales.push(Requant { num, den, activation, rounding, overflow });
+                }
+            }
+        }
+        scales
+    }
+
+    #[test]
+    fn contract_vectors_hold_for_the_reference() {
+        for vector in CONTRACT_VECTORS {
+            assert_eq!(vector.scale().apply(vector.acc), vector.expected, "{:?}", vector);
+            assert_eq!(contract(&vector.scale(), vector.acc), vector.expected, "{:?}", vector);
+        }
+    }
+
+    #[test]
+    fn reference_matches_contract_on_every_small_accumulator() {
+        for &(num, den) in SWEEP_SCALES.iter().filter(|&&(num, den)| num <= 7 && den <= 3) {
+            for scale in every_scale(num, den) {
+                for acc in -2048..=2048 {
+                    assert_eq!(scale.apply(acc), contract(&scale, acc), "{:?} acc {}", scale, acc);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reference_matches_contract_at_edges() {
+        for &(num, den) in SWEEP_SCALES {
+            let mut accs = edge_accumulators(num, den, i64::from(i32::MAX));
+            accs.extend([i64::from(i32::MIN), i64::from(i32::MAX)]);
+            for scale in every_scale(num, den) {
+                for &acc in &accs {
+                    assert_eq!(scale.apply(acc), contract(&scale, acc), "{:?} acc {}", scale, acc);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn defaults_saturate_both_ends_before_relu() {
+        let scale = Requant::new(1, 1, Activation::Identity);
+        assert_eq!(scale.apply(-129), -128);
+        assert_eq!(scale.apply(-100_000), -128);
+        assert_eq!(scale.apply(100_000), 127);
+        // Without the lower clamp a large negative value would wrap to a positive one
+        assert_eq!(Requant::new(1, 1, Activation::Relu).apply(-384), 0);
+        assert_eq!(Requant::new(1, 1, Activation::Leaky).apply(-100_000), -16);
+    }
+
+    #[test]
+    fn mode_code_round_trips() {
+        for scale in every_scale(3, 1024) {
+            let code = scale.mode_code();
+            assert_eq!(Requant::from_mode_code(scale.num, scale.den, code), Some(scale));
+            if scale.has_default_semantics() {
+                assert_eq!(code, scale.activation.code());
+            }
+        }
+        assert_eq!(Requant::from_mode_code(1, 1, 0x30), None);
+        assert_eq!(Requant::from_mode_code(1, 1, 0x80), None);
+    }
+
+    #[test]
+    fn every_cpu_kernel_passes_the_sweep() {
+        for kernel in [CpuKernel::Scalar, crate::cpu::dispatch().kernel] {
+            let executor = CpuExec::with_kernel(kernel).unwrap();
+            let result = run_requant_sweep(&executor).unwrap();
+            assert!(result.passed, "{} kernel: {:?}", kernel, result.first_mismatch);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use thiserror::Error;
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::signing::{parse_pubkey, response_message, verify_payload};
+use crate::log_warn;
+
+/// HTTP header (and gRPC metadata key) carrying the aggregator's signature of a response.
+pub const SIGNATURE_HEADER: &str = "x-aggregator-signature";
+
+/// Responses the worker acts on, and so authenticates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseKind {
+    /// A verdict on a submitted receipt (may move prev_hash and the epoch).
+    Submit,
+    /// An epoch descriptor (`EPOCH_URL`, gRPC `GetEpoch`).
+    Epoch,
+}
+
+impl std::fmt::Display for ResponseKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResponseKind::Submit => write!(f, "submit"),
+            ResponseKind::Epoch => write!(f, "epoch"),
+        }
+    }
+}
+
+/// A response that does not carry a valid signature from `AGGREGATOR_PUBKEY`.
+#[derive(Error, Debug)]
+pub enum UnauthenticatedResponse {
+    #[error("{0} response is not signed ({SIGNATURE_HEADER} missing)")]
+    Missing(ResponseKind),
+    #[error("{0} response signature does not verify against AGGREGATOR_PUBKEY")]
+    Invalid(ResponseKind),
+}
+
+/// Checks that responses come from the aggregator holding `AGGREGATOR_PUBKEY`.
+///
+/// The aggregator signs `signing::response_message` of the body it sends, bound to
+/// the idempotency key of the receipt a verdict answers, with the same
+/// blake3-then-sha256 prehash workers use for receipts. Unauthenticated responses
+/// are counted per kind and must not be acted on.
+pub struct ResponseVerifier {
+    pubkey_hex: String,
+    network_id: Option<String>,
+    metrics: Option<Arc<PrometheusMetrics>>,
+}
+
+impl ResponseVerifier {
+    pub fn new(pubkey_hex: &str, network_id: Option<&str>) -> anyhow::Result<Self> {
+        parse_pubkey(pubkey_hex)?;
+        Ok(Self { pubkey_hex: pubkey_hex.to_string(), network_id: network_id.map(str::to_string), metrics: None })
+    }
+
+    /// Count unauthenticated responses in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<PrometheusMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// `request_key` is the idempotency key sent with the receipt a `Submit` response
+    /// answers, and empty for an `Epoch` response.
+    pub fn verify(&self, kind: ResponseKind, request_key: &str, body: &[u8], signature: Option<&str>) -> Result<(), UnauthenticatedResponse> {
+        let result = match signature {
+            None => Err(UnauthenticatedResponse::Missing(kind)),
+            Some(sig_hex) => {
+                let valid = response_message(body, self.network_id.as_deref(), request_key)
+                    .and_then(|message| verify_payload(&message, sig_hex.trim(), &self.pubkey_hex))
+                    .unwrap_or(false);
+                if valid { Ok(()) } else { Err(UnauthenticatedResponse::Invalid(kind)) }
+            }
+        };
+        if let Err(e) = &result {
+            log_warn!("[auth] ignoring {}", e);
+            if let Some(metrics) = &self.metrics {
+                metrics.record_unauthenticated_response(&kind.to_string());
+            }
+        }
+        result
+    }
+}
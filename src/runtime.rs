@@ -0,0 +1,76 @@
+use std::sync::Arc;
+use crate::attempt::{run_proof_attempt, AttemptOutput, AttemptSettings, Executor};
+use crate::config::Config;
+use crate::epoch::EpochParams;
+use crate::replay::replay_attempt_with;
+use crate::types::{Sizes, WorkReceipt};
+use crate::workload::{ProofWorkload, WorkloadRegistry};
+
+/// The worker's attempt engine for programs that embed the library: a
+/// configuration and the workloads attempts can run, the built-in GEMM and
+/// SpMM plus any the program registers.
+pub struct WorkerRuntime {
+    config: Config,
+    workloads: WorkloadRegistry,
+}
+
+impl WorkerRuntime {
+    pub fn new(config: Config) -> Self {
+        Self { config, workloads: WorkloadRegistry::new() }
+    }
+
+    /// Add a workload; receipts naming it in their `kernel_ver` can then be run and replayed.
+    pub fn register_workload(&mut self, workload: Arc<dyn ProofWorkload>) -> anyhow::Result<()> {
+        self.workloads.register(workload)
+    }
+
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn get_workloads(&self) -> &WorkloadRegistry {
+        &self.workloads
+    }
+
+    /// The built-in workload the configuration selects (`WORKLOAD`).
+    pub fn configured_workload(&self) -> Arc<dyn ProofWorkload> {
+        Arc::new(self.config.get_workload())
+    }
+
+    /// A workload by name or full `kernel_ver`.
+    pub fn workload(&self, kernel_ver: &str) -> anyhow::Result<Arc<dyn ProofWorkload>> {
+        self.workloads.resolve(kernel_ver).ok_or_else(|| anyhow::anyhow!(
+            "unknown workload '{}' (known: {})", kernel_ver, self.workloads.names().join(", ")))
+    }
+
+    /// How attempts of `epoch` run: its salt and hash, with the memory-hard stage and
+    /// requantization the epoch sets or, failing that, the configuration's
+    /// (`MEMHARD_KIB`, `REQUANT_SCALE`, ...), sampled as `WORK_ROOT_SAMPLING` configures.
+    /// The same settings the worker's main loop runs with.
+    pub fn attempt_settings(&self, epoch: &EpochParams) -> AttemptSettings {
+        AttemptSettings {
+            salt: epoch.salt,
+            memhard: self.config.get_memhard(epoch.memhard_kib),
+            scale: self.config.get_requant(epoch.requant).resolve(epoch.salt.as_ref()),
+            hash: epoch.hash_kind,
+            sampling: self.config.work_root_sampling,
+        }
+    }
+
+    /// One attempt of `workload` in `epoch`, run with `attempt_settings`.
+    pub fn run_attempt(
+        &self,
+        executor: &dyn Executor,
+        workload: &dyn ProofWorkload,
+        epoch: &EpochParams,
+        nonce: u32,
+        sizes: &Sizes,
+    ) -> anyhow::Result<AttemptOutput> {
+        run_proof_attempt(executor, workload, &epoch.prev_hash, nonce, sizes, &self.attempt_settings(epoch))
+    }
+
+    /// Run the attempt a receipt describes again, whichever registered workload it names.
+    pub fn replay(&self, executor: &dyn Executor, receipt: &WorkReceipt) -> anyhow::Result<AttemptOutput> {
+        replay_attempt_with(&self.workloads, executor, receipt)
+    }
+}
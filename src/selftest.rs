@@ -0,0 +1,236 @@
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::attempt::Executor;
+use crate::cpu::{CpuExec, CpuKernel};
+use crate::prng::DPrng;
+use crate::requant_vectors::{edge_accumulators, CONTRACT_VECTORS, SWEEP_SCALES};
+use crate::types::{Activation, Overflow, Requant, Rounding, Sizes};
+
+/// What to do when the active executor disagrees with the CPU reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelfTestPolicy {
+    /// Refuse to start (or stop running) on mismatch.
+    Refuse,
+    /// Keep running but report Degraded health.
+    Degrade,
+}
+
+impl std::str::FromStr for SelfTestPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "refuse" => Ok(SelfTestPolicy::Refuse),
+            "degrade" => Ok(SelfTestPolicy::Degrade),
+            other => Err(format!("unknown self-test policy '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for SelfTestPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelfTestPolicy::Refuse => write!(f, "refuse"),
+            SelfTestPolicy::Degrade => write!(f, "degrade"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestResult {
+    pub passed: bool,
+    pub cases: usize,
+    pub mismatched_elements: usize,
+    pub total_elements: usize,
+    /// (case sizes, flat index, expected, got) of the first mismatch
+    pub first_mismatch: Option<(Sizes, usize, i8, i8)>,
+    pub elapsed_ms: u64,
+}
+
+// Small shapes, including ones that are not multiples of common tile sizes
+fn selftest_cases() -> Vec<Sizes> {
+    vec![
+        Sizes { m: 64, n: 64, k: 64, batch: 1 },
+        Sizes { m: 67, n: 45, k: 33, batch: 1 },
+        Sizes { m: 1, n: 128, k: 257, batch: 1 },
+    ]
+}
+
+/// Deterministic inputs for a self-test case, independent of any epoch data.
+fn selftest_inputs(round: u32, case: usize, sizes: &Sizes) -> (Vec<i8>, Vec<i8>) {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"tops-worker/selftest");
+    hasher.update(&round.to_le_bytes());
+    hasher.update(&(case as u32).to_le_bytes());
+    let mut seed = [0u8; 16];
+    seed.copy_from_slice(&hasher.finalize().as_bytes()[..16]);
+    let mut prng = DPrng::from_seed(seed);
+    let a: Vec<i8> = (0..sizes.m * sizes.k).map(|_| prng.next_i8()).collect();
+    let b: Vec<i8> = (0..sizes.k * sizes.n).map(|_| prng.next_i8()).collect();
+    (a, b)
+}
+
+/// Run a small deterministic GEMM on `executor` and compare it bit-exactly against the scalar CPU kernel.
+///
+/// `round` varies the inputs between periodic re-checks so a card can't pass by caching.
+pub fn run_gemm_selftest<E: Executor + ?Sized>(executor: &E, round: u32) -> anyhow::Result<SelfTestResult> {
+    let start = Instant::now();
+    let reference = CpuExec::with_kernel(CpuKernel::Scalar)?;
+    let cases = selftest_cases();
+    let mut mismatched_elements = 0;
+    let mut total_elements = 0;
+    let mut first_mismatch = None;
+
+    // Salted epochs requantize with arbitrary scales, so the check does too, under
+    // every activation a workload can be configured with
+    let salted = Requant::from_salt(Some(blake3::hash(&round.to_le_bytes()).as_bytes()));
+    for (i, sizes) in cases.iter().enumerate() {
+        let (a, b) = selftest_inputs(round, i, sizes);
+        for activation in Activation::ALL {
+            let scale = Requant { activation, ..salted };
+            let expected = reference.run_gemm(&a, &b, sizes, scale)?;
+            let got = executor.run_gemm(&a, &b, sizes, scale)?;
+            total_elements += expected.len();
+            if got.len() != expected.len() {
+                mismatched_elements += expected.len();
+                first_mismatch.get_or_insert((sizes.clone(), got.len().min(expected.len()), 0, 0));
+                continue;
+            }
+            for (idx, (&e, &g)) in expected.iter().zip(got.iter()).enumerate() {
+                if e != g {
+                    mismatched_elements += 1;
+                    first_mismatch.get_or_insert((sizes.clone(), idx, e, g));
+                }
+            }
+        }
+    }
+
+    Ok(SelfTestResult {
+        passed: mismatched_elements == 0,
+        cases: cases.len(),
+        mismatched_elements,
+        total_elements,
+        first_mismatch,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+/// Depth of the sweep GEMM; bounds the accumulators it can produce to about ±16.6M.
+const SWEEP_K: usize = 1024;
+/// Output columns, all computing the same dot products, so kernels tile as usual.
+const SWEEP_N: usize = 8;
+
+/// An output of the requantization sweep that broke the contract.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequantMismatch {
+    pub scale: Requant,
+    pub acc: i64,
+    pub expected: i8,
+    pub got: i8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequantSweepResult {
+    pub passed: bool,
+    pub scales: usize,
+    pub accumulators: usize,
+    pub mismatches: usize,
+    pub first_mismatch: Option<RequantMismatch>,
+    pub elapsed_ms: u64,
+}
+
+// Smallest and largest accumulator `sweep_inputs` can encode
+fn sweep_range() -> (i64, i64) {
+    let rows = (SWEEP_K - 1) as i64;
+    (-127 * 128 * rows, 127 * 127 * rows + 126)
+}
+
+// A row of A per accumulator against a B whose rows are 127 except the last, which
+// is 1: row i's dot product is 127 * (sum of its first K-1 entries) + its last entry
+fn sweep_inputs(accs: &[i64]) -> (Vec<i8>, Vec<i8>) {
+    let mut a = Vec::with_capacity(accs.len() * SWEEP_K);
+    for &acc in accs {
+        let mut rest = acc.div_euclid(127);
+        for _ in 0..SWEEP_K - 1 {
+            let part = rest.clamp(-128, 127);
+            a.push(part as i8);
+            rest -= part;
+        }
+        a.push(acc.rem_euclid(127) as i8);
+    }
+    let mut b = vec![127i8; SWEEP_K * SWEEP_N];
+    b[(SWEEP_K - 1) * SWEEP_N..].fill(1);
+    (a, b)
+}
+
+/// Push edge-case accumulators through `executor`'s requantization and compare them
+/// with the integer contract of `requant_vectors`.
+///
+/// Covers every scale in `SWEEP_SCALES` and `CONTRACT_VECTORS` under every activation
+/// and every rounding and overflow mode, plus the contract vectors' own expected outputs.
+pub fn run_requant_sweep<E: Executor + ?Sized>(executor: &E) -> anyhow::Result<RequantSweepResult> {
+    let start = Instant::now();
+    let (min_acc, max_acc) = sweep_range();
+    let max_abs = max_acc.min(-min_acc);
+
+    // (scale, [(acc, expected)]) per GEMM
+    let mut runs: Vec<(Requant, Vec<(i64, i8)>)> = Vec::new();
+    let mut scales: Vec<(i32, i32)> = SWEEP_SCALES.to_vec();
+    for vector in CONTRACT_VECTORS {
+        if !scales.contains(&(vector.num, vector.den)) {
+            scales.push((vector.num, vector.den));
+        }
+    }
+    for &(num, den) in &scales {
+        let accs = edge_accumulators(num, den, max_abs);
+        // Every activation with the default semantics, every other semantics without an activation
+        let mut variants: Vec<Requant> = Activation::ALL.iter().map(|&activation| Requant::new(num, den, activation)).collect();
+        for rounding in Rounding::ALL {
+            for overflow in Overflow::ALL {
+                let scale = Requant { rounding, overflow, ..Requant::new(num, den, Activation::Identity) };
+                if !scale.has_default_semantics() {
+                    variants.push(scale);
+                }
+            }
+        }
+        for scale in variants {
+            let mut cases: Vec<(i64, i8)> = accs.iter().map(|&acc| (acc, scale.apply(acc))).collect();
+            cases.extend(CONTRACT_VECTORS.iter()
+                .filter(|v| v.scale() == scale && v.acc.abs() <= max_abs)
+                .map(|v| (v.acc, v.expected)));
+            runs.push((scale, cases));
+        }
+    }
+
+    let mut accumulators = 0;
+    let mut mismatches = 0;
+    let mut first_mismatch = None;
+    // Rows are padded with zero accumulators to the backend's alignment (cuBLASLt's
+    // int8 GEMM wants sides in multiples of 4); the padding rows are not checked
+    let alignment = executor.capabilities().alignment.max(1);
+    for (scale, cases) in &runs {
+        let mut accs: Vec<i64> = cases.iter().map(|&(acc, _)| acc).collect();
+        accs.resize(accs.len().next_multiple_of(alignment), 0);
+        let (a, b) = sweep_inputs(&accs);
+        let sizes = Sizes { m: accs.len(), n: SWEEP_N, k: SWEEP_K, batch: 1 };
+        let got = executor.run_gemm(&a, &b, &sizes, *scale)?;
+        anyhow::ensure!(got.len() == sizes.m * sizes.n, "requantization sweep returned {} outputs, expected {}", got.len(), sizes.m * sizes.n);
+        accumulators += cases.len();
+        for (row, &(acc, expected)) in cases.iter().enumerate() {
+            let out = &got[row * SWEEP_N..(row + 1) * SWEEP_N];
+            if let Some(&bad) = out.iter().find(|&&g| g != expected) {
+                mismatches += 1;
+                first_mismatch.get_or_insert(RequantMismatch { scale: *scale, acc, expected, got: bad });
+            }
+        }
+    }
+
+    Ok(RequantSweepResult {
+        passed: mismatches == 0,
+        scales: scales.len(),
+        accumulators,
+        mismatches,
+        first_mismatch,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    })
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use crate::clock::ClockSync;
+use crate::integrity::write_atomic;
+use crate::log_warn;
+
+// Sequence numbers handed out per write of the state file
+const RESERVE_BLOCK: u64 = 1024;
+// Startup floor per millisecond of wall clock; far above any real receipt rate
+const SEQ_PER_MS: u64 = 1000;
+
+/// Replay-protection stamp of one receipt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiptStamp {
+    /// Unix time in milliseconds; never lower than the previous receipt's.
+    pub issued_at_ms: u64,
+    /// Strictly increasing per device; gaps are allowed.
+    pub seq: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct DeviceSequence {
+    // Every seq below this may have been used
+    reserved: u64,
+    last_issued_at_ms: u64,
+}
+
+#[derive(Debug, Default)]
+struct SequenceState {
+    devices: HashMap<String, DeviceSequence>,
+    // Next seq to hand out, per device, this run
+    next: HashMap<String, u64>,
+}
+
+/// Hands out `(issued_at_ms, seq)` for receipts, persisted across restarts.
+///
+/// Sequence numbers are reserved in blocks: the high-water mark is written to
+/// disk before any number below it is used, so a crash never repeats one. The
+/// first number after startup is also at least `now_ms * 1000`, so restoring
+/// an old copy of the state directory cannot go back to numbers already sent.
+pub struct ReceiptSequencer {
+    path: PathBuf,
+    state: Mutex<SequenceState>,
+    clock: Option<Arc<ClockSync>>,
+}
+
+impl ReceiptSequencer {
+    /// Open the sequence state at `path`; a missing file starts fresh.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let devices = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| anyhow::anyhow!("sequence state {} is corrupt: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(anyhow::anyhow!("reading sequence state {}: {}", path.display(), e)),
+        };
+        Ok(Self { path, state: Mutex::new(SequenceState { devices, next: HashMap::new() }), clock: None })
+    }
+
+    /// Take `issued_at` from `clock`, which corrects it by the measured offset with `CLOCK_CORRECT=1`.
+    pub fn with_clock(mut self, clock: Arc<ClockSync>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Stamp the next receipt of `device_did`.
+    pub fn next(&self, device_did: &str) -> anyhow::Result<ReceiptStamp> {
+        let now_ms = match &self.clock {
+            Some(clock) => clock.now_ms(),
+            None => chrono::Utc::now().timestamp_millis(),
+        }.max(0) as u64;
+        let mut state = self.state.lock().unwrap();
+        let device = state.devices.get(device_did).cloned().unwrap_or_default();
+        let seq = match state.next.get(device_did) {
+            Some(&next) => next,
+            None => device.reserved.max(now_ms.saturating_mul(SEQ_PER_MS)),
+        };
+        if now_ms < device.last_issued_at_ms {
+            log_warn!("[sequence] clock is {} ms behind the last receipt of {}; keeping issued_at monotonic",
+                device.last_issued_at_ms - now_ms, device_did);
+        }
+        let issued_at_ms = now_ms.max(device.last_issued_at_ms);
+
+        let mut updated = DeviceSequence { reserved: device.reserved, last_issued_at_ms: issued_at_ms };
+        if seq >= device.reserved {
+            // Persist the new high-water mark before using any number below it
+            updated.reserved = seq + RESERVE_BLOCK;
+            state.devices.insert(device_did.to_string(), updated);
+            if let Err(e) = self.persist(&state.devices) {
+                state.devices.insert(device_did.to_string(), device);
+                return Err(e);
+            }
+        } else {
+            state.devices.insert(device_did.to_string(), updated);
+        }
+        state.next.insert(device_did.to_string(), seq + 1);
+        Ok(ReceiptStamp { issued_at_ms, seq })
+    }
+
+    fn persist(&self, devices: &HashMap<String, DeviceSequence>) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_atomic(&self.path, &serde_json::to_vec_pretty(devices)?)
+    }
+}
+
+/// Where `tops-worker once` picks up its nonces: the chain the last run ended on
+/// and the highest nonce it used there. Nonces are only unique per prev_hash, so
+/// a run on another chain starts over from 1 like the daemon does.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NonceCheckpoint {
+    pub prev_hash_hex: String,
+    pub highest_nonce: u32,
+    pub saved_at: String,
+}
+
+impl NonceCheckpoint {
+    pub fn new(prev_hash_hex: String, highest_nonce: u32) -> Self {
+        Self { prev_hash_hex, highest_nonce, saved_at: chrono::Utc::now().to_rfc3339() }
+    }
+
+    /// The checkpoint at `path`; a missing file means no earlier run.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Option<Self>> {
+        let path = path.as_ref();
+        match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| anyhow::anyhow!("nonce checkpoint {} is corrupt: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("reading nonce checkpoint {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Highest nonce already used on the chain of `prev_hash_hex`, if this checkpoint is on it.
+    pub fn resume_after(&self, prev_hash_hex: &str) -> Option<u32> {
+        self.prev_hash_hex.eq_ignore_ascii_case(prev_hash_hex).then_some(self.highest_nonce)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_atomic(path, &serde_json::to_vec_pretty(self)?)
+    }
+}
@@ -1,15 +1,85 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::net::TcpListener;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use crate::health::{HealthChecker, HealthResponse, MetricsResponse};
-use crate::config::Config;
-use crate::prometheus_metrics::{PrometheusMetrics, get_metric_help_text};
-use serde_json;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use serde::Deserialize;
+use crate::health::HealthChecker;
+use crate::identity::KeyRing;
+use crate::devices;
+use crate::logs::{self, LogQuery};
+use crate::metrics_schema::{self, MetricsSchema};
+use crate::pause::PauseSwitch;
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::shadow::ShadowSink;
+use crate::shutdown::{ExitReason, Shutdown};
+#[cfg(feature = "stats")]
+use crate::stats::StatsStore;
+use crate::{log_error, log_info};
+
+/// Admin operations. Over HTTP they need `ADMIN_TOKEN`; on the control socket
+/// its file permissions decide who may use them.
+pub struct AdminApi {
+    token: Option<String>,
+    keyring: Arc<KeyRing>,
+    shutdown: Arc<Shutdown>,
+    pause: Arc<PauseSwitch>,
+    shadow: Option<Arc<ShadowSink>>,
+}
+
+impl AdminApi {
+    pub fn new(token: Option<String>, keyring: Arc<KeyRing>, shutdown: Arc<Shutdown>, pause: Arc<PauseSwitch>) -> Self {
+        Self { token, keyring, shutdown, pause, shadow: None }
+    }
+    
+    /// Serve the shadow aggregator's kill switch at `/admin/shadow/on` and `/admin/shadow/off`.
+    pub fn with_shadow(mut self, shadow: Option<Arc<ShadowSink>>) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
+    // `Authorization: Bearer <token>`, compared through BLAKE3 so the check takes constant time
+    fn authorized(&self, request: &str) -> bool {
+        let Some(expected) = &self.token else { return false };
+        request.lines()
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+            .filter_map(|(_, value)| value.trim().strip_prefix("Bearer "))
+            .any(|token| blake3::hash(token.trim().as_bytes()) == blake3::hash(expected.as_bytes()))
+    }
+}
+
+/// Where a request came in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    Tcp,
+    /// The control socket; reaching it at all is the authorization.
+    Unix,
+}
+
+#[derive(Deserialize)]
+struct RotateKeyRequest {
+    // May be omitted when the worker has a single identity
+    device_did: Option<String>,
+    sk_hex: String,
+}
+
+// Endpoints that are only served when configured
+#[derive(Clone, Default)]
+struct Extensions {
+    admin: Option<Arc<AdminApi>>,
+    #[cfg(feature = "stats")]
+    stats: Option<Arc<StatsStore>>,
+}
 
 pub struct HealthServer {
     health_checker: Arc<HealthChecker>,
     prometheus_metrics: Arc<PrometheusMetrics>,
-    port: u16,
+    extensions: Extensions,
+    port: Option<u16>,
+    control_socket: Option<(PathBuf, u32)>,
 }
 
 impl HealthServer {
@@ -17,38 +87,122 @@ impl HealthServer {
         Self {
             health_checker,
             prometheus_metrics,
-            port,
+            extensions: Extensions::default(),
+            port: Some(port),
+            control_socket: None,
         }
     }
     
+    /// Serve the control socket only, with no TCP port.
+    pub fn without_http(mut self) -> Self {
+        self.port = None;
+        self
+    }
+    
+    /// Also serve every endpoint on a UNIX socket at `path`, created with
+    /// permissions `mode`. Admin endpoints need no token there.
+    pub fn with_control_socket(mut self, path: PathBuf, mode: u32) -> Self {
+        self.control_socket = Some((path, mode));
+        self
+    }
+    
+    /// Serve the `/admin/*` endpoints.
+    pub fn with_admin(mut self, admin: AdminApi) -> Self {
+        self.extensions.admin = Some(Arc::new(admin));
+        self
+    }
+    
+    /// Serve the hourly statistics at `/stats`.
+    #[cfg(feature = "stats")]
+    pub fn with_stats(mut self, stats: Arc<StatsStore>) -> Self {
+        self.extensions.stats = Some(stats);
+        self
+    }
+    
     pub async fn start(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let listener = TcpListener::bind(format!("127.0.0.1:{}", self.port)).await?;
-        println!("Health server listening on port {}", self.port);
+        if let Some((path, mode)) = &self.control_socket {
+            let control = self.spawn_control_socket(path, *mode)?;
+            if self.port.is_none() {
+                control.await?;
+                return Ok(());
+            }
+        }
+        let Some(port) = self.port else { return Ok(()) };
+        
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).await?;
+        log_info!("Health server listening on port {}", port);
         
         loop {
-            let (mut socket, _) = listener.accept().await?;
+            let (socket, _) = listener.accept().await?;
             let health_checker = Arc::clone(&self.health_checker);
             let prometheus_metrics = Arc::clone(&self.prometheus_metrics);
+            let extensions = self.extensions.clone();
             
             tokio::spawn(async move {
-                let mut buffer = [0; 1024];
-                let n = match socket.read(&mut buffer).await {
-                    Ok(n) if n == 0 => return,
-                    Ok(n) => n,
-                    Err(_) => return,
-                };
-                
-                let request = String::from_utf8_lossy(&buffer[..n]);
-                let response = Self::handle_request(&request, &health_checker, &prometheus_metrics).await;
-                
-                if let Err(_) = socket.write_all(response.as_bytes()).await {
-                    return;
-                }
+                Self::serve_connection(socket, Channel::Tcp, &health_checker, &prometheus_metrics, &extensions).await;
             });
         }
     }
     
-    async fn handle_request(request: &str, health_checker: &HealthChecker, prometheus_metrics: &PrometheusMetrics) -> String {
+    // A socket left behind by an earlier run is replaced
+    #[cfg(unix)]
+    fn spawn_control_socket(&self, path: &std::path::Path, mode: u32) -> std::io::Result<tokio::task::JoinHandle<()>> {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+        if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+        log_info!("Control socket listening at {} (mode {:o})", path.display(), mode);
+        
+        let health_checker = Arc::clone(&self.health_checker);
+        let prometheus_metrics = Arc::clone(&self.prometheus_metrics);
+        let extensions = self.extensions.clone();
+        Ok(tokio::spawn(async move {
+            loop {
+                let socket = match listener.accept().await {
+                    Ok((socket, _)) => socket,
+                    Err(e) => {
+                        log_error!("[control] accept failed: {}", e);
+                        continue;
+                    }
+                };
+                let health_checker = Arc::clone(&health_checker);
+                let prometheus_metrics = Arc::clone(&prometheus_metrics);
+                let extensions = extensions.clone();
+                tokio::spawn(async move {
+                    Self::serve_connection(socket, Channel::Unix, &health_checker, &prometheus_metrics, &extensions).await;
+                });
+            }
+        }))
+    }
+    
+    #[cfg(not(unix))]
+    fn spawn_control_socket(&self, _path: &std::path::Path, _mode: u32) -> std::io::Result<tokio::task::JoinHandle<()>> {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "the control socket needs a UNIX platform"))
+    }
+    
+    async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin>(
+        mut socket: S,
+        channel: Channel,
+        health_checker: &HealthChecker,
+        prometheus_metrics: &PrometheusMetrics,
+        extensions: &Extensions,
+    ) {
+        let mut buffer = [0; 1024];
+        let n = match socket.read(&mut buffer).await {
+            Ok(0) => return,
+            Ok(n) => n,
+            Err(_) => return,
+        };
+        
+        let request = String::from_utf8_lossy(&buffer[..n]);
+        let response = Self::handle_request(&request, channel, health_checker, prometheus_metrics, extensions).await;
+        
+        let _ = socket.write_all(response.as_bytes()).await;
+    }
+    
+    async fn handle_request(request: &str, channel: Channel, health_checker: &HealthChecker, prometheus_metrics: &PrometheusMetrics, extensions: &Extensions) -> String {
         let lines: Vec<&str> = request.lines().collect();
         if lines.is_empty() {
             return Self::error_response(400, "Bad Request");
@@ -62,7 +216,8 @@ impl HealthServer {
         }
         
         let method = parts[0];
-        let path = parts[1];
+        let path = parts[1].split_once('?').map_or(parts[1], |(path, _)| path);
+        let admin = extensions.admin.as_deref();
         
         match (method, path) {
             ("GET", "/health") => {
@@ -74,7 +229,13 @@ impl HealthServer {
             }
             ("GET", "/metrics") => {
                 let metrics = health_checker.get_metrics();
-                match serde_json::to_string(&metrics) {
+                match metrics_schema::with_deprecated_fields(&metrics).and_then(|value| serde_json::to_string(&value)) {
+                    Ok(json) => Self::json_response(200, &json),
+                    Err(_) => Self::error_response(500, "Internal Server Error"),
+                }
+            }
+            ("GET", "/metrics/schema") => {
+                match serde_json::to_string(&MetricsSchema::current()) {
                     Ok(json) => Self::json_response(200, &json),
                     Err(_) => Self::error_response(500, "Internal Server Error"),
                 }
@@ -96,6 +257,100 @@ impl HealthServer {
                     Err(_) => Self::error_response(500, "Internal Server Error"),
                 }
             }
+            ("GET", "/devices") => {
+                // Enumerated afresh on every request; driver calls can block for a while
+                match tokio::task::spawn_blocking(devices::probe).await {
+                    Ok(inventory) => match serde_json::to_string(&inventory) {
+                        Ok(json) => Self::json_response(200, &json),
+                        Err(_) => Self::error_response(500, "Internal Server Error"),
+                    },
+                    Err(_) => Self::error_response(500, "Internal Server Error"),
+                }
+            }
+            #[cfg(feature = "stats")]
+            ("GET", "/stats") => {
+                let Some(stats) = extensions.stats.as_deref() else { return Self::error_response(404, "Not Found") };
+                let query = parts[1].split_once('?').map_or("", |(_, query)| query);
+                Self::stats(query, stats)
+            }
+            ("GET", "/config") => {
+                if let Err(response) = Self::admin(request, channel, admin) {
+                    return response;
+                }
+                match serde_json::to_string(&health_checker.get_config_report()) {
+                    Ok(json) => Self::json_response(200, &json),
+                    Err(_) => Self::error_response(500, "Internal Server Error"),
+                }
+            }
+            ("GET", "/logs") => {
+                if let Err(response) = Self::admin(request, channel, admin) {
+                    return response;
+                }
+                let Some(ring) = logs::ring().filter(|ring| ring.capacity() > 0) else {
+                    return Self::error_response(404, "Not Found");
+                };
+                let query = parts[1].split_once('?').map_or("", |(_, query)| query);
+                match LogQuery::parse(query) {
+                    Ok(query) => match serde_json::to_string(&ring.query(&query)) {
+                        Ok(json) => Self::json_response(200, &json),
+                        Err(_) => Self::error_response(500, "Internal Server Error"),
+                    },
+                    Err(e) => Self::error_response(400, &e),
+                }
+            }
+            ("POST", "/admin/rotate-key") => {
+                let admin = match Self::admin(request, channel, admin) {
+                    Ok(admin) => admin,
+                    Err(response) => return response,
+                };
+                Self::rotate_key(request, admin, prometheus_metrics)
+            }
+            ("POST", "/admin/pause") => {
+                let admin = match Self::admin(request, channel, admin) {
+                    Ok(admin) => admin,
+                    Err(response) => return response,
+                };
+                if admin.pause.pause() {
+                    log_info!("[admin] attempts paused");
+                }
+                Self::json_response(200, "{\"paused\": true}")
+            }
+            ("POST", "/admin/resume") => {
+                let admin = match Self::admin(request, channel, admin) {
+                    Ok(admin) => admin,
+                    Err(response) => return response,
+                };
+                if admin.pause.resume() {
+                    log_info!("[admin] attempts resumed");
+                }
+                Self::json_response(200, "{\"paused\": false}")
+            }
+            ("POST", "/admin/shadow/on") | ("POST", "/admin/shadow/off") => {
+                let admin = match Self::admin(request, channel, admin) {
+                    Ok(admin) => admin,
+                    Err(response) => return response,
+                };
+                let Some(shadow) = &admin.shadow else {
+                    return Self::error_response(404, "No shadow aggregator configured");
+                };
+                let enabled = path == "/admin/shadow/on";
+                if shadow.set_enabled(enabled) {
+                    log_info!("[admin] copies to the shadow aggregator {}", if enabled { "resumed" } else { "stopped" });
+                }
+                Self::json_response(200, &format!("{{\"shadow_enabled\": {}}}", enabled))
+            }
+            ("POST", "/admin/restart") => {
+                let admin = match Self::admin(request, channel, admin) {
+                    Ok(admin) => admin,
+                    Err(response) => return response,
+                };
+                // The main loop drains and exits; the supervisor starts us again
+                if !admin.shutdown.request(ExitReason::Restart) {
+                    return Self::error_response(409, "Shutdown already in progress");
+                }
+                log_info!("[admin] restart requested, draining");
+                Self::json_response(202, &format!("{{\"draining\": true, \"exit_code\": {}}}", ExitReason::Restart.code()))
+            }
             ("GET", "/") => {
                 let html = r#"
 <!DOCTYPE html>
@@ -121,6 +376,10 @@ impl HealthServer {
         <h3><a href="/metrics">/metrics</a></h3>
         <p>Detailed performance metrics and statistics (JSON)</p>
     </div>
+    <div class="endpoint">
+        <h3><a href="/metrics/schema">/metrics/schema</a></h3>
+        <p>Fields of /metrics with their types and deprecations (JSON)</p>
+    </div>
     <div class="endpoint prometheus">
         <h3><a href="/prometheus">/prometheus</a></h3>
         <p>Prometheus-formatted metrics for monitoring systems</p>
@@ -129,6 +388,10 @@ impl HealthServer {
         <h3><a href="/status">/status</a></h3>
         <p>Comprehensive status including configuration and error counts</p>
     </div>
+    <div class="endpoint">
+        <h3><a href="/devices">/devices</a></h3>
+        <p>Compute devices every backend can see, and the one in use (JSON)</p>
+    </div>
 </body>
 </html>
                 "#;
@@ -138,6 +401,76 @@ impl HealthServer {
         }
     }
     
+    // `?from=&to=` as unix seconds, RFC 3339 or YYYY-MM-DD; the last 24 hours by default
+    #[cfg(feature = "stats")]
+    fn stats(query: &str, stats: &StatsStore) -> String {
+        let mut from = None;
+        let mut to = None;
+        for (name, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            let bound = match name {
+                "from" => &mut from,
+                "to" => &mut to,
+                _ => continue,
+            };
+            match crate::stats::parse_time(value) {
+                Some(t) => *bound = Some(t),
+                None => return Self::error_response(400, &format!("Invalid time for {}: {}", name, value)),
+            }
+        }
+        let to = to.unwrap_or_else(|| chrono::Utc::now().timestamp());
+        let from = from.unwrap_or(to - 24 * 3600);
+        if from >= to {
+            return Self::error_response(400, "from must be before to");
+        }
+        
+        let series = tokio::task::block_in_place(|| stats.query(from, to));
+        match series.map(|series| serde_json::to_string(&series)) {
+            Ok(Ok(json)) => Self::json_response(200, &json),
+            _ => Self::error_response(500, "Internal Server Error"),
+        }
+    }
+    
+    // Admin endpoints over TCP need the bearer token, and do not exist without one
+    fn admin<'a>(request: &str, channel: Channel, admin: Option<&'a AdminApi>) -> Result<&'a AdminApi, String> {
+        match admin {
+            Some(admin) if channel == Channel::Unix => Ok(admin),
+            Some(admin) if admin.token.is_some() => {
+                if admin.authorized(request) {
+                    Ok(admin)
+                } else {
+                    Err(Self::error_response(401, "Unauthorized"))
+                }
+            }
+            _ => Err(Self::error_response(404, "Not Found")),
+        }
+    }
+    
+    fn rotate_key(request: &str, admin: &AdminApi, prometheus_metrics: &PrometheusMetrics) -> String {
+        let body = request.split_once("\r\n\r\n").map_or("", |(_, body)| body);
+        let req: RotateKeyRequest = match serde_json::from_str(body) {
+            Ok(req) => req,
+            Err(_) => return Self::error_response(400, "Bad Request"),
+        };
+        let device_did = match req.device_did {
+            Some(did) => did,
+            None if admin.keyring.len() == 1 => admin.keyring.identities()[0].device_did.clone(),
+            None => return Self::error_response(400, "device_did is required with several identities"),
+        };
+        match admin.keyring.rotate_hex(&device_did, &req.sk_hex) {
+            Ok(rotation) => {
+                prometheus_metrics.record_key_rotation(&rotation.device_did, "admin", rotation.key_epoch);
+                match serde_json::to_string(&rotation) {
+                    Ok(json) => Self::json_response(200, &json),
+                    Err(_) => Self::error_response(500, "Internal Server Error"),
+                }
+            }
+            Err(e) => {
+                log_error!("[admin] key rotation of {} failed: {}", device_did, e);
+                Self::error_response(400, "Key rotation failed")
+            }
+        }
+    }
+    
     fn json_response(status: u16, body: &str) -> String {
         format!(
             "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
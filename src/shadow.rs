@@ -0,0 +1,207 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use crate::epoch_summary::EpochSummary;
+use crate::lifecycle::redact_url;
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::submit::{EpochInfo, SubmitError, SubmitOutcome, Submission, Submitter};
+use crate::types::WorkReceipt;
+use crate::log_warn;
+
+/// A second aggregator that gets a copy of every receipt (`SHADOW_AGGREGATOR_URL`),
+/// for dual-writing during a migration.
+///
+/// Copies go out in the background, after the primary submission and whatever its
+/// outcome; nothing the shadow answers reaches the primary's accounting (attempt
+/// counts, endpoint health, rate control, circuit breaker, quarantine). Copies beyond
+/// `max_in_flight` are dropped rather than queued, so a slow shadow costs nothing.
+pub struct ShadowSink {
+    // For logs and /status only, so with any password redacted
+    url: String,
+    submitter: Arc<dyn Submitter>,
+    enabled: AtomicBool,
+    in_flight: Arc<Semaphore>,
+    max_in_flight: usize,
+    counts: Arc<ShadowCounts>,
+    metrics: Option<Arc<PrometheusMetrics>>,
+}
+
+#[derive(Default)]
+struct ShadowCounts {
+    sent: AtomicU64,
+    accepted: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+/// Shadow aggregator reported in /status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShadowStatus {
+    pub url: String,
+    pub enabled: bool,
+    /// Copies sent, whatever came of them.
+    pub sent: u64,
+    pub accepted: u64,
+    /// Copies the shadow rejected, throttled or never answered.
+    pub failed: u64,
+    /// Copies not sent: `max_in_flight` were still outstanding.
+    pub dropped: u64,
+    pub in_flight: usize,
+    pub last_error: Option<String>,
+}
+
+impl ShadowSink {
+    /// Copies go through `submitter`, which should not share endpoints, connection stats
+    /// or metrics with the primary.
+    pub fn new(url: String, submitter: Arc<dyn Submitter>, enabled: bool, max_in_flight: usize) -> Self {
+        Self {
+            url: redact_url(&url),
+            submitter,
+            enabled: AtomicBool::new(enabled),
+            in_flight: Arc::new(Semaphore::new(max_in_flight)),
+            max_in_flight,
+            counts: Arc::new(ShadowCounts::default()),
+            metrics: None,
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: Option<Arc<PrometheusMetrics>>) -> Self {
+        if let Some(metrics) = &metrics {
+            metrics.set_shadow_enabled(self.is_enabled());
+        }
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// The kill switch. Returns false when it was already in that state; copies
+    /// already in flight still complete.
+    pub fn set_enabled(&self, enabled: bool) -> bool {
+        if self.enabled.swap(enabled, Ordering::SeqCst) == enabled {
+            return false;
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.set_shadow_enabled(enabled);
+        }
+        true
+    }
+
+    pub fn status(&self) -> ShadowStatus {
+        ShadowStatus {
+            url: self.url.clone(),
+            enabled: self.is_enabled(),
+            sent: self.counts.sent.load(Ordering::Relaxed),
+            accepted: self.counts.accepted.load(Ordering::Relaxed),
+            failed: self.counts.failed.load(Ordering::Relaxed),
+            dropped: self.counts.dropped.load(Ordering::Relaxed),
+            in_flight: self.max_in_flight - self.in_flight.available_permits(),
+            last_error: self.counts.last_error.lock().ok().and_then(|e| e.clone()),
+        }
+    }
+
+    // Send a copy of `receipt` in the background, if enabled and below the in-flight limit
+    fn copy(&self, receipt: WorkReceipt) {
+        if !self.is_enabled() {
+            return;
+        }
+        let Ok(permit) = Arc::clone(&self.in_flight).try_acquire_owned() else {
+            self.counts.dropped.fetch_add(1, Ordering::Relaxed);
+            if let Some(metrics) = &self.metrics {
+                metrics.record_shadow_receipt("dropped", None);
+            }
+            return;
+        };
+        let submitter = Arc::clone(&self.submitter);
+        let counts = Arc::clone(&self.counts);
+        let metrics = self.metrics.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let nonce = receipt.nonce;
+            let started = Instant::now();
+            let result = submitter.submit(receipt).await;
+            let latency = started.elapsed();
+            let (outcome, error) = match result {
+                Ok(submission) => match submission.outcome {
+                    SubmitOutcome::Accepted { .. } | SubmitOutcome::Queued => ("accepted", None),
+                    SubmitOutcome::Throttled { status, body, .. } => ("throttled", Some(format!("HTTP {}: {}", status, body.trim()))),
+                    SubmitOutcome::Rejected { status, body } => ("rejected", Some(format!("HTTP {}: {}", status, body.trim()))),
+                    SubmitOutcome::Failed { kind, error } => ("failed", Some(format!("{}: {}", kind, error))),
+                },
+                Err(e) => ("error", Some(e.to_string())),
+            };
+            counts.sent.fetch_add(1, Ordering::Relaxed);
+            match &error {
+                None => {
+                    counts.accepted.fetch_add(1, Ordering::Relaxed);
+                }
+                Some(e) => {
+                    counts.failed.fetch_add(1, Ordering::Relaxed);
+                    if let Ok(mut last) = counts.last_error.lock() {
+                        *last = Some(e.clone());
+                    }
+                    log_warn!("[shadow] copy of nonce {} {}: {}", nonce, outcome, e);
+                }
+            }
+            if let Some(metrics) = &metrics {
+                metrics.record_shadow_receipt(outcome, Some(latency));
+            }
+        });
+    }
+}
+
+/// Submits through the primary transport and copies each receipt to a [`ShadowSink`].
+/// Everything the caller sees is the primary's.
+pub struct ShadowSubmitter {
+    inner: Arc<dyn Submitter>,
+    shadow: Arc<ShadowSink>,
+}
+
+impl ShadowSubmitter {
+    pub fn new(inner: Arc<dyn Submitter>, shadow: Arc<ShadowSink>) -> Self {
+        Self { inner, shadow }
+    }
+}
+
+#[async_trait]
+impl Submitter for ShadowSubmitter {
+    fn describe(&self) -> String {
+        format!("{}, shadowed to {}", self.inner.describe(), self.shadow.url())
+    }
+
+    async fn submit(&self, receipt: WorkReceipt) -> Result<Submission, SubmitError> {
+        let copy = receipt.clone();
+        let submission = self.inner.submit(receipt).await;
+        // A receipt the primary refused to sign, or had already delivered, is not copied
+        if submission.is_ok() {
+            self.shadow.copy(copy);
+        }
+        submission
+    }
+
+    async fn current_epoch(&self) -> anyhow::Result<Option<EpochInfo>> {
+        self.inner.current_epoch().await
+    }
+
+    fn pending(&self) -> usize {
+        self.inner.pending()
+    }
+
+    async fn drain_one(&self) -> bool {
+        self.inner.drain_one().await
+    }
+
+    async fn submit_summary(&self, summary: EpochSummary) -> anyhow::Result<bool> {
+        self.inner.submit_summary(summary).await
+    }
+}
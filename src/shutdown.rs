@@ -0,0 +1,154 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use crate::config::ConfigError;
+use crate::{log_info, log_warn};
+
+/// Why the worker exited, as a process exit code a supervisor can branch on
+/// (systemd `OnFailure=`, `RestartPreventExitStatus=`, container restart policies).
+///
+/// The non-zero codes follow `sysexits.h` where one fits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// Drained after SIGTERM / SIGINT.
+    Stopped,
+    /// Any error not covered below.
+    Failure,
+    /// Drained after `POST /admin/restart`; the supervisor should start the worker again.
+    Restart,
+    /// No usable execution backend (GPU initialization failed without a CPU fallback).
+    BackendInit,
+    /// The self-test refused the backend (`SELFTEST_ON_MISMATCH=refuse`).
+    SelfTest,
+    /// Invalid configuration; restarting with the same settings will not help.
+    Config,
+}
+
+impl ExitReason {
+    pub fn code(&self) -> u8 {
+        match self {
+            ExitReason::Stopped => 0,
+            ExitReason::Failure => 1,
+            ExitReason::BackendInit => 69, // EX_UNAVAILABLE
+            ExitReason::SelfTest => 70,    // EX_SOFTWARE
+            ExitReason::Restart => 75,     // EX_TEMPFAIL
+            ExitReason::Config => 78,      // EX_CONFIG
+        }
+    }
+
+    /// Exit reason for a fatal error: configuration errors are recognized by type,
+    /// other classes by an `ExitReason` attached with `anyhow::Context`.
+    pub fn of(error: &anyhow::Error) -> Self {
+        if error.downcast_ref::<ConfigError>().is_some() {
+            return ExitReason::Config;
+        }
+        error.downcast_ref::<ExitReason>().copied().unwrap_or(ExitReason::Failure)
+    }
+}
+
+impl std::fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExitReason::Stopped => write!(f, "stopped"),
+            ExitReason::Failure => write!(f, "failure"),
+            ExitReason::Restart => write!(f, "restart requested"),
+            ExitReason::BackendInit => write!(f, "execution backend initialization failed"),
+            ExitReason::SelfTest => write!(f, "self-test failed"),
+            ExitReason::Config => write!(f, "invalid configuration"),
+        }
+    }
+}
+
+impl From<ExitReason> for std::process::ExitCode {
+    fn from(reason: ExitReason) -> Self {
+        std::process::ExitCode::from(reason.code())
+    }
+}
+
+/// A graceful shutdown request shared by signal handlers, the admin API and the main loop.
+///
+/// The main loop checks `requested()` once per iteration, so the attempt being
+/// submitted when the request arrives is still delivered before the worker exits.
+#[derive(Debug, Default)]
+pub struct Shutdown {
+    requested: Mutex<Option<ExitReason>>,
+    notify: Notify,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the worker to drain and exit with `reason`; the first request wins.
+    /// Returns false if a shutdown was already under way.
+    pub fn request(&self, reason: ExitReason) -> bool {
+        let Ok(mut requested) = self.requested.lock() else { return false };
+        if requested.is_some() {
+            return false;
+        }
+        *requested = Some(reason);
+        self.notify.notify_waiters();
+        true
+    }
+
+    pub fn requested(&self) -> Option<ExitReason> {
+        self.requested.lock().ok().and_then(|r| *r)
+    }
+
+    /// Resolve once a shutdown has been requested; used to cut waits short.
+    pub async fn wait(&self) {
+        loop {
+            let notified = self.notify.notified();
+            if self.requested().is_some() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Turn SIGINT and SIGTERM into a graceful `Stopped` shutdown; a second signal exits at once.
+    pub fn listen_for_signals(self: &Arc<Self>) {
+        let shutdown = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                if wait_for_signal().await.is_err() {
+                    return;
+                }
+                if !shutdown.request(ExitReason::Stopped) {
+                    log_warn!("[shutdown] second signal, exiting without draining");
+                    std::process::exit(ExitReason::Stopped.code() as i32);
+                }
+                log_info!("[shutdown] signal received, draining");
+            }
+        });
+    }
+
+    /// Exit with `reason` if the drain has not finished within `timeout`.
+    pub fn arm_drain_deadline(&self, timeout: Duration) {
+        let Some(reason) = self.requested() else { return };
+        std::thread::Builder::new()
+            .name("drain-deadline".into())
+            .spawn(move || {
+                std::thread::sleep(timeout);
+                log_warn!("[shutdown] drain did not finish within {}s, exiting", timeout.as_secs());
+                std::process::exit(reason.code() as i32);
+            })
+            .expect("failed to spawn drain deadline thread");
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut term = signal(SignalKind::terminate())?;
+    tokio::select! {
+        r = tokio::signal::ctrl_c() => r,
+        _ = term.recv() => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
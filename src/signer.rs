@@ -0,0 +1,346 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use crate::config::Config;
+use crate::epoch_summary::EpochSummary;
+use crate::identity::{Identity, KeyRing};
+use crate::mock_aggregator::{read_request_with, reason_phrase, Request};
+use crate::signing::verify_receipt;
+use crate::submit::sign_receipt;
+use crate::types::WorkReceipt;
+use crate::{log_error, log_warn};
+
+// Same floor as ADMIN_TOKEN
+const MIN_TOKEN_LEN: usize = 16;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// How long a client has to send its whole request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// A receipt or epoch summary is a few KiB of JSON
+const MAX_SIGN_BODY_BYTES: usize = 256 * 1024;
+
+/// An identity a signer holds, as its `GET /identities` lists it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignerIdentity {
+    pub device_did: String,
+    pub pubkey_hex: String,
+    pub key_epoch: u32,
+    pub weight: u32,
+}
+
+/// A document sent to a signer's `POST /sign`, and returned signed:
+/// `{"receipt": {...}}` or `{"epoch_summary": {...}}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Signable {
+    Receipt(Box<WorkReceipt>),
+    EpochSummary(EpochSummary),
+}
+
+/// A signer's answer to `POST /sign`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignResponse {
+    pub signed: Signable,
+    /// Key the signature verifies against.
+    pub pubkey_hex: String,
+}
+
+/// The remote signer could not be reached or answered with a server error (5xx,
+/// 408 or 429): asking it again later may well succeed.
+#[derive(Debug, Error)]
+#[error("remote signer unavailable: {0}")]
+pub struct SignerUnavailable(pub String);
+
+/// Client of a remote signer (`REMOTE_SIGNER_URL`): a compute node sends it the
+/// receipts and epoch summaries it would otherwise sign, and holds no key itself.
+pub struct RemoteSigner {
+    client: reqwest::Client,
+    url: reqwest::Url,
+    token: Option<String>,
+}
+
+impl RemoteSigner {
+    /// `url` is the signer's base URL; `token` goes out as a bearer token.
+    pub fn new(client: reqwest::Client, url: &str, token: Option<String>) -> anyhow::Result<Self> {
+        Ok(Self { client, url: reqwest::Url::parse(url)?, token })
+    }
+
+    /// The signer `REMOTE_SIGNER_URL` names, if set.
+    pub fn from_config(config: &Config) -> anyhow::Result<Option<Self>> {
+        config.remote_signer_url.as_deref()
+            .map(|url| Self::new(reqwest::Client::new(), url, config.remote_signer_token.clone()))
+            .transpose()
+    }
+
+    pub fn url(&self) -> &reqwest::Url {
+        &self.url
+    }
+
+    /// The identities the signer holds.
+    pub async fn identities(&self) -> anyhow::Result<Vec<SignerIdentity>> {
+        let resp = self.send(self.client.get(self.url.join("identities")?)).await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Have `receipt` signed in its current `receipt_version` by `pinned`, the
+    /// identity as the signer listed it at startup. Only the signature and the key
+    /// epoch are taken from the answer; the key the signer reports must be the
+    /// pinned key of that epoch, and the signature must verify against it over the
+    /// receipt as it was sent.
+    pub async fn sign_receipt(&self, receipt: &mut WorkReceipt, pinned: &Identity) -> anyhow::Result<()> {
+        let response = self.sign(Signable::Receipt(Box::new(receipt.clone()))).await?;
+        let Signable::Receipt(signed) = response.signed else {
+            anyhow::bail!("the signer returned an epoch summary for a receipt");
+        };
+        let key_epoch = signed.key_epoch.unwrap_or(0);
+        let pubkey_hex = pinned.key_for_epoch(key_epoch)
+            .ok_or_else(|| anyhow::anyhow!("the signer signed with key epoch {} of {}, which was not pinned at startup",
+                key_epoch, pinned.device_did))?
+            .pubkey_hex_compressed();
+        check_pinned(&response.pubkey_hex, &pubkey_hex, &pinned.device_did)?;
+        receipt.key_epoch = signed.key_epoch;
+        receipt.sig_hex = signed.sig_hex;
+        if !verify_receipt(receipt, &pubkey_hex)? {
+            anyhow::bail!("the signer's receipt signature does not verify against the pinned key");
+        }
+        Ok(())
+    }
+
+    /// Have `summary` signed by `pinned`; its `pubkey_hex` becomes the pinned key,
+    /// which the signer must report and the signature verify against.
+    pub async fn sign_summary(&self, summary: &mut EpochSummary, pinned: &Identity) -> anyhow::Result<()> {
+        let response = self.sign(Signable::EpochSummary(summary.clone())).await?;
+        let Signable::EpochSummary(signed) = response.signed else {
+            anyhow::bail!("the signer returned a receipt for an epoch summary");
+        };
+        let pubkey_hex = pinned.secp().pubkey_hex_compressed();
+        check_pinned(&response.pubkey_hex, &pubkey_hex, &pinned.device_did)?;
+        summary.pubkey_hex = pubkey_hex;
+        summary.sig_hex = signed.sig_hex;
+        if !summary.verify(&summary.pubkey_hex)? {
+            anyhow::bail!("the signer's epoch summary signature does not verify against the pinned key");
+        }
+        Ok(())
+    }
+
+    async fn sign(&self, document: Signable) -> anyhow::Result<SignResponse> {
+        let resp = self.send(self.client.post(self.url.join("sign")?).json(&document)).await?;
+        Ok(resp.json().await.map_err(|e| SignerUnavailable(e.to_string()))?)
+    }
+
+    // Transport failures and server errors come back as `SignerUnavailable`
+    async fn send(&self, request: reqwest::RequestBuilder) -> anyhow::Result<reqwest::Response> {
+        let request = match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        let resp = request.timeout(REQUEST_TIMEOUT).send().await
+            .map_err(|e| SignerUnavailable(e.to_string()))?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            let message = format!("signer answered HTTP {}: {}", status, body.trim());
+            if status.is_server_error() || status.as_u16() == 408 || status.as_u16() == 429 {
+                return Err(SignerUnavailable(message).into());
+            }
+            anyhow::bail!(message);
+        }
+        Ok(resp)
+    }
+}
+
+// The key a signer reports for a signature has to be the one pinned for the identity
+fn check_pinned(reported: &str, pinned: &str, device_did: &str) -> anyhow::Result<()> {
+    let same = match (crate::signing::Secp::public_only(reported), crate::signing::Secp::public_only(pinned)) {
+        (Ok(reported), Ok(pinned)) => reported.pubkey_hex_compressed() == pinned.pubkey_hex_compressed(),
+        _ => false,
+    };
+    if !same {
+        anyhow::bail!("the signer reports key {} for {}, not the pinned {}", reported, device_did, pinned);
+    }
+    Ok(())
+}
+
+/// Clients a signer serves (`SIGNER_ALLOWLIST`): source IP addresses, and bearer
+/// tokens written `token:<secret>`. A request is served when either matches.
+#[derive(Debug, Clone, Default)]
+pub struct SignerAllowlist {
+    addresses: Vec<IpAddr>,
+    // Compared through BLAKE3, so the check takes constant time
+    tokens: Vec<blake3::Hash>,
+}
+
+impl SignerAllowlist {
+    pub fn parse(entries: &[String]) -> Result<Self, String> {
+        let mut allowlist = Self::default();
+        for entry in entries.iter().map(|e| e.trim()).filter(|e| !e.is_empty()) {
+            if let Some(token) = entry.strip_prefix("token:") {
+                if token.len() < MIN_TOKEN_LEN {
+                    return Err(format!("tokens must be at least {} characters", MIN_TOKEN_LEN));
+                }
+                allowlist.tokens.push(blake3::hash(token.as_bytes()));
+            } else {
+                let address = entry.parse()
+                    .map_err(|_| format!("'{}' is neither an IP address nor token:<secret>", entry))?;
+                allowlist.addresses.push(address);
+            }
+        }
+        Ok(allowlist)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty() && self.tokens.is_empty()
+    }
+
+    /// Whether a connection from `peer` can be served at all: its address is listed,
+    /// or tokens are in use and it may still present one.
+    pub fn admits_peer(&self, peer: IpAddr) -> bool {
+        !self.tokens.is_empty() || self.allows(peer, None)
+    }
+
+    pub fn allows(&self, peer: IpAddr, bearer: Option<&str>) -> bool {
+        // IPv4 clients of a dual-stack listener show up as IPv4-mapped IPv6 addresses
+        let peer = peer.to_canonical();
+        self.addresses.iter().any(|address| address.to_canonical() == peer)
+            || bearer.is_some_and(|token| self.tokens.contains(&blake3::hash(token.as_bytes())))
+    }
+}
+
+/// The signer role (`tops-worker signer`): holds the keys on a hardened host and
+/// signs the receipts and epoch summaries allowlisted compute nodes send it.
+pub struct SignerService {
+    keys: Arc<KeyRing>,
+    allowlist: SignerAllowlist,
+    network_id: Option<String>,
+}
+
+impl SignerService {
+    pub fn new(keys: Arc<KeyRing>, allowlist: SignerAllowlist, network_id: Option<String>) -> Self {
+        Self { keys, allowlist, network_id }
+    }
+
+    /// Bind `listen` and serve `GET /health`, `GET /identities` and `POST /sign`
+    /// until the task is dropped.
+    pub async fn serve(self: Arc<Self>, listen: SocketAddr) -> anyhow::Result<()> {
+        let listener = TcpListener::bind(listen).await?;
+        loop {
+            let (socket, peer) = listener.accept().await?;
+            // Nothing is read from a peer that could never be served
+            if !self.allowlist.admits_peer(peer.ip()) {
+                log_warn!("[signer] refused connection from {}: not allowlisted", peer);
+                continue;
+            }
+            let this = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(e) = this.handle_connection(socket, peer.ip()).await {
+                    log_error!("[signer] connection from {} failed: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    // One request per connection, read within `READ_TIMEOUT`; the body only once the client is allowlisted
+    async fn handle_connection(&self, mut socket: TcpStream, peer: IpAddr) -> anyhow::Result<()> {
+        let read = read_request_with(&mut socket, MAX_SIGN_BODY_BYTES, |request| self.allows(request, peer));
+        let request = tokio::time::timeout(READ_TIMEOUT, read).await
+            .map_err(|_| anyhow::anyhow!("no complete request within {:?}", READ_TIMEOUT))??;
+        let Some(request) = request else { return Ok(()) };
+        let (status, body) = self.route(&request, peer);
+        let head = format!("HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status, reason_phrase(status), body.len());
+        socket.write_all(head.as_bytes()).await?;
+        socket.write_all(body.as_bytes()).await?;
+        socket.shutdown().await?;
+        Ok(())
+    }
+
+    fn route(&self, request: &Request, peer: IpAddr) -> (u16, String) {
+        let route = (request.method.as_str(), request.path.as_str());
+        if route == ("GET", "/health") {
+            return (200, serde_json::json!({ "ok": true, "identities": self.keys.len() }).to_string());
+        }
+        if !self.allows(request, peer) {
+            log_warn!("[signer] refused {} {} from {}: not allowlisted", request.method, request.path, peer);
+            return error(403, "not allowlisted");
+        }
+        match route {
+            ("GET", "/identities") => match serde_json::to_string(&self.identities()) {
+                Ok(json) => (200, json),
+                Err(e) => error(500, &e.to_string()),
+            },
+            ("POST", "/sign") => {
+                let document = match serde_json::from_slice::<Signable>(&request.body) {
+                    Ok(document) => document,
+                    Err(e) => return error(400, &format!("not a receipt or epoch summary: {}", e)),
+                };
+                match self.sign(document).map(|response| serde_json::to_string(&response)) {
+                    Ok(Ok(json)) => (200, json),
+                    Ok(Err(e)) => error(500, &e.to_string()),
+                    Err((status, message)) => {
+                        log_warn!("[signer] refused to sign for {}: {}", peer, message);
+                        error(status, &message)
+                    }
+                }
+            }
+            _ => error(404, "not found"),
+        }
+    }
+
+    // The request's source address or bearer token is on the allowlist
+    fn allows(&self, request: &Request, peer: IpAddr) -> bool {
+        let bearer = request.header("authorization").and_then(|v| v.strip_prefix("Bearer ")).map(str::trim);
+        self.allowlist.allows(peer, bearer)
+    }
+
+    /// The identities this signer holds, with their current keys.
+    pub fn identities(&self) -> Vec<SignerIdentity> {
+        self.keys.identities().iter()
+            .map(|identity| {
+                let key = identity.active_key();
+                SignerIdentity {
+                    device_did: identity.device_did.clone(),
+                    pubkey_hex: key.secp.pubkey_hex_compressed(),
+                    key_epoch: key.epoch,
+                    weight: identity.weight,
+                }
+            })
+            .collect()
+    }
+
+    /// Sign `document` with the key of its `device_did`. Documents for another
+    /// network than the signer's, or for identities it does not hold, are refused
+    /// with the HTTP status to answer.
+    pub fn sign(&self, document: Signable) -> Result<SignResponse, (u16, String)> {
+        let (device_did, network_id) = match &document {
+            Signable::Receipt(receipt) => (&receipt.device_did, &receipt.network_id),
+            Signable::EpochSummary(summary) => (&summary.device_did, &summary.network_id),
+        };
+        let Some(identity) = self.keys.identity(device_did) else {
+            return Err((422, format!("no key for {}", device_did)));
+        };
+        if *network_id != self.network_id {
+            return Err((422, format!("document is for network {}, this signer signs for {}",
+                network_id.as_deref().unwrap_or("<none>"), self.network_id.as_deref().unwrap_or("<none>"))));
+        }
+        match document {
+            Signable::Receipt(mut receipt) => {
+                sign_receipt(&self.keys, &mut receipt).map_err(|e| (500, e.to_string()))?;
+                let pubkey_hex = identity.key_for_epoch(receipt.key_epoch.unwrap_or(0))
+                    .map_or_else(|| identity.secp().pubkey_hex_compressed(), |secp| secp.pubkey_hex_compressed());
+                Ok(SignResponse { signed: Signable::Receipt(receipt), pubkey_hex })
+            }
+            Signable::EpochSummary(mut summary) => {
+                summary.sign(&identity.secp()).map_err(|e| (500, e.to_string()))?;
+                let pubkey_hex = summary.pubkey_hex.clone();
+                Ok(SignResponse { signed: Signable::EpochSummary(summary), pubkey_hex })
+            }
+        }
+    }
+}
+
+fn error(status: u16, message: &str) -> (u16, String) {
+    (status, serde_json::json!({ "error": message }).to_string())
+}
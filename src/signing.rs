@@ -1,32 +1,116 @@
-use blake3::Hasher;
 use hex::ToHex;
-use k256::ecdsa::{SigningKey, Signature};
-use k256::ecdsa::signature::hazmat::PrehashSigner;
+use k256::ecdsa::{SigningKey, Signature, VerifyingKey};
+use k256::ecdsa::signature::hazmat::{PrehashSigner, PrehashVerifier};
 
-use sha2::Digest;
 use crate::types::WorkReceipt;
 
-pub struct Secp { sk: SigningKey }
+// The receipt domain and the prehash are the verification core's, so the worker
+// signs exactly what verifiers check
+pub use tops_verify_core::receipt::{prehash, RECEIPT_DOMAIN};
+// Aggregator responses are bound to `<RESPONSE_DOMAIN><network_id>` the same way
+const RESPONSE_DOMAIN: &str = "tops-aggregator/v2/";
+// Fleet config documents have their own domain, so no aggregator response passes for one
+const FLEET_CONFIG_DOMAIN: &str = "tops-fleet-config/v1/";
+// Release manifests are not tied to a network, so their domain has no network ID
+const RELEASE_MANIFEST_DOMAIN: &str = "tops-release-manifest/v1/";
+
+/// A secp256k1 key; without the secret half (`public_only`) it can name a key
+/// that signs elsewhere but not sign itself.
+pub struct Secp { sk: Option<SigningKey>, vk: VerifyingKey }
 
 impl Secp {
     pub fn from_hex(sk_hex: &str) -> anyhow::Result<Self> {
         let bytes = hex::decode(sk_hex)?;
-        Ok(Self { sk: SigningKey::from_bytes(bytes.as_slice().into())? })
+        let sk = SigningKey::from_bytes(bytes.as_slice().into())?;
+        Ok(Self { vk: *sk.verifying_key(), sk: Some(sk) })
+    }
+    /// The public key of a key held by a remote signer.
+    pub fn public_only(pubkey_hex: &str) -> anyhow::Result<Self> {
+        Ok(Self { sk: None, vk: parse_pubkey(pubkey_hex)? })
     }
     pub fn sign_receipt(&self, r: &WorkReceipt) -> anyhow::Result<String> {
-        // Hash a stable serialization (here: JSON without sig, then blake3, then sha256)
-        let mut copy = r.clone();
-        copy.sig_hex = String::new();
-        let json = serde_json::to_vec(&copy)?;
-        let mut h = Hasher::new(); h.update(&json);
-        let b3 = h.finalize();
-        let digest = sha2::Sha256::digest(b3.as_bytes());
-        let sig: Signature = self.sk.sign_prehash(&digest)?;
+        // Hash the domain and the versioned wire encoding without sig (v1: JSON), then blake3, then sha256
+        self.sign_payload(&receipt_message(r)?)
+    }
+    /// Sign arbitrary bytes with the same blake3-then-sha256 prehash used for receipts.
+    pub fn sign_payload(&self, payload: &[u8]) -> anyhow::Result<String> {
+        let sk = self.sk.as_ref().ok_or_else(|| anyhow::anyhow!("the signing key is held by the remote signer"))?;
+        let digest = prehash(payload);
+        let sig: Signature = sk.sign_prehash(&digest)?;
         Ok(sig.to_vec().encode_hex::<String>())
     }
+    /// A 32-byte key for `context` derived from the secret key; None for a remote signer's key.
+    pub fn derive_key(&self, context: &str) -> Option<[u8; 32]> {
+        self.sk.as_ref().map(|sk| blake3::derive_key(context, &sk.to_bytes()))
+    }
     pub fn pubkey_hex_compressed(&self) -> String {
-        let vk = self.sk.verifying_key();
-        let ep = vk.to_encoded_point(true);
+        let ep = self.vk.to_encoded_point(true);
         hex::encode(ep.as_bytes())
     }
 }
+
+/// Check `sig_hex` over `payload` against a SEC1-encoded (compressed or not) hex pubkey.
+pub fn verify_payload(payload: &[u8], sig_hex: &str, pubkey_hex: &str) -> anyhow::Result<bool> {
+    let vk = parse_pubkey(pubkey_hex)?;
+    let sig = Signature::from_slice(&hex::decode(sig_hex)?)?;
+    Ok(vk.verify_prehash(&prehash(payload), &sig).is_ok())
+}
+
+/// Parse a SEC1-encoded (compressed or not) hex pubkey.
+pub fn parse_pubkey(pubkey_hex: &str) -> anyhow::Result<VerifyingKey> {
+    Ok(VerifyingKey::from_sec1_bytes(&hex::decode(pubkey_hex.trim_start_matches("0x"))?)?)
+}
+
+/// The message a receipt signature covers: the length-prefixed domain
+/// `tops-worker/v2/<network_id>` followed by the encoding of its `receipt_version`.
+/// Receipts without a network ID cover the bare encoding, as before.
+pub fn receipt_message(r: &WorkReceipt) -> anyhow::Result<Vec<u8>> {
+    let encoding = r.signing_bytes()?;
+    let Some(network_id) = &r.network_id else { return Ok(encoding) };
+    let domain = format!("{}{}", RECEIPT_DOMAIN, network_id);
+    let mut message = Vec::with_capacity(2 + domain.len() + encoding.len());
+    message.extend_from_slice(&u16::try_from(domain.len())?.to_le_bytes());
+    message.extend_from_slice(domain.as_bytes());
+    message.extend_from_slice(&encoding);
+    Ok(message)
+}
+
+/// Check a receipt's signature over its domain and the encoding of its `receipt_version`.
+pub fn verify_receipt(r: &WorkReceipt, pubkey_hex: &str) -> anyhow::Result<bool> {
+    verify_payload(&receipt_message(r)?, &r.sig_hex, pubkey_hex)
+}
+
+/// The message an aggregator signs to authenticate a response: the length-prefixed
+/// domain `tops-aggregator/v2/<network_id>` (`tops-aggregator/v2/` without a network
+/// ID), the length-prefixed idempotency key of the receipt the response is a verdict
+/// on (empty for responses to no receipt), then the response body exactly as sent.
+/// The key binds a verdict to its receipt, so one cannot be replayed for another.
+pub fn response_message(body: &[u8], network_id: Option<&str>, request_key: &str) -> anyhow::Result<Vec<u8>> {
+    let mut bound = Vec::with_capacity(2 + request_key.len() + body.len());
+    bound.extend_from_slice(&u16::try_from(request_key.len())?.to_le_bytes());
+    bound.extend_from_slice(request_key.as_bytes());
+    bound.extend_from_slice(body);
+    domain_message(RESPONSE_DOMAIN, &bound, network_id)
+}
+
+/// The message a fleet management endpoint signs over a config document: the
+/// length-prefixed domain `tops-fleet-config/v1/<network_id>` followed by the
+/// document exactly as sent.
+pub fn fleet_config_message(body: &[u8], network_id: Option<&str>) -> anyhow::Result<Vec<u8>> {
+    domain_message(FLEET_CONFIG_DOMAIN, body, network_id)
+}
+
+/// The message a release server signs over a release manifest: the length-prefixed
+/// domain `tops-release-manifest/v1/` followed by the manifest exactly as sent.
+pub fn release_manifest_message(body: &[u8]) -> anyhow::Result<Vec<u8>> {
+    domain_message(RELEASE_MANIFEST_DOMAIN, body, None)
+}
+
+fn domain_message(prefix: &str, body: &[u8], network_id: Option<&str>) -> anyhow::Result<Vec<u8>> {
+    let domain = format!("{}{}", prefix, network_id.unwrap_or(""));
+    let mut message = Vec::with_capacity(2 + domain.len() + body.len());
+    message.extend_from_slice(&u16::try_from(domain.len())?.to_le_bytes());
+    message.extend_from_slice(domain.as_bytes());
+    message.extend_from_slice(body);
+    Ok(message)
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use crate::types::Sizes;
+
+// Domain of the hash that turns an attempt seed into a size draw
+const DRAW_DOMAIN: &[u8] = b"tops-worker/size-draw/v1";
+/// Largest side a distribution may ask for; the bundled verifier rejects larger ones.
+pub const MAX_SIDE: usize = 8192;
+
+/// One shape of a distribution and its relative weight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeightedSizes {
+    pub m: usize,
+    pub n: usize,
+    pub k: usize,
+    pub weight: u32,
+}
+
+/// Shapes an epoch draws attempt sizes from, in the order the aggregator listed them.
+///
+/// Each attempt takes `draw(seed)`, where `seed` is the attempt's PRNG seed
+/// (`derive_salted_seed(prev_hash, nonce, salt)`), so a verifier holding the
+/// distribution reproduces the shape from the receipt alone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<WeightedSizes>", into = "Vec<WeightedSizes>")]
+pub struct SizeDistribution {
+    entries: Vec<WeightedSizes>,
+    total_weight: u64,
+}
+
+impl SizeDistribution {
+    pub fn new(entries: Vec<WeightedSizes>) -> Result<Self, String> {
+        if entries.is_empty() {
+            return Err("size distribution is empty".to_string());
+        }
+        for e in &entries {
+            if e.weight == 0 {
+                return Err(format!("size {}x{}x{} has weight 0", e.m, e.n, e.k));
+            }
+            if [e.m, e.n, e.k].iter().any(|&side| side == 0 || side > MAX_SIDE) {
+                return Err(format!("size {}x{}x{} is outside 1..={}", e.m, e.n, e.k, MAX_SIDE));
+            }
+        }
+        let total_weight = entries.iter().map(|e| u64::from(e.weight)).sum();
+        Ok(Self { entries, total_weight })
+    }
+
+    pub fn entries(&self) -> &[WeightedSizes] {
+        &self.entries
+    }
+
+    /// The shape for the attempt with PRNG seed `seed`: the first 8 bytes of
+    /// `BLAKE3("tops-worker/size-draw/v1" || seed)` as u64 LE, modulo the total weight,
+    /// select an entry by cumulative weight in list order.
+    pub fn draw(&self, seed: &[u8; 16]) -> Sizes {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(DRAW_DOMAIN);
+        hasher.update(seed);
+        let hash = hasher.finalize();
+        let bytes: [u8; 8] = hash.as_bytes()[..8].try_into().expect("8 bytes");
+        let mut point = u64::from_le_bytes(bytes) % self.total_weight;
+        let entry = self.entries.iter()
+            .find(|e| match point.checked_sub(u64::from(e.weight)) {
+                Some(rest) => { point = rest; false }
+                None => true,
+            })
+            .unwrap_or(&self.entries[self.entries.len() - 1]);
+        Sizes { m: entry.m, n: entry.n, k: entry.k, batch: 1 }
+    }
+
+    /// The largest shape, for sizing buffers and memory checks.
+    pub fn largest(&self) -> Sizes {
+        let e = self.entries.iter().max_by_key(|e| e.m * e.n + e.m * e.k + e.k * e.n).expect("non-empty");
+        Sizes { m: e.m, n: e.n, k: e.k, batch: 1 }
+    }
+}
+
+impl TryFrom<Vec<WeightedSizes>> for SizeDistribution {
+    type Error = String;
+
+    fn try_from(entries: Vec<WeightedSizes>) -> Result<Self, Self::Error> {
+        Self::new(entries)
+    }
+}
+
+impl From<SizeDistribution> for Vec<WeightedSizes> {
+    fn from(distribution: SizeDistribution) -> Self {
+        distribution.entries
+    }
+}
+
+/// How attempts get their sizes: one shape for all, or a draw per attempt.
+#[derive(Debug, Clone)]
+pub enum AttemptSizes {
+    Fixed(Sizes),
+    Drawn(SizeDistribution),
+}
+
+impl AttemptSizes {
+    pub fn for_seed(&self, seed: &[u8; 16]) -> Sizes {
+        match self {
+            AttemptSizes::Fixed(sizes) => sizes.clone(),
+            AttemptSizes::Drawn(distribution) => distribution.draw(seed),
+        }
+    }
+}
+
+impl From<Sizes> for AttemptSizes {
+    fn from(sizes: Sizes) -> Self {
+        AttemptSizes::Fixed(sizes)
+    }
+}
@@ -0,0 +1,2 @@
+// CSR inputs and the reference SpMM are part of the receipt verification core
+pub use tops_verify_core::workload::{generate_sparse_inputs, spmm_int8_relu_q, CsrMatrix};
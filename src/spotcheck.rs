@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use crate::prng::DPrng;
+use crate::types::{Requant, Sizes};
+use crate::workload::WorkloadInput;
+use tops_verify_core::workload::reference_element;
+
+const SPOTCHECK_CONTEXT: &str = "tops-worker spot-check v1";
+
+/// Outcome of recomputing a few output elements of one attempt on the CPU.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpotCheckResult {
+    pub checked: usize,
+    pub mismatches: usize,
+    /// (flat index, expected, got) of the first mismatch
+    pub first_mismatch: Option<(usize, i8, i8)>,
+}
+
+impl SpotCheckResult {
+    pub fn passed(&self) -> bool {
+        self.mismatches == 0
+    }
+}
+
+/// Recompute `elements` output elements of `y` on the CPU and compare.
+///
+/// The elements are picked from the attempt's seed, so a verifier replaying the
+/// attempt checks the same ones; `input` must be the exact input the kernel saw
+/// (after any memory-hard perturbation). Each element costs one dot product of
+/// length `k` (or one sparse row), so this is cheap next to the kernel itself.
+pub fn spot_check(seed: &[u8; 16], input: &WorkloadInput, y: &[i8], sizes: &Sizes, scale: Requant, elements: usize) -> SpotCheckResult {
+    // Batched GEMMs: the index runs over every item's output, item after item
+    let batch = match input {
+        WorkloadInput::Dense { .. } => sizes.batch.max(1),
+        WorkloadInput::Sparse { .. } => 1,
+    };
+    let per_item = sizes.m * sizes.n;
+    let total = batch * per_item;
+    if total == 0 || elements == 0 {
+        return SpotCheckResult { checked: 0, mismatches: 0, first_mismatch: None };
+    }
+    let key = blake3::derive_key(SPOTCHECK_CONTEXT, seed);
+    let mut s = [0u8; 16];
+    s.copy_from_slice(&key[..16]);
+    let mut prng = DPrng::from_seed(s);
+
+    let mut mismatches = 0;
+    let mut first_mismatch = None;
+    for _ in 0..elements {
+        let idx = (prng.next_u32() as usize) % total;
+        let (item, offset) = (idx / per_item, idx % per_item);
+        let expected = reference_element(input, sizes, scale, item, offset / sizes.n, offset % sizes.n);
+        // A short output is corrupt too; count missing elements as mismatches
+        let got = y.get(idx).copied();
+        if got != Some(expected) {
+            mismatches += 1;
+            first_mismatch.get_or_insert((idx, expected, got.unwrap_or(0)));
+        }
+    }
+    SpotCheckResult { checked: elements, mismatches, first_mismatch }
+}
+
+/// `spot_check` for an attempt whose full output never left the device: recompute
+/// `elements` of its work-root `samples` instead, `indices` being where each sample
+/// was taken from (`WorkSampling::indices`). The samples are picked from the seed
+/// as `spot_check` picks elements.
+pub fn spot_check_samples(
+    seed: &[u8; 16],
+    input: &WorkloadInput,
+    samples: &[i8],
+    indices: &[usize],
+    sizes: &Sizes,
+    scale: Requant,
+    elements: usize,
+) -> SpotCheckResult {
+    let count = samples.len().min(indices.len());
+    if count == 0 || elements == 0 {
+        return SpotCheckResult { checked: 0, mismatches: 0, first_mismatch: None };
+    }
+    let key = blake3::derive_key(SPOTCHECK_CONTEXT, seed);
+    let mut s = [0u8; 16];
+    s.copy_from_slice(&key[..16]);
+    let mut prng = DPrng::from_seed(s);
+
+    let per_item = sizes.m * sizes.n;
+    let mut mismatches = 0;
+    let mut first_mismatch = None;
+    for _ in 0..elements {
+        let pick = (prng.next_u32() as usize) % count;
+        let idx = indices[pick];
+        let (item, offset) = (idx / per_item, idx % per_item);
+        let expected = reference_element(input, sizes, scale, item, offset / sizes.n, offset % sizes.n);
+        if samples[pick] != expected {
+            mismatches += 1;
+            first_mismatch.get_or_insert((idx, expected, samples[pick]));
+        }
+    }
+    SpotCheckResult { checked: elements, mismatches, first_mismatch }
+}
@@ -0,0 +1,188 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use crate::metrics::{Metrics, MetricsCollector};
+use crate::log_warn;
+
+const HOUR_SECS: i64 = 3600;
+/// How often counters are folded into the database.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Activity of one hour (UTC), as stored and as served by `/stats`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HourlyStats {
+    /// Start of the hour, unix seconds.
+    pub hour: i64,
+    pub attempts: u64,
+    /// Receipts accepted (or durably queued) by the transport.
+    pub accepted: u64,
+    pub failed: u64,
+    /// GPU, network, signature and validation errors.
+    pub errors: u64,
+    pub avg_attempt_ms: f64,
+}
+
+/// `/stats` response: the hours in range plus their totals.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsSeries {
+    pub from: i64,
+    pub to: i64,
+    pub hours: Vec<HourlyStats>,
+    pub total: HourlyStats,
+}
+
+// Counters as of the last flush, so each flush only adds what happened since
+#[derive(Default)]
+struct Snapshot {
+    attempts: u64,
+    accepted: u64,
+    failed: u64,
+    errors: u64,
+    time_ms: u64,
+}
+
+impl Snapshot {
+    fn of(metrics: &Metrics) -> Self {
+        Self {
+            attempts: metrics.total_attempts,
+            accepted: metrics.successful_attempts,
+            failed: metrics.failed_attempts,
+            errors: metrics.device_errors + metrics.network_errors + metrics.signature_errors + metrics.validation_errors,
+            time_ms: metrics.total_time_ms,
+        }
+    }
+}
+
+/// Per-hour aggregates in an embedded SQLite database (`$STATE_DIR/stats.sqlite`).
+///
+/// The store samples the `MetricsCollector` counters once a minute and adds the
+/// difference to the current hour's row, so nothing in the attempt loop touches
+/// the database. Rows older than the retention are deleted on each flush.
+pub struct StatsStore {
+    conn: Mutex<Connection>,
+    metrics: Arc<MetricsCollector>,
+    last: Mutex<Snapshot>,
+    retention_days: u32,
+}
+
+impl StatsStore {
+    pub fn open(path: impl AsRef<Path>, metrics: Arc<MetricsCollector>, retention_days: u32) -> anyhow::Result<Self> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS hourly (
+                 hour INTEGER PRIMARY KEY,
+                 attempts INTEGER NOT NULL DEFAULT 0,
+                 accepted INTEGER NOT NULL DEFAULT 0,
+                 failed INTEGER NOT NULL DEFAULT 0,
+                 errors INTEGER NOT NULL DEFAULT 0,
+                 attempt_ms_sum INTEGER NOT NULL DEFAULT 0
+             );",
+        )?;
+        // Counters start at zero with the process, so the first flush records everything
+        Ok(Self { conn: Mutex::new(conn), metrics, last: Mutex::new(Snapshot::default()), retention_days })
+    }
+
+    /// Flush every minute from a background task.
+    pub fn spawn_flusher(self: &Arc<Self>) {
+        let store = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let store = Arc::clone(&store);
+                if let Ok(Err(e)) = tokio::task::spawn_blocking(move || store.flush()).await {
+                    log_warn!("[stats] could not write statistics: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Add the activity since the previous flush to the current hour and apply the retention.
+    pub fn flush(&self) -> anyhow::Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        let hour = now - now.rem_euclid(HOUR_SECS);
+        let current = Snapshot::of(&self.metrics.get_metrics());
+        let mut last = self.last.lock().map_err(|_| anyhow::anyhow!("stats snapshot lock poisoned"))?;
+        let conn = self.conn.lock().map_err(|_| anyhow::anyhow!("stats database lock poisoned"))?;
+        conn.execute(
+            "INSERT INTO hourly (hour, attempts, accepted, failed, errors, attempt_ms_sum)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(hour) DO UPDATE SET
+                 attempts = attempts + excluded.attempts,
+                 accepted = accepted + excluded.accepted,
+                 failed = failed + excluded.failed,
+                 errors = errors + excluded.errors,
+                 attempt_ms_sum = attempt_ms_sum + excluded.attempt_ms_sum",
+            params![
+                hour,
+                current.attempts.saturating_sub(last.attempts) as i64,
+                current.accepted.saturating_sub(last.accepted) as i64,
+                current.failed.saturating_sub(last.failed) as i64,
+                current.errors.saturating_sub(last.errors) as i64,
+                current.time_ms.saturating_sub(last.time_ms) as i64,
+            ],
+        )?;
+        conn.execute("DELETE FROM hourly WHERE hour < ?1", params![now - self.retention_days as i64 * 24 * HOUR_SECS])?;
+        *last = current;
+        Ok(())
+    }
+
+    /// Hours starting in `[from, to)` (unix seconds), oldest first. Hours without activity are omitted.
+    pub fn query(&self, from: i64, to: i64) -> anyhow::Result<StatsSeries> {
+        // Include the activity of the last minute
+        self.flush()?;
+        let conn = self.conn.lock().map_err(|_| anyhow::anyhow!("stats database lock poisoned"))?;
+        let mut stmt = conn.prepare(
+            "SELECT hour, attempts, accepted, failed, errors, attempt_ms_sum FROM hourly
+             WHERE hour >= ?1 AND hour < ?2 AND attempts + errors > 0 ORDER BY hour",
+        )?;
+        let mut total = HourlyStats { hour: from, ..Default::default() };
+        let mut total_ms = 0u64;
+        let hours = stmt.query_map(params![from - from.rem_euclid(HOUR_SECS), to], |row| {
+            let attempts = row.get::<_, i64>(1)? as u64;
+            let attempt_ms_sum = row.get::<_, i64>(5)? as u64;
+            Ok((HourlyStats {
+                hour: row.get(0)?,
+                attempts,
+                accepted: row.get::<_, i64>(2)? as u64,
+                failed: row.get::<_, i64>(3)? as u64,
+                errors: row.get::<_, i64>(4)? as u64,
+                avg_attempt_ms: if attempts > 0 { attempt_ms_sum as f64 / attempts as f64 } else { 0.0 },
+            }, attempt_ms_sum))
+        })?
+        .map(|row| {
+            let (hour, ms) = row?;
+            total.attempts += hour.attempts;
+            total.accepted += hour.accepted;
+            total.failed += hour.failed;
+            total.errors += hour.errors;
+            total_ms += ms;
+            Ok(hour)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+        if total.attempts > 0 {
+            total.avg_attempt_ms = total_ms as f64 / total.attempts as f64;
+        }
+        Ok(StatsSeries { from, to, hours, total })
+    }
+}
+
+/// A `/stats` bound: unix seconds, RFC 3339 (`2026-10-15T00:00:00Z`) or a UTC date (`2026-10-15`).
+pub fn parse_time(value: &str) -> Option<i64> {
+    if let Ok(secs) = value.parse::<i64>() {
+        return Some(secs);
+    }
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(t.timestamp());
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|t| t.and_utc().timestamp())
+}
@@ -0,0 +1,208 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use anyhow::anyhow;
+use crate::attempt::{AttemptOutput, Executor, StreamExecutor};
+use crate::memhard::MemHardParams;
+use crate::persistent::NonceRangeDriver;
+use crate::pipeline::AttemptPipeline;
+use crate::size_distribution::AttemptSizes;
+use crate::types::RequantParams;
+use crate::work_hash::{HashKind, WorkSampling};
+use crate::workload::Workload;
+
+// How often a held stream checks whether it may go on
+const HOLD_POLL: Duration = Duration::from_millis(5);
+
+pub type SharedExecutor = Arc<dyn Executor + Send + Sync>;
+
+/// The executor the streams run on, and optionally a second one (the CPU on a
+/// GPU host) that runs one more stream of its own next to them.
+#[derive(Clone)]
+pub struct StreamBackends {
+    pub primary: SharedExecutor,
+    pub assist: Option<SharedExecutor>,
+}
+
+impl From<SharedExecutor> for StreamBackends {
+    fn from(primary: SharedExecutor) -> Self {
+        Self { primary, assist: None }
+    }
+}
+
+/// A finished attempt and the stream that produced it.
+pub struct StreamAttempt {
+    pub stream: usize,
+    /// Produced by the assist executor rather than the primary one.
+    pub assist: bool,
+    pub nonce: u32,
+    pub out: AttemptOutput,
+}
+
+// What a stream drives its executor with
+enum StreamDriver {
+    Pipeline(AttemptPipeline),
+    Persistent(NonceRangeDriver),
+}
+
+impl StreamDriver {
+    fn next<E: Executor + ?Sized>(&mut self, executor: &E) -> anyhow::Result<(u32, AttemptOutput)> {
+        match self {
+            StreamDriver::Pipeline(pipeline) => pipeline.next(executor),
+            StreamDriver::Persistent(driver) => driver.next(executor),
+        }
+    }
+}
+
+/// Several independent attempt streams on one device.
+///
+/// Each stream owns a thread driving its own `AttemptPipeline` against one of the
+/// executor's queues (`Executor::run_gemm_on` / `run_spmm_on`), so a large GPU sees several kernels
+/// in flight instead of one in-order queue. Stream `s` of `n` covers nonces
+/// `first_nonce + s, first_nonce + s + n, ...`, so streams never collide. An assist
+/// executor gets the last stream, after the primary's, on a single queue.
+///
+/// With `persistent` set (nonces per launch) and fixed sizes, the primary's streams
+/// run a `NonceRangeDriver` instead; the caller checks that the workload, hash and
+/// sampling are ones the persistent kernel implements. The assist stream keeps the
+/// pipeline: the CPU has no launches to save. Likewise `device_sampling` (see
+/// `AttemptPipeline::with_device_sampling`) applies to the primary's streams only.
+pub struct AttemptStreams {
+    streams: usize,
+    stop: Arc<AtomicBool>,
+    hold: Arc<AtomicBool>,
+    results_rx: Option<Receiver<anyhow::Result<StreamAttempt>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl AttemptStreams {
+    #[allow(clippy::too_many_arguments)]
+    pub fn start(
+        backends: impl Into<StreamBackends>,
+        workload: Workload,
+        memhard: Option<MemHardParams>,
+        prev_hash: [u8;32],
+        salt: Option<[u8;32]>,
+        requant: RequantParams,
+        hash: HashKind,
+        sampling: WorkSampling,
+        first_nonce: u32,
+        sizes: impl Into<AttemptSizes>,
+        streams: usize,
+        depth: usize,
+        spot_check: usize,
+        persistent: Option<u32>,
+        device_sampling: bool,
+    ) -> Self {
+        let sizes = sizes.into();
+        let backends = backends.into();
+        let primary_streams = streams.max(1);
+        let streams = primary_streams + usize::from(backends.assist.is_some());
+        let stop = Arc::new(AtomicBool::new(false));
+        let hold = Arc::new(AtomicBool::new(false));
+        let (results_tx, results_rx) = sync_channel(streams);
+
+        let workers = (0..streams)
+            .map(|stream| {
+                let assist = stream >= primary_streams;
+                let (executor, queue) = match &backends.assist {
+                    Some(executor) if assist => (Arc::clone(executor), 0),
+                    _ => (Arc::clone(&backends.primary), stream),
+                };
+                let stop = Arc::clone(&stop);
+                let hold = Arc::clone(&hold);
+                let results_tx = results_tx.clone();
+                let sizes = sizes.clone();
+                std::thread::Builder::new()
+                    .name(format!("attempt-stream-{}", stream))
+                    .spawn(move || {
+                        let first_nonce = first_nonce.wrapping_add(stream as u32);
+                        let mut driver = match (persistent, sizes) {
+                            (Some(range), AttemptSizes::Fixed(sizes)) if !assist => StreamDriver::Persistent(NonceRangeDriver::start(
+                                prev_hash,
+                                salt,
+                                requant,
+                                first_nonce,
+                                streams as u32,
+                                sizes,
+                                range,
+                                spot_check,
+                            )),
+                            (_, sizes) => StreamDriver::Pipeline(AttemptPipeline::start_strided(
+                                workload,
+                                memhard,
+                                prev_hash,
+                                salt,
+                                first_nonce,
+                                streams as u32,
+                                sizes,
+                                depth,
+                            )
+                            .with_requant(requant)
+                            .with_hash_kind(hash)
+                            .with_work_sampling(sampling)
+                            .with_spot_check(spot_check)
+                            .with_device_sampling(device_sampling && !assist)),
+                        };
+                        let exec = StreamExecutor { executor: &*executor, stream: queue };
+                        while !stop.load(Ordering::Relaxed) {
+                            if hold.load(Ordering::Relaxed) {
+                                std::thread::sleep(HOLD_POLL);
+                                continue;
+                            }
+                            let result = driver.next(&exec)
+                                .map(|(nonce, out)| StreamAttempt { stream, assist, nonce, out })
+                                .map_err(|e| anyhow!("stream {}: {}", stream, e));
+                            if results_tx.send(result).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                    .expect("failed to spawn attempt stream thread")
+            })
+            .collect();
+
+        Self {
+            streams,
+            stop,
+            hold,
+            results_rx: Some(results_rx),
+            workers,
+        }
+    }
+
+    pub fn streams(&self) -> usize {
+        self.streams
+    }
+
+    /// Keep the streams from starting further attempts, so a priority attempt
+    /// has the device; attempts already in flight still finish.
+    pub fn hold(&self) {
+        self.hold.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.hold.store(false, Ordering::Relaxed);
+    }
+
+    /// Next finished attempt from whichever stream completes first.
+    pub fn next(&self) -> anyhow::Result<StreamAttempt> {
+        self.results_rx.as_ref()
+            .ok_or_else(|| anyhow!("attempt streams stopped"))?
+            .recv()
+            .map_err(|_| anyhow!("all attempt streams exited"))?
+    }
+}
+
+impl Drop for AttemptStreams {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Closing the results channel unblocks streams waiting to hand over an attempt
+        self.results_rx.take();
+        for h in self.workers.drain(..) {
+            let _ = h.join();
+        }
+    }
+}
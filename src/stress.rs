@@ -0,0 +1,267 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::attempt::Executor;
+use crate::spotcheck::spot_check;
+use crate::thermal;
+use crate::types::{DeviceInfo, Requant, Sizes};
+use crate::workload::{generate_workload_inputs, ProofWorkload, Workload, WorkloadInput};
+use crate::log_info;
+
+const STRESS_CONTEXT: &str = "tops-worker stress v1";
+/// Largest side `stress` picks on its own: the largest an epoch can ask for.
+pub const STRESS_MAX_SIDE: usize = 8192;
+/// Side used when the backend reports neither a largest side nor its memory (the CPU).
+pub const STRESS_DEFAULT_SIDE: usize = 2048;
+/// Shortest run that qualifies a device under ENROLL_REQUIRE_STRESS.
+pub const STRESS_QUALIFYING_SECS: f64 = 300.0;
+/// Smallest side that qualifies a device under ENROLL_REQUIRE_STRESS.
+pub const STRESS_QUALIFYING_SIDE: usize = 1024;
+// Distinct inputs cycled through: each is generated once, so the device is not kept
+// waiting on the host, and every rerun must reproduce its first output bit for bit
+const INPUT_POOL: usize = 4;
+// Temperature readings may shell out to nvidia-smi; no more often than this
+const THERMAL_POLL: Duration = Duration::from_secs(5);
+const PROGRESS_EVERY: Duration = Duration::from_secs(30);
+
+/// What `tops-worker stress` runs.
+#[derive(Debug, Clone)]
+pub struct StressParams {
+    pub duration: Duration,
+    pub sizes: Sizes,
+    /// Output elements recomputed on the CPU per GEMM.
+    pub check_elements: usize,
+    /// The run fails if the GPU gets hotter than this (°C).
+    pub max_temperature_c: Option<f64>,
+}
+
+/// Result of `tops-worker stress`, also kept in `$STATE_DIR/stress.json` for enrollment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StressReport {
+    pub started_at: String,
+    pub device: DeviceInfo,
+    pub sizes: Sizes,
+    pub duration_seconds: f64,
+    pub gemms: u64,
+    /// GEMMs with a CPU mismatch or an output that differs from an earlier run on the same input.
+    pub failed_gemms: u64,
+    /// Reruns whose output differs from the first run on the same input.
+    pub divergent_runs: u64,
+    pub elements_checked: u64,
+    pub element_mismatches: u64,
+    /// (flat index, expected, got) of the first element that differs from the CPU.
+    pub first_mismatch: Option<(usize, i8, i8)>,
+    /// `failed_gemms / gemms`.
+    pub error_rate: f64,
+    /// Sustained throughput over the GEMMs, transfers included.
+    pub tops: f64,
+    pub temperature_start_c: Option<f64>,
+    pub temperature_peak_c: Option<f64>,
+    pub temperature_end_c: Option<f64>,
+    pub max_temperature_c: Option<f64>,
+    /// The backend failed to run a GEMM; the run stops there.
+    pub error: Option<String>,
+    pub passed: bool,
+    /// Why the run passed or failed, in one line.
+    pub verdict: String,
+}
+
+impl StressReport {
+    pub fn passed(&self) -> bool {
+        self.passed
+    }
+
+    pub fn render(&self) -> String {
+        let temp = |c: Option<f64>| c.map_or("-".to_string(), |c| format!("{:.0}°C", c));
+        let mut out = format!("stress: {} ({}, driver {}), m,n,k=({},{},{}) for {:.0}s\n",
+            self.device.device_name, self.device.backend, self.device.driver_version,
+            self.sizes.m, self.sizes.n, self.sizes.k, self.duration_seconds);
+        out.push_str(&format!("  GEMMs           {} ({:.3} TOPS)\n", self.gemms, self.tops));
+        out.push_str(&format!("  failed GEMMs    {} (error rate {:.6})\n", self.failed_gemms, self.error_rate));
+        out.push_str(&format!("  divergent runs  {}\n", self.divergent_runs));
+        out.push_str(&format!("  CPU cross-check {} of {} element(s) differ\n", self.element_mismatches, self.elements_checked));
+        if let Some((idx, expected, got)) = self.first_mismatch {
+            out.push_str(&format!("                  first at index {}: expected {}, got {}\n", idx, expected, got));
+        }
+        out.push_str(&format!("  temperature     start {}, peak {}, end {}{}\n",
+            temp(self.temperature_start_c), temp(self.temperature_peak_c), temp(self.temperature_end_c),
+            self.max_temperature_c.map(|max| format!(" (limit {:.0}°C)", max)).unwrap_or_default()));
+        if let Some(e) = &self.error {
+            out.push_str(&format!("  error           {}\n", e));
+        }
+        out.push_str(&format!("{}: {}\n", if self.passed { "PASS" } else { "FAIL" }, self.verdict));
+        out
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(raw) => Ok(Some(serde_json::from_str(&raw)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Why this report does not qualify `device` for enrollment, if it does not.
+    pub fn disqualifies(&self, device: &DeviceInfo) -> Option<String> {
+        if !self.passed {
+            return Some(format!("the stress run of {} failed: {}", self.started_at, self.verdict));
+        }
+        if self.device != *device {
+            return Some(format!("the stress run of {} was on {} ({}, driver {}), not this device",
+                self.started_at, self.device.device_name, self.device.backend, self.device.driver_version));
+        }
+        // A few seconds on small matrices neither heats the device nor exercises its memory
+        if self.duration_seconds < STRESS_QUALIFYING_SECS {
+            return Some(format!("the stress run of {} lasted {:.0}s, less than the {:.0}s required",
+                self.started_at, self.duration_seconds, STRESS_QUALIFYING_SECS));
+        }
+        let side = self.sizes.m.min(self.sizes.n).min(self.sizes.k);
+        if side < STRESS_QUALIFYING_SIDE {
+            return Some(format!("the stress run of {} used side {}, less than the {} required",
+                self.started_at, side, STRESS_QUALIFYING_SIDE));
+        }
+        None
+    }
+}
+
+/// Largest square side the stress run uses on `executor` without `--size`.
+pub fn default_side(executor: &dyn Executor) -> usize {
+    executor.capabilities()
+        .max_square_side(Workload::Gemm, None, 1)
+        .map_or(STRESS_DEFAULT_SIDE, |side| side.min(STRESS_MAX_SIDE))
+        .max(1)
+}
+
+/// Run GEMMs back to back for `params.duration`, cross-checking random elements of
+/// every output on the CPU and every rerun against the first run on the same input.
+///
+/// Any differing element fails the run: a device that flips bits under load
+/// produces receipts the aggregator rejects. So does a GPU hotter than
+/// `max_temperature_c`, or a GEMM the backend fails to run.
+pub fn run_stress(executor: &dyn Executor, params: &StressParams) -> StressReport {
+    let started_at = chrono::Utc::now().to_rfc3339();
+    let sizes = &params.sizes;
+    let scale = Requant::from_salt(None);
+    // Fresh inputs on every run, so no two stress runs check the same products
+    let prev_hash = *blake3::Hasher::new_derive_key(STRESS_CONTEXT).update(started_at.as_bytes()).finalize().as_bytes();
+    let pool: Vec<WorkloadInput> = (0..INPUT_POOL as u32)
+        .map(|nonce| generate_workload_inputs(Workload::Gemm, &prev_hash, nonce, None, sizes))
+        .collect();
+    let mut first_outputs: Vec<Option<blake3::Hash>> = vec![None; INPUT_POOL];
+
+    let temperature_start_c = thermal::gpu_temperature_c();
+    let mut temperature_peak_c = temperature_start_c;
+    let mut last_thermal = Instant::now();
+    let mut last_progress = Instant::now();
+
+    let mut gemms = 0u64;
+    let mut failed_gemms = 0u64;
+    let mut divergent_runs = 0u64;
+    let mut elements_checked = 0u64;
+    let mut element_mismatches = 0u64;
+    let mut first_mismatch = None;
+    let mut error = None;
+    let mut busy = Duration::ZERO;
+
+    let start = Instant::now();
+    while start.elapsed() < params.duration {
+        let slot = (gemms % INPUT_POOL as u64) as usize;
+        let WorkloadInput::Dense { a, b } = &pool[slot] else {
+            unreachable!("the GEMM workload generates dense inputs");
+        };
+        let run_start = Instant::now();
+        let y = match executor.run_gemm(a, b, sizes, scale) {
+            Ok(y) => y,
+            Err(e) => {
+                error = Some(e.to_string());
+                break;
+            }
+        };
+        busy += run_start.elapsed();
+
+        // Different elements every time, even on a reused input
+        let seed_key = blake3::derive_key(STRESS_CONTEXT, &gemms.to_le_bytes());
+        let mut seed = [0u8; 16];
+        seed.copy_from_slice(&seed_key[..16]);
+        let check = spot_check(&seed, &pool[slot], &y, sizes, scale, params.check_elements);
+        elements_checked += check.checked as u64;
+        element_mismatches += check.mismatches as u64;
+        if first_mismatch.is_none() {
+            first_mismatch = check.first_mismatch;
+        }
+        let digest = blake3::hash(&y.iter().map(|&v| v as u8).collect::<Vec<u8>>());
+        let diverged = match first_outputs[slot] {
+            Some(first) => first != digest,
+            None => {
+                first_outputs[slot] = Some(digest);
+                false
+            }
+        };
+        divergent_runs += u64::from(diverged);
+        failed_gemms += u64::from(diverged || !check.passed());
+        gemms += 1;
+
+        if last_thermal.elapsed() >= THERMAL_POLL {
+            if let Some(celsius) = thermal::gpu_temperature_c() {
+                temperature_peak_c = Some(temperature_peak_c.map_or(celsius, |peak| peak.max(celsius)));
+            }
+            last_thermal = Instant::now();
+        }
+        if last_progress.elapsed() >= PROGRESS_EVERY {
+            log_info!("[stress] {:.0}s of {:.0}s: {} GEMM(s), {} failed{}", start.elapsed().as_secs_f64(), params.duration.as_secs_f64(),
+                gemms, failed_gemms, temperature_peak_c.map(|c| format!(", peak {:.0}°C", c)).unwrap_or_default());
+            last_progress = Instant::now();
+        }
+    }
+    let duration_seconds = start.elapsed().as_secs_f64();
+    let temperature_end_c = thermal::gpu_temperature_c();
+    if let Some(celsius) = temperature_end_c {
+        temperature_peak_c = Some(temperature_peak_c.map_or(celsius, |peak| peak.max(celsius)));
+    }
+
+    let too_hot = params.max_temperature_c.zip(temperature_peak_c).filter(|(max, peak)| peak > max);
+    let (passed, verdict) = if let Some(e) = &error {
+        (false, format!("the backend failed after {} GEMM(s): {}", gemms, e))
+    } else if gemms == 0 {
+        (false, "no GEMM completed within the duration".to_string())
+    } else if failed_gemms > 0 {
+        (false, format!("{} of {} GEMM(s) produced wrong output; lower the clocks or raise the voltage", failed_gemms, gemms))
+    } else if let Some((max, peak)) = too_hot {
+        (false, format!("the GPU peaked at {:.0}°C, above the {:.0}°C limit", peak, max))
+    } else {
+        (true, format!("{} GEMM(s) over {:.0}s without a wrong element", gemms, duration_seconds))
+    };
+
+    let busy_s = busy.as_secs_f64();
+    StressReport {
+        started_at,
+        device: executor.device_info(),
+        sizes: sizes.clone(),
+        duration_seconds,
+        gemms,
+        failed_gemms,
+        divergent_runs,
+        elements_checked,
+        element_mismatches,
+        first_mismatch,
+        error_rate: if gemms > 0 { failed_gemms as f64 / gemms as f64 } else { 0.0 },
+        tops: if busy_s > 0.0 { Workload::Gemm.tera_ops(sizes) * gemms as f64 / busy_s } else { 0.0 },
+        temperature_start_c,
+        temperature_peak_c,
+        temperature_end_c,
+        max_temperature_c: params.max_temperature_c,
+        error,
+        passed,
+        verdict,
+    }
+}
@@ -0,0 +1,798 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::clock::ClockSync;
+use crate::compression::{compress, CompressionMode, CompressionStats, ContentEncoding};
+use crate::endpoints::EndpointManager;
+use crate::error_handling::RetryConfig;
+use crate::idempotency::{idempotency_key, IDEMPOTENCY_KEY_HEADER};
+use crate::epoch::EpochDocument;
+use crate::epoch_summary::EpochSummary;
+use crate::negotiation::{ReceiptNegotiator, RECEIPT_VERSIONS_HEADER};
+use crate::dns::ResolveError;
+use crate::net::ConnectionStats;
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::rate_control;
+use crate::identity::KeyRing;
+use crate::signer::SignerUnavailable;
+use crate::response_auth::{ResponseKind, ResponseVerifier, SIGNATURE_HEADER};
+use crate::size_distribution::SizeDistribution;
+use crate::work_hash::HashKind;
+use crate::types::{RequantParams, WorkReceipt, CONTENT_TYPE_RECEIPT_COMPACT};
+use crate::log_warn;
+
+/// Wire protocol used to deliver receipts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AggregatorProtocol {
+    /// POST to the aggregator URL(s).
+    Http,
+    /// Publish to an MQTT broker (requires the `mqtt` feature).
+    Mqtt,
+    /// Unary calls to the aggregator's gRPC service (requires the `grpc` feature).
+    Grpc,
+}
+
+impl std::str::FromStr for AggregatorProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "http" => Ok(AggregatorProtocol::Http),
+            "mqtt" => Ok(AggregatorProtocol::Mqtt),
+            "grpc" => Ok(AggregatorProtocol::Grpc),
+            other => Err(format!("unknown aggregator protocol '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for AggregatorProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AggregatorProtocol::Http => write!(f, "http"),
+            AggregatorProtocol::Mqtt => write!(f, "mqtt"),
+            AggregatorProtocol::Grpc => write!(f, "grpc"),
+        }
+    }
+}
+
+/// Body format of HTTP receipt submissions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReceiptWireFormat {
+    /// The encoding of the negotiated receipt version (JSON for v1).
+    Default,
+    /// The compact binary format, falling back to the default per endpoint on a 415.
+    Compact,
+}
+
+impl std::str::FromStr for ReceiptWireFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(ReceiptWireFormat::Default),
+            "compact" => Ok(ReceiptWireFormat::Compact),
+            other => Err(format!("unknown receipt wire format '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for ReceiptWireFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReceiptWireFormat::Default => write!(f, "default"),
+            ReceiptWireFormat::Compact => write!(f, "compact"),
+        }
+    }
+}
+
+/// Local failures that prevent a receipt from being sent at all.
+#[derive(Error, Debug)]
+pub enum SubmitError {
+    #[error("Signing failed: {0}")]
+    Signing(String),
+    /// The remote signer is down or timed out; the receipt can be signed later.
+    #[error("Signing deferred: {0}")]
+    SignerUnavailable(String),
+    #[error("Encoding receipt failed: {0}")]
+    Encoding(String),
+    #[error("No aggregator endpoints configured")]
+    NoEndpoint,
+    #[error("Queueing receipt failed: {0}")]
+    Queue(String),
+    #[error("Receipt {0} was already delivered")]
+    Duplicate(String),
+}
+
+/// The summary endpoint refused a summary (a 4xx other than a timeout or throttling), so
+/// sending it again would not help.
+#[derive(Debug, Error)]
+#[error("summary refused with HTTP {0}")]
+pub struct SummaryRejected(pub u16);
+
+/// What the transport made of a receipt.
+#[derive(Debug, Clone)]
+pub enum SubmitOutcome {
+    /// The aggregator accepted the receipt.
+    Accepted { body: String },
+    /// Stored durably; the transport delivers it when it can.
+    Queued,
+    /// The aggregator asked us to slow down (429/503).
+    Throttled { status: u16, body: String, retry_after: Option<Duration> },
+    /// The aggregator refused the receipt itself (other 4xx).
+    Rejected { status: u16, body: String },
+    /// Server error or network failure.
+    Failed { kind: FailureKind, error: String },
+}
+
+/// Why a submission did not get through, as counted, journaled and retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The aggregator's host name did not resolve.
+    Dns,
+    /// No connection within `AGGREGATOR_CONNECT_TIMEOUT_SECS`.
+    ConnectTimeout,
+    /// The connection was refused or reset.
+    Connect,
+    /// The TLS handshake failed: certificate, protocol or a plain-HTTP peer.
+    Tls,
+    /// Connected, but no complete response in time.
+    Timeout,
+    /// A 4xx other than 409 and 429: the receipt itself was refused.
+    Rejected,
+    /// A 409, or a `duplicate` verdict: the aggregator already has the receipt.
+    Duplicate,
+    /// A 429 or 503.
+    Throttled,
+    /// Any other 5xx.
+    ServerError,
+    /// A verdict whose signature did not verify (`AGGREGATOR_PUBKEY`).
+    Unauthenticated,
+    /// Any other transport failure.
+    Network,
+}
+
+/// What is done with a receipt after a failure of a given kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryPolicy {
+    /// Not resent: the receipt is at fault, already delivered, or the endpoint needs an operator.
+    Never,
+    /// Resent at once, failing over once the endpoint is marked down; with a single endpoint, after a backoff.
+    Failover,
+    /// Resent to the same endpoint after an exponential backoff.
+    Backoff,
+    /// Not resent; the rate controller slows attempts down instead.
+    SlowDown,
+}
+
+impl FailureKind {
+    /// Classify a request that got no response.
+    pub fn of_error(e: &reqwest::Error) -> Self {
+        // DNS and TLS failures only show in the underlying errors: the aggregator
+        // resolver's own error type, or otherwise their messages. The top-level
+        // message is skipped because it carries the URL, and a host such as
+        // `ssl-agg.example` must not read as a TLS failure
+        let mut chain = String::new();
+        let mut resolve_failed = false;
+        let mut source = std::error::Error::source(e);
+        while let Some(err) = source {
+            resolve_failed |= err.is::<ResolveError>();
+            chain.push_str(&err.to_string().to_ascii_lowercase());
+            chain.push('\n');
+            source = err.source();
+        }
+        let dns = ["dns error", "failed to lookup address", "name or service not known", "no such host", "nodename nor servname"];
+        let tls = ["tls", "ssl", "certificate", "handshake", "invalid peer"];
+        if resolve_failed || dns.iter().any(|m| chain.contains(m)) {
+            FailureKind::Dns
+        } else if e.is_connect() && e.is_timeout() {
+            FailureKind::ConnectTimeout
+        } else if tls.iter().any(|m| chain.contains(m)) {
+            FailureKind::Tls
+        } else if e.is_connect() {
+            FailureKind::Connect
+        } else if e.is_timeout() {
+            FailureKind::Timeout
+        } else {
+            FailureKind::Network
+        }
+    }
+
+    pub fn retry_policy(self) -> RetryPolicy {
+        match self {
+            FailureKind::Dns | FailureKind::ConnectTimeout | FailureKind::Connect => RetryPolicy::Failover,
+            FailureKind::Timeout | FailureKind::ServerError | FailureKind::Network => RetryPolicy::Backoff,
+            FailureKind::Throttled => RetryPolicy::SlowDown,
+            FailureKind::Tls | FailureKind::Rejected | FailureKind::Duplicate | FailureKind::Unauthenticated => RetryPolicy::Never,
+        }
+    }
+
+    /// What the failure means for the operator, for logs and the journal.
+    pub fn explain(self) -> &'static str {
+        match self {
+            FailureKind::Dns => "the aggregator host name did not resolve; check AGGREGATOR_URL and the resolver",
+            FailureKind::ConnectTimeout => "no connection to the aggregator within the connect timeout",
+            FailureKind::Connect => "the aggregator refused or reset the connection",
+            FailureKind::Tls => "the TLS handshake failed; check the aggregator's certificate and AGGREGATOR_TLS",
+            FailureKind::Timeout => "the aggregator did not answer within the request timeout",
+            FailureKind::Rejected => "the aggregator refused the receipt; it is quarantined",
+            FailureKind::Duplicate => "the aggregator already has this receipt",
+            FailureKind::Throttled => "the aggregator asked to slow down",
+            FailureKind::ServerError => "the aggregator failed with a server error",
+            FailureKind::Unauthenticated => "the aggregator's verdict did not carry a valid signature",
+            FailureKind::Network => "the request to the aggregator failed",
+        }
+    }
+}
+
+impl std::fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureKind::Dns => write!(f, "dns"),
+            FailureKind::ConnectTimeout => write!(f, "connect_timeout"),
+            FailureKind::Connect => write!(f, "connect"),
+            FailureKind::Tls => write!(f, "tls"),
+            FailureKind::Timeout => write!(f, "timeout"),
+            FailureKind::Rejected => write!(f, "rejected"),
+            FailureKind::Duplicate => write!(f, "duplicate"),
+            FailureKind::Throttled => write!(f, "throttled"),
+            FailureKind::ServerError => write!(f, "server_error"),
+            FailureKind::Unauthenticated => write!(f, "unauthenticated"),
+            FailureKind::Network => write!(f, "network"),
+        }
+    }
+}
+
+impl std::fmt::Display for RetryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RetryPolicy::Never => write!(f, "never"),
+            RetryPolicy::Failover => write!(f, "failover"),
+            RetryPolicy::Backoff => write!(f, "backoff"),
+            RetryPolicy::SlowDown => write!(f, "slow_down"),
+        }
+    }
+}
+
+/// Epoch the aggregator wants attempts chained to.
+#[derive(Debug, Clone)]
+pub struct EpochInfo {
+    pub epoch_id: u64,
+    pub prev_hash: [u8; 32],
+    /// Memory-hard stage buffer size the epoch asks for, if any.
+    pub memhard_kib: Option<u32>,
+    /// Minimum work per receipt in TOPS-seconds, if the epoch sets one.
+    pub min_tops_seconds: Option<f64>,
+    /// Per-epoch salt that keeps outputs from being precomputed, if the epoch has one.
+    pub salt: Option<[u8; 32]>,
+    /// Requantization scale and activation the epoch sets, if any.
+    pub requant: RequantParams,
+    /// Sizes attempts draw from, if the epoch sets a distribution.
+    pub size_distribution: Option<SizeDistribution>,
+    /// Hash of the work_root; BLAKE3 unless the epoch picks another.
+    pub hash_kind: HashKind,
+}
+
+/// Why the aggregator refused a receipt, from the `reason` of its response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    /// Submitting too fast; back off.
+    Rate,
+    /// The receipt chains from an outdated prev_hash.
+    StalePrevHash,
+    BadSignature,
+    /// The work root did not verify.
+    BadWork,
+    Duplicate,
+    UnknownDevice,
+    #[serde(other)]
+    Other,
+}
+
+impl RejectReason {
+    /// Map a reason code; codes this worker does not know become `Other`.
+    pub fn from_code(code: &str) -> Self {
+        serde_json::from_value(serde_json::Value::String(code.to_string())).unwrap_or(RejectReason::Other)
+    }
+}
+
+impl std::fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RejectReason::Rate => write!(f, "rate"),
+            RejectReason::StalePrevHash => write!(f, "stale_prev_hash"),
+            RejectReason::BadSignature => write!(f, "bad_signature"),
+            RejectReason::BadWork => write!(f, "bad_work"),
+            RejectReason::Duplicate => write!(f, "duplicate"),
+            RejectReason::UnknownDevice => write!(f, "unknown_device"),
+            RejectReason::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Structured verdict an aggregator may return for a receipt, e.g.
+/// `{"accepted":false,"reason":"rate","suggested_rate":2.0}`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubmitResponse {
+    #[serde(default)]
+    pub accepted: Option<bool>,
+    #[serde(default)]
+    pub reason: Option<RejectReason>,
+    #[serde(default)]
+    pub message: Option<String>,
+    /// prev_hash (hex) the next attempts should chain from.
+    #[serde(default)]
+    pub next_prev_hash: Option<String>,
+    #[serde(default)]
+    pub next_epoch_id: Option<u64>,
+    /// Submissions per second the aggregator would like from us.
+    #[serde(default)]
+    pub suggested_rate: Option<f64>,
+    /// Salt (hex) of the epoch the next attempts belong to.
+    #[serde(default)]
+    pub next_epoch_salt: Option<String>,
+    /// Keep the full output of the next attempt as audit evidence.
+    #[serde(default)]
+    pub request_evidence: Option<bool>,
+    /// A liveness challenge to answer before its deadline.
+    #[serde(default)]
+    pub challenge: Option<crate::challenge::Challenge>,
+}
+
+impl SubmitResponse {
+    /// Parse a response body; aggregators that answer with plain text yield `None`.
+    pub fn parse(body: &str) -> Option<Self> {
+        let trimmed = body.trim_start();
+        if !trimmed.starts_with('{') {
+            return None;
+        }
+        serde_json::from_str(trimmed).ok()
+    }
+
+    pub fn next_prev_hash_bytes(&self) -> Option<[u8; 32]> {
+        hex32(self.next_prev_hash.as_deref()?)
+    }
+
+    pub fn next_epoch_salt_bytes(&self) -> Option<[u8; 32]> {
+        hex32(self.next_epoch_salt.as_deref()?)
+    }
+}
+
+pub(crate) fn hex32(s: &str) -> Option<[u8; 32]> {
+    hex::decode(s.trim_start_matches("0x")).ok()?.try_into().ok()
+}
+
+#[derive(Debug, Clone)]
+pub struct Submission {
+    /// Where the receipt went (URL, broker/topic, ...).
+    pub target: String,
+    pub latency: Duration,
+    pub outcome: SubmitOutcome,
+    /// Set when the body went out with a Content-Encoding.
+    pub compression: Option<CompressionStats>,
+    /// The aggregator's structured verdict, when it sent one.
+    pub response: Option<SubmitResponse>,
+    /// Resends after the first try under the transport's retry policy.
+    pub retries: u32,
+    /// The receipt as it went out: negotiated version, key epoch and signature.
+    /// None when it was parked before signing.
+    pub signed: Option<WorkReceipt>,
+}
+
+impl Submission {
+    /// What kind of failure the outcome is; None when the receipt was accepted or queued.
+    pub fn failure_kind(&self) -> Option<FailureKind> {
+        let duplicate = self.response.as_ref().and_then(|r| r.reason) == Some(RejectReason::Duplicate);
+        match &self.outcome {
+            SubmitOutcome::Accepted { .. } | SubmitOutcome::Queued => None,
+            SubmitOutcome::Throttled { .. } => Some(FailureKind::Throttled),
+            SubmitOutcome::Rejected { status, .. } if *status == 409 || duplicate => Some(FailureKind::Duplicate),
+            SubmitOutcome::Rejected { .. } => Some(FailureKind::Rejected),
+            SubmitOutcome::Failed { kind, .. } => Some(*kind),
+        }
+    }
+}
+
+/// A way of getting signed receipts to the aggregator.
+///
+/// Implementations pick the receipt encoding their peer understands, sign the
+/// receipt in that encoding and deliver it.
+#[async_trait]
+pub trait Submitter: Send + Sync {
+    fn describe(&self) -> String;
+
+    async fn submit(&self, receipt: WorkReceipt) -> Result<Submission, SubmitError>;
+
+    /// Current epoch, for transports that can ask the aggregator for it.
+    async fn current_epoch(&self) -> anyhow::Result<Option<EpochInfo>> {
+        Ok(None)
+    }
+
+    /// Receipts waiting in an offline buffer, if the transport has one.
+    fn pending(&self) -> usize {
+        0
+    }
+
+    /// Try to deliver one buffered receipt without a new one to carry it, for
+    /// buffers that only drain as receipts are submitted. False when nothing was
+    /// sent: the buffer is empty, drains by itself, or cannot be sent yet.
+    async fn drain_one(&self) -> bool {
+        false
+    }
+
+    /// Sign `summary` with the key of its `device_did` and deliver it. False when
+    /// the transport has nowhere to send summaries.
+    async fn submit_summary(&self, _summary: EpochSummary) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+}
+
+/// Sign `receipt` in its current `receipt_version` with the key of its
+/// `device_did` and return the wire body. With a remote signer the keyring
+/// holds no keys and the receipt is signed there.
+pub async fn sign_and_encode(keys: &KeyRing, receipt: &mut WorkReceipt) -> Result<(Vec<u8>, &'static str), SubmitError> {
+    match keys.remote_signer() {
+        Some(signer) => {
+            let pinned = keys.identity(&receipt.device_did)
+                .ok_or_else(|| SubmitError::Signing(format!("no signing key for {}", receipt.device_did)))?;
+            signer.sign_receipt(receipt, pinned).await.map_err(|e| match e.downcast_ref::<SignerUnavailable>() {
+                Some(_) => SubmitError::SignerUnavailable(e.to_string()),
+                None => SubmitError::Signing(e.to_string()),
+            })?
+        }
+        None => sign_receipt(keys, receipt)?,
+    }
+    receipt.encode().map_err(|e| SubmitError::Encoding(e.to_string()))
+}
+
+/// Sign `receipt` with a key held in `keys`.
+///
+/// The receipt is signed with the key of its `key_epoch`, so receipts built just
+/// before a rotation still go out under the old key. If that key is no longer
+/// held the receipt is re-stamped with the current epoch.
+pub fn sign_receipt(keys: &KeyRing, receipt: &mut WorkReceipt) -> Result<(), SubmitError> {
+    let identity = keys.identity(&receipt.device_did)
+        .ok_or_else(|| SubmitError::Signing(format!("no signing key for {}", receipt.device_did)))?;
+    let secp = match identity.key_for_epoch(receipt.key_epoch.unwrap_or(0)) {
+        Some(secp) => secp,
+        None => {
+            let active = identity.active_key();
+            receipt.key_epoch = Some(active.epoch);
+            active.secp
+        }
+    };
+    receipt.sig_hex = secp.sign_receipt(receipt).map_err(|e| SubmitError::Signing(e.to_string()))?;
+    Ok(())
+}
+
+/// Sign `summary` with the key of its `device_did`, here or on the remote signer.
+pub async fn sign_summary(keys: &KeyRing, summary: &mut EpochSummary) -> anyhow::Result<()> {
+    if let Some(signer) = keys.remote_signer() {
+        let pinned = keys.identity(&summary.device_did)
+            .ok_or_else(|| anyhow::anyhow!("no signing key for {}", summary.device_did))?;
+        return signer.sign_summary(summary, pinned).await;
+    }
+    let secp = keys.signer_for(&summary.device_did)
+        .ok_or_else(|| anyhow::anyhow!("no signing key for {}", summary.device_did))?;
+    summary.sign(&secp)
+}
+
+/// Direct HTTP POST to the configured aggregator endpoints with failover.
+pub struct HttpSubmitter {
+    client: reqwest::Client,
+    endpoints: Arc<EndpointManager>,
+    negotiator: ReceiptNegotiator,
+    keys: Arc<KeyRing>,
+    compression: CompressionMode,
+    compression_min_bytes: usize,
+    wire_format: ReceiptWireFormat,
+    epoch_url: Option<String>,
+    summary_url: Option<String>,
+    verifier: Option<Arc<ResponseVerifier>>,
+    connections: Option<Arc<ConnectionStats>>,
+    clock: Option<Arc<ClockSync>>,
+    retry: RetryConfig,
+    metrics: Option<Arc<PrometheusMetrics>>,
+}
+
+impl HttpSubmitter {
+    pub fn new(endpoints: Arc<EndpointManager>, negotiator: ReceiptNegotiator, keys: Arc<KeyRing>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoints,
+            negotiator,
+            keys,
+            compression: CompressionMode::Off,
+            compression_min_bytes: 0,
+            wire_format: ReceiptWireFormat::Default,
+            epoch_url: None,
+            summary_url: None,
+            verifier: None,
+            connections: None,
+            clock: None,
+            retry: RetryConfig { max_retries: 0, ..RetryConfig::default() },
+            metrics: None,
+        }
+    }
+
+    /// Use `client` (proxy, local bind) instead of a default client.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Compress bodies of at least `min_bytes` according to `mode`.
+    pub fn with_compression(mut self, mode: CompressionMode, min_bytes: usize) -> Self {
+        self.compression = mode;
+        self.compression_min_bytes = min_bytes;
+        self
+    }
+
+    /// Send receipts in `format` to endpoints that have not refused it.
+    pub fn with_wire_format(mut self, format: ReceiptWireFormat) -> Self {
+        self.wire_format = format;
+        self
+    }
+
+    /// Fetch the current epoch from `url` (an `EpochDocument`).
+    pub fn with_epoch_url(mut self, url: Option<String>) -> Self {
+        self.epoch_url = url;
+        self
+    }
+
+    /// POST end-of-epoch summaries to `url`.
+    pub fn with_summary_url(mut self, url: Option<String>) -> Self {
+        self.summary_url = url;
+        self
+    }
+
+    /// Only act on verdicts and epoch documents `verifier` authenticates.
+    pub fn with_response_verifier(mut self, verifier: Option<Arc<ResponseVerifier>>) -> Self {
+        self.verifier = verifier;
+        self
+    }
+
+    /// Count requests in `stats`, the same stats `client` reports its new connections to.
+    pub fn with_connection_stats(mut self, stats: Arc<ConnectionStats>) -> Self {
+        self.connections = Some(stats);
+        self
+    }
+
+    /// Measure the local clock against the `Date` header of every response.
+    pub fn with_clock(mut self, clock: Option<Arc<ClockSync>>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Resend failed receipts under the retry policy of their failure kind, at most
+    /// `max_retries` times, backing off as `retry` says.
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Count resends per failure kind.
+    pub fn with_metrics(mut self, metrics: Option<Arc<PrometheusMetrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    fn observe_clock(&self, headers: &reqwest::header::HeaderMap, sent_ms: i64) {
+        if let Some(clock) = &self.clock {
+            clock.observe_date(headers, sent_ms);
+        }
+    }
+
+    fn record_request(&self) {
+        if let Some(connections) = &self.connections {
+            connections.record_request();
+        }
+    }
+
+    fn record_latency(&self, status: Option<u16>, ttfb: Option<Duration>, total: Duration) {
+        if let Some(connections) = &self.connections {
+            connections.record_latency(status, ttfb, total);
+        }
+    }
+
+    // Check a response's signature header when AGGREGATOR_PUBKEY is set
+    fn authenticate(&self, kind: ResponseKind, request_key: &str, headers: &reqwest::header::HeaderMap, body: &[u8]) -> anyhow::Result<()> {
+        let Some(verifier) = &self.verifier else { return Ok(()) };
+        let signature = headers.get(SIGNATURE_HEADER).and_then(|v| v.to_str().ok());
+        Ok(verifier.verify(kind, request_key, body, signature)?)
+    }
+
+    // One delivery attempt, to the endpoint the manager picks
+    async fn send(&self, mut receipt: WorkReceipt) -> Result<Submission, SubmitError> {
+        let (endpoint_idx, url) = self.endpoints.select().ok_or(SubmitError::NoEndpoint)?;
+        receipt.receipt_version = self.negotiator.version_for(endpoint_idx, &self.client, &url).await;
+        // The signature covers the negotiated encoding
+        let (mut body, mut content_type) = sign_and_encode(&self.keys, &mut receipt).await?;
+        let mut compact = false;
+        if self.wire_format == ReceiptWireFormat::Compact && self.negotiator.compact_for(endpoint_idx) {
+            match receipt.encode_compact() {
+                Ok(encoded) => (body, content_type, compact) = (encoded, CONTENT_TYPE_RECEIPT_COMPACT, true),
+                Err(e) => log_warn!("[submit] receipt sent in its default encoding: {}", e),
+            }
+        }
+        let (body, encoding, compression) = self.encode_body(endpoint_idx, body);
+
+        let submit_start = Instant::now();
+        let key = idempotency_key(&receipt);
+        let mut request = self.client.post(&url)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .header(RECEIPT_VERSIONS_HEADER, self.negotiator.offered_versions())
+            .header(IDEMPOTENCY_KEY_HEADER, &key);
+        if encoding != ContentEncoding::Identity {
+            request = request.header(reqwest::header::CONTENT_ENCODING, encoding.to_string());
+        }
+        self.record_request();
+        let sent_ms = chrono::Utc::now().timestamp_millis();
+        let result = request.body(body).send().await;
+        let ttfb = submit_start.elapsed();
+
+        let mut response = None;
+        let mut status_code = None;
+        let outcome = match result {
+            Ok(resp) => {
+                let status = resp.status();
+                status_code = Some(status.as_u16());
+                self.observe_clock(resp.headers(), sent_ms);
+                self.negotiator.observe_response(endpoint_idx, status.as_u16(), resp.headers(), encoding, compact);
+                if status.as_u16() == 415 && compact {
+                    // The endpoint is now marked as refusing compact bodies, so this sends the default encoding
+                    log_warn!("[submit] {} refused the compact receipt format; resending in the default encoding", url);
+                    self.record_latency(Some(415), Some(ttfb), submit_start.elapsed());
+                    return self.submit(receipt).await;
+                }
+                let throttled = rate_control::is_throttle_status(status.as_u16());
+                let retry_after = resp.headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(rate_control::parse_retry_after);
+                let headers = resp.headers().clone();
+                let bytes = resp.bytes().await.unwrap_or_default();
+                let body = String::from_utf8_lossy(&bytes).into_owned();
+                let authenticated = self.authenticate(ResponseKind::Submit, &key, &headers, &bytes);
+                // 5xx, throttling and forged verdicts count against the endpoint; other 4xx are about the receipt
+                if status.is_server_error() || throttled {
+                    self.endpoints.record_failure(endpoint_idx, submit_start.elapsed(), &format!("HTTP {}", status));
+                } else if let Err(e) = &authenticated {
+                    self.endpoints.record_failure(endpoint_idx, submit_start.elapsed(), &e.to_string());
+                } else {
+                    self.endpoints.record_success(endpoint_idx, submit_start.elapsed());
+                }
+                if authenticated.is_ok() {
+                    response = SubmitResponse::parse(&body);
+                }
+
+                // A 2xx can still carry an explicit refusal
+                let refused = response.as_ref().is_some_and(|r| r.accepted == Some(false));
+                if let Err(e) = authenticated {
+                    // Nothing in an unauthenticated answer is acted on, not even its status
+                    SubmitOutcome::Failed { kind: FailureKind::Unauthenticated, error: format!("HTTP {}: {}", status, e) }
+                } else if status.is_success() && !refused {
+                    SubmitOutcome::Accepted { body }
+                } else if throttled {
+                    SubmitOutcome::Throttled { status: status.as_u16(), body, retry_after }
+                } else if status.is_server_error() && !refused {
+                    SubmitOutcome::Failed { kind: FailureKind::ServerError, error: format!("HTTP {}: {}", status, body) }
+                } else {
+                    SubmitOutcome::Rejected { status: status.as_u16(), body }
+                }
+            }
+            Err(e) => {
+                self.endpoints.record_failure(endpoint_idx, submit_start.elapsed(), &e.to_string());
+                SubmitOutcome::Failed { kind: FailureKind::of_error(&e), error: e.to_string() }
+            }
+        };
+
+        let latency = submit_start.elapsed();
+        self.record_latency(status_code, status_code.map(|_| ttfb), latency);
+        Ok(Submission { target: url, latency, outcome, compression, response, retries: 0, signed: Some(receipt) })
+    }
+
+    fn encode_body(&self, endpoint_idx: usize, body: Vec<u8>) -> (Vec<u8>, ContentEncoding, Option<CompressionStats>) {
+        let encoding = if body.len() < self.compression_min_bytes {
+            ContentEncoding::Identity
+        } else {
+            self.negotiator.encoding_for(endpoint_idx, self.compression)
+        };
+        if encoding == ContentEncoding::Identity {
+            return (body, encoding, None);
+        }
+        match compress(encoding, &body) {
+            // Not worth a Content-Encoding if it does not shrink the body
+            Ok(compressed) if compressed.len() < body.len() => {
+                let stats = CompressionStats { encoding, original_bytes: body.len(), compressed_bytes: compressed.len() };
+                (compressed, encoding, Some(stats))
+            }
+            Ok(_) => (body, ContentEncoding::Identity, None),
+            Err(e) => {
+                log_warn!("[submit] {} compression failed, sending uncompressed: {}", encoding, e);
+                (body, ContentEncoding::Identity, None)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Submitter for HttpSubmitter {
+    fn describe(&self) -> String {
+        format!("http ({} endpoint(s), {})", self.endpoints.len(), self.endpoints.mode())
+    }
+
+    async fn submit(&self, receipt: WorkReceipt) -> Result<Submission, SubmitError> {
+        let mut delay = self.retry.retry_delay;
+        let mut retries = 0;
+        loop {
+            let mut submission = self.send(receipt.clone()).await?;
+            submission.retries = retries;
+            let Some(kind) = submission.failure_kind() else { return Ok(submission) };
+            let wait = match kind.retry_policy() {
+                _ if retries >= self.retry.max_retries => return Ok(submission),
+                RetryPolicy::Never | RetryPolicy::SlowDown => return Ok(submission),
+                // Each failure counts towards marking the endpoint down, so resends move on to another one
+                RetryPolicy::Failover if self.endpoints.len() > 1 => Duration::ZERO,
+                RetryPolicy::Failover | RetryPolicy::Backoff => delay,
+            };
+            retries += 1;
+            log_warn!(attempt: &idempotency_key(&receipt), "[submit] nonce {} to {} failed ({}), retry {}/{} in {:.1}s ({})",
+                receipt.nonce, submission.target, kind, retries, self.retry.max_retries, wait.as_secs_f64(), kind.retry_policy());
+            if let Some(metrics) = &self.metrics {
+                metrics.record_submit_retry(&kind.to_string());
+            }
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+                delay = delay.mul_f64(self.retry.backoff_multiplier).min(self.retry.max_retry_delay);
+            }
+        }
+    }
+
+    async fn current_epoch(&self) -> anyhow::Result<Option<EpochInfo>> {
+        let Some(url) = &self.epoch_url else { return Ok(None) };
+        self.record_request();
+        let sent_ms = chrono::Utc::now().timestamp_millis();
+        let response = self.client.get(url).send().await?;
+        self.observe_clock(response.headers(), sent_ms);
+        let response = response.error_for_status()?;
+        let headers = response.headers().clone();
+        let bytes = response.bytes().await?;
+        self.authenticate(ResponseKind::Epoch, "", &headers, &bytes)?;
+        let document: EpochDocument = serde_json::from_slice(&bytes)?;
+        document.into_info().map(Some)
+    }
+
+    async fn submit_summary(&self, mut summary: EpochSummary) -> anyhow::Result<bool> {
+        let Some(url) = &self.summary_url else { return Ok(false) };
+        sign_summary(&self.keys, &mut summary).await?;
+        self.record_request();
+        let sent_ms = chrono::Utc::now().timestamp_millis();
+        let started = Instant::now();
+        let response = match self.client.post(url).json(&summary).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                self.record_latency(None, None, started.elapsed());
+                return Err(e.into());
+            }
+        };
+        let ttfb = started.elapsed();
+        self.observe_clock(response.headers(), sent_ms);
+        let status = response.status();
+        let body = response.bytes().await;
+        self.record_latency(Some(status.as_u16()), Some(ttfb), started.elapsed());
+        body?;
+        if status.is_client_error() && status.as_u16() != 408 && !rate_control::is_throttle_status(status.as_u16()) {
+            return Err(SummaryRejected(status.as_u16()).into());
+        }
+        if !status.is_success() {
+            anyhow::bail!("HTTP {}", status);
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,157 @@
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+/// One time-of-use window of a `TariffSchedule`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TariffWindow {
+    pub start: NaiveTime,
+    /// Exclusive; a window ending before it starts runs past midnight.
+    pub end: NaiveTime,
+    /// Autotune latency target in this window; `AUTOTUNE_TARGET_MS` when unset.
+    pub target_ms: Option<u64>,
+    /// Busy fraction of the loop in [0, 1]; 0 pauses for the whole window.
+    pub duty_cycle: Option<f64>,
+}
+
+impl TariffWindow {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Autotune target and duty cycle by local time of day (`TARIFF_SCHEDULE`).
+///
+/// `22:00-06:00 target=900 duty=1; 17:00-21:00 duty=0.25`: windows are separated by
+/// `;`, and the first one containing the current time applies. Outside every
+/// window the worker runs at `AUTOTUNE_TARGET_MS` and full duty.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TariffSchedule {
+    pub windows: Vec<TariffWindow>,
+}
+
+/// What the schedule asks for right now.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TariffProfile {
+    /// Index into `windows`, `None` outside every window.
+    pub window: Option<usize>,
+    pub target_ms: u64,
+    pub duty_cycle: f64,
+}
+
+impl TariffSchedule {
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// The profile at `time`, with `default_target_ms` where the window sets no target.
+    pub fn profile_at(&self, time: NaiveTime, default_target_ms: u64) -> TariffProfile {
+        let window = self.windows.iter().position(|w| w.contains(time));
+        let active = window.map(|i| &self.windows[i]);
+        TariffProfile {
+            window,
+            target_ms: active.and_then(|w| w.target_ms).unwrap_or(default_target_ms),
+            duty_cycle: active.and_then(|w| w.duty_cycle).unwrap_or(1.0),
+        }
+    }
+
+    /// The profile at the current local time.
+    pub fn current(&self, default_target_ms: u64) -> TariffProfile {
+        self.profile_at(chrono::Local::now().time(), default_target_ms)
+    }
+
+    /// How a profile is logged: its window or "off-schedule".
+    pub fn describe(&self, profile: &TariffProfile) -> String {
+        let window = match profile.window {
+            Some(i) => format!("{}-{}", self.windows[i].start.format("%H:%M"), self.windows[i].end.format("%H:%M")),
+            None => "off-schedule".to_string(),
+        };
+        format!("{} (target {} ms, duty {})", window, profile.target_ms, profile.duty_cycle)
+    }
+}
+
+impl std::str::FromStr for TariffWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let span = parts.next().ok_or_else(|| "empty tariff window".to_string())?;
+        let (start, end) = span.split_once('-')
+            .ok_or_else(|| format!("tariff window '{}' needs HH:MM-HH:MM", span))?;
+        let time = |t: &str| NaiveTime::parse_from_str(t, "%H:%M").map_err(|_| format!("invalid time '{}'", t));
+        let mut window = TariffWindow { start: time(start)?, end: time(end)?, target_ms: None, duty_cycle: None };
+        if window.start == window.end {
+            return Err(format!("tariff window '{}' is empty", span));
+        }
+        for setting in parts {
+            match setting.split_once('=') {
+                Some(("target", ms)) => match ms.trim_end_matches("ms").parse::<u64>() {
+                    Ok(ms) if ms > 0 => window.target_ms = Some(ms),
+                    _ => return Err(format!("invalid tariff target '{}'", ms)),
+                },
+                Some(("duty", duty)) => match duty.parse::<f64>() {
+                    Ok(duty) if (0.0..=1.0).contains(&duty) => window.duty_cycle = Some(duty),
+                    _ => return Err(format!("tariff duty '{}' must be between 0 and 1", duty)),
+                },
+                _ => return Err(format!("unknown tariff setting '{}' (expected target=<ms> or duty=<0..1>)", setting)),
+            }
+        }
+        Ok(window)
+    }
+}
+
+impl std::fmt::Display for TariffWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))?;
+        if let Some(ms) = self.target_ms {
+            write!(f, " target={}", ms)?;
+        }
+        if let Some(duty) = self.duty_cycle {
+            write!(f, " duty={}", duty)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for TariffSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let windows = s.split(';')
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<TariffWindow>, _>>()?;
+        Ok(Self { windows })
+    }
+}
+
+impl std::fmt::Display for TariffSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, window) in self.windows.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", window)?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for TariffSchedule {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<TariffSchedule> for String {
+    fn from(schedule: TariffSchedule) -> Self {
+        schedule.to_string()
+    }
+}
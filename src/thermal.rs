@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Temperature in °C of the hottest GPU the host reports, if any.
+///
+/// Reads the DRM hwmon sensors (`/sys/class/drm/card*/device/hwmon/hwmon*/temp1_input`,
+/// AMD and Intel) and falls back to `nvidia-smi` for NVIDIA cards.
+pub fn gpu_temperature_c() -> Option<f64> {
+    hwmon_temperature_c(Path::new("/sys/class/drm")).or_else(nvidia_smi_temperature_c)
+}
+
+/// Busy percentage of the GPU the attempts run on, if the host reports it.
+///
+/// Reads `/sys/class/drm/card*/device/gpu_busy_percent` (AMD) and falls back to
+/// `nvidia-smi` for NVIDIA cards. The device is the one at the backend's PCI address;
+/// without one, only a host with a single GPU is read.
+pub fn gpu_utilization_pct() -> Option<f64> {
+    let address = crate::devices::selected_pci_address();
+    drm_busy_percent(Path::new("/sys/class/drm"), address.as_deref())
+        .or_else(|| nvidia_smi_utilization_pct(address.as_deref()))
+}
+
+/// The device directories of every GPU under `drm` (`card*/device`).
+fn drm_device_dirs(drm: &Path) -> Vec<PathBuf> {
+    let Ok(cards) = std::fs::read_dir(drm) else { return Vec::new() };
+    cards.flatten()
+        .filter(|card| {
+            // cardN, not the connector entries (cardN-DP-1, ...)
+            let name = card.file_name();
+            name.to_str().and_then(|n| n.strip_prefix("card")).is_some_and(|index| index.parse::<u32>().is_ok())
+        })
+        .map(|card| card.path().join("device"))
+        .collect()
+}
+
+/// The device directory of the GPU at PCI `address` under `drm`; with no address,
+/// that of the only GPU, so a multi-GPU host is never read as a whole.
+fn selected_drm_device_dir(drm: &Path, address: Option<&str>) -> Option<PathBuf> {
+    let devices = drm_device_dirs(drm);
+    match address {
+        // `card*/device` links to the PCI device, whose directory is named by its address
+        Some(address) => devices.into_iter().find(|device| {
+            std::fs::canonicalize(device).ok()
+                .and_then(|path| path.file_name().and_then(|name| name.to_str()).map(|name| name.eq_ignore_ascii_case(address)))
+                .unwrap_or(false)
+        }),
+        None if devices.len() == 1 => devices.into_iter().next(),
+        None => None,
+    }
+}
+
+/// The hwmon directories of every GPU under `drm` (`card*/device/hwmon/hwmon*`).
+pub fn drm_hwmon_dirs(drm: &Path) -> Vec<PathBuf> {
+    drm_device_dirs(drm).iter().flat_map(|device| hwmon_dirs(device)).collect()
+}
+
+/// The hwmon directories of the GPU at PCI `address`, or of the only GPU without one.
+pub fn selected_drm_hwmon_dirs(drm: &Path, address: Option<&str>) -> Vec<PathBuf> {
+    selected_drm_device_dir(drm, address).map(|device| hwmon_dirs(&device)).unwrap_or_default()
+}
+
+fn hwmon_dirs(device: &Path) -> Vec<PathBuf> {
+    let Ok(hwmons) = std::fs::read_dir(device.join("hwmon")) else { return Vec::new() };
+    hwmons.flatten().map(|hwmon| hwmon.path()).collect()
+}
+
+fn drm_busy_percent(drm: &Path, address: Option<&str>) -> Option<f64> {
+    let device = selected_drm_device_dir(drm, address)?;
+    std::fs::read_to_string(device.join("gpu_busy_percent")).ok()?.trim().parse().ok()
+}
+
+fn hwmon_temperature_c(drm: &Path) -> Option<f64> {
+    let mut hottest: Option<f64> = None;
+    for hwmon in drm_hwmon_dirs(drm) {
+        let Ok(raw) = std::fs::read_to_string(hwmon.join("temp1_input")) else { continue };
+        // Millidegrees Celsius
+        if let Ok(milli) = raw.trim().parse::<i64>() {
+            let celsius = milli as f64 / 1000.0;
+            hottest = Some(hottest.map_or(celsius, |h| h.max(celsius)));
+        }
+    }
+    hottest
+}
+
+fn nvidia_smi_temperature_c() -> Option<f64> {
+    let output = Command::new("nvidia-smi")
+        .args(["--query-gpu=temperature.gpu", "--format=csv,noheader,nounits"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse::<f64>().ok())
+        .max_by(|a, b| a.total_cmp(b))
+}
+
+// The GPU at `address`, or the only one when there is no address
+fn nvidia_smi_utilization_pct(address: Option<&str>) -> Option<f64> {
+    let mut command = Command::new("nvidia-smi");
+    command.args(["--query-gpu=utilization.gpu", "--format=csv,noheader,nounits"]);
+    if let Some(address) = address {
+        command.arg(format!("--id={}", address));
+    }
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let readings: Vec<f64> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse::<f64>().ok())
+        .collect();
+    match readings[..] {
+        [pct] => Some(pct),
+        _ => None,
+    }
+}
@@ -1,10 +1,202 @@
 use serde::{Deserialize, Serialize};
+use crate::power::{PowerMode, PowerState};
+use crate::work_hash::{HashKind, WorkSampling};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Sizes { pub m: usize, pub n: usize, pub k: usize, pub batch: usize }
+// Attempt shapes and requantization are shared with the receipt verification core
+pub use tops_verify_core::requant::{Activation, Overflow, Requant, Rounding};
+pub use tops_verify_core::workload::Sizes;
+
+/// Requantization set for the workload by configuration or the epoch. Whatever is
+/// left unset falls back to the salt-derived scale, ReLU, truncation and saturation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequantParams {
+    /// Scale as `(num, den)`, both positive.
+    pub scale: Option<(i32, i32)>,
+    pub activation: Option<Activation>,
+    pub rounding: Option<Rounding>,
+    pub overflow: Option<Overflow>,
+}
+
+impl RequantParams {
+    pub fn is_default(&self) -> bool {
+        self.scale.is_none() && self.activation.is_none() && self.rounding.is_none() && self.overflow.is_none()
+    }
+
+    /// These parameters, with anything unset taken from `fallback`.
+    pub fn or(self, fallback: RequantParams) -> Self {
+        Self {
+            scale: self.scale.or(fallback.scale),
+            activation: self.activation.or(fallback.activation),
+            rounding: self.rounding.or(fallback.rounding),
+            overflow: self.overflow.or(fallback.overflow),
+        }
+    }
+
+    /// The requantization attempts of an epoch with this salt run with.
+    pub fn resolve(&self, salt: Option<&[u8; 32]>) -> Requant {
+        let derived = Requant::from_salt(salt);
+        let (num, den) = self.scale.unwrap_or((derived.num, derived.den));
+        Requant {
+            num,
+            den,
+            activation: self.activation.unwrap_or_default(),
+            rounding: self.rounding.unwrap_or_default(),
+            overflow: self.overflow.unwrap_or_default(),
+        }
+    }
+
+    /// What to record in a receipt: nothing when everything is derived, so verifiers
+    /// that predate explicit parameters see the receipts they always did.
+    pub fn receipt_field(&self, salt: Option<&[u8; 32]>) -> Option<Requant> {
+        (!self.is_default()).then(|| self.resolve(salt))
+    }
+}
+
+/// Parse a scale written as `num/den` (or a bare integer for `num/1`).
+pub fn parse_scale(value: &str) -> Option<(i32, i32)> {
+    let (num, den) = value.split_once('/').unwrap_or((value, "1"));
+    let scale = (num.trim().parse().ok()?, den.trim().parse().ok()?);
+    (scale.0 > 0 && scale.1 > 0).then_some(scale)
+}
+
+/// v1: the original JSON receipt. v2: canonical little-endian binary including device info.
+pub const RECEIPT_VERSION_V1: u16 = 1;
+pub const RECEIPT_VERSION_V2: u16 = 2;
+pub const SUPPORTED_RECEIPT_VERSIONS: &[u16] = &[RECEIPT_VERSION_V1, RECEIPT_VERSION_V2];
+
+pub const CONTENT_TYPE_RECEIPT_V1: &str = "application/json";
+pub const CONTENT_TYPE_RECEIPT_V2: &str = "application/vnd.tops-receipt.v2+octet-stream";
+/// Compact binary transport of a receipt of any version (`RECEIPT_WIRE_FORMAT=compact`).
+pub const CONTENT_TYPE_RECEIPT_COMPACT: &str = "application/vnd.tops-receipt.compact";
+
+const RECEIPT_V2_MAGIC: &[u8; 4] = b"TWR2";
+const RECEIPT_COMPACT_MAGIC: &[u8; 2] = b"TC";
+// Compact device_info presence byte
+const COMPACT_NO_DEVICE_INFO: u8 = 0;
+const COMPACT_DEVICE_INFO: u8 = 1;
+
+// Optional fields after the v2 signature, each preceded by its tag
+const TRAILER_EPOCH_SALT: u8 = 1; // 32 bytes
+const TRAILER_EVIDENCE_HASH: u8 = 2; // 32 bytes
+const TRAILER_KEY_EPOCH: u8 = 3; // u32 LE
+const TRAILER_ISSUED_AT: u8 = 4; // u64 LE
+const TRAILER_SEQ: u8 = 5; // u64 LE
+const TRAILER_NETWORK_ID: u8 = 6; // u16 LE length + UTF-8
+const TRAILER_REQUANT: u8 = 7; // i32 LE num, i32 LE den, u8 mode (`Requant::mode_code`)
+const TRAILER_TIMING_CONFIDENCE: u8 = 8; // u8
+const TRAILER_HASH_KIND: u8 = 9; // u8
+const TRAILER_ENERGY_ESTIMATE: u8 = 10; // f64 LE joules
+const TRAILER_WORK_SAMPLING: u8 = 11; // u8
+const TRAILER_PERF_CONTEXT: u8 = 12; // u8 layout version, then the fields of that version
+
+/// Layout of `perf_context`; a new field means a new version, which older decoders refuse.
+pub const PERF_CONTEXT_VERSION: u8 = 1;
+// perf_context flag bits
+const PERF_THERMAL_THROTTLED: u8 = 1;
+const PERF_POWER_POLICY: u8 = 2;
+
+fn default_receipt_version() -> u16 { RECEIPT_VERSION_V1 }
+
+/// Description of the hardware that produced a receipt (v2+).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub backend: String,
+    pub device_name: String,
+    pub driver_version: String,
+}
+
+/// How the attempt of a receipt was produced, for aggregator-side scoring (`RECEIPT_PERF_CONTEXT=1`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PerfContext {
+    /// Layout version (`PERF_CONTEXT_VERSION`).
+    pub version: u8,
+    /// Attempt streams sharing the device, plus the hybrid CPU stream.
+    pub streams: u16,
+    /// Attempts each stream keeps in flight.
+    pub pipeline_depth: u8,
+    /// The GPU was at or above the health temperature threshold when the attempt finished.
+    pub thermal_throttled: bool,
+    /// Power policy mode, when the worker follows a power signal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power_mode: Option<PowerMode>,
+    /// Share of the time the power policy lets the device run, in permille (1000 without one).
+    pub duty_permille: u16,
+}
+
+impl PerfContext {
+    pub fn new(streams: usize, pipeline_depth: usize, thermal_throttled: bool, power: Option<&PowerState>) -> Self {
+        Self {
+            version: PERF_CONTEXT_VERSION,
+            streams: streams.min(u16::MAX as usize) as u16,
+            pipeline_depth: pipeline_depth.min(u8::MAX as usize) as u8,
+            thermal_throttled,
+            power_mode: power.map(|p| p.mode),
+            duty_permille: power.map(|p| (p.duty_cycle * 1000.0).round().clamp(0.0, 1000.0) as u16).unwrap_or(1000),
+        }
+    }
+}
+
+/// How far a receipt's `time_ms` can be trusted, from comparing the wall-clock
+/// kernel time with the device's own timer for the same kernels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimingConfidence {
+    /// The device timer agrees with the wall clock within `TIMING_DRIFT_PCT`.
+    Verified,
+    /// The backend has no device timer (CPU, Metal) to compare against.
+    Unverified,
+    /// The two disagree by more than `TIMING_DRIFT_PCT`; the driver may report
+    /// completion early or late.
+    Drift,
+}
+
+impl TimingConfidence {
+    /// v2 receipt encoding.
+    pub fn code(&self) -> u8 {
+        match self {
+            TimingConfidence::Verified => 0,
+            TimingConfidence::Unverified => 1,
+            TimingConfidence::Drift => 2,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(TimingConfidence::Verified),
+            1 => Some(TimingConfidence::Unverified),
+            2 => Some(TimingConfidence::Drift),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for TimingConfidence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "verified" => Ok(TimingConfidence::Verified),
+            "unverified" => Ok(TimingConfidence::Unverified),
+            "drift" => Ok(TimingConfidence::Drift),
+            _ => Err(format!("unknown timing confidence: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for TimingConfidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimingConfidence::Verified => write!(f, "verified"),
+            TimingConfidence::Unverified => write!(f, "unverified"),
+            TimingConfidence::Drift => write!(f, "drift"),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkReceipt {
+    #[serde(default = "default_receipt_version")]
+    pub receipt_version: u16,
     pub device_did: String,
     pub epoch_id: u64,
     pub prev_hash_hex: String,
@@ -14,5 +206,686 @@ pub struct WorkReceipt {
     pub time_ms: u64,
     pub kernel_ver: String,
     pub driver_hint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_info: Option<DeviceInfo>,
+    /// Aggregator-provided epoch salt (hex) mixed into the seed and requantization scale.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch_salt_hex: Option<String>,
+    /// BLAKE3 (hex) of the full output kept as audit evidence for this attempt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evidence_hash_hex: Option<String>,
+    /// Rotation count of the signing key; absent until the identity's first rotation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_epoch: Option<u32>,
+    /// Unix time (ms) the receipt was built; never decreases per device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued_at_ms: Option<u64>,
+    /// Per-device sequence number, strictly increasing across restarts, for replay protection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    /// Network the receipt is for (`NETWORK_ID`); also part of the signature's domain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_id: Option<String>,
+    /// Requantization scale and activation, when set by configuration or the epoch
+    /// rather than derived from the salt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requant: Option<Requant>,
+    /// Whether the device's kernel timer backs up `time_ms`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timing_confidence: Option<TimingConfidence>,
+    /// Hash the work_root was computed with, when the epoch picked one other than BLAKE3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_kind: Option<HashKind>,
+    /// Estimated energy of the attempt in joules, when `RECEIPT_ENERGY_ESTIMATE` is set
+    /// and a power sensor is available.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub energy_estimate_j: Option<f64>,
+    /// How the work_root's samples were picked, when not the first outputs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub work_sampling: Option<WorkSampling>,
+    /// Streams, pipeline depth, thermal and power state the attempt ran under.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perf_context: Option<PerfContext>,
+    #[serde(default)]
     pub sig_hex: String, // secp256k1 signature (DER or compact)
 }
+
+// The v1 wire shape: exactly the fields (and order) aggregators saw before versioning.
+// `tops_verify_core::receipt::ReceiptV1` re-encodes it to check signatures; the tests
+// below hold the two to byte-identical signing messages.
+#[derive(Serialize)]
+struct WorkReceiptV1<'a> {
+    device_did: &'a str,
+    epoch_id: u64,
+    prev_hash_hex: &'a str,
+    nonce: u32,
+    work_root_hex: &'a str,
+    sizes: &'a Sizes,
+    time_ms: u64,
+    kernel_ver: &'a str,
+    driver_hint: &'a str,
+    // Only present for salted epochs, so unsalted receipts keep the original shape
+    #[serde(skip_serializing_if = "Option::is_none")]
+    epoch_salt_hex: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evidence_hash_hex: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_epoch: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    issued_at_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seq: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    network_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    requant: Option<&'a Requant>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timing_confidence: Option<TimingConfidence>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash_kind: Option<HashKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    energy_estimate_j: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    work_sampling: Option<WorkSampling>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    perf_context: Option<&'a PerfContext>,
+    sig_hex: &'a str,
+}
+
+impl WorkReceipt {
+    /// Serialize for the wire according to `receipt_version`. Returns (body, content type).
+    pub fn encode(&self) -> anyhow::Result<(Vec<u8>, &'static str)> {
+        match self.receipt_version {
+            RECEIPT_VERSION_V1 => Ok((self.encode_v1(&self.sig_hex)?, CONTENT_TYPE_RECEIPT_V1)),
+            RECEIPT_VERSION_V2 => Ok((self.encode_v2(&self.sig_hex)?, CONTENT_TYPE_RECEIPT_V2)),
+            v => Err(anyhow::anyhow!("unsupported receipt version {}", v)),
+        }
+    }
+
+    /// The exact bytes that get signed: the versioned encoding with an empty signature.
+    pub fn signing_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        match self.receipt_version {
+            RECEIPT_VERSION_V1 => self.encode_v1(""),
+            RECEIPT_VERSION_V2 => self.encode_v2(""),
+            v => Err(anyhow::anyhow!("unsupported receipt version {}", v)),
+        }
+    }
+
+    /// Parse a receipt received with the given content type.
+    pub fn decode(body: &[u8], content_type: &str) -> anyhow::Result<Self> {
+        if content_type.starts_with(CONTENT_TYPE_RECEIPT_V2) {
+            Self::decode_v2(body)
+        } else if content_type.starts_with(CONTENT_TYPE_RECEIPT_COMPACT) {
+            Self::decode_compact(body)
+        } else {
+            let mut r: WorkReceipt = serde_json::from_slice(body)?;
+            r.receipt_version = RECEIPT_VERSION_V1;
+            Ok(r)
+        }
+    }
+
+    /// Serialize in the compact binary format (`CONTENT_TYPE_RECEIPT_COMPACT`): fixed-width
+    /// fields with u8 length prefixes, under half the size of a JSON receipt. The receipt keeps
+    /// its `receipt_version`, and so its signature, which is still over `signing_bytes`.
+    ///
+    /// Fails when the receipt would not come back unchanged (strings over 255 bytes,
+    /// non-canonical hex); callers then send `encode` instead.
+    pub fn encode_compact(&self) -> anyhow::Result<Vec<u8>> {
+        let mut w = Vec::with_capacity(160);
+        w.extend_from_slice(RECEIPT_COMPACT_MAGIC);
+        w.push(u8::try_from(self.receipt_version)?);
+        w.extend_from_slice(&self.epoch_id.to_le_bytes());
+        w.extend_from_slice(&hex32(&self.prev_hash_hex)?);
+        w.extend_from_slice(&self.nonce.to_le_bytes());
+        w.extend_from_slice(&hex32(&self.work_root_hex)?);
+        for dim in [self.sizes.m, self.sizes.n, self.sizes.k, self.sizes.batch] {
+            w.extend_from_slice(&u32::try_from(dim)?.to_le_bytes());
+        }
+        w.extend_from_slice(&self.time_ms.to_le_bytes());
+        put_short(&mut w, &hex::decode(&self.sig_hex)?)?;
+        put_short(&mut w, self.device_did.as_bytes())?;
+        put_short(&mut w, self.kernel_ver.as_bytes())?;
+        put_short(&mut w, self.driver_hint.as_bytes())?;
+        match &self.device_info {
+            Some(info) => {
+                w.push(COMPACT_DEVICE_INFO);
+                put_short(&mut w, info.backend.as_bytes())?;
+                put_short(&mut w, info.device_name.as_bytes())?;
+                put_short(&mut w, info.driver_version.as_bytes())?;
+            }
+            None => w.push(COMPACT_NO_DEVICE_INFO),
+        }
+        self.put_trailer(&mut w)?;
+
+        // The aggregator verifies the signature over the decoded receipt, so it must match exactly
+        let decoded = Self::decode_compact(&w)?;
+        if decoded.signing_bytes()? != self.signing_bytes()? || decoded.sig_hex != self.sig_hex {
+            return Err(anyhow::anyhow!("receipt does not round-trip through the compact format"));
+        }
+        Ok(w)
+    }
+
+    pub fn decode_compact(body: &[u8]) -> anyhow::Result<Self> {
+        let mut r = Reader { buf: body, pos: 0 };
+        if r.take(2)? != RECEIPT_COMPACT_MAGIC {
+            return Err(anyhow::anyhow!("not a compact receipt"));
+        }
+        let receipt_version = u16::from(r.array::<1>()?[0]);
+        if !SUPPORTED_RECEIPT_VERSIONS.contains(&receipt_version) {
+            return Err(anyhow::anyhow!("unsupported receipt version {}", receipt_version));
+        }
+        let epoch_id = u64::from_le_bytes(r.array()?);
+        let prev_hash_hex = hex::encode(r.take(32)?);
+        let nonce = u32::from_le_bytes(r.array()?);
+        let work_root_hex = hex::encode(r.take(32)?);
+        let mut dims = [0usize; 4];
+        for d in dims.iter_mut() {
+            *d = u32::from_le_bytes(r.array()?) as usize;
+        }
+        let time_ms = u64::from_le_bytes(r.array()?);
+        let sig_hex = hex::encode(r.short_bytes()?);
+        let device_did = r.short_string()?;
+        let kernel_ver = r.short_string()?;
+        let driver_hint = r.short_string()?;
+        let device_info = match r.array::<1>()?[0] {
+            COMPACT_NO_DEVICE_INFO => None,
+            COMPACT_DEVICE_INFO => Some(DeviceInfo {
+                backend: r.short_string()?,
+                device_name: r.short_string()?,
+                driver_version: r.short_string()?,
+            }),
+            other => return Err(anyhow::anyhow!("invalid device_info marker {} in compact receipt", other)),
+        };
+        let mut receipt = WorkReceipt {
+            receipt_version,
+            device_did,
+            epoch_id,
+            prev_hash_hex,
+            nonce,
+            work_root_hex,
+            sizes: Sizes { m: dims[0], n: dims[1], k: dims[2], batch: dims[3] },
+            time_ms,
+            kernel_ver,
+            driver_hint,
+            device_info,
+            epoch_salt_hex: None,
+            evidence_hash_hex: None,
+            key_epoch: None,
+            issued_at_ms: None,
+            seq: None,
+            network_id: None,
+            requant: None,
+            timing_confidence: None,
+            hash_kind: None,
+            energy_estimate_j: None,
+            work_sampling: None,
+            perf_context: None,
+            sig_hex,
+        };
+        receipt.read_trailer(&mut r)?;
+        Ok(receipt)
+    }
+
+    fn encode_v1(&self, sig_hex: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&WorkReceiptV1 {
+            device_did: &self.device_did,
+            epoch_id: self.epoch_id,
+            prev_hash_hex: &self.prev_hash_hex,
+            nonce: self.nonce,
+            work_root_hex: &self.work_root_hex,
+            sizes: &self.sizes,
+            time_ms: self.time_ms,
+            kernel_ver: &self.kernel_ver,
+            driver_hint: &self.driver_hint,
+            epoch_salt_hex: self.epoch_salt_hex.as_deref(),
+            evidence_hash_hex: self.evidence_hash_hex.as_deref(),
+            key_epoch: self.key_epoch,
+            issued_at_ms: self.issued_at_ms,
+            seq: self.seq,
+            network_id: self.network_id.as_deref(),
+            requant: self.requant.as_ref(),
+            timing_confidence: self.timing_confidence,
+            hash_kind: self.hash_kind,
+            energy_estimate_j: self.energy_estimate_j,
+            work_sampling: self.work_sampling,
+            perf_context: self.perf_context.as_ref(),
+            sig_hex,
+        })?)
+    }
+
+    fn encode_v2(&self, sig_hex: &str) -> anyhow::Result<Vec<u8>> {
+        let mut w = Vec::with_capacity(256);
+        w.extend_from_slice(RECEIPT_V2_MAGIC);
+        w.extend_from_slice(&RECEIPT_VERSION_V2.to_le_bytes());
+        put_str(&mut w, &self.device_did)?;
+        w.extend_from_slice(&self.epoch_id.to_le_bytes());
+        w.extend_from_slice(&hex32(&self.prev_hash_hex)?);
+        w.extend_from_slice(&self.nonce.to_le_bytes());
+        w.extend_from_slice(&hex32(&self.work_root_hex)?);
+        for dim in [self.sizes.m, self.sizes.n, self.sizes.k, self.sizes.batch] {
+            w.extend_from_slice(&u32::try_from(dim)?.to_le_bytes());
+        }
+        w.extend_from_slice(&self.time_ms.to_le_bytes());
+        put_str(&mut w, &self.kernel_ver)?;
+        put_str(&mut w, &self.driver_hint)?;
+        let info = self.device_info.clone().unwrap_or_default();
+        put_str(&mut w, &info.backend)?;
+        put_str(&mut w, &info.device_name)?;
+        put_str(&mut w, &info.driver_version)?;
+        put_bytes(&mut w, &hex::decode(sig_hex)?)?;
+        // Optional trailer: decoders predating it only ever see receipts without one
+        self.put_trailer(&mut w)?;
+        Ok(w)
+    }
+
+    fn decode_v2(body: &[u8]) -> anyhow::Result<Self> {
+        let mut r = Reader { buf: body, pos: 0 };
+        if r.take(4)? != RECEIPT_V2_MAGIC {
+            return Err(anyhow::anyhow!("not a v2 receipt"));
+        }
+        let version = u16::from_le_bytes(r.array()?);
+        if version != RECEIPT_VERSION_V2 {
+            return Err(anyhow::anyhow!("unexpected receipt version {}", version));
+        }
+        let device_did = r.string()?;
+        let epoch_id = u64::from_le_bytes(r.array()?);
+        let prev_hash_hex = hex::encode(r.take(32)?);
+        let nonce = u32::from_le_bytes(r.array()?);
+        let work_root_hex = hex::encode(r.take(32)?);
+        let mut dims = [0usize; 4];
+        for d in dims.iter_mut() {
+            *d = u32::from_le_bytes(r.array()?) as usize;
+        }
+        let time_ms = u64::from_le_bytes(r.array()?);
+        let kernel_ver = r.string()?;
+        let driver_hint = r.string()?;
+        let device_info = DeviceInfo {
+            backend: r.string()?,
+            device_name: r.string()?,
+            driver_version: r.string()?,
+        };
+        let sig_hex = hex::encode(r.bytes()?);
+        let mut receipt = WorkReceipt {
+            receipt_version: RECEIPT_VERSION_V2,
+            device_did,
+            epoch_id,
+            prev_hash_hex,
+            nonce,
+            work_root_hex,
+            sizes: Sizes { m: dims[0], n: dims[1], k: dims[2], batch: dims[3] },
+            time_ms,
+            kernel_ver,
+            driver_hint,
+            device_info: Some(device_info),
+            epoch_salt_hex: None,
+            evidence_hash_hex: None,
+            key_epoch: None,
+            issued_at_ms: None,
+            seq: None,
+            network_id: None,
+            requant: None,
+            timing_confidence: None,
+            hash_kind: None,
+            energy_estimate_j: None,
+            work_sampling: None,
+            perf_context: None,
+            sig_hex,
+        };
+        receipt.read_trailer(&mut r)?;
+        Ok(receipt)
+    }
+
+    // Optional fields, each preceded by its tag (shared by v2 and the compact format)
+    fn put_trailer(&self, w: &mut Vec<u8>) -> anyhow::Result<()> {
+        for (tag, value) in [(TRAILER_EPOCH_SALT, &self.epoch_salt_hex), (TRAILER_EVIDENCE_HASH, &self.evidence_hash_hex)] {
+            if let Some(value) = value {
+                w.push(tag);
+                w.extend_from_slice(&hex32(value)?);
+            }
+        }
+        if let Some(key_epoch) = self.key_epoch {
+            w.push(TRAILER_KEY_EPOCH);
+            w.extend_from_slice(&key_epoch.to_le_bytes());
+        }
+        for (tag, value) in [(TRAILER_ISSUED_AT, self.issued_at_ms), (TRAILER_SEQ, self.seq)] {
+            if let Some(value) = value {
+                w.push(tag);
+                w.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        if let Some(network_id) = &self.network_id {
+            w.push(TRAILER_NETWORK_ID);
+            put_str(w, network_id)?;
+        }
+        if let Some(requant) = &self.requant {
+            w.push(TRAILER_REQUANT);
+            w.extend_from_slice(&requant.num.to_le_bytes());
+            w.extend_from_slice(&requant.den.to_le_bytes());
+            w.push(requant.mode_code());
+        }
+        if let Some(confidence) = self.timing_confidence {
+            w.push(TRAILER_TIMING_CONFIDENCE);
+            w.push(confidence.code());
+        }
+        if let Some(hash_kind) = self.hash_kind {
+            w.push(TRAILER_HASH_KIND);
+            w.push(hash_kind.code());
+        }
+        if let Some(joules) = self.energy_estimate_j {
+            w.push(TRAILER_ENERGY_ESTIMATE);
+            w.extend_from_slice(&joules.to_le_bytes());
+        }
+        if let Some(sampling) = self.work_sampling {
+            w.push(TRAILER_WORK_SAMPLING);
+            w.push(sampling.code());
+        }
+        if let Some(context) = &self.perf_context {
+            w.push(TRAILER_PERF_CONTEXT);
+            w.push(context.version);
+            w.extend_from_slice(&context.streams.to_le_bytes());
+            w.push(context.pipeline_depth);
+            let mut flags = 0;
+            if context.thermal_throttled {
+                flags |= PERF_THERMAL_THROTTLED;
+            }
+            if context.power_mode.is_some() {
+                flags |= PERF_POWER_POLICY;
+            }
+            w.push(flags);
+            w.push(context.power_mode.map(|mode| mode.code()).unwrap_or(0));
+            w.extend_from_slice(&context.duty_permille.to_le_bytes());
+        }
+        Ok(())
+    }
+
+    // Counterpart of `put_trailer`: reads tagged fields up to the end of the body
+    fn read_trailer(&mut self, r: &mut Reader) -> anyhow::Result<()> {
+        while r.pos != r.buf.len() {
+            let tag = r.array::<1>()?[0];
+            match tag {
+                TRAILER_EPOCH_SALT => self.epoch_salt_hex = Some(hex::encode(r.take(32)?)),
+                TRAILER_EVIDENCE_HASH => self.evidence_hash_hex = Some(hex::encode(r.take(32)?)),
+                TRAILER_KEY_EPOCH => self.key_epoch = Some(u32::from_le_bytes(r.array()?)),
+                TRAILER_ISSUED_AT => self.issued_at_ms = Some(u64::from_le_bytes(r.array()?)),
+                TRAILER_SEQ => self.seq = Some(u64::from_le_bytes(r.array()?)),
+                TRAILER_NETWORK_ID => self.network_id = Some(r.string()?),
+                TRAILER_REQUANT => {
+                    let num = i32::from_le_bytes(r.array()?);
+                    let den = i32::from_le_bytes(r.array()?);
+                    let code = r.array::<1>()?[0];
+                    self.requant = Some(Requant::from_mode_code(num, den, code)
+                        .ok_or_else(|| anyhow::anyhow!("unknown requantization mode {} in receipt trailer", code))?);
+                }
+                TRAILER_TIMING_CONFIDENCE => {
+                    let code = r.array::<1>()?[0];
+                    self.timing_confidence = Some(TimingConfidence::from_code(code)
+                        .ok_or_else(|| anyhow::anyhow!("unknown timing confidence {} in receipt trailer", code))?);
+                }
+                TRAILER_HASH_KIND => {
+                    let code = r.array::<1>()?[0];
+                    self.hash_kind = Some(HashKind::from_code(code)
+                        .ok_or_else(|| anyhow::anyhow!("unknown hash kind {} in receipt trailer", code))?);
+                }
+                TRAILER_ENERGY_ESTIMATE => self.energy_estimate_j = Some(f64::from_le_bytes(r.array()?)),
+                TRAILER_WORK_SAMPLING => {
+                    let code = r.array::<1>()?[0];
+                    self.work_sampling = Some(WorkSampling::from_code(code)
+                        .ok_or_else(|| anyhow::anyhow!("unknown work_root sampling {} in receipt trailer", code))?);
+                }
+                TRAILER_PERF_CONTEXT => {
+                    let version = r.array::<1>()?[0];
+                    if version != PERF_CONTEXT_VERSION {
+                        return Err(anyhow::anyhow!("unsupported perf_context version {} in receipt trailer", version));
+                    }
+                    let streams = u16::from_le_bytes(r.array()?);
+                    let [pipeline_depth, flags, mode] = r.array()?;
+                    let power_mode = if flags & PERF_POWER_POLICY != 0 {
+                        Some(PowerMode::from_code(mode)
+                            .ok_or_else(|| anyhow::anyhow!("unknown power mode {} in receipt trailer", mode))?)
+                    } else {
+                        None
+                    };
+                    self.perf_context = Some(PerfContext {
+                        version,
+                        streams,
+                        pipeline_depth,
+                        thermal_throttled: flags & PERF_THERMAL_THROTTLED != 0,
+                        power_mode,
+                        duty_permille: u16::from_le_bytes(r.array()?),
+                    });
+                }
+                _ => return Err(anyhow::anyhow!("unknown trailer field {} in receipt", tag)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Highest receipt version supported by both sides, capped at `max_local`.
+pub fn select_receipt_version(remote: &[u16], max_local: u16) -> u16 {
+    SUPPORTED_RECEIPT_VERSIONS
+        .iter()
+        .copied()
+        .filter(|v| *v <= max_local && remote.contains(v))
+        .max()
+        .unwrap_or(RECEIPT_VERSION_V1)
+}
+
+fn hex32(s: &str) -> anyhow::Result<[u8; 32]> {
+    hex::decode(s)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("expected 32-byte hex value"))
+}
+
+fn put_bytes(w: &mut Vec<u8>, b: &[u8]) -> anyhow::Result<()> {
+    w.extend_from_slice(&u16::try_from(b.len())?.to_le_bytes());
+    w.extend_from_slice(b);
+    Ok(())
+}
+
+fn put_str(w: &mut Vec<u8>, s: &str) -> anyhow::Result<()> {
+    put_bytes(w, s.as_bytes())
+}
+
+// u8 length prefix, for the compact format
+fn put_short(w: &mut Vec<u8>, b: &[u8]) -> anyhow::Result<()> {
+    w.push(u8::try_from(b.len())?);
+    w.extend_from_slice(b);
+    Ok(())
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.buf.len())
+            .ok_or_else(|| anyhow::anyhow!("truncated receipt"))?;
+        let out = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("take returned N bytes"))
+    }
+
+    fn bytes(&mut self) -> anyhow::Result<&'a [u8]> {
+        let len = u16::from_le_bytes(self.array()?) as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        Ok(String::from_utf8(self.bytes()?.to_vec())?)
+    }
+
+    fn short_bytes(&mut self) -> anyhow::Result<&'a [u8]> {
+        let len = self.array::<1>()?[0] as usize;
+        self.take(len)
+    }
+
+    fn short_string(&mut self) -> anyhow::Result<String> {
+        Ok(String::from_utf8(self.short_bytes()?.to_vec())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt(version: u16) -> WorkReceipt {
+        WorkReceipt {
+            receipt_version: version,
+            device_did: "did:peaq:0x1234567890abcdef1234567890abcdef12345678".to_string(),
+            epoch_id: 1_776_000_123,
+            prev_hash_hex: "ab".repeat(32),
+            nonce: 0xdead_beef,
+            work_root_hex: "0f".repeat(32),
+            sizes: Sizes { m: 4096, n: 4096, k: 4096, batch: 8 },
+            time_ms: 1234,
+            kernel_ver: "cuda-int8-tensorcore-v3".to_string(),
+            driver_hint: "nvidia-550.54".to_string(),
+            device_info: None,
+            epoch_salt_hex: None,
+            evidence_hash_hex: None,
+            key_epoch: None,
+            issued_at_ms: None,
+            seq: None,
+            network_id: None,
+            requant: None,
+            timing_confidence: None,
+            hash_kind: None,
+            energy_estimate_j: None,
+            work_sampling: None,
+            perf_context: None,
+            sig_hex: "5a".repeat(64),
+        }
+    }
+
+    fn full_receipt(version: u16) -> WorkReceipt {
+        WorkReceipt {
+            device_info: Some(DeviceInfo {
+                backend: "cuda".to_string(),
+                device_name: "NVIDIA GeForce RTX 4090".to_string(),
+                driver_version: "550.54.14".to_string(),
+            }),
+            epoch_salt_hex: Some("11".repeat(32)),
+            evidence_hash_hex: Some("22".repeat(32)),
+            key_epoch: Some(3),
+            issued_at_ms: Some(1_776_000_123_456),
+            seq: Some(42),
+            network_id: Some("peaq-testnet".to_string()),
+            requant: Some(Requant::new(3, 1024, Activation::Relu)),
+            timing_confidence: Some(TimingConfidence::Verified),
+            hash_kind: Some(HashKind::Sha3_256),
+            energy_estimate_j: Some(12.5),
+            work_sampling: Some(WorkSampling::Seeded),
+            perf_context: Some(PerfContext {
+                version: PERF_CONTEXT_VERSION,
+                streams: 2,
+                pipeline_depth: 3,
+                thermal_throttled: true,
+                power_mode: Some(PowerMode::Throttled),
+                duty_permille: 750,
+            }),
+            ..receipt(version)
+        }
+    }
+
+    fn assert_round_trips(original: &WorkReceipt) {
+        let body = original.encode_compact().unwrap();
+        let decoded = WorkReceipt::decode(&body, CONTENT_TYPE_RECEIPT_COMPACT).unwrap();
+        assert_eq!(decoded.receipt_version, original.receipt_version);
+        assert_eq!(decoded.signing_bytes().unwrap(), original.signing_bytes().unwrap());
+        assert_eq!(decoded.sig_hex, original.sig_hex);
+        assert_eq!(decoded.device_info, original.device_info);
+        assert_eq!(decoded.perf_context, original.perf_context);
+    }
+
+    #[test]
+    fn compact_round_trips_every_version() {
+        for version in SUPPORTED_RECEIPT_VERSIONS.iter().copied() {
+            assert_round_trips(&receipt(version));
+            assert_round_trips(&full_receipt(version));
+        }
+    }
+
+    #[test]
+    fn v1_signing_message_matches_verify_core() {
+        let key = crate::signing::Secp::from_hex(&"01".repeat(32)).unwrap();
+        for mut r in [receipt(RECEIPT_VERSION_V1), full_receipt(RECEIPT_VERSION_V1)] {
+            r.sig_hex = key.sign_receipt(&r).unwrap();
+            let (json, _) = r.encode().unwrap();
+            let core = tops_verify_core::receipt::ReceiptV1::from_json(std::str::from_utf8(&json).unwrap()).unwrap();
+            assert_eq!(core.signing_message().unwrap(), crate::signing::receipt_message(&r).unwrap());
+            assert!(core.verify_signature(&key.pubkey_hex_compressed()).unwrap());
+        }
+    }
+
+    #[test]
+    fn committed_work_root_matches_verify_core() {
+        use crate::attempt::{compute_work_root, run_attempt};
+        use crate::workload::ProofWorkload;
+        let prev_hash = [0xab; 32];
+        let sizes = Sizes { m: 24, n: 40, k: 32, batch: 1 };
+        let out = run_attempt(&crate::cpu::CpuExec::new().unwrap(), &prev_hash, 7, &sizes).unwrap();
+        let mut r = WorkReceipt {
+            prev_hash_hex: hex::encode(prev_hash),
+            nonce: 7,
+            work_root_hex: hex::encode(out.work_root),
+            sizes: sizes.clone(),
+            kernel_ver: crate::workload::Workload::Gemm.kernel_ver(),
+            work_sampling: Some(WorkSampling::Committed),
+            ..receipt(RECEIPT_VERSION_V1)
+        };
+        r.sig_hex = crate::signing::Secp::from_hex(&"01".repeat(32)).unwrap().sign_receipt(&r).unwrap();
+        let (json, _) = r.encode().unwrap();
+        let core = tops_verify_core::receipt::ReceiptV1::from_json(std::str::from_utf8(&json).unwrap()).unwrap();
+        assert_eq!(tops_verify_core::verify::recompute_work_root(&core).unwrap().unwrap(), out.work_root);
+
+        // Any element moves the root, sampled or not
+        let mut y = out.y1.clone();
+        y[0] = y[0].wrapping_add(1);
+        assert_ne!(compute_work_root(&y, HashKind::Blake3, WorkSampling::Committed, &prev_hash, 7).0, out.work_root);
+    }
+
+    #[test]
+    fn compact_is_a_fraction_of_json() {
+        let plain = receipt(RECEIPT_VERSION_V1);
+        let (json, _) = plain.encode().unwrap();
+        let compact = plain.encode_compact().unwrap();
+        assert!(compact.len() * 2 < json.len(), "compact {} bytes, JSON {} bytes", compact.len(), json.len());
+
+        let full = full_receipt(RECEIPT_VERSION_V2);
+        let (v2, _) = full.encode().unwrap();
+        assert!(full.encode_compact().unwrap().len() < v2.len());
+    }
+
+    #[test]
+    fn compact_refuses_receipts_it_cannot_carry_unchanged() {
+        let mut upper = receipt(RECEIPT_VERSION_V1);
+        upper.prev_hash_hex = upper.prev_hash_hex.to_uppercase();
+        assert!(upper.encode_compact().is_err());
+
+        let mut long = receipt(RECEIPT_VERSION_V1);
+        long.kernel_ver = "k".repeat(256);
+        assert!(long.encode_compact().is_err());
+    }
+
+    #[test]
+    fn compact_rejects_truncated_and_foreign_bodies() {
+        // Without a trailer every byte is required
+        let with_info = WorkReceipt { device_info: full_receipt(RECEIPT_VERSION_V2).device_info, ..receipt(RECEIPT_VERSION_V2) };
+        let body = with_info.encode_compact().unwrap();
+        for len in 0..body.len() {
+            assert!(WorkReceipt::decode_compact(&body[..len]).is_err(), "accepted {} of {} bytes", len, body.len());
+        }
+        let (v2, _) = with_info.encode().unwrap();
+        assert!(WorkReceipt::decode_compact(&v2).is_err());
+        let mut unknown_version = body.clone();
+        unknown_version[2] = 9;
+        assert!(WorkReceipt::decode_compact(&unknown_version).is_err());
+    }
+}
@@ -0,0 +1,388 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::config::Config;
+use crate::integrity::write_atomic;
+use crate::prometheus_metrics::PrometheusMetrics;
+use crate::signing::{parse_pubkey, release_manifest_message, verify_payload};
+use crate::{log_error, log_info, log_warn};
+
+/// HTTP header carrying the release server's signature of a manifest.
+pub const SIGNATURE_HEADER: &str = "x-release-signature";
+
+/// Version of the running build.
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// A binary takes far longer to fetch than a manifest
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(600);
+// Largest binary downloaded, whatever the manifest or the server claims
+const MAX_ARTIFACT_BYTES: u64 = 512 * 1024 * 1024;
+
+/// A release manifest: the latest version and its binary for each target.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseManifest {
+    pub version: String,
+    #[serde(default)]
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub notes_url: Option<String>,
+    /// Binaries by target, as named by `UPDATE_TARGET` (e.g. `x86_64-linux`).
+    #[serde(default)]
+    pub artifacts: BTreeMap<String, ReleaseArtifact>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseArtifact {
+    /// Absolute, or relative to the manifest's URL.
+    pub url: String,
+    /// SHA-256 of the binary, hex.
+    pub sha256: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+/// A `major.minor.patch` version, optionally `v`-prefixed, with an optional
+/// `-pre` suffix that sorts before the release, pre-releases ordered as SemVer
+/// orders them (`rc.9 < rc.10`). `+build` metadata is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Option<String>,
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let core = s.strip_prefix('v').unwrap_or(s);
+        let core = core.split_once('+').map_or(core, |(core, _)| core);
+        let (core, pre) = match core.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (core, None),
+        };
+        if let Some(pre) = pre {
+            // Versions end up in file names, so the suffix is held to semver's characters
+            if pre.is_empty() || !pre.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-') {
+                anyhow::bail!("invalid pre-release in version {:?}", s);
+            }
+        }
+        let parts: Vec<u64> = core.split('.')
+            .map(|part| part.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| anyhow::anyhow!("invalid version {:?}", s))?;
+        let [major, minor, patch] = parts[..] else {
+            anyhow::bail!("invalid version {:?}: expected major.minor.patch", s);
+        };
+        Ok(Self { major, minor, patch, pre: pre.map(str::to_string) })
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch).cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => compare_pre(a, b),
+            })
+    }
+}
+
+// SemVer 11.4: dot-separated identifiers in turn, numeric ones by value and below
+// alphanumeric ones, and a prefix of another list below it
+fn compare_pre(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.split('.'), b.split('.'));
+    loop {
+        let (x, y) = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => (x, y),
+        };
+        let numeric = |s: &str| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+        let order = match (numeric(x), numeric(y)) {
+            // Without leading zeros the longer number is the larger
+            (true, true) => x.trim_start_matches('0').len().cmp(&y.trim_start_matches('0').len())
+                .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0'))),
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => x.cmp(y),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{}", pre)?;
+        }
+        Ok(())
+    }
+}
+
+/// `$STATE_DIR/update/staged.json`: the binary waiting for the supervisor, and the
+/// signed manifest it came from so the supervisor can check it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StagedRelease {
+    pub version: String,
+    pub path: String,
+    pub sha256: String,
+    pub staged_at: String,
+    /// The manifest exactly as received, and its `x-release-signature`.
+    pub manifest: String,
+    pub signature: String,
+}
+
+/// Update checks reported in /status.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateStatus {
+    pub manifest_url: String,
+    pub current_version: String,
+    pub target: String,
+    /// Version the latest verified manifest names; `None` before the first.
+    pub latest_version: Option<String>,
+    pub update_available: bool,
+    /// Binaries are downloaded and staged (`UPDATE_DOWNLOAD=1`).
+    pub download: bool,
+    /// A newer release staged for the supervisor to swap in.
+    pub staged_version: Option<String>,
+    pub staged_path: Option<String>,
+    pub last_check: Option<String>,
+    /// Why the latest check failed (unreachable, unsigned, no binary for the target).
+    pub last_error: Option<String>,
+}
+
+/// Checks a signed release manifest at `UPDATE_MANIFEST_URL` every `UPDATE_CHECK_SECS`.
+///
+/// A manifest counts only if the `UPDATE_PUBKEY` signature in its `x-release-signature`
+/// header verifies over `signing::release_manifest_message`. A newer version is
+/// reported in /status and `tops_worker_update_available`; with `UPDATE_DOWNLOAD=1`
+/// the binary for `UPDATE_TARGET` is also downloaded, checked against the manifest's
+/// SHA-256 and staged under `$STATE_DIR/update`. The worker never runs it: swapping
+/// it in is left to the supervisor.
+pub struct UpdateChecker {
+    url: String,
+    pubkey_hex: String,
+    target: String,
+    download: bool,
+    dir: PathBuf,
+    interval: Duration,
+    client: reqwest::Client,
+    metrics: Option<Arc<PrometheusMetrics>>,
+    status: Mutex<UpdateStatus>,
+}
+
+impl UpdateChecker {
+    /// `None` without `UPDATE_MANIFEST_URL`. A release staged before the restart is
+    /// reported again while it is still newer than the running version.
+    pub fn from_config(config: &Config) -> anyhow::Result<Option<Self>> {
+        let (Some(url), Some(pubkey_hex)) = (config.update_manifest_url.clone(), config.update_pubkey.clone()) else {
+            return Ok(None);
+        };
+        parse_pubkey(&pubkey_hex)?;
+        let checker = Self {
+            status: Mutex::new(UpdateStatus {
+                manifest_url: url.clone(),
+                current_version: CURRENT_VERSION.to_string(),
+                target: config.update_target.clone(),
+                download: config.update_download,
+                ..UpdateStatus::default()
+            }),
+            url,
+            pubkey_hex,
+            target: config.update_target.clone(),
+            download: config.update_download,
+            dir: config.get_update_dir(),
+            interval: config.get_update_check_interval(),
+            client: reqwest::Client::builder().timeout(Duration::from_secs(10)).build()?,
+            metrics: None,
+        };
+        match checker.load_staged() {
+            Ok(Some(staged)) if is_newer(&staged.version) => {
+                if let Ok(mut status) = checker.status.lock() {
+                    status.staged_version = Some(staged.version);
+                    status.staged_path = Some(staged.path);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => log_warn!("[update] ignoring {}: {}", checker.staged_path().display(), e),
+        }
+        Ok(Some(checker))
+    }
+
+    pub fn with_metrics(mut self, metrics: Option<Arc<PrometheusMetrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn status(&self) -> UpdateStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// Check in the background, starting now.
+    pub fn spawn(self: &Arc<Self>) {
+        let checker = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(checker.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let result = checker.check().await;
+                if let Ok(mut status) = checker.status.lock() {
+                    status.last_check = Some(chrono::Utc::now().to_rfc3339());
+                    status.last_error = result.as_ref().err().map(|e| e.to_string());
+                }
+                if let Err(e) = result {
+                    log_error!("[update] check failed: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        let response = self.client.get(&self.url).send().await?.error_for_status()?;
+        let signature = response.headers().get(SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_string())
+            .ok_or_else(|| anyhow::anyhow!("manifest is not signed ({} missing)", SIGNATURE_HEADER))?;
+        let body = response.text().await?;
+        let manifest = self.verify(&body, &signature)?;
+        let latest: Version = manifest.version.parse()?;
+        let available = is_newer(&manifest.version);
+
+        let previous = self.status().latest_version;
+        if let Ok(mut status) = self.status.lock() {
+            status.latest_version = Some(latest.to_string());
+            status.update_available = available;
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.set_update_available(available);
+        }
+        if !available {
+            return Ok(());
+        }
+        if previous.as_deref() != Some(latest.to_string().as_str()) {
+            log_info!("[update] version {} is available (running {}){}", latest, CURRENT_VERSION,
+                manifest.notes_url.as_ref().map(|url| format!(", notes: {}", url)).unwrap_or_default());
+        }
+        if !self.download || self.status().staged_version.as_deref() == Some(latest.to_string().as_str()) {
+            return Ok(());
+        }
+
+        let artifact = manifest.artifacts.get(&self.target)
+            .ok_or_else(|| anyhow::anyhow!("manifest for version {} has no binary for target {}", latest, self.target))?;
+        let staged = self.stage(&latest, artifact, body, signature).await?;
+        log_info!("[update] staged version {} at {} for the supervisor to swap in", staged.version, staged.path);
+        if let Ok(mut status) = self.status.lock() {
+            status.staged_version = Some(staged.version);
+            status.staged_path = Some(staged.path);
+        }
+        Ok(())
+    }
+
+    fn verify(&self, body: &str, signature: &str) -> anyhow::Result<ReleaseManifest> {
+        let message = release_manifest_message(body.as_bytes())?;
+        if !verify_payload(&message, signature, &self.pubkey_hex).unwrap_or(false) {
+            anyhow::bail!("manifest signature does not verify against UPDATE_PUBKEY");
+        }
+        Ok(serde_json::from_str(body)?)
+    }
+
+    // Downloaded aside, checked and renamed, so the supervisor never finds half a binary;
+    // it is only named in staged.json once it is in place and executable
+    async fn stage(&self, version: &Version, artifact: &ReleaseArtifact, manifest: String, signature: String) -> anyhow::Result<StagedRelease> {
+        let url = reqwest::Url::parse(&self.url)?.join(&artifact.url)?;
+        let limit = artifact.size.unwrap_or(MAX_ARTIFACT_BYTES);
+        if limit > MAX_ARTIFACT_BYTES {
+            anyhow::bail!("{} is {} bytes, more than the {} MiB an update may be", url, limit, MAX_ARTIFACT_BYTES >> 20);
+        }
+        let mut response = self.client.get(url.clone()).timeout(DOWNLOAD_TIMEOUT).send().await?
+            .error_for_status()?;
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if (bytes.len() + chunk.len()) as u64 > limit {
+                anyhow::bail!("{} is larger than {} bytes", url, limit);
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        if let Some(size) = artifact.size {
+            if bytes.len() as u64 != size {
+                anyhow::bail!("{} is {} bytes, the manifest says {}", url, bytes.len(), size);
+            }
+        }
+        let sha256 = hex::encode(Sha256::digest(&bytes));
+        if !sha256.eq_ignore_ascii_case(artifact.sha256.trim()) {
+            anyhow::bail!("{} has SHA-256 {}, the manifest says {}", url, sha256, artifact.sha256);
+        }
+
+        let name = format!("tops-worker-{}{}", version, std::env::consts::EXE_SUFFIX);
+        let staged = StagedRelease {
+            version: version.to_string(),
+            path: self.dir.join(&name).display().to_string(),
+            sha256,
+            staged_at: chrono::Utc::now().to_rfc3339(),
+            manifest,
+            signature,
+        };
+        let record = serde_json::to_vec_pretty(&staged)?;
+        let (dir, record_path) = (self.dir.clone(), self.staged_path());
+        let path = PathBuf::from(&staged.path);
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            std::fs::create_dir_all(&dir)?;
+            write_atomic(&path, &bytes)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+            }
+            write_atomic(&record_path, &record)
+        }).await??;
+
+        // Only the latest release stays staged
+        if let Some(previous) = self.status().staged_path.filter(|p| *p != staged.path) {
+            let _ = tokio::fs::remove_file(previous).await;
+        }
+        Ok(staged)
+    }
+
+    fn staged_path(&self) -> PathBuf {
+        self.dir.join("staged.json")
+    }
+
+    fn load_staged(&self) -> anyhow::Result<Option<StagedRelease>> {
+        let raw = match std::fs::read_to_string(self.staged_path()) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(serde_json::from_str(&raw)?))
+    }
+}
+
+// Whether `version` is newer than the running build; unparseable versions never are
+fn is_newer(version: &str) -> bool {
+    match (version.parse::<Version>(), CURRENT_VERSION.parse::<Version>()) {
+        (Ok(version), Ok(current)) => version > current,
+        _ => false,
+    }
+}
@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// Startup phase whose attempts absorb kernel compilation and driver warm-up.
+///
+/// Warm-up attempts are computed like any other but never reach the metrics,
+/// the size autotuner or the aggregator. The phase ends once both the attempt
+/// count and the time spent on warm-up attempts have been reached.
+#[derive(Debug)]
+pub struct Warmup {
+    target_attempts: u32,
+    target: Duration,
+    attempts: AtomicU32,
+    elapsed_ms: AtomicU64,
+    active: AtomicBool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmupStatus {
+    pub active: bool,
+    pub attempts: u32,
+    pub target_attempts: u32,
+    pub elapsed_ms: u64,
+    pub target_ms: u64,
+}
+
+impl Warmup {
+    pub fn new(target_attempts: u32, target: Duration) -> Self {
+        let enabled = target_attempts > 0 || !target.is_zero();
+        Self {
+            target_attempts,
+            target,
+            attempts: AtomicU32::new(0),
+            elapsed_ms: AtomicU64::new(0),
+            active: AtomicBool::new(enabled),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Count a finished warm-up attempt; false once the phase is over.
+    pub fn record_attempt(&self, elapsed_ms: u64) -> bool {
+        let attempts = self.attempts.fetch_add(1, Ordering::Relaxed) + 1;
+        let elapsed_ms = self.elapsed_ms.fetch_add(elapsed_ms, Ordering::Relaxed) + elapsed_ms;
+        if attempts >= self.target_attempts && elapsed_ms >= self.target.as_millis() as u64 {
+            self.active.store(false, Ordering::Relaxed);
+        }
+        self.is_active()
+    }
+
+    pub fn status(&self) -> WarmupStatus {
+        WarmupStatus {
+            active: self.is_active(),
+            attempts: self.attempts.load(Ordering::Relaxed),
+            target_attempts: self.target_attempts,
+            elapsed_ms: self.elapsed_ms.load(Ordering::Relaxed),
+            target_ms: self.target.as_millis() as u64,
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::Mutex;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::types::Sizes;
+
+/// What the worker would contribute to the fleet, estimated without keys (`WATCH_ONLY=1`).
+///
+/// Every attempt that passes the spot check counts as the receipt it would have
+/// become. Nothing is signed, submitted or kept as evidence.
+#[derive(Debug)]
+pub struct WatchEstimate {
+    started: Instant,
+    state: Mutex<WatchState>,
+}
+
+#[derive(Debug, Default)]
+struct WatchState {
+    attempts: u64,
+    tera_ops: f64,
+    compute_ms: u64,
+    sizes: Option<Sizes>,
+}
+
+/// The estimate reported in /status.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchSummary {
+    /// Attempts that would have been receipts.
+    pub attempts: u64,
+    pub elapsed_seconds: f64,
+    pub receipts_per_second: f64,
+    /// Tera-operations of those attempts per second of wall time: the rate the
+    /// worker would be credited at, pauses and pacing included.
+    pub estimated_tops: f64,
+    /// Tera-operations per second of attempt time: the device's own speed.
+    pub device_tops: f64,
+    /// Sizes of the latest attempt.
+    pub sizes: Option<Sizes>,
+}
+
+impl Default for WatchEstimate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WatchEstimate {
+    pub fn new() -> Self {
+        Self { started: Instant::now(), state: Mutex::new(WatchState::default()) }
+    }
+
+    /// Count an attempt of `tera_ops` that took `elapsed_ms`.
+    pub fn record(&self, sizes: &Sizes, tera_ops: f64, elapsed_ms: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            state.attempts += 1;
+            state.tera_ops += tera_ops;
+            state.compute_ms += elapsed_ms;
+            state.sizes = Some(sizes.clone());
+        }
+    }
+
+    pub fn summary(&self) -> WatchSummary {
+        let Ok(state) = self.state.lock() else { return WatchSummary::default() };
+        let elapsed_seconds = self.started.elapsed().as_secs_f64();
+        let per_second = |value: f64| if elapsed_seconds > 0.0 { value / elapsed_seconds } else { 0.0 };
+        WatchSummary {
+            attempts: state.attempts,
+            elapsed_seconds,
+            receipts_per_second: per_second(state.attempts as f64),
+            estimated_tops: per_second(state.tera_ops),
+            device_tops: if state.compute_ms > 0 { state.tera_ops / (state.compute_ms as f64 / 1000.0) } else { 0.0 },
+            sizes: state.sizes.clone(),
+        }
+    }
+}
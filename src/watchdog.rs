@@ -0,0 +1,121 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::{log_error, log_warn};
+
+/// Liveness of the main loop, bumped once per iteration.
+#[derive(Debug)]
+pub struct Heartbeat {
+    start: Instant,
+    last_beat_ms: AtomicU64,
+    // Intentional waits (power pause, Retry-After) are not stalls
+    idle: AtomicBool,
+    stalled: AtomicBool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeartbeatStatus {
+    pub age_ms: u64,
+    pub idle: bool,
+    pub stalled: bool,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            last_beat_ms: AtomicU64::new(0),
+            idle: AtomicBool::new(false),
+            stalled: AtomicBool::new(false),
+        }
+    }
+
+    pub fn beat(&self) {
+        self.last_beat_ms.store(self.start.elapsed().as_millis() as u64, Ordering::Relaxed);
+        self.stalled.store(false, Ordering::Relaxed);
+    }
+
+    /// Mark the loop as deliberately waiting (or done waiting); both count as a beat.
+    pub fn set_idle(&self, idle: bool) {
+        self.beat();
+        self.idle.store(idle, Ordering::Relaxed);
+    }
+
+    pub fn age(&self) -> Duration {
+        let now = self.start.elapsed().as_millis() as u64;
+        Duration::from_millis(now.saturating_sub(self.last_beat_ms.load(Ordering::Relaxed)))
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        self.stalled.load(Ordering::Relaxed)
+    }
+
+    pub fn status(&self) -> HeartbeatStatus {
+        HeartbeatStatus {
+            age_ms: self.age().as_millis() as u64,
+            idle: self.idle.load(Ordering::Relaxed),
+            stalled: self.is_stalled(),
+        }
+    }
+}
+
+/// Watches the heartbeat from a plain OS thread, so it keeps running even when
+/// the stalled loop blocks the async runtime.
+pub struct Watchdog;
+
+impl Watchdog {
+    /// Flag the heartbeat as stalled once it is older than `stall_after`; with
+    /// `restart`, re-exec the worker with the same arguments.
+    pub fn spawn(heartbeat: Arc<Heartbeat>, stall_after: Duration, restart: bool) {
+        let poll = (stall_after / 4).clamp(Duration::from_millis(100), Duration::from_secs(5));
+        std::thread::Builder::new()
+            .name("watchdog".into())
+            .spawn(move || loop {
+                std::thread::sleep(poll);
+                if heartbeat.idle.load(Ordering::Relaxed) || heartbeat.is_stalled() {
+                    continue;
+                }
+                let age = heartbeat.age();
+                if age < stall_after {
+                    continue;
+                }
+                heartbeat.stalled.store(true, Ordering::Relaxed);
+                log_warn!("[watchdog] main loop has not advanced for {:.0}s, health is now critical", age.as_secs_f64());
+                if restart {
+                    let err = restart_self();
+                    log_error!("[watchdog] self-restart failed: {}", err);
+                }
+            })
+            .expect("failed to spawn watchdog thread");
+    }
+}
+
+/// Replace the process with a fresh copy of the worker; only returns on failure.
+fn restart_self() -> std::io::Error {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => return e,
+    };
+    log_warn!("[watchdog] restarting {}", exe.display());
+    let mut command = std::process::Command::new(exe);
+    command.args(std::env::args_os().skip(1));
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.exec()
+    }
+    #[cfg(not(unix))]
+    {
+        match command.spawn() {
+            Ok(_) => std::process::exit(0),
+            Err(e) => e,
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use light_poseidon::{Poseidon, PoseidonBytesHasher};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+// Which outputs are sampled is shared with the receipt verification core
+pub use tops_verify_core::sampling::{WorkSampling, WORK_ROOT_SAMPLES};
+
+// Bytes packed into one BN254 field element: 31 are always below the modulus
+const POSEIDON_CHUNK_BYTES: usize = 31;
+
+/// Hash that turns an attempt's output samples into its work_root, chosen by the epoch.
+///
+/// The attempt pipeline and every verifier (quarantine revalidation, the mock
+/// aggregator) go through `digest`, so a receipt recomputes with the hash it names.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HashKind {
+    /// BLAKE3 of the samples; what every receipt used before the epoch could choose.
+    #[default]
+    #[serde(rename = "blake3")]
+    Blake3,
+    #[serde(rename = "sha3-256")]
+    Sha3_256,
+    /// Circom-compatible Poseidon over BN254, cheap to verify on chain: the samples are
+    /// packed 31 bytes to a field element and absorbed one at a time,
+    /// `acc = P(acc, chunk)` from `acc = 0`, then the root is `P(acc, sample count)`.
+    #[serde(rename = "poseidon")]
+    Poseidon,
+}
+
+impl HashKind {
+    /// v2 receipt encoding.
+    pub fn code(&self) -> u8 {
+        match self {
+            HashKind::Blake3 => 0,
+            HashKind::Sha3_256 => 1,
+            HashKind::Poseidon => 2,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(HashKind::Blake3),
+            1 => Some(HashKind::Sha3_256),
+            2 => Some(HashKind::Poseidon),
+            _ => None,
+        }
+    }
+
+    /// What to record in a receipt: nothing for BLAKE3, so verifiers that predate the
+    /// choice see the receipts they always did.
+    pub fn receipt_field(&self) -> Option<HashKind> {
+        (*self != HashKind::Blake3).then_some(*self)
+    }
+
+    /// The work_root of `samples`.
+    pub fn digest(&self, samples: &[i8]) -> [u8; 32] {
+        let bytes: Vec<u8> = samples.iter().map(|&x| x as u8).collect();
+        match self {
+            HashKind::Blake3 => blake3::hash(&bytes).into(),
+            HashKind::Sha3_256 => Sha3_256::digest(&bytes).into(),
+            HashKind::Poseidon => poseidon(&bytes),
+        }
+    }
+}
+
+fn poseidon(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Poseidon::<ark_bn254::Fr>::new_circom(2).expect("circom parameters exist for two inputs");
+    let mut acc = [0u8; 32];
+    for chunk in bytes.chunks(POSEIDON_CHUNK_BYTES) {
+        acc = hasher.hash_bytes_be(&[&acc, chunk]).expect("31 bytes always fit a field element");
+    }
+    let count = (bytes.len() as u64).to_be_bytes();
+    hasher.hash_bytes_be(&[&acc, &count]).expect("a u64 always fits a field element")
+}
+
+impl std::str::FromStr for HashKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "blake3" => Ok(HashKind::Blake3),
+            "sha3-256" | "sha3_256" | "sha3" => Ok(HashKind::Sha3_256),
+            "poseidon" => Ok(HashKind::Poseidon),
+            _ => Err(format!("unknown hash kind: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for HashKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashKind::Blake3 => write!(f, "blake3"),
+            HashKind::Sha3_256 => write!(f, "sha3-256"),
+            HashKind::Poseidon => write!(f, "poseidon"),
+        }
+    }
+}
+
+/// The output an `Executor::run_gemm_sampled` call samples: that of attempt
+/// (prev_hash, nonce), with `sampling`.
+#[derive(Debug, Clone, Copy)]
+pub struct OutputSampling {
+    pub sampling: WorkSampling,
+    pub prev_hash: [u8;32],
+    pub nonce: u32,
+}
+
+impl OutputSampling {
+    /// The root of `y` as the host computes it, the reference for the device's.
+    pub fn root_of(&self, y: &[i8]) -> SampledRoot {
+        let samples = self.sampling.sample(y, &self.prev_hash, self.nonce);
+        SampledRoot { work_root: HashKind::Blake3.digest(&samples), samples }
+    }
+}
+
+/// A BLAKE3 work_root computed where the output lives, with the samples it was
+/// hashed from: all that comes back of an output that stays on the device.
+#[derive(Debug, Clone)]
+pub struct SampledRoot {
+    pub work_root: [u8;32],
+    pub samples: Vec<i8>,
+}
+
+impl SampledRoot {
+    /// Whether this is what the host would have computed from the samples, for an
+    /// output of `len` elements: as many samples as it gives, hashing to the root.
+    pub fn is_intact(&self, len: usize) -> bool {
+        self.samples.len() == WORK_ROOT_SAMPLES.min(len) && HashKind::Blake3.digest(&self.samples) == self.work_root
+    }
+}
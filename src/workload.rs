@@ -0,0 +1,175 @@
+use std::sync::Arc;
+use crate::attempt::{compute_work_root, Executor};
+use crate::prng::derive_salted_seed;
+use crate::types::{Requant, Sizes};
+use crate::work_hash::{HashKind, WorkSampling};
+
+// The built-in workloads' inputs and reference kernels live in the verification core,
+// so receipts verify the same way in the worker and in a browser
+pub use tops_verify_core::workload::{CsrMatrix, Workload, WorkloadInput, WorkloadKind, GEMM_KERNEL_VER, SPMM_KERNEL_VER};
+
+// The implementation behind a built-in workload
+fn with_impl<R>(workload: &Workload, f: impl FnOnce(&dyn ProofWorkload) -> R) -> R {
+    match *workload {
+        Workload::Gemm => f(&GemmWorkload),
+        Workload::Spmm { density_permille } => f(&SpmmWorkload { density_permille }),
+    }
+}
+
+impl ProofWorkload for Workload {
+    fn kernel_ver(&self) -> String {
+        with_impl(self, |w| w.kernel_ver())
+    }
+
+    fn tera_ops(&self, sizes: &Sizes) -> f64 {
+        with_impl(self, |w| w.tera_ops(sizes))
+    }
+
+    fn generate_inputs(&self, seed: [u8; 16], sizes: &Sizes) -> WorkloadInput {
+        with_impl(self, |w| w.generate_inputs(seed, sizes))
+    }
+
+    fn execute(&self, executor: &dyn Executor, input: &WorkloadInput, sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        with_impl(self, |w| w.execute(executor, input, sizes, scale))
+    }
+}
+
+/// A proof-of-work kernel: how an attempt's inputs are drawn from its seed, how
+/// they run on an executor, how the output commits to a work root, and how the
+/// receipt names it so a verifier can run it again.
+///
+/// GEMM and SpMM are the built-in implementations; library users add their own
+/// with `WorkerRuntime::register_workload`.
+pub trait ProofWorkload: Send + Sync {
+    /// Kernel identifier put in receipts. The part before the first `;` names
+    /// the workload; anything after it carries parameters.
+    fn kernel_ver(&self) -> String;
+
+    /// Work of one attempt in tera-operations (TOPS-seconds).
+    fn tera_ops(&self, sizes: &Sizes) -> f64;
+
+    /// Deterministic inputs from the attempt seed (`prng::derive_salted_seed`).
+    fn generate_inputs(&self, seed: [u8; 16], sizes: &Sizes) -> WorkloadInput;
+
+    /// Run the kernel on `executor`; the output is what the work root samples.
+    fn execute(&self, executor: &dyn Executor, input: &WorkloadInput, sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>>;
+
+    /// Work root and samples of output `y`; by default the worker's own sampling and hash.
+    fn work_root(&self, y: &[i8], hash: HashKind, sampling: WorkSampling, prev_hash: &[u8; 32], nonce: u32) -> ([u8; 32], Vec<i8>) {
+        compute_work_root(y, hash, sampling, prev_hash, nonce)
+    }
+
+    /// Registry name: `kernel_ver` up to the first `;`.
+    fn name(&self) -> String {
+        self.kernel_ver().split(';').next().unwrap_or_default().to_string()
+    }
+}
+
+/// Dense int8 GEMM with requantization and ReLU, the original workload.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GemmWorkload;
+
+impl ProofWorkload for GemmWorkload {
+    fn kernel_ver(&self) -> String {
+        GEMM_KERNEL_VER.into()
+    }
+
+    /// A multiply-accumulate counts as two operations.
+    fn tera_ops(&self, sizes: &Sizes) -> f64 {
+        2.0 * sizes.m as f64 * sizes.n as f64 * sizes.k as f64 * sizes.batch.max(1) as f64 / 1e12
+    }
+
+    fn generate_inputs(&self, seed: [u8; 16], sizes: &Sizes) -> WorkloadInput {
+        Workload::Gemm.inputs(seed, sizes)
+    }
+
+    fn execute(&self, executor: &dyn Executor, input: &WorkloadInput, sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        match input {
+            WorkloadInput::Dense { a, b } => executor.run_gemm_batched(a, b, sizes, scale),
+            WorkloadInput::Sparse { .. } => anyhow::bail!("GEMM needs dense inputs"),
+        }
+    }
+}
+
+/// CSR sparse x dense int8 SpMM; the density is part of the kernel_ver so attempts can be replayed.
+#[derive(Debug, Clone, Copy)]
+pub struct SpmmWorkload {
+    pub density_permille: u16,
+}
+
+impl ProofWorkload for SpmmWorkload {
+    fn kernel_ver(&self) -> String {
+        format!("{};density_permille={}", SPMM_KERNEL_VER, self.density_permille)
+    }
+
+    /// Only the stored entries of A count.
+    fn tera_ops(&self, sizes: &Sizes) -> f64 {
+        GemmWorkload.tera_ops(sizes) * self.density_permille as f64 / 1000.0
+    }
+
+    fn generate_inputs(&self, seed: [u8; 16], sizes: &Sizes) -> WorkloadInput {
+        Workload::Spmm { density_permille: self.density_permille }.inputs(seed, sizes)
+    }
+
+    fn execute(&self, executor: &dyn Executor, input: &WorkloadInput, sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+        match input {
+            WorkloadInput::Sparse { a, b } => executor.run_spmm(a, b, sizes, scale),
+            WorkloadInput::Dense { .. } => anyhow::bail!("SpMM needs sparse inputs"),
+        }
+    }
+}
+
+/// The workloads an attempt's `kernel_ver` can name: the built-in ones, then
+/// any registered by name.
+#[derive(Clone, Default)]
+pub struct WorkloadRegistry {
+    registered: Vec<Arc<dyn ProofWorkload>>,
+}
+
+impl WorkloadRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a workload; its name must be new and free of `;`.
+    pub fn register(&mut self, workload: Arc<dyn ProofWorkload>) -> anyhow::Result<()> {
+        let name = workload.name();
+        if name.is_empty() || workload.kernel_ver().split(';').next() != Some(name.as_str()) {
+            anyhow::bail!("workload name '{}' must be the first part of its kernel_ver", name);
+        }
+        if self.names().contains(&name) {
+            anyhow::bail!("workload '{}' is already registered", name);
+        }
+        self.registered.push(workload);
+        Ok(())
+    }
+
+    /// Names of every workload, built-in first.
+    pub fn names(&self) -> Vec<String> {
+        [GEMM_KERNEL_VER.to_string(), SPMM_KERNEL_VER.to_string()].into_iter()
+            .chain(self.registered.iter().map(|w| w.name()))
+            .collect()
+    }
+
+    /// The workload a receipt's `kernel_ver` names, with its parameters.
+    pub fn resolve(&self, kernel_ver: &str) -> Option<Arc<dyn ProofWorkload>> {
+        if let Some(workload) = Workload::from_kernel_ver(kernel_ver) {
+            return Some(Arc::new(workload));
+        }
+        let name = kernel_ver.split(';').next()?;
+        self.registered.iter().find(|w| w.name() == name).cloned()
+    }
+}
+
+/// Deterministic inputs for (prev_hash, nonce, salt); unsalted GEMM inputs match `attempt::generate_inputs`.
+pub fn generate_workload_inputs(workload: Workload, prev_hash_bytes: &[u8;32], nonce: u32, salt: Option<&[u8;32]>, sizes: &Sizes) -> WorkloadInput {
+    workload.generate_inputs(derive_salted_seed(prev_hash_bytes, nonce, salt), sizes)
+}
+
+/// Run the kernel matching `input` on `executor` with the given requantization.
+pub fn execute_workload<E: Executor + ?Sized>(executor: &E, input: &WorkloadInput, sizes: &Sizes, scale: Requant) -> anyhow::Result<Vec<i8>> {
+    match input {
+        WorkloadInput::Dense { a, b } => executor.run_gemm_batched(a, b, sizes, scale),
+        WorkloadInput::Sparse { a, b } => executor.run_spmm(a, b, sizes, scale),
+    }
+}
@@ -7,6 +7,7 @@ set -e
 
 # Default environment
 export WORKER_SK_HEX=7b706b652278aba9b01dd473e026fd0baf215fd5afbf92d860b03fa661e07dc2
+export NETWORK_ID=peaq-testnet
 export AGGREGATOR_URL=http://localhost:8081/verify
 
 CONFIG_NAME=${1:-"Baseline"}
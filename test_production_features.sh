@@ -5,6 +5,7 @@ echo "=========================================="
 
 # Set up environment
 export WORKER_SK_HEX=7b706b652278aba9b01dd473e026fd0baf215fd5afbf92d860b03fa661e07dc2
+export NETWORK_ID=peaq-testnet
 export AGGREGATOR_URL=http://localhost:8081/verify
 export METRICS_ENABLED=1
 export WORKER_DEBUG_RECEIPT=1
@@ -1,15 +1,60 @@
 import express from "express";
 import morgan from "morgan";
 import { blake3 } from "@noble/hashes/blake3";
-import { sha256 } from "@noble/hashes/sha256";
 import { secp256k1 } from "@noble/curves/secp256k1";
+import { computeMessageDigest, hexToBytes } from "./receipt.js";
 
 const app = express();
-app.use(express.json({ limit: "1mb" }));
+// The signature covers the worker's own encoding, so the body is kept as received
+app.use(express.json({ limit: "1mb", verify: (req, _res, buf) => { req.rawBody = buf; } }));
 app.use(morgan("dev"));
 
 const VERIFY_PUBKEY = process.env.VERIFY_PUBKEY || ""; // hex (compressed or uncompressed)
 const VERIFY_DISABLE = process.env.VERIFY_DISABLE === "1";
+const VERIFY_NETWORK_ID = process.env.VERIFY_NETWORK_ID || ""; // reject receipts signed for other networks
+// The epoch's size distribution as "m,n,k:weight;..."; receipts must carry the sizes their seed draws
+const VERIFY_SIZE_DISTRIBUTION = parseSizeDistribution(process.env.VERIFY_SIZE_DISTRIBUTION || "");
+
+// Domain of the hash that turns an attempt seed into a size draw (size_distribution.rs)
+const SIZE_DRAW_DOMAIN = "tops-worker/size-draw/v1";
+
+function parseSizeDistribution(spec) {
+  if (!spec) return null;
+  return spec.split(";").map((entry) => {
+    const [shape, weight] = entry.split(":");
+    const [m, n, k] = shape.split(",").map(Number);
+    const w = Number(weight);
+    if (![m, n, k, w].every((v) => Number.isInteger(v) && v > 0))
+      throw new Error(`invalid VERIFY_SIZE_DISTRIBUTION entry "${entry}"`);
+    return { m, n, k, weight: BigInt(w) };
+  });
+}
+
+// The attempt's PRNG seed: blake3(prev_hash || nonce u32 LE [|| salt])[..16]
+function attemptSeed(prevHash, nonce, salt) {
+  const msg = new Uint8Array(36 + (salt ? 32 : 0));
+  msg.set(prevHash, 0);
+  new DataView(msg.buffer).setUint32(32, nonce, true);
+  if (salt) msg.set(salt, 36);
+  return blake3(msg).slice(0, 16);
+}
+
+// Same draw as SizeDistribution::draw: first 8 bytes of blake3(domain || seed) as
+// u64 LE, modulo the total weight, walked through the cumulative weights
+function drawSizes(distribution, seed) {
+  const domain = new TextEncoder().encode(SIZE_DRAW_DOMAIN);
+  const msg = new Uint8Array(domain.length + seed.length);
+  msg.set(domain, 0);
+  msg.set(seed, domain.length);
+  const h = blake3(msg);
+  const total = distribution.reduce((sum, e) => sum + e.weight, 0n);
+  let point = new DataView(h.buffer, h.byteOffset, 8).getBigUint64(0, true) % total;
+  for (const e of distribution) {
+    if (point < e.weight) return e;
+    point -= e.weight;
+  }
+  return distribution[distribution.length - 1];
+}
 
 // Minimal schema check
 function isValidReceipt(r) {
@@ -34,22 +79,6 @@ function isValidReceipt(r) {
   );
 }
 
-function hexToBytes(hex) {
-  if (hex.startsWith("0x")) hex = hex.slice(2);
-  if (hex.length % 2 !== 0) throw new Error("invalid hex length");
-  const out = new Uint8Array(hex.length / 2);
-  for (let i = 0; i < out.length; i++)
-    out[i] = parseInt(hex.slice(2 * i, 2 * i + 2), 16);
-  return out;
-}
-
-function computeMessageDigest(receipt) {
-  const copy = { ...receipt, sig_hex: "" };
-  const msg = new TextEncoder().encode(JSON.stringify(copy));
-  const b3 = blake3(msg);
-  return sha256(b3);
-}
-
 function parseSignatureBytes(sigHex) {
   const bytes = hexToBytes(sigHex);
   // Try DER -> convert to compact raw bytes for verify()
@@ -69,6 +98,11 @@ function normalizePubkey(pubHex) {
   throw new Error("invalid pubkey length");
 }
 
+// Receipt version handshake: this verifier only understands v1 (JSON) receipts
+app.options("/verify", (_req, res) => {
+  res.set("X-Receipt-Versions", "1").sendStatus(204);
+});
+
 // Health
 app.get("/healthz", (_req, res) => res.json({ ok: true }));
 
@@ -89,12 +123,25 @@ app.post("/verify", async (req, res) => {
         .status(400)
         .json({ ok: false, error: "invalid prev_hash_hex" });
     }
+    if (VERIFY_NETWORK_ID && receipt.network_id !== VERIFY_NETWORK_ID) {
+      return res
+        .status(400)
+        .json({ ok: false, error: "wrong network_id" });
+    }
     const { m, n, k } = receipt.sizes;
     if (m <= 0 || n <= 0 || k <= 0 || m > 8192 || n > 8192 || k > 8192) {
       return res.status(400).json({ ok: false, error: "unreasonable sizes" });
     }
+    if (VERIFY_SIZE_DISTRIBUTION) {
+      const salt = receipt.epoch_salt_hex ? hexToBytes(receipt.epoch_salt_hex) : null;
+      const seed = attemptSeed(hexToBytes(receipt.prev_hash_hex), receipt.nonce, salt);
+      const drawn = drawSizes(VERIFY_SIZE_DISTRIBUTION, seed);
+      if (drawn.m !== m || drawn.n !== n || drawn.k !== k) {
+        return res.status(400).json({ ok: false, error: "sizes do not match the size distribution" });
+      }
+    }
 
-    const digest = computeMessageDigest(receipt);
+    const digest = computeMessageDigest(receipt, req.rawBody);
 
     let sigOk = false;
     let pubHexUsed = null;
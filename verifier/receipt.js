@@ -0,0 +1,45 @@
+import { blake3 } from "@noble/hashes/blake3";
+import { sha256 } from "@noble/hashes/sha256";
+
+// Signatures of receipts with a network_id cover this domain plus the network ID
+const RECEIPT_DOMAIN = "tops-worker/v2/";
+
+export function hexToBytes(hex) {
+  if (hex.startsWith("0x")) hex = hex.slice(2);
+  if (hex.length % 2 !== 0) throw new Error("invalid hex length");
+  const out = new Uint8Array(hex.length / 2);
+  for (let i = 0; i < out.length; i++)
+    out[i] = parseInt(hex.slice(2 * i, 2 * i + 2), 16);
+  return out;
+}
+
+// The JSON the worker signed: the body as received with an empty sig_hex. Re-serializing
+// the parsed receipt is not the same bytes (JavaScript prints a whole-number float such
+// as 5.0 as 5), so that is only the fallback for bodies without the worker's layout.
+function signedEncoding(receipt, rawBody) {
+  const text = rawBody ? Buffer.from(rawBody).toString("utf8") : null;
+  const field = `"sig_hex":${JSON.stringify(receipt.sig_hex)}`;
+  const at = text === null ? -1 : text.lastIndexOf(field);
+  if (at < 0) {
+    return new TextEncoder().encode(JSON.stringify({ ...receipt, sig_hex: "" }));
+  }
+  const unsigned = text.slice(0, at) + `"sig_hex":""` + text.slice(at + field.length);
+  return new TextEncoder().encode(unsigned);
+}
+
+// blake3-then-sha256 digest of what a receipt signature covers
+export function computeMessageDigest(receipt, rawBody) {
+  const encoding = signedEncoding(receipt, rawBody);
+  let msg = encoding;
+  if (typeof receipt.network_id === "string") {
+    // u16 LE length of the domain, the domain, then the encoding
+    const domain = new TextEncoder().encode(RECEIPT_DOMAIN + receipt.network_id);
+    msg = new Uint8Array(2 + domain.length + encoding.length);
+    msg[0] = domain.length & 0xff;
+    msg[1] = domain.length >> 8;
+    msg.set(domain, 2);
+    msg.set(encoding, 2 + domain.length);
+  }
+  const b3 = blake3(msg);
+  return sha256(b3);
+}
@@ -0,0 +1,23 @@
+import { test } from "node:test";
+import assert from "node:assert/strict";
+import { readFileSync } from "node:fs";
+import { secp256k1 } from "@noble/curves/secp256k1";
+import { computeMessageDigest, hexToBytes } from "../receipt.js";
+
+const fixture = (name) =>
+  JSON.parse(readFileSync(new URL(`./fixtures/${name}`, import.meta.url), "utf8"));
+
+test("a worker receipt with a whole-number energy_estimate_j verifies", () => {
+  const { pubkey_hex, body } = fixture("energy-whole-number.json");
+  const receipt = JSON.parse(body);
+  assert.equal(receipt.energy_estimate_j, 5);
+  const digest = computeMessageDigest(receipt, Buffer.from(body));
+  assert.ok(secp256k1.verify(hexToBytes(receipt.sig_hex), digest, hexToBytes(pubkey_hex)));
+});
+
+test("re-serializing the parsed receipt loses the worker's float formatting", () => {
+  const { pubkey_hex, body } = fixture("energy-whole-number.json");
+  const receipt = JSON.parse(body);
+  const digest = computeMessageDigest(receipt);
+  assert.ok(!secp256k1.verify(hexToBytes(receipt.sig_hex), digest, hexToBytes(pubkey_hex)));
+});
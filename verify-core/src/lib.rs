@@ -0,0 +1,51 @@
+//! Deterministic core of tops-worker receipts: attempt seeds and inputs, the CPU
+//! reference kernels, work-root sampling and hashing, and receipt signatures.
+//!
+//! The worker builds on these same modules, so a receipt checked here (natively,
+//! or in a browser through the `wasm` feature's `verify_receipt`) is recomputed
+//! exactly as the worker computed it. Everything but the `std` and `wasm` features
+//! builds without the standard library.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod memhard;
+pub mod prng;
+pub mod receipt;
+pub mod requant;
+pub mod sampling;
+pub mod verify;
+pub mod workload;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use alloc::string::String;
+
+pub use verify::{verify_receipt_json, ReceiptVerification};
+
+/// Why a receipt could not be checked at all (as opposed to failing a check).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// Not a v1 receipt.
+    Json(String),
+    /// A field that cannot be what a worker sends.
+    Field { field: &'static str, reason: &'static str },
+}
+
+impl VerifyError {
+    pub fn field(field: &'static str, reason: &'static str) -> Self {
+        VerifyError::Field { field, reason }
+    }
+}
+
+impl core::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            VerifyError::Json(e) => write!(f, "invalid receipt JSON: {}", e),
+            VerifyError::Field { field, reason } => write!(f, "{} {}", field, reason),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerifyError {}
@@ -0,0 +1,119 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use serde::{Deserialize, Serialize};
+
+/// 32-bit words per ROMix block (64 bytes, one Salsa20/8 state).
+pub const MEMHARD_BLOCK_WORDS: usize = 16;
+
+/// Largest buffer a worker runs the stage with or a verifier accepts (4 GiB).
+pub const MEMHARD_MAX_KIB: u32 = 4 * 1024 * 1024;
+/// Most passes over the buffer a worker runs or a verifier accepts.
+pub const MEMHARD_MAX_PASSES: u32 = 16;
+
+const MEMHARD_CONTEXT: &str = "tops-worker memhard romix_salsa8 v1";
+
+/// Parameters of the optional memory-hard stage.
+///
+/// The stage is scrypt's ROMix with a single Salsa20/8 block per step: `mem_kib`
+/// of blocks are filled sequentially, then read back `passes` times in a
+/// data-dependent order. Its digest perturbs the attempt's inputs, so an attempt
+/// cannot be computed without holding the whole buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemHardParams {
+    pub mem_kib: u32,
+    pub passes: u32,
+}
+
+impl MemHardParams {
+    /// Number of 64-byte blocks in the buffer.
+    pub fn blocks(&self) -> usize {
+        // 16 blocks per KiB; multiplying first would overflow a 32-bit usize
+        self.mem_kib as usize * (1024 / (MEMHARD_BLOCK_WORDS * 4))
+    }
+
+    /// Random reads in the second phase.
+    pub fn iterations(&self) -> usize {
+        self.blocks() * self.passes as usize
+    }
+
+    /// Stage description recorded in receipts (appended to `kernel_ver`).
+    pub fn tag(&self) -> String {
+        format!("memhard=romix_salsa8_v1,kib={},passes={}", self.mem_kib, self.passes)
+    }
+
+    /// Inverse of `tag`; None for a tag beyond `MEMHARD_MAX_KIB` or `MEMHARD_MAX_PASSES`.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let rest = tag.strip_prefix("memhard=romix_salsa8_v1,kib=")?;
+        let (kib, passes) = rest.split_once(",passes=")?;
+        let params = MemHardParams { mem_kib: kib.parse().ok()?, passes: passes.parse().ok()? };
+        (params.mem_kib <= MEMHARD_MAX_KIB && (1..=MEMHARD_MAX_PASSES).contains(&params.passes)).then_some(params)
+    }
+}
+
+/// First ROMix block, expanded from the attempt seed.
+pub fn initial_block(seed: &[u8; 16]) -> [u32; MEMHARD_BLOCK_WORDS] {
+    let mut hasher = blake3::Hasher::new_derive_key(MEMHARD_CONTEXT);
+    hasher.update(seed);
+    let mut bytes = [0u8; MEMHARD_BLOCK_WORDS * 4];
+    hasher.finalize_xof().fill(&mut bytes);
+    let mut x = [0u32; MEMHARD_BLOCK_WORDS];
+    for (w, chunk) in x.iter_mut().zip(bytes.chunks_exact(4)) {
+        *w = u32::from_le_bytes(chunk.try_into().expect("4-byte chunk"));
+    }
+    x
+}
+
+/// Reference ROMix; GPU backends must produce the same final block.
+pub fn romix(block: &[u32; MEMHARD_BLOCK_WORDS], params: &MemHardParams) -> [u32; MEMHARD_BLOCK_WORDS] {
+    let n = params.blocks().max(1);
+    let mut v = vec![[0u32; MEMHARD_BLOCK_WORDS]; n];
+    let mut x = *block;
+    for slot in v.iter_mut() {
+        *slot = x;
+        salsa20_8(&mut x);
+    }
+    for _ in 0..params.iterations() {
+        let j = x[0] as usize % n;
+        for (xi, vi) in x.iter_mut().zip(v[j].iter()) {
+            *xi ^= vi;
+        }
+        salsa20_8(&mut x);
+    }
+    x
+}
+
+/// Digest of the stage for one attempt, binding the final block to the seed.
+pub fn finalize(seed: &[u8; 16], block: &[u32; MEMHARD_BLOCK_WORDS]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key(MEMHARD_CONTEXT);
+    hasher.update(seed);
+    for w in block {
+        hasher.update(&w.to_le_bytes());
+    }
+    hasher.finalize().into()
+}
+
+fn quarter(x: &mut [u32; MEMHARD_BLOCK_WORDS], a: usize, b: usize, c: usize, d: usize) {
+    x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+    x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+    x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+    x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+}
+
+/// The Salsa20/8 core as used by scrypt: 4 double rounds plus the feed-forward.
+fn salsa20_8(b: &mut [u32; MEMHARD_BLOCK_WORDS]) {
+    let mut x = *b;
+    for _ in 0..4 {
+        quarter(&mut x, 0, 4, 8, 12);
+        quarter(&mut x, 5, 9, 13, 1);
+        quarter(&mut x, 10, 14, 2, 6);
+        quarter(&mut x, 15, 3, 7, 11);
+        quarter(&mut x, 0, 1, 2, 3);
+        quarter(&mut x, 5, 6, 7, 4);
+        quarter(&mut x, 10, 11, 8, 9);
+        quarter(&mut x, 15, 12, 13, 14);
+    }
+    for (bi, xi) in b.iter_mut().zip(x.iter()) {
+        *bi = bi.wrapping_add(*xi);
+    }
+}
@@ -0,0 +1,119 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use k256::ecdsa::signature::hazmat::PrehashVerifier;
+use k256::ecdsa::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use crate::requant::Requant;
+use crate::sampling::WorkSampling;
+use crate::workload::Sizes;
+use crate::VerifyError;
+
+/// Receipt signatures are bound to `<RECEIPT_DOMAIN><network_id>`, so a receipt
+/// signed for one network does not verify on another.
+pub const RECEIPT_DOMAIN: &str = "tops-worker/v2/";
+
+/// Performance context of a v1 receipt (`perf_context`), kept as recorded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PerfContextV1 {
+    pub version: u8,
+    pub streams: u16,
+    pub pipeline_depth: u8,
+    pub thermal_throttled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power_mode: Option<String>,
+    pub duty_permille: u16,
+}
+
+/// A v1 (JSON) receipt as the worker sends it.
+///
+/// Fields and their order mirror the worker's encoder (`WorkReceipt::encode_v1`):
+/// the signature covers this exact serialization with an empty `sig_hex`, so a
+/// field added there has to be added here, in the same place. Enumerations the
+/// verifier only passes through (`timing_confidence`, `hash_kind`) stay strings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptV1 {
+    pub device_did: String,
+    pub epoch_id: u64,
+    pub prev_hash_hex: String,
+    pub nonce: u32,
+    pub work_root_hex: String,
+    pub sizes: Sizes,
+    pub time_ms: u64,
+    pub kernel_ver: String,
+    pub driver_hint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch_salt_hex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evidence_hash_hex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_epoch: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued_at_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requant: Option<Requant>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timing_confidence: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub energy_estimate_j: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub work_sampling: Option<WorkSampling>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perf_context: Option<PerfContextV1>,
+    pub sig_hex: String,
+}
+
+impl ReceiptV1 {
+    pub fn from_json(json: &str) -> Result<Self, VerifyError> {
+        serde_json::from_str(json).map_err(|e| VerifyError::Json(format!("{}", e)))
+    }
+
+    /// The message the signature covers: the length-prefixed domain
+    /// `tops-worker/v2/<network_id>` followed by the JSON encoding with an empty
+    /// `sig_hex`. Receipts without a network ID cover the bare encoding.
+    pub fn signing_message(&self) -> Result<Vec<u8>, VerifyError> {
+        let unsigned = ReceiptV1 { sig_hex: String::new(), ..self.clone() };
+        let encoding = serde_json::to_vec(&unsigned).map_err(|e| VerifyError::Json(format!("{}", e)))?;
+        let Some(network_id) = &self.network_id else { return Ok(encoding) };
+        let domain = format!("{}{}", RECEIPT_DOMAIN, network_id);
+        let len = u16::try_from(domain.len())
+            .map_err(|_| VerifyError::field("network_id", "is longer than 65535 bytes"))?;
+        let mut message = Vec::with_capacity(2 + domain.len() + encoding.len());
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(domain.as_bytes());
+        message.extend_from_slice(&encoding);
+        Ok(message)
+    }
+
+    /// Check `sig_hex` against a SEC1-encoded (compressed or not) hex pubkey.
+    pub fn verify_signature(&self, pubkey_hex: &str) -> Result<bool, VerifyError> {
+        let key = hex::decode(pubkey_hex.trim_start_matches("0x"))
+            .ok()
+            .and_then(|bytes| VerifyingKey::from_sec1_bytes(&bytes).ok())
+            .ok_or_else(|| VerifyError::field("pubkey", "is not a hex SEC1 secp256k1 key"))?;
+        let signature = hex::decode(&self.sig_hex)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or_else(|| VerifyError::field("sig_hex", "is not a hex compact signature"))?;
+        Ok(key.verify_prehash(&prehash(&self.signing_message()?), &signature).is_ok())
+    }
+}
+
+/// What receipt and response signatures sign: blake3 of the message, then sha256.
+pub fn prehash(message: &[u8]) -> [u8; 32] {
+    sha2::Sha256::digest(blake3::hash(message).as_bytes()).into()
+}
+
+/// Decode a 32-byte hex field.
+pub fn hex32(field: &'static str, value: &str) -> Result<[u8; 32], VerifyError> {
+    let mut out = [0u8; 32];
+    hex::decode_to_slice(value, &mut out).map_err(|_| VerifyError::field(field, "is not 32 bytes of hex"))?;
+    Ok(out)
+}
@@ -0,0 +1,290 @@
+use alloc::format;
+use alloc::string::String;
+use serde::{Deserialize, Serialize};
+
+// Domain for deriving the requantization scale from an epoch salt
+const REQUANT_CONTEXT: &str = "tops-worker requant scale v1";
+
+/// Activation applied to the requantized int8 value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Activation {
+    /// `max(q, 0)`
+    #[default]
+    Relu,
+    /// `clamp(q, 0, 96)`: 6.0 with the output read as Q3.4 fixed point.
+    Relu6,
+    /// `q` unchanged (the full int8 range).
+    Identity,
+    /// `q` for `q >= 0`, otherwise `q / 8` (rounded towards zero).
+    Leaky,
+}
+
+impl Activation {
+    pub const ALL: [Activation; 4] = [Activation::Relu, Activation::Relu6, Activation::Identity, Activation::Leaky];
+
+    /// Kernel argument and v2 receipt encoding.
+    pub fn code(&self) -> u8 {
+        match self {
+            Activation::Relu => 0,
+            Activation::Identity => 1,
+            Activation::Relu6 => 2,
+            Activation::Leaky => 3,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Activation::Relu),
+            1 => Some(Activation::Identity),
+            2 => Some(Activation::Relu6),
+            3 => Some(Activation::Leaky),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, q: i8) -> i8 {
+        match self {
+            Activation::Relu => q.max(0),
+            Activation::Relu6 => q.clamp(0, 96),
+            Activation::Identity => q,
+            Activation::Leaky => if q < 0 { q / 8 } else { q },
+        }
+    }
+}
+
+impl core::str::FromStr for Activation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "relu" => Ok(Activation::Relu),
+            "relu6" => Ok(Activation::Relu6),
+            "identity" | "none" => Ok(Activation::Identity),
+            "leaky" | "leaky_relu" => Ok(Activation::Leaky),
+            _ => Err(format!("unknown activation: {}", s)),
+        }
+    }
+}
+
+impl core::fmt::Display for Activation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Activation::Relu => write!(f, "relu"),
+            Activation::Relu6 => write!(f, "relu6"),
+            Activation::Identity => write!(f, "identity"),
+            Activation::Leaky => write!(f, "leaky"),
+        }
+    }
+}
+
+/// How `(acc * num) / den` is rounded to an integer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rounding {
+    /// Truncate: `-2.5` -> `-2`.
+    #[default]
+    TowardZero,
+    /// Round down: `-2.5` -> `-3`, `-2.4` -> `-3`.
+    Floor,
+    /// Round to nearest, halves away from zero: `-2.5` -> `-3`, `2.5` -> `3`.
+    HalfAwayFromZero,
+}
+
+impl Rounding {
+    pub const ALL: [Rounding; 3] = [Rounding::TowardZero, Rounding::Floor, Rounding::HalfAwayFromZero];
+
+    pub fn code(&self) -> u8 {
+        match self {
+            Rounding::TowardZero => 0,
+            Rounding::Floor => 1,
+            Rounding::HalfAwayFromZero => 2,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Rounding::TowardZero),
+            1 => Some(Rounding::Floor),
+            2 => Some(Rounding::HalfAwayFromZero),
+            _ => None,
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `n / d` rounded this way; `d` must be positive.
+    pub fn divide(&self, n: i64, d: i64) -> i64 {
+        let (q, r) = (n / d, n % d);
+        match self {
+            Rounding::TowardZero => q,
+            Rounding::Floor => if r < 0 { q - 1 } else { q },
+            Rounding::HalfAwayFromZero => if 2 * r.abs() >= d { q + n.signum() } else { q },
+        }
+    }
+}
+
+impl core::str::FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "toward-zero" | "trunc" => Ok(Rounding::TowardZero),
+            "floor" => Ok(Rounding::Floor),
+            "half-away-from-zero" | "round" => Ok(Rounding::HalfAwayFromZero),
+            _ => Err(format!("unknown rounding: {}", s)),
+        }
+    }
+}
+
+impl core::fmt::Display for Rounding {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Rounding::TowardZero => write!(f, "toward-zero"),
+            Rounding::Floor => write!(f, "floor"),
+            Rounding::HalfAwayFromZero => write!(f, "half-away-from-zero"),
+        }
+    }
+}
+
+/// How a rounded value outside the int8 range is brought into it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    /// Clamp to `[-128, 127]`.
+    #[default]
+    Saturate,
+    /// Keep the low 8 bits (two's complement): `128` -> `-128`, `-129` -> `127`.
+    Wrap,
+}
+
+impl Overflow {
+    pub const ALL: [Overflow; 2] = [Overflow::Saturate, Overflow::Wrap];
+
+    pub fn code(&self) -> u8 {
+        match self {
+            Overflow::Saturate => 0,
+            Overflow::Wrap => 1,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Overflow::Saturate),
+            1 => Some(Overflow::Wrap),
+            _ => None,
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn narrow(&self, q: i64) -> i8 {
+        match self {
+            Overflow::Saturate => q.clamp(-128, 127) as i8,
+            Overflow::Wrap => q as i8,
+        }
+    }
+}
+
+impl core::str::FromStr for Overflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "saturate" | "saturating" => Ok(Overflow::Saturate),
+            "wrap" | "wrapping" => Ok(Overflow::Wrap),
+            _ => Err(format!("unknown overflow mode: {}", s)),
+        }
+    }
+}
+
+impl core::fmt::Display for Overflow {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Overflow::Saturate => write!(f, "saturate"),
+            Overflow::Wrap => write!(f, "wrap"),
+        }
+    }
+}
+
+/// Requantization of the int32 accumulators:
+/// `q = activation(overflow(rounding((acc * num) / den)))`. By default the quotient
+/// truncates towards zero and saturates to `[-128, 127]` before the activation, so
+/// with ReLU this is `clamp((acc * num) / den, 0, 127)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Requant {
+    pub num: i32,
+    pub den: i32,
+    #[serde(default)]
+    pub activation: Activation,
+    // Left out at their defaults, so receipts that predate them sign the same bytes
+    #[serde(default, skip_serializing_if = "Rounding::is_default")]
+    pub rounding: Rounding,
+    #[serde(default, skip_serializing_if = "Overflow::is_default")]
+    pub overflow: Overflow,
+}
+
+// Bits of the mode code above the activation
+const MODE_ROUNDING_SHIFT: u8 = 4;
+const MODE_OVERFLOW_SHIFT: u8 = 6;
+
+impl Requant {
+    /// The unsalted scale every kernel used before epoch salts.
+    pub const IDENTITY: Requant = Requant {
+        num: 1,
+        den: 1,
+        activation: Activation::Relu,
+        rounding: Rounding::TowardZero,
+        overflow: Overflow::Saturate,
+    };
+
+    /// `num/den` with the default rounding and overflow.
+    pub fn new(num: i32, den: i32, activation: Activation) -> Self {
+        Requant { num, den, activation, ..Self::IDENTITY }
+    }
+
+    /// Scale for an epoch: `num` in 1..=256, `den` in 1..=65536, both from the salt.
+    pub fn from_salt(salt: Option<&[u8; 32]>) -> Self {
+        let Some(salt) = salt else { return Self::IDENTITY };
+        let bytes = blake3::derive_key(REQUANT_CONTEXT, salt);
+        Requant::new(bytes[0] as i32 + 1, u16::from_le_bytes([bytes[1], bytes[2]]) as i32 + 1, Activation::Relu)
+    }
+
+    /// Requantize one accumulator; the reference every backend has to match.
+    pub fn apply(&self, acc: i64) -> i8 {
+        let q = self.rounding.divide(acc * self.num as i64, self.den as i64);
+        self.activation.apply(self.overflow.narrow(q))
+    }
+
+    /// Whether rounding and overflow are the defaults every backend has always used.
+    pub fn has_default_semantics(&self) -> bool {
+        self.rounding.is_default() && self.overflow.is_default()
+    }
+
+    /// Activation, rounding and overflow in one byte, the kernels' `activation`
+    /// argument and the v2 receipt encoding: activation in bits 0-3, rounding in
+    /// bits 4-5, overflow in bit 6. With the default semantics it is the activation code.
+    pub fn mode_code(&self) -> u8 {
+        self.activation.code() | self.rounding.code() << MODE_ROUNDING_SHIFT | self.overflow.code() << MODE_OVERFLOW_SHIFT
+    }
+
+    pub fn from_mode_code(num: i32, den: i32, code: u8) -> Option<Self> {
+        Some(Requant {
+            num,
+            den,
+            activation: Activation::from_code(code & 0x0F)?,
+            rounding: Rounding::from_code((code >> MODE_ROUNDING_SHIFT) & 0x03)?,
+            overflow: Overflow::from_code((code >> MODE_OVERFLOW_SHIFT) & 0x03)?,
+        })
+    }
+}
+
+impl Default for Requant {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
@@ -0,0 +1,127 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+use crate::prng::{derive_seed, DPrng};
+
+const SAMPLING_CONTEXT: &str = "tops-worker work-root sampling v1";
+const COMMITTED_SAMPLING_CONTEXT: &str = "tops-worker committed work-root sampling v1";
+const COMMITMENT_CONTEXT: &str = "tops-worker output commitment v1";
+
+/// Output elements hashed into the work_root (fewer for smaller outputs).
+pub const WORK_ROOT_SAMPLES: usize = 1024;
+
+/// Which output elements are hashed into the work_root (`WORK_ROOT_SAMPLING`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkSampling {
+    /// The first elements of the output, what receipts without `work_sampling` use.
+    /// Computing the first row or so is enough to get these right.
+    Prefix,
+    /// Elements drawn across the whole output, with replacement, from a PRNG seeded
+    /// by (prev_hash, nonce): index `i` is the `i`-th `u32` of `DPrng` keyed with
+    /// `BLAKE3.derive_key("tops-worker work-root sampling v1", derive_seed(prev_hash, nonce))`,
+    /// modulo the output length. The indices are known before the output is computed,
+    /// so a verifier can recompute just those elements, and so can a worker.
+    Seeded,
+    /// Like `Seeded`, but the PRNG is keyed with
+    /// `BLAKE3.derive_key("tops-worker committed work-root sampling v1", derive_seed(prev_hash, nonce) || commitment)`,
+    /// `commitment` being the `output_commitment` of the whole output. The indices
+    /// are not known until every element is, so a correct root takes the whole
+    /// output, and checking one takes recomputing it.
+    Committed,
+}
+
+/// BLAKE3 of every element of an output, in the `"tops-worker output commitment v1"`
+/// derive-key mode: what `WorkSampling::Committed` draws its indices from.
+pub fn output_commitment(y: &[i8]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key(COMMITMENT_CONTEXT);
+    for chunk in y.chunks(64 * 1024) {
+        let bytes: Vec<u8> = chunk.iter().map(|&x| x as u8).collect();
+        hasher.update(&bytes);
+    }
+    hasher.finalize().into()
+}
+
+impl WorkSampling {
+    /// v2 receipt encoding.
+    pub fn code(&self) -> u8 {
+        match self {
+            WorkSampling::Prefix => 0,
+            WorkSampling::Seeded => 1,
+            WorkSampling::Committed => 2,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(WorkSampling::Prefix),
+            1 => Some(WorkSampling::Seeded),
+            2 => Some(WorkSampling::Committed),
+            _ => None,
+        }
+    }
+
+    /// What to record in a receipt: nothing for `Prefix`, so current aggregators
+    /// see the receipts they always did.
+    pub fn receipt_field(&self) -> Option<WorkSampling> {
+        (*self != WorkSampling::Prefix).then_some(*self)
+    }
+
+    /// Whether the sampled indices depend on the output itself, so the whole of it
+    /// has to be at hand (read back from the device, recomputed by a verifier).
+    pub fn needs_output(&self) -> bool {
+        *self == WorkSampling::Committed
+    }
+
+    /// The elements of `y` that go into the work_root of attempt (prev_hash, nonce).
+    pub fn sample(&self, y: &[i8], prev_hash: &[u8;32], nonce: u32) -> Vec<i8> {
+        let commitment = self.needs_output().then(|| output_commitment(y));
+        self.indices(y.len(), prev_hash, nonce, commitment.as_ref()).into_iter().map(|i| y[i]).collect()
+    }
+
+    /// Flat indices of the sampled elements in an output of `len` elements, in sample
+    /// order. `commitment` is the output's `output_commitment`, which only `Committed`
+    /// draws from, and which it must be given.
+    pub fn indices(&self, len: usize, prev_hash: &[u8;32], nonce: u32, commitment: Option<&[u8;32]>) -> Vec<usize> {
+        let count = WORK_ROOT_SAMPLES.min(len);
+        let key = match self {
+            WorkSampling::Prefix => return (0..count).collect(),
+            WorkSampling::Seeded => blake3::derive_key(SAMPLING_CONTEXT, &derive_seed(prev_hash, nonce)),
+            WorkSampling::Committed => {
+                let commitment = commitment.expect("committed sampling draws from the output commitment");
+                let mut material = [0u8; 48];
+                material[..16].copy_from_slice(&derive_seed(prev_hash, nonce));
+                material[16..].copy_from_slice(commitment);
+                blake3::derive_key(COMMITTED_SAMPLING_CONTEXT, &material)
+            }
+        };
+        let mut s = [0u8; 16];
+        s.copy_from_slice(&key[..16]);
+        let mut prng = DPrng::from_seed(s);
+        (0..count).map(|_| prng.next_u32() as usize % len).collect()
+    }
+}
+
+impl core::str::FromStr for WorkSampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "prefix" => Ok(WorkSampling::Prefix),
+            "seeded" => Ok(WorkSampling::Seeded),
+            "committed" => Ok(WorkSampling::Committed),
+            _ => Err(format!("unknown work_root sampling: {}", s)),
+        }
+    }
+}
+
+impl core::fmt::Display for WorkSampling {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            WorkSampling::Prefix => write!(f, "prefix"),
+            WorkSampling::Seeded => write!(f, "seeded"),
+            WorkSampling::Committed => write!(f, "committed"),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use crate::memhard::{finalize, initial_block, romix, MemHardParams};
+use crate::prng::derive_salted_seed;
+use crate::receipt::{hex32, prehash, ReceiptV1};
+use crate::requant::Requant;
+use crate::sampling::WorkSampling;
+use crate::workload::{output_element, Workload};
+use crate::VerifyError;
+
+// Largest matrix side and batch a worker runs (`AUTOTUNE_MAX_BATCH`)
+const MAX_SIDE: usize = 8192;
+const MAX_BATCH: usize = 1024;
+// Input bytes regenerated at most, so a hostile receipt cannot ask for gigabytes
+const MAX_INPUT_BYTES: u64 = 1 << 28;
+// Largest memory-hard buffer rebuilt here (1 GiB, the worker's default MEMHARD_MAX_KIB)
+const MAX_MEMHARD_KIB: u32 = 1024 * 1024;
+// Multiply-adds spent at most rebuilding a whole output for a committed root
+const MAX_COMMITTED_MACS: u64 = 1 << 32;
+
+/// Outcome of checking one receipt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptVerification {
+    /// Whether `sig_hex` verifies against the given pubkey; `None` without one.
+    pub signature_valid: Option<bool>,
+    /// Whether the recomputed work root is the receipt's; `None` when it was not recomputed.
+    pub work_root_valid: Option<bool>,
+    /// The recomputed work root.
+    pub work_root_hex: Option<String>,
+    /// blake3-then-sha256 digest of the signed message, what the signature is over.
+    pub message_digest_hex: String,
+    /// Why the work root was not recomputed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+}
+
+impl ReceiptVerification {
+    /// Nothing that was checked failed.
+    pub fn passed(&self) -> bool {
+        self.signature_valid != Some(false) && self.work_root_valid != Some(false)
+    }
+}
+
+/// Verify a v1 (JSON) receipt: its signature against `pubkey_hex` when given, and
+/// its work root by running the attempt again on the CPU.
+///
+/// Only the output elements the work root samples are recomputed (one dot product,
+/// or one sparse row, each), from inputs regenerated from the receipt's prev_hash,
+/// nonce, salt, sizes and `kernel_ver`, perturbed by the memory-hard stage if it
+/// names one, and requantized as recorded. A root with `committed` sampling needs
+/// the whole output rebuilt first, up to `MAX_COMMITTED_MACS` multiply-adds.
+/// BLAKE3 and SHA3-256 roots are checked; a Poseidon root is reported as skipped.
+pub fn verify_receipt_json(json: &str, pubkey_hex: Option<&str>) -> Result<ReceiptVerification, VerifyError> {
+    let receipt = ReceiptV1::from_json(json)?;
+    let message_digest_hex = hex::encode(prehash(&receipt.signing_message()?));
+    let signature_valid = match pubkey_hex {
+        Some(pubkey) => Some(receipt.verify_signature(pubkey)?),
+        None => None,
+    };
+    let (work_root_valid, work_root_hex, skipped) = match recompute_work_root(&receipt)? {
+        Ok(root) => (Some(receipt.work_root_hex.eq_ignore_ascii_case(&hex::encode(root))), Some(hex::encode(root)), None),
+        Err(reason) => (None, None, Some(reason)),
+    };
+    Ok(ReceiptVerification { signature_valid, work_root_valid, work_root_hex, message_digest_hex, skipped })
+}
+
+/// The work root of the attempt `receipt` describes, or why it cannot be recomputed here.
+pub fn recompute_work_root(receipt: &ReceiptV1) -> Result<Result<[u8; 32], String>, VerifyError> {
+    let hash = receipt.hash_kind.as_deref().unwrap_or("blake3");
+    if hash != "blake3" && hash != "sha3-256" {
+        return Ok(Err(format!("{} work roots are not recomputed", hash)));
+    }
+    let Some(workload) = Workload::from_kernel_ver(&receipt.kernel_ver) else {
+        return Ok(Err(format!("unknown kernel_ver '{}'", receipt.kernel_ver)));
+    };
+    let memhard = match receipt.kernel_ver.split(';').find(|part| part.starts_with("memhard=")) {
+        Some(tag) => Some(MemHardParams::from_tag(tag).ok_or_else(|| VerifyError::field("kernel_ver", "names an unknown or oversized memory-hard stage"))?),
+        None => None,
+    };
+    if let Some(params) = memhard.filter(|params| params.mem_kib > MAX_MEMHARD_KIB) {
+        return Ok(Err(format!("memory-hard stages of {} KiB (over {} MiB) are not rebuilt", params.mem_kib, MAX_MEMHARD_KIB >> 10)));
+    }
+    let sizes = &receipt.sizes;
+    if [sizes.m, sizes.n, sizes.k].iter().any(|&side| side == 0 || side > MAX_SIDE) || sizes.batch > MAX_BATCH {
+        return Err(VerifyError::field("sizes", "are outside what a worker runs"));
+    }
+    // In u64: on wasm32 a usize wraps well within MAX_SIDE and MAX_BATCH
+    let (m, n, k) = (sizes.m as u64, sizes.n as u64, sizes.k as u64);
+    let input_bytes = (m * k + k * n).checked_mul(sizes.batch.max(1) as u64);
+    if input_bytes.is_none_or(|bytes| bytes > MAX_INPUT_BYTES) {
+        return Ok(Err(format!("inputs of more than {} MiB are not regenerated", MAX_INPUT_BYTES >> 20)));
+    }
+    let prev_hash = hex32("prev_hash_hex", &receipt.prev_hash_hex)?;
+    let salt = match &receipt.epoch_salt_hex {
+        Some(hex) => Some(hex32("epoch_salt_hex", hex)?),
+        None => None,
+    };
+
+    let seed = derive_salted_seed(&prev_hash, receipt.nonce, salt.as_ref());
+    let mut input = workload.inputs(seed, sizes);
+    if let Some(params) = &memhard {
+        input.perturb(&finalize(&seed, &romix(&initial_block(&seed), params)));
+    }
+    let scale = receipt.requant.unwrap_or_else(|| Requant::from_salt(salt.as_ref()));
+    // SpMM runs a single item whatever the batch
+    let len = match workload {
+        Workload::Gemm => (m * n).checked_mul(sizes.batch.max(1) as u64),
+        Workload::Spmm { .. } => Some(m * n),
+    };
+    let Some(len) = len.and_then(|len| usize::try_from(len).ok()) else {
+        return Ok(Err(String::from("the output has more elements than this platform can index")));
+    };
+    let sampling = receipt.work_sampling.unwrap_or(WorkSampling::Prefix);
+    let samples: Vec<u8> = if sampling.needs_output() {
+        if (len as u64).saturating_mul(k) > MAX_COMMITTED_MACS {
+            return Ok(Err(format!("committed roots over {} multiply-adds are not recomputed", MAX_COMMITTED_MACS)));
+        }
+        let y: Vec<i8> = (0..len).map(|index| output_element(&input, sizes, scale, index)).collect();
+        sampling.sample(&y, &prev_hash, receipt.nonce).into_iter().map(|x| x as u8).collect()
+    } else {
+        sampling.indices(len, &prev_hash, receipt.nonce, None).into_iter()
+            .map(|index| output_element(&input, sizes, scale, index) as u8)
+            .collect()
+    };
+    Ok(Ok(match hash {
+        "sha3-256" => Sha3_256::digest(&samples).into(),
+        _ => blake3::hash(&samples).into(),
+    }))
+}
+
+impl core::fmt::Display for ReceiptVerification {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let show = |v: Option<bool>| match v {
+            Some(true) => "ok",
+            Some(false) => "FAILED",
+            None => "not checked",
+        };
+        write!(f, "signature {}, work_root {}", show(self.signature_valid), show(self.work_root_valid))?;
+        if let Some(reason) = &self.skipped {
+            write!(f, " ({})", reason)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+use wasm_bindgen::prelude::*;
+use crate::verify::verify_receipt_json;
+
+/// Verify a v1 receipt given as JSON, its signature too when `pubkey_hex` is given.
+///
+/// Returns the `ReceiptVerification` as JSON (`signature_valid`, `work_root_valid`,
+/// `work_root_hex`, `message_digest_hex`, `skipped`); throws when the receipt
+/// cannot be checked at all.
+#[wasm_bindgen]
+pub fn verify_receipt(receipt_json: &str, pubkey_hex: Option<String>) -> Result<String, JsError> {
+    let verification = verify_receipt_json(receipt_json, pubkey_hex.as_deref())?;
+    Ok(serde_json::to_string(&verification)?)
+}
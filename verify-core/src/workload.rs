@@ -0,0 +1,226 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+use crate::prng::DPrng;
+use crate::requant::Requant;
+
+/// Kernel identifier of the dense GEMM in receipts.
+pub const GEMM_KERNEL_VER: &str = "gemm_int8_relu_q_v1";
+/// Kernel identifier of the sparse SpMM in receipts, followed by `;density_permille=`.
+pub const SPMM_KERNEL_VER: &str = "spmm_csr_int8_relu_q_v1";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sizes { pub m: usize, pub n: usize, pub k: usize, pub batch: usize }
+
+/// Which proof-of-work kernel an attempt runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkloadKind {
+    /// Dense int8 GEMM (compute-bound).
+    Gemm,
+    /// CSR sparse x dense int8 SpMM (memory-bound).
+    Spmm,
+}
+
+impl core::str::FromStr for WorkloadKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gemm" => Ok(WorkloadKind::Gemm),
+            "spmm" => Ok(WorkloadKind::Spmm),
+            other => Err(format!("unknown workload kind '{}'", other)),
+        }
+    }
+}
+
+impl core::fmt::Display for WorkloadKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            WorkloadKind::Gemm => write!(f, "gemm"),
+            WorkloadKind::Spmm => write!(f, "spmm"),
+        }
+    }
+}
+
+/// A workload kind with its parameters; everything a verifier needs besides the sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Workload {
+    Gemm,
+    Spmm { density_permille: u16 },
+}
+
+impl Workload {
+    /// `density_permille` (clamped to 1..=1000) only matters for SpMM.
+    pub fn new(kind: WorkloadKind, density_permille: u16) -> Self {
+        match kind {
+            WorkloadKind::Gemm => Workload::Gemm,
+            WorkloadKind::Spmm => Workload::Spmm { density_permille: density_permille.clamp(1, 1000) },
+        }
+    }
+
+    pub fn kind(&self) -> WorkloadKind {
+        match self {
+            Workload::Gemm => WorkloadKind::Gemm,
+            Workload::Spmm { .. } => WorkloadKind::Spmm,
+        }
+    }
+
+    /// Inverse of `kernel_ver`; anything after the workload's own part (the memory-hard tag) is ignored.
+    pub fn from_kernel_ver(kernel_ver: &str) -> Option<Self> {
+        let mut parts = kernel_ver.split(';');
+        match parts.next()? {
+            GEMM_KERNEL_VER => Some(Workload::Gemm),
+            SPMM_KERNEL_VER => {
+                let density = parts.next()?.strip_prefix("density_permille=")?.parse().ok()?;
+                Some(Workload::Spmm { density_permille: density })
+            }
+            _ => None,
+        }
+    }
+
+    /// Deterministic inputs from the attempt seed (`prng::derive_salted_seed`).
+    pub fn inputs(&self, seed: [u8; 16], sizes: &Sizes) -> WorkloadInput {
+        let mut prng = DPrng::from_seed(seed);
+        match *self {
+            Workload::Gemm => {
+                let (a, b) = generate_batched_inputs(&mut prng, sizes);
+                WorkloadInput::Dense { a, b }
+            }
+            Workload::Spmm { density_permille } => {
+                let (a, b) = generate_sparse_inputs(&mut prng, sizes, density_permille);
+                WorkloadInput::Sparse { a, b }
+            }
+        }
+    }
+}
+
+/// Generated inputs of one attempt.
+pub enum WorkloadInput {
+    Dense { a: Vec<i8>, b: Vec<i8> },
+    Sparse { a: CsrMatrix, b: Vec<i8> },
+}
+
+impl WorkloadInput {
+    /// Fold the memory-hard stage's digest into the inputs: A is XORed with the
+    /// digest repeated, B with the digest rotated by 16 bytes. Stored sparse values
+    /// that become zero are bumped to 1 so the sparsity pattern is unchanged.
+    pub fn perturb(&mut self, digest: &[u8; 32]) {
+        let sparse = matches!(self, WorkloadInput::Sparse { .. });
+        let (a_values, b) = match self {
+            WorkloadInput::Dense { a, b } => (a, b),
+            WorkloadInput::Sparse { a, b } => (&mut a.values, b),
+        };
+        for (i, v) in a_values.iter_mut().enumerate() {
+            *v ^= digest[i % 32] as i8;
+            if sparse && *v == 0 {
+                *v = 1;
+            }
+        }
+        for (i, v) in b.iter_mut().enumerate() {
+            *v ^= digest[(i + 16) % 32] as i8;
+        }
+    }
+}
+
+/// A and B of every batch item, drawn item by item (A then B) from `prng` and
+/// stored back to back; a single item draws exactly the unbatched inputs.
+pub fn generate_batched_inputs(prng: &mut DPrng, sizes: &Sizes) -> (Vec<i8>, Vec<i8>) {
+    let batch = sizes.batch.max(1);
+    let mut a = Vec::with_capacity(batch * sizes.m * sizes.k);
+    let mut b = Vec::with_capacity(batch * sizes.k * sizes.n);
+    for _ in 0..batch {
+        a.extend((0..sizes.m * sizes.k).map(|_| prng.next_i8()));
+        b.extend((0..sizes.k * sizes.n).map(|_| prng.next_i8()));
+    }
+    (a, b)
+}
+
+/// Compressed sparse row int8 matrix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsrMatrix {
+    pub rows: usize,
+    pub cols: usize,
+    /// `rows + 1` offsets into `col_idx` / `values`.
+    pub row_ptr: Vec<u32>,
+    pub col_idx: Vec<u32>,
+    pub values: Vec<i8>,
+}
+
+impl CsrMatrix {
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+}
+
+/// Deterministic sparse A (m x k, CSR) and dense B (k x n) for one attempt.
+///
+/// For every position of A, in row-major order, one PRNG draw decides whether the
+/// entry is stored (`draw % 1000 < density_permille`) and a second draw gives its
+/// value; stored zeros are bumped to 1 so `nnz` matches the pattern. B is then filled
+/// exactly like the dense workload's B.
+pub fn generate_sparse_inputs(prng: &mut DPrng, sizes: &Sizes, density_permille: u16) -> (CsrMatrix, Vec<i8>) {
+    let threshold = u32::from(density_permille.min(1000));
+    let mut row_ptr = Vec::with_capacity(sizes.m + 1);
+    let mut col_idx = Vec::new();
+    let mut values = Vec::new();
+    row_ptr.push(0);
+    for _ in 0..sizes.m {
+        for col in 0..sizes.k {
+            if prng.next_u32() % 1000 < threshold {
+                let v = prng.next_i8();
+                col_idx.push(col as u32);
+                values.push(if v == 0 { 1 } else { v });
+            }
+        }
+        row_ptr.push(col_idx.len() as u32);
+    }
+    let b: Vec<i8> = (0..sizes.k * sizes.n).map(|_| prng.next_i8()).collect();
+    (CsrMatrix { rows: sizes.m, cols: sizes.k, row_ptr, col_idx, values }, b)
+}
+
+/// Reference CSR x dense int8 product with the same requantization as the GEMM.
+pub fn spmm_int8_relu_q(a: &CsrMatrix, b: &[i8], n: usize, scale: Requant) -> Vec<i8> {
+    let mut y = vec![0i8; a.rows * n];
+    for row in 0..a.rows {
+        let (start, end) = (a.row_ptr[row] as usize, a.row_ptr[row + 1] as usize);
+        for col in 0..n {
+            let mut acc: i64 = 0;
+            for p in start..end {
+                acc += (a.values[p] as i64) * (b[a.col_idx[p] as usize * n + col] as i64);
+            }
+            y[row*n + col] = scale.apply(acc);
+        }
+    }
+    y
+}
+
+/// One element of the CPU reference kernels (`gemm_int8_relu_q` / `spmm_int8_relu_q`),
+/// in batch item `item` for dense inputs.
+pub fn reference_element(input: &WorkloadInput, sizes: &Sizes, scale: Requant, item: usize, row: usize, col: usize) -> i8 {
+    let n = sizes.n;
+    let acc: i64 = match input {
+        WorkloadInput::Dense { a, b } => {
+            let (a, b) = (&a[item * sizes.m * sizes.k..], &b[item * sizes.k * n..]);
+            (0..sizes.k)
+                .map(|t| a[row*sizes.k + t] as i64 * b[t*n + col] as i64)
+                .sum()
+        }
+        WorkloadInput::Sparse { a, b } => {
+            let (start, end) = (a.row_ptr[row] as usize, a.row_ptr[row + 1] as usize);
+            (start..end)
+                .map(|p| a.values[p] as i64 * b[a.col_idx[p] as usize * n + col] as i64)
+                .sum()
+        }
+    };
+    scale.apply(acc)
+}
+
+/// Output element `index` (flat over every batch item) of the attempt's kernel.
+pub fn output_element(input: &WorkloadInput, sizes: &Sizes, scale: Requant, index: usize) -> i8 {
+    let per_item = sizes.m * sizes.n;
+    let (item, offset) = (index / per_item, index % per_item);
+    reference_element(input, sizes, scale, item, offset / sizes.n, offset % sizes.n)
+}